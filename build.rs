@@ -1,12 +1,73 @@
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Optional ISL functions: (function name, cfg flag set when it is available).
+/// Code using these must provide a `#[cfg(not(...))]` path that degrades gracefully.
+const OPTIONAL_ISL_FUNCTIONS: &[(&str, &str)] = &[
+    ("isl_set_count_val", "isl_has_set_count_val"),
+    ("isl_set_sum", "isl_has_set_sum"),
+    ("isl_version", "isl_has_version"),
+];
+
+/// Find the ISL installation prefix.
+///
+/// `ISL_PREFIX` selects a specific install (e.g. a locally built, vendored ISL);
+/// otherwise we ask pkg-config for the system ISL and fall back to /usr.
+fn isl_prefix() -> PathBuf {
+    if let Ok(prefix) = env::var("ISL_PREFIX") {
+        return PathBuf::from(prefix);
+    }
+    let pkg_config = Command::new("pkg-config")
+        .args(["--variable=prefix", "isl"])
+        .output();
+    if let Ok(output) = pkg_config {
+        let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !prefix.is_empty() {
+            return PathBuf::from(prefix);
+        }
+    }
+    PathBuf::from("/usr")
+}
+
+/// Concatenate all ISL headers so we can check which functions they declare
+fn read_isl_headers(include_path: &Path) -> String {
+    let mut contents = String::new();
+    if let Ok(entries) = fs::read_dir(include_path.join("isl")) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "h") {
+                contents.push_str(&fs::read_to_string(&path).unwrap_or_default());
+            }
+        }
+    }
+    contents
+}
 
 fn main() {
-    // ... (ISL_PREFIX, link-search, link-lib setup as before) ...
-    let isl_prefix_str = env::var("ISL_PREFIX").unwrap_or_else(|_| "/usr".to_string());
-    let isl_prefix = PathBuf::from(&isl_prefix_str);
+    for (_, cfg) in OPTIONAL_ISL_FUNCTIONS {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+    }
+    // Without the `isl` feature nothing is compiled or linked against ISL, so that the
+    // library builds where ISL is not installed (see `src/lib.rs`)
+    if env::var_os("CARGO_FEATURE_ISL").is_none() {
+        return;
+    }
+
+    let isl_prefix = isl_prefix();
     let include_path = isl_prefix.join("include");
 
+    println!("cargo:rerun-if-env-changed=ISL_PREFIX");
+    if !include_path.join("isl/ctx.h").exists() {
+        panic!(
+            "ISL headers not found in {}: install ISL (e.g. the libisl-dev package), set \
+             ISL_PREFIX to its install prefix, or build only the library without ISL with \
+             --no-default-features",
+            include_path.display()
+        );
+    }
+    println!("cargo:rustc-env=SER_ISL_PREFIX={}", isl_prefix.display());
     println!(
         "cargo:rustc-link-search={}",
         isl_prefix.join("lib").display()
@@ -22,9 +83,31 @@ fn main() {
         .compile("isl_helpers"); // Resulting static lib name (libisl_helpers.a)
     // --- End C compilation ---
 
-    let bindings = bindgen::Builder::default()
+    // --- Detect optional ISL functions ---
+    let headers = read_isl_headers(&include_path);
+    let has_version_header = include_path.join("isl/version.h").exists();
+    for (function, cfg) in OPTIONAL_ISL_FUNCTIONS {
+        let declared = headers.contains(&format!("{}(", function));
+        // isl_version is only bound if we can include the header that declares it
+        if declared && (*function != "isl_version" || has_version_header) {
+            println!("cargo:rustc-cfg={}", cfg);
+        } else {
+            println!(
+                "cargo:warning=ISL at {} does not provide {}; dependent features are disabled",
+                isl_prefix.display(),
+                function
+            );
+        }
+    }
+    // --- End detection ---
+
+    let mut builder = bindgen::Builder::default()
         .header("src/isl_wrapper.h")
-        .clang_arg(format!("-I{}", include_path.display()))
+        .clang_arg(format!("-I{}", include_path.display()));
+    if has_version_header {
+        builder = builder.clang_arg("-DSER_HAVE_ISL_VERSION_H");
+    }
+    let bindings = builder
         .allowlist_type("isl_.*")
         .allowlist_function("isl_.*")
         // --- Allowlist your C helper function(s) ---
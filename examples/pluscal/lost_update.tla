@@ -0,0 +1,19 @@
+---- MODULE lost_update ----
+EXTENDS Integers
+
+(* --algorithm lost_update
+variables counter = 0;
+
+\* Each increment reads and writes the counter in separate steps
+process Increment \in 1..2
+variables tmp = 0, response = 0;
+begin
+  Read:
+    tmp := counter;
+  Write:
+    counter := tmp + 1;
+    response := tmp + 1;
+end process;
+
+end algorithm; *)
+====
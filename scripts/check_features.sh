@@ -0,0 +1,39 @@
+#!/bin/bash
+# Check that the crate compiles under every combination of its optional features
+set -e
+cd "$(dirname "$0")/.."
+
+# The `isl` feature needs the ISL headers and library, found like build.rs does.
+# Without them only the combinations without `isl` are checked.
+isl_prefix=${ISL_PREFIX:-$(pkg-config --variable=prefix isl 2>/dev/null || true)}
+isl_prefix=${isl_prefix:-/usr}
+if [ -f "$isl_prefix/include/isl/ctx.h" ]; then
+    have_isl=1
+else
+    have_isl=0
+    echo "== ISL not found in $isl_prefix (set ISL_PREFIX): skipping the combinations with isl"
+fi
+
+features=(isl smpt viz fake-smpt)
+for mask in $(seq 0 $(( (1 << ${#features[@]}) - 1 ))); do
+    if (( mask & 1 )) && (( ! have_isl )); then
+        continue
+    fi
+    enabled=()
+    for i in "${!features[@]}"; do
+        if (( mask & (1 << i) )); then
+            enabled+=("${features[$i]}")
+        fi
+    done
+    list=$(IFS=,; echo "${enabled[*]}")
+    echo "== features: ${list:-(none)}"
+    cargo check --all-targets --no-default-features --features "$list"
+done
+
+# Without ISL the library is what a WebAssembly certificate checker embeds
+if rustup target list --installed 2>/dev/null | grep -qx wasm32-unknown-unknown; then
+    echo "== target: wasm32-unknown-unknown, features: (none)"
+    cargo check --lib --no-default-features --target wasm32-unknown-unknown
+else
+    echo "== skipped wasm32-unknown-unknown: install it with 'rustup target add wasm32-unknown-unknown'"
+fi
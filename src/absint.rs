@@ -0,0 +1,391 @@
+//! Interval abstract interpretation over a Network System.
+//!
+//! For every global state we over-approximate the reachable multisets of request
+//! states (in-flight at a local state, or completed with a response) by a box: an
+//! interval per counter. The analysis is cheap and, for systems where the box at
+//! quiescent states already lies inside the serializable set, it yields a proof
+//! certificate without building the Petri net or calling SMPT. The box is turned
+//! into an `NSInvariant` and checked with `check_proof`, so a successful pre-pass
+//! is exactly as trustworthy as any other certificate.
+
+use crate::deterministic_map::{HashMap, HashSet};
+use crate::ns::NS;
+use crate::ns_decision::{NSInvariant, RequestState, RequestStatePair};
+use crate::proof_parser::{AffineExpr, CompOp, Constraint, Formula, ProofInvariant};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Set the abstract interpretation flag (called from `main.rs`)
+pub fn set_absint(enabled: bool) {
+    crate::options::update(|options| options.absint = enabled);
+}
+
+/// Helper to check whether the abstract interpretation pre-pass should run
+pub fn absint_enabled() -> bool {
+    crate::options::get(|options| options.absint)
+}
+
+/// Number of joins at a global state before widening kicks in
+const WIDENING_DELAY: usize = 3;
+
+/// A nonempty interval of natural numbers, `hi == None` meaning unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: u64,
+    pub hi: Option<u64>,
+}
+
+impl Interval {
+    const ZERO: Interval = Interval { lo: 0, hi: Some(0) };
+
+    fn join(self, other: Interval) -> Interval {
+        Interval {
+            lo: self.lo.min(other.lo),
+            hi: match (self.hi, other.hi) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Standard interval widening: bounds that moved are dropped
+    fn widen(self, next: Interval) -> Interval {
+        Interval {
+            lo: if next.lo < self.lo { 0 } else { self.lo },
+            hi: match (self.hi, next.hi) {
+                (Some(a), Some(b)) if b <= a => Some(a),
+                _ => None,
+            },
+        }
+    }
+
+    fn can_be_positive(self) -> bool {
+        self.hi != Some(0)
+    }
+
+    fn increment(self) -> Interval {
+        Interval {
+            lo: self.lo + 1,
+            hi: self.hi.map(|h| h + 1),
+        }
+    }
+
+    /// Remove one token, assuming there is at least one
+    fn decrement(self) -> Interval {
+        Interval {
+            lo: self.lo.max(1) - 1,
+            hi: self.hi.map(|h| h - 1),
+        }
+    }
+}
+
+/// An interval per counter; counters that are not present are 0
+pub type IntervalBox<V> = HashMap<V, Interval>;
+
+fn get<V: Eq + Hash>(b: &IntervalBox<V>, v: &V) -> Interval {
+    b.get(v).copied().unwrap_or(Interval::ZERO)
+}
+
+fn set<V: Eq + Hash>(b: &mut IntervalBox<V>, v: V, i: Interval) {
+    if i == Interval::ZERO {
+        b.remove(&v);
+    } else {
+        b.insert(v, i);
+    }
+}
+
+/// Move one token from `from` (or create one, if `None`) to `to`.
+/// Returns `None` if `from` is always empty in the box.
+fn transfer<V: Clone + Eq + Hash>(
+    b: &IntervalBox<V>,
+    from: Option<&V>,
+    to: &V,
+) -> Option<IntervalBox<V>> {
+    let mut result = b.clone();
+    if let Some(from) = from {
+        let i = get(b, from);
+        if !i.can_be_positive() {
+            return None;
+        }
+        if from == to {
+            // The token has to be there, but the count does not change
+            set(
+                &mut result,
+                from.clone(),
+                Interval {
+                    lo: i.lo.max(1),
+                    hi: i.hi,
+                },
+            );
+            return Some(result);
+        }
+        set(&mut result, from.clone(), i.decrement());
+    }
+    let i = get(&result, to);
+    set(&mut result, to.clone(), i.increment());
+    Some(result)
+}
+
+/// Join `b` into `a` (widening after `WIDENING_DELAY` joins). Returns whether `a` changed.
+fn join_into<V: Clone + Eq + Hash>(
+    a: &mut IntervalBox<V>,
+    b: &IntervalBox<V>,
+    widen: bool,
+) -> bool {
+    let mut changed = false;
+    let keys: HashSet<V> = a.keys().chain(b.keys()).cloned().collect();
+    for v in keys {
+        let old = get(a, &v);
+        let joined = old.join(get(b, &v));
+        let new = if widen { old.widen(joined) } else { joined };
+        if new != old {
+            set(a, v, new);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Compute a box per reachable global state that over-approximates the reachable
+/// request multisets at that global state.
+///
+/// Requests are only created at the initial global state: creation does not depend
+/// on the global state, so every execution can be reordered to create its requests
+/// first (the certificate checker uses the same argument).
+pub fn analyze<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+) -> HashMap<G, IntervalBox<RequestStatePair<Req, L, Resp>>>
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let mut states: HashMap<G, IntervalBox<RequestStatePair<Req, L, Resp>>> = HashMap::default();
+    let mut joins: HashMap<G, usize> = HashMap::default();
+    // Initially only the initial requests are in flight
+    let mut initial: IntervalBox<RequestStatePair<Req, L, Resp>> = HashMap::default();
+    for (req, l) in &ns.initial_requests {
+        let var = RequestStatePair(req.clone(), RequestState::InFlight(l.clone()));
+        let interval = initial.entry(var).or_insert(Interval::ZERO);
+        interval.lo += 1;
+        interval.hi = interval.hi.map(|hi| hi + 1);
+    }
+    states.insert(ns.initial_global.clone(), initial);
+    let mut todo = vec![ns.initial_global.clone()];
+    let requests = ns.get_requests();
+
+    while let Some(g) = todo.pop() {
+        let b = states[&g].clone();
+        let mut posts: Vec<(G, IntervalBox<RequestStatePair<Req, L, Resp>>)> = Vec::new();
+
+        if g == ns.initial_global {
+            for (req, l) in &ns.requests {
+                let to = RequestStatePair(req.clone(), RequestState::InFlight(l.clone()));
+                posts.extend(transfer(&b, None, &to).map(|p| (g.clone(), p)));
+            }
+        }
+        for req in &requests {
+            for (l, g1, l2, g2) in &ns.transitions {
+                if *g1 != g {
+                    continue;
+                }
+                let from = RequestStatePair((*req).clone(), RequestState::InFlight(l.clone()));
+                let to = RequestStatePair((*req).clone(), RequestState::InFlight(l2.clone()));
+                posts.extend(transfer(&b, Some(&from), &to).map(|p| (g2.clone(), p)));
+            }
+            for (l, resp) in &ns.responses {
+                let from = RequestStatePair((*req).clone(), RequestState::InFlight(l.clone()));
+                let to = RequestStatePair((*req).clone(), RequestState::Completed(resp.clone()));
+                posts.extend(transfer(&b, Some(&from), &to).map(|p| (g.clone(), p)));
+            }
+        }
+
+        for (g2, post) in posts {
+            let changed = match states.get_mut(&g2) {
+                Some(existing) => {
+                    let count = joins.entry(g2.clone()).or_insert(0);
+                    *count += 1;
+                    join_into(existing, &post, *count > WIDENING_DELAY)
+                }
+                None => {
+                    states.insert(g2.clone(), post);
+                    true
+                }
+            };
+            if changed && !todo.contains(&g2) {
+                todo.push(g2);
+            }
+        }
+    }
+
+    states
+}
+
+/// Turn the boxes into an NS invariant. Every invariant mentions all counters that
+/// are nonzero in some box, so counters missing from a box are pinned to 0.
+pub fn boxes_to_invariant<G, L, Req, Resp>(
+    boxes: &HashMap<G, IntervalBox<RequestStatePair<Req, L, Resp>>>,
+) -> NSInvariant<G, L, Req, Resp>
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Ord + Eq + Hash + Display,
+    Req: Clone + Ord + Eq + Hash + Display,
+    Resp: Clone + Ord + Eq + Hash + Display,
+{
+    let mut variables: Vec<RequestStatePair<Req, L, Resp>> = boxes
+        .values()
+        .flat_map(|b| b.keys().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    variables.sort_by_key(|a| a.to_string());
+
+    let mut global_invariants = HashMap::default();
+    for (g, b) in boxes {
+        let mut constraints = Vec::new();
+        for v in &variables {
+            let i = get(b, v);
+            let x = AffineExpr::from_var(v.clone());
+            if i.hi == Some(i.lo) {
+                // x = lo
+                constraints.push(Formula::Constraint(Constraint::new(
+                    x.sub(&AffineExpr::from_const(i.lo as i64)),
+                    CompOp::Eq,
+                )));
+                continue;
+            }
+            if i.lo > 0 {
+                // x - lo >= 0
+                constraints.push(Formula::Constraint(Constraint::new(
+                    x.sub(&AffineExpr::from_const(i.lo as i64)),
+                    CompOp::Geq,
+                )));
+            }
+            if let Some(hi) = i.hi {
+                // hi - x >= 0
+                constraints.push(Formula::Constraint(Constraint::new(
+                    AffineExpr::from_const(hi as i64).sub(&x),
+                    CompOp::Geq,
+                )));
+            }
+        }
+        global_invariants.insert(
+            g.clone(),
+            ProofInvariant::new(variables.clone(), Formula::And(constraints)),
+        );
+    }
+
+    NSInvariant { global_invariants }
+}
+
+/// Run the pre-pass: compute the interval fixpoint, synthesize the invariant and
+/// check it. Returns the invariant if it is a valid serializability certificate.
+pub fn try_prove_serializable<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+) -> Option<NSInvariant<G, L, Req, Resp>>
+where
+    G: Clone + Display + Eq + Hash + Ord + Debug + ToString,
+    L: Clone + Display + Eq + Hash + Ord + Debug + ToString,
+    Req: Clone + Display + Eq + Hash + Ord + Debug + ToString,
+    Resp: Clone + Display + Eq + Hash + Ord + Debug + ToString,
+{
+    let boxes = analyze(ns);
+    let mut invariant = boxes_to_invariant(&boxes);
+
+    // Global states that the analysis never reached get the invariant `false`
+    let variables = invariant
+        .global_invariants
+        .values()
+        .next()
+        .map(|inv| inv.variables.clone())
+        .unwrap_or_default();
+    for g in ns.get_global_states() {
+        if !invariant.global_invariants.contains_key(g) {
+            invariant.global_invariants.insert(
+                g.clone(),
+                ProofInvariant::new(variables.clone(), Formula::Or(vec![])),
+            );
+        }
+    }
+
+    match invariant.check_proof(ns) {
+        Ok(()) => Some(invariant),
+        Err(err) => {
+            println!("Abstract interpretation inconclusive: {}", err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_flight(req: &str, l: &str) -> RequestStatePair<String, String, String> {
+        RequestStatePair(req.to_string(), RequestState::InFlight(l.to_string()))
+    }
+
+    #[test]
+    fn test_interval_widening() {
+        let a = Interval { lo: 1, hi: Some(2) };
+        assert_eq!(a.join(Interval::ZERO), Interval { lo: 0, hi: Some(2) });
+        assert_eq!(
+            a.widen(Interval { lo: 1, hi: Some(3) }),
+            Interval { lo: 1, hi: None }
+        );
+        assert_eq!(
+            a.widen(Interval { lo: 0, hi: Some(2) }),
+            Interval { lo: 0, hi: Some(2) }
+        );
+    }
+
+    #[test]
+    fn test_analyze_boxes() {
+        // A request moves from L0 to L1 while flipping the global state G0 -> G1
+        let mut ns = NS::<String, String, String, String>::new("G0".to_string());
+        ns.add_request("req".to_string(), "L0".to_string());
+        ns.add_transition(
+            "L0".to_string(),
+            "G0".to_string(),
+            "L1".to_string(),
+            "G1".to_string(),
+        );
+        ns.add_response("L1".to_string(), "ok".to_string());
+
+        let boxes = analyze(&ns);
+        let g0 = &boxes["G0"];
+        let g1 = &boxes["G1"];
+        // Any number of requests can be waiting in L0
+        assert_eq!(
+            get(g0, &in_flight("req", "L0")),
+            Interval { lo: 0, hi: None }
+        );
+        assert_eq!(get(g0, &in_flight("req", "L1")), Interval::ZERO);
+        // In G1, the request that moved is in L1 or has completed
+        assert!(get(g1, &in_flight("req", "L1")).can_be_positive());
+        let completed =
+            RequestStatePair("req".to_string(), RequestState::Completed("ok".to_string()));
+        assert!(get(g1, &completed).can_be_positive());
+        assert_eq!(get(g0, &completed), Interval::ZERO);
+    }
+
+    #[test]
+    fn test_boxes_to_invariant_pins_missing_counters() {
+        let mut boxes: HashMap<String, IntervalBox<RequestStatePair<String, String, String>>> =
+            HashMap::default();
+        let mut b = HashMap::default();
+        b.insert(in_flight("req", "L0"), Interval { lo: 1, hi: None });
+        boxes.insert("G0".to_string(), b);
+        boxes.insert("G1".to_string(), HashMap::default());
+
+        let invariant = boxes_to_invariant(&boxes);
+        let g1 = &invariant.global_invariants["G1"];
+        assert_eq!(g1.variables, vec![in_flight("req", "L0")]);
+        // x = 0
+        assert!(matches!(
+            &g1.formula,
+            Formula::And(fs) if matches!(&fs[..], [Formula::Constraint(c)] if c.op == CompOp::Eq)
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+//! Bounded explicit search for a shortest counterexample (`--bounded-search <k>`).
+//!
+//! Before the analysis, the markings of the serializability problem's Petri net are
+//! explored breadth-first, up to `k` transitions from the initial marking. Every
+//! quiescent marking that is reached has its completed requests checked against the
+//! serializable set, like the simulations do. Since the search goes by depth, the
+//! counterexample it finds is a shortest one, while the traces of SMPT can be much
+//! longer than needed. The number of markings grows quickly with the depth in nets that
+//! spawn requests, so the search gives up once it has visited the state budget
+//! (`--bounded-search-states <n>`). Without a counterexample, the analysis runs as usual.
+
+use crate::deterministic_map::HashMap;
+use crate::petri::Petri;
+use crate::semilinear::{SemilinearSet, SparseVector};
+use colored::*;
+use either::Either::{self, Left, Right};
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// Look for counterexamples of at most `depth` transitions (called from `main.rs`)
+pub fn set_bounded_search_depth(depth: Option<usize>) {
+    crate::options::update(|options| options.bounded_search_depth = depth);
+}
+
+pub fn bounded_search_depth() -> Option<usize> {
+    crate::options::get(|options| options.bounded_search_depth)
+}
+
+/// Set the number of markings the search may visit (called from `main.rs`)
+pub fn set_bounded_search_states(states: usize) {
+    crate::options::update(|options| options.bounded_search_states = states);
+}
+
+pub fn bounded_search_states() -> usize {
+    crate::options::get(|options| options.bounded_search_states)
+}
+
+/// Outcome of the bounded search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchOutcome<P> {
+    /// A shortest sequence of transitions to a non-serializable marking
+    CounterExample(Vec<(Vec<P>, Vec<P>)>),
+    /// No execution of at most the given depth is a counterexample
+    NoCounterExample,
+    /// The state budget ran out; executions shorter than `depth` are no counterexamples
+    Unknown { depth: usize },
+}
+
+/// A shortest sequence of at most `depth` transitions that reaches a quiescent marking
+/// whose completed requests are outside `ser`, visiting at most `max_states` markings
+pub fn shortest_counterexample<P>(
+    petri: &Petri<Either<P, P>>,
+    places_that_must_be_zero: &[P],
+    ser: &SemilinearSet<P>,
+    depth: usize,
+    max_states: usize,
+) -> SearchOutcome<Either<P, P>>
+where
+    P: Clone + Ord + Hash + Display,
+{
+    let places = petri.get_places_sorted();
+    let index: HashMap<&Either<P, P>, usize> =
+        places.iter().enumerate().map(|(i, p)| (p, i)).collect();
+    let must_be_zero: Vec<usize> = places_that_must_be_zero
+        .iter()
+        .filter_map(|place| index.get(&Left(place.clone())).copied())
+        .collect();
+    let transitions: Vec<(Vec<usize>, Vec<usize>)> = petri
+        .get_transitions()
+        .iter()
+        .map(|(input, output)| {
+            (
+                input.iter().map(|p| index[p]).collect(),
+                output.iter().map(|p| index[p]).collect(),
+            )
+        })
+        .collect();
+
+    let mut initial = vec![0usize; places.len()];
+    for place in petri.get_initial_marking() {
+        initial[index[&place]] += 1;
+    }
+
+    // Whether completed requests are serializable, by their multiset
+    let mut serializable: HashMap<SparseVector<P>, bool> = HashMap::default();
+    let mut is_counterexample = |marking: &[usize]| {
+        if must_be_zero.iter().any(|&i| marking[i] > 0) {
+            return false;
+        }
+        let mut completed = SparseVector::new();
+        for (place, &count) in places.iter().zip(marking) {
+            if let Right(place) = place {
+                completed.set(place.clone(), count);
+            }
+        }
+        !*serializable
+            .entry(completed)
+            .or_insert_with_key(|completed| ser.contains(completed))
+    };
+
+    // Every visited marking with the (parent, transition) that first reached it
+    let mut visited: HashMap<Vec<usize>, Option<(Vec<usize>, usize)>> = HashMap::default();
+    visited.insert(initial.clone(), None);
+    let mut frontier = vec![initial];
+    for steps in 0..=depth {
+        if let Some(marking) = frontier
+            .iter()
+            .find(|marking| is_counterexample(marking.as_slice()))
+        {
+            println!(
+                "{} found a counterexample of {} steps",
+                "Bounded search:".cyan(),
+                steps
+            );
+            let marking = marking.clone();
+            return SearchOutcome::CounterExample(crate::smpt_fallback::reconstruct_trace(
+                petri, &visited, marking,
+            ));
+        }
+        if steps == depth || frontier.is_empty() {
+            break;
+        }
+        let mut next_frontier = Vec::new();
+        for marking in &frontier {
+            for (t, (input, output)) in transitions.iter().enumerate() {
+                let mut next = marking.clone();
+                let mut enabled = true;
+                for &p in input {
+                    if next[p] == 0 {
+                        enabled = false;
+                        break;
+                    }
+                    next[p] -= 1;
+                }
+                if !enabled {
+                    continue;
+                }
+                for &p in output {
+                    next[p] += 1;
+                }
+                if !visited.contains_key(&next) {
+                    if visited.len() >= max_states {
+                        println!(
+                            "{} gave up at {} steps after {} markings (state budget)",
+                            "Bounded search:".cyan(),
+                            steps + 1,
+                            visited.len()
+                        );
+                        return SearchOutcome::Unknown { depth: steps + 1 };
+                    }
+                    visited.insert(next.clone(), Some((marking.clone(), t)));
+                    next_frontier.push(next);
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    println!(
+        "{} no counterexample within {} steps ({} markings explored)",
+        "Bounded search:".cyan(),
+        depth,
+        visited.len()
+    );
+    SearchOutcome::NoCounterExample
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kleene::Kleene;
+
+    #[test]
+    fn test_shortest_counterexample() {
+        let place = |name: &str| Left(name.to_string());
+        let completed = |name: &str| Right(name.to_string());
+        // Completing `a` is serializable; `b` only completes after two steps
+        let mut petri = Petri::new(vec![place("g")]);
+        petri.add_transition(vec![place("g")], vec![place("g"), completed("a")]);
+        petri.add_transition(vec![place("g")], vec![place("busy")]);
+        petri.add_transition(vec![place("busy")], vec![place("g"), completed("b")]);
+        let ser = SemilinearSet::atom("a".to_string()).star();
+        let must_be_zero = ["busy".to_string()];
+
+        assert_eq!(
+            shortest_counterexample(&petri, &must_be_zero, &ser, 1, 1000),
+            SearchOutcome::NoCounterExample
+        );
+        assert_eq!(
+            shortest_counterexample(&petri, &must_be_zero, &ser, 5, 1000),
+            SearchOutcome::CounterExample(vec![
+                (vec![place("g")], vec![place("busy")]),
+                (vec![place("busy")], vec![place("g"), completed("b")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_state_budget() {
+        // Every step spawns another request, so the markings never repeat
+        let mut petri = Petri::new(vec![Left("g".to_string())]);
+        petri.add_transition(
+            vec![Left("g".to_string())],
+            vec![Left("g".to_string()), Left("req".to_string())],
+        );
+        let ser = SemilinearSet::atom("a".to_string()).star();
+        assert_eq!(
+            shortest_counterexample(&petri, &[], &ser, 100, 10),
+            SearchOutcome::Unknown { depth: 10 }
+        );
+    }
+}
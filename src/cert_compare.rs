@@ -0,0 +1,241 @@
+//! `ser cert-compare <a.json> <b.json>`: compare two certificates of the same model,
+//! for instance ones produced by different tool versions or optimization settings.
+//!
+//! For two proofs, the invariants are compared per global state with the same subset
+//! checks that `check_proof` uses: A is stronger than B if every invariant of A implies
+//! the one of B. Two counterexamples are equivalent if they complete the same multiset
+//! of requests with the same responses, even if they interleave differently.
+
+use crate::expr_to_ns::{ExprRequest, Global, LocalExpr};
+use crate::ns_decision::{NSDecision, NSStep, NSTrace};
+use colored::*;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::path::Path;
+
+/// How two certificates relate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// Both proofs, with invariants that imply each other
+    EquivalentProofs,
+    /// Both proofs, and the invariant of A implies the one of B but not conversely
+    StrongerA,
+    /// Both proofs, and the invariant of B implies the one of A but not conversely
+    StrongerB,
+    /// Both proofs, and neither invariant implies the other
+    Incomparable,
+    /// Both counterexamples, with exactly the same steps
+    IdenticalTraces,
+    /// Both counterexamples, completing the same requests with the same responses
+    EquivalentTraces,
+    /// Both counterexamples, with different completed requests or responses
+    DifferentTraces,
+    /// The certificates reach different verdicts
+    DifferentVerdicts(String, String),
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparison::EquivalentProofs => write!(f, "the invariants are equivalent"),
+            Comparison::StrongerA => write!(f, "the invariant of A is strictly stronger"),
+            Comparison::StrongerB => write!(f, "the invariant of B is strictly stronger"),
+            Comparison::Incomparable => write!(f, "the invariants are incomparable"),
+            Comparison::IdenticalTraces => write!(f, "the traces are identical"),
+            Comparison::EquivalentTraces => {
+                write!(
+                    f,
+                    "the traces complete the same requests with the same responses"
+                )
+            }
+            Comparison::DifferentTraces => {
+                write!(f, "the traces complete different requests or responses")
+            }
+            Comparison::DifferentVerdicts(a, b) => {
+                write!(f, "the verdicts differ (A: {}, B: {})", a, b)
+            }
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [a_path, b_path] = args else {
+        return Err("Usage: ser cert-compare <a.json> <b.json>".to_string());
+    };
+    // Certificates of `.ser` programs have structured states, the others plain strings
+    let comparison = match (load::<String, String, String, String>(a_path), load(b_path)) {
+        (Ok(a), Ok(b)) => compare(&a, &b)?,
+        _ => {
+            let a = load::<Global, LocalExpr, ExprRequest, i64>(a_path)?;
+            let b = load(b_path)?;
+            compare(&a, &b)?
+        }
+    };
+    println!("{}: {}", "Result".bold(), comparison);
+    Ok(())
+}
+
+fn load<G, L, Req, Resp>(path: &str) -> Result<NSDecision<G, L, Req, Resp>, String>
+where
+    G: Eq + Hash,
+    L: Eq + Hash,
+    Req: Eq + Hash,
+    Resp: Eq + Hash,
+    for<'de> G: serde::Deserialize<'de>,
+    for<'de> L: serde::Deserialize<'de>,
+    for<'de> Req: serde::Deserialize<'de>,
+    for<'de> Resp: serde::Deserialize<'de>,
+{
+    NSDecision::load_from_file(Path::new(path))
+        .map_err(|err| format!("Cannot load certificate {}: {}", path, err))
+}
+
+/// Compare two certificates, printing the implications per global state for proofs
+pub fn compare<G, L, Req, Resp>(
+    a: &NSDecision<G, L, Req, Resp>,
+    b: &NSDecision<G, L, Req, Resp>,
+) -> Result<Comparison, String>
+where
+    G: Clone + Display + Eq + Hash + Ord,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash + Ord,
+    Resp: Clone + Display + Eq + Hash + Ord,
+{
+    match (a, b) {
+        (NSDecision::Serializable { invariant: a }, NSDecision::Serializable { invariant: b }) => {
+            let a_implies_b = a.implies(b)?;
+            let b_implies_a = b.implies(a)?;
+            for ((global, ab), (_, ba)) in a_implies_b.iter().zip(&b_implies_a) {
+                println!("  {}: A => B {}, B => A {}", global, mark(*ab), mark(*ba));
+            }
+            let ab = a_implies_b.iter().all(|(_, holds)| *holds);
+            let ba = b_implies_a.iter().all(|(_, holds)| *holds);
+            Ok(match (ab, ba) {
+                (true, true) => Comparison::EquivalentProofs,
+                (true, false) => Comparison::StrongerA,
+                (false, true) => Comparison::StrongerB,
+                (false, false) => Comparison::Incomparable,
+            })
+        }
+        (NSDecision::NotSerializable { trace: a }, NSDecision::NotSerializable { trace: b }) => {
+            Ok(if a.steps == b.steps {
+                Comparison::IdenticalTraces
+            } else if completed(a) == completed(b) {
+                Comparison::EquivalentTraces
+            } else {
+                Comparison::DifferentTraces
+            })
+        }
+        _ => Ok(Comparison::DifferentVerdicts(
+            verdict_name(a).to_string(),
+            verdict_name(b).to_string(),
+        )),
+    }
+}
+
+fn mark(holds: bool) -> ColoredString {
+    if holds { "yes".green() } else { "no".red() }
+}
+
+fn verdict_name<G, L, Req, Resp>(decision: &NSDecision<G, L, Req, Resp>) -> &'static str
+where
+    G: Eq + Hash,
+    L: Eq + Hash,
+    Req: Eq + Hash,
+    Resp: Eq + Hash,
+{
+    match decision {
+        NSDecision::Serializable { .. } => "serializable",
+        NSDecision::NotSerializable { .. } => "not serializable",
+        NSDecision::Timeout { .. } => "timeout",
+        NSDecision::Unknown { .. } => "unknown",
+    }
+}
+
+/// The completed (request, response) pairs of a trace, sorted
+fn completed<G, L, Req: Clone + Ord, Resp: Clone + Ord>(
+    trace: &NSTrace<G, L, Req, Resp>,
+) -> Vec<(Req, Resp)> {
+    let mut pairs: Vec<(Req, Resp)> = trace
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            NSStep::RequestComplete {
+                request, response, ..
+            } => Some((request.clone(), response.clone())),
+            _ => None,
+        })
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deterministic_map::HashMap;
+    use crate::ns_decision::{CheckBackend, NSInvariant, RequestState, RequestStatePair};
+    use crate::proof_parser::{AffineExpr, Constraint, Formula, ProofInvariant};
+
+    type Decision = NSDecision<String, String, String, String>;
+
+    fn proof(bound: i64) -> Decision {
+        // At most `bound` completed requests at global state G0
+        let var = RequestStatePair("req".to_string(), RequestState::Completed("ok".to_string()));
+        let formula = Formula::Constraint(Constraint::le(AffineExpr::from_var(var.clone()), bound));
+        let mut global_invariants = HashMap::default();
+        global_invariants.insert("G0".to_string(), ProofInvariant::new(vec![var], formula));
+        NSDecision::Serializable {
+            invariant: NSInvariant { global_invariants },
+        }
+    }
+
+    fn trace(order: &[&str]) -> Decision {
+        let steps = order
+            .iter()
+            .map(|request| NSStep::RequestComplete {
+                request: request.to_string(),
+                final_local: "L".to_string(),
+                response: "ok".to_string(),
+            })
+            .collect();
+        NSDecision::NotSerializable {
+            trace: NSTrace { steps },
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let options = crate::options::AnalysisOptions {
+            check_backend: CheckBackend::Fallback,
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            assert_eq!(
+                compare(&proof(1), &proof(1)),
+                Ok(Comparison::EquivalentProofs)
+            );
+            assert_eq!(compare(&proof(1), &proof(2)), Ok(Comparison::StrongerA));
+            assert_eq!(compare(&proof(2), &proof(1)), Ok(Comparison::StrongerB));
+        });
+        assert_eq!(
+            compare(&trace(&["a", "b"]), &trace(&["a", "b"])),
+            Ok(Comparison::IdenticalTraces)
+        );
+        assert_eq!(
+            compare(&trace(&["a", "b"]), &trace(&["b", "a"])),
+            Ok(Comparison::EquivalentTraces)
+        );
+        assert_eq!(
+            compare(&trace(&["a"]), &trace(&["b"])),
+            Ok(Comparison::DifferentTraces)
+        );
+        assert_eq!(
+            compare(&proof(1), &trace(&["a"])),
+            Ok(Comparison::DifferentVerdicts(
+                "serializable".to_string(),
+                "not serializable".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Certificate checking from text.
+//!
+//! `check` verifies a certificate against a model given as source text, without
+//! touching the file system or printing, so the checker can be embedded where there
+//! is neither: a "verify this certificate" widget compiled to WebAssembly, for
+//! instance. Without the `isl` feature, the Presburger questions of the check are
+//! decided by `presburger_fallback`.
+
+use crate::expr_to_ns;
+use crate::frontend::parse_ser;
+use crate::initial_states::Verdict;
+use crate::ns::{NS, NSFormat};
+use crate::ns_decision::NSDecision;
+use crate::parser::ExprHc;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Check `certificate` (the JSON of a `certificate.json`) against the model `source`,
+/// written in the language of the file extension `extension` (`ser`, `json`, `yaml`,
+/// `yml` or `toml`). Returns the verdict of a valid certificate, and why the
+/// certificate is invalid otherwise. Timeout and unknown certificates claim nothing,
+/// so they are valid for any model.
+pub fn check(source: &str, extension: &str, certificate: &str) -> Result<Verdict, String> {
+    if extension == "ser" {
+        let mut table = ExprHc::new();
+        let program = parse_ser(source, &mut table)?;
+        let ns = expr_to_ns::program_to_ns(&mut table, &program);
+        check_decision(&ns, certificate)
+    } else {
+        let format = NSFormat::from_extension(extension)
+            .ok_or_else(|| format!("Unsupported model format '{}'", extension))?;
+        let ns = NS::<String, String, String, String>::from_str_in_format(source, format)
+            .map_err(|err| format!("Error parsing {} as Network System: {}", format.name(), err))?;
+        check_decision(&ns, certificate)
+    }
+}
+
+fn check_decision<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    certificate: &str,
+) -> Result<Verdict, String>
+where
+    G: Clone + Ord + Hash + Display + Debug + for<'de> serde::Deserialize<'de>,
+    L: Clone + Ord + Hash + Display + Debug + for<'de> serde::Deserialize<'de>,
+    Req: Clone + Ord + Hash + Display + Debug + for<'de> serde::Deserialize<'de>,
+    Resp: Clone + Ord + Hash + Display + Debug + for<'de> serde::Deserialize<'de>,
+{
+    let decision = NSDecision::<G, L, Req, Resp>::from_json_str(certificate)
+        .map_err(|err| format!("Error loading certificate: {}", err))?;
+    match &decision {
+        NSDecision::Serializable { invariant } => invariant.check_proof(ns)?,
+        NSDecision::NotSerializable { trace } => {
+            ns.check_counterexample_trace(trace)?;
+        }
+        NSDecision::Timeout { .. } | NSDecision::Unknown { .. } => {}
+    }
+    Ok(Verdict::of(&decision))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns_decision::{CERTIFICATE_FORMAT_VERSION, CheckBackend, NSStep, NSTrace};
+
+    // The JSON of a certificate file with this decision
+    fn certificate(decision: &NSDecision<String, String, String, String>) -> String {
+        serde_json::json!({"version": CERTIFICATE_FORMAT_VERSION, "decision": decision}).to_string()
+    }
+
+    const MODEL: &str = r#"{
+        "initial_global": "G0",
+        "requests": [["req", "L0"]],
+        "responses": [["L1", "ok"]],
+        "transitions": [["L0", "G0", "L1", "G0"]]
+    }"#;
+
+    #[test]
+    fn test_check_with_fallback() {
+        let ns = NS::<String, String, String, String>::from_str_in_format(MODEL, NSFormat::Json)
+            .unwrap();
+        let invariant = crate::absint::try_prove_serializable(&ns).expect("absint proves it");
+        let proof = certificate(&NSDecision::Serializable { invariant });
+        // Completing a request is serializable, so this trace is no counterexample
+        let s = |x: &str| x.to_string();
+        let trace = NSTrace {
+            steps: vec![
+                NSStep::RequestStart {
+                    request: s("req"),
+                    initial_local: s("L0"),
+                },
+                NSStep::InternalStep {
+                    request: s("req"),
+                    from_local: s("L0"),
+                    from_global: s("G0"),
+                    to_local: s("L1"),
+                    to_global: s("G0"),
+                },
+                NSStep::RequestComplete {
+                    request: s("req"),
+                    final_local: s("L1"),
+                    response: s("ok"),
+                },
+            ],
+        };
+        let bogus = certificate(&NSDecision::NotSerializable { trace });
+
+        let options = crate::options::AnalysisOptions {
+            check_backend: CheckBackend::Fallback,
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            assert_eq!(check(MODEL, "json", &proof), Ok(Verdict::Serializable));
+            assert!(check(MODEL, "json", &bogus).is_err());
+            assert_eq!(
+                check(MODEL, "json", r#"{"Timeout":{"message":"slow"}}"#),
+                Ok(Verdict::Timeout)
+            );
+            assert!(check(MODEL, "xml", &proof).is_err());
+        });
+    }
+}
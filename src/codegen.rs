@@ -0,0 +1,253 @@
+//! Test-case generation from counterexample traces.
+//!
+//! A `NotSerializable` certificate contains an `NSTrace`: the interleaving of request
+//! starts, internal steps and completions that produces a non-serializable outcome.
+//! This module turns such a trace into a Rust unit test skeleton that spells out the
+//! schedule step by step, as documentation for whoever fixes the protocol.
+//!
+//! Traces do not say which instance of a request takes a step, so the schedule assigns
+//! thread names itself: a step of request `r` from local state `l` is attributed to the
+//! earliest started, still running instance of `r` that is currently in `l`.
+
+use crate::ns_decision::{NSStep, NSTrace};
+use crate::utils::string::sanitize;
+use std::fmt::{Display, Write};
+
+/// One step of a schedule, attributed to a thread (a request instance)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledStep {
+    /// Thread name, e.g. `t2`
+    pub thread: String,
+    pub request: String,
+    pub description: String,
+}
+
+/// Attribute every step of a trace to a thread
+pub fn schedule<G, L, Req, Resp>(trace: &NSTrace<G, L, Req, Resp>) -> Vec<ScheduledStep>
+where
+    G: Display,
+    L: Display + PartialEq + Clone,
+    Req: Display + PartialEq + Clone,
+    Resp: Display,
+{
+    // Running instances: (thread number, request, current local state)
+    let mut running: Vec<(usize, Req, L)> = Vec::new();
+    let mut started = 0;
+    let mut steps = Vec::new();
+
+    // Find (or, for traces that do not replay, invent) the thread of a step
+    let thread_of = |running: &mut Vec<(usize, Req, L)>,
+                     started: &mut usize,
+                     request: &Req,
+                     local: &L| match running
+        .iter()
+        .position(|(_, r, l)| r == request && l == local)
+    {
+        Some(i) => i,
+        None => {
+            *started += 1;
+            running.push((*started, request.clone(), local.clone()));
+            running.len() - 1
+        }
+    };
+
+    for step in &trace.steps {
+        match step {
+            NSStep::RequestStart {
+                request,
+                initial_local,
+            } => {
+                started += 1;
+                running.push((started, request.clone(), initial_local.clone()));
+                steps.push(ScheduledStep {
+                    thread: format!("t{}", started),
+                    request: request.to_string(),
+                    description: format!("start `{}` in local state {}", request, initial_local),
+                });
+            }
+            NSStep::InternalStep {
+                request,
+                from_local,
+                from_global,
+                to_local,
+                to_global,
+            } => {
+                let i = thread_of(&mut running, &mut started, request, from_local);
+                running[i].2 = to_local.clone();
+                steps.push(ScheduledStep {
+                    thread: format!("t{}", running[i].0),
+                    request: request.to_string(),
+                    description: format!(
+                        "step local {} -> {}, global {} -> {}",
+                        from_local, to_local, from_global, to_global
+                    ),
+                });
+            }
+            NSStep::RequestComplete {
+                request,
+                final_local,
+                response,
+            } => {
+                let i = thread_of(&mut running, &mut started, request, final_local);
+                let (thread, _, _) = running.remove(i);
+                steps.push(ScheduledStep {
+                    thread: format!("t{}", thread),
+                    request: request.to_string(),
+                    description: format!("complete `{}` with response {}", request, response),
+                });
+            }
+        }
+    }
+    steps
+}
+
+/// Rust unit test skeleton that documents the schedule of a counterexample
+pub fn rust_test_skeleton<G, L, Req, Resp>(trace: &NSTrace<G, L, Req, Resp>, name: &str) -> String
+where
+    G: Display,
+    L: Display + PartialEq + Clone,
+    Req: Display + PartialEq + Clone,
+    Resp: Display,
+{
+    let steps = schedule(trace);
+    let mut threads: Vec<(&str, &str)> = Vec::new();
+    for step in &steps {
+        if !threads.iter().any(|(t, _)| *t == step.thread) {
+            threads.push((&step.thread, &step.request));
+        }
+    }
+    let completed: Vec<String> = trace
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            NSStep::RequestComplete {
+                request, response, ..
+            } => Some(format!("{}/{}", request, response)),
+            _ => None,
+        })
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "// Generated by `ser` from a non-serializability counterexample."
+    );
+    let _ = writeln!(
+        out,
+        "// Replace the todo!()s with calls into the implementation under test."
+    );
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#[test]");
+    let _ = writeln!(
+        out,
+        "fn nonserializable_interleaving_{}() {{",
+        sanitize(name).to_lowercase()
+    );
+    for (thread, request) in &threads {
+        let _ = writeln!(out, "    // {}: an instance of `{}`", thread, request);
+    }
+    for (i, step) in steps.iter().enumerate() {
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    // {}. {}: {}",
+            i + 1,
+            step.thread,
+            step.description
+        );
+        let _ = writeln!(
+            out,
+            "    todo!(\"{}: {}\");",
+            step.thread,
+            step.description.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+    }
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "    // Completed: {{{}}}. No serial order of these requests produces these",
+        completed.join(", ")
+    );
+    let _ = writeln!(
+        out,
+        "    // responses; after the fix, this schedule must be impossible or serializable."
+    );
+    let _ = writeln!(out, "}}");
+    out
+}
+
+/// Write `counterexample_test.rs` for a trace into the output directory
+pub fn save_rust_test<G, L, Req, Resp>(
+    trace: &NSTrace<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Result<String, std::io::Error>
+where
+    G: Display,
+    L: Display + PartialEq + Clone,
+    Req: Display + PartialEq + Clone,
+    Resp: Display,
+{
+    let name = std::path::Path::new(out_dir)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("counterexample");
+    let path = crate::utils::file::join(out_dir, "counterexample_test.rs");
+    crate::utils::file::safe_write_file(&path, &rust_test_skeleton(trace, name))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_trace() -> NSTrace<String, String, String, String> {
+        let s = |x: &str| x.to_string();
+        NSTrace {
+            steps: vec![
+                NSStep::RequestStart {
+                    request: s("inc"),
+                    initial_local: s("L0"),
+                },
+                NSStep::RequestStart {
+                    request: s("inc"),
+                    initial_local: s("L0"),
+                },
+                NSStep::InternalStep {
+                    request: s("inc"),
+                    from_local: s("L0"),
+                    from_global: s("G0"),
+                    to_local: s("L1"),
+                    to_global: s("G0"),
+                },
+                NSStep::RequestComplete {
+                    request: s("inc"),
+                    final_local: s("L0"),
+                    response: s("0"),
+                },
+                NSStep::RequestComplete {
+                    request: s("inc"),
+                    final_local: s("L1"),
+                    response: s("0"),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_schedule_assigns_threads() {
+        let threads: Vec<String> = schedule(&example_trace())
+            .into_iter()
+            .map(|s| s.thread)
+            .collect();
+        // The internal step moves t1; t2 is still in L0 when it completes
+        assert_eq!(threads, vec!["t1", "t2", "t1", "t2", "t1"]);
+    }
+
+    #[test]
+    fn test_rust_test_skeleton() {
+        let code = rust_test_skeleton(&example_trace(), "lost-update");
+        assert!(code.contains("fn nonserializable_interleaving_lost_update()"));
+        assert_eq!(code.matches("todo!(\"").count(), 5);
+        assert!(code.contains("Completed: {inc/0, inc/0}"));
+    }
+}
@@ -0,0 +1,238 @@
+//! Commutativity of request types (`--commutativity`).
+//!
+//! Two request types commute when, from any global state and any pair of local states
+//! their instances can be in, taking a step of the first and then a step of the second
+//! has exactly the same outcomes (local states of both and final global state) as
+//! taking them in the other order. This is the diamond property, checked for single
+//! steps, so it also covers steps that are only enabled in one order. A request type
+//! is compared with itself as well, for two of its instances.
+//!
+//! If all pairs commute, the steps of any execution can be swapped one by one until
+//! the instances run one after the other, with the same responses and final global
+//! state, so the system is serializable. The diagnosis uses this to decide subsystems
+//! without running the analysis.
+
+use crate::deterministic_map::HashSet;
+use crate::ns::NS;
+use colored::*;
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// Show the commutativity matrix in the report
+pub fn set_commutativity(enabled: bool) {
+    crate::options::update(|options| options.commutativity = enabled);
+}
+
+pub fn commutativity_enabled() -> bool {
+    crate::options::get(|options| options.commutativity)
+}
+
+/// Which pairs of request types commute, indexed like `requests`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommutativityMatrix<Req> {
+    pub requests: Vec<Req>,
+    pub commutes: Vec<Vec<bool>>,
+}
+
+impl<Req: PartialEq + Clone> CommutativityMatrix<Req> {
+    /// Whether `a` and `b` commute; `None` if either is not a request of the system
+    pub fn get(&self, a: &Req, b: &Req) -> Option<bool> {
+        let i = self.requests.iter().position(|req| req == a)?;
+        let j = self.requests.iter().position(|req| req == b)?;
+        Some(self.commutes[i][j])
+    }
+
+    pub fn all_commute(&self) -> bool {
+        self.commutes.iter().all(|row| row.iter().all(|&c| c))
+    }
+
+    /// Pairs that do not commute, each listed once
+    pub fn conflicts(&self) -> Vec<(Req, Req)> {
+        let mut pairs = Vec::new();
+        for i in 0..self.requests.len() {
+            for j in i..self.requests.len() {
+                if !self.commutes[i][j] {
+                    pairs.push((self.requests[i].clone(), self.requests[j].clone()));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+/// Compute which pairs of request types of `ns` commute
+pub fn commutativity_matrix<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>) -> CommutativityMatrix<Req>
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Ord + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let mut requests: Vec<Req> = ns.get_requests().into_iter().cloned().collect();
+    requests.sort();
+    requests.dedup();
+    let locals: Vec<HashSet<&L>> = requests.iter().map(|req| ns.request_locals(req)).collect();
+    let globals = ns.get_global_states();
+
+    let mut commutes = vec![vec![true; requests.len()]; requests.len()];
+    for i in 0..requests.len() {
+        for j in i..requests.len() {
+            let commute = globals.iter().all(|global| {
+                locals[i].iter().all(|first| {
+                    locals[j]
+                        .iter()
+                        .all(|second| steps_commute(ns, *global, *first, *second))
+                })
+            });
+            commutes[i][j] = commute;
+            commutes[j][i] = commute;
+        }
+    }
+    CommutativityMatrix { requests, commutes }
+}
+
+/// Whether a step from `first` and a step from `second`, in `global`, have the same
+/// outcomes in both orders
+fn steps_commute<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    global: &G,
+    first: &L,
+    second: &L,
+) -> bool
+where
+    G: Clone + Eq + Hash,
+    L: Clone + Eq + Hash,
+{
+    let step = |local, global| steps_from(&ns.transitions, local, global);
+    let mut first_then_second: HashSet<(&L, &L, &G)> = HashSet::default();
+    for (first_to, mid) in step(first, global) {
+        for (second_to, end) in step(second, mid) {
+            first_then_second.insert((first_to, second_to, end));
+        }
+    }
+    let mut second_then_first: HashSet<(&L, &L, &G)> = HashSet::default();
+    for (second_to, mid) in step(second, global) {
+        for (first_to, end) in step(first, mid) {
+            second_then_first.insert((first_to, second_to, end));
+        }
+    }
+    first_then_second == second_then_first
+}
+
+/// Local and global states after a step from `local` in `global`
+fn steps_from<'a, G: Eq, L: Eq>(
+    transitions: &'a [(L, G, L, G)],
+    local: &'a L,
+    global: &'a G,
+) -> impl Iterator<Item = (&'a L, &'a G)> {
+    transitions
+        .iter()
+        .filter(move |(from_local, from_global, _, _)| from_local == local && from_global == global)
+        .map(|(_, _, to_local, to_global)| (to_local, to_global))
+}
+
+/// Print the matrix as part of the report
+pub fn print_matrix<Req: Display + PartialEq + Clone>(matrix: &CommutativityMatrix<Req>) {
+    println!();
+    println!("Commutativity of requests:");
+    let names: Vec<String> = matrix.requests.iter().map(|req| req.to_string()).collect();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    print!("  {:width$}", "", width = width);
+    for name in &names {
+        print!(" {}", name);
+    }
+    println!();
+    for (i, name) in names.iter().enumerate() {
+        print!("  {:width$}", name, width = width);
+        for (j, other) in names.iter().enumerate() {
+            let cell = if matrix.commutes[i][j] {
+                "✓".green()
+            } else {
+                "✗".red()
+            };
+            print!(" {}{}", " ".repeat(other.chars().count() - 1), cell);
+        }
+        println!();
+    }
+    if matrix.all_commute() {
+        println!(
+            "  {} All requests commute, so every execution can be reordered into a serial one",
+            "✓".green()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commutativity_matrix() {
+        let mut ns = NS::<String, String, String, String>::new("0".to_string());
+
+        // set writes 1, get reads the global state, nop ignores it
+        ns.add_request("set".to_string(), "s".to_string());
+        ns.add_transition(
+            "s".to_string(),
+            "0".to_string(),
+            "s_done".to_string(),
+            "1".to_string(),
+        );
+        ns.add_transition(
+            "s".to_string(),
+            "1".to_string(),
+            "s_done".to_string(),
+            "1".to_string(),
+        );
+        ns.add_response("s_done".to_string(), "ok".to_string());
+
+        ns.add_request("get".to_string(), "g".to_string());
+        ns.add_transition(
+            "g".to_string(),
+            "0".to_string(),
+            "g0".to_string(),
+            "0".to_string(),
+        );
+        ns.add_transition(
+            "g".to_string(),
+            "1".to_string(),
+            "g1".to_string(),
+            "1".to_string(),
+        );
+        ns.add_response("g0".to_string(), "0".to_string());
+        ns.add_response("g1".to_string(), "1".to_string());
+
+        ns.add_request("nop".to_string(), "n".to_string());
+        ns.add_transition(
+            "n".to_string(),
+            "0".to_string(),
+            "n_done".to_string(),
+            "0".to_string(),
+        );
+        ns.add_transition(
+            "n".to_string(),
+            "1".to_string(),
+            "n_done".to_string(),
+            "1".to_string(),
+        );
+        ns.add_response("n_done".to_string(), "ok".to_string());
+
+        let matrix = commutativity_matrix(&ns);
+        let (get, set, nop) = ("get".to_string(), "set".to_string(), "nop".to_string());
+        assert_eq!(matrix.get(&set, &set), Some(true));
+        assert_eq!(matrix.get(&get, &get), Some(true));
+        assert_eq!(matrix.get(&get, &set), Some(false));
+        assert_eq!(matrix.get(&set, &get), Some(false));
+        assert_eq!(matrix.get(&nop, &set), Some(true));
+        assert_eq!(matrix.get(&nop, &get), Some(true));
+        assert_eq!(matrix.conflicts(), vec![(get, set)]);
+        assert!(!matrix.all_commute());
+
+        let without_get = ns.restrict_requests(|req| req != "get");
+        assert!(commutativity_matrix(&without_get).all_commute());
+    }
+}
@@ -0,0 +1,297 @@
+//! Linear constraints over variables, the Rust form of the sets that `presburger` reads
+//! back from ISL.
+//!
+//! Proofs and certificates are built from these as well, so they live apart from the
+//! ISL bindings and are available in builds without the `isl` feature.
+
+use either::Either;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Variable<T> {
+    Var(T),
+    Existential(usize),
+}
+
+// Pretty printing for Variable<T>: Var(T) is printed as V{T} and Existential(n) is printed as E{n}
+impl<T: Display> Display for Variable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variable::Var(t) => write!(f, "V{}", t),
+            Variable::Existential(n) => write!(f, "E{}", n),
+        }
+    }
+}
+
+impl<T> Variable<T> {
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Variable<U> {
+        match self {
+            Variable::Var(t) => Variable::Var(f(t)),
+            Variable::Existential(n) => Variable::Existential(n),
+        }
+    }
+}
+
+/// Represents an existentially quantified conjunction of linear constraints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuantifiedSet<T> {
+    // The variables T are from the original set, and the variables usize are the existential variables
+    // The existential quantification is over *natural numbers* not integers
+    // Note: the existential quantification is at the top-level: it's an existential of conjunctions,
+    // not a conjunction of existentials.
+    pub(crate) constraints: Vec<Constraint<Variable<T>>>,
+}
+
+impl<T: Clone> QuantifiedSet<T> {
+    /// Create a new QuantifiedSet with the given constraints
+    pub fn new(constraints: Vec<Constraint<Variable<T>>>) -> Self {
+        QuantifiedSet { constraints }
+    }
+
+    /// Get the constraints in this quantified set
+    pub fn constraints(&self) -> &[Constraint<Variable<T>>] {
+        &self.constraints
+    }
+
+    pub fn extract_and_reify_existential_variables(
+        &self,
+    ) -> (Vec<Either<usize, T>>, Vec<Constraint<Either<usize, T>>>) {
+        // Collect all existential variables
+        let mut existential_vars = std::collections::BTreeSet::new();
+        for constraint in &self.constraints {
+            for (_, var) in &constraint.linear_combination {
+                if let Variable::Existential(n) = var {
+                    existential_vars.insert(*n);
+                }
+            }
+        }
+
+        // Convert existential variables to Either::Left format
+        let existential_places: Vec<Either<usize, T>> = existential_vars
+            .into_iter()
+            .map(|n| Either::Left(n))
+            .collect();
+
+        // Transform constraints by converting Variable<T> to Either<usize, T>
+        let transformed_constraints: Vec<Constraint<Either<usize, T>>> = self
+            .constraints
+            .iter()
+            .map(|constraint| {
+                let transformed_linear_combination: Vec<(i32, Either<usize, T>)> = constraint
+                    .linear_combination
+                    .iter()
+                    .map(|(coeff, var)| {
+                        let new_var = match var {
+                            Variable::Var(t) => Either::Right(t.clone()),
+                            Variable::Existential(n) => Either::Left(*n),
+                        };
+                        (*coeff, new_var)
+                    })
+                    .collect();
+
+                Constraint::new(
+                    transformed_linear_combination,
+                    constraint.constant_term,
+                    constraint.constraint_type,
+                )
+            })
+            .collect();
+
+        (existential_places, transformed_constraints)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint<T> {
+    pub(crate) linear_combination: Vec<(i32, T)>,
+    pub(crate) constant_term: i32,
+    pub(crate) constraint_type: ConstraintType,
+}
+
+impl<T> Constraint<T> {
+    /// Create a new constraint
+    pub fn new(
+        linear_combination: Vec<(i32, T)>,
+        constant_term: i32,
+        constraint_type: ConstraintType,
+    ) -> Self {
+        Constraint {
+            linear_combination,
+            constant_term,
+            constraint_type,
+        }
+    }
+
+    /// Get the linear combination of variables in this constraint
+    pub fn linear_combination(&self) -> &[(i32, T)] {
+        &self.linear_combination
+    }
+
+    /// Get the constant term in this constraint
+    pub fn constant_term(&self) -> i32 {
+        self.constant_term
+    }
+
+    /// Get the constraint type
+    pub fn constraint_type(&self) -> ConstraintType {
+        self.constraint_type
+    }
+
+    /// Extracts all variables from a clause that have constraints of the form "coeff*var = 0"
+    /// (EqualToZero with single variable and zero constant term, any coefficient)
+    pub fn extract_zero_variables(clause: &[Constraint<T>]) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut zero_vars = Vec::new();
+
+        for constraint in clause {
+            if constraint.constraint_type == ConstraintType::EqualToZero
+                && constraint.linear_combination.len() == 1
+                && constraint.constant_term == 0
+            {
+                zero_vars.push(constraint.linear_combination[0].1.clone());
+            }
+        }
+
+        zero_vars
+    }
+
+    /// Extracts all variables from a clause that have constraints requiring them to be nonzero.
+    /// This includes:
+    /// - Variables with NonNegative constraints where constant_term < 0 (i.e., var >= positive_value)
+    /// - Any variables that appear in constraints that are not of the form "var = 0"
+    ///
+    /// This is the complement of extract_zero_variables and is useful for identifying
+    /// which variables must have nonzero values in the solution.
+    pub fn extract_nonzero_variables(clause: &[Constraint<T>]) -> Vec<T>
+    where
+        T: Clone + Eq + std::hash::Hash,
+    {
+        let mut nonzero_vars = Vec::new();
+        let mut seen_vars = std::collections::HashSet::new();
+
+        for constraint in clause {
+            match constraint.constraint_type {
+                ConstraintType::NonNegative => {
+                    // For constraints of the form: linear_combination + constant_term >= 0
+                    // If constant_term < 0, then linear_combination >= -constant_term > 0
+                    if constraint.constant_term < 0 {
+                        // All variables in this constraint must contribute to making it positive
+                        for (_, var) in &constraint.linear_combination {
+                            if seen_vars.insert(var.clone()) {
+                                nonzero_vars.push(var.clone());
+                            }
+                        }
+                    }
+                    // Note: If constant_term >= 0, the constraint might be satisfiable with zeros
+                }
+                ConstraintType::EqualToZero => {
+                    // Skip pure zero constraints (handled by extract_zero_variables)
+                    if constraint.linear_combination.len() == 1 && constraint.constant_term == 0 {
+                        continue;
+                    }
+                    // For more complex equality constraints, all variables might need to be nonzero
+                    // to satisfy the constraint (conservative approach)
+                    if constraint.constant_term != 0 || constraint.linear_combination.len() > 1 {
+                        for (_, var) in &constraint.linear_combination {
+                            if seen_vars.insert(var.clone()) {
+                                nonzero_vars.push(var.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        nonzero_vars
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintType {
+    NonNegative,
+    EqualToZero,
+}
+
+// Pretty printing for Constraint<T>
+impl<T: Display> Display for Constraint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Format each term in the linear combination
+        let mut first = true;
+        for &(coef, ref var) in &self.linear_combination {
+            if coef == 0 {
+                continue; // Skip zero coefficients
+            }
+
+            if !first {
+                if coef > 0 {
+                    write!(f, " + ")?;
+                } else {
+                    write!(f, " -")?;
+                }
+            } else if coef < 0 {
+                // First term and negative
+                write!(f, "-")?;
+            }
+
+            let abs_coef = coef.abs();
+            if abs_coef == 1 {
+                write!(f, "{}", var)?;
+            } else {
+                write!(f, "{}{}", abs_coef, var)?;
+            }
+
+            first = false;
+        }
+
+        // Add the constant term if non-zero or if there are no terms
+        if self.constant_term != 0 || first {
+            if !first {
+                match self.constant_term.cmp(&0) {
+                    std::cmp::Ordering::Greater => write!(f, " + ")?,
+                    std::cmp::Ordering::Less => write!(f, " -")?,
+                    std::cmp::Ordering::Equal => {}
+                }
+            } else if self.constant_term < 0 {
+                // First term and negative
+                write!(f, "-")?;
+            }
+
+            write!(f, "{}", self.constant_term.abs())?;
+        }
+
+        // Add the constraint type
+        match self.constraint_type {
+            ConstraintType::NonNegative => write!(f, " ≥ 0"),
+            ConstraintType::EqualToZero => write!(f, " = 0"),
+        }
+    }
+}
+
+// Pretty printing for QuantifiedSet<T>
+impl<T: Display> Display for QuantifiedSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Check if there are existential variables
+        let has_existentials = self.constraints.iter().any(|c| {
+            c.linear_combination
+                .iter()
+                .any(|(_, var)| matches!(var, Variable::Existential(_)))
+        });
+
+        // Write existential quantifier if needed
+        if has_existentials {
+            write!(f, "∃ ")?;
+        }
+
+        // Write all constraints connected by '∧'
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ∧ ")?;
+            }
+            write!(f, "{}", constraint)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! All counterexamples of a non-serializable system, grouped into patterns
+//! (`--all-counterexamples`).
+//!
+//! The analysis stops at the first reachable disjunct of the complement of the
+//! serializable set, so it reports one counterexample. This queries every disjunct
+//! instead, and groups the traces it finds by what makes them violations: the multiset
+//! of completed requests and the order in which the request instances take their
+//! steps. Traces that only differ in the states they pass through, or in when requests
+//! start and complete, fall into one pattern, so the report lists distinct violations
+//! rather than near-identical repeats.
+
+use crate::ns::NS;
+use crate::ns_decision::{NSDecision, NSStep, NSTrace, petri_decision_to_ns};
+use crate::reachability_with_proofs::{Decision, can_reach_quantified_set, complement_disjuncts};
+use crate::smpt::CancellationToken;
+use colored::*;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Look for a counterexample in every disjunct and report the patterns
+pub fn set_all_counterexamples(enabled: bool) {
+    crate::options::update(|options| options.all_counterexamples = enabled);
+}
+
+pub fn all_counterexamples_enabled() -> bool {
+    crate::options::get(|options| options.all_counterexamples)
+}
+
+/// File in the output directory that holds the patterns
+pub const PATTERNS_FILE: &str = "counterexample_patterns.json";
+
+/// Counterexamples that violate serializability the same way
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CounterexamplePattern<G, L, Req, Resp> {
+    /// Completed requests with their responses, sorted
+    pub completed: Vec<(Req, Resp)>,
+    /// Turns of the request instances: each entry is an instance (numbered in order of
+    /// its first step) that takes one or more consecutive steps
+    pub interleaving: Vec<(usize, Req)>,
+    /// Number of distinct traces with this pattern
+    pub traces: usize,
+    /// The first of these traces
+    pub example: NSTrace<G, L, Req, Resp>,
+}
+
+impl<G, L, Req: Display, Resp: Display> Display for CounterexamplePattern<G, L, Req, Resp> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let turns: Vec<String> = self
+            .interleaving
+            .iter()
+            .map(|(instance, req)| format!("{}#{}", req, instance))
+            .collect();
+        let completed: Vec<String> = self
+            .completed
+            .iter()
+            .map(|(req, resp)| format!("{}/{}", req, resp))
+            .collect();
+        write!(
+            f,
+            "{} completing {{{}}}",
+            if turns.is_empty() {
+                "no steps".to_string()
+            } else {
+                turns.join(" → ")
+            },
+            completed.join(", ")
+        )
+    }
+}
+
+/// Completed requests and instance turns of a trace (see `CounterexamplePattern`)
+#[allow(clippy::type_complexity)]
+fn pattern_key<G, L, Req, Resp>(
+    trace: &NSTrace<G, L, Req, Resp>,
+) -> (Vec<(Req, Resp)>, Vec<(usize, Req)>)
+where
+    L: Clone + Eq,
+    Req: Clone + Ord,
+    Resp: Clone + Ord,
+{
+    // Active instances as (id, request, local state); a step is attributed to the
+    // first active instance of its request in its local state
+    let mut active: Vec<(Option<usize>, Req, L)> = Vec::new();
+    let mut next_id = 0;
+    let mut completed = Vec::new();
+    let mut interleaving: Vec<(usize, Req)> = Vec::new();
+    for step in &trace.steps {
+        match step {
+            NSStep::RequestStart {
+                request,
+                initial_local,
+            } => active.push((None, request.clone(), initial_local.clone())),
+            NSStep::InternalStep {
+                request,
+                from_local,
+                to_local,
+                ..
+            } => {
+                let Some(instance) = active
+                    .iter_mut()
+                    .find(|(_, req, local)| req == request && local == from_local)
+                else {
+                    continue;
+                };
+                let id = *instance.0.get_or_insert_with(|| {
+                    next_id += 1;
+                    next_id
+                });
+                instance.2 = to_local.clone();
+                if interleaving.last().map(|(last, _)| *last) != Some(id) {
+                    interleaving.push((id, request.clone()));
+                }
+            }
+            NSStep::RequestComplete {
+                request,
+                final_local,
+                response,
+            } => {
+                if let Some(i) = active
+                    .iter()
+                    .position(|(_, req, local)| req == request && local == final_local)
+                {
+                    active.remove(i);
+                }
+                completed.push((request.clone(), response.clone()));
+            }
+        }
+    }
+    completed.sort();
+    (completed, interleaving)
+}
+
+/// Drop duplicate traces and group the rest into patterns, in order of first occurrence
+pub fn cluster<G, L, Req, Resp>(
+    traces: &[NSTrace<G, L, Req, Resp>],
+) -> Vec<CounterexamplePattern<G, L, Req, Resp>>
+where
+    G: Clone + Debug,
+    L: Clone + Eq + Debug,
+    Req: Clone + Ord + Debug,
+    Resp: Clone + Ord + Debug,
+{
+    let mut seen = crate::deterministic_map::HashSet::default();
+    let mut patterns: Vec<CounterexamplePattern<G, L, Req, Resp>> = Vec::new();
+    for trace in traces {
+        if !seen.insert(format!("{:?}", trace.steps)) {
+            continue;
+        }
+        let (completed, interleaving) = pattern_key(trace);
+        match patterns
+            .iter_mut()
+            .find(|p| p.completed == completed && p.interleaving == interleaving)
+        {
+            Some(pattern) => pattern.traces += 1,
+            None => patterns.push(CounterexamplePattern {
+                completed,
+                interleaving,
+                traces: 1,
+                example: trace.clone(),
+            }),
+        }
+    }
+    patterns
+}
+
+/// Query every disjunct of the complement of the serializable set and return the
+/// counterexamples found, starting with the one of `decision`. Queries write to
+/// `<out_dir>/counterexamples`.
+pub fn find_all<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    decision: &NSDecision<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Vec<NSTrace<G, L, Req, Resp>>
+where
+    G: Clone + Ord + Hash + Display + Debug,
+    L: Clone + Ord + Hash + Display + Debug,
+    Req: Clone + Ord + Hash + Display + Debug,
+    Resp: Clone + Ord + Hash + Display + Debug,
+{
+    let mut traces = Vec::new();
+    if let NSDecision::NotSerializable { trace } = decision {
+        traces.push(trace.clone());
+    }
+    let sub_dir = crate::utils::file::join(out_dir, "counterexamples");
+    if let Err(err) = crate::utils::file::ensure_dir_exists(&sub_dir) {
+        eprintln!("Failed to create {}: {}", sub_dir, err);
+        return traces;
+    }
+
+    let (petri, places_that_must_be_zero, ser) = ns.serializability_problem();
+    let disjuncts = complement_disjuncts(&petri, &places_that_must_be_zero, ser);
+    let cancel = CancellationToken::new();
+    for (i, disjunct) in disjuncts.into_iter().enumerate() {
+        if let Decision::CounterExample { trace } =
+            can_reach_quantified_set(petri.clone(), disjunct, &sub_dir, i, &cancel)
+        {
+            if let NSDecision::NotSerializable { trace } =
+                petri_decision_to_ns(Decision::CounterExample { trace }, ns)
+            {
+                traces.push(trace);
+            }
+        }
+    }
+    traces
+}
+
+/// Find all counterexamples of a non-serializable system, print their patterns and
+/// save them to `<out_dir>/counterexample_patterns.json`
+pub fn report<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    decision: &NSDecision<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Vec<CounterexamplePattern<G, L, Req, Resp>>
+where
+    G: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+    L: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+    Req: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+    Resp: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+{
+    if !matches!(decision, NSDecision::NotSerializable { .. }) {
+        return Vec::new();
+    }
+    println!();
+    println!(
+        "{}",
+        "Looking for counterexamples in all disjuncts..."
+            .blue()
+            .bold()
+    );
+    let traces = find_all(ns, decision, out_dir);
+    let patterns = cluster(&traces);
+    print_patterns(&patterns, traces.len());
+
+    let path = crate::utils::file::join(out_dir, PATTERNS_FILE);
+    match serde_json::to_string_pretty(&patterns) {
+        Ok(json) => match crate::utils::file::safe_write_file(&path, &json) {
+            Ok(_) => println!("- {}", path.green()),
+            Err(err) => eprintln!("Failed to write {}: {}", path, err),
+        },
+        Err(err) => eprintln!("Failed to serialize counterexample patterns: {}", err),
+    }
+    patterns
+}
+
+/// Print the patterns as part of the report
+pub fn print_patterns<G, L, Req: Display, Resp: Display>(
+    patterns: &[CounterexamplePattern<G, L, Req, Resp>],
+    found: usize,
+) {
+    let distinct: usize = patterns.iter().map(|p| p.traces).sum();
+    println!();
+    println!(
+        "Counterexample patterns ({} found, {} distinct, {} pattern{}):",
+        found,
+        distinct,
+        patterns.len(),
+        if patterns.len() == 1 { "" } else { "s" }
+    );
+    for (i, pattern) in patterns.iter().enumerate() {
+        println!(
+            "  {}. {} ({} trace{})",
+            i + 1,
+            pattern,
+            pattern.traces,
+            if pattern.traces == 1 { "" } else { "s" }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn internal(request: &str, from: &str, to: &str) -> NSStep<String, String, String, String> {
+        NSStep::InternalStep {
+            request: request.to_string(),
+            from_local: from.to_string(),
+            from_global: "g".to_string(),
+            to_local: to.to_string(),
+            to_global: "g".to_string(),
+        }
+    }
+
+    fn start(request: &str, local: &str) -> NSStep<String, String, String, String> {
+        NSStep::RequestStart {
+            request: request.to_string(),
+            initial_local: local.to_string(),
+        }
+    }
+
+    fn complete(request: &str, local: &str, resp: &str) -> NSStep<String, String, String, String> {
+        NSStep::RequestComplete {
+            request: request.to_string(),
+            final_local: local.to_string(),
+            response: resp.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cluster() {
+        // a reads, b writes, a reads again
+        let interleaved = NSTrace {
+            steps: vec![
+                start("a", "a0"),
+                internal("a", "a0", "a1"),
+                start("b", "b0"),
+                internal("b", "b0", "b1"),
+                complete("b", "b1", "ok"),
+                internal("a", "a1", "a2"),
+                complete("a", "a2", "0"),
+            ],
+        };
+        // The same turns, but b starts at the beginning
+        let early_start = NSTrace {
+            steps: vec![
+                start("b", "b0"),
+                start("a", "a0"),
+                internal("a", "a0", "a1"),
+                internal("b", "b0", "b1"),
+                internal("a", "a1", "a2"),
+                complete("a", "a2", "0"),
+                complete("b", "b1", "ok"),
+            ],
+        };
+        // a takes both steps in one turn
+        let serial = NSTrace {
+            steps: vec![
+                start("a", "a0"),
+                internal("a", "a0", "a1"),
+                internal("a", "a1", "a2"),
+                complete("a", "a2", "0"),
+                start("b", "b0"),
+                internal("b", "b0", "b1"),
+                complete("b", "b1", "ok"),
+            ],
+        };
+
+        let traces = vec![interleaved.clone(), early_start, interleaved, serial];
+        let patterns = cluster(&traces);
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].traces, 2);
+        assert_eq!(
+            patterns[0].to_string(),
+            "a#1 → b#2 → a#1 completing {a/0, b/ok}"
+        );
+        assert_eq!(patterns[1].traces, 1);
+        assert_eq!(patterns[1].to_string(), "a#1 → b#2 completing {a/0, b/ok}");
+    }
+}
@@ -0,0 +1,212 @@
+//! Cross-validation of serializability certificates with SMPT.
+//!
+//! A `Serializable` certificate is an NS invariant, which `NSInvariant::check_proof`
+//! checks in Rust and ISL. The invariant itself is composed from SMPT proofs by a
+//! chain of translations (existential elimination, place renaming, projection to
+//! per-global-state invariants), and a bug in any of them could produce an invariant
+//! that the Rust checker accepts for the wrong reasons. With `--cross-validate`, the
+//! invariant is translated back to the Petri net with requests and SMPT is asked,
+//! independently, whether any reachable marking violates it. A reachable violation
+//! means that the two checkers disagree, which is reported loudly.
+//!
+//! The invariant of global state `g` holds in markings with a token in `G_g`, where
+//! in-flight requests `(req, l)` are the tokens in `L_l_REQ_req` and completed requests
+//! `(req, resp)` the tokens in `RESP_resp_REQ_req`. The complement of the union of
+//! these sets is split into disjuncts, which are queried like the disjuncts of the
+//! serializability check.
+
+use crate::ns::NS;
+use crate::ns_decision::{NSInvariant, RequestState, RequestStatePair};
+use crate::ns_to_petri::{ReqPetriState, ns_to_petri_with_requests};
+use crate::presburger::PresburgerSet;
+use crate::proof_parser::{AffineExpr, CompOp, Constraint, Formula};
+use crate::reachability_with_proofs::{Decision, can_reach_quantified_set};
+use crate::smpt::CancellationToken;
+use colored::*;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Enable cross-validation of serializability certificates with SMPT
+pub fn set_cross_validate(enabled: bool) {
+    crate::options::update(|options| options.cross_validate = enabled);
+}
+
+pub fn cross_validate_enabled() -> bool {
+    crate::options::get(|options| options.cross_validate)
+}
+
+/// Outcome of cross-validating a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrossValidation {
+    /// SMPT proved that no reachable marking violates the invariant
+    Agree,
+    /// SMPT found a reachable marking that violates the invariant
+    Disagree {
+        disjunct: usize,
+        trace_length: usize,
+    },
+    /// SMPT could not decide some disjunct
+    Inconclusive { message: String },
+    /// SMPT is not installed
+    Skipped,
+}
+
+/// Place of the Petri net with requests that counts a request state
+fn place_of<G, L, Req, Resp>(pair: &RequestStatePair<Req, L, Resp>) -> String
+where
+    G: Display,
+    L: Display + Clone,
+    Req: Display + Clone,
+    Resp: Display + Clone,
+{
+    let place: ReqPetriState<L, G, Req, Resp> = match &pair.1 {
+        RequestState::InFlight(local) => ReqPetriState::Local(pair.0.clone(), local.clone()),
+        RequestState::Completed(resp) => ReqPetriState::Response(pair.0.clone(), resp.clone()),
+    };
+    place.to_string()
+}
+
+/// The invariant as a formula over the places of the Petri net with requests
+fn invariant_formula<G, L, Req, Resp>(invariant: &NSInvariant<G, L, Req, Resp>) -> Formula<String>
+where
+    G: Display + Clone + Eq + Hash,
+    L: Display + Clone + Eq + Hash,
+    Req: Display + Clone + Eq + Hash,
+    Resp: Display + Clone + Eq + Hash,
+{
+    let mut per_global = Vec::new();
+    for (global, global_invariant) in &invariant.global_invariants {
+        let global_place = ReqPetriState::<L, G, Req, Resp>::Global(global.clone()).to_string();
+        let marked = AffineExpr::from_var(global_place).sub(&AffineExpr::from_const(1));
+        let formula = global_invariant
+            .clone()
+            .map(|pair| place_of::<G, L, Req, Resp>(&pair))
+            .formula;
+        per_global.push(Formula::And(vec![
+            Formula::Constraint(Constraint::new(marked, CompOp::Eq)),
+            formula,
+        ]));
+    }
+    Formula::Or(per_global)
+}
+
+/// Ask SMPT whether a reachable marking of the Petri net with requests violates the
+/// invariant. Query files are written to `<out_dir>/cross_validation`.
+pub fn cross_validate<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    invariant: &NSInvariant<G, L, Req, Resp>,
+    out_dir: &str,
+) -> CrossValidation
+where
+    G: Clone + Ord + Hash + Display + Debug,
+    L: Clone + Ord + Hash + Display + Debug,
+    Req: Clone + Ord + Hash + Display + Debug,
+    Resp: Clone + Ord + Hash + Display + Debug,
+{
+    if !crate::smpt::smpt_available() {
+        return CrossValidation::Skipped;
+    }
+
+    let petri = ns_to_petri_with_requests(ns).rename(|place| place.to_string());
+    let formula = invariant_formula(invariant);
+    let mut places = petri.get_places();
+    for place in formula.collect_free_variables() {
+        if !places.contains(&place) {
+            places.push(place);
+        }
+    }
+    places.sort();
+
+    let holds = crate::proofinvariant_to_presburger::formula_to_presburger(&formula, &places);
+    let violated = PresburgerSet::universe(places).difference(&holds);
+    let disjuncts = violated.to_quantified_sets();
+
+    let query_dir = crate::utils::file::join(out_dir, "cross_validation");
+    let cancel = CancellationToken::new();
+    let mut inconclusive = Vec::new();
+    for (i, disjunct) in disjuncts.into_iter().enumerate() {
+        match can_reach_quantified_set(petri.clone(), disjunct, &query_dir, i, &cancel) {
+            Decision::Proof { .. } => {}
+            Decision::CounterExample { trace } => {
+                return CrossValidation::Disagree {
+                    disjunct: i,
+                    trace_length: trace.len(),
+                };
+            }
+            Decision::Timeout { message } => {
+                inconclusive.push(format!("disjunct {}: {}", i, message))
+            }
+            Decision::Unknown { reason, .. } => {
+                inconclusive.push(format!("disjunct {}: {:?}", i, reason))
+            }
+        }
+    }
+    if inconclusive.is_empty() {
+        CrossValidation::Agree
+    } else {
+        CrossValidation::Inconclusive {
+            message: inconclusive.join("; "),
+        }
+    }
+}
+
+/// Cross-validate and print the outcome. Returns false only on a disagreement.
+pub fn report<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    invariant: &NSInvariant<G, L, Req, Resp>,
+    out_dir: &str,
+) -> bool
+where
+    G: Clone + Ord + Hash + Display + Debug,
+    L: Clone + Ord + Hash + Display + Debug,
+    Req: Clone + Ord + Hash + Display + Debug,
+    Resp: Clone + Ord + Hash + Display + Debug,
+{
+    println!();
+    println!(
+        "{}",
+        "Cross-validating the invariant with SMPT...".cyan().bold()
+    );
+    match cross_validate(ns, invariant, out_dir) {
+        CrossValidation::Agree => {
+            println!(
+                "  {} SMPT confirms that no reachable marking violates the invariant",
+                "✓".green()
+            );
+            true
+        }
+        CrossValidation::Disagree {
+            disjunct,
+            trace_length,
+        } => {
+            eprintln!();
+            eprintln!("{}", "!!! CROSS-VALIDATION DISAGREEMENT !!!".red().bold());
+            eprintln!(
+                "The certificate checker accepted the invariant, but SMPT reaches a marking \
+                 that violates it (disjunct {}, trace of {} transitions).",
+                disjunct, trace_length
+            );
+            eprintln!(
+                "This indicates a bug in the translation from Petri net proofs to NS invariants \
+                 or in the certificate checker. Query files: {}/cross_validation",
+                out_dir
+            );
+            false
+        }
+        CrossValidation::Inconclusive { message } => {
+            println!(
+                "  {} Cross-validation inconclusive: {}",
+                "?".yellow(),
+                message
+            );
+            true
+        }
+        CrossValidation::Skipped => {
+            println!(
+                "  {} SMPT is not available; cross-validation skipped",
+                "⚠".yellow()
+            );
+            true
+        }
+    }
+}
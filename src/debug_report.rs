@@ -1,6 +1,6 @@
 //! HTML debugging report generation for serializability analysis
 
-use crate::presburger::Constraint;
+use crate::constraints::Constraint;
 use crate::size_logger::{SemilinearStats, log_semilinear_size_csv};
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
@@ -32,6 +32,10 @@ pub struct DebugReport {
     pub smpt_calls: Vec<SmptCall>,
     pub final_result: String,
     pub total_execution_time_ms: u64,
+    /// Counterexample as a Mermaid sequence diagram, if one was found
+    pub trace_diagram: Option<String>,
+    /// Documentation of the requests, from `///` comments
+    pub request_docs: Vec<(String, String)>,
 }
 
 impl DebugReport {
@@ -43,6 +47,8 @@ impl DebugReport {
             smpt_calls: Vec::new(),
             final_result: String::new(),
             total_execution_time_ms: 0,
+            trace_diagram: None,
+            request_docs: Vec::new(),
         }
     }
 
@@ -60,6 +66,14 @@ impl DebugReport {
         self.smpt_calls.push(call);
     }
 
+    pub fn set_trace_diagram(&mut self, diagram: String) {
+        self.trace_diagram = Some(diagram);
+    }
+
+    pub fn set_request_docs(&mut self, docs: Vec<(String, String)>) {
+        self.request_docs = docs;
+    }
+
     pub fn set_final_result(&mut self, result: String, total_time_ms: u64) {
         self.final_result = result;
         self.total_execution_time_ms = total_time_ms;
@@ -154,7 +168,7 @@ impl DebugReport {
             <h2>📄 Program Source</h2>
             <div class="code-block">{}</div>
         </div>
-
+{}{}
         <div class="section">
             <h2>🔄 Algorithm Execution Steps</h2>
             {}
@@ -191,12 +205,62 @@ impl DebugReport {
             self.smpt_calls.len(),
             self.algorithm_steps.len(),
             html_escape(&self.program_content),
+            self.render_request_docs(),
+            self.render_trace_diagram(),
             self.render_algorithm_steps(),
             self.render_smpt_calls(),
             self.render_timeline()
         )
     }
 
+    fn render_request_docs(&self) -> String {
+        if self.request_docs.is_empty() {
+            return String::new();
+        }
+        let rows: String = self
+            .request_docs
+            .iter()
+            .map(|(request, doc)| {
+                format!(
+                    "<tr><td>{}</td><td style=\"white-space: pre-wrap\">{}</td></tr>",
+                    html_escape(request),
+                    html_escape(doc)
+                )
+            })
+            .collect();
+        format!(
+            r#"
+        <div class="section">
+            <h2>📝 Requests</h2>
+            <table>
+                <tr><th>Request</th><th>Documentation</th></tr>
+                {}
+            </table>
+        </div>
+"#,
+            rows
+        )
+    }
+
+    fn render_trace_diagram(&self) -> String {
+        let Some(diagram) = &self.trace_diagram else {
+            return String::new();
+        };
+        format!(
+            r#"
+        <div class="section">
+            <h2>❌ Counterexample Trace</h2>
+            <pre class="mermaid">{}</pre>
+            <script type="module">
+                import mermaid from 'https://cdn.jsdelivr.net/npm/mermaid@10/dist/mermaid.esm.min.mjs';
+                mermaid.initialize({{ startOnLoad: true }});
+            </script>
+        </div>
+"#,
+            html_escape(diagram)
+        )
+    }
+
     fn render_algorithm_steps(&self) -> String {
         self.algorithm_steps
             .iter()
@@ -324,6 +388,18 @@ impl DebugLogger {
         }
     }
 
+    pub fn request_docs(&self, docs: Vec<(String, String)>) {
+        if let Ok(mut report) = self.report.lock() {
+            report.set_request_docs(docs);
+        }
+    }
+
+    pub fn trace_diagram(&self, diagram: String) {
+        if let Ok(mut report) = self.report.lock() {
+            report.set_trace_diagram(diagram);
+        }
+    }
+
     pub fn finalize(
         &self,
         result: String,
@@ -332,7 +408,7 @@ impl DebugLogger {
     ) -> Result<(), std::io::Error> {
         if let Ok(mut report) = self.report.lock() {
             report.set_final_result(result, total_time_ms);
-            let output_path = format!("{}/debug_report.html", output_dir);
+            let output_path = crate::utils::file::join(output_dir, "debug_report.html");
             report.generate_html(&output_path)?;
         }
         Ok(())
@@ -524,7 +600,7 @@ impl DebugLogger {
         &self,
         name: &str,
         description: &str,
-        constraints: &[crate::presburger::Constraint<P>],
+        constraints: &[crate::constraints::Constraint<P>],
     ) {
         let mut details = String::new();
         details.push_str(&format!("⚖️ CONSTRAINTS ({}):\n\n", constraints.len()));
@@ -545,7 +621,7 @@ impl DebugLogger {
                 .filter(|c| {
                     matches!(
                         c.constraint_type(),
-                        crate::presburger::ConstraintType::EqualToZero
+                        crate::constraints::ConstraintType::EqualToZero
                     )
                 })
                 .count();
@@ -568,7 +644,7 @@ impl DebugLogger {
                 if constraint.linear_combination().is_empty() {
                     let rhs = -constraint.constant_term();
                     match constraint.constraint_type() {
-                        crate::presburger::ConstraintType::EqualToZero => {
+                        crate::constraints::ConstraintType::EqualToZero => {
                             if rhs != 0 {
                                 details.push_str(&format!(
                                     "  ⚠️ CONTRADICTION: 0 = {} (impossible!)\n",
@@ -577,7 +653,7 @@ impl DebugLogger {
                                 has_contradiction = true;
                             }
                         }
-                        crate::presburger::ConstraintType::NonNegative => {
+                        crate::constraints::ConstraintType::NonNegative => {
                             if rhs < 0 {
                                 details.push_str(&format!(
                                     "  ⚠️ CONTRADICTION: 0 ≥ {} (impossible!)\n",
@@ -601,7 +677,7 @@ impl DebugLogger {
     pub fn log_disjunct_start<T: Clone + Display + Debug>(
         &self,
         disjunct_id: usize,
-        quantified_set: &crate::presburger::QuantifiedSet<T>,
+        quantified_set: &crate::constraints::QuantifiedSet<T>,
     ) {
         let mut details = String::new();
         details.push_str(&format!("🎯 DISJUNCT {} ANALYSIS:\n\n", disjunct_id));
@@ -642,6 +718,7 @@ impl DebugLogger {
     }
 
     /// Log PresburgerSet using its Display implementation
+    #[cfg(feature = "isl")]
     pub fn log_presburger_set<T: Clone + Display + Debug>(
         &self,
         name: &str,
@@ -666,7 +743,7 @@ impl DebugLogger {
         &self,
         name: &str,
         description: &str,
-        qset: &crate::presburger::QuantifiedSet<T>,
+        qset: &crate::constraints::QuantifiedSet<T>,
     ) {
         let mut details = String::new();
         details.push_str("⚖️ QUANTIFIED SET:\n\n");
@@ -730,7 +807,7 @@ pub fn finalize_debug_report(
         if let Ok(mut report_opt) = mutex.lock() {
             if let Some(report) = report_opt.as_mut() {
                 report.set_final_result(result, total_time_ms);
-                let output_path = format!("{}/debug_report.html", output_dir);
+                let output_path = crate::utils::file::join(output_dir, "debug_report.html");
                 report.generate_html(&output_path)?;
             }
         }
@@ -770,8 +847,8 @@ pub fn format_constraints_description<P: Display>(constraints: &[Constraint<P>])
             let rhs = -constraint.constant_term();
 
             let op = match constraint.constraint_type() {
-                crate::presburger::ConstraintType::NonNegative => "≥",
-                crate::presburger::ConstraintType::EqualToZero => "=",
+                crate::constraints::ConstraintType::NonNegative => "≥",
+                crate::constraints::ConstraintType::EqualToZero => "=",
             };
 
             format!("{}. {} {} {}", i + 1, lhs, op, rhs)
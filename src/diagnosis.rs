@@ -0,0 +1,249 @@
+//! Per-request diagnosis of non-serializable systems.
+//!
+//! Finds a set of request types whose removal makes the system serializable and
+//! that is irreducible: putting back any one of them breaks serializability again.
+//! This is the analogue of a minimal unsat core, over requests instead of clauses.
+//!
+//! The search avoids trying all subsets. Counterexamples guide the growth phase:
+//! every counterexample involves some request that has to be removed, so we remove
+//! the request that occurs most often in the current counterexample until the rest is
+//! serializable. A shrink phase then tries to put each removed request back. All
+//! checks are cached by the set of removed requests.
+
+use crate::deterministic_map::HashMap;
+#[cfg(feature = "isl")]
+use crate::ns::NS;
+#[cfg(feature = "isl")]
+use crate::ns_decision::NSDecision;
+use crate::ns_decision::NSStep;
+#[cfg(feature = "isl")]
+use colored::*;
+use std::cmp::Reverse;
+use std::fmt::Debug;
+#[cfg(feature = "isl")]
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// Set the diagnose flag (called from `main.rs`)
+pub fn set_diagnose(enabled: bool) {
+    crate::options::update(|options| options.diagnose = enabled);
+}
+
+/// Helper to check whether non-serializable systems are diagnosed
+pub fn diagnose_enabled() -> bool {
+    crate::options::get(|options| options.diagnose)
+}
+
+/// Outcome of checking the system with some requests removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict<Req> {
+    Serializable,
+    /// Not serializable; the requests started in the counterexample, most frequent first
+    Conflict(Vec<Req>),
+    /// Timeout or unknown
+    Inconclusive,
+}
+
+/// Result of a successful diagnosis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis<Req> {
+    /// Irreducible set of request types whose removal makes the system serializable
+    pub conflicting: Vec<Req>,
+    /// Number of distinct subsystems that were checked
+    pub checks: usize,
+}
+
+/// Requests started in a counterexample, most frequent first (ties in order of first start)
+pub fn counterexample_requests<G, L, Req, Resp>(steps: &[NSStep<G, L, Req, Resp>]) -> Vec<Req>
+where
+    Req: Clone + Eq + Hash,
+{
+    let mut counts: Vec<(Req, usize)> = Vec::new();
+    for step in steps {
+        if let NSStep::RequestStart { request, .. } = step {
+            match counts.iter_mut().find(|(r, _)| r == request) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((request.clone(), 1)),
+            }
+        }
+    }
+    counts.sort_by_key(|&(_, count)| Reverse(count));
+    counts.into_iter().map(|(r, _)| r).collect()
+}
+
+/// Find an irreducible set of requests to remove, starting from a known conflict.
+/// `check` decides the system with the given requests removed (sorted). Returns `None`
+/// if some check is inconclusive or no removal helps.
+pub fn minimal_conflict_set<Req>(
+    initial_conflict: Vec<Req>,
+    mut check: impl FnMut(&[Req]) -> Verdict<Req>,
+) -> Option<Diagnosis<Req>>
+where
+    Req: Clone + Ord + Hash,
+{
+    let mut cache: HashMap<Vec<Req>, Verdict<Req>> = HashMap::default();
+    let mut cached_check = |removed: &[Req]| -> Verdict<Req> {
+        let mut key = removed.to_vec();
+        key.sort();
+        if let Some(verdict) = cache.get(&key) {
+            return verdict.clone();
+        }
+        let verdict = check(&key);
+        cache.insert(key, verdict.clone());
+        verdict
+    };
+
+    // Grow: remove a request of each counterexample until the rest is serializable
+    let mut removed: Vec<Req> = Vec::new();
+    let mut verdict = Verdict::Conflict(initial_conflict);
+    loop {
+        match verdict {
+            Verdict::Serializable => break,
+            Verdict::Inconclusive => return None,
+            Verdict::Conflict(requests) => {
+                let next = requests.into_iter().find(|r| !removed.contains(r))?;
+                removed.push(next);
+                verdict = cached_check(&removed);
+            }
+        }
+    }
+
+    // Shrink: put back every request that is not needed
+    let mut i = 0;
+    while i < removed.len() {
+        let mut without = removed.clone();
+        without.remove(i);
+        if cached_check(&without) == Verdict::Serializable {
+            removed = without;
+        } else {
+            i += 1;
+        }
+    }
+
+    removed.sort();
+    Some(Diagnosis {
+        conflicting: removed,
+        checks: cache.len(),
+    })
+}
+
+/// Diagnose a non-serializable system given the counterexample of the full analysis,
+/// and print the conflicting requests. Subsystem analyses write to `<out_dir>/diagnosis`.
+#[cfg(feature = "isl")]
+pub fn diagnose<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    decision: &NSDecision<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Option<Diagnosis<Req>>
+where
+    G: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    L: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Req: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Resp:
+        Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
+    let NSDecision::NotSerializable { trace } = decision else {
+        return None;
+    };
+
+    println!();
+    println!("{}", "Diagnosing conflicting requests...".blue().bold());
+    let mut runs = 0;
+    let result = minimal_conflict_set(counterexample_requests(&trace.steps), |removed| {
+        runs += 1;
+        let subsystem = ns.restrict_requests(|req| !removed.contains(req));
+        let sub_dir = crate::utils::file::join(out_dir, &format!("diagnosis/check_{}", runs));
+        if let Err(err) = crate::utils::file::ensure_dir_exists(&sub_dir) {
+            eprintln!("Failed to create {}: {}", sub_dir, err);
+            return Verdict::Inconclusive;
+        }
+        // A subsystem whose requests all commute is serializable (see `commutativity`)
+        let verdict = if crate::commutativity::commutativity_matrix(&subsystem).all_commute() {
+            Verdict::Serializable
+        } else {
+            match subsystem.create_certificate(&sub_dir) {
+                NSDecision::Serializable { .. } => Verdict::Serializable,
+                NSDecision::NotSerializable { trace } => {
+                    Verdict::Conflict(counterexample_requests(&trace.steps))
+                }
+                NSDecision::Timeout { .. } | NSDecision::Unknown { .. } => Verdict::Inconclusive,
+            }
+        };
+        println!(
+            "  without {{{}}}: {}",
+            removed
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            match &verdict {
+                Verdict::Serializable => "serializable".green(),
+                Verdict::Conflict(_) => "not serializable".red(),
+                Verdict::Inconclusive => "inconclusive".yellow(),
+            }
+        );
+        verdict
+    });
+
+    match &result {
+        Some(diagnosis) => println!(
+            "{} {{{}}} ({} subsystems checked)",
+            "conflicting requests:".red().bold(),
+            diagnosis
+                .conflicting
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            diagnosis.checks
+        ),
+        None => println!(
+            "{}",
+            "Could not determine the conflicting requests (a subsystem check was inconclusive)"
+                .yellow()
+        ),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counterexample_requests() {
+        let start = |r: &str| NSStep::<String, String, String, String>::RequestStart {
+            request: r.to_string(),
+            initial_local: "l".to_string(),
+        };
+        let steps = vec![start("read"), start("write"), start("write")];
+        assert_eq!(counterexample_requests(&steps), vec!["write", "read"]);
+    }
+
+    #[test]
+    fn test_minimal_conflict_set() {
+        // Serializable iff write is removed, or both transfer and audit are removed
+        let mut calls = 0;
+        let check = |removed: &[&str]| {
+            calls += 1;
+            let has = |r| removed.contains(&r);
+            if has("write") || (has("transfer") && has("audit")) {
+                Verdict::Serializable
+            } else if !has("transfer") {
+                Verdict::Conflict(vec!["transfer", "write"])
+            } else {
+                Verdict::Conflict(vec!["audit", "write"])
+            }
+        };
+        let diagnosis = minimal_conflict_set(vec!["transfer", "write"], check).unwrap();
+        // Growing removes transfer and audit; neither can be put back
+        assert_eq!(diagnosis.conflicting, vec!["audit", "transfer"]);
+        assert_eq!(diagnosis.checks, calls);
+    }
+
+    #[test]
+    fn test_inconclusive_check() {
+        let check = |_: &[&str]| Verdict::Inconclusive;
+        assert_eq!(minimal_conflict_set(vec!["a"], check), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Order of the dimensions of Presburger sets (`--dim-order`).
+//!
+//! A `PresburgerSet` maps its dimensions to atoms, and the order of the atoms can make
+//! a large difference for the performance of ISL. All sets must order their atoms the
+//! same way, because `harmonize` embeds a set into a bigger one by inserting missing
+//! dimensions, which only works if the atoms the sets share are in the same order. So
+//! the order is a single total order on atoms, fixed for the whole analysis:
+//!
+//! - `sorted`: the `Ord` order of the atoms (default)
+//! - `frequency`: atoms that occur in more transitions of the Petri net first
+//! - `structural`: in the order the transitions of the Petri net first mention them,
+//!   so the places of a transition are close together
+//! - `random[:seed]`: a pseudo-random order, to test how robust the analysis is
+//!
+//! `frequency` and `structural` rank atoms by their `Display` output, using the Petri
+//! net of the current analysis (see `record_petri_net`). Atoms without a rank come
+//! after the ranked ones, in `Ord` order.
+
+use crate::deterministic_map::HashMap;
+use crate::petri::Petri;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DimOrder {
+    Sorted,
+    Frequency,
+    Structural,
+    Random(u64),
+}
+
+impl DimOrder {
+    /// Parse `sorted`, `frequency`, `structural`, `random` or `random:<seed>`. Without a
+    /// seed, the seed is taken from the clock.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sorted" => Some(DimOrder::Sorted),
+            "frequency" => Some(DimOrder::Frequency),
+            "structural" => Some(DimOrder::Structural),
+            "random" => {
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or(0);
+                Some(DimOrder::Random(nanos))
+            }
+            _ => name
+                .strip_prefix("random:")
+                .and_then(|seed| seed.parse().ok())
+                .map(DimOrder::Random),
+        }
+    }
+}
+
+thread_local! {
+    /// Rank of each atom by `Display` output, for `frequency` and `structural`. Per
+    /// thread, like the ISL sets that are ordered by it.
+    static RANKS: RefCell<Option<HashMap<String, usize>>> = const { RefCell::new(None) };
+}
+
+/// Select the order (called from `main.rs`, before any Presburger set is built)
+pub fn set_dim_order(order: DimOrder) {
+    crate::options::update(|options| options.dim_order = order);
+}
+
+pub fn dim_order() -> DimOrder {
+    crate::options::get(|options| options.dim_order)
+}
+
+/// Rank the places of `petri` for the `frequency` and `structural` orders. Called with
+/// the net of every analysis before it builds its sets, so that the ranks of an earlier
+/// analysis (e.g. of another file in directory mode) are not reused.
+pub fn record_petri_net<P>(petri: &Petri<P>)
+where
+    P: Clone + Eq + Hash + ToString,
+{
+    let order = dim_order();
+    let ranks = matches!(order, DimOrder::Frequency | DimOrder::Structural)
+        .then(|| petri_ranks(petri, order));
+    RANKS.with(|r| *r.borrow_mut() = ranks);
+}
+
+fn petri_ranks<P>(petri: &Petri<P>, order: DimOrder) -> HashMap<String, usize>
+where
+    P: Clone + Eq + Hash + ToString,
+{
+    // Places in order of first mention, with the number of transitions they occur in
+    let mut places: Vec<(String, usize)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::default();
+    let mut mention = |place: &P, count: usize| {
+        let name = place.to_string();
+        let i = *index.entry(name.clone()).or_insert_with(|| {
+            places.push((name, 0));
+            places.len() - 1
+        });
+        places[i].1 += count;
+    };
+    for place in petri.get_initial_marking() {
+        mention(&place, 0);
+    }
+    for (input, output) in petri.get_transitions() {
+        for place in input.iter().chain(&output) {
+            mention(place, 1);
+        }
+    }
+
+    if order == DimOrder::Frequency {
+        // Stable, so ties stay in order of first mention
+        places.sort_by_key(|&(_, count)| Reverse(count));
+    }
+    places
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (name, _))| (name, rank))
+        .collect()
+}
+
+/// Sort atoms in the selected order
+pub fn sort_atoms<T: Ord + ToString>(atoms: &mut [T]) {
+    // The keys are computed once per atom; sorting by them is stable, so atoms with
+    // the same key stay in `Ord` order
+    atoms.sort();
+    match dim_order() {
+        DimOrder::Sorted => {}
+        DimOrder::Random(seed) => atoms
+            .sort_by_cached_key(|t| crate::naming::fnv1a(&format!("{}:{}", seed, t.to_string()))),
+        DimOrder::Frequency | DimOrder::Structural => RANKS.with(|ranks| {
+            let ranks = ranks.borrow();
+            atoms.sort_by_cached_key(|t| {
+                ranks
+                    .as_ref()
+                    .and_then(|ranks| ranks.get(&t.to_string()).copied())
+                    .unwrap_or(usize::MAX)
+            })
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_petri_ranks() {
+        let mut petri = Petri::new(vec!["a"]);
+        petri.add_transition(vec!["a"], vec!["b"]);
+        petri.add_transition(vec!["c"], vec!["b"]);
+        petri.add_transition(vec!["b"], vec!["d"]);
+
+        let structural = petri_ranks(&petri, DimOrder::Structural);
+        assert_eq!(structural["a"], 0);
+        assert_eq!(structural["b"], 1);
+        assert_eq!(structural["c"], 2);
+        assert_eq!(structural["d"], 3);
+
+        // b occurs in three transitions, the others in one
+        let frequency = petri_ranks(&petri, DimOrder::Frequency);
+        assert_eq!(frequency["b"], 0);
+        assert_eq!(frequency["a"], 1);
+        assert_eq!(frequency["c"], 2);
+        assert_eq!(frequency["d"], 3);
+    }
+
+    #[test]
+    fn test_ranks_per_analysis() {
+        let options = crate::options::AnalysisOptions {
+            dim_order: DimOrder::Structural,
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            let mut first = Petri::new(vec!["b"]);
+            first.add_transition(vec!["b"], vec!["a"]);
+            record_petri_net(&first);
+            let mut atoms = vec!["a", "b", "c"];
+            sort_atoms(&mut atoms);
+            assert_eq!(atoms, vec!["b", "a", "c"]);
+
+            // The next analysis ranks its own places
+            let mut second = Petri::new(vec!["c"]);
+            second.add_transition(vec!["c"], vec!["a"]);
+            record_petri_net(&second);
+            sort_atoms(&mut atoms);
+            assert_eq!(atoms, vec!["c", "a", "b"]);
+        });
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(DimOrder::from_name("sorted"), Some(DimOrder::Sorted));
+        assert_eq!(DimOrder::from_name("random:7"), Some(DimOrder::Random(7)));
+        assert!(matches!(
+            DimOrder::from_name("random"),
+            Some(DimOrder::Random(_))
+        ));
+        assert_eq!(DimOrder::from_name("random:x"), None);
+        assert_eq!(DimOrder::from_name("alphabetical"), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Errors of the command line tool and their exit codes.
+//!
+//! Processing functions return a `SerError`, and `main` turns it into a message on
+//! stderr and one of the exit codes below, so that scripts can tell failures apart
+//! without parsing the output. A completed analysis exits with 0 whatever its verdict;
+//! the verdict is in `out/<name>/certificate.json` and `stats.json`.
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 2 | Invalid command line arguments |
+//! | 3 | The input could not be parsed |
+//! | 4 | A file could not be read or written |
+//! | 5 | ISL failed |
+//! | 6 | SMPT failed or could not decide the analysis |
+//! | 7 | A certificate is missing, unreadable or invalid |
+//! | 8 | The analysis timed out |
+//! | 101 | Internal error (a panic) |
+
+use std::fmt::Display;
+
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_PARSE: i32 = 3;
+pub const EXIT_IO: i32 = 4;
+pub const EXIT_ISL: i32 = 5;
+pub const EXIT_SMPT: i32 = 6;
+pub const EXIT_CERTIFICATE: i32 = 7;
+pub const EXIT_TIMEOUT: i32 = 8;
+/// Exit code of a process that panicked, set by the Rust runtime
+pub const EXIT_PANIC: i32 = 101;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerError {
+    /// Invalid command line arguments
+    Usage(String),
+    ParseError(String),
+    IoError(String),
+    IslError(String),
+    SmptError(String),
+    CertificateError(String),
+    Timeout(String),
+}
+
+impl SerError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SerError::Usage(_) => EXIT_USAGE,
+            SerError::ParseError(_) => EXIT_PARSE,
+            SerError::IoError(_) => EXIT_IO,
+            SerError::IslError(_) => EXIT_ISL,
+            SerError::SmptError(_) => EXIT_SMPT,
+            SerError::CertificateError(_) => EXIT_CERTIFICATE,
+            SerError::Timeout(_) => EXIT_TIMEOUT,
+        }
+    }
+
+    /// Short description of the kind of error, used as the prefix of messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SerError::Usage(_) => "Usage error",
+            SerError::ParseError(_) => "Parse error",
+            SerError::IoError(_) => "I/O error",
+            SerError::IslError(_) => "ISL error",
+            SerError::SmptError(_) => "SMPT error",
+            SerError::CertificateError(_) => "Certificate error",
+            SerError::Timeout(_) => "Timeout",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            SerError::Usage(message)
+            | SerError::ParseError(message)
+            | SerError::IoError(message)
+            | SerError::IslError(message)
+            | SerError::SmptError(message)
+            | SerError::CertificateError(message)
+            | SerError::Timeout(message) => message,
+        }
+    }
+}
+
+impl Display for SerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind(), self.message())
+    }
+}
+
+impl std::error::Error for SerError {}
+
+impl From<std::io::Error> for SerError {
+    fn from(err: std::io::Error) -> Self {
+        SerError::IoError(err.to_string())
+    }
+}
+
+/// Classify a panic raised during an analysis. ISL reports its failures by panicking
+/// with an `IslFailure` (see `isl::fail`), so those become `IslError`; other panics are
+/// bugs and are not turned into errors.
+#[cfg(feature = "isl")]
+pub fn from_panic(payload: &(dyn std::any::Any + Send)) -> Option<SerError> {
+    payload
+        .downcast_ref::<crate::isl::IslFailure>()
+        .map(|failure| SerError::IslError(failure.to_string()))
+}
+
+/// Without ISL every panic is a bug
+#[cfg(not(feature = "isl"))]
+pub fn from_panic(_payload: &(dyn std::any::Any + Send)) -> Option<SerError> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            SerError::Usage(String::new()),
+            SerError::ParseError(String::new()),
+            SerError::IoError(String::new()),
+            SerError::IslError(String::new()),
+            SerError::SmptError(String::new()),
+            SerError::CertificateError(String::new()),
+            SerError::Timeout(String::new()),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(SerError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+
+        let err: SerError = std::io::Error::other("disk full").into();
+        assert_eq!(err.exit_code(), EXIT_IO);
+        assert_eq!(err.to_string(), "I/O error: disk full");
+    }
+
+    #[test]
+    #[cfg(feature = "isl")]
+    fn test_isl_failures_are_errors() {
+        let panic =
+            std::panic::catch_unwind(|| crate::isl::fail("out of memory".to_string())).unwrap_err();
+        let err = from_panic(panic.as_ref()).unwrap();
+        assert_eq!(err.exit_code(), EXIT_ISL);
+        assert_eq!(err.message(), "out of memory");
+        // Other panics are bugs, even if their message mentions ISL
+        let panic: Box<dyn std::any::Any + Send> = Box::new("ISL set has the wrong space");
+        assert!(from_panic(panic.as_ref()).is_none());
+    }
+}
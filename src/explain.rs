@@ -0,0 +1,352 @@
+//! Human-readable explanations of serializability proofs (`--explain`).
+//!
+//! The invariant of a proof is a formula over token counts. Many of its top-level
+//! conjuncts are instances of a few familiar patterns, such as a lock that at most one
+//! request holds or a counter that never drops below another. This module recognizes
+//! those patterns and turns them into sentences; conjuncts that fit none of them (or
+//! that use quantifiers or disjunctions) are only counted.
+
+use crate::constraints::Variable;
+use crate::ns_decision::{NSInvariant, RequestState};
+use crate::proof_parser::{CompOp, Constraint, Formula, ProofInvariant};
+use colored::*;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+/// Explain the invariant in the report of serializability proofs
+pub fn set_explain(enabled: bool) {
+    crate::options::update(|options| options.explain = enabled);
+}
+
+pub fn explain_enabled() -> bool {
+    crate::options::get(|options| options.explain)
+}
+
+/// A recognized conjunct of an invariant. Sums range over the listed places.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern<T> {
+    /// The places never hold a token
+    Empty(Vec<T>),
+    /// The places hold at most one token in total
+    MutualExclusion(Vec<T>),
+    /// The places hold at most `bound` tokens in total
+    Bound { places: Vec<T>, bound: i64 },
+    /// The places always hold exactly `total` tokens
+    Conservation { places: Vec<T>, total: i64 },
+    /// `more` holds at least `offset` more tokens than `less`
+    Dominates {
+        more: Vec<T>,
+        less: Vec<T>,
+        offset: i64,
+    },
+    /// `left` holds exactly `offset` more tokens than `right`
+    Balance {
+        left: Vec<T>,
+        right: Vec<T>,
+        offset: i64,
+    },
+}
+
+/// The recognized patterns of an invariant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation<T> {
+    pub patterns: Vec<Pattern<T>>,
+    /// Conjuncts that are not one of the patterns
+    pub unexplained: usize,
+}
+
+/// Recognize the patterns among the top-level conjuncts of `invariant`
+pub fn explain<T>(invariant: &ProofInvariant<T>) -> Explanation<T>
+where
+    T: Clone + Ord + Hash,
+{
+    let mut conjuncts = Vec::new();
+    flatten_and(&invariant.formula, &mut conjuncts);
+
+    let mut explanation = Explanation {
+        patterns: Vec::new(),
+        unexplained: 0,
+    };
+    for conjunct in conjuncts {
+        let recognized = match conjunct {
+            Formula::Constraint(constraint) => recognize(constraint),
+            _ => None,
+        };
+        match recognized {
+            Some(Some(pattern)) => {
+                if !explanation.patterns.contains(&pattern) {
+                    explanation.patterns.push(pattern);
+                }
+            }
+            // Trivially true, like `x ≥ 0`
+            Some(None) => {}
+            None => explanation.unexplained += 1,
+        }
+    }
+    explanation
+}
+
+fn flatten_and<'a, T: Eq + Hash>(formula: &'a Formula<T>, out: &mut Vec<&'a Formula<T>>) {
+    match formula {
+        Formula::And(conjuncts) => {
+            for conjunct in conjuncts {
+                flatten_and(conjunct, out);
+            }
+        }
+        _ => out.push(formula),
+    }
+}
+
+/// The pattern of a constraint `Σ positive - Σ negative + constant (= or ≥) 0` with
+/// unit coefficients. `Some(None)` means the constraint holds trivially.
+fn recognize<T>(constraint: &Constraint<T>) -> Option<Option<Pattern<T>>>
+where
+    T: Clone + Ord + Hash,
+{
+    let (terms, constant) = constraint.expr.to_linear_combination();
+    let mut positive = Vec::new();
+    let mut negative = Vec::new();
+    for (coeff, var) in terms {
+        let Variable::Var(place) = var else {
+            return None;
+        };
+        match coeff {
+            0 => {}
+            1 => positive.push(place),
+            -1 => negative.push(place),
+            _ => return None,
+        }
+    }
+    positive.sort();
+    negative.sort();
+
+    let pattern = match constraint.op {
+        CompOp::Geq => match (positive.is_empty(), negative.is_empty()) {
+            (_, true) if constant >= 0 => return Some(None),
+            (true, false) => match constant {
+                0 => Pattern::Empty(negative),
+                1 => Pattern::MutualExclusion(negative),
+                bound if bound > 1 => Pattern::Bound {
+                    places: negative,
+                    bound,
+                },
+                _ => return None,
+            },
+            (false, false) => Pattern::Dominates {
+                more: positive,
+                less: negative,
+                offset: -constant,
+            },
+            _ => return None,
+        },
+        CompOp::Eq => {
+            // Orient the equation so that the constant is on the side of the tokens
+            let (left, right, constant) = if positive.is_empty() {
+                (negative, positive, constant)
+            } else {
+                (positive, negative, -constant)
+            };
+            match (left.is_empty(), right.is_empty()) {
+                (true, true) => return (constant == 0).then_some(None),
+                (false, true) if constant == 0 => Pattern::Empty(left),
+                (false, true) if constant > 0 => Pattern::Conservation {
+                    places: left,
+                    total: constant,
+                },
+                (false, false) => Pattern::Balance {
+                    left,
+                    right,
+                    offset: constant,
+                },
+                _ => return None,
+            }
+        }
+    };
+    Some(Some(pattern))
+}
+
+fn join<T: Display>(places: &[T]) -> String {
+    places
+        .iter()
+        .map(|place| place.to_string())
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// `a + b + 2` for the places and an offset
+fn join_offset<T: Display>(places: &[T], offset: i64) -> String {
+    match offset {
+        0 => join(places),
+        offset if offset > 0 => format!("{} + {}", join(places), offset),
+        offset => format!("{} - {}", join(places), -offset),
+    }
+}
+
+impl<T: Display> Display for Pattern<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Empty(places) if places.len() == 1 => {
+                write!(f, "{} never holds a token", places[0])
+            }
+            Pattern::Empty(places) => write!(
+                f,
+                "none of {} ever holds a token",
+                places
+                    .iter()
+                    .map(|place| place.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Pattern::MutualExclusion(places) if places.len() == 1 => {
+                write!(f, "{} always holds ≤ 1 token (mutual exclusion)", places[0])
+            }
+            Pattern::MutualExclusion(places) => write!(
+                f,
+                "{} hold ≤ 1 token together (mutual exclusion)",
+                join(places)
+            ),
+            Pattern::Bound { places, bound } => {
+                write!(
+                    f,
+                    "{} always holds ≤ {} tokens (bound)",
+                    join(places),
+                    bound
+                )
+            }
+            Pattern::Conservation { places, total } => write!(
+                f,
+                "{} always holds exactly {} token{} (token conservation)",
+                join(places),
+                total,
+                if *total == 1 { "" } else { "s" }
+            ),
+            Pattern::Dominates { more, less, offset } => write!(
+                f,
+                "{} ≥ {} always holds (counter bound)",
+                join(more),
+                join_offset(less, *offset)
+            ),
+            Pattern::Balance {
+                left,
+                right,
+                offset,
+            } => write!(
+                f,
+                "{} = {} always holds (tokens move between them)",
+                join(left),
+                join_offset(right, *offset)
+            ),
+        }
+    }
+}
+
+/// Explain the invariant of each global state, with request states named `req@local`
+/// (in flight) and `req/resp` (completed), in the order of the global states
+pub fn explain_ns<G, L, Req, Resp>(
+    invariant: &NSInvariant<G, L, Req, Resp>,
+) -> Vec<(String, Explanation<String>)>
+where
+    G: Clone + Ord + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let mut globals: Vec<&G> = invariant.global_invariants.keys().collect();
+    globals.sort();
+    globals
+        .into_iter()
+        .map(|global| {
+            let named = invariant.global_invariants[global]
+                .clone()
+                .map(|pair| match pair.1 {
+                    RequestState::InFlight(local) => format!("{}@{}", pair.0, local),
+                    RequestState::Completed(resp) => format!("{}/{}", pair.0, resp),
+                });
+            (global.to_string(), explain(&named))
+        })
+        .collect()
+}
+
+/// Print the explanations as part of the report
+pub fn print_explanations(explanations: &[(String, Explanation<String>)]) {
+    println!();
+    println!("Why serializable:");
+    for (global, explanation) in explanations {
+        println!("  In global state {}:", global.cyan());
+        for pattern in &explanation.patterns {
+            println!("    - {}", pattern);
+        }
+        if explanation.patterns.is_empty() {
+            println!("    - no simple pattern");
+        }
+        if explanation.unexplained > 0 {
+            println!(
+                "    ({} more constraint{} without a simple pattern)",
+                explanation.unexplained,
+                if explanation.unexplained == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof_parser::AffineExpr;
+
+    fn constraint(terms: &[(i64, &str)], constant: i64, op: CompOp) -> Formula<String> {
+        let mut expr = AffineExpr::from_const(constant);
+        for (coeff, var) in terms {
+            expr = expr.add(&AffineExpr::from_var(var.to_string()).mul_by_const(*coeff));
+        }
+        Formula::Constraint(Constraint::new(expr, op))
+    }
+
+    #[test]
+    fn test_explain_patterns() {
+        let formula = Formula::And(vec![
+            constraint(&[(-1, "lock"), (-1, "crit")], 1, CompOp::Geq),
+            constraint(&[(1, "free"), (1, "held")], -1, CompOp::Eq),
+            constraint(&[(1, "inc"), (-1, "get")], 0, CompOp::Geq),
+            constraint(&[(-1, "q")], 3, CompOp::Geq),
+            constraint(&[(1, "x")], 0, CompOp::Geq),
+            constraint(&[(2, "x"), (-1, "y")], 0, CompOp::Geq),
+        ]);
+        let variables = ["lock", "crit", "free", "held", "inc", "get", "q", "x", "y"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let explanation = explain(&ProofInvariant::new(variables, formula));
+
+        let places = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            explanation.patterns,
+            vec![
+                Pattern::MutualExclusion(places(&["crit", "lock"])),
+                Pattern::Conservation {
+                    places: places(&["free", "held"]),
+                    total: 1
+                },
+                Pattern::Dominates {
+                    more: places(&["inc"]),
+                    less: places(&["get"]),
+                    offset: 0
+                },
+                Pattern::Bound {
+                    places: places(&["q"]),
+                    bound: 3
+                },
+            ]
+        );
+        // x ≥ 0 is trivial, 2x - y ≥ 0 has no pattern
+        assert_eq!(explanation.unexplained, 1);
+        assert_eq!(
+            explanation.patterns[0].to_string(),
+            "crit + lock hold ≤ 1 token together (mutual exclusion)"
+        );
+    }
+}
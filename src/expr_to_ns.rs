@@ -3,9 +3,20 @@ use crate::parser::*;
 use hash_cons::Hc;
 
 use crate::deterministic_map::{HashMap, HashSet};
+use colored::*;
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::hash::{Hash, Hasher};
 
+/// Whether request bodies are simplified before the conversion (see `simplify`)
+pub fn set_simplify(enabled: bool) {
+    crate::options::update(|options| options.simplify_exprs = enabled);
+}
+
+pub fn simplify_enabled() -> bool {
+    crate::options::get(|options| options.simplify_exprs)
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Env {
     vars: HashMap<String, i64>,
@@ -82,6 +93,8 @@ pub type Global = Env;
 pub enum ExprResult {
     Yielding(Hc<Expr>),
     Returning(i64),
+    /// A `return` ran: the request responds with this value, skipping the rest of its body
+    Responding(i64),
 }
 
 fn is_local(var: &str) -> bool {
@@ -107,6 +120,9 @@ pub fn run_expr(
                             global,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local, global));
+                    }
                     ExprResult::Returning(n) => {
                         // Assign to local or global
                         if is_local(var) {
@@ -136,6 +152,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n1) => {
                         for (expr_result2, local2, global2) in run_expr(exprhc, e2, local1, global1)
                         {
@@ -145,6 +164,9 @@ pub fn run_expr(
                                     let e = exprhc.equal(e1, e);
                                     results.push((ExprResult::Yielding(e), local2, global2));
                                 }
+                                ExprResult::Responding(n) => {
+                                    results.push((ExprResult::Responding(n), local2, global2));
+                                }
                                 ExprResult::Returning(n2) => {
                                     let result = if n1 == n2 { 1 } else { 0 };
                                     results.push((ExprResult::Returning(result), local2, global2));
@@ -165,6 +187,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n1) => {
                         for (expr_result2, local2, global2) in run_expr(exprhc, e2, local1, global1)
                         {
@@ -174,6 +199,9 @@ pub fn run_expr(
                                     let e = exprhc.add(e1, e);
                                     results.push((ExprResult::Yielding(e), local2, global2));
                                 }
+                                ExprResult::Responding(n) => {
+                                    results.push((ExprResult::Responding(n), local2, global2));
+                                }
                                 ExprResult::Returning(n2) => {
                                     let result = n1 + n2;
                                     results.push((ExprResult::Returning(result), local2, global2));
@@ -194,6 +222,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n1) => {
                         for (expr_result2, local2, global2) in run_expr(exprhc, e2, local1, global1)
                         {
@@ -203,6 +234,9 @@ pub fn run_expr(
                                     let e = exprhc.subtract(e1, e);
                                     results.push((ExprResult::Yielding(e), local2, global2));
                                 }
+                                ExprResult::Responding(n) => {
+                                    results.push((ExprResult::Responding(n), local2, global2));
+                                }
                                 ExprResult::Returning(n2) => {
                                     let result = n1 - n2;
                                     results.push((ExprResult::Returning(result), local2, global2));
@@ -223,6 +257,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(_) => {
                         // Ignore the result of e1 and continue with e2
                         for (expr_result2, local2, global2) in run_expr(exprhc, e2, local1, global1)
@@ -247,6 +284,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n) => {
                         if n != 0 {
                             // Condition is true, execute then branch
@@ -292,6 +332,9 @@ pub fn run_expr(
                                 global1,
                             ));
                         }
+                        ExprResult::Responding(n) => {
+                            results.push((ExprResult::Responding(n), local1, global1));
+                        }
                         ExprResult::Returning(n) => {
                             if n != 0 {
                                 // Condition is true, execute body
@@ -311,6 +354,13 @@ pub fn run_expr(
                                                 global2,
                                             ));
                                         }
+                                        ExprResult::Responding(n) => {
+                                            results.push((
+                                                ExprResult::Responding(n),
+                                                local2,
+                                                global2,
+                                            ));
+                                        }
                                         ExprResult::Returning(_) => {
                                             // Body completed without yielding, continue loop
                                             todo.push((local2, global2));
@@ -335,6 +385,22 @@ pub fn run_expr(
             // Unimplemented (do we actually need this?)
             panic!("Exit not implemented");
         }
+        Expr::Return(e) => {
+            for (expr_result, local1, global1) in run_expr(exprhc, e, local, global) {
+                match expr_result {
+                    ExprResult::Yielding(e) => {
+                        results.push((
+                            ExprResult::Yielding(exprhc.return_expr(e)),
+                            local1,
+                            global1,
+                        ));
+                    }
+                    ExprResult::Returning(n) | ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
+                }
+            }
+        }
         Expr::Unknown => {
             // Returns both 0 and 1
             results.push((ExprResult::Returning(0), local.clone(), global.clone()));
@@ -358,6 +424,9 @@ pub fn run_expr(
                     ExprResult::Yielding(e) => {
                         results.push((ExprResult::Yielding(exprhc.not(e)), local1, global1));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n) => {
                         let result = if n == 0 { 1 } else { 0 };
                         results.push((ExprResult::Returning(result), local1, global1));
@@ -375,6 +444,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n1) => {
                         if n1 == 0 {
                             // Short-circuit: If first operand is false, result is false
@@ -389,6 +461,9 @@ pub fn run_expr(
                                         // Second operand yielded
                                         results.push((ExprResult::Yielding(e), local2, global2));
                                     }
+                                    ExprResult::Responding(n) => {
+                                        results.push((ExprResult::Responding(n), local2, global2));
+                                    }
                                     ExprResult::Returning(n2) => {
                                         // Second operand returned, result is n2 != 0
                                         let result = if n2 == 0 { 0 } else { 1 };
@@ -415,6 +490,9 @@ pub fn run_expr(
                             global1,
                         ));
                     }
+                    ExprResult::Responding(n) => {
+                        results.push((ExprResult::Responding(n), local1, global1));
+                    }
                     ExprResult::Returning(n1) => {
                         if n1 != 0 {
                             // Short-circuit: If first operand is true, result is true
@@ -429,6 +507,9 @@ pub fn run_expr(
                                         // Second operand yielded
                                         results.push((ExprResult::Yielding(e), local2, global2));
                                     }
+                                    ExprResult::Responding(n) => {
+                                        results.push((ExprResult::Responding(n), local2, global2));
+                                    }
                                     ExprResult::Returning(n2) => {
                                         // Second operand returned, result is n2 != 0
                                         let result = if n2 == 0 { 0 } else { 1 };
@@ -449,6 +530,327 @@ pub fn run_expr(
     results
 }
 
+/// Whether evaluating `expr` has no effect besides computing its value: no assignments,
+/// yields, exits or returns, and no loops, which might not terminate
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Assign(_, _) | Expr::While(_, _) | Expr::Yield | Expr::Exit | Expr::Return(_) => {
+            false
+        }
+        Expr::Number(_) | Expr::Variable(_) | Expr::Unknown => true,
+        Expr::Not(e) => is_pure(e),
+        Expr::Equal(a, b)
+        | Expr::Add(a, b)
+        | Expr::Subtract(a, b)
+        | Expr::Sequence(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b) => is_pure(a) && is_pure(b),
+        Expr::If(c, t, e) => is_pure(c) && is_pure(t) && is_pure(e),
+    }
+}
+
+/// Pure and always computes the same value in the same state
+fn is_deterministic(expr: &Expr) -> bool {
+    is_pure(expr) && !mentions(expr, &|e| matches!(e, Expr::Unknown))
+}
+
+fn mentions(expr: &Expr, matches: &impl Fn(&Expr) -> bool) -> bool {
+    matches(expr)
+        || match expr {
+            Expr::Assign(_, e) | Expr::Not(e) | Expr::Return(e) => mentions(e, matches),
+            Expr::Equal(a, b)
+            | Expr::Add(a, b)
+            | Expr::Subtract(a, b)
+            | Expr::Sequence(a, b)
+            | Expr::While(a, b)
+            | Expr::And(a, b)
+            | Expr::Or(a, b) => mentions(a, matches) || mentions(b, matches),
+            Expr::If(c, t, e) => mentions(c, matches) || mentions(t, matches) || mentions(e, matches),
+            Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => false,
+        }
+}
+
+/// Number of nodes of the expression tree
+pub fn expr_size(expr: &Expr) -> usize {
+    1 + match expr {
+        Expr::Assign(_, e) | Expr::Not(e) | Expr::Return(e) => expr_size(e),
+        Expr::Equal(a, b)
+        | Expr::Add(a, b)
+        | Expr::Subtract(a, b)
+        | Expr::Sequence(a, b)
+        | Expr::While(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b) => expr_size(a) + expr_size(b),
+        Expr::If(c, t, e) => expr_size(c) + expr_size(t) + expr_size(e),
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => 0,
+    }
+}
+
+/// Simplify an expression without changing its behavior, so that the conversion
+/// produces fewer local states. Besides the constant folding and dead-branch
+/// elimination of the `ExprHc` constructors, which now also apply to subexpressions
+/// that only become constant after simplification, this
+/// - drops `+ 0` and `- 0`, and turns `e == e` into 1 for deterministic `e`
+/// - swaps the branches of `if (!c)` and drops the condition if both branches are equal
+/// - drops pure statements in sequences, whose value is discarded
+/// - merges `X := e1; X := e2` into `X := e2` if `e1` is pure and `e2` does not read
+///   `X`; no other request can observe `X` in between, since there is no yield
+pub fn simplify(exprhc: &mut ExprHc, expr: &Hc<Expr>) -> Hc<Expr> {
+    simplify_memo(exprhc, expr, &mut HashMap::default())
+}
+
+fn simplify_memo(
+    exprhc: &mut ExprHc,
+    expr: &Hc<Expr>,
+    memo: &mut HashMap<Hc<Expr>, Hc<Expr>>,
+) -> Hc<Expr> {
+    if let Some(simplified) = memo.get(expr) {
+        return simplified.clone();
+    }
+    let mut simplify = |e: &Hc<Expr>| simplify_memo(exprhc, e, memo);
+    let result = match expr.get() {
+        Expr::Assign(var, e) => {
+            let e = simplify(e);
+            exprhc.assign(var.clone(), e)
+        }
+        Expr::Equal(a, b) => {
+            let (a, b) = (simplify(a), simplify(b));
+            if a == b && is_deterministic(&a) {
+                exprhc.number(1)
+            } else {
+                exprhc.equal(a, b)
+            }
+        }
+        Expr::Add(a, b) => {
+            let (a, b) = (simplify(a), simplify(b));
+            match (a.get(), b.get()) {
+                (_, Expr::Number(0)) => a,
+                (Expr::Number(0), _) => b,
+                _ => exprhc.add(a, b),
+            }
+        }
+        Expr::Subtract(a, b) => {
+            let (a, b) = (simplify(a), simplify(b));
+            match b.get() {
+                Expr::Number(0) => a,
+                _ => exprhc.subtract(a, b),
+            }
+        }
+        Expr::Sequence(first, second) => {
+            let (first, second) = (simplify(first), simplify(second));
+            // The assignment that `second` starts with, if any
+            let next_assignment = match second.get() {
+                Expr::Sequence(next, _) => next.get(),
+                next => next,
+            };
+            let overwritten = match (first.get(), next_assignment) {
+                (Expr::Assign(x, e1), Expr::Assign(y, e2)) => {
+                    x == y
+                        && is_pure(e1)
+                        && !mentions(e2, &|e| matches!(e, Expr::Variable(v) if v == x))
+                }
+                _ => false,
+            };
+            if is_pure(&first) || overwritten {
+                second
+            } else {
+                exprhc.sequence(first, second)
+            }
+        }
+        Expr::If(c, t, e) => {
+            let (c, t, e) = (simplify(c), simplify(t), simplify(e));
+            let (c, t, e) = match c.get() {
+                Expr::Not(inner) => (inner.clone(), e, t),
+                _ => (c, t, e),
+            };
+            if t == e && is_pure(&c) {
+                t
+            } else {
+                exprhc.if_expr(c, t, e)
+            }
+        }
+        Expr::While(c, body) => {
+            let (c, body) = (simplify(c), simplify(body));
+            exprhc.while_expr(c, body)
+        }
+        Expr::Not(e) => {
+            let e = simplify(e);
+            exprhc.not(e)
+        }
+        Expr::Return(e) => {
+            let e = simplify(e);
+            exprhc.return_expr(e)
+        }
+        Expr::And(a, b) => {
+            let (a, b) = (simplify(a), simplify(b));
+            exprhc.and(a, b)
+        }
+        Expr::Or(a, b) => {
+            let (a, b) = (simplify(a), simplify(b));
+            exprhc.or(a, b)
+        }
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {
+            expr.clone()
+        }
+    };
+    // The simplified expression is where the original one was
+    if let Some(span) = exprhc.span(expr).cloned() {
+        exprhc.set_span(&result, span);
+    }
+    memo.insert(expr.clone(), result.clone());
+    result
+}
+
+/// `expr` with every `return e` replaced by `return f(e)`, e.g. to run some cleanup
+/// before a request responds early
+pub fn map_returns(
+    exprhc: &mut ExprHc,
+    expr: &Hc<Expr>,
+    f: &mut impl FnMut(&mut ExprHc, Hc<Expr>) -> Hc<Expr>,
+) -> Hc<Expr> {
+    let mut go = |e: &Hc<Expr>| map_returns(exprhc, e, f);
+    match expr.get() {
+        Expr::Return(e) => {
+            let e = go(e);
+            let value = f(exprhc, e);
+            exprhc.return_expr(value)
+        }
+        Expr::Assign(var, e) => {
+            let e = go(e);
+            exprhc.assign(var.clone(), e)
+        }
+        Expr::Not(e) => {
+            let e = go(e);
+            exprhc.not(e)
+        }
+        Expr::Equal(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.equal(a, b)
+        }
+        Expr::Add(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.add(a, b)
+        }
+        Expr::Subtract(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.subtract(a, b)
+        }
+        Expr::Sequence(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.sequence(a, b)
+        }
+        Expr::And(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.and(a, b)
+        }
+        Expr::Or(a, b) => {
+            let (a, b) = (go(a), go(b));
+            exprhc.or(a, b)
+        }
+        Expr::While(c, b) => {
+            let (c, b) = (go(c), go(b));
+            exprhc.while_expr(c, b)
+        }
+        Expr::If(c, t, e) => {
+            let (c, t, e) = (go(c), go(t), go(e));
+            exprhc.if_expr(c, t, e)
+        }
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {
+            expr.clone()
+        }
+    }
+}
+
+/// Global that tracks the progress of a session: `2i` while it waits to issue its
+/// request at position `i`, and `2i + 1` while that request runs
+pub fn session_var(session: &str) -> String {
+    format!("SESSION_{}", session)
+}
+
+const SESSION_RESULT_VAR: &str = "session_result";
+
+/// The body of the request at `position` of `session`:
+/// `while (!(S == 2i)) { yield }; S := 2i + 1; session_result := body; S := 2i + 2;
+/// session_result`, where early returns of `body` advance the session too. Testing and
+/// advancing `S` is one step, so a session runs its requests one at a time and in order;
+/// since the serial executions go through the same steps, they respect the order too.
+fn session_step(exprhc: &mut ExprHc, session: &str, position: usize, body: &Hc<Expr>) -> Hc<Expr> {
+    let var = session_var(session);
+    let waiting = 2 * position as i64;
+    let (running, done) = (waiting + 1, waiting + 2);
+    let mut advance = |exprhc: &mut ExprHc, value: Hc<Expr>| {
+        let save = exprhc.assign(SESSION_RESULT_VAR.to_string(), value);
+        let done = exprhc.number(done);
+        let advance = exprhc.assign(var.clone(), done);
+        let result = exprhc.variable(SESSION_RESULT_VAR.to_string());
+        let rest = exprhc.sequence(advance, result);
+        exprhc.sequence(save, rest)
+    };
+    let body = map_returns(exprhc, body, &mut advance);
+    let run = advance(exprhc, body);
+
+    let progress = exprhc.variable(var.clone());
+    let waiting = exprhc.number(waiting);
+    let turn = exprhc.equal(progress, waiting);
+    let not_turn = exprhc.not(turn);
+    let wait_body = exprhc.yield_expr();
+    let wait = exprhc.while_expr(not_turn, wait_body);
+    let running = exprhc.number(running);
+    let start = exprhc.assign(var, running);
+    let rest = exprhc.sequence(start, run);
+    exprhc.sequence(wait, rest)
+}
+
+/// The body of the request that models a crash: `X := x0; Y := y0; ...; 0`, with an
+/// assignment of its initial value to every global the program declares or writes,
+/// except the progress of sessions, which belongs to the clients
+fn crash_body(exprhc: &mut ExprHc, program: &Program) -> Hc<Expr> {
+    let mut globals: BTreeMap<String, i64> = program.globals.iter().cloned().collect();
+    let mut written = BTreeSet::new();
+    for request in &program.requests {
+        assigned_variables(&request.body, &mut written);
+    }
+    for var in written {
+        if !is_local(&var) {
+            globals.entry(var).or_insert(0);
+        }
+    }
+    let mut body = exprhc.number(0);
+    for (var, value) in globals.into_iter().rev() {
+        let value = exprhc.number(value);
+        let reset = exprhc.assign(var, value);
+        body = exprhc.sequence(reset, body);
+    }
+    body
+}
+
+/// Add the variables that `expr` assigns to `out`
+fn assigned_variables(expr: &Hc<Expr>, out: &mut BTreeSet<String>) {
+    match expr.get() {
+        Expr::Assign(var, e) => {
+            out.insert(var.clone());
+            assigned_variables(e, out);
+        }
+        Expr::Not(e) | Expr::Return(e) => assigned_variables(e, out),
+        Expr::Equal(a, b)
+        | Expr::Add(a, b)
+        | Expr::Subtract(a, b)
+        | Expr::Sequence(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b)
+        | Expr::While(a, b) => {
+            assigned_variables(a, out);
+            assigned_variables(b, out);
+        }
+        Expr::If(c, t, e) => {
+            assigned_variables(c, out);
+            assigned_variables(t, out);
+            assigned_variables(e, out);
+        }
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {}
+    }
+}
+
 // Request type that holds the request name
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, serde::Serialize, serde::Deserialize)]
 pub struct ExprRequest {
@@ -476,22 +878,96 @@ pub fn program_to_ns(
     exprhc: &mut ExprHc,
     program: &Program,
 ) -> NS<Global, LocalExpr, ExprRequest, i64> {
-    let mut ns = NS::new(Global::new());
+    // Globals start at their declared initial value, or 0 if undeclared
+    let initial_global = program
+        .globals
+        .iter()
+        .fold(Global::new(), |env, (name, value)| env.insert(name.clone(), *value));
+    let mut ns = NS::new(initial_global.clone());
+    for bound in &program.bounds {
+        ns.add_multiplicity_bound(
+            ExprRequest {
+                name: bound.request.clone(),
+            },
+            bound.response,
+            bound.max,
+        );
+    }
+    for name in &program.idempotent {
+        ns.add_idempotent_request(ExprRequest { name: name.clone() });
+    }
+    for request in &program.requests {
+        if let Some(doc) = &request.doc {
+            ns.add_request_doc(
+                ExprRequest {
+                    name: request.name.clone(),
+                },
+                doc.clone(),
+            );
+        }
+    }
 
     // Track seen states to avoid duplication and infinite loops
     let mut seen_packets: HashSet<LocalExpr> = HashSet::default();
     let mut seen_globals: HashSet<Global> = HashSet::default();
     let mut todo = vec![];
 
+    // Requests of sessions are only issued by their sessions, each with its own body
+    let in_session = |name: &String| {
+        program
+            .sessions
+            .iter()
+            .any(|session| session.requests.contains(name))
+    };
+    let (mut names, mut bodies): (Vec<String>, Vec<Hc<Expr>>) = program
+        .requests
+        .iter()
+        .filter(|request| !in_session(&request.name))
+        .map(|request| (request.name.clone(), request.body.clone()))
+        .unzip();
+    for session in &program.sessions {
+        for (position, name) in session.requests.iter().enumerate() {
+            let request = program.requests.iter().find(|r| r.name == *name).unwrap();
+            names.push(name.clone());
+            bodies.push(session_step(exprhc, &session.name, position, &request.body));
+        }
+    }
+
+    // A crash resets the globals in one step, while other requests stay in flight
+    if crate::ns::model_crashes_enabled() {
+        let mut name = "crash".to_string();
+        while program.requests.iter().any(|request| request.name == name) {
+            name.push('_');
+        }
+        names.push(name);
+        bodies.push(crash_body(exprhc, program));
+    }
+
+    // Simplify the request bodies first; this gives fewer local states
+    let bodies: Vec<Hc<Expr>> = if simplify_enabled() {
+        let simplified: Vec<Hc<Expr>> = bodies.iter().map(|body| simplify(exprhc, body)).collect();
+        let before: usize = bodies.iter().map(|body| expr_size(body)).sum();
+        let after: usize = simplified.iter().map(|body| expr_size(body)).sum();
+        if after < before {
+            println!(
+                "{} {} of {} expression nodes eliminated",
+                "Simplified request bodies:".cyan(),
+                before - after,
+                before
+            );
+        }
+        simplified
+    } else {
+        bodies
+    };
+
     // Process each request in the program
-    for request in &program.requests {
-        let request_name = &request.name;
-        let expr = &request.body;
+    for (request_name, expr) in names.iter().zip(&bodies) {
 
         // Starting state - add a request that transitions to initial state
         let initial_local = Local::new();
         let initial_expr = expr.clone();
-        let initial_global = Global::new();
+        let initial_global = initial_global.clone();
         let initial_local_expr = LocalExpr(initial_local.clone(), initial_expr.clone());
 
         todo.push((
@@ -508,7 +984,11 @@ pub fn program_to_ns(
             initial_local_expr.clone(),
         );
         seen_globals.insert(initial_global.clone());
-        seen_packets.insert(initial_local_expr.clone());
+        if seen_packets.insert(initial_local_expr.clone()) {
+            if let Some(span) = exprhc.location(&initial_expr) {
+                ns.add_local_source(initial_local_expr.clone(), span.clone());
+            }
+        }
     }
 
     // Process states
@@ -544,7 +1024,8 @@ pub fn program_to_ns(
                             new_globals.push(new_global.clone());
                             new_packets.push(new_local_expr.clone());
                         }
-                        ExprResult::Returning(n) => {
+                        // A `return` skips the rest of the body, so both respond with n
+                        ExprResult::Returning(n) | ExprResult::Responding(n) => {
                             // Add new global state to track if it's new
                             new_globals.push(new_global.clone());
                             let new_local_expr = LocalExpr(new_local.clone(), exprhc.number(n));
@@ -570,6 +1051,9 @@ pub fn program_to_ns(
 
                 for packet in new_packets {
                     if seen_packets.insert(packet.clone()) {
+                        if let Some(span) = exprhc.location(&packet.1) {
+                            ns.add_local_source(packet.clone(), span.clone());
+                        }
                         // Add ALL combinations of seen globals and new packet
                         for global in seen_globals.iter() {
                             todo.push((packet.1.clone(), packet.0.clone(), global.clone()));
@@ -683,4 +1167,141 @@ mod tests {
         assert_eq!(local_expr2.0.get("x"), 10);
         assert_eq!(local_expr2.0.get("y"), 20);
     }
+
+    #[test]
+    fn test_simplify() {
+        use crate::parser::{ExprHc, parse};
+
+        let mut table = ExprHc::new();
+        let simplified = |source: &str, table: &mut ExprHc| {
+            let expr = parse(source, table).unwrap();
+            simplify(table, &expr)
+        };
+        let expected = |source: &str, table: &mut ExprHc| parse(source, table).unwrap();
+
+        // Overwritten assignment, pure statement, `+ 0`
+        let s = simplified("X := 1; x; X := y + 0; yield; X", &mut table);
+        assert_eq!(s, expected("X := y; yield; X", &mut table));
+        // The second assignment reads X, so the first one stays
+        let s = simplified("X := 1; X := X + 1", &mut table);
+        assert_eq!(s, expected("X := 1; X := X + 1", &mut table));
+        // A condition that only becomes constant after simplification
+        let s = simplified("if (X == X) { yield } else { exit }", &mut table);
+        assert_eq!(s, expected("yield", &mut table));
+        // ? is not deterministic
+        let s = simplified("if (? == ?) { yield } else { exit }", &mut table);
+        assert_eq!(s, expected("if (? == ?) { yield } else { exit }", &mut table));
+        let s = simplified("if (!(X == 1)) { X := 2 } else { X := 3 }", &mut table);
+        assert_eq!(s, expected("if (X == 1) { X := 3 } else { X := 2 }", &mut table));
+
+        let before = expected("X := 1; x; X := y + 0; yield; X", &mut table);
+        let after = expected("X := y; yield; X", &mut table);
+        assert_eq!(expr_size(&before), 13);
+        assert_eq!(expr_size(&after), 6);
+    }
+
+    #[test]
+    fn test_return() {
+        use crate::parser::{ExprHc, parse_program};
+
+        let mut table = ExprHc::new();
+        let source = "#lang ser/2\nrequest take { X := 1; yield; return X + 1; X := 5 }";
+        let program = parse_program(source, &mut table).unwrap();
+        let ns = program_to_ns(&mut table, &program);
+
+        // The request responds with the returned value, and never gets to `X := 5`
+        let mut responses = ns.get_responses();
+        responses.sort();
+        assert_eq!(responses, vec![&1, &2]);
+        assert!(ns.get_global_states().iter().all(|g| g.get("X") != 5));
+    }
+
+    #[test]
+    fn test_sessions() {
+        use crate::parser::{ExprHc, parse_program};
+
+        // Whether some serial execution has `get` respond with 0
+        let get_can_see_zero = |source: &str| {
+            let mut table = ExprHc::new();
+            let program = parse_program(source, &mut table).unwrap();
+            let ns = program_to_ns(&mut table, &program);
+            ns.serialized_automaton()
+                .iter()
+                .any(|(_, req, resp, _)| req.name == "get" && *resp == 0)
+        };
+        let requests = "#lang ser/2
+            request set { yield; X := 1; return 7 }
+            request get { X }";
+        assert!(get_can_see_zero(requests));
+        // In the session, `get` only starts after `set` responded, even with its early return
+        let session = format!("{}\nsession client {{ set; get }}", requests);
+        assert!(!get_can_see_zero(&session));
+        // In the other order, `get` runs first
+        let session = format!("{}\nsession client {{ get; set }}", requests);
+        assert!(get_can_see_zero(&session));
+    }
+
+    #[test]
+    fn test_model_crashes() {
+        use crate::parser::{ExprHc, parse_program};
+
+        let source = "global X := 2;
+            request set { X := 1 }
+            request get { X }";
+        let load = |crashes: bool| {
+            let mut table = ExprHc::new();
+            let program = parse_program(source, &mut table).unwrap();
+            let options = crate::options::AnalysisOptions {
+                model_crashes: crashes,
+                ..crate::options::AnalysisOptions::default()
+            };
+            crate::options::with_options(options, || program_to_ns(&mut table, &program))
+        };
+
+        let ns = load(false);
+        assert!(
+            ns.get_requests()
+                .iter()
+                .all(|request| request.name != "crash")
+        );
+
+        // The crash resets X to its declared value from every global state
+        let ns = load(true);
+        let crashes: Vec<_> = ns
+            .serialized_automaton()
+            .into_iter()
+            .filter(|(_, req, _, _)| req.name == "crash")
+            .collect();
+        assert!(!crashes.is_empty());
+        for (from, _, _, to) in &crashes {
+            assert_eq!(to.get("X"), 2);
+            assert!(from.get("X") == 1 || from.get("X") == 2);
+        }
+        assert!(crashes.iter().any(|(from, _, _, _)| from.get("X") == 1));
+    }
+
+    #[test]
+    fn test_local_sources() {
+        use crate::parser::{ExprHc, parse_program};
+
+        let mut table = ExprHc::new();
+        let source = "request flip {\n    x := X;\n    yield;\n    X := 1 - x\n}";
+        let program = parse_program(source, &mut table).unwrap();
+        let ns = program_to_ns(&mut table, &program);
+
+        // The request starts at its first statement and continues after the yield
+        let initial = &ns.requests[0].1;
+        assert_eq!(
+            ns.local_source(initial).map(|span| span.to_string()),
+            Some("line 2 `x := X`".to_string())
+        );
+        let mut spans: Vec<String> = ns
+            .local_sources
+            .iter()
+            .map(|(_, span)| span.to_string())
+            .collect();
+        spans.sort();
+        spans.dedup();
+        assert_eq!(spans, vec!["line 2 `x := X`", "line 4 `X := 1 - x`"]);
+    }
 }
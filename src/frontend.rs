@@ -0,0 +1,263 @@
+//! Input languages.
+//!
+//! A front end reads one kind of input file and produces a network system. The driver
+//! in `main.rs` asks the registered front ends, in turn, whether they accept a file, and
+//! analyzes whatever the first one that does loads. A new input language is added by
+//! implementing `FrontEnd` and calling `register`; the driver does not change.
+//!
+//! Front ends produce one of the network system types the rest of the tool works
+//! with (`LoadedNS`): string-labelled networks, as in `.json` files, or networks lowered
+//! from a `Program`, as in `.ser` files. A language with its own request bodies is
+//! lowered to a `Program`; one that describes states and transitions directly produces
+//! strings.
+
+use crate::expr_to_ns::{self, ExprRequest, Global, LocalExpr};
+use crate::ns::{NS, NSFormat};
+use crate::parser::{Expr, ExprHc, Program, Request, parse, parse_program};
+use colored::*;
+use hash_cons::Hc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A network system loaded by a front end
+pub enum LoadedNS {
+    /// States, requests and responses are plain strings
+    Strings(NS<String, String, String, String>),
+    /// Lowered from a `Program` by `expr_to_ns::program_to_ns`
+    Program(NS<Global, LocalExpr, ExprRequest, i64>),
+}
+
+/// Reader for one input language
+pub trait FrontEnd: Send + Sync {
+    /// Name of the language, used in messages
+    fn name(&self) -> &'static str;
+
+    /// File extensions (without the dot) of the language
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether this front end reads the given file. By default, by its extension.
+    fn accepts(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions().contains(&ext))
+    }
+
+    fn load(&self, path: &Path) -> Result<LoadedNS, String>;
+
+    /// The program that `load` lowers to a network system, for front ends that go
+    /// through a `Program`. Analyses that change the program and check it again, like
+    /// `repair`, need it. `None` for languages without request bodies.
+    fn load_program(&self, _path: &Path, _table: &mut ExprHc) -> Option<Result<Program, String>> {
+        None
+    }
+}
+
+/// Network systems written as JSON, YAML or TOML
+pub struct NSFileFrontEnd;
+
+impl FrontEnd for NSFileFrontEnd {
+    fn name(&self) -> &'static str {
+        "NS"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json", "yaml", "yml", "toml"]
+    }
+
+    fn load(&self, path: &Path) -> Result<LoadedNS, String> {
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(NSFormat::from_extension)
+            .unwrap_or(NSFormat::Json);
+        let content = read(path)?;
+        let mut ns = NS::from_str_in_format(&content, format)
+            .map_err(|err| format!("Error parsing {} as Network System: {}", format.name(), err))?;
+        if crate::ns::model_crashes_enabled() {
+            add_crash_request(&mut ns);
+        }
+        Ok(LoadedNS::Strings(ns))
+    }
+}
+
+/// Add a `crash` request (see `NS::add_crash_request`), named so that it does not
+/// clash with the requests and local states of `ns`
+fn add_crash_request(ns: &mut NS<String, String, String, String>) {
+    let mut name = "crash".to_string();
+    let done = |name: &str| format!("{}_done", name);
+    let taken = |ns: &NS<String, String, String, String>, name: &str| {
+        let locals = ns.get_local_states();
+        ns.get_requests().iter().any(|request| *request == name)
+            || locals
+                .iter()
+                .any(|local| *local == name || **local == done(name))
+    };
+    while taken(ns, &name) {
+        name.push('_');
+    }
+    ns.add_crash_request(
+        name.clone(),
+        name.clone(),
+        done(&name),
+        "crashed".to_string(),
+    );
+}
+
+/// Programs in the `.ser` language
+pub struct SerFrontEnd;
+
+impl FrontEnd for SerFrontEnd {
+    fn name(&self) -> &'static str {
+        "Ser"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ser"]
+    }
+
+    fn load(&self, path: &Path) -> Result<LoadedNS, String> {
+        let content = read(path)?;
+        let mut table = ExprHc::new();
+        // A file is a program with named requests, or a single expression
+        let program = match parse_program(&content, &mut table) {
+            Ok(program) => {
+                println!(
+                    "{} {} requests",
+                    "Parsed program with".blue().bold(),
+                    program.requests.len()
+                );
+                program
+            }
+            Err(_) => {
+                let expr = parse(&content, &mut table)
+                    .map_err(|err| format!("Error parsing SER file: {}", err))?;
+                println!("{} {}", "Parsed expression:".blue().bold(), expr);
+                single_request(expr)
+            }
+        };
+        println!(
+            "{}",
+            "Converting program to Network System...".cyan().bold()
+        );
+        Ok(LoadedNS::Program(expr_to_ns::program_to_ns(
+            &mut table, &program,
+        )))
+    }
+
+    fn load_program(&self, path: &Path, table: &mut ExprHc) -> Option<Result<Program, String>> {
+        Some(read(path).and_then(|content| parse_ser(&content, table)))
+    }
+}
+
+/// Parse the text of a `.ser` file: a program with named requests, or a single
+/// expression
+pub fn parse_ser(content: &str, table: &mut ExprHc) -> Result<Program, String> {
+    match parse_program(content, table) {
+        Ok(program) => Ok(program),
+        Err(_) => parse(content, table)
+            .map(single_request)
+            .map_err(|err| format!("Error parsing SER file: {}", err)),
+    }
+}
+
+/// A `.ser` file with a single expression is a program with one request
+fn single_request(body: Hc<Expr>) -> Program {
+    Program {
+        requests: vec![Request {
+            name: "request".to_string(),
+            body,
+            doc: None,
+        }],
+        globals: vec![],
+        bounds: vec![],
+        sessions: vec![],
+        idempotent: vec![],
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("Error reading file: {}", err))
+}
+
+lazy_static::lazy_static! {
+    static ref FRONT_ENDS: Mutex<Vec<Arc<dyn FrontEnd>>> = Mutex::new(vec![
+        Arc::new(NSFileFrontEnd) as Arc<dyn FrontEnd>,
+        Arc::new(SerFrontEnd),
+        Arc::new(crate::pluscal::PlusCalFrontEnd),
+        Arc::new(crate::proto::ProtoFrontEnd),
+    ]);
+}
+
+/// Add a front end. It is asked before the ones registered earlier, so it can take
+/// over files another front end would accept.
+pub fn register(front_end: Arc<dyn FrontEnd>) {
+    FRONT_ENDS.lock().unwrap().insert(0, front_end);
+}
+
+/// The front end that reads the given file, if any
+pub fn find(path: &Path) -> Option<Arc<dyn FrontEnd>> {
+    FRONT_ENDS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|front_end| front_end.accepts(path))
+        .cloned()
+}
+
+/// Extensions of all registered front ends, in the order they are asked
+pub fn supported_extensions() -> Vec<&'static str> {
+    FRONT_ENDS
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|front_end| front_end.extensions().iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubFrontEnd;
+
+    impl FrontEnd for StubFrontEnd {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["nsu"]
+        }
+
+        fn load(&self, _path: &Path) -> Result<LoadedNS, String> {
+            Err("not implemented".to_string())
+        }
+    }
+
+    #[test]
+    fn test_find_front_end() {
+        assert_eq!(find(Path::new("a/b.yml")).unwrap().name(), "NS");
+        assert_eq!(find(Path::new("b.ser")).unwrap().name(), "Ser");
+        assert!(find(Path::new("b.txt")).is_none());
+        assert!(find(Path::new("b.nsu")).is_none());
+
+        register(Arc::new(StubFrontEnd));
+        assert_eq!(find(Path::new("b.nsu")).unwrap().name(), "stub");
+        assert!(supported_extensions().starts_with(&["nsu"]));
+        assert!(
+            supported_extensions()
+                .ends_with(&["json", "yaml", "yml", "toml", "ser", "tla", "pcal", "proto"])
+        );
+    }
+
+    #[test]
+    fn test_load_ser() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flag.ser");
+        std::fs::write(&path, "request set { X := 1 }").unwrap();
+        match find(&path).unwrap().load(&path).unwrap() {
+            LoadedNS::Program(ns) => assert_eq!(ns.requests.len(), 1),
+            LoadedNS::Strings(_) => panic!("a .ser file is a program"),
+        }
+    }
+}
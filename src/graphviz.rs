@@ -1,19 +1,26 @@
 use std::fs::{self, create_dir_all};
 use std::path::Path;
+#[cfg(feature = "viz")]
 use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
-
-/// Global flag for visualization generation
-pub static VIZ_GENERATION_ENABLED: AtomicBool = AtomicBool::new(true);
 
 /// Set whether visualization generation is enabled
 pub fn set_viz_enabled(enabled: bool) {
-    VIZ_GENERATION_ENABLED.store(enabled, Ordering::SeqCst);
+    crate::options::update(|options| options.viz = enabled);
 }
 
 /// Check whether visualization generation is enabled
 pub fn viz_enabled() -> bool {
-    VIZ_GENERATION_ENABLED.load(Ordering::SeqCst)
+    crate::options::get(|options| options.viz)
+}
+
+/// Set whether the serialized automaton is emitted as its own DOT graph
+pub fn set_serialized_automaton_dot(enabled: bool) {
+    crate::options::update(|options| options.serialized_automaton_dot = enabled);
+}
+
+/// Check whether the serialized automaton is emitted as its own DOT graph
+pub fn serialized_automaton_dot() -> bool {
+    crate::options::get(|options| options.serialized_automaton_dot)
 }
 
 /// Save GraphViz DOT files to disk and generate visualizations
@@ -58,117 +65,129 @@ pub fn save_graphviz(
     match fs::write(&dot_path, dot_content) {
         Ok(_) => {
             generated_files.push(dot_path.to_string_lossy().to_string());
+            render(
+                &dot_path,
+                &png_path,
+                &svg_path,
+                &pdf_path,
+                &mut generated_files,
+            );
+        }
+        Err(e) => return Err(format!("Failed to write DOT file: {}", e)),
+    }
 
-            // Generate PNG
-            match Command::new("dot")
-                .args(["-Tpng", "-o", &png_path.to_string_lossy()])
-                .arg(&dot_path)
-                .output()
-            {
-                Ok(output) => {
-                    // Check if the command executed successfully (exit code 0)
-                    if output.status.success() {
-                        // Verify the file was created
-                        if png_path.exists() {
-                            generated_files.push(png_path.to_string_lossy().to_string());
-                        } else {
-                            println!("Warning: dot command executed but PNG file was not created");
-                            if !output.stderr.is_empty() {
-                                println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
-                            }
-                        }
-                    } else {
-                        // Command failed with non-zero exit code
-                        println!(
-                            "Warning: GraphViz dot command failed with exit code {:?}: {}",
-                            output.status.code(),
-                            String::from_utf8_lossy(&output.stderr)
-                        );
-                    }
-                }
-                Err(e) => {
-                    println!(
-                        "Warning: Failed to generate visualization PNG: {}. \
-                        Is GraphViz installed? Try installing with 'brew install graphviz' on macOS or \
-                        'apt-get install graphviz' on Linux.",
-                        e
-                    );
-                }
+    // Try to open the PNG files for viewing (platform-specific)
+    if open_files && cfg!(feature = "viz") {
+        // Try to open the PNG if it exists
+        if png_path.exists() {
+            if let Err(e) = crate::utils::process::open_in_viewer(&png_path) {
+                println!("Warning: Could not open PNG: {}", e);
             }
+        } else {
+            println!("Warning: PNG file does not exist: {}", png_path.display());
+        }
+    }
 
-            // Generate SVG (better for web viewing)
-            match Command::new("dot")
-                .args(["-Tsvg", "-o", &svg_path.to_string_lossy()])
-                .arg(&dot_path)
-                .output()
-            {
-                Ok(output) => {
-                    if output.status.success() && svg_path.exists() {
-                        generated_files.push(svg_path.to_string_lossy().to_string());
-                    } else if !output.status.success() {
-                        println!(
-                            "Warning: Failed to generate SVG: {}",
-                            String::from_utf8_lossy(&output.stderr)
-                        );
-                    }
-                }
-                Err(e) => {
-                    println!("Warning: Failed to execute dot for SVG: {}", e);
-                }
-            }
+    Ok(generated_files)
+}
 
-            // Generate PDF (better for printing)
-            match Command::new("dot")
-                .args(["-Tpdf", "-o", &pdf_path.to_string_lossy()])
-                .arg(&dot_path)
-                .output()
-            {
-                Ok(output) => {
-                    if output.status.success() && pdf_path.exists() {
-                        generated_files.push(pdf_path.to_string_lossy().to_string());
-                    } else if !output.status.success() {
-                        println!(
-                            "Warning: Failed to generate PDF: {}",
-                            String::from_utf8_lossy(&output.stderr)
-                        );
+/// Render a DOT file as PNG, SVG and PDF with GraphViz, adding the files that were
+/// generated to `generated_files`
+#[cfg(feature = "viz")]
+fn render(
+    dot_path: &Path,
+    png_path: &Path,
+    svg_path: &Path,
+    pdf_path: &Path,
+    generated_files: &mut Vec<String>,
+) {
+    // Generate PNG
+    match Command::new("dot")
+        .args(["-Tpng", "-o", &png_path.to_string_lossy()])
+        .arg(dot_path)
+        .output()
+    {
+        Ok(output) => {
+            // Check if the command executed successfully (exit code 0)
+            if output.status.success() {
+                // Verify the file was created
+                if png_path.exists() {
+                    generated_files.push(png_path.to_string_lossy().to_string());
+                } else {
+                    println!("Warning: dot command executed but PNG file was not created");
+                    if !output.stderr.is_empty() {
+                        println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
                     }
                 }
-                Err(e) => {
-                    println!("Warning: Failed to execute dot for PDF: {}", e);
-                }
+            } else {
+                // Command failed with non-zero exit code
+                println!(
+                    "Warning: GraphViz dot command failed with exit code {:?}: {}",
+                    output.status.code(),
+                    String::from_utf8_lossy(&output.stderr)
+                );
             }
         }
-        Err(e) => return Err(format!("Failed to write DOT file: {}", e)),
+        Err(e) => {
+            println!(
+                "Warning: Failed to generate visualization PNG: {}. \
+                Is GraphViz installed? Try installing with 'brew install graphviz' on macOS or \
+                'apt-get install graphviz' on Linux.",
+                e
+            );
+        }
     }
 
-    // Try to open the PNG files for viewing (platform-specific)
-    if open_files {
-        // Try to open the PNG if it exists
-        if png_path.exists() {
-            #[cfg(target_os = "macos")]
-            match Command::new("open").arg(&png_path).spawn() {
-                Ok(_) => {}
-                Err(e) => println!("Warning: Could not open PNG: {}", e),
-            }
-
-            #[cfg(target_os = "linux")]
-            match Command::new("xdg-open").arg(&png_path).spawn() {
-                Ok(_) => {}
-                Err(e) => println!("Warning: Could not open PNG: {}", e),
+    // Generate SVG (better for web viewing)
+    match Command::new("dot")
+        .args(["-Tsvg", "-o", &svg_path.to_string_lossy()])
+        .arg(dot_path)
+        .output()
+    {
+        Ok(output) => {
+            if output.status.success() && svg_path.exists() {
+                generated_files.push(svg_path.to_string_lossy().to_string());
+            } else if !output.status.success() {
+                println!(
+                    "Warning: Failed to generate SVG: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
             }
+        }
+        Err(e) => {
+            println!("Warning: Failed to execute dot for SVG: {}", e);
+        }
+    }
 
-            #[cfg(target_os = "windows")]
-            match Command::new("cmd")
-                .args(["/C", "start", &png_path.to_string_lossy()])
-                .spawn()
-            {
-                Ok(_) => {}
-                Err(e) => println!("Warning: Could not open PNG: {}", e),
+    // Generate PDF (better for printing)
+    match Command::new("dot")
+        .args(["-Tpdf", "-o", &pdf_path.to_string_lossy()])
+        .arg(dot_path)
+        .output()
+    {
+        Ok(output) => {
+            if output.status.success() && pdf_path.exists() {
+                generated_files.push(pdf_path.to_string_lossy().to_string());
+            } else if !output.status.success() {
+                println!(
+                    "Warning: Failed to generate PDF: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
             }
-        } else {
-            println!("Warning: PNG file does not exist: {}", png_path.display());
+        }
+        Err(e) => {
+            println!("Warning: Failed to execute dot for PDF: {}", e);
         }
     }
+}
 
-    Ok(generated_files)
+/// Without the `viz` feature only the DOT file is written
+#[cfg(not(feature = "viz"))]
+fn render(
+    _dot_path: &Path,
+    _png_path: &Path,
+    _svg_path: &Path,
+    _pdf_path: &Path,
+    _generated_files: &mut Vec<String>,
+) {
 }
@@ -0,0 +1,108 @@
+//! Reversible encoding of identifiers for external formats.
+//!
+//! Names of requests, states and places end up in Graphviz files, SMPT `.net` files
+//! and XML queries, LP files and SMT-LIB proofs. Replacing every unsupported character
+//! by `_` (as `utils::string::sanitize` does) is lossy: `a-b` and `a.b` collide, and
+//! names that come back from a tool cannot be mapped back. `encode` keeps ASCII letters
+//! and digits and writes every other character, including `_` itself, as `_<hex>_`
+//! (its code point in lowercase hex), so `decode(encode(s)) == s` for every `s`.
+//!
+//! ISL strings built by `presburger` name their dimensions positionally (`p0`, `p1`,
+//! ...), so user names never reach ISL and need no encoding there.
+
+/// Format an identifier is written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Graphviz,
+    /// SMPT `.net` files and XML queries
+    PetriNet,
+    /// CPLEX LP files
+    Lp,
+    SmtLib,
+}
+
+impl Target {
+    /// Whether identifiers may start with a digit
+    fn leading_digit_allowed(self) -> bool {
+        matches!(self, Target::PetriNet)
+    }
+}
+
+/// Encoding of the empty string, which is not a valid identifier in any target
+const EMPTY: &str = "_empty_";
+
+/// Encode `s` as an identifier that is valid in `target`
+pub fn encode(s: &str, target: Target) -> String {
+    if s.is_empty() {
+        return EMPTY.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        let leading_digit = i == 0 && c.is_ascii_digit() && !target.leading_digit_allowed();
+        if c.is_ascii_alphanumeric() && !leading_digit {
+            out.push(c);
+        } else {
+            out.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    out
+}
+
+/// Invert `encode`. Returns `None` if `s` is not the encoding of any string.
+pub fn decode(s: &str) -> Option<String> {
+    if s == EMPTY {
+        return Some(String::new());
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '_' {
+            let hex: String = chars.by_ref().take_while(|&c| c != '_').collect();
+            let code = u32::from_str_radix(&hex, 16).ok()?;
+            out.push(char::from_u32(code)?);
+        } else if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let names = [
+            "a-b",
+            "a.b",
+            "a_b",
+            "a__b",
+            "Right((x, 1))",
+            "1st",
+            "ünïcode",
+            "",
+            "_",
+        ];
+        for target in [
+            Target::Graphviz,
+            Target::PetriNet,
+            Target::Lp,
+            Target::SmtLib,
+        ] {
+            let encoded: Vec<String> = names.iter().map(|n| encode(n, target)).collect();
+            for (name, enc) in names.iter().zip(&encoded) {
+                assert!(enc.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+                assert_eq!(decode(enc).as_deref(), Some(*name));
+            }
+            // Names that sanitize identically stay distinct
+            assert_ne!(encoded[0], encoded[1]);
+            assert_ne!(encoded[0], encoded[2]);
+        }
+        assert_eq!(encode("1st", Target::PetriNet), "1st");
+        assert_eq!(encode("1st", Target::SmtLib), "_31_st");
+        assert_eq!(decode("a_zz_"), None);
+    }
+}
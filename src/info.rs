@@ -0,0 +1,305 @@
+//! `ser info <file>`: structural statistics of a model, without analysis.
+//!
+//! Builds the network system and the Petri net of the serializability problem and
+//! reports their sizes, the strongly connected components of the NS state graph (the
+//! `(local, global)` states linked by transitions) and a rough interleaving factor, so
+//! users can tell quickly how hard a model will be before running SMPT.
+//!
+//! The interleaving factor is the number of ways two instances of the longest request
+//! can interleave their internal steps, `C(2d, d)` for a request that takes at most `d`
+//! steps. It ignores the global state, so it overestimates `d`, and it is unbounded
+//! when a request can loop.
+
+use crate::deterministic_map::{HashMap, HashSet};
+use crate::ns::NS;
+use colored::*;
+use std::fmt::Display;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub global_states: usize,
+    pub local_states: usize,
+    pub requests: usize,
+    pub responses: usize,
+    pub transitions: usize,
+    pub petri_places: usize,
+    pub petri_transitions: usize,
+    /// Strongly connected components of the `(local, global)` state graph
+    pub sccs: usize,
+    /// Components that contain a cycle, i.e. loops of the model
+    pub cyclic_sccs: usize,
+    /// Most internal steps one request can take; `None` if a request can loop
+    pub max_request_steps: Option<usize>,
+    /// Interleavings of two instances of the longest request; `None` if unbounded or
+    /// too large to count
+    pub interleaving_factor: Option<u128>,
+}
+
+/// Component number of every node, numbered in reverse topological order (a node's
+/// successors are in the same or a lower-numbered component). Iterative Tarjan, so
+/// long chains of states do not overflow the stack.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<usize> {
+    const UNVISITED: usize = usize::MAX;
+    let n = successors.len();
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut component = vec![UNVISITED; n];
+    let mut next_index = 0;
+    let mut components = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // (node, number of successors visited so far)
+        let mut work = vec![(root, 0)];
+        while let Some((v, child)) = work.last().copied() {
+            if child == 0 && index[v] == UNVISITED {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+            if let Some(&w) = successors[v].get(child) {
+                work.last_mut().unwrap().1 += 1;
+                if index[w] == UNVISITED {
+                    work.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+            } else {
+                work.pop();
+                if let Some(&(u, _)) = work.last() {
+                    lowlink[u] = lowlink[u].min(lowlink[v]);
+                }
+                if lowlink[v] == index[v] {
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component[w] = components;
+                        if w == v {
+                            break;
+                        }
+                    }
+                    components += 1;
+                }
+            }
+        }
+    }
+    component
+}
+
+/// Whether each component contains a cycle (more than one node, or a self-loop)
+fn cyclic_components(successors: &[Vec<usize>], component: &[usize]) -> Vec<bool> {
+    let count = component.iter().map(|c| c + 1).max().unwrap_or(0);
+    let mut sizes = vec![0; count];
+    let mut cyclic = vec![false; count];
+    for (v, &c) in component.iter().enumerate() {
+        sizes[c] += 1;
+        cyclic[c] |= successors[v].contains(&v);
+    }
+    for (c, size) in sizes.into_iter().enumerate() {
+        cyclic[c] |= size > 1;
+    }
+    cyclic
+}
+
+/// Graph on the values of `nodes`, with the given edges
+fn graph<T: Clone + Eq + Hash>(
+    nodes: impl IntoIterator<Item = T>,
+    edges: impl IntoIterator<Item = (T, T)>,
+) -> (HashMap<T, usize>, Vec<Vec<usize>>) {
+    let mut ids: HashMap<T, usize> = HashMap::default();
+    let mut successors: Vec<Vec<usize>> = Vec::new();
+    let mut id = |node: T, successors: &mut Vec<Vec<usize>>| {
+        let next = ids.len();
+        let id = *ids.entry(node).or_insert(next);
+        if id == successors.len() {
+            successors.push(Vec::new());
+        }
+        id
+    };
+    for node in nodes {
+        id(node, &mut successors);
+    }
+    for (from, to) in edges {
+        let from = id(from, &mut successors);
+        let to = id(to, &mut successors);
+        if !successors[from].contains(&to) {
+            successors[from].push(to);
+        }
+    }
+    (ids, successors)
+}
+
+/// `C(2d, d)`, or `None` if it does not fit in a `u128`
+fn interleavings(d: usize) -> Option<u128> {
+    // C(d + i, i) from C(d + i - 1, i - 1); every intermediate value is an integer
+    let mut result: u128 = 1;
+    for i in 1..=d as u128 {
+        result = result.checked_mul(d as u128 + i)? / i;
+    }
+    Some(result)
+}
+
+/// Most internal steps a request can take from each of the given initial local states,
+/// following transitions regardless of the global state; `None` if one can loop
+fn max_request_steps<G, L>(transitions: &[(L, G, L, G)], initial: &[&L]) -> Option<usize>
+where
+    L: Clone + Eq + Hash,
+{
+    let (ids, successors) = graph(
+        initial.iter().map(|l| (*l).clone()),
+        transitions
+            .iter()
+            .map(|(from, _, to, _)| (from.clone(), to.clone())),
+    );
+    let component = strongly_connected_components(&successors);
+    let cyclic = cyclic_components(&successors, &component);
+
+    // Nodes reachable from an initial local state
+    let mut reachable = vec![false; successors.len()];
+    let mut todo: Vec<usize> = initial.iter().map(|l| ids[*l]).collect();
+    while let Some(v) = todo.pop() {
+        if !std::mem::replace(&mut reachable[v], true) {
+            todo.extend(&successors[v]);
+        }
+    }
+    if (0..successors.len()).any(|v| reachable[v] && cyclic[component[v]]) {
+        return None;
+    }
+
+    // Longest paths, successors first
+    let mut order: Vec<usize> = (0..successors.len()).filter(|&v| reachable[v]).collect();
+    order.sort_by_key(|&v| component[v]);
+    let mut longest = vec![0; successors.len()];
+    for v in order {
+        longest[v] = successors[v]
+            .iter()
+            .map(|&w| longest[w] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+    initial.iter().map(|l| longest[ids[*l]]).max().or(Some(0))
+}
+
+/// Structural statistics of a network system and its Petri net
+pub fn model_info<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>) -> ModelInfo
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let petri = crate::ns_to_petri::ns_to_petri_with_requests(ns);
+
+    let (_, successors) = graph(
+        std::iter::empty(),
+        ns.transitions
+            .iter()
+            .map(|(l, g, l2, g2)| ((l.clone(), g.clone()), (l2.clone(), g2.clone()))),
+    );
+    let component = strongly_connected_components(&successors);
+    let cyclic = cyclic_components(&successors, &component);
+
+    let initial: Vec<&L> = ns
+        .requests
+        .iter()
+        .chain(&ns.initial_requests)
+        .map(|(_, l)| l)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let max_request_steps = max_request_steps(&ns.transitions, &initial);
+
+    ModelInfo {
+        global_states: ns.get_global_states().len(),
+        local_states: ns.get_local_states().len(),
+        requests: ns.get_requests().len(),
+        responses: ns.get_responses().len(),
+        transitions: ns.transitions.len(),
+        petri_places: petri.get_places().len(),
+        petri_transitions: petri.get_transitions().len(),
+        sccs: cyclic.len(),
+        cyclic_sccs: cyclic.iter().filter(|c| **c).count(),
+        max_request_steps,
+        interleaving_factor: max_request_steps.and_then(interleavings),
+    }
+}
+
+/// Print the statistics of `ser info`
+pub fn print_info<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>)
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let info = model_info(ns);
+    println!("{}", "Model statistics".bold());
+    println!("  NS global states:          {}", info.global_states);
+    println!("  NS local states:           {}", info.local_states);
+    println!("  NS requests:               {}", info.requests);
+    println!("  NS responses:              {}", info.responses);
+    println!("  NS transitions:            {}", info.transitions);
+    println!("  Petri net places:          {}", info.petri_places);
+    println!("  Petri net transitions:     {}", info.petri_transitions);
+    println!(
+        "  NS graph SCCs:             {} ({} with cycles)",
+        info.sccs, info.cyclic_sccs
+    );
+    match info.max_request_steps {
+        Some(steps) => println!("  Max steps per request:     {}", steps),
+        None => println!("  Max steps per request:     unbounded (requests can loop)"),
+    }
+    match (info.max_request_steps, info.interleaving_factor) {
+        (Some(_), Some(factor)) => println!("  Interleaving factor:       {}", factor),
+        (Some(_), None) => println!("  Interleaving factor:       more than 10^38"),
+        (None, _) => println!("  Interleaving factor:       unbounded"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_info() {
+        let s = |x: &str| x.to_string();
+        let mut ns: NS<String, String, String, String> = NS::new(s("G0"));
+        // `inc` takes two steps, `spin` loops between two states
+        ns.add_request(s("inc"), s("a0"));
+        ns.add_transition(s("a0"), s("G0"), s("a1"), s("G1"));
+        ns.add_transition(s("a1"), s("G1"), s("a2"), s("G0"));
+        ns.add_response(s("a2"), s("ok"));
+        ns.add_request(s("spin"), s("b0"));
+        ns.add_transition(s("b0"), s("G0"), s("b1"), s("G0"));
+        ns.add_transition(s("b1"), s("G0"), s("b0"), s("G0"));
+        ns.add_response(s("b1"), s("done"));
+
+        let info = model_info(&ns);
+        assert_eq!(info.requests, 2);
+        assert_eq!(info.transitions, 4);
+        assert_eq!(info.sccs, 4);
+        assert_eq!(info.cyclic_sccs, 1);
+        assert_eq!(info.max_request_steps, None);
+        assert_eq!(info.interleaving_factor, None);
+
+        let inc = ns.restrict_requests(|req| req == "inc");
+        let info = model_info(&inc);
+        assert_eq!(info.max_request_steps, Some(2));
+        // Two requests of two steps: 4 choose 2
+        assert_eq!(info.interleaving_factor, Some(6));
+    }
+
+    #[test]
+    fn test_interleavings() {
+        assert_eq!(interleavings(0), Some(1));
+        assert_eq!(interleavings(3), Some(20));
+        assert_eq!(interleavings(10), Some(184_756));
+        assert_eq!(interleavings(1000), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! Analysis of a system from several initial global states (`--all-initial-states`,
+//! `--initial-states`).
+//!
+//! A system is often meant to be serializable from every global state it can be
+//! deployed in, not only from the one in its input file. This repeats the analysis for
+//! each global state of the system, or for a list of them given on the command line,
+//! and reports a verdict per initial state. The Petri net with requests is the same
+//! for all of them except for its initial marking, so it is built only once.
+
+#[cfg(feature = "isl")]
+use crate::error::SerError;
+use crate::ns::NS;
+use crate::ns_decision::NSDecision;
+#[cfg(feature = "isl")]
+use crate::utils::string::sanitize;
+use colored::*;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Which initial global states to analyze
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitialStates {
+    /// Every global state of the system
+    All,
+    /// The global states with these names
+    List(Vec<String>),
+}
+
+/// Repeat the analysis for the given initial global states (called from `main.rs`)
+pub fn set_initial_states(states: Option<InitialStates>) {
+    crate::options::update(|options| options.initial_states = states);
+}
+
+pub fn initial_states() -> Option<InitialStates> {
+    crate::options::get(|options| options.initial_states.clone())
+}
+
+/// File in the output directory that holds the verdicts
+pub const INITIAL_STATES_FILE: &str = "initial_states.json";
+
+/// Outcome of the analysis from one initial global state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Verdict {
+    Serializable,
+    NotSerializable,
+    Timeout,
+    Unknown,
+}
+
+impl Verdict {
+    pub fn of<G, L, Req, Resp>(decision: &NSDecision<G, L, Req, Resp>) -> Self
+    where
+        G: Eq + Hash,
+        L: Eq + Hash,
+        Req: Eq + Hash,
+        Resp: Eq + Hash,
+    {
+        match decision {
+            NSDecision::Serializable { .. } => Verdict::Serializable,
+            NSDecision::NotSerializable { .. } => Verdict::NotSerializable,
+            NSDecision::Timeout { .. } => Verdict::Timeout,
+            NSDecision::Unknown { .. } => Verdict::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct InitialStateVerdict {
+    pub initial_global: String,
+    pub verdict: Verdict,
+    /// Output directory of the analysis from this initial state
+    pub out_dir: String,
+}
+
+/// The global states of `ns` selected by `states`, in order. Names of the list that
+/// are not global states of `ns` are an error.
+pub fn select_initial_globals<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    states: &InitialStates,
+) -> Result<Vec<G>, String>
+where
+    G: Clone + Ord + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let mut globals: Vec<G> = ns.get_global_states().into_iter().cloned().collect();
+    globals.sort();
+    globals.dedup();
+    match states {
+        InitialStates::All => Ok(globals),
+        InitialStates::List(names) => names
+            .iter()
+            .map(|name| {
+                globals
+                    .iter()
+                    .find(|global| global.to_string() == *name)
+                    .cloned()
+                    .ok_or_else(|| format!("'{}' is not a global state of the system", name))
+            })
+            .collect(),
+    }
+}
+
+/// Analyze `ns` from each selected initial global state, print the verdicts and save
+/// them to `<out_dir>/initial_states.json`. The analyses write to
+/// `<out_dir>/initial_states/<i>_<state>`.
+#[cfg(feature = "isl")]
+pub fn analyze_initial_states<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    states: &InitialStates,
+    out_dir: &str,
+) -> Result<Vec<InitialStateVerdict>, SerError>
+where
+    G: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    L: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Req: Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Resp:
+        Clone + Ord + Hash + Display + Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
+    let globals = select_initial_globals(ns, states).map_err(SerError::Usage)?;
+    let dirs: Vec<String> = globals
+        .iter()
+        .enumerate()
+        .map(|(i, global)| {
+            crate::utils::file::join(
+                out_dir,
+                &format!("initial_states/{}_{}", i, sanitize(&global.to_string())),
+            )
+        })
+        .collect();
+    for dir in &dirs {
+        crate::utils::file::ensure_dir_exists(dir)
+            .map_err(|err| SerError::IoError(format!("Failed to create {}: {}", dir, err)))?;
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("Analyzing {} initial global states...", globals.len())
+            .blue()
+            .bold()
+    );
+    let decisions = ns.create_certificates_for_initial_globals(&globals, |i, _| dirs[i].clone());
+    let verdicts: Vec<InitialStateVerdict> = globals
+        .iter()
+        .zip(&decisions)
+        .zip(dirs)
+        .map(|((global, decision), dir)| InitialStateVerdict {
+            initial_global: global.to_string(),
+            verdict: Verdict::of(decision),
+            out_dir: dir,
+        })
+        .collect();
+
+    print_verdicts(&verdicts);
+    let path = crate::utils::file::join(out_dir, INITIAL_STATES_FILE);
+    let json = serde_json::to_string_pretty(&verdicts)
+        .map_err(|err| SerError::IoError(err.to_string()))?;
+    crate::utils::file::safe_write_file(&path, &json)?;
+    println!("- {}", path.green());
+    Ok(verdicts)
+}
+
+/// Print the verdicts as part of the report
+pub fn print_verdicts(verdicts: &[InitialStateVerdict]) {
+    println!();
+    println!("Verdicts per initial global state:");
+    let width = verdicts
+        .iter()
+        .map(|v| v.initial_global.chars().count())
+        .max()
+        .unwrap_or(0);
+    for verdict in verdicts {
+        let label = match verdict.verdict {
+            Verdict::Serializable => "serializable".green(),
+            Verdict::NotSerializable => "not serializable".red(),
+            Verdict::Timeout => "timeout".yellow(),
+            Verdict::Unknown => "unknown".yellow(),
+        };
+        println!(
+            "  {:width$}  {}",
+            verdict.initial_global,
+            label,
+            width = width
+        );
+    }
+    let serializable = verdicts
+        .iter()
+        .filter(|v| v.verdict == Verdict::Serializable)
+        .count();
+    println!(
+        "  {} of {} initial states serializable",
+        serializable,
+        verdicts.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_initial_globals() {
+        let mut ns = NS::<String, String, String, String>::new("b".to_string());
+        ns.add_request("r".to_string(), "l".to_string());
+        ns.add_transition(
+            "l".to_string(),
+            "b".to_string(),
+            "l2".to_string(),
+            "a".to_string(),
+        );
+        ns.add_response("l2".to_string(), "ok".to_string());
+
+        assert_eq!(
+            select_initial_globals(&ns, &InitialStates::All).unwrap(),
+            vec!["a".to_string(), "b".to_string()]
+        );
+        assert_eq!(
+            select_initial_globals(&ns, &InitialStates::List(vec!["b".to_string()])).unwrap(),
+            vec!["b".to_string()]
+        );
+        assert!(select_initial_globals(&ns, &InitialStates::List(vec!["c".to_string()])).is_err());
+    }
+}
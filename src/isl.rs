@@ -9,6 +9,161 @@ pub mod bindings {
 }
 pub use bindings::*;
 
+use std::cell::Cell;
+use std::ffi::{CStr, c_char};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Kinds of ISL objects that are counted while tracking is on (see `track_isl_objects`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IslObject {
+    /// Strings owned by an `IslString`
+    String,
+    /// Sets owned by a `PresburgerSet`
+    Set,
+    /// Maps from `map_from_multi_aff` that `set_apply` has not consumed yet
+    Map,
+}
+
+thread_local! {
+    /// Whether ISL objects are counted on this thread
+    static TRACK_ISL_OBJECTS: Cell<bool> = const { Cell::new(false) };
+    /// Live objects of each `IslObject` kind on this thread
+    static LIVE_ISL_OBJECTS: Cell<[isize; 3]> = const { Cell::new([0; 3]) };
+}
+
+/// Count ISL objects on this thread until the returned guard is dropped, to catch
+/// leaks in tests
+pub fn track_isl_objects() -> IslObjectTracking {
+    IslObjectTracking {
+        enclosing: TRACK_ISL_OBJECTS.with(|tracking| tracking.replace(true)),
+    }
+}
+
+/// Guard of `track_isl_objects`, which restores the previous setting on drop
+pub struct IslObjectTracking {
+    enclosing: bool,
+}
+
+impl Drop for IslObjectTracking {
+    fn drop(&mut self) {
+        TRACK_ISL_OBJECTS.with(|tracking| tracking.set(self.enclosing));
+    }
+}
+
+/// Number of objects of this kind created on this thread and not yet freed (only
+/// counted while tracking is on)
+pub fn live_isl_objects(kind: IslObject) -> isize {
+    LIVE_ISL_OBJECTS.with(|live| live.get()[kind as usize])
+}
+
+/// Count an object of this kind as created (`delta` 1) or freed (`delta` -1)
+pub fn track_isl_object(kind: IslObject, delta: isize) {
+    if TRACK_ISL_OBJECTS.with(Cell::get) {
+        LIVE_ISL_OBJECTS.with(|live| {
+            let mut counts = live.get();
+            counts[kind as usize] += delta;
+            live.set(counts);
+        });
+    }
+}
+
+/// `isl_map_from_multi_aff`, counting the map as live until `set_apply` consumes it
+///
+/// # Safety
+/// `ma` must be a multi-aff that ISL hands over to the map.
+pub unsafe fn map_from_multi_aff(ma: *mut isl_multi_aff) -> *mut isl_map {
+    track_isl_object(IslObject::Map, 1);
+    unsafe { isl_map_from_multi_aff(ma) }
+}
+
+/// `isl_set_apply`, which consumes both the set and the map
+///
+/// # Safety
+/// `set` and `map` must be live ISL objects owned by the caller.
+pub unsafe fn set_apply(set: *mut isl_set, map: *mut isl_map) -> *mut isl_set {
+    track_isl_object(IslObject::Map, -1);
+    unsafe { isl_set_apply(set, map) }
+}
+
+/// An owned C string returned by ISL (e.g. from `isl_set_to_str`), freed on drop.
+pub struct IslString(*mut c_char);
+
+impl IslString {
+    /// Take ownership of a string allocated by ISL.
+    ///
+    /// # Safety
+    /// `ptr` must be null or a NUL-terminated string that ISL handed over to the caller.
+    pub unsafe fn from_raw(ptr: *mut c_char) -> Self {
+        if !ptr.is_null() {
+            track_isl_object(IslObject::String, 1);
+        }
+        IslString(ptr)
+    }
+}
+
+impl fmt::Display for IslString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_null() {
+            return write!(f, "<null>");
+        }
+        write!(f, "{}", unsafe { CStr::from_ptr(self.0) }.to_string_lossy())
+    }
+}
+
+impl Drop for IslString {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { libc::free(self.0 as *mut libc::c_void) };
+            track_isl_object(IslObject::String, -1);
+        }
+    }
+}
+
+/// A borrowed ISL set, for printing without taking ownership
+#[derive(Clone, Copy)]
+pub struct IslSetRef<'a> {
+    ptr: *mut isl_set,
+    _marker: PhantomData<&'a isl_set>,
+}
+
+impl IslSetRef<'_> {
+    /// # Safety
+    /// `ptr` must be null or point to a live ISL set for the lifetime of the reference.
+    pub unsafe fn new(ptr: *mut isl_set) -> Self {
+        IslSetRef {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl fmt::Display for IslSetRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ptr.is_null() {
+            return write!(f, "<null>");
+        }
+        let s = unsafe { IslString::from_raw(isl_set_to_str(self.ptr)) };
+        write!(f, "{}", s)
+    }
+}
+
+/// Payload of the panics that report ISL failures, so that `error::from_panic` can
+/// tell them apart from bugs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IslFailure(pub String);
+
+impl fmt::Display for IslFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Abort the current operation because ISL failed
+pub fn fail(message: String) -> ! {
+    std::panic::panic_any(IslFailure(message))
+}
+
 /// Get the (thread-local, unique) ISL ctx.
 ///
 /// This is preferred over manually calling isl_ctx_alloc() to make sure there's only one isl_ctx.
@@ -18,3 +173,77 @@ pub fn get_ctx() -> *mut isl_ctx {
     }
     ISL_CTX.with(|ctx| *ctx)
 }
+
+/// Stand-in for `isl_set_sum` when the linked ISL does not provide it, so that
+/// Minkowski sums fail with a clear message instead of at build or link time.
+#[cfg(not(isl_has_set_sum))]
+pub unsafe fn isl_set_sum(_set1: *mut isl_set, _set2: *mut isl_set) -> *mut isl_set {
+    fail(format!(
+        "Minkowski sum of Presburger sets needs isl_set_sum, which the ISL at {} does not provide",
+        prefix()
+    ))
+}
+
+/// Version string of the linked ISL library, if it exposes one
+pub fn version() -> Option<String> {
+    #[cfg(isl_has_version)]
+    {
+        let ptr = unsafe { isl_version() };
+        if ptr.is_null() {
+            return None;
+        }
+        let version = unsafe { std::ffi::CStr::from_ptr(ptr) };
+        Some(version.to_string_lossy().trim().to_string())
+    }
+    #[cfg(not(isl_has_version))]
+    {
+        None
+    }
+}
+
+/// Installation prefix of the ISL library we were built against
+pub fn prefix() -> &'static str {
+    env!("SER_ISL_PREFIX")
+}
+
+/// Optional ISL functions and whether the linked ISL provides them (detected by build.rs)
+pub fn capabilities() -> Vec<(&'static str, bool)> {
+    vec![
+        ("isl_set_count_val", cfg!(isl_has_set_count_val)),
+        ("isl_set_sum", cfg!(isl_has_set_sum)),
+        ("isl_version", cfg!(isl_has_version)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_match_version() {
+        let caps = capabilities();
+        assert!(caps.iter().any(|(name, _)| *name == "isl_set_sum"));
+        let has_version = caps.iter().any(|(name, ok)| *name == "isl_version" && *ok);
+        assert_eq!(version().is_some(), has_version);
+        assert!(!prefix().is_empty());
+    }
+
+    #[test]
+    fn test_isl_string_is_freed() {
+        let _tracking = track_isl_objects();
+        let before = live_isl_objects(IslObject::String);
+        unsafe {
+            let space = isl_space_set_alloc(get_ctx(), 0, 1);
+            let set = isl_set_fix_si(isl_set_universe(space), isl_dim_type_isl_dim_set, 0, 3);
+            let printed = IslSetRef::new(set).to_string();
+            assert!(printed.contains('3'), "{}", printed);
+            {
+                let _s = IslString::from_raw(isl_set_to_str(set));
+                assert_eq!(live_isl_objects(IslObject::String), before + 1);
+            }
+            isl_set_free(set);
+        }
+        assert_eq!(live_isl_objects(IslObject::String), before);
+        assert_eq!(unsafe { IslSetRef::new(std::ptr::null_mut()) }.to_string(), "<null>");
+    }
+}
@@ -0,0 +1,191 @@
+//! ISL operation tracing (`--trace-isl <file>`, `ser isl-trace <file>`).
+//!
+//! With tracing on, every ISL set operation of `presburger` appends a line to a CSV
+//! file: the operation, the dimensions and number of basic sets of its operands, and
+//! how long ISL took. Lines are written unbuffered, so a trace survives a timeout or
+//! a crash; the time to write them is not part of the measured durations.
+//! `ser isl-trace <file>` summarizes a trace by operation, hottest first.
+
+use crate::isl;
+use colored::*;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const HEADER: &str = "operation,dimensions,basic_sets,microseconds";
+
+/// Trace ISL operations to this file (called from `main.rs`)
+pub fn set_isl_trace(path: Option<String>) {
+    crate::options::update(|options| options.isl_trace = path);
+}
+
+pub fn isl_trace() -> Option<String> {
+    crate::options::get(|options| options.isl_trace.clone())
+}
+
+/// The open trace file and its path
+static TRACE_FILE: Mutex<Option<(String, File)>> = Mutex::new(None);
+
+/// Run the ISL operation `f` on `operands`, and trace it if tracing is on
+pub fn record<R>(operation: &str, operands: &[*mut isl::isl_set], f: impl FnOnce() -> R) -> R {
+    let Some(path) = isl_trace() else {
+        return f();
+    };
+    let (dimensions, basic_sets) = operand_sizes(operands);
+    let start = Instant::now();
+    let result = f();
+    let micros = start.elapsed().as_micros();
+    let line = format!("{},{},{},{}", operation, dimensions, basic_sets, micros);
+    if let Err(err) = append(&path, &line) {
+        eprintln!("Warning: cannot write ISL trace {}: {}", path, err);
+    }
+    result
+}
+
+/// The largest dimension and the total number of basic sets of the operands
+fn operand_sizes(operands: &[*mut isl::isl_set]) -> (usize, usize) {
+    let mut dimensions = 0;
+    let mut basic_sets = 0;
+    for &set in operands.iter().filter(|set| !set.is_null()) {
+        let dim = unsafe { isl::isl_set_dim(set, isl::isl_dim_type_isl_dim_set) };
+        let n = unsafe { isl::isl_set_n_basic_set(set) };
+        dimensions = dimensions.max(dim.max(0) as usize);
+        basic_sets += n.max(0) as usize;
+    }
+    (dimensions, basic_sets)
+}
+
+fn append(path: &str, line: &str) -> std::io::Result<()> {
+    let mut trace = TRACE_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if trace.as_ref().is_none_or(|(open, _)| open != path) {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        writeln!(file, "{}", HEADER)?;
+        *trace = Some((path.to_string(), file));
+    }
+    let (_, file) = trace.as_mut().expect("trace file was just opened");
+    writeln!(file, "{}", line)
+}
+
+/// Totals of one operation in a trace
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperationSummary {
+    pub calls: usize,
+    pub total_micros: u128,
+    pub max_micros: u128,
+    pub max_dimensions: usize,
+    pub mean_basic_sets: f64,
+}
+
+/// Summarize a trace by operation, by decreasing total time
+pub fn summarize(trace: &str) -> Result<Vec<(String, OperationSummary)>, String> {
+    let mut summaries: BTreeMap<String, OperationSummary> = BTreeMap::new();
+    for (number, line) in trace.lines().enumerate() {
+        if (number == 0 && line == HEADER) || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        let parse = |i: usize| -> Result<u128, String> {
+            fields
+                .get(i)
+                .and_then(|field| field.trim().parse().ok())
+                .ok_or_else(|| format!("Line {}: expected {}", number + 1, HEADER))
+        };
+        let (dimensions, basic_sets, micros) = (parse(1)?, parse(2)?, parse(3)?);
+        let summary = summaries.entry(fields[0].to_string()).or_default();
+        summary.calls += 1;
+        summary.total_micros += micros;
+        summary.max_micros = summary.max_micros.max(micros);
+        summary.max_dimensions = summary.max_dimensions.max(dimensions as usize);
+        // Running mean, so that long traces do not need another pass
+        summary.mean_basic_sets +=
+            (basic_sets as f64 - summary.mean_basic_sets) / summary.calls as f64;
+    }
+    let mut summaries: Vec<(String, OperationSummary)> = summaries.into_iter().collect();
+    summaries.sort_by_key(|(_, summary)| Reverse(summary.total_micros));
+    Ok(summaries)
+}
+
+/// `ser isl-trace <file>`: print the summary of a trace
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err("Usage: ser isl-trace <trace file>".to_string());
+    };
+    let trace =
+        std::fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path, err))?;
+    let summaries = summarize(&trace)?;
+    let total: u128 = summaries.iter().map(|(_, s)| s.total_micros).sum();
+    println!(
+        "{}",
+        format!(
+            "{:<16} {:>8} {:>12} {:>7} {:>10} {:>8} {:>11}",
+            "operation", "calls", "total (ms)", "share", "max (ms)", "max dim", "basic sets"
+        )
+        .bold()
+    );
+    for (operation, summary) in &summaries {
+        println!(
+            "{:<16} {:>8} {:>12.1} {:>6.1}% {:>10.1} {:>8} {:>11.1}",
+            operation,
+            summary.calls,
+            summary.total_micros as f64 / 1000.0,
+            100.0 * summary.total_micros as f64 / total.max(1) as f64,
+            summary.max_micros as f64 / 1000.0,
+            summary.max_dimensions,
+            summary.mean_basic_sets
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let trace = format!("{}\nunion,2,1,10\nis_empty,3,2,500\nunion,4,3,30\n", HEADER);
+        let summaries = summarize(&trace).unwrap();
+        let operations: Vec<&str> = summaries.iter().map(|(op, _)| op.as_str()).collect();
+        assert_eq!(operations, vec!["is_empty", "union"]);
+        let union = &summaries[1].1;
+        assert_eq!(union.calls, 2);
+        assert_eq!(union.total_micros, 40);
+        assert_eq!(union.max_micros, 30);
+        assert_eq!(union.max_dimensions, 4);
+        assert_eq!(union.mean_basic_sets, 2.0);
+
+        assert!(summarize("union,2,x,10").is_err());
+    }
+
+    #[test]
+    fn test_record_writes_trace() {
+        use crate::presburger::PresburgerSet;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.csv").to_string_lossy().to_string();
+        let options = crate::options::AnalysisOptions {
+            isl_trace: Some(path.clone()),
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            let a = PresburgerSet::atom("a");
+            let b = PresburgerSet::atom("b");
+            assert!(!a.union(&b).is_empty());
+        });
+        let trace = std::fs::read_to_string(&path).unwrap();
+        let summaries = summarize(&trace).unwrap();
+        assert!(
+            summaries
+                .iter()
+                .any(|(op, s)| op == "union" && s.calls >= 1)
+        );
+        assert!(summaries.iter().any(|(op, _)| op == "is_empty"));
+    }
+}
@@ -5,10 +5,15 @@
 #include <isl/val.h>
 #include <isl/space.h>
 #include <isl/set.h>
+#include <isl/point.h>
+#include <isl/map.h>
 #include <isl/constraint.h>
 #include <isl/space_type.h>
 #include <isl/aff.h>
 #include <isl/list.h>
+#ifdef SER_HAVE_ISL_VERSION_H
+#include <isl/version.h>
+#endif
 
 typedef struct {
     isl_set *set1;
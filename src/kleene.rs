@@ -1,28 +1,120 @@
-// Kleene algebra trait with operations:
-// - star
-// - plus
-// - times
-// - one
-// - zero
+//! Kleene algebras and a generic state-elimination solver.
+//!
+//! A Kleene algebra has operations:
+//! - star
+//! - plus
+//! - times
+//! - one
+//! - zero
+//!
+//! `nfa_to_kleene` and `nfa_to_kleene_accepting` compute the "path expression" of
+//! an automaton whose edges are labelled with Kleene algebra elements, so the same
+//! automaton can be interpreted in different algebras: `bool` (reachability),
+//! `Tropical` (shortest paths), `Regex` (the language as a regular expression) or
+//! `SemilinearSet` (the Parikh image, as used for the serialized automaton).
 
 use crate::deterministic_map::{HashMap, HashSet};
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::semilinear::semilinear_strategy;
 
-use crate::semilinear::GENERATE_LESS;
+/// Heuristic for the order in which `nfa_to_kleene_accepting` eliminates states.
+///
+/// The result denotes the same element whatever the order, but intermediate edge
+/// labels can blow up under a bad one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KleeneOrder {
+    /// Whatever order the state set iterates in
+    Arbitrary,
+    /// Fewest edges that eliminating the state would create (the default)
+    Degree,
+    /// Smallest estimated size of the labels that eliminating the state would create,
+    /// using `Kleene::size`
+    Weight,
+    /// A fixed order for the whole elimination, found by simulated annealing on the
+    /// number of created edges. Only worth it for small automata.
+    Annealing,
+    /// `Annealing`, `Weight` or `Degree`, depending on the number of states
+    Auto,
+}
+
+/// Largest automaton `Auto` uses simulated annealing for
+pub const ANNEALING_MAX_STATES: usize = 12;
+/// Largest automaton `Auto` uses the weight heuristic for
+pub const WEIGHT_MAX_STATES: usize = 100;
+
+impl KleeneOrder {
+    /// Parse a heuristic name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "arbitrary" => Some(KleeneOrder::Arbitrary),
+            "degree" => Some(KleeneOrder::Degree),
+            "weight" => Some(KleeneOrder::Weight),
+            "annealing" => Some(KleeneOrder::Annealing),
+            "auto" => Some(KleeneOrder::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KleeneOrder::Arbitrary => "arbitrary",
+            KleeneOrder::Degree => "degree",
+            KleeneOrder::Weight => "weight",
+            KleeneOrder::Annealing => "annealing",
+            KleeneOrder::Auto => "auto",
+        }
+    }
 
-pub static SMART_ORDER: AtomicBool = AtomicBool::new(true);
+    /// The heuristic to use for an automaton with `num_states` states
+    pub fn resolve(self, num_states: usize) -> Self {
+        match self {
+            KleeneOrder::Auto if num_states <= ANNEALING_MAX_STATES => KleeneOrder::Annealing,
+            KleeneOrder::Auto if num_states <= WEIGHT_MAX_STATES => KleeneOrder::Weight,
+            KleeneOrder::Auto => KleeneOrder::Degree,
+            order => order,
+        }
+    }
+}
 
+/// Set the elimination order heuristic (called from `main.rs`)
+pub fn set_kleene_order(order: KleeneOrder) {
+    crate::options::update(|options| options.kleene_order = order);
+}
+
+/// Get the elimination order heuristic
+pub fn kleene_order() -> KleeneOrder {
+    crate::options::get(|options| options.kleene_order)
+}
+
+/// Switch between the `Degree` heuristic (on) and `Arbitrary` order (off)
 pub fn set_smart_kleene_order(on: bool) {
-    SMART_ORDER.store(on, Ordering::SeqCst);
+    set_kleene_order(if on {
+        KleeneOrder::Degree
+    } else {
+        KleeneOrder::Arbitrary
+    });
 }
 
+/// A Kleene algebra: an idempotent semiring with a star operation.
+///
+/// Implementations should satisfy the usual laws, in particular
+/// `zero` is the unit of `plus` and annihilates `times`, `one` is the unit of
+/// `times`, and `star(a) = one + a · star(a)`.
 pub trait Kleene {
+    /// The empty choice (no path)
     fn zero() -> Self;
+    /// The empty sequence (the trivial path)
     fn one() -> Self;
+    /// Choice between two alternatives
     fn plus(self, other: Self) -> Self;
+    /// Sequential composition
     fn times(self, other: Self) -> Self;
+    /// Zero or more repetitions
     fn star(self) -> Self;
+    /// Size of the representation, used to steer and log state elimination
+    fn size(&self) -> usize {
+        1
+    }
 }
 
 impl Kleene for bool {
@@ -43,6 +135,53 @@ impl Kleene for bool {
     }
 }
 
+/// Min-plus (tropical) Kleene algebra over nonnegative weights, for shortest-path
+/// style analyses. `Tropical(None)` is infinity (no path).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tropical(pub Option<u64>);
+
+impl Tropical {
+    /// A path of the given length
+    pub fn weight(w: u64) -> Self {
+        Tropical(Some(w))
+    }
+}
+
+impl std::fmt::Display for Tropical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(w) => write!(f, "{}", w),
+            None => write!(f, "∞"),
+        }
+    }
+}
+
+impl Kleene for Tropical {
+    fn zero() -> Self {
+        Tropical(None)
+    }
+    fn one() -> Self {
+        Tropical(Some(0))
+    }
+    fn plus(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Tropical(Some(a.min(b))),
+            (a, b) => Tropical(a.or(b)),
+        }
+    }
+    fn times(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Tropical(Some(a.saturating_add(b))),
+            _ => Tropical(None),
+        }
+    }
+    fn star(self) -> Self {
+        // Weights are nonnegative, so repeating never helps
+        Tropical::one()
+    }
+}
+
+/// Regular expressions over an alphabet `T`
 #[derive(Debug, Clone, PartialEq)]
 pub enum Regex<T> {
     Atom(T),
@@ -66,6 +205,154 @@ impl<T: std::fmt::Display> std::fmt::Display for Regex<T> {
     }
 }
 
+impl<T: Clone + PartialEq> Regex<T> {
+    /// Number of nodes in the expression tree
+    pub fn size(&self) -> usize {
+        match self {
+            Regex::Atom(_) | Regex::Zero | Regex::One => 1,
+            Regex::Plus(a, b) | Regex::Times(a, b) => 1 + a.size() + b.size(),
+            Regex::Star(a) => 1 + a.size(),
+        }
+    }
+
+    /// Algebraically simplify the expression, preserving its language.
+    ///
+    /// Applies the unit and annihilator laws for `0` and `1`, removes duplicate
+    /// alternatives, collapses nested stars and factors common prefixes out of
+    /// alternatives (`a·b + a·c = a·(b + c)`). Rewriting is repeated until the
+    /// expression stops shrinking.
+    pub fn simplify(&self) -> Self {
+        let mut current = self.simplify_once();
+        loop {
+            let next = current.simplify_once();
+            if next.size() >= current.size() {
+                return current;
+            }
+            current = next;
+        }
+    }
+
+    fn simplify_once(&self) -> Self {
+        match self {
+            Regex::Atom(_) | Regex::Zero | Regex::One => self.clone(),
+            Regex::Plus(a, b) => {
+                let mut alternatives = Vec::new();
+                a.simplify_once().collect_alternatives(&mut alternatives);
+                b.simplify_once().collect_alternatives(&mut alternatives);
+                Self::simplify_alternatives(alternatives)
+            }
+            Regex::Times(a, b) => {
+                let mut factors = Vec::new();
+                a.simplify_once().collect_factors(&mut factors);
+                b.simplify_once().collect_factors(&mut factors);
+                Self::simplify_factors(factors)
+            }
+            Regex::Star(a) => {
+                let inner = a.simplify_once();
+                // (1 + x)* = x*, and (x*)* = x*
+                let mut alternatives = Vec::new();
+                inner.collect_alternatives(&mut alternatives);
+                alternatives.retain(|r| *r != Regex::One);
+                match Self::simplify_alternatives(alternatives) {
+                    Regex::Zero | Regex::One => Regex::One,
+                    Regex::Star(x) => Regex::Star(x),
+                    x => Regex::Star(Box::new(x)),
+                }
+            }
+        }
+    }
+
+    fn collect_alternatives(self, out: &mut Vec<Self>) {
+        match self {
+            Regex::Plus(a, b) => {
+                a.collect_alternatives(out);
+                b.collect_alternatives(out);
+            }
+            x => out.push(x),
+        }
+    }
+
+    fn collect_factors(self, out: &mut Vec<Self>) {
+        match self {
+            Regex::Times(a, b) => {
+                a.collect_factors(out);
+                b.collect_factors(out);
+            }
+            x => out.push(x),
+        }
+    }
+
+    /// Build a product from already simplified factors
+    fn simplify_factors(factors: Vec<Self>) -> Self {
+        if factors.contains(&Regex::Zero) {
+            return Regex::Zero;
+        }
+        let mut kept: Vec<Self> = Vec::new();
+        for factor in factors {
+            if factor == Regex::One {
+                continue;
+            }
+            // x*·x* = x*
+            if matches!(factor, Regex::Star(_)) && kept.last() == Some(&factor) {
+                continue;
+            }
+            kept.push(factor);
+        }
+        kept.into_iter()
+            .rev()
+            .reduce(|acc, f| Regex::Times(Box::new(f), Box::new(acc)))
+            .unwrap_or(Regex::One)
+    }
+
+    /// Build a sum from already simplified alternatives, factoring common prefixes
+    fn simplify_alternatives(alternatives: Vec<Self>) -> Self {
+        let mut unique: Vec<Self> = Vec::new();
+        for alt in alternatives {
+            if alt != Regex::Zero && !unique.contains(&alt) {
+                unique.push(alt);
+            }
+        }
+        // 1 + x* = x*
+        if unique.iter().any(|r| matches!(r, Regex::Star(_))) {
+            unique.retain(|r| *r != Regex::One);
+        }
+
+        // Group alternatives by their first factor, keeping first-occurrence order
+        let mut groups: Vec<(Self, Vec<Vec<Self>>)> = Vec::new();
+        for alt in unique {
+            let mut factors = Vec::new();
+            alt.collect_factors(&mut factors);
+            let head = factors.remove(0);
+            match groups.iter_mut().find(|(h, _)| *h == head) {
+                Some((_, tails)) => tails.push(factors),
+                None => groups.push((head, vec![factors])),
+            }
+        }
+
+        let mut result: Vec<Self> = Vec::new();
+        for (head, mut tails) in groups {
+            let alt = if tails.len() == 1 {
+                let mut factors = vec![head];
+                factors.append(&mut tails[0]);
+                Self::simplify_factors(factors)
+            } else {
+                let tail = Self::simplify_alternatives(
+                    tails.into_iter().map(Self::simplify_factors).collect(),
+                );
+                Self::simplify_factors(vec![head, tail])
+            };
+            if !result.contains(&alt) {
+                result.push(alt);
+            }
+        }
+        result
+            .into_iter()
+            .rev()
+            .reduce(|acc, r| Regex::Plus(Box::new(r), Box::new(acc)))
+            .unwrap_or(Regex::Zero)
+    }
+}
+
 impl<T> Kleene for Regex<T> {
     fn zero() -> Self {
         Regex::Zero
@@ -74,7 +361,7 @@ impl<T> Kleene for Regex<T> {
         Regex::One
     }
     fn plus(self, other: Self) -> Self {
-        if GENERATE_LESS.load(Ordering::SeqCst) {
+        if semilinear_strategy().simplifies() {
             match (self, other) {
                 (Regex::Zero, x) | (x, Regex::Zero) => x,
                 (a, b) => Regex::Plus(Box::new(a), Box::new(b)),
@@ -85,7 +372,7 @@ impl<T> Kleene for Regex<T> {
         }
     }
     fn times(self, other: Self) -> Self {
-        if GENERATE_LESS.load(Ordering::SeqCst) {
+        if semilinear_strategy().simplifies() {
             match (self, other) {
                 (Regex::Zero, _) | (_, Regex::Zero) => Regex::Zero,
                 (Regex::One, x) | (x, Regex::One) => x,
@@ -97,7 +384,7 @@ impl<T> Kleene for Regex<T> {
         }
     }
     fn star(self) -> Self {
-        if GENERATE_LESS.load(Ordering::SeqCst) {
+        if semilinear_strategy().simplifies() {
             match self {
                 Regex::Zero | Regex::One => Regex::One,
                 Regex::Star(x) => Regex::Star(x),
@@ -108,6 +395,13 @@ impl<T> Kleene for Regex<T> {
             Regex::Star(Box::new(self))
         }
     }
+    fn size(&self) -> usize {
+        match self {
+            Regex::Atom(_) | Regex::Zero | Regex::One => 1,
+            Regex::Plus(a, b) | Regex::Times(a, b) => 1 + a.size() + b.size(),
+            Regex::Star(a) => 1 + a.size(),
+        }
+    }
 }
 
 // Kleene's algorithm for converting a NFA to a Kleene algebra
@@ -115,6 +409,16 @@ impl<T> Kleene for Regex<T> {
 pub fn nfa_to_kleene<S: Clone + Eq + std::hash::Hash, K: Kleene + Clone>(
     nfa_vec: &[(S, K, S)],
     start: S,
+) -> K {
+    nfa_to_kleene_accepting(nfa_vec, start, |_| true)
+}
+
+/// Generic state-elimination solver: computes the sum over all paths from `start`
+/// to a state satisfying `accepting` of the product of the edge labels.
+pub fn nfa_to_kleene_accepting<S: Clone + Eq + std::hash::Hash, K: Kleene + Clone>(
+    nfa_vec: &[(S, K, S)],
+    start: S,
+    accepting: impl Fn(&S) -> bool,
 ) -> K {
     // We add an extra state `None` and eliminate all states except that one
 
@@ -137,35 +441,33 @@ pub fn nfa_to_kleene<S: Clone + Eq + std::hash::Hash, K: Kleene + Clone>(
 
     states_todo.insert(&start);
 
-    // Insert epsilon edges from all states_todo to None
-    for state in states_todo.iter() {
+    // Insert epsilon edges from all accepting states to None
+    for state in states_todo.iter().filter(|s| accepting(s)) {
         nfa.entry((Some(state), None))
             .and_modify(|e| *e = e.clone().plus(K::one()))
             .or_insert(K::one());
     }
 
+    let order = kleene_order().resolve(states_todo.len());
+    let mut planned = match order {
+        KleeneOrder::Annealing => annealed_order(&nfa, &states_todo),
+        _ => Vec::new(),
+    };
+    planned.reverse();
+    let mut trajectory = Vec::new();
+
     while !states_todo.is_empty() {
-        let state = *states_todo
-            .iter()
-            .min_by_key(|s| {
-                // Optionally, disable the heuristics for picking the next state
-                if !SMART_ORDER.load(Ordering::SeqCst) {
-                    return 0;
-                }
-                let mut count = 0;
-                for ((_, to), _) in nfa.iter() {
-                    if to == &Some(**s) && !nfa.contains_key(&(Some(s), *to)) {
-                        count += 1;
-                    }
-                }
-                for ((from, _), _) in nfa.iter() {
-                    if from == &Some(**s) && !nfa.contains_key(&(*from, Some(s))) {
-                        count += 1;
-                    }
-                }
-                count
-            })
-            .unwrap();
+        let state = match order {
+            KleeneOrder::Annealing => planned.pop().unwrap(),
+            _ => *states_todo
+                .iter()
+                .min_by_key(|s| match order {
+                    KleeneOrder::Weight => weight_score(&nfa, *s),
+                    KleeneOrder::Degree => degree_score(&nfa, *s),
+                    _ => 0,
+                })
+                .unwrap(),
+        };
         states_todo.remove(&state);
         let mut new_nfa: Vec<(Option<&S>, Option<&S>, K)> = vec![];
         let mut incoming: Vec<(Option<&S>, Option<&S>, K)> = vec![];
@@ -208,7 +510,9 @@ pub fn nfa_to_kleene<S: Clone + Eq + std::hash::Hash, K: Kleene + Clone>(
                 .or_insert(k.clone());
         }
         nfa = new_nfa_map;
+        trajectory.push(nfa.values().map(|k| k.size()).sum());
     }
+    crate::stats::record_kleene_elimination(order.name(), trajectory);
     let mut answer = K::zero();
     for ((from, to), k) in nfa.iter() {
         assert!(from.is_none());
@@ -218,10 +522,147 @@ pub fn nfa_to_kleene<S: Clone + Eq + std::hash::Hash, K: Kleene + Clone>(
     answer
 }
 
+/// Edges that eliminating `s` would create, not counting edges that already exist
+fn degree_score<S: Eq + std::hash::Hash, K>(
+    nfa: &HashMap<(Option<&S>, Option<&S>), K>,
+    s: &S,
+) -> usize {
+    let mut count = 0;
+    for ((_, to), _) in nfa.iter() {
+        if to == &Some(s) && !nfa.contains_key(&(Some(s), *to)) {
+            count += 1;
+        }
+    }
+    for ((from, _), _) in nfa.iter() {
+        if from == &Some(s) && !nfa.contains_key(&(*from, Some(s))) {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Estimated total size of the labels that eliminating `s` would create
+fn weight_score<S: Eq + std::hash::Hash, K: Kleene>(
+    nfa: &HashMap<(Option<&S>, Option<&S>), K>,
+    s: &S,
+) -> usize {
+    let (mut in_count, mut in_size, mut out_count, mut out_size, mut loop_size) = (0, 0, 0, 0, 0);
+    for ((from, to), k) in nfa.iter() {
+        if from == &Some(s) && to == &Some(s) {
+            loop_size += k.size();
+        } else if to == &Some(s) {
+            in_count += 1;
+            in_size += k.size();
+        } else if from == &Some(s) {
+            out_count += 1;
+            out_size += k.size();
+        }
+    }
+    in_size * out_count + out_size * in_count + loop_size * in_count * out_count
+}
+
+/// Edges created when eliminating states in the given order, on the edge structure
+/// only. States are indices; `None` is never eliminated.
+fn elimination_cost(edges: &HashSet<(Option<usize>, Option<usize>)>, order: &[usize]) -> usize {
+    let mut edges = edges.clone();
+    let mut cost = 0;
+    for &v in order {
+        let v = Some(v);
+        let ins: Vec<_> = edges.iter().filter(|e| e.1 == v && e.0 != v).map(|e| e.0).collect();
+        let outs: Vec<_> = edges.iter().filter(|e| e.0 == v && e.1 != v).map(|e| e.1).collect();
+        cost += ins.len() * outs.len();
+        edges.retain(|e| e.0 != v && e.1 != v);
+        for &from in &ins {
+            for &to in &outs {
+                edges.insert((from, to));
+            }
+        }
+    }
+    cost
+}
+
+/// Elimination order found by simulated annealing on `elimination_cost`, starting
+/// from the order the `Degree` heuristic would pick. Deterministic.
+fn annealed_order<'a, S: Eq + std::hash::Hash, K>(
+    nfa: &HashMap<(Option<&'a S>, Option<&'a S>), K>,
+    states: &HashSet<&'a S>,
+) -> Vec<&'a S> {
+    let states: Vec<&S> = states.iter().copied().collect();
+    let index = |s: &Option<&S>| s.map(|s| states.iter().position(|t| *t == s).unwrap());
+    let edges: HashSet<(Option<usize>, Option<usize>)> =
+        nfa.keys().map(|(from, to)| (index(from), index(to))).collect();
+
+    let mut order: Vec<usize> = (0..states.len()).collect();
+    order.sort_by_key(|&i| degree_score(nfa, states[i]));
+    let mut cost = elimination_cost(&edges, &order);
+    let mut best = (cost, order.clone());
+
+    // xorshift64, so runs are reproducible
+    let mut seed: u64 = 0x9e3779b97f4a7c15;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let n = order.len();
+    let iterations = if n > 1 { 2000 } else { 0 };
+    let mut temperature = (cost as f64 / 10.0).max(1.0);
+    for _ in 0..iterations {
+        let (i, j) = ((random() % n as u64) as usize, (random() % n as u64) as usize);
+        order.swap(i, j);
+        let new_cost = elimination_cost(&edges, &order);
+        let accept = new_cost <= cost
+            || (random() as f64 / u64::MAX as f64)
+                < (-((new_cost - cost) as f64) / temperature).exp();
+        if accept {
+            cost = new_cost;
+            if cost < best.0 {
+                best = (cost, order.clone());
+            }
+        } else {
+            order.swap(i, j);
+        }
+        temperature *= 0.995;
+    }
+    best.1.into_iter().map(|i| states[i]).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_elimination_orders() {
+        // A star: the hub has 3 incoming and 3 outgoing edges, the leaves one each
+        let mut nfa: HashMap<(Option<&usize>, Option<&usize>), Tropical> = HashMap::default();
+        let states = [0, 1, 2, 3, 4, 5, 6];
+        for leaf in &states[1..4] {
+            nfa.insert((Some(leaf), Some(&states[0])), Tropical::weight(1));
+        }
+        for leaf in &states[4..] {
+            nfa.insert((Some(&states[0]), Some(leaf)), Tropical::weight(1));
+        }
+        assert_eq!(degree_score(&nfa, &0), 6);
+        assert_eq!(weight_score(&nfa, &0), 18);
+
+        let all: HashSet<&usize> = states.iter().collect();
+        let index = |s: &Option<&usize>| s.copied();
+        let edges: HashSet<(Option<usize>, Option<usize>)> =
+            nfa.keys().map(|(from, to)| (index(from), index(to))).collect();
+        // Eliminating the hub first creates 9 edges, eliminating it last none
+        assert_eq!(elimination_cost(&edges, &[0, 1, 2, 3, 4, 5, 6]), 9);
+        assert_eq!(elimination_cost(&edges, &[1, 2, 3, 4, 5, 6, 0]), 0);
+
+        let annealed: Vec<usize> = annealed_order(&nfa, &all).into_iter().copied().collect();
+        assert_eq!(annealed.len(), 7);
+        assert_eq!(elimination_cost(&edges, &annealed), 0);
+
+        assert_eq!(KleeneOrder::Auto.resolve(5), KleeneOrder::Annealing);
+        assert_eq!(KleeneOrder::Auto.resolve(50), KleeneOrder::Weight);
+        assert_eq!(KleeneOrder::Auto.resolve(500), KleeneOrder::Degree);
+    }
+
     #[test]
     fn test_nfa_to_kleene() {
         // Create a simple NFA with 3 states
@@ -247,4 +688,59 @@ mod tests {
         assert!(chars.contains(&'c'));
         assert!(chars.contains(&'d'));
     }
+
+    #[test]
+    fn test_nfa_to_kleene_accepting_instances() {
+        // 0 -1-> 1 -1-> 2, and a direct but longer edge 0 -5-> 2; 3 is unreachable
+        let weighted = vec![
+            (0, Tropical::weight(1), 1),
+            (1, Tropical::weight(1), 2),
+            (0, Tropical::weight(5), 2),
+            (3, Tropical::weight(1), 2),
+        ];
+        assert_eq!(
+            nfa_to_kleene_accepting(&weighted, 0, |s| *s == 2),
+            Tropical::weight(2)
+        );
+        assert_eq!(nfa_to_kleene_accepting(&weighted, 0, |s| *s == 3), Tropical::zero());
+        // Every state is accepting: the empty path has length 0
+        assert_eq!(nfa_to_kleene(&weighted, 0), Tropical::one());
+
+        let reachable: Vec<(i32, bool, i32)> =
+            weighted.iter().map(|(a, _, b)| (*a, true, *b)).collect();
+        assert!(nfa_to_kleene_accepting(&reachable, 0, |s| *s == 2));
+        assert!(!nfa_to_kleene_accepting(&reachable, 0, |s| *s == 3));
+
+        let regex = vec![(0, Regex::Atom('a'), 1), (1, Regex::Atom('b'), 1)];
+        let result = nfa_to_kleene_accepting(&regex, 0, |s| *s == 1);
+        assert_eq!(result.to_string(), "(a · (b)*)");
+    }
+
+    #[test]
+    fn test_regex_simplify() {
+        let atom = Regex::Atom;
+        let times = |a, b| Regex::Times(Box::new(a), Box::new(b));
+        let plus = |a, b| Regex::Plus(Box::new(a), Box::new(b));
+        let star = |a| Regex::Star(Box::new(a));
+
+        // Identity and annihilator laws
+        assert_eq!(plus(Regex::Zero, times(Regex::One, atom('a'))).simplify(), atom('a'));
+        assert_eq!(times(atom('a'), times(Regex::Zero, atom('b'))).simplify(), Regex::Zero);
+        assert_eq!(star(plus(Regex::One, star(Regex::Zero))).simplify(), Regex::One);
+
+        // Duplicates and nested stars
+        assert_eq!(plus(atom('a'), atom('a')).simplify(), atom('a'));
+        assert_eq!(star(star(plus(Regex::One, atom('a')))).simplify(), star(atom('a')));
+        assert_eq!(plus(Regex::One, star(atom('a'))).simplify(), star(atom('a')));
+
+        // Common prefixes are factored out
+        let r = plus(
+            times(atom('a'), atom('b')),
+            plus(times(atom('a'), atom('c')), atom('d')),
+        );
+        assert_eq!(r.simplify().to_string(), "((a · (b + c)) + d)");
+        let r = plus(times(atom('a'), atom('b')), atom('a'));
+        assert_eq!(r.simplify().to_string(), "(a · (b + 1))");
+        assert!(r.simplify().size() <= r.size());
+    }
 }
@@ -0,0 +1,86 @@
+//! The analysis as a library, for tools that embed it (see "Embedding" in the README).
+//! The `ser` binary is a command line front end to these modules.
+#![allow(dead_code)]
+
+// mod affine_constraints;
+pub mod absint;
+pub mod bounded_search;
+pub mod cert_compare;
+pub mod certificate_check;
+pub mod codegen;
+pub mod commutativity;
+pub mod constraints;
+#[cfg(feature = "isl")]
+pub mod counterexamples;
+#[cfg(feature = "isl")]
+pub mod cross_validate;
+pub mod debug_report;
+pub mod deterministic_map;
+pub mod diagnosis;
+pub mod dim_order;
+pub mod error;
+pub mod explain;
+pub mod expr_to_ns;
+pub mod frontend;
+pub mod graphviz;
+pub mod ident;
+pub mod info;
+pub mod initial_states;
+#[cfg(feature = "isl")]
+pub mod isl;
+#[cfg(feature = "isl")]
+pub mod isl_trace;
+pub mod kleene;
+pub mod manifest;
+pub mod marking_equation;
+pub mod mermaid;
+#[cfg(feature = "isl")]
+pub mod micro_bench;
+pub mod naming;
+pub mod ns;
+pub mod ns_decision;
+pub mod ns_to_petri;
+pub mod observer;
+pub mod options;
+pub mod parser;
+pub mod petri;
+pub mod place_id;
+pub mod pluscal;
+#[cfg(feature = "isl")]
+pub mod presburger;
+pub mod presburger_fallback;
+#[cfg(all(test, feature = "isl"))]
+pub mod presburger_harmonize_tests;
+pub mod proof_parser;
+pub mod proofinvariant_to_presburger;
+pub mod proto;
+#[cfg(feature = "isl")]
+pub mod quantitative;
+#[cfg(feature = "isl")]
+pub mod query;
+pub mod quiescence;
+pub mod reachability;
+pub mod reachability_with_proofs;
+pub mod repair;
+pub mod semilinear;
+pub mod serializable_subset;
+pub mod shrink;
+pub mod size_logger;
+pub mod smpt;
+#[cfg(any(test, feature = "fake-smpt"))]
+pub mod smpt_fake;
+pub mod smpt_fallback;
+pub mod smpt_offline;
+#[cfg(feature = "isl")]
+pub mod spresburger;
+pub mod stats;
+pub mod templates;
+pub mod user_invariant;
+pub mod utils;
+pub mod var_map;
+#[cfg(feature = "isl")]
+pub mod witness;
+
+// Proof invariants, for code and test harnesses that build them directly instead of
+// writing SMT-LIB
+pub use proof_parser::{AffineExpr, CompOp, Constraint, Formula, ProofInvariant};
@@ -1,48 +1,58 @@
 #![allow(dead_code)]
 
-// mod affine_constraints;
-mod debug_report;
-mod deterministic_map;
-mod expr_to_ns;
-mod graphviz;
-mod isl;
-
-mod kleene;
-mod ns;
-mod ns_decision;
-mod ns_to_petri;
-mod parser;
-mod petri;
-mod presburger;
-#[cfg(test)]
-mod presburger_harmonize_tests;
-mod proof_parser;
-mod proofinvariant_to_presburger;
-mod reachability;
-mod reachability_with_proofs;
-mod semilinear;
-mod size_logger;
-mod smpt;
-mod spresburger;
-mod stats;
-mod utils;
-
 use colored::*;
-use parser::Program;
-use parser::Request;
 use std::env;
 use std::fmt::Display;
 use std::fs;
 use std::hash::Hash;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 
+use ser::*;
+
+use error::SerError;
 use ns::NS;
-use parser::{ExprHc, parse, parse_program};
+use parser::{ExprHc, parse_program};
 
 fn print_usage() {
     println!("{}", "Usage: ser [options] <filename or directory>".bold());
+    println!(
+        "{}",
+        "       ser micro-bench [--max-size <n>] [--dims <n>] [--repeat <n>]".bold()
+    );
+    println!(
+        "{}",
+        "       ser compose [options] <directory>   (analyze all .ser files as one system)".bold()
+    );
+    println!(
+        "{}",
+        "       ser query <output directory>        (ask about completed multisets of an analysis)".bold()
+    );
+    println!(
+        "{}",
+        "       ser cert-compare <a.json> <b.json>  (compare two certificates)".bold()
+    );
+    println!(
+        "{}",
+        "       ser isl-trace <trace file>          (summarize a --trace-isl trace)".bold()
+    );
+    println!(
+        "{}",
+        "       ser info <file>                     (model statistics, without analysis)".bold()
+    );
+    println!(
+        "{}",
+        "       ser new <template> [--n <k>] [-o <file>] (2pc, lock-service or lost-update skeleton)".bold()
+    );
+    println!(
+        "{}",
+        "       ser shrink <file> [--max-runs <n>] [-- <options>] (minimal program that crashes the analysis)".bold()
+    );
     println!("{}", "Options:".bold());
+    println!(
+        "  {}               Print the version and the capabilities of the linked ISL",
+        "--version".green()
+    );
     println!(
         "  {}                  Open generated visualization files",
         "--open".green()
@@ -55,6 +65,14 @@ fn print_usage() {
         "  {}   Disable optimizations (default: optimizations ON)",
         "--without-bidirectional".green()
     );
+    println!(
+        "  {} Simplification in semilinear star/union: exact, generate-less (default), aggressive",
+        "--semilinear-strategy <s>".green()
+    );
+    println!(
+        "  {} State elimination order: arbitrary, degree (default), weight, annealing, auto",
+        "--kleene-order <h>".green()
+    );
     println!(
         "  {}               Check SMPT installation status",
         "--check-smpt".green()
@@ -63,10 +81,164 @@ fn print_usage() {
         "  {}      Set SMPT timeout in seconds (default: 300)",
         "--timeout <seconds>".green()
     );
+    println!(
+        "  {} Spend a total budget on SMPT, with adaptive timeouts per disjunct",
+        "--auto-timeout <seconds>".green()
+    );
     println!(
         "  {}             Enable SMPT result caching",
         "--use-cache".green()
     );
+    println!(
+        "  {}        Also emit the serialized automaton as its own DOT graph",
+        "--serialized-dot".green()
+    );
+    println!(
+        "  {}                Resume an interrupted run, skipping disjuncts already decided",
+        "--resume".green()
+    );
+    println!(
+        "  {}              If not serializable, find the requests that conflict",
+        "--diagnose".green()
+    );
+    println!(
+        "  {}   If not serializable, query every disjunct and group the counterexamples into patterns",
+        "--all-counterexamples".green()
+    );
+    println!(
+        "  {} Show counterexample traces as Petri net transitions, NS steps (default) or source statements",
+        "--trace-detail <petri|ns|source>".green()
+    );
+    println!(
+        "  {}   If not serializable, list which completed requests are still serializable",
+        "--serializable-subset".green()
+    );
+    println!(
+        "  {}           If not serializable, search for a small fix: drop a yield or add a lock (experimental)",
+        "--suggest-fix".green()
+    );
+    println!(
+        "  {}               Only build the queries and estimate the problem size, without SMPT",
+        "--dry-run".green()
+    );
+    println!(
+        "  {}                Try a quick interval analysis proof before the full analysis",
+        "--absint".green()
+    );
+    println!(
+        "  {}             Write the marking equation of each disjunct as .lp and .mps files",
+        "--export-lp".green()
+    );
+    println!(
+        "  {}          Skip disjuncts whose marking equation HiGHS proves infeasible",
+        "--lp-prefilter".green()
+    );
+    println!(
+        "  {} Warn (or with :prune, enable pruning) when places, transitions or components exceed n",
+        "--size-threshold <metric>:<n>[:prune]".green()
+    );
+    println!(
+        "  {} Order in which disjuncts are checked: as-given (default), fewest-constraints, fewest-nonzero",
+        "--disjunct-order <s>".green()
+    );
+    println!(
+        "  {} Place names in .net files and SMPT proofs: full (default), short or hashed",
+        "--naming <scheme>".green()
+    );
+    println!(
+        "  {} Order of ISL dimensions: sorted (default), frequency, structural, random[:seed]",
+        "--dim-order <o>".green()
+    );
+    println!(
+        "  {} Write SMPT queries, a manifest and run_queries.sh to <out>/smpt_queries instead of running SMPT",
+        "--export-smpt-queries".green()
+    );
+    println!(
+        "  {} Take SMPT verdicts and proofs from a directory of exported queries run elsewhere",
+        "--import-smpt-results <dir>".green()
+    );
+    if cfg!(feature = "fake-smpt") {
+        println!(
+            "  {}     Answer SMPT queries with injected failures: <outcome> or <disjunct>=<outcome>, comma-separated",
+            "--fake-smpt <spec>".green()
+        );
+    }
+    println!(
+        "  {} Analyze requests that never read or write the global state with the others",
+        "--without-read-only-fast-path".green()
+    );
+    println!(
+        "  {} Convert request bodies as written, without simplifying them first",
+        "--without-expr-simplification".green()
+    );
+    println!(
+        "  {} Parse .ser files as language version <n>, whatever their #lang header says",
+        "--lang-version <n>".green()
+    );
+    println!(
+        "  {} When serializable, show the linear sets of the serializable language and the global states they complete in",
+        "--witness".green()
+    );
+    println!(
+        "  {} Also write the invariant of a serializability proof as a LaTeX document (invariant.tex)",
+        "--latex".green()
+    );
+    println!(
+        "  {} Also write the semilinear set as a table (semilinear.tex or semilinear.md)",
+        "--semilinear-format <text|latex|markdown>".green()
+    );
+    println!(
+        "  {} Before the analysis, play the token game N times at random looking for a counterexample",
+        "--simulate <N>".green()
+    );
+    println!(
+        "  {} Like --simulate, but steer the runs towards the non-serializable markings",
+        "--simulate-guided <N>".green()
+    );
+    println!(
+        "  {} Before the analysis, search breadth-first for a shortest counterexample of at most k steps",
+        "--bounded-search <k>".green()
+    );
+    println!(
+        "  {} Give up the bounded search after visiting N markings (default: 1000000)",
+        "--bounded-search-states <N>".green()
+    );
+    println!(
+        "  {} When serializable, explain the invariant with simple patterns like mutual exclusion",
+        "--explain".green()
+    );
+    println!(
+        "  {} Show which pairs of requests commute on the global state",
+        "--commutativity".green()
+    );
+    println!(
+        "  {} Show how often each response can occur in a serial execution, and which exclude each other",
+        "--quantitative".green()
+    );
+    println!(
+        "  {} Also check that the requests in flight can always complete",
+        "--check quiescence".green()
+    );
+    println!(
+        "  {} Let the server crash at any time, resetting the globals to their initial values",
+        "--model-crashes".green()
+    );
+    println!(
+        "  {} Compare completed requests as a multiset (default) or as a set, ignoring duplicates",
+        "--duplicates <multiset|set>".green()
+    );
+    println!(
+        "  {} Also analyze the system from every global state as initial state",
+        "--all-initial-states".green()
+    );
+    println!(
+        "  {} Also analyze the system from these initial states (comma-separated)",
+        "--initial-states <g1,g2,...>".green()
+    );
+    println!(
+        "  {} After checking a proof certificate, also ask SMPT whether any reachable marking violates its invariant",
+        "--cross-validate".green()
+    );
     println!(
         "  {}   Create and save serializability certificate only",
         "--create-certificate".green()
@@ -75,21 +247,60 @@ fn print_usage() {
         "  {}    Load and verify previously saved certificate",
         "--check-certificate".green()
     );
+    println!(
+        "  {}   Log every ISL operation with its operand sizes and duration to a CSV file",
+        "--trace-isl <file>".green()
+    );
+    println!(
+        "  {}     Check a hand-written invariant (SMT-LIB cert, or JSON from global state to formula) instead of analyzing",
+        "--invariant <file>".green()
+    );
+    println!(
+        "  {} Compress saved certificates (none, gzip, zstd)",
+        "--compress-certificate <c>".green()
+    );
+    println!(
+        "  {} Refuse to save or load certificates larger than this",
+        "--max-certificate-size <bytes>".green()
+    );
+    println!(
+        "  {} How to check inductiveness of certificates (isl, formula, both)",
+        "--inductiveness-check <m>".green()
+    );
+    println!(
+        "  {} How certificate checks decide Presburger formulas (isl, fallback)",
+        "--check-backend <b>".green()
+    );
+    println!(
+        "  {} Global states without an invariant (unreachable, reject)",
+        "--missing-invariants <p>".green()
+    );
     println!();
     println!("  - {}", "If a file is provided:".bold());
     println!(
         "    - {}: Parses as a Network System (NS), saves as graphviz, converts to Petri net and saves that as graphviz and .net",
         ".json extension".yellow()
     );
+    println!(
+        "    - {}: Same schema as json files, written in YAML or TOML",
+        ".yaml/.yml/.toml extension".yellow()
+    );
     println!(
         "    - {}: Parses as an Expr, converts to NS, and processes it like json files",
         ".ser extension".yellow()
     );
+    println!(
+        "    - {}: Imports the PlusCal algorithm (one request type per process) and processes it like .ser files",
+        ".tla/.pcal extension".yellow()
+    );
+    println!(
+        "    - {}: Imports the rpc methods of each service, with handlers from `// @ser` comments",
+        ".proto extension".yellow()
+    );
     println!("  - {}", "If a directory is provided:".bold());
     println!(
-        "    - Recursively processes all {} and {} files in the directory and its subdirectories",
-        ".json".yellow(),
-        ".ser".yellow()
+        "    - Recursively processes all files of a supported type ({}) in the directory and its subdirectories",
+        frontend::supported_extensions().join(", ").yellow()
     );
     println!("  - {}:", "Output".bold());
     println!(
@@ -103,83 +314,855 @@ fn print_usage() {
     );
 }
 
+fn print_version() {
+    println!("ser {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "ISL {} ({})",
+        isl::version().unwrap_or_else(|| "(unknown version)".to_string()),
+        isl::prefix()
+    );
+    for (function, available) in isl::capabilities() {
+        if available {
+            println!("  {} {}", "✓".green(), function);
+        } else {
+            println!("  {} {} (unavailable)", "✗".red(), function);
+        }
+    }
+    println!("Features");
+    let features = [
+        ("isl", cfg!(feature = "isl")),
+        ("smpt", cfg!(feature = "smpt")),
+        ("viz", cfg!(feature = "viz")),
+    ];
+    for (feature, enabled) in features {
+        if enabled {
+            println!("  {} {}", "✓".green(), feature);
+        } else {
+            println!("  {} {} (disabled)", "✗".red(), feature);
+        }
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    // Subcommands
+    if args.get(1).map(String::as_str) == Some("micro-bench") {
+        if let Err(err) = micro_bench::run(&args[2..]) {
+            eprintln!("{}: {}", "Error".red().bold(), err);
+            process::exit(error::EXIT_USAGE);
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("query") {
+        if let Err(err) = query::run(&args[2..]) {
+            eprintln!("{}: {}", "Error".red().bold(), err);
+            process::exit(error::EXIT_USAGE);
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("cert-compare") {
+        if let Err(err) = cert_compare::run(&args[2..]) {
+            eprintln!("{}: {}", "Error".red().bold(), err);
+            process::exit(error::EXIT_USAGE);
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("isl-trace") {
+        if let Err(err) = isl_trace::run(&args[2..]) {
+            eprintln!("{}: {}", "Error".red().bold(), err);
+            process::exit(error::EXIT_USAGE);
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("info") {
+        let result = match &args[2..] {
+            [file_path] => load_file(file_path).map(|loaded| match loaded {
+                frontend::LoadedNS::Strings(ns) => info::print_info(&ns),
+                frontend::LoadedNS::Program(ns) => info::print_info(&ns),
+            }),
+            _ => Err(SerError::Usage("Usage: ser info <file>".to_string())),
+        };
+        if let Err(err) = result {
+            eprintln!("{}: {}", err.kind().red().bold(), err.message());
+            process::exit(err.exit_code());
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("new") {
+        if let Err(err) = templates::run(&args[2..]) {
+            eprintln!("{}: {}", err.kind().red().bold(), err.message());
+            process::exit(err.exit_code());
+        }
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("shrink") {
+        if let Err(err) = shrink::run(&args[2..]) {
+            eprintln!("{}: {}", err.kind().red().bold(), err.message());
+            process::exit(err.exit_code());
+        }
+        return;
+    }
+
+    // `ser compose <dir>` takes the same options as a normal run
+    let compose_mode = args.get(1).map(String::as_str) == Some("compose");
+
     // Parse command line flags
     let mut open_files = false;
     let mut optimize_enabled = true;
     let mut path_str = "";
     let mut create_certificate_mode = false;
     let mut check_certificate_mode = false;
+    let mut invariant_path: Option<String> = None;
 
-    // Skip the program name (args[0])
-    let mut i = 1;
+    // Skip the program name (args[0]) and the subcommand
+    let mut i = if compose_mode { 2 } else { 1 };
     while i < args.len() {
         match args[i].as_str() {
+            "--version" => {
+                print_version();
+                process::exit(0);
+            }
             "--open" => {
+                if !cfg!(feature = "viz") {
+                    eprintln!(
+                        "{}: --open has no effect, ser was built without the viz feature",
+                        "Warning".yellow().bold()
+                    );
+                }
                 open_files = true;
                 i += 1;
             }
-            "--no-viz" => {
-                graphviz::set_viz_enabled(false);
+            "--no-viz" => {
+                graphviz::set_viz_enabled(false);
+                i += 1;
+            }
+            "--check-smpt" => {
+                smpt::ensure_smpt_available();
+                process::exit(0);
+            }
+            "--without-bidirectional" => {
+                optimize_enabled = false;
+                i += 1;
+            }
+            "--create-certificate" => {
+                create_certificate_mode = true;
+                i += 1;
+            }
+            "--without-read-only-fast-path" => {
+                ns::set_read_only_fast_path(false);
+                i += 1;
+            }
+            "--without-expr-simplification" => {
+                expr_to_ns::set_simplify(false);
+                i += 1;
+            }
+            "--witness" => {
+                witness::set_witness(true);
+                i += 1;
+            }
+            "--latex" => {
+                ns_decision::set_latex_export(true);
+                i += 1;
+            }
+            "--semilinear-format" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --semilinear-format requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match semilinear::SemilinearFormat::from_name(&args[i]) {
+                    Some(format) => {
+                        semilinear::set_semilinear_format(format);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid semilinear format '{}' (expected text, latex or markdown)",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--simulate" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --simulate requires a number of runs", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<usize>() {
+                    Ok(runs) => {
+                        ns::set_simulation_runs(runs);
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid number of simulation runs '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--bounded-search" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --bounded-search requires a depth",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<usize>() {
+                    Ok(depth) => {
+                        bounded_search::set_bounded_search_depth(Some(depth));
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid bounded search depth '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--bounded-search-states" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --bounded-search-states requires a number of markings",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<usize>() {
+                    Ok(states) => {
+                        bounded_search::set_bounded_search_states(states);
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid number of markings '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--simulate-guided" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --simulate-guided requires a number of runs",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<usize>() {
+                    Ok(runs) => {
+                        ns::set_guided_simulation_runs(runs);
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid number of simulation runs '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--explain" => {
+                explain::set_explain(true);
+                i += 1;
+            }
+            "--commutativity" => {
+                commutativity::set_commutativity(true);
+                i += 1;
+            }
+            "--quantitative" => {
+                quantitative::set_quantitative(true);
+                i += 1;
+            }
+            "--check" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --check requires a property", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].as_str() {
+                    "quiescence" => quiescence::set_check_quiescence(true),
+                    property => {
+                        eprintln!("{}: Unknown property '{}'", "Error".red().bold(), property);
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+                i += 1;
+            }
+            "--all-initial-states" => {
+                initial_states::set_initial_states(Some(initial_states::InitialStates::All));
+                i += 1;
+            }
+            "--initial-states" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --initial-states requires a comma-separated list of global states",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                let names = args[i]
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                initial_states::set_initial_states(Some(initial_states::InitialStates::List(names)));
+                i += 1;
+            }
+            "--cross-validate" => {
+                cross_validate::set_cross_validate(true);
+                i += 1;
+            }
+            "--check-certificate" => {
+                check_certificate_mode = true;
+                i += 1;
+            }
+            "--trace-isl" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --trace-isl requires a file", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                isl_trace::set_isl_trace(Some(args[i + 1].clone()));
+                i += 2;
+            }
+            "--invariant" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --invariant requires a file", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                invariant_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--timeout" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --timeout requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<u64>() {
+                    Ok(timeout) => {
+                        smpt::set_smpt_timeout(timeout);
+                        println!("Set SMPT timeout to {} seconds", timeout);
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid timeout value '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--auto-timeout" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --auto-timeout requires a number of seconds",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<u64>() {
+                    Ok(budget) => {
+                        reachability::set_auto_timeout(Some(budget));
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid time budget '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--compress-certificate" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --compress-certificate requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match ns_decision::CertificateCompression::from_name(&args[i]) {
+                    Some(compression) => {
+                        ns_decision::set_certificate_compression(compression);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid certificate compression '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--inductiveness-check" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --inductiveness-check requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match ns_decision::InductivenessCheck::from_name(&args[i]) {
+                    Some(check) => {
+                        ns_decision::set_inductiveness_check(check);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid inductiveness check '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--missing-invariants" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --missing-invariants requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match ns_decision::MissingInvariants::from_name(&args[i]) {
+                    Some(policy) => {
+                        ns_decision::set_missing_invariants(policy);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid missing invariants policy '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--check-backend" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --check-backend requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match ns_decision::CheckBackend::from_name(&args[i]) {
+                    Some(backend) => {
+                        ns_decision::set_check_backend(backend);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid check backend '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--size-threshold" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --size-threshold requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match size_logger::SizeThreshold::parse(&args[i]) {
+                    Ok(threshold) => {
+                        size_logger::add_size_threshold(threshold);
+                        i += 1;
+                    }
+                    Err(err) => {
+                        eprintln!("{}: {}", "Error".red().bold(), err);
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--max-certificate-size" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --max-certificate-size requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match args[i].parse::<u64>() {
+                    Ok(max_bytes) => {
+                        ns_decision::set_max_certificate_size(Some(max_bytes));
+                        i += 1;
+                    }
+                    Err(_) => {
+                        eprintln!(
+                            "{}: Invalid certificate size '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--without-remove-redundant" => {
+                semilinear::set_remove_redundant(false);
+                i += 1;
+            }
+            "--without-generate-less" => {
+                semilinear::set_generate_less(false);
+                i += 1;
+            }
+            "--semilinear-strategy" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --semilinear-strategy requires a value",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match semilinear::SemilinearStrategy::from_name(&args[i]) {
+                    Some(strategy) => semilinear::set_semilinear_strategy(strategy),
+                    None => {
+                        eprintln!(
+                            "{}: Unknown semilinear strategy '{}' (expected exact, generate-less or aggressive)",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+                i += 1;
+            }
+            "--without-smart-kleene-order" => {
+                kleene::set_smart_kleene_order(false);
                 i += 1;
             }
-            "--check-smpt" => {
-                smpt::ensure_smpt_available();
-                process::exit(0);
+            "--kleene-order" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --kleene-order requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match kleene::KleeneOrder::from_name(&args[i]) {
+                    Some(order) => kleene::set_kleene_order(order),
+                    None => {
+                        eprintln!(
+                            "{}: Unknown Kleene order '{}' (expected arbitrary, degree, weight, annealing or auto)",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+                i += 1;
             }
-            "--without-bidirectional" => {
-                optimize_enabled = false;
+            "--use-cache" => {
+                smpt::set_use_cache(true);
                 i += 1;
             }
-            "--create-certificate" => {
-                create_certificate_mode = true;
+            "--serialized-dot" => {
+                graphviz::set_serialized_automaton_dot(true);
                 i += 1;
             }
-            "--check-certificate" => {
-                check_certificate_mode = true;
+            "--resume" => {
+                reachability::set_resume(true);
                 i += 1;
             }
-            "--timeout" => {
+            "--suggest-fix" => {
+                repair::set_suggest_fix(true);
+                i += 1;
+            }
+            "--serializable-subset" => {
+                serializable_subset::set_serializable_subset(true);
+                i += 1;
+            }
+            "--all-counterexamples" => {
+                counterexamples::set_all_counterexamples(true);
+                i += 1;
+            }
+            "--trace-detail" => {
                 if i + 1 >= args.len() {
-                    eprintln!("{}: --timeout requires a value", "Error".red().bold());
+                    eprintln!("{}: --trace-detail requires a value", "Error".red().bold());
                     print_usage();
-                    process::exit(1);
+                    process::exit(error::EXIT_USAGE);
                 }
                 i += 1;
-                match args[i].parse::<u64>() {
-                    Ok(timeout) => {
-                        smpt::set_smpt_timeout(timeout);
-                        println!("Set SMPT timeout to {} seconds", timeout);
+                match ns_decision::TraceDetail::from_name(&args[i]) {
+                    Some(detail) => {
+                        ns_decision::set_trace_detail(detail);
                         i += 1;
                     }
-                    Err(_) => {
+                    None => {
                         eprintln!(
-                            "{}: Invalid timeout value '{}'",
+                            "{}: Invalid trace detail '{}' (expected petri, ns or source)",
                             "Error".red().bold(),
                             args[i]
                         );
                         print_usage();
-                        process::exit(1);
+                        process::exit(error::EXIT_USAGE);
                     }
                 }
             }
-            "--without-remove-redundant" => {
-                semilinear::set_remove_redundant(false);
+            "--diagnose" => {
+                diagnosis::set_diagnose(true);
                 i += 1;
             }
-            "--without-generate-less" => {
-                semilinear::set_generate_less(false);
+            "--dry-run" => {
+                reachability::set_dry_run(true);
                 i += 1;
             }
-            "--without-smart-kleene-order" => {
-                kleene::set_smart_kleene_order(false);
+            "--absint" => {
+                absint::set_absint(true);
                 i += 1;
             }
-            "--use-cache" => {
-                smpt::set_use_cache(true);
+            "--export-lp" => {
+                marking_equation::set_export_marking_equation(true);
+                i += 1;
+            }
+            "--lp-prefilter" => {
+                marking_equation::set_lp_prefilter(true);
+                i += 1;
+            }
+            "--export-smpt-queries" => {
+                smpt_offline::set_offline_mode(smpt_offline::OfflineMode::Export);
+                i += 1;
+            }
+            "--import-smpt-results" => {
+                if i + 1 >= args.len() {
+                    eprintln!(
+                        "{}: --import-smpt-results requires a directory",
+                        "Error".red().bold()
+                    );
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                smpt_offline::set_offline_mode(smpt_offline::OfflineMode::Import(args[i].clone()));
+                i += 1;
+            }
+            #[cfg(feature = "fake-smpt")]
+            "--fake-smpt" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --fake-smpt requires a spec", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match smpt_fake::FakeSmpt::from_spec(&args[i]) {
+                    Ok(fake) => {
+                        smpt_fake::set_fake_smpt(Some(fake));
+                        i += 1;
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "{}: Invalid --fake-smpt spec: {}",
+                            "Error".red().bold(),
+                            err
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--naming" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --naming requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match naming::NamingScheme::from_name(&args[i]) {
+                    Some(scheme) => naming::set_naming_scheme(scheme),
+                    None => {
+                        eprintln!(
+                            "{}: Unknown naming scheme '{}' (expected short, full or hashed)",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+                i += 1;
+            }
+            "--lang-version" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --lang-version requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                let version = args[i]
+                    .strip_prefix("ser/")
+                    .unwrap_or(&args[i])
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid language version '{}'", args[i]))
+                    .and_then(parser::check_lang_version);
+                match version {
+                    Ok(version) => {
+                        parser::set_lang_version(Some(version));
+                        i += 1;
+                    }
+                    Err(err) => {
+                        eprintln!("{}: {}", "Error".red().bold(), err);
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--dim-order" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --dim-order requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match dim_order::DimOrder::from_name(&args[i]) {
+                    Some(order) => {
+                        if let dim_order::DimOrder::Random(seed) = order {
+                            println!("Random dimension order with seed {}", seed);
+                        }
+                        dim_order::set_dim_order(order);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid dimension order '{}' (expected sorted, frequency, structural or random[:seed])",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--model-crashes" => {
+                ns::set_model_crashes(true);
+                i += 1;
+            }
+            "--duplicates" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --duplicates requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
+                i += 1;
+                match ns::DuplicateResponses::from_name(&args[i]) {
+                    Some(mode) => {
+                        ns::set_duplicate_responses(mode);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid duplicates mode '{}' (expected multiset or set)",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
+            }
+            "--disjunct-order" => {
+                if i + 1 >= args.len() {
+                    eprintln!("{}: --disjunct-order requires a value", "Error".red().bold());
+                    print_usage();
+                    process::exit(error::EXIT_USAGE);
+                }
                 i += 1;
+                match reachability::DisjunctOrder::from_name(&args[i]) {
+                    Some(order) => {
+                        reachability::set_disjunct_order(order);
+                        i += 1;
+                    }
+                    None => {
+                        eprintln!(
+                            "{}: Invalid disjunct order '{}'",
+                            "Error".red().bold(),
+                            args[i]
+                        );
+                        print_usage();
+                        process::exit(error::EXIT_USAGE);
+                    }
+                }
             }
             _ => {
                 // If it's not a recognized flag, it must be the path
@@ -194,7 +1177,7 @@ fn main() {
                         args[i]
                     );
                     print_usage();
-                    process::exit(1);
+                    process::exit(error::EXIT_USAGE);
                 }
             }
         }
@@ -203,133 +1186,128 @@ fn main() {
     // Ensure we have a path
     if path_str.is_empty() {
         print_usage();
-        process::exit(1);
-    }
-
-    // Check for mutually exclusive flags
-    if create_certificate_mode && check_certificate_mode {
-        eprintln!(
-            "{}: Cannot use --create-certificate and --check-certificate together",
-            "Error".red().bold()
-        );
-        print_usage();
-        process::exit(1);
+        process::exit(error::EXIT_USAGE);
     }
 
     let path = Path::new(path_str);
 
     // Make the optimize flag available globally (via a simple static, or by passing it down).
     // Here we’ll use a simple static AtomicBool in reachability.rs (see next section).
-    crate::reachability::set_optimize_flag(optimize_enabled);
+    reachability::set_optimize_flag(optimize_enabled);
+
+    let result = if create_certificate_mode && check_certificate_mode {
+        Err(SerError::Usage(
+            "Cannot use --create-certificate and --check-certificate together".to_string(),
+        ))
+    } else if invariant_path.is_some() && (compose_mode || create_certificate_mode) {
+        Err(SerError::Usage(
+            "--invariant cannot be combined with ser compose or --create-certificate".to_string(),
+        ))
+    } else if compose_mode {
+        compose(path, create_certificate_mode || check_certificate_mode, open_files)
+    } else if create_certificate_mode || check_certificate_mode {
+        certificate_operation(path, create_certificate_mode)
+    } else if let Some(invariant_path) = &invariant_path {
+        check_invariant_for_file(path, invariant_path)
+    } else {
+        analyze(path, open_files)
+    };
+    if let Err(err) = result {
+        eprintln!("{}: {}", err.kind().red().bold(), err.message());
+        if let SerError::Usage(_) = err {
+            print_usage();
+        }
+        process::exit(err.exit_code());
+    }
+}
 
+fn check_exists(path: &Path) -> Result<(), SerError> {
     if !path.exists() {
-        eprintln!("{}: '{}' does not exist", "Error".red().bold(), path_str);
-        process::exit(1);
+        return Err(SerError::Usage(format!(
+            "'{}' does not exist",
+            path.display()
+        )));
     }
+    Ok(())
+}
 
-    // Handle certificate modes
-    if create_certificate_mode || check_certificate_mode {
-        if path.is_dir() {
-            eprintln!(
-                "{}: Certificate operations do not support directories",
-                "Error".red().bold()
-            );
-            process::exit(1);
-        }
+// `ser compose <dir>`
+fn compose(path: &Path, certificate_mode: bool, open_files: bool) -> Result<(), SerError> {
+    check_exists(path)?;
+    if !path.is_dir() {
+        return Err(SerError::Usage(format!(
+            "ser compose expects a directory, got '{}'",
+            path.display()
+        )));
+    }
+    if certificate_mode {
+        return Err(SerError::Usage(
+            "Certificate operations are not supported with ser compose".to_string(),
+        ));
+    }
+    process_composed_directory(path, open_files)
+}
 
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("json") => {
-                if create_certificate_mode {
-                    create_certificate_for_json_file(path_str);
-                } else {
-                    check_certificate_for_json_file(path_str);
-                }
-            }
-            Some("ser") => {
-                if create_certificate_mode {
-                    create_certificate_for_ser_file(path_str);
-                } else {
-                    check_certificate_for_ser_file(path_str);
-                }
-            }
-            _ => {
-                eprintln!(
-                    "{}: Unsupported file extension for '{}'. Please use {} or {}",
-                    "Error".red().bold(),
-                    path_str,
-                    ".json".yellow(),
-                    ".ser".yellow()
-                );
-                print_usage();
-                process::exit(1);
-            }
-        }
-        return;
+// --create-certificate and --check-certificate
+fn certificate_operation(path: &Path, create: bool) -> Result<(), SerError> {
+    check_exists(path)?;
+    if path.is_dir() {
+        return Err(SerError::Usage(
+            "Certificate operations do not support directories".to_string(),
+        ));
+    }
+    let path_str = path.to_string_lossy();
+    if create {
+        create_certificate_for_file(&path_str)
+    } else {
+        check_certificate_for_file(&path_str)
     }
+}
 
+// Analyze a file, or all files in a directory recursively
+fn analyze(path: &Path, open_files: bool) -> Result<(), SerError> {
+    check_exists(path)?;
     if path.is_dir() {
-        // Process directory recursively
-        match process_directory(path, open_files) {
-            Ok(count) => {
-                println!(
-                    "{} {} files",
-                    "Successfully processed".green().bold(),
-                    count
-                );
-            }
-            Err(err) => {
-                eprintln!("{} directory: {}", "Error processing".red().bold(), err);
-                process::exit(1);
-            }
-        }
+        let count = process_directory(path, open_files)?;
+        println!(
+            "{} {} files",
+            "Successfully processed".green().bold(),
+            count
+        );
+        Ok(())
     } else {
-        // Process single file
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("json") => process_json_file(path_str, open_files),
-            Some("ser") => process_ser_file(path_str, open_files),
-            _ => {
-                eprintln!(
-                    "{}: Unsupported file extension for '{}'. Please use {} or {}",
-                    "Error".red().bold(),
-                    path_str,
-                    ".json".yellow(),
-                    ".ser".yellow()
-                );
-                print_usage();
-                process::exit(1);
-            }
-        }
+        let path = path.to_string_lossy();
+        process_file(&path, &out_dir_for(&path), open_files)
     }
 }
 
 // Process a Network System: generate visualizations for NS, Petri net, and Petri net with requests
-fn process_ns<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>, out_dir: &str, open_files: bool)
+fn process_ns<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    out_dir: &str,
+    open_files: bool,
+) -> Result<(), SerError>
 where
     G: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
     L: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
     Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
     Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
 {
-    // Clear the output directory if it exists
-    if Path::new(out_dir).exists() {
+    if reachability::dry_run_enabled() {
+        ns.print_dry_run_estimate();
+        return Ok(());
+    }
+
+    // Clear the output directory if it exists (unless resuming, which needs the disjunct journal)
+    if Path::new(out_dir).exists() && !reachability::resume_enabled() {
         if let Err(err) = fs::remove_dir_all(out_dir) {
-            eprintln!(
-                "{} existing output directory: {}",
-                "Failed to clear".red().bold(),
-                err
-            );
-            process::exit(1);
+            return Err(SerError::IoError(format!("Failed to clear existing output directory: {}", err)));
         }
     }
 
     // Create the output directory
     if let Err(err) = utils::file::ensure_dir_exists(out_dir) {
-        eprintln!(
-            "{} output directory: {}",
-            "Failed to create".red().bold(),
-            err
-        );
-        process::exit(1);
+        return Err(SerError::IoError(format!("Failed to create output directory: {}", err)));
     }
 
     // Generate GraphViz output for the Network System
@@ -352,12 +1330,7 @@ where
                 }
             }
             Err(err) => {
-                eprintln!(
-                    "{} NS visualization: {}",
-                    "Failed to save".red().bold(),
-                    err
-                );
-                process::exit(1);
+                return Err(SerError::IoError(format!("Failed to save NS visualization: {}", err)));
             }
         }
     }
@@ -370,49 +1343,7 @@ where
         "Converting to Petri net...".cyan().bold()
     );
     let petri = ns_to_petri::ns_to_petri(ns);
-
-    // Generate Petri net visualization
-    if graphviz::viz_enabled() {
-        println!(
-            "{} {}",
-            "🎨".cyan(),
-            "Generating Petri net visualization...".cyan().bold()
-        );
-        match petri.save_graphviz(out_dir, open_files) {
-            Ok(files) => {
-                println!(
-                    "{} the following Petri net files:",
-                    "Successfully generated".green().bold()
-                );
-                for file in files {
-                    println!("- {}", file.green());
-                }
-            }
-            Err(err) => {
-                eprintln!(
-                    "{} Petri net visualization: {}",
-                    "Failed to save".red().bold(),
-                    err
-                );
-                process::exit(1);
-            }
-        }
-    }
-
-    // Output Petri net in .net format
-    let pnet_content = crate::smpt::petri_to_pnet(&petri, "petri");
-    let pnet_file = format!("{}/petri.net", out_dir);
-    match utils::file::safe_write_file(&pnet_file, &pnet_content) {
-        Ok(_) => println!("- {}", pnet_file.green()),
-        Err(err) => {
-            eprintln!(
-                "{} Petri net in .net format: {}",
-                "Failed to save".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    }
+    export_petri(&petri, out_dir, "petri", "Petri net", open_files)?;
 
     // Convert to Petri net with requests
     println!();
@@ -422,140 +1353,213 @@ where
         "Converting to Petri net with requests...".cyan().bold()
     );
     let petri_with_requests = ns_to_petri::ns_to_petri_with_requests(ns);
-
-    // Generate visualization if enabled
-    if graphviz::viz_enabled() {
-        println!(
-            "{} {}",
-            "🎨".cyan(),
-            "Generating Petri net with requests visualization...".cyan().bold()
-        );
-        
-        // Use the same output directory for Petri net with requests
-        // Create a custom method or modify the underlying implementation to use a different viz_type
-        // For now, we need to make a direct call to the graphviz module
-        let dot_content = petri_with_requests.to_graphviz();
-        match crate::graphviz::save_graphviz(&dot_content, out_dir, "petri_with_requests", open_files) {
+    export_petri(
+        &petri_with_requests,
+        out_dir,
+        "petri_with_requests",
+        "Petri net with requests",
+        open_files,
+    )?;
+
+    // Output the serialized automaton itself, if requested
+    if graphviz::serialized_automaton_dot() {
+        match ns.save_serialized_automaton_graphviz(out_dir, open_files) {
             Ok(files) => {
-                println!(
-                    "{} the following Petri net with requests files:",
-                    "Successfully generated".green().bold()
-                );
                 for file in files {
                     println!("- {}", file.green());
                 }
             }
             Err(err) => {
-                eprintln!(
-                    "{} Petri net with requests visualization: {}",
-                    "Failed to save".red().bold(),
-                    err
-                );
-                process::exit(1);
+                return Err(SerError::IoError(format!("Failed to save serialized automaton visualization: {}", err)));
             }
         }
     }
 
-    // Output Petri net with requests in .net format
-    let pnet_req_content = crate::smpt::petri_to_pnet(&petri_with_requests, "petri_with_requests");
-    let pnet_req_file = format!("{}/petri_with_requests.net", out_dir);
-    match utils::file::safe_write_file(&pnet_req_file, &pnet_req_content) {
-        Ok(_) => println!("- {}", pnet_req_file.green()),
-        Err(err) => {
-            eprintln!(
-                "{} Petri net with requests in .net format: {}",
-                "Failed to save".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
+    // Save the semilinear set itself for `ser query`
+    let semilinear = ns.serialized_automaton_semilinear();
+    match query::save_semilinear(out_dir, &semilinear) {
+        Ok(file) => println!("- {}", file.green()),
+        Err(err) => eprintln!(
+            "{} semilinear set for queries: {}",
+            "Failed to save".red().bold(),
+            err
+        ),
     }
 
-    // Output the Regex to semilinear.txt
-    let regex = ns.serialized_automaton_regex();
-    let regex_file = format!("{}/semilinear.txt", out_dir);
+    // Output the (simplified) Regex to semilinear.txt
+    let regex = ns.serialized_automaton_regex().simplify();
+    let regex_file = utils::file::join(out_dir, "semilinear.txt");
     let mut regex_content = String::new();
     regex_content.push_str(&format!("Regex: {}\n", regex));
     regex_content.push_str(&format!(
-        "Semilinear:\n{}\n",
-        ns.serialized_automaton_semilinear()
+        "Semilinear:\n{}",
+        semilinear.pretty(semilinear::SemilinearFormat::Text)
     ));
     match utils::file::safe_write_file(&regex_file, &regex_content) {
         Ok(_) => println!("- {}", regex_file.green()),
         Err(err) => {
-            eprintln!(
-                "{} Regex in semilinear format: {}",
+            return Err(SerError::IoError(format!("Failed to save Regex in semilinear format: {}", err)));
+        }
+    }
+
+    // The semilinear set as a table, if requested
+    let format = semilinear::semilinear_format();
+    if format != semilinear::SemilinearFormat::Text {
+        let table_file = utils::file::join(out_dir, &format!("semilinear.{}", format.extension()));
+        match utils::file::safe_write_file(&table_file, &semilinear.pretty(format).to_string()) {
+            Ok(_) => println!("- {}", table_file.green()),
+            Err(err) => eprintln!(
+                "{} semilinear table: {}",
                 "Failed to save".red().bold(),
                 err
-            );
-            process::exit(1);
+            ),
         }
     }
 
-    // Check serializability
-    println!();
-    // Run serializability analysis (this prints all results internally)
-    let _ = ns.is_serializable(out_dir);
-    stats::finalize_stats();
+    // Check serializability
+    println!();
+    // Run serializability analysis (this prints all results internally). ISL reports
+    // failures by panicking, which are turned into errors here.
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ns.is_serializable(out_dir)))
+        .map_err(|payload| {
+            error::from_panic(payload.as_ref())
+                .unwrap_or_else(|| std::panic::resume_unwind(payload))
+        })?;
+    stats::finalize_stats();
+
+    let decision = ns_decision::NSDecision::load_from_file(ns_decision::certificate_path(out_dir))
+        .map_err(|err| SerError::CertificateError(format!("Failed to load certificate: {}", err)))?;
+
+    // The subsystem checks of the diagnosis are not part of the run statistics
+    if diagnosis::diagnose_enabled() {
+        let _ = diagnosis::diagnose(ns, &decision, out_dir);
+    }
+
+    if counterexamples::all_counterexamples_enabled() {
+        counterexamples::report(ns, &decision, out_dir);
+    }
+
+    if serializable_subset::serializable_subset_enabled() {
+        serializable_subset::report(ns, &decision, out_dir);
+    }
+
+    if let Some(states) = initial_states::initial_states() {
+        initial_states::analyze_initial_states(ns, &states, out_dir)?;
+    }
+
+    inconclusive_error(&decision)
 }
 
-fn process_json_file(file_path: &str, open_files: bool) {
-    println!("{} {}", "Processing JSON file:".blue().bold(), file_path);
-    
-    // Initialize stats collection
-    stats::start_analysis(file_path.to_string());
+// An analysis that ended without a verdict is an error for scripts
+fn inconclusive_error<G, L, Req, Resp>(
+    decision: &ns_decision::NSDecision<G, L, Req, Resp>,
+) -> Result<(), SerError>
+where
+    G: Eq + Hash,
+    L: Eq + Hash,
+    Req: Eq + Hash,
+    Resp: Eq + Hash,
+{
+    match decision {
+        ns_decision::NSDecision::Timeout { message } => Err(SerError::Timeout(message.clone())),
+        ns_decision::NSDecision::Unknown { reason, .. } => Err(SerError::SmptError(format!(
+            "Analysis inconclusive: {}",
+            reason
+        ))),
+        _ => Ok(()),
+    }
+}
 
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
+// Save a Petri net as <out_dir>/<name>.net, and visualize it if enabled
+fn export_petri<P>(
+    petri: &petri::Petri<P>,
+    out_dir: &str,
+    name: &str,
+    description: &str,
+    open_files: bool,
+) -> Result<(), SerError>
+where
+    P: Clone + Eq + Hash + Display,
+{
+    if graphviz::viz_enabled() {
+        println!(
+            "{} {}",
+            "🎨".cyan(),
+            format!("Generating {} visualization...", description).cyan().bold()
+        );
+        let files = graphviz::save_graphviz(&petri.to_graphviz(), out_dir, name, open_files)
+            .map_err(|err| {
+                SerError::IoError(format!("Failed to save {} visualization: {}", description, err))
+            })?;
+        println!(
+            "{} the following {} files:",
+            "Successfully generated".green().bold(),
+            description
+        );
+        for file in files {
+            println!("- {}", file.green());
         }
-    };
+    }
 
-    // Parse the JSON as a Network System
-    let ns = match NS::<String, String, String, String>::from_json(&content) {
-        Ok(ns) => ns,
-        Err(err) => {
-            eprintln!(
-                "{} JSON as Network System: {}",
-                "Error parsing".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    };
+    let pnet_file = utils::file::join(out_dir, &format!("{}.net", name));
+    utils::file::safe_write_file(&pnet_file, &smpt::petri_to_pnet(petri, name)).map_err(|err| {
+        SerError::IoError(format!("Failed to save {} in .net format: {}", description, err))
+    })?;
+    println!("- {}", pnet_file.green());
+    Ok(())
+}
 
-    // Get the file name without extension to use as the base name for output files
+// Load a file with the front end that accepts it
+fn load_file(file_path: &str) -> Result<frontend::LoadedNS, SerError> {
     let path = Path::new(file_path);
-    let file_stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("network");
-    let out_dir = format!("out/{}", file_stem);
+    let front_end = frontend::find(path).ok_or_else(|| {
+        let extensions: Vec<String> = frontend::supported_extensions()
+            .iter()
+            .map(|ext| format!(".{}", ext).yellow().to_string())
+            .collect();
+        SerError::Usage(format!(
+            "Unsupported file extension for '{}'. Please use {}",
+            file_path,
+            extensions.join(", ")
+        ))
+    })?;
+    front_end.load(path).map_err(|err| {
+        SerError::ParseError(format!("Failed to load {} file: {}", front_end.name(), err))
+    })
+}
 
-    // Process the Network System
-    process_ns(&ns, &out_dir, open_files);
-    
-    // Print cache statistics if caching is enabled
-    if smpt::is_cache_enabled() {
-        smpt::print_cache_stats();
-    }
+// Output directory of an input file analyzed on its own: out/<stem>
+fn out_dir_for(file_path: &str) -> String {
+    utils::file::output_dir(Path::new(file_path), None)
+}
 
-    // Copy this JSON into out/<stem>/<stem>.json after processing
-    let dst_json = format!("{}/{}.json", out_dir, file_stem);
-    if let Err(err) = fs::copy(file_path, &dst_json) {
-        eprintln!("{} JSON file: {}", "Failed to copy".red().bold(), err);
-    }
-    
-    // Finalize stats collection
-    stats::finalize_stats();
+// Output directory holding the certificate of a file: out/<stem>, or where a directory
+// analysis put it. Prefer a directory with a copy of this very input, since files with
+// the same stem in other directories have their own output directories.
+fn certificate_dir_for(file_path: &str) -> String {
+    let with_certificate: Vec<String> = utils::file::output_dir_candidates(Path::new(file_path))
+        .into_iter()
+        .filter(|dir| ns_decision::find_certificate(dir).is_some())
+        .collect();
+    let content = fs::read(file_path).ok();
+    let copy_of_input = |dir: &String| {
+        let copy = Path::new(file_path)
+            .file_name()
+            .map(|name| Path::new(dir).join(name));
+        content.is_some() && copy.and_then(|copy| fs::read(copy).ok()) == content
+    };
+    with_certificate
+        .iter()
+        .find(|dir| copy_of_input(dir))
+        .or(with_certificate.first())
+        .cloned()
+        .unwrap_or_else(|| out_dir_for(file_path))
 }
 
-fn process_ser_file(file_path: &str, open_files: bool) {
+fn process_file(file_path: &str, out_dir: &str, open_files: bool) -> Result<(), SerError> {
     // Initialize stats collection
     stats::start_analysis(file_path.to_string());
-    
+
     println!();
     println!(
         "{}",
@@ -566,99 +1570,83 @@ fn process_ser_file(file_path: &str, open_files: bool) {
     println!(
         "{} {} {}",
         "📄".blue(),
-        "Processing Ser file:".blue().bold(),
+        "Processing file:".blue().bold(),
         file_path.cyan()
     );
 
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
-        }
-    };
-
-    // Try to parse as a program with multiple requests first
-    let mut table = ExprHc::new();
-    let ns = match parse_program(&content, &mut table) {
-        Ok(program) => {
-            println!(
-                "{} {} requests",
-                "Parsed program with".blue().bold(),
-                program.requests.len()
-            );
-            // Convert program to Network System
-            println!(
-                "{}",
-                "Converting program to Network System...".cyan().bold()
-            );
-            expr_to_ns::program_to_ns(&mut table, &program)
-        }
-        Err(_) => {
-            // Fall back to parsing as a single expression
-            match parse(&content, &mut table) {
-                Ok(expr) => {
-                    println!("{} {}", "Parsed expression:".blue().bold(), expr);
-                    // Convert expression to Network System
-                    println!(
-                        "{}",
-                        "Converting expression to Network System...".cyan().bold()
-                    );
-                    expr_to_ns::program_to_ns(
-                        &mut table,
-                        &Program {
-                            requests: vec![Request {
-                                name: "request".to_string(),
-                                body: expr,
-                            }],
-                        },
-                    )
-                }
-                Err(err) => {
-                    eprintln!("{} SER file: {}", "Error parsing".red().bold(), err);
-                    process::exit(1);
-                }
-            }
-        }
+    // Process the Network System
+    let result = match load_file(file_path)? {
+        frontend::LoadedNS::Strings(ns) => process_ns(&ns, out_dir, open_files),
+        frontend::LoadedNS::Program(ns) => process_ns(&ns, out_dir, open_files),
     };
+    if result.is_ok() && repair::suggest_fix_enabled() {
+        let _ = repair::suggest_fix(Path::new(file_path), out_dir);
+    }
 
-    // Get the file name without extension to use as the base name for output files
-    let path = Path::new(file_path);
-    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("expr");
-    let out_dir = format!("out/{}", file_stem);
-
-    // Process the Network System
-    process_ns(&ns, &out_dir, open_files);
-    
     // Print cache statistics if caching is enabled
     if smpt::is_cache_enabled() {
         smpt::print_cache_stats();
     }
 
-    // Copy this SER into out/<stem>/<stem>.ser after processing
-    let dst_ser = format!("{}/{}.ser", out_dir, file_stem);
-    if let Err(err) = fs::copy(file_path, &dst_ser) {
-        eprintln!("{} SER file: {}", "Failed to copy".red().bold(), err);
+    // Copy the input file into its output directory after processing
+    if let Some(file_name) = Path::new(file_path).file_name() {
+        let dst = Path::new(out_dir).join(file_name);
+        if let Err(err) = fs::copy(file_path, &dst) {
+            eprintln!("{} input file: {}", "Failed to copy".red().bold(), err);
+        }
     }
-    
+
     // Finalize stats collection
     stats::finalize_stats();
+    result
+}
+
+// Recursively process all files in a directory and its subdirectories. A file that
+// fails does not stop the others; the first failure is returned at the end. Outputs
+// mirror the directory structure: `<dir>/a/model.ser` is written to `out/a/model`,
+// and `out/index.json` summarizes all files.
+fn process_directory(dir: &Path, open_files: bool) -> Result<usize, SerError> {
+    fs::read_dir(dir).map_err(|err| {
+        SerError::IoError(format!("Error reading directory '{}': {}", dir.display(), err))
+    })?;
+    let mut first_error = None;
+    let mut index = manifest::Index::new(&dir.to_string_lossy());
+    let processed_count = visit_directory(dir, dir, open_files, &mut index, &mut first_error);
+    match manifest::save_index(&index, graphviz::viz_enabled()) {
+        Ok(files) => {
+            for file in files {
+                println!("- {}", file.green());
+            }
+        }
+        Err(err) => eprintln!("{} index: {}", "Failed to save".red().bold(), err),
+    }
+    match first_error {
+        Some(err) => Err(err),
+        None => Ok(processed_count),
+    }
 }
 
-// Recursively process all files in a directory and its subdirectories
-fn process_directory(dir: &Path, open_files: bool) -> Result<usize, String> {
+fn visit_directory(
+    root: &Path,
+    dir: &Path,
+    open_files: bool,
+    index: &mut manifest::Index,
+    first_error: &mut Option<SerError>,
+) -> usize {
     let mut processed_count = 0;
 
     // Read directory contents
     let entries = match fs::read_dir(dir) {
         Ok(entries) => entries,
         Err(err) => {
-            return Err(format!(
-                "{} directory '{}': {}",
-                "Error reading".red().bold(),
+            let err = SerError::IoError(format!(
+                "Error reading directory '{}': {}",
                 dir.display(),
                 err
             ));
+            eprintln!("{}: {}", "Warning".yellow().bold(), err);
+            first_error.get_or_insert(err);
+            return 0;
         }
     };
 
@@ -680,136 +1668,116 @@ fn process_directory(dir: &Path, open_files: bool) -> Result<usize, String> {
 
         if path.is_dir() {
             // Recursively process subdirectory
-            match process_directory(&path, open_files) {
-                Ok(count) => processed_count += count,
-                Err(err) => eprintln!("{}: {}", "Warning".yellow().bold(), err),
-            }
+            processed_count += visit_directory(root, &path, open_files, index, first_error);
         } else if path.is_file() {
-            // Process file if it has a supported extension
-            if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
-                let path_str = path.to_string_lossy().to_string();
-
-                match ext {
-                    "json" => {
-                        process_json_file(&path_str, open_files);
-                        processed_count += 1;
-                    }
-                    "ser" => {
-                        process_ser_file(&path_str, open_files);
-                        processed_count += 1;
-                    }
-                    _ => {} // Skip files with unsupported extensions
+            // Process file if a front end accepts it, skip it otherwise
+            if frontend::find(&path).is_some() {
+                let file = path.to_string_lossy();
+                let out_dir = utils::file::output_dir(&path, Some(root));
+                let start = std::time::Instant::now();
+                let result = process_file(&file, &out_dir, open_files);
+                // A file that fails to load never finalizes its statistics
+                let stats = stats::last_finished().filter(|stats| stats.example == file);
+                index.push(manifest::IndexEntry::new(
+                    &file,
+                    &out_dir,
+                    stats.as_ref(),
+                    result.as_ref().err(),
+                    start.elapsed().as_millis() as u64,
+                ));
+                if let Err(err) = result {
+                    eprintln!("{}: {}", "Warning".yellow().bold(), err);
+                    first_error.get_or_insert(err);
                 }
+                processed_count += 1;
                 println!();
             }
         }
     }
 
-    Ok(processed_count)
+    processed_count
 }
 
-// Certificate creation functions
-fn create_certificate_for_ser_file(file_path: &str) {
-    println!();
-    println!(
-        "{}",
-        "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
-            .blue()
-            .bold()
-    );
-    println!(
-        "{} {} {}",
-        "🔐".blue(),
-        "Creating certificate for Ser file:".blue().bold(),
-        file_path.cyan()
-    );
-
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
+// Merge all .ser files in a directory (one per service) into a single program and analyze it
+fn process_composed_directory(dir: &Path, open_files: bool) -> Result<(), SerError> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("ser"))
+            .collect(),
         Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
+            return Err(SerError::IoError(format!("Error reading directory: {}", err)));
         }
     };
+    files.sort();
+    if files.is_empty() {
+        return Err(SerError::Usage(format!(
+            "No .ser files found in '{}'",
+            dir.display()
+        )));
+    }
 
-    // Try to parse as a program with multiple requests first
-    let mut table = ExprHc::new();
-    let ns = match parse_program(&content, &mut table) {
-        Ok(program) => {
-            println!(
-                "{} {} requests",
-                "Parsed program with".blue().bold(),
-                program.requests.len()
-            );
-            expr_to_ns::program_to_ns(&mut table, &program)
-        }
-        Err(_) => {
-            // Fall back to parsing as a single expression
-            match parse(&content, &mut table) {
-                Ok(expr) => {
-                    println!("{} {}", "Parsed expression:".blue().bold(), expr);
-                    expr_to_ns::program_to_ns(
-                        &mut table,
-                        &Program {
-                            requests: vec![Request {
-                                name: "request".to_string(),
-                                body: expr,
-                            }],
-                        },
-                    )
-                }
-                Err(err) => {
-                    eprintln!("{} SER file: {}", "Error parsing".red().bold(), err);
-                    process::exit(1);
-                }
-            }
-        }
-    };
+    let dir_name = dir
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("composed")
+        .to_string();
+    stats::start_analysis(dir.to_string_lossy().to_string());
 
-    // Get the file name without extension
-    let path = Path::new(file_path);
-    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("expr");
-    let out_dir = format!("out/{}", file_stem);
+    println!();
+    println!(
+        "{} {} {}",
+        "📄".blue(),
+        "Composing Ser files in:".blue().bold(),
+        dir.display().to_string().cyan()
+    );
 
-    // Create output directory
-    if let Err(err) = utils::file::ensure_dir_exists(&out_dir) {
-        eprintln!(
-            "{} output directory: {}",
-            "Failed to create".red().bold(),
-            err
+    // Each file becomes a namespace named after its file stem
+    let mut table = ExprHc::new();
+    let mut parts = Vec::new();
+    for file in &files {
+        let content = fs::read_to_string(file).map_err(|err| {
+            SerError::IoError(format!("Error reading {}: {}", file.display(), err))
+        })?;
+        let program = parse_program(&content, &mut table).map_err(|err| {
+            SerError::ParseError(format!("Error parsing {}: {}", file.display(), err))
+        })?;
+        let namespace = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("service")
+            .to_string();
+        println!(
+            "  {} {} ({} requests)",
+            "Service".blue().bold(),
+            namespace,
+            program.requests.len()
         );
-        process::exit(1);
+        parts.push((namespace, program));
     }
 
-    // Create the certificate
+    let program = parser::compose_programs(parts)
+        .map_err(|err| SerError::ParseError(format!("Error composing programs: {}", err)))?;
     println!(
-        "{}",
-        "Running serializability analysis...".cyan().bold()
+        "{} {} requests, {} declared globals",
+        "Composed program with".blue().bold(),
+        program.requests.len(),
+        program.globals.len()
     );
-    let decision = ns.create_certificate(&out_dir);
 
-    // Save the certificate
-    let cert_path = format!("{}/certificate.json", out_dir);
-    match decision.save_to_file(&cert_path) {
-        Ok(_) => {
-            println!(
-                "{} certificate to: {}",
-                "Successfully saved".green().bold(),
-                cert_path.green()
-            );
-        }
-        Err(err) => {
-            eprintln!(
-                "{} certificate: {}",
-                "Failed to save".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
+    let ns = expr_to_ns::program_to_ns(&mut table, &program);
+    let out_dir = utils::file::join(utils::file::OUT_DIR, &dir_name);
+    let result = process_ns(&ns, &out_dir, open_files);
+
+    if smpt::is_cache_enabled() {
+        smpt::print_cache_stats();
     }
+    stats::finalize_stats();
+    result
 }
 
-fn create_certificate_for_json_file(file_path: &str) {
+// Certificate creation functions
+fn create_certificate_for_file(file_path: &str) -> Result<(), SerError> {
     println!();
     println!(
         "{}",
@@ -820,81 +1788,57 @@ fn create_certificate_for_json_file(file_path: &str) {
     println!(
         "{} {} {}",
         "🔐".blue(),
-        "Creating certificate for JSON file:".blue().bold(),
+        "Creating certificate for file:".blue().bold(),
         file_path.cyan()
     );
 
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
-        }
-    };
-
-    // Parse the JSON as a Network System
-    let ns = match NS::<String, String, String, String>::from_json(&content) {
-        Ok(ns) => ns,
-        Err(err) => {
-            eprintln!(
-                "{} JSON as Network System: {}",
-                "Error parsing".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    };
-
-    // Get the file name without extension
-    let path = Path::new(file_path);
-    let file_stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("network");
-    let out_dir = format!("out/{}", file_stem);
+    let out_dir = out_dir_for(file_path);
+    match load_file(file_path)? {
+        frontend::LoadedNS::Strings(ns) => create_certificate(&ns, &out_dir),
+        frontend::LoadedNS::Program(ns) => create_certificate(&ns, &out_dir),
+    }
+}
 
+fn create_certificate<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Result<(), SerError>
+where
+    G: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    L: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+{
     // Create output directory
-    if let Err(err) = utils::file::ensure_dir_exists(&out_dir) {
-        eprintln!(
-            "{} output directory: {}",
-            "Failed to create".red().bold(),
-            err
-        );
-        process::exit(1);
-    }
+    utils::file::ensure_dir_exists(out_dir).map_err(|err| {
+        SerError::IoError(format!("Failed to create output directory: {}", err))
+    })?;
 
     // Create the certificate
     println!(
         "{}",
         "Running serializability analysis...".cyan().bold()
     );
-    let decision = ns.create_certificate(&out_dir);
+    let decision = ns.create_certificate(out_dir);
 
     // Save the certificate
-    let cert_path = format!("{}/certificate.json", out_dir);
-    match decision.save_to_file(&cert_path) {
-        Ok(_) => {
-            println!(
-                "{} certificate to: {}",
-                "Successfully saved".green().bold(),
-                cert_path.green()
-            );
-        }
-        Err(err) => {
-            eprintln!(
-                "{} certificate: {}",
-                "Failed to save".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    }
+    let cert_path = ns_decision::certificate_path(out_dir);
+    decision
+        .save_to_file(&cert_path)
+        .map_err(|err| SerError::IoError(format!("Failed to save certificate: {}", err)))?;
+    println!(
+        "{} certificate to: {}",
+        "Successfully saved".green().bold(),
+        cert_path.green()
+    );
+    inconclusive_error(&decision)
 }
 
 // Certificate verification helper
 fn verify_certificate<G, L, Req, Resp>(
     ns: &NS<G, L, Req, Resp>,
     decision: &ns_decision::NSDecision<G, L, Req, Resp>,
+    out_dir: &str,
 ) -> bool
 where
     G: Clone + Ord + Hash + Display + std::fmt::Debug + ToString,
@@ -929,7 +1873,8 @@ where
                     println!("  ✓ Initial state satisfies the invariant");
                     println!("  ✓ Invariant is inductive (preserved by all transitions)");
                     println!("  ✓ Invariant implies serializability when no requests in flight");
-                    true
+                    !cross_validate::cross_validate_enabled()
+                        || cross_validate::report(ns, invariant, out_dir)
                 }
                 Err(err) => {
                     println!("{} {}", "❌".red(), "Certificate is INVALID".red().bold());
@@ -942,11 +1887,12 @@ where
             println!("{} {}", "Certificate type:".cyan(), "NOT SERIALIZABLE".red().bold());
             println!();
             
-            // Validate the trace using NS's check_trace method
-            match ns.check_trace(trace) {
+            // Validate the trace by replaying it and checking it is outside the serializable set
+            match ns.check_counterexample_trace(trace) {
                 Ok(completed_pairs) => {
                     println!("{} {}", "✅".green(), "Certificate trace is VALID".green().bold());
                     println!("  ✓ Trace is executable in the Network System");
+                    println!("  ✓ Completed requests are not in the serializable set");
                     
                     // Display the non-serializable multiset
                     println!("\nCompleted Request/Response Pairs (Non-Serializable):");
@@ -984,122 +1930,74 @@ where
             println!("  {}", message);
             false
         }
+        ns_decision::NSDecision::Unknown {
+            reason,
+            partial_results,
+        } => {
+            println!("{} {}", "Certificate type:".cyan(), "UNKNOWN".yellow().bold());
+            println!();
+            println!("{} {}", "❓".yellow(), "Analysis was inconclusive".yellow());
+            println!("  {}", reason);
+            for r in partial_results {
+                println!("  Disjunct {}: {:?}", r.disjunct, r.outcome);
+            }
+            false
+        }
     }
 }
 
-// Certificate checking functions
-fn check_certificate_for_ser_file(file_path: &str) {
-    println!();
-    println!(
-        "{}",
-        "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
-            .blue()
-            .bold()
-    );
+// --invariant: check a hand-written invariant with check_proof, without SMPT
+fn check_invariant_for_file(path: &Path, invariant_path: &str) -> Result<(), SerError> {
+    check_exists(path)?;
+    if path.is_dir() {
+        return Err(SerError::Usage(
+            "--invariant does not support directories".to_string(),
+        ));
+    }
+    let file_path = path.to_string_lossy();
     println!(
         "{} {} {}",
         "🔍".blue(),
-        "Checking certificate for Ser file:".blue().bold(),
-        file_path.cyan()
+        "Checking invariant".blue().bold(),
+        invariant_path.cyan()
     );
-
-    // Load and parse the .ser file to get NS
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
-        }
-    };
-
-    let mut table = ExprHc::new();
-    let ns = match parse_program(&content, &mut table) {
-        Ok(program) => expr_to_ns::program_to_ns(&mut table, &program),
-        Err(_) => {
-            match parse(&content, &mut table) {
-                Ok(expr) => {
-                    expr_to_ns::program_to_ns(
-                        &mut table,
-                        &Program {
-                            requests: vec![Request {
-                                name: "request".to_string(),
-                                body: expr,
-                            }],
-                        },
-                    )
-                }
-                Err(err) => {
-                    eprintln!("{} SER file: {}", "Error parsing".red().bold(), err);
-                    process::exit(1);
-                }
-            }
-        }
-    };
-
-    // Get the output directory path
-    let path = Path::new(file_path);
-    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("expr");
-    let out_dir = format!("out/{}", file_stem);
-    let cert_path = format!("{}/certificate.json", out_dir);
-
-    // Check if certificate exists
-    if !Path::new(&cert_path).exists() {
-        eprintln!(
-            "{}: Certificate not found at {}",
-            "Error".red().bold(),
-            cert_path
-        );
-        eprintln!("Run with --create-certificate first to generate the certificate");
-        process::exit(1);
+    let content = fs::read_to_string(invariant_path).map_err(|err| {
+        SerError::IoError(format!("Cannot read invariant {}: {}", invariant_path, err))
+    })?;
+    let json = invariant_path.ends_with(".json");
+    let out_dir = out_dir_for(&file_path);
+    match load_file(&file_path)? {
+        frontend::LoadedNS::Strings(ns) => check_invariant(&ns, &content, json, &out_dir),
+        frontend::LoadedNS::Program(ns) => check_invariant(&ns, &content, json, &out_dir),
     }
+}
 
-    // Load the certificate with proper types
-    println!("Loading certificate from: {}", cert_path.cyan());
-    
-    // Import the required types
-    use crate::expr_to_ns::{Env, ExprRequest, LocalExpr};
-    
-    let decision = match ns_decision::NSDecision::<Env, LocalExpr, ExprRequest, i64>::load_from_file(&cert_path) {
-        Ok(decision) => decision,
-        Err(err) => {
-            eprintln!(
-                "{} certificate: {}",
-                "Error loading".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    };
-
-    // Now we can properly verify the certificate with the NS
-    let is_valid = verify_certificate(&ns, &decision);
-
-    println!();
-    println!(
-        "{}",
-        "════════════════════════════════════════════════════════════".bright_black()
-    );
-    if is_valid {
-        println!(
-            "{} {}",
-            "✅",
-            "CERTIFICATE VERIFICATION PASSED".green().bold()
-        );
+fn check_invariant<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    content: &str,
+    json: bool,
+    out_dir: &str,
+) -> Result<(), SerError>
+where
+    G: Clone + Ord + Hash + Display + std::fmt::Debug,
+    L: Clone + Ord + Hash + Display + std::fmt::Debug,
+    Req: Clone + Ord + Hash + Display + std::fmt::Debug,
+    Resp: Clone + Ord + Hash + Display + std::fmt::Debug,
+{
+    let invariant = user_invariant::parse(ns, content, json)
+        .map_err(|err| SerError::ParseError(format!("Invalid invariant: {}", err)))?;
+    let decision = ns_decision::NSDecision::Serializable { invariant };
+    if verify_certificate(ns, &decision, out_dir) {
+        Ok(())
     } else {
-        println!(
-            "{} {}",
-            "❌",
-            "CERTIFICATE VERIFICATION FAILED".red().bold()
-        );
-        process::exit(1);
+        Err(SerError::CertificateError(
+            "The invariant does not prove serializability".to_string(),
+        ))
     }
-    println!(
-        "{}",
-        "════════════════════════════════════════════════════════════".bright_black()
-    );
 }
 
-fn check_certificate_for_json_file(file_path: &str) {
+// Certificate checking functions
+fn check_certificate_for_file(file_path: &str) -> Result<(), SerError> {
     println!();
     println!(
         "{}",
@@ -1110,90 +2008,41 @@ fn check_certificate_for_json_file(file_path: &str) {
     println!(
         "{} {} {}",
         "🔍".blue(),
-        "Checking certificate for JSON file:".blue().bold(),
+        "Checking certificate for file:".blue().bold(),
         file_path.cyan()
     );
 
-    // Load and parse the JSON file to get NS
-    let content = match fs::read_to_string(file_path) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("{} file: {}", "Error reading".red().bold(), err);
-            process::exit(1);
-        }
-    };
-
-    let _ns = match NS::<String, String, String, String>::from_json(&content) {
-        Ok(ns) => ns,
-        Err(err) => {
-            eprintln!(
-                "{} JSON as Network System: {}",
-                "Error parsing".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    };
-
-    // Get the output directory path
-    let path = Path::new(file_path);
-    let file_stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("network");
-    let out_dir = format!("out/{}", file_stem);
-    let cert_path = format!("{}/certificate.json", out_dir);
-
-    // Check if certificate exists
-    if !Path::new(&cert_path).exists() {
-        eprintln!(
-            "{}: Certificate not found at {}",
-            "Error".red().bold(),
-            cert_path
-        );
-        eprintln!("Run with --create-certificate first to generate the certificate");
-        process::exit(1);
+    let out_dir = certificate_dir_for(file_path);
+    match load_file(file_path)? {
+        frontend::LoadedNS::Strings(ns) => check_certificate(&ns, &out_dir),
+        frontend::LoadedNS::Program(ns) => check_certificate(&ns, &out_dir),
     }
+}
 
-    // Load the certificate as String-based decision
+fn check_certificate<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Result<(), SerError>
+where
+    G: Clone + Ord + Hash + Display + std::fmt::Debug + for<'de> serde::Deserialize<'de>,
+    L: Clone + Ord + Hash + Display + std::fmt::Debug + for<'de> serde::Deserialize<'de>,
+    Req: Clone + Ord + Hash + Display + std::fmt::Debug + for<'de> serde::Deserialize<'de>,
+    Resp: Clone + Ord + Hash + Display + std::fmt::Debug + for<'de> serde::Deserialize<'de>,
+{
+    // Check if certificate exists
+    let cert_path = ns_decision::find_certificate(out_dir).ok_or_else(|| {
+        SerError::CertificateError(format!(
+            "Certificate not found at {}. Run with --create-certificate first to generate the certificate",
+            ns_decision::certificate_path(out_dir)
+        ))
+    })?;
+
+    // Load the certificate with the types of the network system
     println!("Loading certificate from: {}", cert_path.cyan());
-    let string_decision = match ns_decision::NSDecision::<String, String, String, String>::load_from_file(&cert_path) {
-        Ok(decision) => decision,
-        Err(err) => {
-            eprintln!(
-                "{} certificate: {}",
-                "Error loading".red().bold(),
-                err
-            );
-            process::exit(1);
-        }
-    };
+    let decision = ns_decision::NSDecision::<G, L, Req, Resp>::load_from_file(&cert_path)
+        .map_err(|err| SerError::CertificateError(format!("Error loading certificate: {}", err)))?;
 
-    // For now, we'll skip verification of loaded certificates from .ser files
-    // since the types don't match (Env vs String, etc.)
-    println!();
-    println!("{} {}", "⚠️ ".yellow(), "Certificate loaded successfully".yellow());
-    println!("Note: Full verification of .ser certificates is not yet implemented");
-    println!("(Type conversion from String to Env/LocalExpr/ExprRequest needed)");
-    
-    // Just check the certificate type
-    let is_valid = match string_decision {
-        ns_decision::NSDecision::Serializable { .. } => {
-            println!();
-            println!("{} {}", "Certificate type:".cyan(), "SERIALIZABLE".green().bold());
-            true
-        }
-        ns_decision::NSDecision::NotSerializable { .. } => {
-            println!();
-            println!("{} {}", "Certificate type:".cyan(), "NOT SERIALIZABLE".red().bold());
-            true
-        }
-        ns_decision::NSDecision::Timeout { .. } => {
-            println!();
-            println!("{} {}", "Certificate type:".cyan(), "TIMEOUT".yellow().bold());
-            true
-        }
-    };
+    let is_valid = verify_certificate(ns, &decision, out_dir);
 
     println!();
     println!(
@@ -1212,10 +2061,20 @@ fn check_certificate_for_json_file(file_path: &str) {
             "❌",
             "CERTIFICATE VERIFICATION FAILED".red().bold()
         );
-        process::exit(1);
     }
     println!(
         "{}",
         "════════════════════════════════════════════════════════════".bright_black()
     );
+
+    if is_valid {
+        Ok(())
+    } else {
+        // A timed out or inconclusive certificate is not wrong, only incomplete
+        inconclusive_error(&decision)?;
+        Err(SerError::CertificateError(format!(
+            "Certificate {} is invalid",
+            cert_path
+        )))
+    }
 }
@@ -0,0 +1,231 @@
+//! Summary of a directory analysis (`out/index.json`).
+//!
+//! When `ser` analyzes a directory, every file gets its own output directory. The
+//! index lists them in one place: for each file the verdict, the timing and SMPT
+//! counts from its statistics, and the files written to its output directory. The
+//! index is written as JSON for scripts, and as an HTML page with links to the
+//! artifacts unless visualizations are disabled (`--no-viz`).
+
+use crate::error::SerError;
+use crate::stats::Snapshot;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// File in `out` with the index of a directory analysis
+pub const INDEX_FILE: &str = "index.json";
+
+/// HTML version of the index, next to `index.json`
+pub const INDEX_HTML_FILE: &str = "index.html";
+
+/// One analyzed file of the directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexEntry {
+    pub file: String,
+    pub out_dir: String,
+    /// The result in the statistics of the run, or "error" if the file failed
+    pub verdict: String,
+    pub error: Option<String>,
+    pub total_time_ms: u64,
+    pub smpt_calls: usize,
+    pub smpt_timeouts: usize,
+    pub num_disjuncts: usize,
+    /// Files in the output directory, relative to it
+    pub artifacts: Vec<String>,
+}
+
+impl IndexEntry {
+    /// The entry of `file`, from the statistics of its run (if it got that far) and
+    /// its error. `elapsed_ms` is the time taken when there are no statistics.
+    pub fn new(
+        file: &str,
+        out_dir: &str,
+        stats: Option<&Snapshot>,
+        error: Option<&SerError>,
+        elapsed_ms: u64,
+    ) -> Self {
+        let verdict = match (error, stats) {
+            (Some(_), _) => "error".to_string(),
+            (None, Some(stats)) => stats.result.clone(),
+            (None, None) => "unknown".to_string(),
+        };
+        IndexEntry {
+            file: file.to_string(),
+            out_dir: out_dir.to_string(),
+            verdict,
+            error: error.map(|err| err.to_string()),
+            total_time_ms: stats.map_or(elapsed_ms, |stats| stats.total_time_ms),
+            smpt_calls: stats.map_or(0, |stats| stats.smpt_calls),
+            smpt_timeouts: stats.map_or(0, |stats| stats.smpt_timeouts),
+            num_disjuncts: stats.map_or(0, |stats| stats.num_disjuncts),
+            artifacts: list_artifacts(Path::new(out_dir)),
+        }
+    }
+}
+
+/// The index of a directory analysis
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Index {
+    pub root: String,
+    /// Number of files with each verdict
+    pub verdicts: BTreeMap<String, usize>,
+    pub total_time_ms: u64,
+    pub files: Vec<IndexEntry>,
+}
+
+impl Index {
+    pub fn new(root: &str) -> Self {
+        Index {
+            root: root.to_string(),
+            ..Index::default()
+        }
+    }
+
+    pub fn push(&mut self, entry: IndexEntry) {
+        *self.verdicts.entry(entry.verdict.clone()).or_insert(0) += 1;
+        self.total_time_ms += entry.total_time_ms;
+        self.files.push(entry);
+    }
+
+    /// A page with a table of the files, linking to their artifacts. The page is in
+    /// `out`, so the links are relative to it.
+    pub fn to_html(&self) -> String {
+        use crate::utils::string::html_escape;
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>ser: {}</title>\n",
+            html_escape(&self.root)
+        ));
+        html.push_str(
+            "<style>body { font-family: sans-serif; } td, th { padding: 2px 8px; text-align: left; vertical-align: top; }</style>\n",
+        );
+        html.push_str("</head>\n<body>\n");
+        html.push_str(&format!("<h1>{}</h1>\n<p>", html_escape(&self.root)));
+        let counts: Vec<String> = self
+            .verdicts
+            .iter()
+            .map(|(verdict, count)| format!("{}: {}", html_escape(verdict), count))
+            .collect();
+        html.push_str(&counts.join(", "));
+        html.push_str(&format!(" ({} ms)</p>\n", self.total_time_ms));
+        html.push_str(
+            "<table>\n<tr><th>File</th><th>Verdict</th><th>Time (ms)</th><th>SMPT calls</th><th>Disjuncts</th><th>Artifacts</th></tr>\n",
+        );
+        for entry in &self.files {
+            let dir = Path::new(&entry.out_dir);
+            let dir = dir.strip_prefix(crate::utils::file::OUT_DIR).unwrap_or(dir);
+            let links: Vec<String> = entry
+                .artifacts
+                .iter()
+                .map(|artifact| {
+                    let href = dir.join(artifact).to_string_lossy().replace('\\', "/");
+                    format!(
+                        "<a href=\"{}\">{}</a>",
+                        html_escape(&href),
+                        html_escape(artifact)
+                    )
+                })
+                .collect();
+            let verdict = match &entry.error {
+                Some(error) => format!(
+                    "<span title=\"{}\">{}</span>",
+                    html_escape(error),
+                    html_escape(&entry.verdict)
+                ),
+                None => html_escape(&entry.verdict),
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&entry.file),
+                verdict,
+                entry.total_time_ms,
+                entry.smpt_calls,
+                entry.num_disjuncts,
+                links.join("<br>")
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+/// The files in `dir` and its subdirectories, relative to `dir` and sorted
+pub fn list_artifacts(dir: &Path) -> Vec<String> {
+    fn visit(dir: &Path, prefix: &str, artifacts: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            let path = entry.path();
+            if path.is_dir() {
+                visit(&path, &format!("{}/", name), artifacts);
+            } else {
+                artifacts.push(name);
+            }
+        }
+    }
+    let mut artifacts = Vec::new();
+    visit(dir, "", &mut artifacts);
+    artifacts.sort();
+    artifacts
+}
+
+/// Write `index.json`, and `index.html` if `html` is set, into `out`, returning the
+/// paths written
+pub fn save_index(index: &Index, html: bool) -> Result<Vec<String>, String> {
+    let mut written = Vec::new();
+    let path = crate::utils::file::join(crate::utils::file::OUT_DIR, INDEX_FILE);
+    let json = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+    crate::utils::file::safe_write_file(&path, &json).map_err(|e| e.to_string())?;
+    written.push(path);
+    if html {
+        let path = crate::utils::file::join(crate::utils::file::OUT_DIR, INDEX_HTML_FILE);
+        crate::utils::file::safe_write_file(&path, &index.to_html()).map_err(|e| e.to_string())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let out_dir = temp_dir.path().to_str().unwrap();
+        crate::utils::file::safe_write_file(&crate::utils::file::join(out_dir, "stats.json"), "{}")
+            .unwrap();
+        crate::utils::file::safe_write_file(&crate::utils::file::join(out_dir, "repair/a.ser"), "")
+            .unwrap();
+        assert_eq!(
+            list_artifacts(temp_dir.path()),
+            vec!["repair/a.ser".to_string(), "stats.json".to_string()]
+        );
+
+        let mut index = Index::new("examples");
+        index.push(IndexEntry::new("examples/a.ser", out_dir, None, None, 3));
+        let error = SerError::ParseError("unexpected <token>".to_string());
+        index.push(IndexEntry::new(
+            "examples/b.ser",
+            "out/b",
+            None,
+            Some(&error),
+            4,
+        ));
+        assert_eq!(index.files[0].verdict, "unknown");
+        assert_eq!(index.files[0].artifacts.len(), 2);
+        assert_eq!(index.files[1].verdict, "error");
+        assert!(index.files[1].artifacts.is_empty());
+        assert_eq!(index.total_time_ms, 7);
+        let html = index.to_html();
+        assert!(html.contains("stats.json</a>"));
+        assert!(html.contains("unexpected &lt;token&gt;"));
+        assert_eq!(
+            index.verdicts.into_iter().collect::<Vec<_>>(),
+            vec![("error".to_string(), 1), ("unknown".to_string(), 1)]
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Export of the Petri net state equation (marking equation) as a linear program.
+//!
+//! A target marking `m` can only be reachable from the initial marking `m0` if there
+//! is a firing count vector `x ≥ 0` with `m = m0 + C·x`, where `C` is the incidence
+//! matrix of the net. Together with the target constraints of a disjunct this gives
+//! an integer linear program that users can hand to CPLEX, HiGHS, etc. If the program
+//! is infeasible, the disjunct is unreachable, which the optional prefilter uses to
+//! skip the SMPT call. The markings that satisfy the marking equation are then the
+//! proof of the disjunct (see `invariant`), so the certificate still covers it.
+
+use crate::constraints::{Constraint, ConstraintType, Variable};
+use crate::deterministic_map::HashMap;
+use crate::petri::Petri;
+use crate::ident::{Target, encode};
+use crate::proof_parser::{self, AffineExpr, Formula, ProofInvariant};
+use crate::utils::string::sanitize;
+use colored::*;
+use either::{Either, Left, Right};
+use std::fmt::{Display, Write};
+use std::fs;
+use std::hash::Hash;
+use std::process::Command;
+
+/// Set whether to write the marking equation of every disjunct (called from `main.rs`)
+pub fn set_export_marking_equation(enabled: bool) {
+    crate::options::update(|options| options.export_marking_equation = enabled);
+}
+
+/// Whether the marking equation of every disjunct is written
+pub fn export_marking_equation_enabled() -> bool {
+    crate::options::get(|options| options.export_marking_equation)
+}
+
+/// Set whether to run the HiGHS prefilter (called from `main.rs`)
+pub fn set_lp_prefilter(enabled: bool) {
+    crate::options::update(|options| options.lp_prefilter = enabled);
+}
+
+/// Whether the HiGHS prefilter runs before SMPT
+pub fn lp_prefilter_enabled() -> bool {
+    crate::options::get(|options| options.lp_prefilter)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sense {
+    Eq,
+    Geq,
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    name: String,
+    coefficients: Vec<(usize, i64)>,
+    sense: Sense,
+    rhs: i64,
+}
+
+/// The marking equation of a Petri net with target constraints, as an integer
+/// linear program over nonnegative variables
+#[derive(Debug, Clone)]
+pub struct MarkingEquation {
+    name: String,
+    /// `m_<place>` for every place, then `t_<i>` for every transition
+    columns: Vec<String>,
+    /// Number of place columns at the start of `columns`
+    num_places: usize,
+    rows: Vec<Row>,
+}
+
+impl MarkingEquation {
+    /// Build the marking equation of `petri` together with the target `constraints`.
+    /// Constraint variables that are not places of the net are taken to be 0.
+    pub fn new<P>(name: &str, petri: &Petri<P>, constraints: &[Constraint<P>]) -> Self
+    where
+        P: Clone + Hash + Ord + Display,
+    {
+        let places = petri.get_places_sorted();
+        let transitions = petri.get_transitions();
+        let place_index: HashMap<&P, usize> =
+            places.iter().enumerate().map(|(i, p)| (p, i)).collect();
+
+        let mut columns: Vec<String> = places
+            .iter()
+            .map(|p| format!("m_{}", encode(&p.to_string(), Target::Lp)))
+            .collect();
+        columns.extend((0..transitions.len()).map(|i| format!("t_{}", i)));
+
+        // Incidence: effect of each transition on each place
+        let mut effect: Vec<HashMap<usize, i64>> = vec![HashMap::default(); places.len()];
+        for (t, (inputs, outputs)) in transitions.iter().enumerate() {
+            for p in inputs {
+                *effect[place_index[p]].entry(t).or_insert(0) -= 1;
+            }
+            for p in outputs {
+                *effect[place_index[p]].entry(t).or_insert(0) += 1;
+            }
+        }
+        let mut initial = vec![0i64; places.len()];
+        for p in petri.get_initial_marking() {
+            initial[place_index[&p]] += 1;
+        }
+
+        let mut rows = Vec::new();
+        // m_p - Σ_t C[p][t]·x_t = m0(p)
+        for (i, place_effect) in effect.into_iter().enumerate() {
+            let mut coefficients = vec![(i, 1)];
+            let mut firing: Vec<(usize, i64)> = place_effect
+                .into_iter()
+                .filter(|(_, c)| *c != 0)
+                .map(|(t, c)| (places.len() + t, -c))
+                .collect();
+            firing.sort();
+            coefficients.extend(firing);
+            rows.push(Row {
+                name: format!("place_{}", i),
+                coefficients,
+                sense: Sense::Eq,
+                rhs: initial[i],
+            });
+        }
+
+        // Target constraints: Σ a·m_p + c (= 0 | ≥ 0)
+        for (k, constraint) in constraints.iter().enumerate() {
+            let mut summed: HashMap<usize, i64> = HashMap::default();
+            for (coeff, p) in constraint.linear_combination() {
+                if let Some(&i) = place_index.get(p) {
+                    *summed.entry(i).or_insert(0) += *coeff as i64;
+                }
+            }
+            let mut coefficients: Vec<(usize, i64)> =
+                summed.into_iter().filter(|(_, c)| *c != 0).collect();
+            coefficients.sort();
+            rows.push(Row {
+                name: format!("target_{}", k),
+                coefficients,
+                sense: match constraint.constraint_type() {
+                    ConstraintType::EqualToZero => Sense::Eq,
+                    ConstraintType::NonNegative => Sense::Geq,
+                },
+                rhs: -(constraint.constant_term() as i64),
+            });
+        }
+
+        MarkingEquation {
+            name: sanitize(name),
+            columns,
+            num_places: places.len(),
+            rows,
+        }
+    }
+
+    /// Render in CPLEX LP format. The objective minimizes the total number of firings.
+    pub fn to_lp(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "\\ Petri net marking equation: {}", self.name).unwrap();
+        writeln!(out, "Minimize").unwrap();
+        let firings: Vec<&str> = self.columns[self.num_places..]
+            .iter()
+            .map(|c| c.as_str())
+            .collect();
+        if firings.is_empty() {
+            writeln!(out, " obj: 0 {}", self.columns.first().map_or("x", |c| c)).unwrap();
+        } else {
+            writeln!(out, " obj: {}", firings.join(" + ")).unwrap();
+        }
+        writeln!(out, "Subject To").unwrap();
+        for row in &self.rows {
+            let mut lhs = String::new();
+            for (col, coeff) in &row.coefficients {
+                let sign = if *coeff < 0 { "-" } else { "+" };
+                if lhs.is_empty() {
+                    let sign = if *coeff < 0 { "- " } else { "" };
+                    write!(lhs, "{}{} {}", sign, coeff.abs(), self.columns[*col]).unwrap();
+                } else {
+                    write!(lhs, " {} {} {}", sign, coeff.abs(), self.columns[*col]).unwrap();
+                }
+            }
+            if lhs.is_empty() {
+                // LP format needs a variable in every row
+                lhs = format!("0 {}", self.columns.first().map_or("x", |c| c));
+            }
+            let op = match row.sense {
+                Sense::Eq => "=",
+                Sense::Geq => ">=",
+            };
+            writeln!(out, " {}: {} {} {}", row.name, lhs, op, row.rhs).unwrap();
+        }
+        // Variables are nonnegative by default in LP format
+        if !self.columns.is_empty() {
+            writeln!(out, "General").unwrap();
+            writeln!(out, " {}", self.columns.join(" ")).unwrap();
+        }
+        writeln!(out, "End").unwrap();
+        out
+    }
+
+    /// Render in free MPS format
+    pub fn to_mps(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "NAME {}", self.name).unwrap();
+        writeln!(out, "ROWS").unwrap();
+        writeln!(out, " N obj").unwrap();
+        for row in &self.rows {
+            let sense = match row.sense {
+                Sense::Eq => "E",
+                Sense::Geq => "G",
+            };
+            writeln!(out, " {} {}", sense, row.name).unwrap();
+        }
+
+        // MPS lists the matrix column by column
+        let mut by_column: Vec<Vec<(&str, i64)>> = vec![Vec::new(); self.columns.len()];
+        for row in &self.rows {
+            for (col, coeff) in &row.coefficients {
+                by_column[*col].push((&row.name, *coeff));
+            }
+        }
+        writeln!(out, "COLUMNS").unwrap();
+        writeln!(out, " MARKER 'MARKER' 'INTORG'").unwrap();
+        for (i, entries) in by_column.iter().enumerate() {
+            let column = &self.columns[i];
+            if i >= self.num_places {
+                writeln!(out, " {} obj 1", column).unwrap();
+            }
+            for (row, coeff) in entries {
+                writeln!(out, " {} {} {}", column, row, coeff).unwrap();
+            }
+        }
+        writeln!(out, " MARKER 'MARKER' 'INTEND'").unwrap();
+
+        writeln!(out, "RHS").unwrap();
+        for row in &self.rows {
+            if row.rhs != 0 {
+                writeln!(out, " RHS {} {}", row.name, row.rhs).unwrap();
+            }
+        }
+
+        // Integer columns default to [0, 1] in some readers, so bound them explicitly
+        writeln!(out, "BOUNDS").unwrap();
+        for column in &self.columns {
+            writeln!(out, " PL BND {}", column).unwrap();
+        }
+        writeln!(out, "ENDATA").unwrap();
+        out
+    }
+}
+
+/// Extract the model status from the output of the `highs` command line tool,
+/// e.g. `Model status        : Infeasible`
+fn parse_highs_status(stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let key: Vec<&str> = key.split_whitespace().collect();
+        if key == ["Model", "status"] {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Run `highs` on an MPS file and return its model status
+fn run_highs(mps_file: &str) -> Result<String, String> {
+    let mut command = Command::new("highs");
+    let timeout = crate::smpt::get_smpt_timeout();
+    if timeout > 0 {
+        command.args(["--time_limit", &timeout.to_string()]);
+    }
+    let output = command
+        .args(["--model_file", mps_file])
+        .output()
+        .map_err(|e| format!("Failed to run highs: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_highs_status(&stdout).ok_or_else(|| "No model status in highs output".to_string())
+}
+
+/// The markings that satisfy the marking equation, `∃x ≥ 0. m = m0 + C·x`, as an
+/// invariant over the places of `petri` and the variables of `constraints` (which are 0
+/// if they are not places). The initial marking satisfies it and firing a transition
+/// preserves it, so if the marking equation with the constraints is infeasible, this
+/// proves the constraints unreachable.
+pub fn invariant<P>(petri: &Petri<P>, constraints: &[Constraint<P>]) -> ProofInvariant<P>
+where
+    P: Clone + Hash + Ord + Display,
+{
+    let places = petri.get_places_sorted();
+    let transitions = petri.get_transitions();
+
+    // Firing counts are Left(t), places Right(p), until the counts are bound below
+    let place = |p: &P| Right::<usize, P>(p.clone());
+    let mut effect: HashMap<P, AffineExpr<Either<usize, P>>> = HashMap::default();
+    for (t, (inputs, outputs)) in transitions.iter().enumerate() {
+        for p in inputs {
+            let e = effect.entry(p.clone()).or_default();
+            *e = e.add(&AffineExpr::term(-1, Left(t)));
+        }
+        for p in outputs {
+            let e = effect.entry(p.clone()).or_default();
+            *e = e.add(&AffineExpr::term(1, Left(t)));
+        }
+    }
+    let initial = petri.get_initial_marking();
+    let mut conjuncts = Vec::new();
+    // m_p - Σ_t C[p][t]·x_t = m0(p)
+    for p in &places {
+        let mut expr = AffineExpr::from_var(place(p));
+        if let Some(e) = effect.get(p) {
+            expr = expr.sub(e);
+        }
+        let m0 = initial.iter().filter(|q| *q == p).count() as i64;
+        conjuncts.push(Formula::Constraint(proof_parser::Constraint::eq(expr, m0)));
+    }
+    for t in 0..transitions.len() {
+        let x = AffineExpr::from_var(Left(t));
+        conjuncts.push(Formula::Constraint(proof_parser::Constraint::ge(x, 0)));
+    }
+    let mut variables = places;
+    for constraint in constraints {
+        for (_, p) in constraint.linear_combination() {
+            if !variables.contains(p) {
+                variables.push(p.clone());
+                let v = AffineExpr::from_var(place(p));
+                conjuncts.push(Formula::Constraint(proof_parser::Constraint::eq(v, 0)));
+            }
+        }
+    }
+
+    let mut formula = Formula::and(conjuncts).rename_vars(&mut |v| match v {
+        Variable::Var(Left(t)) => Variable::Existential(t),
+        Variable::Var(Right(p)) => Variable::Var(p),
+        Variable::Existential(i) => Variable::Existential(i),
+    });
+    for t in (0..transitions.len()).rev() {
+        formula = Formula::Exists(t, Box::new(formula));
+    }
+    ProofInvariant::new(variables, formula)
+}
+
+/// Write the marking equation of a disjunct to `out_dir` if requested, and run the
+/// HiGHS prefilter if enabled. Returns the proof (see `invariant`) if the disjunct was
+/// proved unreachable.
+pub fn check_disjunct<P>(
+    petri: &Petri<P>,
+    constraints: &[Constraint<P>],
+    out_dir: &str,
+    disjunct_id: usize,
+) -> Option<ProofInvariant<P>>
+where
+    P: Clone + Hash + Ord + Display,
+{
+    let export = export_marking_equation_enabled();
+    let prefilter = lp_prefilter_enabled();
+    if !export && !prefilter {
+        return None;
+    }
+
+    let equation = MarkingEquation::new(&format!("disjunct_{}", disjunct_id), petri, constraints);
+    let file = |extension: &str| {
+        crate::utils::file::join(
+            out_dir,
+            &format!("marking_equation_disjunct_{}.{}", disjunct_id, extension),
+        )
+    };
+    let mps_file = file("mps");
+    if let Err(e) = fs::write(&mps_file, equation.to_mps()) {
+        eprintln!("Failed to write {}: {}", mps_file, e);
+        return None;
+    }
+    if export {
+        let lp_file = file("lp");
+        if let Err(e) = fs::write(&lp_file, equation.to_lp()) {
+            eprintln!("Failed to write {}: {}", lp_file, e);
+        }
+    }
+    if !prefilter {
+        return None;
+    }
+
+    match run_highs(&mps_file) {
+        Ok(status) if status == "Infeasible" => {
+            println!(
+                "{} Disjunct {} has an infeasible marking equation, skipping SMPT",
+                "✓".green().bold(),
+                disjunct_id
+            );
+            crate::stats::increment_lp_prefilter_proofs();
+            Some(invariant(petri, constraints))
+        }
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("{}: LP prefilter skipped: {}", "Warning".yellow(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> MarkingEquation {
+        // a -> b, starting with one token in a; target: b >= 2
+        let mut petri = Petri::new(vec!["a"]);
+        petri.add_transition(vec!["a"], vec!["b"]);
+        let target = Constraint::new(vec![(1, "b")], -2, ConstraintType::NonNegative);
+        MarkingEquation::new("sample", &petri, &[target])
+    }
+
+    #[test]
+    fn test_marking_equation_lp() {
+        let lp = sample().to_lp();
+        assert!(lp.contains(" obj: t_0\n"), "{}", lp);
+        assert!(lp.contains(" place_0: 1 m_a + 1 t_0 = 1\n"), "{}", lp);
+        assert!(lp.contains(" place_1: 1 m_b - 1 t_0 = 0\n"), "{}", lp);
+        assert!(lp.contains(" target_0: 1 m_b >= 2\n"), "{}", lp);
+        assert!(lp.contains("General\n m_a m_b t_0\n"), "{}", lp);
+    }
+
+    #[test]
+    fn test_marking_equation_mps() {
+        let mps = sample().to_mps();
+        assert!(mps.contains(" E place_0\n E place_1\n G target_0\n"), "{}", mps);
+        assert!(
+            mps.contains(" t_0 obj 1\n t_0 place_0 1\n t_0 place_1 -1\n"),
+            "{}",
+            mps
+        );
+        assert!(mps.contains(" RHS place_0 1\n RHS target_0 2\n"), "{}", mps);
+        assert!(mps.contains(" PL BND t_0\n"), "{}", mps);
+    }
+
+    #[test]
+    fn test_invariant() {
+        use crate::presburger_fallback::is_satisfiable;
+        let s = |x: &str| x.to_string();
+        // a -> b + b, starting with one token in a; target: b >= 3 and c >= 0
+        let mut petri = Petri::new(vec![s("a")]);
+        petri.add_transition(vec![s("a")], vec![s("b"), s("b")]);
+        let target = [
+            Constraint::new(vec![(1, s("b"))], -3, ConstraintType::NonNegative),
+            Constraint::new(vec![(1, s("c"))], 0, ConstraintType::NonNegative),
+        ];
+        let invariant = invariant(&petri, &target);
+        assert_eq!(invariant.variables(), &[s("a"), s("b"), s("c")]);
+
+        let marking = |a: i64, b: i64, c: i64| {
+            let places = [(s("a"), a), (s("b"), b), (s("c"), c)];
+            let equations = places.map(|(p, n)| {
+                Formula::Constraint(proof_parser::Constraint::eq(AffineExpr::from_var(p), n))
+            });
+            Formula::and([invariant.formula.clone(), Formula::and(equations)])
+        };
+        // The reachable markings satisfy the invariant, others do not
+        assert_eq!(is_satisfiable(&marking(1, 0, 0)), Ok(true));
+        assert_eq!(is_satisfiable(&marking(0, 2, 0)), Ok(true));
+        assert_eq!(is_satisfiable(&marking(0, 1, 0)), Ok(false));
+        assert_eq!(is_satisfiable(&marking(1, 0, 1)), Ok(false));
+        // So it excludes the target, like the marking equation
+        let b_at_least_3 = proof_parser::Constraint::ge(AffineExpr::from_var(s("b")), 3);
+        let targeted = Formula::and([invariant.formula.clone(), Formula::Constraint(b_at_least_3)]);
+        assert_eq!(is_satisfiable(&targeted), Ok(false));
+    }
+
+    #[test]
+    fn test_parse_highs_status() {
+        let output =
+            "Solving MIP model with:\nModel status        : Infeasible\nObjective value     : 0\n";
+        assert_eq!(parse_highs_status(output), Some("Infeasible".to_string()));
+        assert_eq!(
+            parse_highs_status("Model   status      : Optimal"),
+            Some("Optimal".to_string())
+        );
+        assert_eq!(parse_highs_status("no status here"), None);
+    }
+}
@@ -0,0 +1,189 @@
+//! Mermaid sequence diagrams for counterexample traces.
+//!
+//! Every request instance of an `NSTrace` becomes a participant (named after its
+//! thread in `codegen::schedule`), its steps become self-messages, and changes of the
+//! global state become notes spanning all participants. The result is plain Mermaid
+//! text, so it can be pasted into GitHub issues and Markdown docs as a `mermaid` block.
+
+use crate::codegen::schedule;
+use crate::ns_decision::{NSStep, NSTrace};
+use std::fmt::{Display, Write};
+
+/// Escape text for a Mermaid label: `;` ends a statement and `#` starts an entity
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '#' => out.push_str("#35;"),
+            ';' => out.push_str("#59;"),
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render a trace as a Mermaid `sequenceDiagram`
+pub fn sequence_diagram<G, L, Req, Resp>(trace: &NSTrace<G, L, Req, Resp>) -> String
+where
+    G: Display + PartialEq,
+    L: Display + PartialEq + Clone,
+    Req: Display + PartialEq + Clone,
+    Resp: Display,
+{
+    let scheduled = schedule(trace);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "sequenceDiagram");
+
+    // Participants in order of first appearance
+    let mut threads: Vec<&str> = Vec::new();
+    for step in &scheduled {
+        if !threads.contains(&step.thread.as_str()) {
+            threads.push(&step.thread);
+            let _ = writeln!(
+                out,
+                "    participant {} as {} ({})",
+                step.thread,
+                escape(&step.request),
+                step.thread
+            );
+        }
+    }
+    let everyone = match (threads.first(), threads.last()) {
+        (Some(first), Some(last)) if first != last => format!("{},{}", first, last),
+        (Some(first), _) => first.to_string(),
+        _ => String::new(),
+    };
+
+    if trace.steps.is_empty() {
+        let _ = writeln!(out, "    %% Empty trace: violation at the initial state");
+    }
+    for (step, at) in trace.steps.iter().zip(&scheduled) {
+        let thread = &at.thread;
+        match step {
+            NSStep::RequestStart { initial_local, .. } => {
+                let _ = writeln!(
+                    out,
+                    "    {}->>{}: start in {}",
+                    thread,
+                    thread,
+                    escape(&initial_local.to_string())
+                );
+            }
+            NSStep::InternalStep {
+                from_local,
+                from_global,
+                to_local,
+                to_global,
+                ..
+            } => {
+                let _ = writeln!(
+                    out,
+                    "    {}->>{}: {} → {}",
+                    thread,
+                    thread,
+                    escape(&from_local.to_string()),
+                    escape(&to_local.to_string())
+                );
+                if from_global != to_global {
+                    let _ = writeln!(
+                        out,
+                        "    Note over {}: global {} → {}",
+                        everyone,
+                        escape(&from_global.to_string()),
+                        escape(&to_global.to_string())
+                    );
+                }
+            }
+            NSStep::RequestComplete {
+                final_local,
+                response,
+                ..
+            } => {
+                let _ = writeln!(
+                    out,
+                    "    {}-->>{}: respond {} (in {})",
+                    thread,
+                    thread,
+                    escape(&response.to_string()),
+                    escape(&final_local.to_string())
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Write `trace.mmd` for a trace into the output directory
+pub fn save_sequence_diagram<G, L, Req, Resp>(
+    trace: &NSTrace<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Result<String, std::io::Error>
+where
+    G: Display + PartialEq,
+    L: Display + PartialEq + Clone,
+    Req: Display + PartialEq + Clone,
+    Resp: Display,
+{
+    let path = crate::utils::file::join(out_dir, "trace.mmd");
+    crate::utils::file::safe_write_file(&path, &sequence_diagram(trace))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_diagram() {
+        let s = |x: &str| x.to_string();
+        let trace: NSTrace<String, String, String, String> = NSTrace {
+            steps: vec![
+                NSStep::RequestStart {
+                    request: s("inc"),
+                    initial_local: s("L0"),
+                },
+                NSStep::RequestStart {
+                    request: s("inc"),
+                    initial_local: s("L0"),
+                },
+                NSStep::InternalStep {
+                    request: s("inc"),
+                    from_local: s("L0"),
+                    from_global: s("X=0"),
+                    to_local: s("L1"),
+                    to_global: s("X=1"),
+                },
+                NSStep::InternalStep {
+                    request: s("inc"),
+                    from_local: s("L0"),
+                    from_global: s("X=1"),
+                    to_local: s("L1"),
+                    to_global: s("X=1"),
+                },
+                NSStep::RequestComplete {
+                    request: s("inc"),
+                    final_local: s("L1"),
+                    response: s("a;b#"),
+                },
+            ],
+        };
+        let diagram = sequence_diagram(&trace);
+        let lines: Vec<&str> = diagram.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "sequenceDiagram",
+                "    participant t1 as inc (t1)",
+                "    participant t2 as inc (t2)",
+                "    t1->>t1: start in L0",
+                "    t2->>t2: start in L0",
+                "    t1->>t1: L0 → L1",
+                "    Note over t1,t2: global X=0 → X=1",
+                "    t2->>t2: L0 → L1",
+                "    t1-->>t1: respond a#59;b#35; (in L1)",
+            ]
+        );
+    }
+}
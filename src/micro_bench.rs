@@ -0,0 +1,356 @@
+//! `ser micro-bench`: times representative set operations on both the
+//! `SemilinearSet` and the `PresburgerSet` (ISL) representation, for generated
+//! inputs of increasing size. The resulting table is meant to guide the
+//! representation switching heuristics in `SPresburgerSet`. It also times the
+//! proof parser on generated multi-megabyte and deeply nested proofs.
+
+use crate::kleene::Kleene;
+use crate::presburger::PresburgerSet;
+use crate::proof_parser::parse_proof_file;
+use crate::semilinear::{LinearSet, SemilinearSet, SparseVector};
+use crate::spresburger::SPresburgerSet;
+use colored::*;
+use std::time::Instant;
+
+/// Star of a union of n linear sets has up to 2^n components, so keep it small
+const MAX_STAR_COMPONENTS: usize = 8;
+
+struct Options {
+    max_size: usize,
+    dims: usize,
+    repeat: usize,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        max_size: 64,
+        dims: 3,
+        repeat: 3,
+    };
+    let mut i = 0;
+    while i < args.len() {
+        let target = match args[i].as_str() {
+            "--max-size" => &mut options.max_size,
+            "--dims" => &mut options.dims,
+            "--repeat" => &mut options.repeat,
+            other => return Err(format!("Unexpected micro-bench argument '{}'", other)),
+        };
+        let value = args
+            .get(i + 1)
+            .ok_or_else(|| format!("{} requires a value", args[i]))?;
+        *target = match value.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("Invalid value '{}' for {}", value, args[i])),
+        };
+        i += 2;
+    }
+    if options.dims > 26 {
+        return Err("--dims must be at most 26".to_string());
+    }
+    Ok(options)
+}
+
+fn next_random(seed: &mut u32) -> u32 {
+    *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+    (*seed >> 16) & 0x7fff
+}
+
+/// Generate a semilinear set with the given number of components over `dims`
+/// letters. With `boxes_only`, all periods are unit vectors, which keeps the set
+/// in the fragment that `SemilinearSet::difference` handles directly.
+fn generate(
+    seed: &mut u32,
+    components: usize,
+    dims: usize,
+    boxes_only: bool,
+) -> SemilinearSet<char> {
+    let alphabet: Vec<char> = (0..dims).map(|i| (b'a' + i as u8) as char).collect();
+    let mut linear_sets = Vec::new();
+    for _ in 0..components {
+        let mut base = SparseVector::new();
+        let mut periods = Vec::new();
+        for &k in &alphabet {
+            base.set(k, (next_random(seed) % 3) as usize);
+            if next_random(seed).is_multiple_of(2) {
+                periods.push(SparseVector::unit(k));
+            }
+        }
+        if !boxes_only && dims > 1 {
+            let i = next_random(seed) as usize % dims;
+            let j = (i + 1) % dims;
+            periods.push(SparseVector::unit(alphabet[i]).add(&SparseVector::unit(alphabet[j])));
+        }
+        linear_sets.push(LinearSet { base, periods });
+    }
+    SemilinearSet {
+        components: linear_sets,
+    }
+}
+
+/// Clauses of a generated proof per megabyte
+const PROOF_CLAUSES_PER_MB: usize = 10_000;
+
+/// Nesting depth of the deeply nested generated proof
+const PROOF_DEPTH: usize = 100_000;
+
+/// A proof shaped like the ones SMPT produces: a conjunction of `clauses` disjunctions
+/// of comparisons over `vars` variables, with an existential quantifier in each
+fn generate_proof(seed: &mut u32, clauses: usize, vars: usize) -> String {
+    let mut proof = String::from("(set-logic LIA)\n(define-fun cert (");
+    for v in 0..vars {
+        proof.push_str(&format!("(p{} Int)", v));
+    }
+    proof.push_str(") Bool\n  (and\n");
+    for _ in 0..clauses {
+        let a = next_random(seed) as usize % vars;
+        let b = next_random(seed) as usize % vars;
+        let k = next_random(seed) % 10;
+        proof.push_str(&format!(
+            "    (or (>= (+ p{} (* 2 p{})) {}) (exists ((t Int)) (and (>= t 0) (= p{} (- t {})))))\n",
+            a, b, k, a, k
+        ));
+    }
+    proof.push_str("  ))\n");
+    proof
+}
+
+/// A proof whose formula and expression are nested `depth` levels deep
+fn generate_nested_proof(depth: usize) -> String {
+    format!(
+        "(define-fun cert ((x Int)) Bool {}(>= {}x{} 0){})",
+        "(not ".repeat(depth),
+        "(+ 1 ".repeat(depth),
+        ")".repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+fn proof_row(name: &str, proof: &str, repeat: usize) {
+    let (ms, result) = time_ms(repeat, || {
+        parse_proof_file(proof).map(|invariant| invariant.formula.num_constraints())
+    });
+    let note = match result {
+        Ok(constraints) => format!(
+            "{} constraints, {:.1} MB/s",
+            constraints,
+            proof.len() as f64 / 1e6 / (ms / 1000.0)
+        ),
+        Err(err) => format!("failed: {}", err),
+    };
+    println!(
+        "{:<20} {:>10} {:>16.3}  {}",
+        name,
+        proof.len() / 1024,
+        ms,
+        note
+    );
+}
+
+/// Time `parse_proof_file` on proofs of a few megabytes, and on one nested deeper than
+/// the call stack would allow a recursive parser to go
+fn proof_parser_rows(seed: &mut u32, repeat: usize) {
+    println!();
+    println!(
+        "{}",
+        format!(
+            "{:<20} {:>10} {:>16}  {}",
+            "proof", "size (KB)", "parse (ms)", "note"
+        )
+        .bold()
+    );
+    for megabytes in [1, 2, 4] {
+        let clauses = megabytes * PROOF_CLAUSES_PER_MB;
+        let proof = generate_proof(seed, clauses, 16);
+        proof_row(&format!("{} clauses", clauses), &proof, repeat);
+    }
+    let proof = generate_nested_proof(PROOF_DEPTH);
+    proof_row(&format!("nested {}", PROOF_DEPTH), &proof, repeat);
+}
+
+/// Run `f` `repeat` times and return the average time in milliseconds and the last result
+fn time_ms<R>(repeat: usize, mut f: impl FnMut() -> R) -> (f64, R) {
+    let start = Instant::now();
+    let mut result = f();
+    for _ in 1..repeat {
+        result = f();
+    }
+    (
+        start.elapsed().as_secs_f64() * 1000.0 / repeat as f64,
+        result,
+    )
+}
+
+fn print_row(
+    operation: &str,
+    size: usize,
+    semilinear: Option<f64>,
+    presburger: Option<f64>,
+    note: &str,
+) {
+    let cell = |t: Option<f64>| t.map_or("-".to_string(), |t| format!("{:.3}", t));
+    println!(
+        "{:<20} {:>6} {:>16} {:>16}  {}",
+        operation,
+        size,
+        cell(semilinear),
+        cell(presburger),
+        note
+    );
+}
+
+fn difference_row(
+    operation: &str,
+    size: usize,
+    repeat: usize,
+    a: &SemilinearSet<char>,
+    b: &SemilinearSet<char>,
+) {
+    let (semilinear_ms, result) = time_ms(repeat, || {
+        SPresburgerSet::from_semilinear(a.clone())
+            .difference(SPresburgerSet::from_semilinear(b.clone()))
+    });
+    let note = match result {
+        SPresburgerSet::Semilinear(_) => "stayed semilinear",
+        SPresburgerSet::Presburger(_) => "fell back to ISL",
+    };
+    let pa = PresburgerSet::from_semilinear_set(a);
+    let pb = PresburgerSet::from_semilinear_set(b);
+    let (presburger_ms, _) = time_ms(repeat, || pa.difference(&pb));
+    print_row(
+        operation,
+        size,
+        Some(semilinear_ms),
+        Some(presburger_ms),
+        note,
+    );
+}
+
+/// Entry point of the `micro-bench` subcommand
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = parse_options(args)?;
+    let mut seed = 42;
+
+    println!(
+        "{} (dims = {}, repeat = {}, times are averages in ms)",
+        "Micro-benchmark: semilinear vs. Presburger".bold(),
+        options.dims,
+        options.repeat
+    );
+    println!(
+        "{}",
+        format!(
+            "{:<20} {:>6} {:>16} {:>16}  {}",
+            "operation", "size", "semilinear", "presburger", "note"
+        )
+        .bold()
+    );
+
+    let mut size = 1;
+    while size <= options.max_size {
+        let a = generate(&mut seed, size, options.dims, true);
+        let b = generate(&mut seed, size, options.dims, true);
+        let general_a = generate(&mut seed, size, options.dims, false);
+        let general_b = generate(&mut seed, size, options.dims, false);
+
+        let (conversion_ms, pa) =
+            time_ms(options.repeat, || PresburgerSet::from_semilinear_set(&a));
+        print_row(
+            "to presburger",
+            size,
+            None,
+            Some(conversion_ms),
+            "conversion cost",
+        );
+        let pb = PresburgerSet::from_semilinear_set(&b);
+
+        let (semilinear_ms, _) = time_ms(options.repeat, || a.clone().plus(b.clone()));
+        let (presburger_ms, _) = time_ms(options.repeat, || pa.union(&pb));
+        print_row("union", size, Some(semilinear_ms), Some(presburger_ms), "");
+
+        if size <= MAX_STAR_COMPONENTS {
+            let (semilinear_ms, _) = time_ms(options.repeat, || general_a.clone().star());
+            print_row(
+                "star",
+                size,
+                Some(semilinear_ms),
+                None,
+                "not supported by ISL",
+            );
+        } else {
+            print_row("star", size, None, None, "skipped (exponential)");
+        }
+
+        difference_row("difference (boxes)", size, options.repeat, &a, &b);
+        difference_row(
+            "difference (general)",
+            size,
+            options.repeat,
+            &general_a,
+            &general_b,
+        );
+
+        // Emptiness of a \ b in both representations
+        let sdiff = SPresburgerSet::from_semilinear(a.clone())
+            .difference(SPresburgerSet::from_semilinear(b.clone()));
+        let (semilinear_ms, _) = time_ms(options.repeat, || sdiff.clone().is_empty());
+        let pdiff = pa.difference(&pb);
+        let (presburger_ms, _) = time_ms(options.repeat, || pdiff.is_empty());
+        print_row(
+            "emptiness",
+            size,
+            Some(semilinear_ms),
+            Some(presburger_ms),
+            "",
+        );
+
+        size *= 2;
+    }
+
+    proof_parser_rows(&mut seed, options.repeat);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let args: Vec<String> = ["--max-size", "8", "--dims", "2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let options = parse_options(&args).unwrap();
+        assert_eq!(options.max_size, 8);
+        assert_eq!(options.dims, 2);
+        assert_eq!(options.repeat, 3);
+
+        assert!(parse_options(&["--dims".to_string()]).is_err());
+        assert!(parse_options(&["--repeat".to_string(), "0".to_string()]).is_err());
+        assert!(parse_options(&["--bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_generate_proof() {
+        let mut seed = 1;
+        let proof = generate_proof(&mut seed, 100, 4);
+        let invariant = parse_proof_file(&proof).unwrap();
+        assert_eq!(invariant.variables.len(), 4);
+        assert_eq!(invariant.formula.num_constraints(), 300);
+        assert!(parse_proof_file(&generate_nested_proof(1000)).is_ok());
+    }
+
+    #[test]
+    fn test_generate_boxes() {
+        let mut seed = 1;
+        let set = generate(&mut seed, 5, 3, true);
+        assert_eq!(set.components.len(), 5);
+        assert!(
+            set.components
+                .iter()
+                .all(|lin| lin.periods.iter().all(|p| p.values.len() == 1))
+        );
+        // Box-shaped sets stay in the directly supported fragment
+        assert!(set.complement(&['a', 'b', 'c']).is_some());
+    }
+}
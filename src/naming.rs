@@ -0,0 +1,182 @@
+//! Names of Petri net places in generated files.
+//!
+//! Places are structured values (nested `Either`s of request and state names) whose
+//! `Display` output is long and unwieldy in `.net` files and SMPT proofs. Every name that goes into a `.net` file, an SMPT XML query, or comes back in an SMPT
+//! proof goes through `place_name`, which applies the selected `NamingScheme` and
+//! records the name in a bidirectional map. The map is saved next to the SMPT inputs,
+//! so proofs and traces can be translated back to structured places.
+//!
+//! Transitions are always named `t0`, `t1`, ... by their index in the net.
+
+use crate::deterministic_map::HashMap;
+use crate::ident::{Target, encode};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// How places are named in generated files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NamingScheme {
+    /// The `Display` output, encoded by `ident` (default)
+    Full,
+    /// `p0`, `p1`, ... in the order places are first named
+    Short,
+    /// `p` followed by a hash of the `Display` output, stable across runs
+    Hashed,
+}
+
+impl NamingScheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(NamingScheme::Full),
+            "short" => Some(NamingScheme::Short),
+            "hashed" => Some(NamingScheme::Hashed),
+            _ => None,
+        }
+    }
+}
+
+/// Bidirectional map between the `Display` output of places and their names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameMap {
+    pub scheme: NamingScheme,
+    /// (name in generated files, `Display` output of the place)
+    pub names: Vec<(String, String)>,
+    #[serde(skip)]
+    by_display: HashMap<String, usize>,
+    #[serde(skip)]
+    by_name: HashMap<String, usize>,
+}
+
+impl NameMap {
+    pub fn new(scheme: NamingScheme) -> Self {
+        NameMap {
+            scheme,
+            names: Vec::new(),
+            by_display: HashMap::default(),
+            by_name: HashMap::default(),
+        }
+    }
+
+    /// Name of the place with the given `Display` output, assigning one if needed
+    pub fn name(&mut self, display: &str) -> String {
+        if let Some(&i) = self.by_display.get(display) {
+            return self.names[i].0.clone();
+        }
+        let name = match self.scheme {
+            NamingScheme::Full => encode(display, Target::PetriNet),
+            NamingScheme::Short => format!("p{}", self.names.len()),
+            NamingScheme::Hashed => format!("p{:016x}", fnv1a(display)),
+        };
+        if let Some(&i) = self.by_name.get(&name) {
+            eprintln!(
+                "Warning: places `{}` and `{}` are both named `{}`",
+                self.names[i].1, display, name
+            );
+        }
+        let i = self.names.len();
+        self.names.push((name.clone(), display.to_string()));
+        self.by_display.insert(display.to_string(), i);
+        self.by_name.entry(name.clone()).or_insert(i);
+        name
+    }
+
+    /// `Display` output of the place with the given name
+    pub fn original(&self, name: &str) -> Option<&str> {
+        self.by_name.get(name).map(|&i| self.names[i].1.as_str())
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let mut map: NameMap = serde_json::from_str(json)?;
+        for (i, (name, display)) in map.names.iter().enumerate() {
+            map.by_display.insert(display.clone(), i);
+            map.by_name.entry(name.clone()).or_insert(i);
+        }
+        Ok(map)
+    }
+}
+
+/// FNV-1a, used instead of `DefaultHasher` because its output is fixed across releases
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Name map of the current analysis
+static NAME_MAP: Mutex<Option<NameMap>> = Mutex::new(None);
+
+/// Select the naming scheme (called from `main.rs`, before any place is named)
+pub fn set_naming_scheme(scheme: NamingScheme) {
+    *NAME_MAP.lock().unwrap() = Some(NameMap::new(scheme));
+}
+
+/// Forget the names assigned so far, keeping the scheme. Called at the start of every
+/// analysis, so that the names and the saved name map of a file don't depend on the
+/// files analyzed before it.
+pub fn reset_name_map() {
+    if let Some(map) = NAME_MAP.lock().unwrap().as_mut() {
+        *map = NameMap::new(map.scheme);
+    }
+}
+
+/// Name of a place in generated files, given its `Display` output
+pub fn place_name(display: &str) -> String {
+    NAME_MAP
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| NameMap::new(NamingScheme::Full))
+        .name(display)
+}
+
+/// File the name map is saved to in an output directory
+pub fn name_map_path(out_dir: &str) -> String {
+    crate::utils::file::join(out_dir, "place_names.json")
+}
+
+/// Save the names assigned so far to `<out_dir>/place_names.json`
+pub fn save_name_map(out_dir: &str) -> Result<(), String> {
+    let json = match NAME_MAP.lock().unwrap().as_ref() {
+        Some(map) => map.to_json().map_err(|e| e.to_string())?,
+        None => return Ok(()),
+    };
+    crate::utils::file::safe_write_file(&name_map_path(out_dir), &json).map_err(|e| e.to_string())
+}
+
+/// Load a saved name map
+pub fn load_name_map(path: &str) -> Result<NameMap, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    NameMap::from_json(&json).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming_schemes() {
+        let place = "Right(Left((inc, L0)))";
+
+        let mut full = NameMap::new(NamingScheme::Full);
+        assert_eq!(full.name(place), "Right_28_Left_28__28_inc_2c__20_L0_29__29__29_");
+
+        let mut short = NameMap::new(NamingScheme::Short);
+        assert_eq!(short.name(place), "p0");
+        assert_eq!(short.name("G0"), "p1");
+        assert_eq!(short.name(place), "p0");
+        assert_eq!(short.original("p1"), Some("G0"));
+
+        let mut hashed = NameMap::new(NamingScheme::Hashed);
+        let name = hashed.name(place);
+        assert_eq!(name.len(), 17);
+        assert_eq!(NameMap::new(NamingScheme::Hashed).name(place), name);
+
+        // The map survives a round trip through its file format
+        let loaded = NameMap::from_json(&short.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.original("p0"), Some(place));
+        assert_eq!(loaded.scheme, NamingScheme::Short);
+    }
+}
@@ -7,6 +7,7 @@
 
 use crate::deterministic_map::{HashMap, HashSet};
 use crate::ns_to_petri::ReqPetriState;
+use crate::parser::Span;
 use crate::petri::Petri;
 use colored::*;
 use either::*;
@@ -18,7 +19,7 @@ use crate::kleene::{Kleene, Regex, nfa_to_kleene};
 use crate::semilinear::*;
 
 // Use the shared utility function for GraphViz escaping
-use crate::utils::string::escape_for_graphviz_id;
+use crate::ident::{Target, encode};
 
 // Type aliases to reduce complexity
 type PetriPlace<L, G, Req, Resp> =
@@ -28,6 +29,287 @@ type PetriTraceStep<L, G, Req, Resp> = (
     Vec<PetriPlace<L, G, Req, Resp>>,
 );
 
+/// Whether requests found by `NS::oblivious_requests` are left out of the analysis
+pub fn set_read_only_fast_path(enabled: bool) {
+    crate::options::update(|options| options.read_only_fast_path = enabled);
+}
+
+pub fn read_only_fast_path_enabled() -> bool {
+    crate::options::get(|options| options.read_only_fast_path)
+}
+
+/// Steps of each random simulation, before its draining phase (see `Petri::simulate`)
+pub const SIMULATION_STEPS: usize = 200;
+
+/// Number of random simulations to run before the analysis (`--simulate N`)
+pub fn set_simulation_runs(runs: usize) {
+    crate::options::update(|options| options.simulation_runs = runs);
+}
+
+pub fn simulation_runs() -> usize {
+    crate::options::get(|options| options.simulation_runs)
+}
+
+/// Number of guided simulations to run before the analysis (`--simulate-guided N`)
+pub fn set_guided_simulation_runs(runs: usize) {
+    crate::options::update(|options| options.guided_simulation_runs = runs);
+}
+
+pub fn guided_simulation_runs() -> usize {
+    crate::options::get(|options| options.guided_simulation_runs)
+}
+
+/// How often a completed (request, response) pair counts when comparing an execution
+/// with the serial ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateResponses {
+    /// Every completion counts: executions are compared as multisets
+    Multiset,
+    /// Only whether a pair completed counts, as if every request were idempotent
+    Set,
+}
+
+impl DuplicateResponses {
+    /// Parse a mode name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "multiset" => Some(DuplicateResponses::Multiset),
+            "set" => Some(DuplicateResponses::Set),
+            _ => None,
+        }
+    }
+}
+
+/// Set how duplicate completions count (`--duplicates multiset|set`)
+pub fn set_duplicate_responses(mode: DuplicateResponses) {
+    crate::options::update(|options| options.duplicate_responses = mode);
+}
+
+pub fn duplicate_responses() -> DuplicateResponses {
+    crate::options::get(|options| options.duplicate_responses)
+}
+
+/// Let the server crash at any time, resetting the globals (`--model-crashes`). Front
+/// ends add the crash as a request of its own, so a serializable execution may crash
+/// between requests as often as the concurrent one does.
+pub fn set_model_crashes(enabled: bool) {
+    crate::options::update(|options| options.model_crashes = enabled);
+}
+
+pub fn model_crashes_enabled() -> bool {
+    crate::options::get(|options| options.model_crashes)
+}
+
+/// Completed requests of a quiescent marking, or `None` if the places that must be
+/// zero at the end hold tokens
+fn quiescent_completed<P>(
+    marking: &HashMap<Either<P, P>, usize>,
+    must_be_zero: &HashSet<Either<P, P>>,
+) -> Option<SparseVector<P>>
+where
+    P: Clone + Ord + Hash,
+{
+    if marking.keys().any(|place| must_be_zero.contains(place)) {
+        return None;
+    }
+    let mut completed = SparseVector::new();
+    for (place, count) in marking {
+        if let Right(place) = place {
+            completed.set(place.clone(), *count);
+        }
+    }
+    Some(completed)
+}
+
+/// Run random simulations of the serializability problem's Petri net and check the
+/// completed requests of every quiescent marking against the serializable set.
+/// Returns the fired transitions of the first run that reaches a marking outside it.
+fn simulate_serializability<P>(
+    petri: &Petri<Either<P, P>>,
+    places_that_must_be_zero: &[P],
+    ser: &SemilinearSet<P>,
+    runs: usize,
+) -> Option<Vec<(Vec<Either<P, P>>, Vec<Either<P, P>>)>>
+where
+    P: Clone + Ord + Hash + Display,
+{
+    let must_be_zero: HashSet<Either<P, P>> = places_that_must_be_zero
+        .iter()
+        .map(|place| Left(place.clone()))
+        .collect();
+    let transitions = petri.get_transitions();
+    let mut checked: HashSet<SparseVector<P>> = HashSet::default();
+    let mut quiescent = 0;
+    for run in 0..runs {
+        let seed = (run as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+        let mut counterexample = None;
+        petri.simulate(SIMULATION_STEPS, seed, |marking, fired| {
+            let Some(completed) = quiescent_completed(marking, &must_be_zero) else {
+                return false;
+            };
+            quiescent += 1;
+            if !checked.insert(completed.clone()) || ser.contains(&completed) {
+                return false;
+            }
+            let trace: Vec<_> = fired.iter().map(|&i| transitions[i].clone()).collect();
+            counterexample = Some((trace, completed));
+            true
+        });
+        if let Some((trace, completed)) = counterexample {
+            println!(
+                "{} run {} of {} reached completed requests outside the serializable set: {}",
+                "Simulation:".cyan(),
+                run + 1,
+                runs,
+                completed
+            );
+            return Some(trace);
+        }
+    }
+    println!(
+        "{} {} runs reached {} quiescent markings with {} distinct completed multisets, all serializable",
+        "Simulation:".cyan(),
+        runs,
+        quiescent,
+        checked.len()
+    );
+    None
+}
+
+/// Like `simulate_serializability`, but steers each run towards the disjuncts of
+/// the complement of the serializable set with `Petri::simulate_guided`. The distance
+/// of a marking to a disjunct is how far it is from satisfying the disjunct's
+/// constraints, leaving out those with existential variables, and a run aims at the
+/// closest disjunct. A marking outside the serializable set is still recognized
+/// exactly; the disjunct reported for it is the first whose constraints without
+/// existential variables it satisfies, if any. Returns that disjunct and the fired
+/// transitions of the first run that hits one.
+#[cfg(feature = "isl")]
+fn simulate_guided_serializability<P>(
+    petri: &Petri<Either<P, P>>,
+    places_that_must_be_zero: &[P],
+    ser: &SemilinearSet<P>,
+    runs: usize,
+) -> Option<(Option<usize>, Vec<(Vec<Either<P, P>>, Vec<Either<P, P>>)>)>
+where
+    P: Clone + Ord + Hash + Display + Debug,
+{
+    use crate::constraints::{ConstraintType, Variable};
+
+    let disjuncts: Vec<Vec<crate::constraints::Constraint<Variable<Either<P, P>>>>> =
+        crate::reachability_with_proofs::complement_disjuncts(
+            petri,
+            places_that_must_be_zero,
+            ser.clone(),
+        )
+        .into_iter()
+        .map(|disjunct| {
+            disjunct
+                .constraints()
+                .iter()
+                .filter(|constraint| {
+                    constraint
+                        .linear_combination()
+                        .iter()
+                        .all(|(_, var)| matches!(var, Variable::Var(_)))
+                })
+                .cloned()
+                .collect()
+        })
+        .collect();
+    if disjuncts.is_empty() {
+        println!(
+            "{} the complement of the serializable set is empty",
+            "Guided simulation:".cyan()
+        );
+        return None;
+    }
+    let violation = |marking: &HashMap<Either<P, P>, usize>,
+                     disjunct: &[crate::constraints::Constraint<Variable<Either<P, P>>>]|
+     -> u64 {
+        disjunct
+            .iter()
+            .map(|constraint| {
+                let value: i64 = constraint
+                    .linear_combination()
+                    .iter()
+                    .map(|(coeff, var)| match var {
+                        Variable::Var(place) => {
+                            *coeff as i64 * marking.get(place).copied().unwrap_or(0) as i64
+                        }
+                        Variable::Existential(_) => 0,
+                    })
+                    .sum::<i64>()
+                    + constraint.constant_term() as i64;
+                match constraint.constraint_type() {
+                    ConstraintType::NonNegative => (-value).max(0) as u64,
+                    ConstraintType::EqualToZero => value.unsigned_abs(),
+                }
+            })
+            .sum()
+    };
+
+    let must_be_zero: HashSet<Either<P, P>> = places_that_must_be_zero
+        .iter()
+        .map(|place| Left(place.clone()))
+        .collect();
+    let transitions = petri.get_transitions();
+    let mut closest = u64::MAX;
+    for run in 0..runs {
+        let seed = (run as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+        let mut hit = None;
+        petri.simulate_guided(
+            2 * SIMULATION_STEPS,
+            seed,
+            |marking| {
+                let distance = disjuncts
+                    .iter()
+                    .map(|disjunct| violation(marking, disjunct))
+                    .min()
+                    .unwrap_or(0);
+                closest = closest.min(distance);
+                distance
+            },
+            |marking, fired| {
+                let Some(completed) = quiescent_completed(marking, &must_be_zero) else {
+                    return false;
+                };
+                if ser.contains(&completed) {
+                    return false;
+                }
+                let disjunct = disjuncts
+                    .iter()
+                    .position(|disjunct| violation(marking, disjunct) == 0);
+                let trace: Vec<_> = fired.iter().map(|&i| transitions[i].clone()).collect();
+                hit = Some((disjunct, trace, completed));
+                true
+            },
+        );
+        if let Some((disjunct, trace, completed)) = hit {
+            let which = match disjunct {
+                Some(index) => format!("disjunct {} of {}", index + 1, disjuncts.len()),
+                None => "the complement of the serializable set".to_string(),
+            };
+            println!(
+                "{} run {} of {} hit {} with completed requests {}",
+                "Guided simulation:".cyan(),
+                run + 1,
+                runs,
+                which,
+                completed
+            );
+            return Some((disjunct, trace));
+        }
+    }
+    println!(
+        "{} {} runs did not reach the complement of the serializable set (closest distance {})",
+        "Guided simulation:".cyan(),
+        runs,
+        closest
+    );
+    None
+}
+
 // Helper function to properly quote strings for GraphViz labels
 fn quote_for_graphviz(s: &str) -> String {
     format!("\"{}\"", s.replace('\"', "\\\""))
@@ -39,6 +321,9 @@ fn quote_for_graphviz(s: &str) -> String {
 /// - Req: Request type
 /// - Resp: Response type
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(bound(
+    deserialize = "G: Deserialize<'de>, L: Deserialize<'de>, Req: Deserialize<'de>, Resp: Deserialize<'de>"
+))]
 pub struct NS<G, L, Req, Resp> {
     /// Initial global state
     pub initial_global: G,
@@ -51,6 +336,86 @@ pub struct NS<G, L, Req, Resp> {
 
     /// State transitions (from_local, from_global, to_local, to_global)
     pub transitions: Vec<(L, G, L, G)>,
+
+    /// Bounds on how often requests may complete in a serializable execution
+    #[serde(default)]
+    pub multiplicity_bounds: Vec<MultiplicityBound<Req, Resp>>,
+
+    /// Requests already in flight in the initial state, at the given local state.
+    /// They have to complete like any other request; a serial execution runs each of
+    /// them once, atomically from its local state.
+    #[serde(default)]
+    pub initial_requests: Vec<(Req, L)>,
+
+    /// Requests whose completions with the same response count once: an execution
+    /// matches a serial one that completes them with the same responses, however
+    /// often each of them occurs in either
+    #[serde(default)]
+    pub idempotent_requests: Vec<Req>,
+
+    /// Documentation of requests, shown in reports and visualizations
+    #[serde(default)]
+    pub request_docs: Vec<(Req, String)>,
+
+    /// Where in the source of the system the execution of a local state continues,
+    /// for source-level traces (`--trace-detail source`)
+    #[serde(default)]
+    pub local_sources: Vec<(L, Span)>,
+}
+
+/// File formats a network system can be written in. All of them use the schema of
+/// the derived `Deserialize` implementation of `NS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NSFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl NSFormat {
+    /// Detect the format from a file extension
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "json" => Some(NSFormat::Json),
+            "yaml" | "yml" => Some(NSFormat::Yaml),
+            "toml" => Some(NSFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NSFormat::Json => "JSON",
+            NSFormat::Yaml => "YAML",
+            NSFormat::Toml => "TOML",
+        }
+    }
+}
+
+/// Global state of a product of a network system with an automaton (see `NS::product`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProductState<G, S>(pub G, pub S);
+
+impl<G: Display, S: Display> Display for ProductState<G, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.0, self.1)
+    }
+}
+
+/// The request `request` may complete at most `max` times in a serializable
+/// execution, counting only completions with `response` if given
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiplicityBound<Req, Resp> {
+    pub request: Req,
+    pub response: Option<Resp>,
+    pub max: usize,
+}
+
+impl<Req: PartialEq, Resp: PartialEq> MultiplicityBound<Req, Resp> {
+    /// Whether a completion of `req` with `resp` counts towards this bound
+    pub fn matches(&self, req: &Req, resp: &Resp) -> bool {
+        self.request == *req && self.response.as_ref().is_none_or(|r| r == resp)
+    }
 }
 
 impl<G, L, Req, Resp> NS<G, L, Req, Resp>
@@ -67,6 +432,11 @@ where
             requests: Vec::new(),
             responses: Vec::new(),
             transitions: Vec::new(),
+            multiplicity_bounds: Vec::new(),
+            initial_requests: Vec::new(),
+            idempotent_requests: Vec::new(),
+            request_docs: Vec::new(),
+            local_sources: Vec::new(),
         }
     }
 
@@ -108,12 +478,249 @@ where
         }
     }
 
+    /// Allow `request` to complete at most `max` times (only counting `response`, if given)
+    pub fn add_multiplicity_bound(&mut self, request: Req, response: Option<Resp>, max: usize) {
+        self.multiplicity_bounds.push(MultiplicityBound {
+            request,
+            response,
+            max,
+        });
+    }
+
+    /// Add a request that is already in flight at `local_state` in the initial state
+    pub fn add_initial_request(&mut self, request: Req, local_state: L) {
+        self.initial_requests.push((request, local_state));
+    }
+
+    /// Add `request`, which crashes the server: it goes from `start` to `done` in one
+    /// step that resets any global state to the initial one, and responds with
+    /// `response`. Requests in flight keep their local states and continue on the reset
+    /// globals.
+    pub fn add_crash_request(&mut self, request: Req, start: L, done: L, response: Resp) {
+        let globals: Vec<G> = self.get_global_states().into_iter().cloned().collect();
+        for global in globals {
+            let initial_global = self.initial_global.clone();
+            self.add_transition(start.clone(), global, done.clone(), initial_global);
+        }
+        self.add_request(request, start);
+        self.add_response(done, response);
+    }
+
+    /// Count the completions of `request` modulo duplication
+    pub fn add_idempotent_request(&mut self, request: Req) {
+        if !self.idempotent_requests.contains(&request) {
+            self.idempotent_requests.push(request);
+        }
+    }
+
+    /// Document `request`. Documenting a request again appends a paragraph, so requests
+    /// that are merged keep the documentation of all of them.
+    pub fn add_request_doc(&mut self, request: Req, doc: String) {
+        match self
+            .request_docs
+            .iter_mut()
+            .find(|(req, _)| *req == request)
+        {
+            Some((_, existing)) if *existing != doc => {
+                existing.push_str("\n\n");
+                existing.push_str(&doc);
+            }
+            Some(_) => {}
+            None => self.request_docs.push((request, doc)),
+        }
+    }
+
+    /// The documentation of `request`, if it has any
+    pub fn request_doc(&self, request: &Req) -> Option<&str> {
+        self.request_docs
+            .iter()
+            .find(|(req, _)| req == request)
+            .map(|(_, doc)| doc.as_str())
+    }
+
+    /// Record where the execution of `local` continues in the source. Front ends add
+    /// each local state once.
+    pub fn add_local_source(&mut self, local: L, span: Span) {
+        self.local_sources.push((local, span));
+    }
+
+    /// Where the execution of `local` continues in the source, if it is known
+    pub fn local_source(&self, local: &L) -> Option<&Span> {
+        self.local_sources
+            .iter()
+            .find(|(l, _)| l == local)
+            .map(|(_, span)| span)
+    }
+
+    /// Whether completions of `request` count modulo duplication, because it was
+    /// declared idempotent or because of `--duplicates set`
+    pub fn is_idempotent(&self, request: &Req) -> bool {
+        duplicate_responses() == DuplicateResponses::Set
+            || self.idempotent_requests.contains(request)
+    }
+
+    /// The same system with only the requests for which `keep` holds. Transitions
+    /// and responses are kept; those only reachable from dropped requests are dead.
+    pub fn restrict_requests(&self, mut keep: impl FnMut(&Req) -> bool) -> Self {
+        NS {
+            initial_global: self.initial_global.clone(),
+            requests: self
+                .requests
+                .iter()
+                .filter(|(req, _)| keep(req))
+                .cloned()
+                .collect(),
+            responses: self.responses.clone(),
+            transitions: self.transitions.clone(),
+            multiplicity_bounds: self
+                .multiplicity_bounds
+                .iter()
+                .filter(|bound| keep(&bound.request))
+                .cloned()
+                .collect(),
+            initial_requests: self
+                .initial_requests
+                .iter()
+                .filter(|(req, _)| keep(req))
+                .cloned()
+                .collect(),
+            idempotent_requests: self
+                .idempotent_requests
+                .iter()
+                .filter(|req| keep(req))
+                .cloned()
+                .collect(),
+            request_docs: self
+                .request_docs
+                .iter()
+                .filter(|(req, _)| keep(req))
+                .cloned()
+                .collect(),
+            local_sources: self.local_sources.clone(),
+        }
+    }
+
+    /// The same system with every request renamed by `f`. Requests that get the same
+    /// name are merged into one request type.
+    pub fn rename_requests<Req2>(&self, f: impl Fn(&Req) -> Req2) -> NS<G, L, Req2, Resp>
+    where
+        Req2: Clone + PartialEq + Eq + std::hash::Hash + std::fmt::Display,
+    {
+        let mut ns = NS {
+            initial_global: self.initial_global.clone(),
+            requests: Vec::new(),
+            responses: self.responses.clone(),
+            transitions: self.transitions.clone(),
+            multiplicity_bounds: Vec::new(),
+            initial_requests: Vec::new(),
+            idempotent_requests: Vec::new(),
+            request_docs: Vec::new(),
+            local_sources: self.local_sources.clone(),
+        };
+        for (req, local) in &self.requests {
+            ns.add_request(f(req), local.clone());
+        }
+        for (req, local) in &self.initial_requests {
+            ns.add_initial_request(f(req), local.clone());
+        }
+        for bound in &self.multiplicity_bounds {
+            ns.add_multiplicity_bound(f(&bound.request), bound.response.clone(), bound.max);
+        }
+        for req in &self.idempotent_requests {
+            ns.add_idempotent_request(f(req));
+        }
+        for (req, doc) in &self.request_docs {
+            ns.add_request_doc(f(req), doc.clone());
+        }
+        ns
+    }
+
+    /// The same system with every response relabeled by `f`, e.g. to abstract from
+    /// response details that a specification does not care about
+    pub fn relabel_responses<Resp2>(&self, f: impl Fn(&Resp) -> Resp2) -> NS<G, L, Req, Resp2>
+    where
+        Resp2: Clone + PartialEq + Eq + std::hash::Hash + std::fmt::Display,
+    {
+        let mut ns = NS {
+            initial_global: self.initial_global.clone(),
+            requests: self.requests.clone(),
+            responses: Vec::new(),
+            transitions: self.transitions.clone(),
+            multiplicity_bounds: Vec::new(),
+            initial_requests: self.initial_requests.clone(),
+            idempotent_requests: self.idempotent_requests.clone(),
+            request_docs: self.request_docs.clone(),
+            local_sources: self.local_sources.clone(),
+        };
+        for (local, resp) in &self.responses {
+            ns.add_response(local.clone(), f(resp));
+        }
+        for bound in &self.multiplicity_bounds {
+            ns.add_multiplicity_bound(
+                bound.request.clone(),
+                bound.response.as_ref().map(&f),
+                bound.max,
+            );
+        }
+        ns
+    }
+
+    /// Synchronous product with a finite automaton that observes the transitions of
+    /// this system. The automaton starts in `initial`; `step` gives its successor on
+    /// a transition `(from_local, from_global, to_local, to_global)`, or `None` to
+    /// block the transition. Global states of the product pair a global state with an
+    /// automaton state; only pairs reachable from the initial pair are built.
+    pub fn product<S>(
+        &self,
+        initial: S,
+        step: impl Fn(&S, &(L, G, L, G)) -> Option<S>,
+    ) -> NS<ProductState<G, S>, L, Req, Resp>
+    where
+        S: Clone + PartialEq + Eq + std::hash::Hash + std::fmt::Display,
+    {
+        let initial_global = ProductState(self.initial_global.clone(), initial);
+        let mut ns = NS {
+            initial_global: initial_global.clone(),
+            requests: self.requests.clone(),
+            responses: self.responses.clone(),
+            transitions: Vec::new(),
+            multiplicity_bounds: self.multiplicity_bounds.clone(),
+            initial_requests: self.initial_requests.clone(),
+            idempotent_requests: self.idempotent_requests.clone(),
+            request_docs: self.request_docs.clone(),
+            local_sources: self.local_sources.clone(),
+        };
+
+        let mut seen: HashSet<ProductState<G, S>> = HashSet::default();
+        seen.insert(initial_global.clone());
+        let mut todo = vec![initial_global];
+        while let Some(ProductState(g, s)) = todo.pop() {
+            for transition in self.transitions.iter().filter(|t| t.1 == g) {
+                let Some(s2) = step(&s, transition) else {
+                    continue;
+                };
+                let (from_local, _, to_local, to_global) = transition;
+                let target = ProductState(to_global.clone(), s2);
+                ns.add_transition(
+                    from_local.clone(),
+                    ProductState(g.clone(), s.clone()),
+                    to_local.clone(),
+                    target.clone(),
+                );
+                if seen.insert(target.clone()) {
+                    todo.push(target);
+                }
+            }
+        }
+        ns
+    }
+
     /// Get all unique local states in the network system
     pub fn get_local_states(&self) -> Vec<&L> {
         let mut local_states = HashSet::default();
 
         // Collect local states from requests
-        for (_, local) in &self.requests {
+        for (_, local) in self.requests.iter().chain(&self.initial_requests) {
             local_states.insert(local);
         }
 
@@ -145,10 +752,42 @@ where
         globals.into_iter().collect()
     }
 
+    /// The global states that may be reachable, each with a path of global states
+    /// from the initial one. A transition may fire once its local and global states
+    /// may be reachable, ignoring how many requests are in each local state, so this
+    /// over-approximates: a global state left out is certainly unreachable.
+    pub fn reachable_global_states(&self) -> HashMap<&G, Vec<&G>> {
+        let mut paths: HashMap<&G, Vec<&G>> = HashMap::default();
+        paths.insert(&self.initial_global, vec![&self.initial_global]);
+        let mut locals: HashSet<&L> = self
+            .requests
+            .iter()
+            .chain(&self.initial_requests)
+            .map(|(_, local)| local)
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (from_local, from_global, to_local, to_global) in &self.transitions {
+                if !locals.contains(from_local) || !paths.contains_key(from_global) {
+                    continue;
+                }
+                changed |= locals.insert(to_local);
+                if !paths.contains_key(to_global) {
+                    let mut path = paths[from_global].clone();
+                    path.push(to_global);
+                    paths.insert(to_global, path);
+                    changed = true;
+                }
+            }
+        }
+        paths
+    }
+
     /// Get all unique requests in the network system
     pub fn get_requests(&self) -> Vec<&Req> {
         let mut requests = HashSet::default();
-        for (req, _) in &self.requests {
+        for (req, _) in self.requests.iter().chain(&self.initial_requests) {
             requests.insert(req);
         }
         requests.into_iter().collect()
@@ -163,6 +802,97 @@ where
         responses.into_iter().collect()
     }
 
+    /// Local states that instances of `req` can reach, in any global state
+    pub fn request_locals(&self, req: &Req) -> HashSet<&L> {
+        let mut reached: HashSet<&L> = HashSet::default();
+        let mut todo: Vec<&L> = self
+            .requests
+            .iter()
+            .chain(&self.initial_requests)
+            .filter(|(r, _)| r == req)
+            .map(|(_, local)| local)
+            .collect();
+        while let Some(local) = todo.pop() {
+            if reached.insert(local) {
+                for (from_local, _, to_local, _) in &self.transitions {
+                    if from_local == local {
+                        todo.push(to_local);
+                    }
+                }
+            }
+        }
+        reached
+    }
+
+    /// Distinct requests, in the order they are declared
+    fn requests_in_order(&self) -> Vec<&Req> {
+        let mut seen = HashSet::default();
+        self.requests
+            .iter()
+            .chain(&self.initial_requests)
+            .map(|(req, _)| req)
+            .filter(|req| seen.insert(*req))
+            .collect()
+    }
+
+    /// Requests that never change the global state.
+    ///
+    /// These do not in general commute with other requests: a read-only request can
+    /// observe the global state between two atomic steps of another request, which no
+    /// serial execution shows. See `oblivious_requests` for the ones that do.
+    pub fn read_only_requests(&self) -> Vec<Req> {
+        self.requests_in_order()
+            .into_iter()
+            .filter(|req| {
+                let locals = self.request_locals(req);
+                self.transitions
+                    .iter()
+                    .all(|(from_local, from_global, _, to_global)| {
+                        !locals.contains(from_local) || from_global == to_global
+                    })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Read-only requests that also take the same local steps in every global state,
+    /// and have no multiplicity bounds. They can be left out of the analysis:
+    ///
+    /// - Their steps can be removed from any execution without disabling the steps of
+    ///   other requests, which never see their local states and see the same globals.
+    /// - What remains is an execution of the system without them. If that system is
+    ///   serializable, the remaining completions have a serial witness, and each
+    ///   removed instance can run after it, in whatever global state it ends in,
+    ///   taking the same local steps to the same response.
+    ///
+    /// So the system is serializable if and only if the system without these requests
+    /// is (the converse holds because its executions are executions of the system).
+    pub fn oblivious_requests(&self) -> Vec<Req> {
+        let globals = self.get_global_states();
+        self.read_only_requests()
+            .into_iter()
+            .filter(|req| {
+                if self.multiplicity_bounds.iter().any(|bound| &bound.request == req) {
+                    return false;
+                }
+                // The successors of each local state must not depend on the global state
+                self.request_locals(req).into_iter().all(|local| {
+                    let successors = |global: &G| -> HashSet<&L> {
+                        self.transitions
+                            .iter()
+                            .filter(|(from_local, from_global, _, _)| {
+                                from_local == local && from_global == global
+                            })
+                            .map(|(_, _, to_local, _)| to_local)
+                            .collect()
+                    };
+                    let first = successors(&self.initial_global);
+                    globals.iter().all(|global| successors(global) == first)
+                })
+            })
+            .collect()
+    }
+
     /// Make an automaton corresponding to the serialized executions of the network system
     /// An element (g, req, resp, g') is present if there is a
     /// - request req in the network system that goes to some local state l
@@ -174,28 +904,8 @@ where
         for g in self.get_global_states() {
             // iterate over all requests
             for (req, l) in &self.requests {
-                // find all reachable states from (l, g)
-                let mut todo = vec![(l, g)];
-                let mut reached = HashSet::default();
-                while let Some((l, g)) = todo.pop() {
-                    reached.insert((l, g));
-                    for (l1, g1, l2, g2) in &self.transitions {
-                        if l == l1 && g == g1 && !reached.contains(&(l2, g2)) {
-                            todo.push((l2, g2));
-                        }
-                    }
-                }
-                // find all reachable responses from (l, g)
-                let mut reached_responses: HashSet<(&Resp, &G)> = HashSet::default();
-                for (l, g) in reached {
-                    for (l2, resp) in &self.responses {
-                        if l == l2 {
-                            reached_responses.insert((resp, g));
-                        }
-                    }
-                }
                 // add all reachable (g, req, resp, g') to the serialized automaton
-                for (resp, g2) in reached_responses {
+                for (resp, g2) in self.serial_outcomes(l, g) {
                     serialized_automaton.push((g.clone(), req.clone(), resp.clone(), g2.clone()));
                 }
             }
@@ -203,10 +913,42 @@ where
         serialized_automaton
     }
 
+    /// The responses and final global states of a request that runs alone from local
+    /// state `l` in global state `g`
+    fn serial_outcomes<'a>(&'a self, l: &'a L, g: &'a G) -> HashSet<(&'a Resp, &'a G)> {
+        // find all reachable states from (l, g)
+        let mut todo = vec![(l, g)];
+        let mut reached = HashSet::default();
+        while let Some((l, g)) = todo.pop() {
+            reached.insert((l, g));
+            for (l1, g1, l2, g2) in &self.transitions {
+                if l == l1 && g == g1 && !reached.contains(&(l2, g2)) {
+                    todo.push((l2, g2));
+                }
+            }
+        }
+        // find all reachable responses from (l, g)
+        let mut reached_responses: HashSet<(&Resp, &G)> = HashSet::default();
+        for (l, g) in reached {
+            for (l2, resp) in &self.responses {
+                if l == l2 {
+                    reached_responses.insert((resp, g));
+                }
+            }
+        }
+        reached_responses
+    }
+
     pub fn serialized_automaton_kleene<K: Kleene + Clone>(
         &self,
         atom: impl Fn(Req, Resp) -> K,
     ) -> K {
+        if !self.multiplicity_bounds.is_empty()
+            || !self.initial_requests.is_empty()
+            || self.requests.iter().any(|(req, _)| self.is_idempotent(req))
+        {
+            return self.bounded_serialized_automaton_kleene(atom);
+        }
         let nfa: Vec<(G, K, G)> = self
             .serialized_automaton()
             .into_iter()
@@ -215,6 +957,106 @@ where
         nfa_to_kleene(&nfa, self.initial_global.clone())
     }
 
+    /// Like `serialized_automaton_kleene`, but on the product of the serialized automaton
+    /// with a counter per multiplicity bound and a flag per initial request that
+    /// records whether it has run. Edges that would exceed a bound are dropped, and
+    /// only states in which every initial request has run are accepting.
+    ///
+    /// The product also has a flag per (request, response) pair of an idempotent
+    /// request that records whether the pair completed. Once it has, later edges with
+    /// the pair may also be taken without counting it, and a loop on every state
+    /// counts it again, so the language is closed under changing the number of
+    /// occurrences of such a pair between any two positive numbers.
+    fn bounded_serialized_automaton_kleene<K: Kleene + Clone>(
+        &self,
+        atom: impl Fn(Req, Resp) -> K,
+    ) -> K {
+        let mut edges_from: HashMap<G, Vec<(Option<usize>, Req, Resp, G)>> = HashMap::default();
+        for (g, req, resp, g2) in self.serialized_automaton() {
+            edges_from.entry(g).or_default().push((None, req, resp, g2));
+        }
+        for g in self.get_global_states() {
+            for (i, (req, l)) in self.initial_requests.iter().enumerate() {
+                for (resp, g2) in self.serial_outcomes(l, g) {
+                    edges_from.entry(g.clone()).or_default().push((
+                        Some(i),
+                        req.clone(),
+                        resp.clone(),
+                        g2.clone(),
+                    ));
+                }
+            }
+        }
+
+        let mut idempotent: Vec<(Req, Resp)> = Vec::new();
+        for (_, req, resp, _) in edges_from.values().flatten() {
+            let pair = (req.clone(), resp.clone());
+            if self.is_idempotent(req) && !idempotent.contains(&pair) {
+                idempotent.push(pair);
+            }
+        }
+
+        type State<T> = (T, Vec<usize>, Vec<bool>, Vec<bool>);
+        let start: State<G> = (
+            self.initial_global.clone(),
+            vec![0; self.multiplicity_bounds.len()],
+            vec![false; self.initial_requests.len()],
+            vec![false; idempotent.len()],
+        );
+        let mut nfa: Vec<(State<G>, K, State<G>)> = Vec::new();
+        let mut seen = HashSet::default();
+        seen.insert(start.clone());
+        let mut todo = vec![start.clone()];
+        while let Some((g, counts, done, completed)) = todo.pop() {
+            let state = (g.clone(), counts.clone(), done.clone(), completed.clone());
+            for (i, (req, resp)) in idempotent.iter().enumerate() {
+                if completed[i] {
+                    nfa.push((
+                        state.clone(),
+                        atom(req.clone(), resp.clone()),
+                        state.clone(),
+                    ));
+                }
+            }
+            for (initial, req, resp, g2) in edges_from.get(&g).into_iter().flatten() {
+                let mut new_done = done.clone();
+                if let Some(i) = initial {
+                    if done[*i] {
+                        continue;
+                    }
+                    new_done[*i] = true;
+                }
+                let mut new_counts = counts.clone();
+                let mut within_bounds = true;
+                for (count, bound) in new_counts.iter_mut().zip(&self.multiplicity_bounds) {
+                    if bound.matches(req, resp) {
+                        *count += 1;
+                        within_bounds &= *count <= bound.max;
+                    }
+                }
+                if !within_bounds {
+                    continue;
+                }
+                let mut label = atom(req.clone(), resp.clone());
+                let mut new_completed = completed.clone();
+                if let Some(i) = idempotent.iter().position(|(r, v)| r == req && v == resp) {
+                    if completed[i] {
+                        label = label.plus(K::one());
+                    }
+                    new_completed[i] = true;
+                }
+                let target = (g2.clone(), new_counts, new_done, new_completed);
+                if seen.insert(target.clone()) {
+                    todo.push(target.clone());
+                }
+                nfa.push((state.clone(), label, target));
+            }
+        }
+        crate::kleene::nfa_to_kleene_accepting(&nfa, start, |(_, _, done, _)| {
+            done.iter().all(|&ran| ran)
+        })
+    }
+
     pub fn serialized_automaton_regex(&self) -> Regex<String> {
         self.serialized_automaton_kleene(|req, resp| Regex::Atom(format!("{req}/{resp}")))
     }
@@ -245,6 +1087,43 @@ where
         serde_json::from_str(json)
     }
 
+    /// Create a network system from a YAML string (same schema as JSON)
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yaml::Error>
+    where
+        for<'de> G: Deserialize<'de>,
+        for<'de> L: Deserialize<'de>,
+        for<'de> Req: Deserialize<'de>,
+        for<'de> Resp: Deserialize<'de>,
+    {
+        serde_yaml::from_str(yaml)
+    }
+
+    /// Create a network system from a TOML string (same schema as JSON)
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error>
+    where
+        for<'de> G: Deserialize<'de>,
+        for<'de> L: Deserialize<'de>,
+        for<'de> Req: Deserialize<'de>,
+        for<'de> Resp: Deserialize<'de>,
+    {
+        toml::from_str(toml)
+    }
+
+    /// Create a network system from a string in the given format
+    pub fn from_str_in_format(content: &str, format: NSFormat) -> Result<Self, String>
+    where
+        for<'de> G: Deserialize<'de>,
+        for<'de> L: Deserialize<'de>,
+        for<'de> Req: Deserialize<'de>,
+        for<'de> Resp: Deserialize<'de>,
+    {
+        match format {
+            NSFormat::Json => Self::from_json(content).map_err(|e| e.to_string()),
+            NSFormat::Yaml => Self::from_yaml(content).map_err(|e| e.to_string()),
+            NSFormat::Toml => Self::from_toml(content).map_err(|e| e.to_string()),
+        }
+    }
+
     /// Generate Graphviz DOT format for visualizing the network system
     pub fn to_graphviz(&self) -> String {
         let mut dot = String::from("digraph NetworkSystem {\n");
@@ -261,7 +1140,7 @@ where
         let local_state_nodes: Vec<_> = self
             .get_local_states()
             .iter()
-            .map(|local| format!("L_{}", escape_for_graphviz_id(&format!("{}", local))))
+            .map(|local| format!("L_{}", encode(&local.to_string(), Target::Graphviz)))
             .collect();
         if !local_state_nodes.is_empty() {
             dot.push_str(&format!(
@@ -273,7 +1152,7 @@ where
         let request_nodes: Vec<_> = self
             .get_requests()
             .iter()
-            .map(|req| format!("REQ_{}", escape_for_graphviz_id(&format!("{}", req))))
+            .map(|req| format!("REQ_{}", encode(&req.to_string(), Target::Graphviz)))
             .collect();
         if !request_nodes.is_empty() {
             dot.push_str(&format!(
@@ -285,7 +1164,7 @@ where
         let response_nodes: Vec<_> = self
             .get_responses()
             .iter()
-            .map(|resp| format!("RESP_{}", escape_for_graphviz_id(&format!("{}", resp))))
+            .map(|resp| format!("RESP_{}", encode(&resp.to_string(), Target::Graphviz)))
             .collect();
         if !response_nodes.is_empty() {
             dot.push_str(&format!(
@@ -299,7 +1178,7 @@ where
         dot.push_str("  // Local state nodes\n");
         let local_states = self.get_local_states();
         for local in local_states {
-            let id = format!("L_{}", escape_for_graphviz_id(&format!("{}", local)));
+            let id = format!("L_{}", encode(&local.to_string(), Target::Graphviz));
             let label = quote_for_graphviz(&format!("{}", local));
             dot.push_str(&format!("  {} [label={}];\n", id, label));
         }
@@ -309,14 +1188,17 @@ where
         let unique_requests = self.get_requests();
         for req in unique_requests {
             // Create request node with proper escaping
-            let req_id = format!("REQ_{}", escape_for_graphviz_id(&format!("{}", req)));
-            let req_label = quote_for_graphviz(&format!("{}", req));
+            let req_id = format!("REQ_{}", encode(&req.to_string(), Target::Graphviz));
+            let req_label = match self.request_doc(req) {
+                Some(doc) => quote_for_graphviz(&format!("{}\\n{}", req, doc.replace('\n', "\\n"))),
+                None => quote_for_graphviz(&format!("{}", req)),
+            };
             dot.push_str(&format!("  {} [label={}];\n", req_id, req_label));
 
             // Connect request to local states
             for (request, local) in &self.requests {
                 if request == req {
-                    let local_id = format!("L_{}", escape_for_graphviz_id(&format!("{}", local)));
+                    let local_id = format!("L_{}", encode(&local.to_string(), Target::Graphviz));
                     dot.push_str(&format!("  {} -> {} [style=dashed];\n", req_id, local_id));
                 }
             }
@@ -327,14 +1209,14 @@ where
         let unique_responses = self.get_responses();
         for resp in unique_responses {
             // Create response node with proper escaping
-            let resp_id = format!("RESP_{}", escape_for_graphviz_id(&format!("{}", resp)));
+            let resp_id = format!("RESP_{}", encode(&resp.to_string(), Target::Graphviz));
             let resp_label = quote_for_graphviz(&format!("{}", resp));
             dot.push_str(&format!("  {} [label={}];\n", resp_id, resp_label));
 
             // Connect local states to responses
             for (local, response) in &self.responses {
                 if response == resp {
-                    let local_id = format!("L_{}", escape_for_graphviz_id(&format!("{}", local)));
+                    let local_id = format!("L_{}", encode(&local.to_string(), Target::Graphviz));
                     dot.push_str(&format!("  {} -> {} [style=dashed];\n", local_id, resp_id));
                 }
             }
@@ -343,8 +1225,8 @@ where
         // Define transitions between local states with global states
         dot.push_str("\n  // Transitions between local states with global states\n");
         for (from_local, from_global, to_local, to_global) in &self.transitions {
-            let from_local_id = format!("L_{}", escape_for_graphviz_id(&format!("{}", from_local)));
-            let to_local_id = format!("L_{}", escape_for_graphviz_id(&format!("{}", to_local)));
+            let from_local_id = format!("L_{}", encode(&from_local.to_string(), Target::Graphviz));
+            let to_local_id = format!("L_{}", encode(&to_local.to_string(), Target::Graphviz));
             let transition_label = quote_for_graphviz(&format!("{} → {}", from_global, to_global));
 
             dot.push_str(&format!(
@@ -364,7 +1246,7 @@ where
         let global_nodes: Vec<_> = self
             .get_global_states()
             .iter()
-            .map(|g| format!("G_{}", escape_for_graphviz_id(&format!("{}", g))))
+            .map(|g| format!("G_{}", encode(&g.to_string(), Target::Graphviz)))
             .collect();
         if !global_nodes.is_empty() {
             dot.push_str(&format!("    node [style=\"filled, rounded\", fillcolor=lightblue] {}; // Global states\n\n",
@@ -378,7 +1260,7 @@ where
             let is_initial = &self.initial_global == global;
 
             // Create properly escaped IDs and labels
-            let global_id = format!("G_{}", escape_for_graphviz_id(&format!("{}", global)));
+            let global_id = format!("G_{}", encode(&global.to_string(), Target::Graphviz));
             let global_label = if is_initial {
                 quote_for_graphviz(&format!("{} (initial)", global))
             } else {
@@ -401,8 +1283,8 @@ where
         let serialized = self.serialized_automaton();
         for (from_global, req, resp, to_global) in &serialized {
             let from_global_id =
-                format!("G_{}", escape_for_graphviz_id(&format!("{}", from_global)));
-            let to_global_id = format!("G_{}", escape_for_graphviz_id(&format!("{}", to_global)));
+                format!("G_{}", encode(&from_global.to_string(), Target::Graphviz));
+            let to_global_id = format!("G_{}", encode(&to_global.to_string(), Target::Graphviz));
             let transition_label = quote_for_graphviz(&format!("{} / {}", req, resp));
 
             dot.push_str(&format!(
@@ -419,6 +1301,63 @@ where
         dot
     }
 
+    /// Generate a standalone Graphviz DOT graph of the serialized automaton.
+    ///
+    /// Parallel edges between the same pair of global states are merged into a
+    /// single edge listing all their `req / resp` labels.
+    pub fn serialized_automaton_to_graphviz(&self) -> String {
+        let mut dot = String::from("digraph SerializedAutomaton {\n");
+        dot.push_str("  rankdir=LR;\n");
+        dot.push_str("  node [fontsize=10, style=\"filled, rounded\", fillcolor=lightblue];\n");
+        dot.push_str("  edge [fontsize=10];\n\n");
+
+        for global in self.get_global_states() {
+            let global_id = format!("G_{}", encode(&global.to_string(), Target::Graphviz));
+            if &self.initial_global == global {
+                dot.push_str(&format!(
+                    "  {} [label={}, penwidth=3, color=darkgreen];\n",
+                    global_id,
+                    quote_for_graphviz(&format!("{} (initial)", global))
+                ));
+            } else {
+                dot.push_str(&format!(
+                    "  {} [label={}];\n",
+                    global_id,
+                    quote_for_graphviz(&format!("{}", global))
+                ));
+            }
+        }
+        dot.push('\n');
+
+        let mut edges: Vec<((String, String), Vec<String>)> = Vec::new();
+        for (from_global, req, resp, to_global) in self.serialized_automaton() {
+            let key = (
+                format!("G_{}", encode(&from_global.to_string(), Target::Graphviz)),
+                format!("G_{}", encode(&to_global.to_string(), Target::Graphviz)),
+            );
+            let label = format!("{} / {}", req, resp);
+            match edges.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, labels)) => {
+                    if !labels.contains(&label) {
+                        labels.push(label)
+                    }
+                }
+                None => edges.push((key, vec![label])),
+            }
+        }
+        for ((from_id, to_id), labels) in edges {
+            dot.push_str(&format!(
+                "  {} -> {} [label={}];\n",
+                from_id,
+                to_id,
+                quote_for_graphviz(&labels.join("\\n"))
+            ));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
     /// Save GraphViz DOT files to disk and generate visualizations
     ///
     /// # Arguments
@@ -438,6 +1377,16 @@ where
         self.save_graphviz(name, false)
     }
 
+    /// Save the standalone serialized automaton graph (see `serialized_automaton_to_graphviz`)
+    pub fn save_serialized_automaton_graphviz(
+        &self,
+        name: &str,
+        open_files: bool,
+    ) -> Result<Vec<String>, String> {
+        let dot_content = self.serialized_automaton_to_graphviz();
+        crate::graphviz::save_graphviz(&dot_content, name, "serialized", open_files)
+    }
+
     pub fn merge_requests(&mut self, other: &NS<G, L, Req, Resp>) {
         // Merge all requests
         for (req, l) in &other.requests {
@@ -457,20 +1406,67 @@ where
 
     /// Check if a trace can be executed by this NS
     /// Returns Ok(multiset of (request, response) pairs) if valid and no requests in flight
-    /// Returns Err(message) if invalid or if requests remain in flight
+    /// Returns a `TraceError` locating the failing step if invalid or if requests remain
+    /// in flight
     pub fn check_trace(
         &self,
         trace: &crate::ns_decision::NSTrace<G, L, Req, Resp>,
-    ) -> Result<Vec<(Req, Resp)>, String> {
-        use crate::ns_decision::NSStep;
+    ) -> Result<Vec<(Req, Resp)>, crate::ns_decision::TraceError> {
+        use crate::ns_decision::{NSStep, TraceError, TraceErrorKind};
 
         // Initialize simulation state
         let mut global_state = self.initial_global.clone();
-        let mut in_flight: Vec<(Req, L)> = Vec::new(); // Multiset of active requests
+        let mut in_flight: Vec<(Req, L)> = self.initial_requests.clone(); // Multiset of active requests
         let mut completed: Vec<(Req, Resp)> = Vec::new(); // Multiset of completed requests
 
+        let describe_state = |global_state: &G, in_flight: &[(Req, L)]| {
+            let in_flight_str: Vec<String> = in_flight
+                .iter()
+                .map(|(req, local)| format!("({}, {})", req, local))
+                .collect();
+            format!(
+                "global {} with requests in flight [{}]",
+                global_state,
+                in_flight_str.join(", ")
+            )
+        };
+        // Steps the instances of `request` in flight can take in `global_state`
+        let enabled_steps = |request: &Req, global_state: &G, in_flight: &[(Req, L)]| {
+            let steps: Vec<String> = self
+                .transitions
+                .iter()
+                .filter(|(from_local, from_global, _, _)| {
+                    from_global == global_state
+                        && in_flight
+                            .iter()
+                            .any(|(req, local)| req == request && local == from_local)
+                })
+                .map(|(from_local, _, to_local, to_global)| {
+                    format!("{} -> {} (global {})", from_local, to_local, to_global)
+                })
+                .collect();
+            (!steps.is_empty())
+                .then(|| format!("{} can step {}", request, steps.join(", ")))
+        };
+        let instances = |request: &Req, in_flight: &[(Req, L)]| {
+            let locals: Vec<String> = in_flight
+                .iter()
+                .filter(|(req, _)| req == request)
+                .map(|(_, local)| local.to_string())
+                .collect();
+            (!locals.is_empty())
+                .then(|| format!("{} is in flight at {}", request, locals.join(", ")))
+        };
+
         // Process each step in the trace
         for (step_idx, step) in trace.steps.iter().enumerate() {
+            let error = |kind, expected_enabled: String, suggestion: Option<String>| TraceError {
+                kind,
+                step_index: Some(step_idx),
+                expected_enabled,
+                actual_state: describe_state(&global_state, &in_flight),
+                suggestion,
+            };
             match step {
                 NSStep::RequestStart {
                     request,
@@ -481,9 +1477,28 @@ where
                         .requests
                         .contains(&(request.clone(), initial_local.clone()))
                     {
-                        return Err(format!(
-                            "Step {}: Unknown request type or wrong initial state: ({}, {})",
-                            step_idx, request, initial_local
+                        let starts: Vec<String> = self
+                            .requests
+                            .iter()
+                            .filter(|(req, _)| req == request)
+                            .map(|(_, local)| local.to_string())
+                            .collect();
+                        let suggestion = if starts.is_empty() {
+                            format!(
+                                "known requests: {}",
+                                self.requests_in_order()
+                                    .iter()
+                                    .map(|req| req.to_string())
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            )
+                        } else {
+                            format!("{} starts in {}", request, starts.join(", "))
+                        };
+                        return Err(error(
+                            TraceErrorKind::UnknownRequest,
+                            format!("a request ({}, {})", request, initial_local),
+                            Some(suggestion),
                         ));
                     }
 
@@ -500,9 +1515,10 @@ where
                 } => {
                     // Verify global state matches
                     if &global_state != from_global {
-                        return Err(format!(
-                            "Step {}: Global state mismatch: expected {}, found {}",
-                            step_idx, from_global, global_state
+                        return Err(error(
+                            TraceErrorKind::GlobalStateMismatch,
+                            format!("global {}", from_global),
+                            enabled_steps(request, &global_state, &in_flight),
                         ));
                     }
 
@@ -514,9 +1530,13 @@ where
                         to_global.clone(),
                     );
                     if !self.transitions.contains(&transition) {
-                        return Err(format!(
-                            "Step {}: Transition not found in NS: ({}, {}, {}, {})",
-                            step_idx, from_local, from_global, to_local, to_global
+                        return Err(error(
+                            TraceErrorKind::UnknownTransition,
+                            format!(
+                                "a transition ({}, {}, {}, {})",
+                                from_local, from_global, to_local, to_global
+                            ),
+                            enabled_steps(request, &global_state, &in_flight),
                         ));
                     }
 
@@ -525,9 +1545,10 @@ where
                     if let Some(pos) = in_flight.iter().position(|entry| entry == &request_entry) {
                         in_flight.remove(pos);
                     } else {
-                        return Err(format!(
-                            "Step {}: No active request found matching: ({}, {})",
-                            step_idx, request, from_local
+                        return Err(error(
+                            TraceErrorKind::NoActiveRequest,
+                            format!("a request ({}, {}) in flight", request, from_local),
+                            instances(request, &in_flight),
                         ));
                     }
 
@@ -548,9 +1569,18 @@ where
                         .responses
                         .contains(&(final_local.clone(), response.clone()))
                     {
-                        return Err(format!(
-                            "Step {}: Response not found in NS: ({}, {})",
-                            step_idx, final_local, response
+                        let responses: Vec<String> = self
+                            .responses
+                            .iter()
+                            .filter(|(local, _)| local == final_local)
+                            .map(|(_, resp)| resp.to_string())
+                            .collect();
+                        return Err(error(
+                            TraceErrorKind::UnknownResponse,
+                            format!("a response ({}, {})", final_local, response),
+                            (!responses.is_empty()).then(|| {
+                                format!("{} responds with {}", final_local, responses.join(", "))
+                            }),
                         ));
                     }
 
@@ -559,9 +1589,10 @@ where
                     if let Some(pos) = in_flight.iter().position(|entry| entry == &request_entry) {
                         in_flight.remove(pos);
                     } else {
-                        return Err(format!(
-                            "Step {}: No active request found matching: ({}, {})",
-                            step_idx, request, final_local
+                        return Err(error(
+                            TraceErrorKind::NoActiveRequest,
+                            format!("a request ({}, {}) in flight", request, final_local),
+                            instances(request, &in_flight),
                         ));
                     }
 
@@ -571,16 +1602,18 @@ where
             }
         }
 
-        // Check that no requests remain in flight
-        if !in_flight.is_empty() {
-            let in_flight_str: Vec<String> = in_flight
-                .iter()
-                .map(|(req, local)| format!("({}, {})", req, local))
-                .collect();
-            return Err(format!(
-                "Requests still in flight at end of trace: [{}]",
-                in_flight_str.join(", ")
-            ));
+        // Check that no requests remain in flight
+        if !in_flight.is_empty() {
+            return Err(TraceError {
+                kind: TraceErrorKind::RequestsInFlight,
+                step_index: None,
+                expected_enabled: "no requests in flight".to_string(),
+                actual_state: describe_state(&global_state, &in_flight),
+                suggestion: Some(
+                    "complete these requests, or drop the steps of those that cannot complete"
+                        .to_string(),
+                ),
+            });
         }
 
         Ok(completed)
@@ -595,6 +1628,7 @@ where
     Resp: Clone + Ord + Hash + Display + Debug,
 {
     /// Check if the network system is serializable using both methods and report results
+    #[cfg(feature = "isl")]
     #[must_use]
     pub fn is_serializable(&self, out_dir: &str) -> bool 
     where
@@ -603,13 +1637,15 @@ where
         Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
         Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
     {
+        crate::stats::set_output_dir(out_dir);
+
         // Create certificate with timing
         let decision = crate::stats::record_certificate_creation_time(|| {
             self.create_certificate(out_dir)
         });
         
         // Save certificate to standard location
-        let cert_path = format!("{}/certificate.json", out_dir);
+        let cert_path = crate::ns_decision::certificate_path(out_dir);
         if let Err(err) = decision.save_to_file(&cert_path) {
             eprintln!("Warning: Failed to save certificate: {}", err);
             // Continue with the in-memory decision
@@ -624,6 +1660,16 @@ where
             }
         };
         
+        if let crate::ns_decision::NSDecision::Serializable { invariant } = &loaded_decision {
+            crate::stats::set_invariant_size(
+                invariant
+                    .global_invariants
+                    .values()
+                    .map(|inv| inv.formula.num_constraints())
+                    .sum(),
+            );
+        }
+
         // Verify and return result with timing
         let result = crate::stats::record_certificate_checking_time(|| {
             self.verify_ns_decision(&loaded_decision)
@@ -648,7 +1694,21 @@ where
         // Print the semilinear set for compatibility
         println!();
         println!("Serialized automaton semilinear set:");
-        println!("{}", self.serialized_automaton_semilinear());
+        let serialized = self.serialized_automaton_semilinear();
+        println!("{}", serialized);
+        if crate::quantitative::quantitative_enabled() {
+            let report = crate::quantitative::analyze(&serialized);
+            crate::quantitative::print_report(&report);
+            if let Err(err) = crate::quantitative::save_report(out_dir, &report) {
+                eprintln!("Warning: Failed to save response counts: {}", err);
+            }
+        }
+        if crate::commutativity::commutativity_enabled() {
+            crate::commutativity::print_matrix(&crate::commutativity::commutativity_matrix(self));
+        }
+        if crate::quiescence::check_quiescence_enabled() {
+            crate::quiescence::print_report(&crate::quiescence::check(self));
+        }
         
         // Print decision details
         match &loaded_decision {
@@ -657,12 +1717,45 @@ where
                 println!("✅ PROOF CERTIFICATE FOUND");
                 println!();
                 invariant.pretty_print_with_verification(self);
+                if crate::explain::explain_enabled() {
+                    crate::explain::print_explanations(&crate::explain::explain_ns(invariant));
+                }
+                if crate::witness::witness_enabled() {
+                    let components = crate::witness::decompose(self, invariant);
+                    crate::witness::print_decomposition(&components);
+                    if let Err(err) = crate::witness::save_decomposition(out_dir, &components) {
+                        eprintln!("Warning: Failed to save witness decomposition: {}", err);
+                    }
+                }
+                if crate::ns_decision::latex_export_enabled() {
+                    let title = std::path::Path::new(out_dir)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    match invariant.save_latex(out_dir, &title) {
+                        Ok(path) => println!("LaTeX version of the invariant: {}", path),
+                        Err(err) => eprintln!("Warning: Failed to write LaTeX invariant: {}", err),
+                    }
+                }
+                if crate::cross_validate::cross_validate_enabled() {
+                    crate::cross_validate::report(self, invariant, out_dir);
+                }
             }
             crate::ns_decision::NSDecision::NotSerializable { trace } => {
                 println!();
                 println!("❌ COUNTEREXAMPLE TRACE FOUND");
                 println!();
-                trace.pretty_print(self);
+                trace.print(self, crate::ns_decision::trace_detail());
+                match crate::codegen::save_rust_test(trace, out_dir) {
+                    Ok(path) => println!("Test skeleton for this interleaving: {}", path),
+                    Err(err) => eprintln!("Warning: Failed to write test skeleton: {}", err),
+                }
+                match crate::mermaid::save_sequence_diagram(trace, out_dir) {
+                    Ok(path) => println!("Mermaid sequence diagram: {}", path),
+                    Err(err) => eprintln!("Warning: Failed to write sequence diagram: {}", err),
+                }
+                crate::reachability::get_debug_logger()
+                    .trace_diagram(crate::mermaid::sequence_diagram(trace));
             }
             crate::ns_decision::NSDecision::Timeout { message } => {
                 println!();
@@ -670,6 +1763,18 @@ where
                 println!();
                 println!("{}", message);
             }
+            crate::ns_decision::NSDecision::Unknown {
+                reason,
+                partial_results,
+            } => {
+                println!();
+                println!("❓ ANALYSIS INCONCLUSIVE");
+                println!();
+                println!("{}", reason);
+                for r in partial_results {
+                    println!("  Disjunct {}: {:?}", r.disjunct, r.outcome);
+                }
+            }
         }
         
         // Determine the result and stats string based on decision type
@@ -677,6 +1782,7 @@ where
             crate::ns_decision::NSDecision::Serializable { .. } => ("✅", "SERIALIZABLE".green().bold(), "serializable"),
             crate::ns_decision::NSDecision::NotSerializable { .. } => ("❌", "NOT SERIALIZABLE".red().bold(), "not_serializable"),
             crate::ns_decision::NSDecision::Timeout { .. } => ("⏱️", "TIMEOUT".yellow().bold(), "timeout"),
+            crate::ns_decision::NSDecision::Unknown { .. } => ("❓", "UNKNOWN".yellow().bold(), "unknown"),
         };
         
         println!();
@@ -694,22 +1800,197 @@ where
             "════════════════════════════════════════════════════════════".bright_black()
         );
         
+        if crate::smpt_fallback::fallback_used() {
+            println!(
+                "{} SMPT was not available: disjuncts were decided by structural checks and a \
+                 bounded explicit search. Counterexamples and proofs from these checks are exact, \
+                 but an inconclusive result only means no counterexample was found within {} markings.",
+                "⚠".yellow(),
+                crate::smpt_fallback::MAX_EXPLORED_MARKINGS
+            );
+        }
+
         // Record result in stats
         crate::stats::set_analysis_result(stats_result);
         
         result
     }
 
+    /// The reachability problem that decides serializability: the Petri net with
+    /// requests (response places on the right), the places that must be empty in a
+    /// final marking, and the serialized automaton as a semilinear set of responses
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn serializability_problem(
+        &self,
+    ) -> (
+        Petri<PetriPlace<L, G, Req, Resp>>,
+        Vec<ReqPetriState<L, G, Req, Resp>>,
+        SemilinearSet<ReqPetriState<L, G, Req, Resp>>,
+    ) {
+        let (petri, places_that_must_be_zero) = self.serializability_petri();
+        (petri, places_that_must_be_zero, self.serializability_semilinear())
+    }
+
+    /// The Petri net with requests of the serializability problem, and the places that
+    /// must be empty in the final marking
+    fn serializability_petri(
+        &self,
+    ) -> (
+        Petri<PetriPlace<L, G, Req, Resp>>,
+        Vec<ReqPetriState<L, G, Req, Resp>>,
+    ) {
+        use crate::ns_to_petri::*;
+        use ReqPetriState::*;
+
+        // Convert to Petri net
+        let mut places_that_must_be_zero = HashSet::default();
+        let petri = crate::stats::time_stage("petri_net", || {
+            ns_to_petri_with_requests(self).rename(|st| match st {
+                Response(_, _) => Right(st),
+                Global(_) => Left(st),
+                Local(_, _) | Request(_) => {
+                    places_that_must_be_zero.insert(st.clone());
+                    Left(st)
+                }
+            })
+        });
+        let places_that_must_be_zero: Vec<_> = places_that_must_be_zero.into_iter().collect();
+        (petri, places_that_must_be_zero)
+    }
+
+    /// The completed multisets of serial executions, over the response places
+    fn serializability_semilinear(&self) -> SemilinearSet<ReqPetriState<L, G, Req, Resp>> {
+        crate::stats::time_stage("serialized_automaton", || {
+            self.serialized_automaton_kleene(|req, resp| {
+                SemilinearSet::singleton(SparseVector::unit(ReqPetriState::Response(req, resp)))
+            })
+        })
+    }
+
+    /// Print the size of the serializability problem and of the SMPT queries it
+    /// generates, without running them (`--dry-run`)
+    #[cfg(feature = "isl")]
+    pub fn print_dry_run_estimate(&self) {
+        let (petri, places_that_must_be_zero, ser) = self.serializability_problem();
+        let estimate = crate::reachability_with_proofs::estimate_queries(
+            &petri,
+            &places_that_must_be_zero,
+            ser.clone(),
+        );
+
+        println!("{}", "Dry run: problem size estimate".bold());
+        println!("  Petri net places:          {}", petri.get_places().len());
+        println!("  Petri net transitions:     {}", petri.get_transitions().len());
+        println!("  Semilinear components:     {}", ser.components.len());
+        println!(
+            "  Max periods per component: {}",
+            ser.components.iter().map(|c| c.periods.len()).max().unwrap_or(0)
+        );
+        println!("  Complement disjuncts:      {}", estimate.num_disjuncts);
+        println!(
+            "  Largest constraint system: {} constraints, {} existential variables",
+            estimate.largest_constraint_system, estimate.max_existentials
+        );
+        println!(
+            "  Predicted SMPT calls:      up to {} (timeout {}s each)",
+            estimate.predicted_smpt_calls,
+            crate::smpt::get_smpt_timeout()
+        );
+    }
+
     /// Create a serializability certificate (NSDecision) without full visualization
+    #[cfg(feature = "isl")]
     pub fn create_certificate(&self, out_dir: &str) -> crate::ns_decision::NSDecision<G, L, Req, Resp>
     where
-        G: Clone + Ord + Hash + Display + std::fmt::Debug,
-        L: Clone + Ord + Hash + Display + std::fmt::Debug,
-        Req: Clone + Ord + Hash + Display + std::fmt::Debug,
-        Resp: Clone + Ord + Hash + Display + std::fmt::Debug,
+        G: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        L: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
     {
-        use crate::ns_to_petri::*;
-        use ReqPetriState::*;
+        self.create_certificate_with_petri(out_dir, None)
+    }
+
+    /// Create a certificate for each of the given initial global states, the i-th in
+    /// `out_dir(i, initial_global)`. The Petri net with requests is built once: only its
+    /// initial marking depends on the initial global state.
+    #[cfg(feature = "isl")]
+    pub fn create_certificates_for_initial_globals(
+        &self,
+        initial_globals: &[G],
+        out_dir: impl Fn(usize, &G) -> String,
+    ) -> Vec<crate::ns_decision::NSDecision<G, L, Req, Resp>>
+    where
+        G: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        L: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    {
+        let (petri, places_that_must_be_zero) = self.serializability_petri();
+        initial_globals
+            .iter()
+            .enumerate()
+            .map(|(i, initial_global)| {
+                let mut ns = self.clone();
+                ns.set_initial_global(initial_global.clone());
+                let initial_marking = petri
+                    .get_initial_marking()
+                    .into_iter()
+                    .map(|place| match place {
+                        Left(ReqPetriState::Global(_)) => {
+                            Left(ReqPetriState::Global(initial_global.clone()))
+                        }
+                        place => place,
+                    })
+                    .collect();
+                ns.create_certificate_with_petri(
+                    &out_dir(i, initial_global),
+                    Some((
+                        petri.with_initial_marking(initial_marking),
+                        places_that_must_be_zero.clone(),
+                    )),
+                )
+            })
+            .collect()
+    }
+
+    /// `create_certificate`, with the Petri net of `serializability_petri` if it is
+    /// already known
+    #[cfg(feature = "isl")]
+    fn create_certificate_with_petri(
+        &self,
+        out_dir: &str,
+        petri: Option<(
+            Petri<PetriPlace<L, G, Req, Resp>>,
+            Vec<ReqPetriState<L, G, Req, Resp>>,
+        )>,
+    ) -> crate::ns_decision::NSDecision<G, L, Req, Resp>
+    where
+        G: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        L: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Req: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+        Resp: Clone + Ord + Hash + Display + std::fmt::Debug + serde::Serialize + for<'de> serde::Deserialize<'de>,
+    {
+        // Requests that never change the global state and do not depend on it are
+        // left out, and added back to the certificate (see `oblivious_requests`)
+        if read_only_fast_path_enabled() {
+            let read_only = self.read_only_requests();
+            let oblivious = self.oblivious_requests();
+            if !read_only.is_empty() {
+                let names: Vec<String> = read_only.iter().map(|req| req.to_string()).collect();
+                println!("{} {}", "Read-only requests:".cyan(), names.join(", "));
+            }
+            if !oblivious.is_empty() && oblivious.len() < self.get_requests().len() {
+                let names: Vec<String> = oblivious.iter().map(|req| req.to_string()).collect();
+                println!(
+                    "{} {}",
+                    "Leaving out requests that do not depend on the global state:".cyan(),
+                    names.join(", ")
+                );
+                let reduced = self.restrict_requests(|req| !oblivious.contains(req));
+                let decision = reduced.create_certificate(out_dir);
+                return self.add_oblivious_requests(decision, &oblivious);
+            }
+        }
 
         // Initialize debug logger
         let program_name = std::path::Path::new(out_dir)
@@ -722,28 +2003,45 @@ where
             program_name.clone(),
             format!("Network System: {:?}", self),
         );
+        crate::naming::reset_name_map();
+        crate::reachability::get_debug_logger().request_docs(
+            self.request_docs
+                .iter()
+                .map(|(req, doc)| (req.to_string(), doc.clone()))
+                .collect(),
+        );
 
-        // Convert to Petri net
-        let mut places_that_must_be_zero = HashSet::default();
-        let petri = ns_to_petri_with_requests(self).rename(|st| match st {
-            Response(_, _) => Right(st),
-            Global(_) => Left(st),
-            Local(_, _) | Request(_) => {
-                places_that_must_be_zero.insert(st.clone());
-                Left(st)
+        // Cheap interval analysis first; it may already give a certificate
+        if crate::absint::absint_enabled() {
+            if let Some(invariant) = crate::absint::try_prove_serializable(self) {
+                println!("{}", "Serializability proved by abstract interpretation".green());
+                return crate::ns_decision::NSDecision::Serializable { invariant };
             }
-        });
-        let places_that_must_be_zero: Vec<_> = places_that_must_be_zero.into_iter().collect();
+        }
+
+        let (petri, places_that_must_be_zero) =
+            petri.unwrap_or_else(|| self.serializability_petri());
+        crate::dim_order::record_petri_net(&petri);
+        if crate::observer::is_observed() {
+            let built = crate::observer::PetriBuilt {
+                places: petri.get_places().len(),
+                transitions: petri.get_transitions().len(),
+                net: crate::smpt::petri_to_pnet(&petri, &program_name),
+            };
+            crate::observer::notify(|observer| observer.on_petri_built(&built));
+        }
+        let ser = self.serializability_semilinear();
 
-        // Create serialized automaton semilinear set
-        let ser: SemilinearSet<_> = self.serialized_automaton_kleene(|req, resp| {
-            SemilinearSet::singleton(SparseVector::unit(Response(req, resp)))
-        });
-        
         // Collect Petri net size stats
         let places_count = petri.get_places().len();
         let transitions_count = petri.get_transitions().len();
         crate::stats::set_petri_net_sizes(places_count, transitions_count);
+        {
+            use crate::size_logger::{SizeMetric, check_size_thresholds};
+            check_size_thresholds(SizeMetric::PetriPlaces, places_count);
+            check_size_thresholds(SizeMetric::PetriTransitions, transitions_count);
+            check_size_thresholds(SizeMetric::SemilinearComponents, ser.components.len());
+        }
         
         // Collect semilinear set stats
         let semilinear_stats = crate::stats::SemilinearSetStats {
@@ -754,19 +2052,150 @@ where
         };
         crate::stats::set_semilinear_stats(semilinear_stats);
 
+        // A shortest counterexample within the bound, before anything else finds a longer one
+        let search = crate::bounded_search::bounded_search_depth().map(|depth| {
+            crate::bounded_search::shortest_counterexample(
+                &petri,
+                &places_that_must_be_zero,
+                &ser,
+                depth,
+                crate::bounded_search::bounded_search_states(),
+            )
+        });
+        if let Some(crate::bounded_search::SearchOutcome::CounterExample(trace)) = search {
+            return crate::ns_decision::petri_decision_to_ns(
+                crate::reachability_with_proofs::Decision::CounterExample { trace },
+                self,
+            );
+        }
+
+        // Random simulations may find a counterexample without SMPT
+        if simulation_runs() > 0 {
+            if let Some(trace) = simulate_serializability(
+                &petri,
+                &places_that_must_be_zero,
+                &ser,
+                simulation_runs(),
+            ) {
+                return crate::ns_decision::petri_decision_to_ns(
+                    crate::reachability_with_proofs::Decision::CounterExample { trace },
+                    self,
+                );
+            }
+        }
+
+        if guided_simulation_runs() > 0 {
+            if let Some((_, trace)) = simulate_guided_serializability(
+                &petri,
+                &places_that_must_be_zero,
+                &ser,
+                guided_simulation_runs(),
+            ) {
+                return crate::ns_decision::petri_decision_to_ns(
+                    crate::reachability_with_proofs::Decision::CounterExample { trace },
+                    self,
+                );
+            }
+        }
+
         // Run the proof-based analysis to get Decision
-        let result_with_proofs =
+        let result_with_proofs = crate::stats::time_stage("reachability", || {
             crate::reachability_with_proofs::is_petri_reachability_set_subset_of_semilinear_new(
                 petri.clone(),
                 &places_that_must_be_zero,
                 ser.clone(),
                 out_dir,
-            );
+            )
+        });
 
         // Convert Petri decision to NS decision
         crate::ns_decision::petri_decision_to_ns(result_with_proofs, self)
     }
 
+    /// Turn a decision for the system without the given oblivious requests into one
+    /// for this system. Traces stay valid; invariants get the states of the left-out
+    /// requests as unconstrained variables, which keeps them inductive since those
+    /// requests never change the global state.
+    fn add_oblivious_requests(
+        &self,
+        decision: crate::ns_decision::NSDecision<G, L, Req, Resp>,
+        oblivious: &[Req],
+    ) -> crate::ns_decision::NSDecision<G, L, Req, Resp>
+    where
+        G: Clone + Ord + Hash + Display + std::fmt::Debug,
+        L: Clone + Ord + Hash + Display + std::fmt::Debug,
+        Req: Clone + Ord + Hash + Display + std::fmt::Debug,
+        Resp: Clone + Ord + Hash + Display + std::fmt::Debug,
+    {
+        use crate::ns_decision::{NSDecision, RequestState, RequestStatePair};
+        let NSDecision::Serializable { mut invariant } = decision else {
+            return decision;
+        };
+
+        let mut extra = Vec::new();
+        for req in oblivious {
+            let locals = self.request_locals(req);
+            let mut sorted_locals: Vec<&L> = locals.iter().copied().collect();
+            sorted_locals.sort();
+            for local in sorted_locals {
+                extra.push(RequestStatePair(req.clone(), RequestState::InFlight(local.clone())));
+            }
+            let mut responses: Vec<&Resp> = self
+                .responses
+                .iter()
+                .filter(|(local, _)| locals.contains(local))
+                .map(|(_, resp)| resp)
+                .collect();
+            responses.sort();
+            responses.dedup();
+            for resp in responses {
+                extra.push(RequestStatePair(req.clone(), RequestState::Completed(resp.clone())));
+            }
+        }
+        for global_invariant in invariant.global_invariants.values_mut() {
+            for pair in &extra {
+                if !global_invariant.variables.contains(pair) {
+                    global_invariant.variables.push(pair.clone());
+                }
+            }
+        }
+        NSDecision::Serializable { invariant }
+    }
+
+    /// Check that a trace is a genuine counterexample to serializability.
+    /// In addition to replaying the trace with `check_trace`, this recomputes the
+    /// serialized automaton as a semilinear set and verifies that the multiset of
+    /// completed (request, response) pairs is not a member of it.
+    /// Returns Ok(multiset of (request, response) pairs) if the trace is a counterexample.
+    pub fn check_counterexample_trace(
+        &self,
+        trace: &crate::ns_decision::NSTrace<G, L, Req, Resp>,
+    ) -> Result<Vec<(Req, Resp)>, String> {
+        let completed = self.check_trace(trace).map_err(|err| err.to_string())?;
+
+        let mut multiset = SparseVector::new();
+        for pair in &completed {
+            let count = multiset.get(pair);
+            multiset.set(pair.clone(), count + 1);
+        }
+
+        let ser: SemilinearSet<(Req, Resp)> = self.serialized_automaton_kleene(|req, resp| {
+            SemilinearSet::atom((req, resp))
+        });
+        if ser.contains(&multiset) {
+            let pairs: Vec<String> = completed
+                .iter()
+                .map(|(req, resp)| format!("{}/{}", req, resp))
+                .collect();
+            return Err(format!(
+                "Completed requests [{}] are in the serializable set, so the trace is not a counterexample",
+                pairs.join(", ")
+            ));
+        }
+
+        Ok(completed)
+    }
+
     /// Verify an NSDecision against this Network System
     /// Returns true if the system is serializable based on the decision
     pub fn verify_ns_decision(&self, decision: &crate::ns_decision::NSDecision<G, L, Req, Resp>) -> bool
@@ -784,7 +2213,7 @@ where
             crate::ns_decision::NSDecision::NotSerializable { trace } => {
                 // If we have a valid counterexample trace, the system is NOT serializable
                 // So we return false (not serializable)
-                if self.check_trace(trace).is_ok() {
+                if self.check_counterexample_trace(trace).is_ok() {
                     false // Valid counterexample means not serializable
                 } else {
                     // Invalid trace - this shouldn't happen, but we can't conclude serializability
@@ -797,6 +2226,11 @@ where
                 eprintln!("Warning: Analysis timed out - cannot determine serializability");
                 false
             }
+            crate::ns_decision::NSDecision::Unknown { reason, .. } => {
+                // Inconclusive analysis means we cannot determine serializability
+                eprintln!("Warning: Analysis inconclusive ({}) - cannot determine serializability", reason);
+                false
+            }
         }
     }
 }
@@ -1023,6 +2457,93 @@ mod tests {
         assert_eq!(ns.transitions.len(), 2);
     }
 
+    #[test]
+    fn test_ns_from_yaml_and_toml() {
+        let yaml = r#"
+initial_global: G0
+requests: [[Req1, L0], [Req2, L1]]
+responses: [[L0, RespA], [L1, RespB]]
+transitions:
+  - [L0, G0, L1, G1]
+  - [L1, G1, L0, G0]
+"#;
+        let toml = r#"
+initial_global = "G0"
+requests = [["Req1", "L0"], ["Req2", "L1"]]
+responses = [["L0", "RespA"], ["L1", "RespB"]]
+transitions = [["L0", "G0", "L1", "G1"], ["L1", "G1", "L0", "G0"]]
+
+[[multiplicity_bounds]]
+request = "Req1"
+max = 1
+"#;
+        let from_yaml =
+            NS::<String, String, String, String>::from_str_in_format(yaml, NSFormat::Yaml)
+                .unwrap();
+        let from_toml =
+            NS::<String, String, String, String>::from_str_in_format(toml, NSFormat::Toml)
+                .unwrap();
+
+        assert_eq!(from_yaml.requests, from_toml.requests);
+        assert_eq!(from_yaml.transitions, from_toml.transitions);
+        assert_eq!(from_toml.multiplicity_bounds.len(), 1);
+        assert_eq!(from_toml.multiplicity_bounds[0].response, None);
+        assert_eq!(NSFormat::from_extension("yml"), Some(NSFormat::Yaml));
+    }
+
+    fn counter_ns() -> NS<String, String, String, String> {
+        let s = |x: &str| x.to_string();
+        let mut ns = NS::new(s("0"));
+        ns.add_request(s("inc"), s("start"));
+        ns.add_request(s("read"), s("reading"));
+        ns.add_transition(s("start"), s("0"), s("done"), s("1"));
+        ns.add_transition(s("start"), s("1"), s("done"), s("0"));
+        ns.add_transition(s("reading"), s("0"), s("zero"), s("0"));
+        ns.add_response(s("done"), s("ok"));
+        ns.add_response(s("zero"), s("0"));
+        ns.add_multiplicity_bound(s("inc"), Some(s("ok")), 2);
+        ns
+    }
+
+    #[test]
+    fn test_restrict_rename_relabel() {
+        let ns = counter_ns();
+
+        let only_inc = ns.restrict_requests(|req| req == "inc");
+        assert_eq!(only_inc.requests, vec![("inc".to_string(), "start".to_string())]);
+        assert_eq!(only_inc.multiplicity_bounds.len(), 1);
+
+        // Renaming both requests to the same name keeps both entry points
+        let renamed = ns.rename_requests(|_| "op".to_string());
+        assert_eq!(renamed.get_requests().len(), 1);
+        assert_eq!(renamed.requests.len(), 2);
+        assert_eq!(renamed.multiplicity_bounds[0].request, "op");
+
+        let relabeled = ns.relabel_responses(|resp| resp.len());
+        assert_eq!(relabeled.responses, vec![("done".to_string(), 2), ("zero".to_string(), 1)]);
+        assert_eq!(relabeled.multiplicity_bounds[0].response, Some(2));
+    }
+
+    #[test]
+    fn test_product_with_automaton() {
+        // The automaton counts increments and blocks the third one
+        let ns = counter_ns();
+        let product = ns.product(0usize, |count, (from_local, ..)| {
+            if from_local == "start" {
+                (*count < 2).then_some(count + 1)
+            } else {
+                Some(*count)
+            }
+        });
+
+        assert_eq!(product.initial_global, ProductState("0".to_string(), 0));
+        // Reachable pairs: (0,0) -> (1,1) -> (0,2), each with a read self-loop on global 0
+        assert_eq!(product.get_global_states().len(), 3);
+        assert_eq!(product.transitions.len(), 4);
+        assert_eq!(product.requests, ns.requests);
+        assert_eq!(format!("{}", product.initial_global), "(0, 0)");
+    }
+
     #[test]
     fn test_ns_build_and_serialize() {
         let mut ns = NS::<String, String, String, String>::new("EmptySession".to_string());
@@ -1064,7 +2585,7 @@ mod tests {
 
     #[test]
     fn test_check_trace() {
-        use crate::ns_decision::{NSStep, NSTrace};
+        use crate::ns_decision::{NSStep, NSTrace, TraceErrorKind};
 
         // Create a simple NS
         let mut ns = NS::<String, String, String, String>::new("G0".to_string());
@@ -1155,8 +2676,10 @@ mod tests {
         };
 
         let result2 = ns.check_trace(&trace2);
-        assert!(result2.is_err());
-        assert!(result2.unwrap_err().contains("Requests still in flight"));
+        let error2 = result2.unwrap_err();
+        assert_eq!(error2.kind, TraceErrorKind::RequestsInFlight);
+        assert_eq!(error2.step_index, None);
+        assert!(error2.to_string().contains("Requests still in flight"));
 
         // Test 3: Invalid trace - wrong global state
         let trace3 = NSTrace {
@@ -1176,8 +2699,15 @@ mod tests {
         };
 
         let result3 = ns.check_trace(&trace3);
-        assert!(result3.is_err());
-        assert!(result3.unwrap_err().contains("Global state mismatch"));
+        let error3 = result3.unwrap_err();
+        assert_eq!(error3.kind, TraceErrorKind::GlobalStateMismatch);
+        assert_eq!(error3.step_index, Some(1));
+        assert!(error3.to_string().contains("Global state mismatch"));
+        // Req1 can only take its step from G0
+        assert_eq!(
+            error3.suggestion.as_deref(),
+            Some("Req1 can step L0 -> L2 (global G1)")
+        );
 
         // Test 4: Invalid trace - unknown request
         let trace4 = NSTrace {
@@ -1188,8 +2718,98 @@ mod tests {
         };
 
         let result4 = ns.check_trace(&trace4);
-        assert!(result4.is_err());
-        assert!(result4.unwrap_err().contains("Unknown request type"));
+        let error4 = result4.unwrap_err();
+        assert_eq!(error4.kind, TraceErrorKind::UnknownRequest);
+        assert_eq!(error4.step_index, Some(0));
+        assert!(error4.to_string().contains("Unknown request type"));
+    }
+
+    #[test]
+    fn test_check_counterexample_trace() {
+        use crate::ns_decision::{NSStep, NSTrace};
+
+        // Two requests that each read the global and then write the other value.
+        // Running both reads before either write yields a non-serializable outcome.
+        let mut ns = NS::<String, String, String, String>::new("G0".to_string());
+        ns.add_request("Req".to_string(), "Start".to_string());
+        ns.add_transition(
+            "Start".to_string(),
+            "G0".to_string(),
+            "Saw0".to_string(),
+            "G0".to_string(),
+        );
+        ns.add_transition(
+            "Saw0".to_string(),
+            "G0".to_string(),
+            "Done0".to_string(),
+            "G1".to_string(),
+        );
+        ns.add_transition(
+            "Saw0".to_string(),
+            "G1".to_string(),
+            "Done0".to_string(),
+            "G1".to_string(),
+        );
+        ns.add_transition(
+            "Start".to_string(),
+            "G1".to_string(),
+            "Done1".to_string(),
+            "G1".to_string(),
+        );
+        ns.add_response("Done0".to_string(), "0".to_string());
+        ns.add_response("Done1".to_string(), "1".to_string());
+
+        let start = || NSStep::RequestStart {
+            request: "Req".to_string(),
+            initial_local: "Start".to_string(),
+        };
+        let step = |from_local: &str, from_global: &str, to_local: &str, to_global: &str| {
+            NSStep::InternalStep {
+                request: "Req".to_string(),
+                from_local: from_local.to_string(),
+                from_global: from_global.to_string(),
+                to_local: to_local.to_string(),
+                to_global: to_global.to_string(),
+            }
+        };
+        let complete = |final_local: &str, response: &str| NSStep::RequestComplete {
+            request: "Req".to_string(),
+            final_local: final_local.to_string(),
+            response: response.to_string(),
+        };
+
+        // Interleaved: both requests observe G0, so both respond 0
+        let interleaved = NSTrace {
+            steps: vec![
+                start(),
+                start(),
+                step("Start", "G0", "Saw0", "G0"),
+                step("Start", "G0", "Saw0", "G0"),
+                step("Saw0", "G0", "Done0", "G1"),
+                step("Saw0", "G1", "Done0", "G1"),
+                complete("Done0", "0"),
+                complete("Done0", "0"),
+            ],
+        };
+        let completed = ns.check_counterexample_trace(&interleaved).unwrap();
+        assert_eq!(completed.len(), 2);
+
+        // Serial: the second request observes G1 and responds 1
+        let serial = NSTrace {
+            steps: vec![
+                start(),
+                step("Start", "G0", "Saw0", "G0"),
+                step("Saw0", "G0", "Done0", "G1"),
+                complete("Done0", "0"),
+                start(),
+                step("Start", "G1", "Done1", "G1"),
+                complete("Done1", "1"),
+            ],
+        };
+        assert!(ns.check_trace(&serial).is_ok());
+        let result = ns.check_counterexample_trace(&serial);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("in the serializable set"));
     }
 
     #[test]
@@ -1279,6 +2899,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialized_automaton_multiplicity_bound() {
+        let mut ns = NS::<String, String, String, String>::new("G0".to_string());
+        ns.add_request("init".to_string(), "L0".to_string());
+        ns.add_request("get".to_string(), "L0".to_string());
+        ns.add_response("L0".to_string(), "ok".to_string());
+
+        let times = |init: usize, get: usize| {
+            let mut v = SparseVector::new();
+            v.set("init/ok".to_string(), init);
+            v.set("get/ok".to_string(), get);
+            v
+        };
+        assert!(ns.serialized_automaton_semilinear().contains(&times(2, 3)));
+
+        // At most one init, any number of gets
+        ns.add_multiplicity_bound("init".to_string(), None, 1);
+        let bounded = ns.serialized_automaton_semilinear();
+        assert!(bounded.contains(&times(0, 3)));
+        assert!(bounded.contains(&times(1, 3)));
+        assert!(!bounded.contains(&times(2, 3)));
+
+        // Bounds on a specific response do not count other responses
+        ns.add_multiplicity_bound("get".to_string(), Some("error".to_string()), 0);
+        assert!(ns.serialized_automaton_semilinear().contains(&times(1, 3)));
+    }
+
+    #[test]
+    fn test_serialized_automaton_idempotent() {
+        let s = |x: &str| x.to_string();
+        // The first `put` responds fresh, later ones respond again
+        let mut ns = NS::<String, String, String, String>::new(s("G0"));
+        ns.add_request(s("put"), s("L0"));
+        ns.add_transition(s("L0"), s("G0"), s("L1"), s("G1"));
+        ns.add_transition(s("L0"), s("G1"), s("L2"), s("G1"));
+        ns.add_response(s("L1"), s("fresh"));
+        ns.add_response(s("L2"), s("again"));
+
+        let times = |fresh: usize, again: usize| {
+            let mut v = SparseVector::new();
+            v.set(s("put/fresh"), fresh);
+            v.set(s("put/again"), again);
+            v
+        };
+        let ser = ns.serialized_automaton_semilinear();
+        assert!(ser.contains(&times(1, 2)));
+        assert!(!ser.contains(&times(2, 0)));
+
+        // Modulo duplication, any positive number of fresh responses matches one
+        ns.add_idempotent_request(s("put"));
+        let ser = ns.serialized_automaton_semilinear();
+        assert!(ser.contains(&times(0, 0)));
+        assert!(ser.contains(&times(2, 0)));
+        assert!(ser.contains(&times(3, 5)));
+        assert!(!ser.contains(&times(0, 1)));
+
+        // `--duplicates set` makes every request idempotent
+        ns.idempotent_requests.clear();
+        let options = crate::options::AnalysisOptions {
+            duplicate_responses: DuplicateResponses::Set,
+            ..crate::options::AnalysisOptions::default()
+        };
+        let ser = crate::options::with_options(options, || ns.serialized_automaton_semilinear());
+        assert!(ser.contains(&times(2, 0)));
+        assert!(!ser.contains(&times(0, 1)));
+    }
+
     #[test]
     fn test_serialized_automaton_chain_of_transitions() {
         let mut ns = NS::<String, String, String, String>::new("G0".to_string());
@@ -1385,6 +3072,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_initial_requests() {
+        use crate::ns_decision::{NSStep, NSTrace};
+
+        // A job already holds the lock in the initial state
+        let mut ns = NS::<String, String, String, String>::new("Held".to_string());
+        ns.add_request("Job".to_string(), "Start".to_string());
+        ns.add_transition("Start".to_string(), "Free".to_string(), "Critical".to_string(), "Held".to_string());
+        ns.add_transition("Critical".to_string(), "Held".to_string(), "Done".to_string(), "Free".to_string());
+        ns.add_response("Done".to_string(), "Ok".to_string());
+        ns.add_initial_request("Job".to_string(), "Critical".to_string());
+
+        // The initial job has to complete, so serial executions complete at least one job
+        let ser = ns.serialized_automaton_semilinear();
+        let jobs = |n: usize| {
+            let mut v = SparseVector::new();
+            v.set("Job/Ok".to_string(), n);
+            v
+        };
+        assert!(!ser.contains(&jobs(0)));
+        assert!(ser.contains(&jobs(1)));
+        assert!(ser.contains(&jobs(3)));
+
+        // Traces start with the initial job in flight
+        let trace = NSTrace {
+            steps: vec![
+                NSStep::InternalStep {
+                    request: "Job".to_string(),
+                    from_local: "Critical".to_string(),
+                    from_global: "Held".to_string(),
+                    to_local: "Done".to_string(),
+                    to_global: "Free".to_string(),
+                },
+                NSStep::RequestComplete {
+                    request: "Job".to_string(),
+                    final_local: "Done".to_string(),
+                    response: "Ok".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            ns.check_trace(&trace).unwrap(),
+            vec![("Job".to_string(), "Ok".to_string())]
+        );
+        assert!(ns.check_trace(&NSTrace { steps: vec![] }).is_err());
+    }
+
     #[test]
     fn test_serialized_automaton_cycle() {
         let mut ns = NS::<String, String, String, String>::new("G0".to_string());
@@ -1466,6 +3200,62 @@ mod tests {
         assert!(dot.contains("Login / Success"));
     }
 
+    #[test]
+    fn test_serialized_automaton_graphviz() {
+        let mut ns = NS::<String, String, String, String>::new("G0".to_string());
+        ns.add_request("A".to_string(), "L0".to_string());
+        ns.add_request("B".to_string(), "L0".to_string());
+        ns.add_response("L1".to_string(), "Done".to_string());
+        ns.add_transition(
+            "L0".to_string(),
+            "G0".to_string(),
+            "L1".to_string(),
+            "G1".to_string(),
+        );
+
+        let dot = ns.serialized_automaton_to_graphviz();
+        assert!(dot.starts_with("digraph SerializedAutomaton {"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("G_G0 [label=\"G0 (initial)\""));
+        // Both requests lead from G0 to G1, so they share a single edge
+        assert_eq!(dot.matches("G_G0 -> G_G1").count(), 1);
+        assert!(dot.contains("A / Done\\nB / Done"));
+    }
+
+    #[test]
+    fn test_read_only_and_oblivious_requests() {
+        let mut ns = NS::<String, String, String, String>::new("0".to_string());
+
+        // inc changes the global state
+        ns.add_request("inc".to_string(), "i".to_string());
+        ns.add_transition("i".to_string(), "0".to_string(), "i_done".to_string(), "1".to_string());
+        ns.add_transition("i".to_string(), "1".to_string(), "i_done".to_string(), "1".to_string());
+        ns.add_response("i_done".to_string(), "ok".to_string());
+
+        // get reads the global state, so it is read-only but not oblivious
+        ns.add_request("get".to_string(), "g".to_string());
+        ns.add_transition("g".to_string(), "0".to_string(), "g0".to_string(), "0".to_string());
+        ns.add_transition("g".to_string(), "1".to_string(), "g1".to_string(), "1".to_string());
+        ns.add_response("g0".to_string(), "0".to_string());
+        ns.add_response("g1".to_string(), "1".to_string());
+
+        // ping does the same in every global state
+        ns.add_request("ping".to_string(), "p".to_string());
+        ns.add_transition("p".to_string(), "0".to_string(), "p_done".to_string(), "0".to_string());
+        ns.add_transition("p".to_string(), "1".to_string(), "p_done".to_string(), "1".to_string());
+        ns.add_response("p_done".to_string(), "pong".to_string());
+
+        assert_eq!(
+            ns.read_only_requests(),
+            vec!["get".to_string(), "ping".to_string()]
+        );
+        assert_eq!(ns.oblivious_requests(), vec!["ping".to_string()]);
+
+        // A multiplicity bound makes the number of instances matter
+        ns.add_multiplicity_bound("ping".to_string(), None, 1);
+        assert!(ns.oblivious_requests().is_empty());
+    }
+
     // #[test]
     // fn test_save_graphviz() {
     //     // This test is conditional on GraphViz being installed
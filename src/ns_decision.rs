@@ -1,14 +1,20 @@
+use crate::constraints::Variable;
 use crate::deterministic_map::{HashMap, HashSet};
 use crate::ns::NS;
 use crate::ns_to_petri::ReqPetriState;
-use crate::proof_parser::{Formula, ProofInvariant};
+use crate::proof_parser::{CompOp, Constraint, Formula, ProofInvariant};
+#[cfg(feature = "isl")]
+use crate::presburger::{AffineMap, PresburgerSet};
+#[cfg(feature = "isl")]
 use crate::proofinvariant_to_presburger::formula_to_presburger;
-use crate::reachability_with_proofs::Decision;
+use crate::reachability_with_proofs::{Decision, DisjunctResult, UnknownReason};
 use either::Either;
 use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
 use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
 
@@ -59,8 +65,9 @@ impl<L: Display, Resp: Display> Display for RequestState<L, Resp> {
     }
 }
 
-/// Wrapper struct for (Req, RequestState<L, Resp>) to implement Display
-#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+/// Wrapper struct for (Req, RequestState<L, Resp>) to implement Display.
+/// Serialized as a stable place identifier, see `place_id`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RequestStatePair<Req, L, Resp>(pub Req, pub RequestState<L, Resp>);
 
 impl<Req: Display, L: Display, Resp: Display> Display for RequestStatePair<Req, L, Resp> {
@@ -83,7 +90,7 @@ impl<Req: Display, Resp: Display> Display for CompletedRequestPair<Req, Resp> {
 }
 
 /// NS-level step in a trace
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum NSStep<G, L, Req, Resp> {
     /// A new request is created
     RequestStart { request: Req, initial_local: L },
@@ -110,6 +117,150 @@ pub struct NSTrace<G, L, Req, Resp> {
     pub steps: Vec<NSStep<G, L, Req, Resp>>,
 }
 
+/// How counterexample traces are shown (`--trace-detail`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceDetail {
+    /// The transitions of the Petri net, as SMPT found them
+    Petri,
+    /// The steps of the network system, with their local and global states
+    Ns,
+    /// The statement of the source each step starts at
+    Source,
+}
+
+impl TraceDetail {
+    /// Parse a level name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "petri" => Some(TraceDetail::Petri),
+            "ns" => Some(TraceDetail::Ns),
+            "source" => Some(TraceDetail::Source),
+            _ => None,
+        }
+    }
+}
+
+/// Set how counterexample traces are shown (`--trace-detail petri|ns|source`)
+pub fn set_trace_detail(detail: TraceDetail) {
+    crate::options::update(|options| options.trace_detail = detail);
+}
+
+pub fn trace_detail() -> TraceDetail {
+    crate::options::get(|options| options.trace_detail)
+}
+
+impl<G, L, Req, Resp> NSStep<G, L, Req, Resp>
+where
+    G: Clone,
+    L: Clone,
+    Req: Clone,
+    Resp: Clone,
+{
+    /// The transition of the Petri net that this step fires, as its input and output
+    /// places (the inverse of `convert_petri_trace_to_ns`)
+    #[allow(clippy::type_complexity)]
+    pub fn petri_transition(
+        &self,
+    ) -> (
+        Vec<ReqPetriState<L, G, Req, Resp>>,
+        Vec<ReqPetriState<L, G, Req, Resp>>,
+    ) {
+        match self {
+            NSStep::RequestStart {
+                request,
+                initial_local,
+            } => (
+                vec![],
+                vec![ReqPetriState::Local(request.clone(), initial_local.clone())],
+            ),
+            NSStep::InternalStep {
+                request,
+                from_local,
+                from_global,
+                to_local,
+                to_global,
+            } => (
+                vec![
+                    ReqPetriState::Local(request.clone(), from_local.clone()),
+                    ReqPetriState::Global(from_global.clone()),
+                ],
+                vec![
+                    ReqPetriState::Local(request.clone(), to_local.clone()),
+                    ReqPetriState::Global(to_global.clone()),
+                ],
+            ),
+            NSStep::RequestComplete {
+                request,
+                final_local,
+                response,
+            } => (
+                vec![ReqPetriState::Local(request.clone(), final_local.clone())],
+                vec![ReqPetriState::Response(request.clone(), response.clone())],
+            ),
+        }
+    }
+}
+
+/// The check of `NS::check_trace` that a trace failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TraceErrorKind {
+    UnknownRequest,
+    GlobalStateMismatch,
+    UnknownTransition,
+    NoActiveRequest,
+    UnknownResponse,
+    RequestsInFlight,
+}
+
+impl Display for TraceErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            TraceErrorKind::UnknownRequest => "Unknown request type or wrong initial state",
+            TraceErrorKind::GlobalStateMismatch => "Global state mismatch",
+            TraceErrorKind::UnknownTransition => "Transition not found in NS",
+            TraceErrorKind::NoActiveRequest => "No active request found matching",
+            TraceErrorKind::UnknownResponse => "Response not found in NS",
+            TraceErrorKind::RequestsInFlight => "Requests still in flight at end of trace",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Why a trace cannot be executed, located at the step that fails, so that tools
+/// replaying or displaying the trace can point at it
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TraceError {
+    pub kind: TraceErrorKind,
+    /// Index of the failing step, or `None` if every step executed but the trace
+    /// ends with requests in flight
+    pub step_index: Option<usize>,
+    /// What the step needs to be enabled
+    pub expected_enabled: String,
+    /// The state before the step: the global state and the requests in flight
+    pub actual_state: String,
+    /// A way to fix the step, when one is apparent
+    pub suggestion: Option<String>,
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(index) = self.step_index {
+            write!(f, "Step {}: ", index)?;
+        }
+        write!(
+            f,
+            "{}: expected {}, but the state is {}",
+            self.kind, self.expected_enabled, self.actual_state
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TraceError {}
+
 impl<G, L, Req, Resp> NSTrace<G, L, Req, Resp>
 where
     G: Display + Clone + Eq + Hash,
@@ -117,6 +268,80 @@ where
     Req: Display + Clone + Eq + Hash,
     Resp: Display + Clone + Eq + Hash,
 {
+    /// Print the trace at the given level of detail
+    pub fn print(&self, ns: &NS<G, L, Req, Resp>, detail: TraceDetail) {
+        match detail {
+            TraceDetail::Petri => self.print_petri(),
+            TraceDetail::Ns => self.pretty_print(ns),
+            TraceDetail::Source => self.print_source(ns),
+        }
+    }
+
+    /// Print the trace as the transitions of the Petri net
+    pub fn print_petri(&self) {
+        println!("Petri-Level Counterexample Trace:");
+        println!("=================================");
+        if self.steps.is_empty() {
+            println!("(Empty trace - violation at initial marking)");
+            return;
+        }
+        let places = |places: Vec<ReqPetriState<L, G, Req, Resp>>| {
+            if places.is_empty() {
+                "∅".to_string()
+            } else {
+                places
+                    .iter()
+                    .map(|place| place.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        for (i, step) in self.steps.iter().enumerate() {
+            let (inputs, outputs) = step.petri_transition();
+            println!("  {}. {} → {}", i + 1, places(inputs), places(outputs));
+        }
+    }
+
+    /// Print the trace as the statements of the source that the steps start at, for
+    /// systems whose front end records them (see `NS::local_source`)
+    pub fn print_source(&self, ns: &NS<G, L, Req, Resp>) {
+        println!("Source-Level Counterexample Trace:");
+        println!("==================================");
+        if self.steps.is_empty() {
+            println!("(Empty trace - violation at initial state)");
+            return;
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let line = match step {
+                NSStep::RequestStart { request, .. } => format!("request {} starts", request),
+                NSStep::InternalStep {
+                    request,
+                    from_local,
+                    from_global,
+                    to_global,
+                    ..
+                } => {
+                    let location = match ns.local_source(from_local) {
+                        Some(span) => span.to_string(),
+                        None => format!("at {}", from_local),
+                    };
+                    if from_global == to_global {
+                        format!("request {}: {}", request, location)
+                    } else {
+                        format!(
+                            "request {}: {}  {} → {}",
+                            request, location, from_global, to_global
+                        )
+                    }
+                }
+                NSStep::RequestComplete {
+                    request, response, ..
+                } => format!("request {} responds {}", request, response),
+            };
+            println!("  {}. {}", i + 1, line);
+        }
+    }
+
     /// Pretty print the NS trace
     pub fn pretty_print(&self, ns: &NS<G, L, Req, Resp>) {
         println!("NS-Level Counterexample Trace:");
@@ -219,6 +444,12 @@ where
     NotSerializable { trace: NSTrace<G, L, Req, Resp> },
     /// Analysis timed out
     Timeout { message: String },
+    /// Analysis was inconclusive (solver unknown, approximation too coarse)
+    Unknown {
+        reason: UnknownReason,
+        /// Per-disjunct results that were obtained before giving up
+        partial_results: Vec<DisjunctResult>,
+    },
 }
 
 impl<G, L, Req, Resp> NSDecision<G, L, Req, Resp>
@@ -229,7 +460,9 @@ where
     Resp: Eq + Hash,
 {
     /// Save the NSDecision to a JSON file
-    /// This method properly serializes the decision using serde
+    /// The decision is wrapped in a versioned envelope `{"version": N, "decision": ...}`
+    /// and streamed to disk, compressed according to the file extension
+    /// (`.gz` or `.zst`). Fails if the file would exceed the configured maximum size.
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> 
     where
         G: serde::Serialize,
@@ -237,22 +470,71 @@ where
         Req: serde::Serialize,
         Resp: serde::Serialize,
     {
-        // Debug: Try to serialize with better error handling
-        match serde_json::to_string_pretty(&self) {
-            Ok(json) => {
-                fs::write(path, json)?;
-                Ok(())
+        let path = path.as_ref();
+        let envelope = CertificateEnvelope {
+            version: CERTIFICATE_FORMAT_VERSION,
+            decision: self,
+        };
+        let file = fs::File::create(path)?;
+        let writer = BufWriter::new(SizeLimited::new(file, get_max_certificate_size()));
+
+        let result = match CertificateCompression::from_path(path) {
+            CertificateCompression::None => {
+                let mut writer = writer;
+                serde_json::to_writer_pretty(&mut writer, &envelope)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| writer.flush())
             }
-            Err(e) => {
-                eprintln!("Serialization error details: {:?}", e);
-                eprintln!("Error type: {}", std::any::type_name_of_val(&e));
-                Err(std::io::Error::new(std::io::ErrorKind::Other, e))
+            CertificateCompression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+                serde_json::to_writer_pretty(&mut encoder, &envelope)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| encoder.finish())
+                    .and_then(|mut writer| writer.flush())
             }
+            CertificateCompression::Zstd => zstd::stream::write::Encoder::new(writer, 0)
+                .and_then(|mut encoder| {
+                    serde_json::to_writer_pretty(&mut encoder, &envelope)
+                        .map_err(std::io::Error::from)?;
+                    encoder.finish()
+                })
+                .and_then(|mut writer| writer.flush()),
+        };
+
+        if let Err(e) = result {
+            // Don't leave a truncated certificate behind
+            let _ = fs::remove_file(path);
+            return Err(e);
         }
+        let written = crate::observer::CertificateWritten {
+            path: path.to_string_lossy().into_owned(),
+            verdict: crate::initial_states::Verdict::of(self),
+        };
+        crate::observer::notify(|observer| observer.on_certificate(&written));
+        Ok(())
+    }
+
+    /// Parse an NSDecision from JSON text, migrating older certificate formats like
+    /// `load_from_file` does. For embedders that have no file system, like the
+    /// browser build of the certificate checker.
+    pub fn from_json_str(json: &str) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        for<'de> G: serde::Deserialize<'de>,
+        for<'de> L: serde::Deserialize<'de>,
+        for<'de> Req: serde::Deserialize<'de>,
+        for<'de> Resp: serde::Deserialize<'de>,
+    {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let decision_value = migrate_certificate(value)?;
+        let decision = serde_json::from_value(decision_value)?;
+        Ok(decision)
     }
 
     /// Load an NSDecision from a JSON file
-    /// This method properly deserializes the decision using serde
+    /// Older certificate formats are migrated to the current format; certificates
+    /// written by a newer version of the tool are rejected with an explicit error.
+    /// Compressed certificates (`.gz`, `.zst`) are decompressed while streaming.
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>>
     where
         for<'de> G: serde::Deserialize<'de>,
@@ -260,12 +542,463 @@ where
         for<'de> Req: serde::Deserialize<'de>,
         for<'de> Resp: serde::Deserialize<'de>,
     {
-        let json = fs::read_to_string(path)?;
-        let decision = serde_json::from_str(&json)?;
+        let path = path.as_ref();
+        let max_size = get_max_certificate_size();
+        let file = fs::File::open(path)?;
+        if let Some(limit) = max_size {
+            let size = file.metadata()?.len();
+            if size > limit {
+                return Err(format!(
+                    "Certificate {} is {} bytes, which exceeds the maximum certificate size of {} bytes",
+                    path.display(),
+                    size,
+                    limit
+                )
+                .into());
+            }
+        }
+
+        let reader = BufReader::new(file);
+        // The size limit also applies to the decompressed JSON
+        let CertificateFile(decision) = match CertificateCompression::from_path(path) {
+            CertificateCompression::None => serde_json::from_reader(reader)?,
+            CertificateCompression::Gzip => serde_json::from_reader(SizeLimited::new(
+                flate2::read::MultiGzDecoder::new(reader),
+                max_size,
+            ))?,
+            CertificateCompression::Zstd => serde_json::from_reader(SizeLimited::new(
+                zstd::stream::read::Decoder::with_buffer(reader)?,
+                max_size,
+            ))?,
+        };
         Ok(decision)
     }
 }
 
+/// On-disk wrapper around an `NSDecision`, serialized by reference so that
+/// saving streams directly to the output file.
+#[derive(serde::Serialize)]
+struct CertificateEnvelope<'a, D> {
+    version: u64,
+    decision: &'a D,
+}
+
+/// A certificate file read in one pass. The decision of a certificate in the current
+/// format is deserialized straight from the reader; only older formats are read into a
+/// `serde_json::Value` for `migrate_certificate`.
+struct CertificateFile<D>(D);
+
+impl<'de, D: serde::de::DeserializeOwned> Deserialize<'de> for CertificateFile<D> {
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        deserializer.deserialize_map(CertificateFileVisitor(std::marker::PhantomData))
+    }
+}
+
+struct CertificateFileVisitor<D>(std::marker::PhantomData<D>);
+
+impl<'de, D: serde::de::DeserializeOwned> serde::de::Visitor<'de> for CertificateFileVisitor<D> {
+    type Value = CertificateFile<D>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a certificate object")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        use serde::de::{Error, IgnoredAny};
+
+        // `save_to_file` writes the version before the decision
+        let mut version = None;
+        let mut entries = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let current = version.as_ref().and_then(serde_json::Value::as_u64)
+                == Some(CERTIFICATE_FORMAT_VERSION);
+            match key.as_str() {
+                "version" => version = Some(map.next_value()?),
+                "decision" if current => {
+                    let decision = map.next_value()?;
+                    while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                    return Ok(CertificateFile(decision));
+                }
+                _ => {
+                    entries.insert(key, map.next_value()?);
+                }
+            }
+        }
+        if let Some(version) = version {
+            entries.insert("version".to_string(), version);
+        }
+        let decision =
+            migrate_certificate(serde_json::Value::Object(entries)).map_err(A::Error::custom)?;
+        serde_json::from_value(decision)
+            .map(CertificateFile)
+            .map_err(A::Error::custom)
+    }
+}
+
+/// Compression applied to a certificate file, determined by its extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CertificateCompression {
+    /// Plain JSON (`certificate.json`)
+    None,
+    /// Gzip-compressed JSON (`certificate.json.gz`)
+    Gzip,
+    /// Zstandard-compressed JSON (`certificate.json.zst`)
+    Zstd,
+}
+
+impl CertificateCompression {
+    /// Parse a compression name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(CertificateCompression::None),
+            "gzip" | "gz" => Some(CertificateCompression::Gzip),
+            "zstd" | "zst" => Some(CertificateCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Determine the compression of a certificate file from its extension
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => CertificateCompression::Gzip,
+            Some("zst") => CertificateCompression::Zstd,
+            _ => CertificateCompression::None,
+        }
+    }
+
+    /// File name used for certificates with this compression
+    pub fn file_name(self) -> &'static str {
+        match self {
+            CertificateCompression::None => "certificate.json",
+            CertificateCompression::Gzip => "certificate.json.gz",
+            CertificateCompression::Zstd => "certificate.json.zst",
+        }
+    }
+}
+
+/// Set the compression used when writing new certificates
+pub fn set_certificate_compression(compression: CertificateCompression) {
+    crate::options::update(|options| options.certificate_compression = compression);
+}
+
+/// Get the compression used when writing new certificates
+pub fn get_certificate_compression() -> CertificateCompression {
+    crate::options::get(|options| options.certificate_compression)
+}
+
+/// Set the maximum size in bytes of a certificate file (and of its decompressed
+/// contents); `None` means unlimited
+pub fn set_max_certificate_size(max_bytes: Option<u64>) {
+    crate::options::update(|options| options.max_certificate_size = max_bytes);
+}
+
+/// Get the maximum certificate size in bytes
+pub fn get_max_certificate_size() -> Option<u64> {
+    crate::options::get(|options| options.max_certificate_size)
+}
+
+/// How inductiveness of a certificate invariant is checked for each transition
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InductivenessCheck {
+    /// Apply the transition as an affine map to the invariant set in ISL
+    TransitionRelation,
+    /// Encode the transition in the formula with fresh existential variables
+    Formula,
+    /// Run both and report an error if they disagree
+    CrossValidate,
+}
+
+impl InductivenessCheck {
+    /// Parse a check name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "isl" | "relation" => Some(InductivenessCheck::TransitionRelation),
+            "formula" => Some(InductivenessCheck::Formula),
+            "both" => Some(InductivenessCheck::CrossValidate),
+            _ => None,
+        }
+    }
+}
+
+/// Set the method used to check inductiveness when verifying certificates
+pub fn set_inductiveness_check(check: InductivenessCheck) {
+    crate::options::update(|options| options.inductiveness_check = check);
+}
+
+/// Get the method used to check inductiveness when verifying certificates
+pub fn get_inductiveness_check() -> InductivenessCheck {
+    crate::options::get(|options| options.inductiveness_check)
+}
+
+/// What certificate checks do with global states that have no invariant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingInvariants {
+    /// Global states that cannot be reached get the invariant `false`, which holds
+    /// trivially there; a missing invariant of a reachable state is an error
+    Unreachable,
+    /// Every missing invariant is an error
+    Reject,
+}
+
+impl MissingInvariants {
+    /// Parse a policy name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unreachable" => Some(MissingInvariants::Unreachable),
+            "reject" => Some(MissingInvariants::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Set what certificate checks do with global states without an invariant
+pub fn set_missing_invariants(policy: MissingInvariants) {
+    crate::options::update(|options| options.missing_invariants = policy);
+}
+
+/// Get what certificate checks do with global states without an invariant
+pub fn get_missing_invariants() -> MissingInvariants {
+    crate::options::get(|options| options.missing_invariants)
+}
+
+/// How the Presburger questions of certificate checking (inclusion, satisfiability)
+/// are decided
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckBackend {
+    /// ISL sets
+    #[cfg(feature = "isl")]
+    Isl,
+    /// Cooper's method in pure Rust (`presburger_fallback`), for builds without ISL
+    Fallback,
+}
+
+impl CheckBackend {
+    /// ISL if it is built in
+    #[cfg(feature = "isl")]
+    pub const DEFAULT: CheckBackend = CheckBackend::Isl;
+    #[cfg(not(feature = "isl"))]
+    pub const DEFAULT: CheckBackend = CheckBackend::Fallback;
+
+    /// Parse a backend name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            #[cfg(feature = "isl")]
+            "isl" => Some(CheckBackend::Isl),
+            "fallback" => Some(CheckBackend::Fallback),
+            _ => None,
+        }
+    }
+}
+
+/// Set how certificate checks decide Presburger formulas
+pub fn set_check_backend(backend: CheckBackend) {
+    crate::options::update(|options| options.check_backend = backend);
+}
+
+/// Get how certificate checks decide Presburger formulas
+pub fn get_check_backend() -> CheckBackend {
+    crate::options::get(|options| options.check_backend)
+}
+
+/// Path at which a new certificate for `out_dir` is written
+pub fn certificate_path(out_dir: &str) -> String {
+    crate::utils::file::join(out_dir, get_certificate_compression().file_name())
+}
+
+/// Find an existing certificate in `out_dir`, whatever compression it was saved with
+pub fn find_certificate(out_dir: &str) -> Option<String> {
+    [
+        get_certificate_compression(),
+        CertificateCompression::None,
+        CertificateCompression::Gzip,
+        CertificateCompression::Zstd,
+    ]
+    .iter()
+    .map(|c| crate::utils::file::join(out_dir, c.file_name()))
+    .find(|p| Path::new(p).exists())
+}
+
+/// Reader/writer adapter that fails once more than `limit` bytes pass through it
+struct SizeLimited<T> {
+    inner: T,
+    count: u64,
+    limit: Option<u64>,
+}
+
+impl<T> SizeLimited<T> {
+    fn new(inner: T, limit: Option<u64>) -> Self {
+        SizeLimited {
+            inner,
+            count: 0,
+            limit,
+        }
+    }
+
+    fn account(&mut self, n: usize) -> std::io::Result<()> {
+        self.count += n as u64;
+        match self.limit {
+            Some(limit) if self.count > limit => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!(
+                    "Certificate exceeds the maximum certificate size of {} bytes",
+                    limit
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Write for SizeLimited<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.account(n)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for SizeLimited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.account(n)?;
+        Ok(n)
+    }
+}
+
+/// Current version of the certificate file format written by `NSDecision::save_to_file`.
+///
+/// Version history:
+/// - 0: bare `NSDecision` JSON without an envelope (legacy)
+/// - 1: `{"version": 1, "decision": <NSDecision>}`
+/// - 2: the variables of invariants are place identifiers (see `place_id`) instead of
+///   `[request, {"InFlight": local}]` and `[request, {"Completed": response}]`
+pub const CERTIFICATE_FORMAT_VERSION: u64 = 2;
+
+/// Bring a certificate JSON value up to `CERTIFICATE_FORMAT_VERSION` and return the
+/// decision payload. Each older version is migrated one step at a time.
+fn migrate_certificate(value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let (mut version, mut decision) = match value {
+        serde_json::Value::Object(mut obj) if obj.contains_key("version") => {
+            let version = obj["version"].as_u64().ok_or_else(|| {
+                format!("Invalid certificate version field: {}", obj["version"])
+            })?;
+            let decision = obj
+                .remove("decision")
+                .ok_or_else(|| format!("Certificate (format version {}) has no decision", version))?;
+            (version, decision)
+        }
+        // Legacy certificates are a bare NSDecision: an object with a single variant tag
+        serde_json::Value::Object(obj)
+            if obj.len() == 1
+                && obj.keys().all(|k| {
+                    matches!(k.as_str(), "Serializable" | "NotSerializable" | "Timeout")
+                }) =>
+        {
+            (0, serde_json::Value::Object(obj))
+        }
+        _ => return Err("Unrecognized certificate format".to_string()),
+    };
+
+    if version > CERTIFICATE_FORMAT_VERSION {
+        return Err(format!(
+            "Certificate format version {} is newer than the supported version {}; please upgrade ser to check this certificate",
+            version, CERTIFICATE_FORMAT_VERSION
+        ));
+    }
+
+    while version < CERTIFICATE_FORMAT_VERSION {
+        decision = match version {
+            // 0 -> 1: only the envelope was added, the decision itself is unchanged
+            0 => decision,
+            1 => migrate_place_ids(decision)?,
+            _ => {
+                return Err(format!(
+                    "No migration available for certificate format version {}",
+                    version
+                ));
+            }
+        };
+        version += 1;
+    }
+
+    Ok(decision)
+}
+
+/// 1 -> 2: rewrite the variables of a proof, in its variable list and in the `Var`s
+/// of its formula, as place identifiers
+fn migrate_place_ids(mut decision: serde_json::Value) -> Result<serde_json::Value, String> {
+    let entries = decision
+        .pointer_mut("/Serializable/invariant/global_invariants")
+        .and_then(serde_json::Value::as_array_mut);
+    for entry in entries.into_iter().flatten() {
+        let invariant = entry
+            .get_mut(1)
+            .ok_or_else(|| "Invalid global state invariant".to_string())?;
+        if let Some(variables) = invariant
+            .get_mut("variables")
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            for variable in variables {
+                *variable = place_id_v1(variable.take())?;
+            }
+        }
+        if let Some(formula) = invariant.get_mut("formula") {
+            migrate_formula_place_ids(formula)?;
+        }
+    }
+    Ok(decision)
+}
+
+fn migrate_formula_place_ids(value: &mut serde_json::Value) -> Result<(), String> {
+    match value {
+        serde_json::Value::Object(obj) if obj.len() == 1 && obj.contains_key("Var") => {
+            let variable = obj["Var"].take();
+            obj["Var"] = place_id_v1(variable)?;
+        }
+        serde_json::Value::Object(obj) => {
+            for field in obj.values_mut() {
+                migrate_formula_place_ids(field)?;
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                migrate_formula_place_ids(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The place identifier of a version 1 variable `[request, {"InFlight": local}]` or
+/// `[request, {"Completed": response}]`
+fn place_id_v1(variable: serde_json::Value) -> Result<serde_json::Value, String> {
+    let invalid = || format!("Invalid invariant variable: {}", variable);
+    let serde_json::Value::Array(pair) = &variable else {
+        return Err(invalid());
+    };
+    let [request, serde_json::Value::Object(state)] = pair.as_slice() else {
+        return Err(invalid());
+    };
+    match state.iter().next() {
+        Some((tag, local)) if state.len() == 1 && tag == "InFlight" => Ok(serde_json::json!({
+            "place": "in_flight",
+            "request": request,
+            "local": local,
+        })),
+        Some((tag, response)) if state.len() == 1 && tag == "Completed" => Ok(serde_json::json!({
+            "place": "completed",
+            "request": request,
+            "response": response,
+        })),
+        _ => Err(invalid()),
+    }
+}
+
 /// NS-level invariant structure that captures per-global-state invariants
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(bound(serialize = "G: Serialize, L: Serialize, Req: Serialize, Resp: Serialize"))]
@@ -394,19 +1127,115 @@ where
         Req: Clone + Display + Eq + Hash + Ord + Debug + ToString,
         Resp: Clone + Display + Eq + Hash + Ord + Debug + ToString,
     {
+        let invariant = self.with_default_invariants(ns)?;
+
         // Check 1: Initial state satisfies the invariant
-        self.check_initial_state(ns)?;
+        invariant.check_initial_state(ns)?;
 
         // Check 2: Invariant is inductive
-        self.check_inductive(ns)?;
+        invariant.check_inductive(ns)?;
 
         // Check 3: Invariant implies target (serializability)
-        self.check_implies_target(ns)?;
+        invariant.check_implies_target(ns)?;
 
         Ok(())
     }
 
-    /// Check that the initial state satisfies the invariant
+    /// The invariant with an invariant for every global state of `ns`: under
+    /// `MissingInvariants::Unreachable`, global states that cannot be reached get
+    /// `false`. Otherwise an error lists the global states without an invariant, with a
+    /// path to each reachable one.
+    pub fn with_default_invariants(&self, ns: &NS<G, L, Req, Resp>) -> Result<Cow<'_, Self>, String>
+    where
+        G: Clone + Display + Eq + Hash + Ord,
+        L: Clone + Eq + Hash,
+        Req: Clone + Eq + Hash,
+        Resp: Clone + Eq + Hash,
+    {
+        let mut missing: Vec<&G> = ns
+            .get_global_states()
+            .into_iter()
+            .filter(|global| !self.global_invariants.contains_key(*global))
+            .collect();
+        if missing.is_empty() {
+            return Ok(Cow::Borrowed(self));
+        }
+        missing.sort();
+
+        let reachable = ns.reachable_global_states();
+        let policy = get_missing_invariants();
+        let uncovered: Vec<String> = missing
+            .iter()
+            .filter_map(|global| match reachable.get(*global) {
+                Some(path) => {
+                    let path: Vec<String> = path.iter().map(|g| g.to_string()).collect();
+                    Some(format!("{} (reachable: {})", global, path.join(" -> ")))
+                }
+                None if policy == MissingInvariants::Reject => Some(format!(
+                    "{} (unreachable; allowed with --missing-invariants unreachable)",
+                    global
+                )),
+                None => None,
+            })
+            .collect();
+        if !uncovered.is_empty() {
+            return Err(format!(
+                "No invariant for global state{} {}",
+                if uncovered.len() == 1 { "" } else { "s" },
+                uncovered.join(", ")
+            ));
+        }
+
+        // Same variables as the other invariants, so that all of them share a space
+        let variables = self
+            .global_invariants
+            .values()
+            .next()
+            .map(|invariant| invariant.variables.clone())
+            .unwrap_or_default();
+        let mut completed = self.clone();
+        for global in missing {
+            completed.global_invariants.insert(
+                global.clone(),
+                ProofInvariant::new(variables.clone(), Formula::Or(vec![])),
+            );
+        }
+        Ok(Cow::Owned(completed))
+    }
+
+    /// For each global state with an invariant in either certificate, whether the
+    /// invariant of `self` implies the one of `other`. A global state without an
+    /// invariant is unreachable, so its invariant is `false`.
+    pub fn implies(&self, other: &Self) -> Result<Vec<(G, bool)>, String>
+    where
+        G: Clone + Display + Eq + Hash + Ord,
+        L: Clone + Display + Eq + Hash + ToString,
+        Req: Clone + Display + Eq + Hash + ToString,
+        Resp: Clone + Display + Eq + Hash + ToString,
+    {
+        let mut globals: Vec<&G> = self
+            .global_invariants
+            .keys()
+            .chain(other.global_invariants.keys())
+            .collect();
+        globals.sort();
+        globals.dedup();
+        let unreachable = ProofInvariant::new(vec![], Formula::Or(vec![]));
+        globals
+            .into_iter()
+            .map(|global| {
+                let antecedent = self.global_invariants.get(global).unwrap_or(&unreachable);
+                let consequent = other.global_invariants.get(global).unwrap_or(&unreachable);
+                let implied = self.check_formula_implies(antecedent, consequent)?;
+                Ok((global.clone(), implied))
+            })
+            .collect()
+    }
+
+    /// Check that the initial state satisfies the invariant. The initial state is the
+    /// initial marking of the Petri net with requests, which is also where the
+    /// analysis starts: the token of the initial global state, and a token for each
+    /// request that is in flight or has completed before the system starts.
     fn check_initial_state(&self, ns: &NS<G, L, Req, Resp>) -> Result<(), String>
     where
         G: Clone + Display,
@@ -414,31 +1243,57 @@ where
         Req: Clone + Display,
         Resp: Clone + Display,
     {
-        // Get the invariant for the initial global state
-        let initial_invariant =
-            self.global_invariants
-                .get(&ns.initial_global)
-                .ok_or_else(|| {
-                    format!(
-                        "No invariant found for initial global state: {}",
-                        ns.initial_global
-                    )
-                })?;
+        let mut globals = Vec::new();
+        let mut counts: HashMap<RequestStatePair<Req, L, Resp>, i32> = HashMap::default();
+        for place in crate::ns_to_petri::ns_to_petri_with_requests(ns).get_initial_marking() {
+            let var = match place {
+                ReqPetriState::Global(global) => {
+                    globals.push(global);
+                    continue;
+                }
+                ReqPetriState::Local(req, local) => {
+                    RequestStatePair(req, RequestState::InFlight(local))
+                }
+                ReqPetriState::Response(req, resp) => {
+                    RequestStatePair(req, RequestState::Completed(resp))
+                }
+                // Requests that can still be issued are not counted by the invariant
+                ReqPetriState::Request(_) => continue,
+            };
+            *counts.entry(var).or_insert(0) += 1;
+        }
+        let initial_global = match globals.as_slice() {
+            [global] => global,
+            _ => {
+                return Err(format!(
+                    "Initial marking has {} global state tokens instead of one",
+                    globals.len()
+                ));
+            }
+        };
 
-        // Initial state has empty multiset (no requests in flight or completed)
-        // This means all variables in the formula should be substituted with 0
-        let mut mapping = |_var: &RequestStatePair<Req, L, Resp>| -> Either<String, i32> {
-            // All variables map to 0 in the empty multiset
-            Either::Right(0)
+        // Get the invariant for the initial global state
+        let initial_invariant = self.global_invariants.get(initial_global).ok_or_else(|| {
+            format!(
+                "No invariant found for initial global state: {}",
+                initial_global
+            )
+        })?;
+
+        // Every variable is substituted with its number of tokens in the initial marking
+        let mut mapping = |var: &RequestStatePair<Req, L, Resp>| -> Either<String, i32> {
+            Either::Right(counts.get(var).copied().unwrap_or(0))
         };
         let substituted_invariant: ProofInvariant<String> =
             initial_invariant.substitute(&mut mapping);
 
         // Check if the substituted formula is satisfiable
-        if is_formula_satisfied_string(&substituted_invariant.formula) {
+        if is_formula_satisfied_string(&substituted_invariant.formula)? {
             Ok(())
-        } else {
+        } else if counts.is_empty() {
             Err("Initial state (empty multiset) does not satisfy the invariant".to_string())
+        } else {
+            Err("Initial state (with the initial requests in flight) does not satisfy the invariant".to_string())
         }
     }
 
@@ -463,25 +1318,14 @@ where
                 .ok_or_else(|| format!("No invariant for global state: {}", to_global))?;
 
             // For each possible request type that could be in this local state
-            for (req, _) in &ns.requests {
+            for req in ns.get_requests() {
                 let from_var =
                     RequestStatePair(req.clone(), RequestState::InFlight(from_local.clone()));
                 let to_var =
                     RequestStatePair(req.clone(), RequestState::InFlight(to_local.clone()));
 
-                // Convert to Either type for the operations
-                let from_inv_either: ProofInvariant<Either<usize, RequestStatePair<Req, L, Resp>>> =
-                    from_inv.clone().map(|v| Either::Right(v.clone()));
-
                 // Apply the transition: remove one from source, add one to target
-                let inv_after_remove = from_inv_either.filter_and_subtract_one(&from_var);
-                let inv_after_add = inv_after_remove.add_one(&to_var);
-
-                // Project back to the original type
-                let inv_after_transition = inv_after_add.project_right();
-
-                // Check if the result implies the target invariant
-                if !self.check_formula_implies(&inv_after_transition, to_inv)? {
+                if !self.check_transfer_preserves(from_inv, Some(&from_var), &to_var, to_inv)? {
                     return Err(format!(
                         "Invariant not inductive for transition ({}, {}) -> ({}, {}) with request {}",
                         from_local, from_global, to_local, to_global, req
@@ -505,15 +1349,8 @@ where
             let new_var =
                 RequestStatePair(req.clone(), RequestState::InFlight(initial_local.clone()));
 
-            // Convert to Either type for the operation
-            let initial_inv_either: ProofInvariant<Either<usize, RequestStatePair<Req, L, Resp>>> =
-                initial_inv.clone().map(|v| Either::Right(v.clone()));
-
-            let inv_after_add = initial_inv_either.add_one(&new_var);
-            let inv_after_creation = inv_after_add.project_right();
-
             // Check if creating a new request preserves the initial state invariant
-            if !self.check_formula_implies(&inv_after_creation, initial_inv)? {
+            if !self.check_transfer_preserves(initial_inv, None, &new_var, initial_inv)? {
                 return Err(format!(
                     "Invariant not inductive for request creation: {} at local state {}",
                     req, initial_local
@@ -531,34 +1368,156 @@ where
                     .ok_or_else(|| format!("No invariant for global state: {}", global_state))?;
 
                 // For each request type that could complete with this response
-                for (req, _) in &ns.requests {
+                for req in ns.get_requests() {
                     let inflight_var =
                         RequestStatePair(req.clone(), RequestState::InFlight(final_local.clone()));
                     let completed_var =
                         RequestStatePair(req.clone(), RequestState::Completed(resp.clone()));
 
-                    // Convert to Either type for the operations
-                    let global_inv_either: ProofInvariant<
-                        Either<usize, RequestStatePair<Req, L, Resp>>,
-                    > = global_inv.clone().map(|v| Either::Right(v.clone()));
+                    // Apply completion: remove inflight, add completed, and check that
+                    // this preserves the same global state invariant
+                    if !self.check_transfer_preserves(
+                        global_inv,
+                        Some(&inflight_var),
+                        &completed_var,
+                        global_inv,
+                    )? {
+                        return Err(format!(
+                            "Invariant not inductive for request completion: {} at {} -> {} in global state {}",
+                            req, final_local, resp, global_state
+                        ));
+                    }
+                }
+            }
+        }
 
-                    // Apply completion: remove inflight, add completed
-                    let inv_after_remove = global_inv_either.filter_and_subtract_one(&inflight_var);
-                    let inv_after_add = inv_after_remove.add_one(&completed_var);
-                    let inv_after_completion = inv_after_add.project_right();
+        Ok(())
+    }
 
-                    // Check if completion preserves the same global state invariant
-                    if !self.check_formula_implies(&inv_after_completion, global_inv)? {
+    /// Check that moving one token from `from_var` (or creating one, if `None`) to
+    /// `to_var` takes every state satisfying `from_inv` to a state satisfying `to_inv`,
+    /// using the method selected with `set_inductiveness_check`
+    fn check_transfer_preserves(
+        &self,
+        from_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+        from_var: Option<&RequestStatePair<Req, L, Resp>>,
+        to_var: &RequestStatePair<Req, L, Resp>,
+        to_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+    ) -> Result<bool, String>
+    where
+        G: Display,
+        L: Clone + Display + Eq + Hash + ToString,
+        Req: Clone + Display + Eq + Hash + ToString,
+        Resp: Clone + Display + Eq + Hash + ToString,
+    {
+        match get_check_backend() {
+            #[cfg(feature = "isl")]
+            CheckBackend::Isl => match get_inductiveness_check() {
+                InductivenessCheck::TransitionRelation => {
+                    self.check_transfer_via_relation(from_inv, from_var, to_var, to_inv)
+                }
+                InductivenessCheck::Formula => {
+                    self.check_transfer_via_formula(from_inv, from_var, to_var, to_inv)
+                }
+                InductivenessCheck::CrossValidate => {
+                    let relation = self.check_transfer_via_relation(from_inv, from_var, to_var, to_inv)?;
+                    let formula = self.check_transfer_via_formula(from_inv, from_var, to_var, to_inv)?;
+                    if relation != formula {
                         return Err(format!(
-                            "Invariant not inductive for request completion: {} at {} -> {} in global state {}",
-                            req, final_local, resp, global_state
+                            "Inductiveness checks disagree for {} -> {}: transition relation says {}, formula encoding says {}",
+                            from_var.map_or("(new request)".to_string(), |v| v.to_string()),
+                            to_var,
+                            relation,
+                            formula
                         ));
                     }
+                    Ok(relation)
                 }
+            },
+            // The transition relation is applied to ISL sets, so without ISL the
+            // formula encoding is used
+            CheckBackend::Fallback => {
+                self.check_transfer_via_formula(from_inv, from_var, to_var, to_inv)
             }
         }
+    }
 
-        Ok(())
+    /// Transfer check using the old encoding: the transition is expressed in the
+    /// formula itself with existential variables for the values before the step
+    fn check_transfer_via_formula(
+        &self,
+        from_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+        from_var: Option<&RequestStatePair<Req, L, Resp>>,
+        to_var: &RequestStatePair<Req, L, Resp>,
+        to_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+    ) -> Result<bool, String>
+    where
+        G: Display,
+        L: Clone + Display + Eq + Hash + ToString,
+        Req: Clone + Display + Eq + Hash + ToString,
+        Resp: Clone + Display + Eq + Hash + ToString,
+    {
+        // Convert to Either type for the operations
+        let inv_either: ProofInvariant<Either<usize, RequestStatePair<Req, L, Resp>>> =
+            from_inv.clone().map(|v| Either::Right(v.clone()));
+
+        // Remove one from the source, if any
+        let inv_after_remove = match from_var {
+            Some(from_var) => inv_either.filter_and_subtract_one(from_var),
+            None => inv_either,
+        };
+
+        // Add one to the target and project back to the original type
+        let inv_after_transition = inv_after_remove.add_one(to_var).project_right();
+
+        self.check_formula_implies(&inv_after_transition, to_inv)
+    }
+
+    /// Transfer check using the transition relation: the invariant set is mapped
+    /// through the affine effect of the step in ISL and compared with the target set
+    #[cfg(feature = "isl")]
+    fn check_transfer_via_relation(
+        &self,
+        from_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+        from_var: Option<&RequestStatePair<Req, L, Resp>>,
+        to_var: &RequestStatePair<Req, L, Resp>,
+        to_inv: &ProofInvariant<RequestStatePair<Req, L, Resp>>,
+    ) -> Result<bool, String>
+    where
+        G: Display,
+        L: Clone + Display + Eq + Hash + ToString,
+        Req: Clone + Display + Eq + Hash + ToString,
+        Resp: Clone + Display + Eq + Hash + ToString,
+    {
+        // All variables of both invariants and of the step, as sorted strings
+        let mut string_vars: Vec<String> = from_inv
+            .variables
+            .iter()
+            .chain(to_inv.variables.iter())
+            .chain(from_var)
+            .chain(std::iter::once(to_var))
+            .map(|v| v.to_string())
+            .collect();
+        string_vars.sort();
+        string_vars.dedup();
+
+        let from_string = from_inv.clone().map(|v| v.to_string());
+        let to_string = to_inv.clone().map(|v| v.to_string());
+        let mut states = formula_to_presburger(&from_string.formula, &string_vars);
+        let target = formula_to_presburger(&to_string.formula, &string_vars);
+        let naturals = PresburgerSet::universe(string_vars.clone());
+
+        // Remove the token first and drop the states where there was none to remove.
+        // This is done as a separate step so that self-loops still require a token.
+        if let Some(from_var) = from_var {
+            let remove =
+                AffineMap::identity(&string_vars).with_offset(from_var.to_string(), -1);
+            states = states.apply_affine_map(&remove).intersection(&naturals);
+        }
+        let add = AffineMap::identity(&string_vars).with_offset(to_var.to_string(), 1);
+        let after = states.apply_affine_map(&add);
+
+        Ok(after.difference(&target).is_empty())
     }
 
     /// Check if one proof invariant implies another using Presburger arithmetic
@@ -586,13 +1545,24 @@ where
         let antecedent_string = antecedent.clone().map(|v| v.to_string());
         let consequent_string = consequent.clone().map(|v| v.to_string());
 
-        // Convert to Presburger sets using the same variable mapping
-        let antecedent_set = formula_to_presburger(&antecedent_string.formula, &string_vars);
-        let consequent_set = formula_to_presburger(&consequent_string.formula, &string_vars);
-
-        // Check if antecedent ⊆ consequent (i.e., antecedent \ consequent = ∅)
-        let difference = antecedent_set.difference(&consequent_set);
-        Ok(difference.is_empty())
+        match get_check_backend() {
+            #[cfg(feature = "isl")]
+            CheckBackend::Isl => {
+                // Convert to Presburger sets using the same variable mapping
+                let antecedent_set =
+                    formula_to_presburger(&antecedent_string.formula, &string_vars);
+                let consequent_set =
+                    formula_to_presburger(&consequent_string.formula, &string_vars);
+
+                // Check if antecedent ⊆ consequent (i.e., antecedent \ consequent = ∅)
+                let difference = antecedent_set.difference(&consequent_set);
+                Ok(difference.is_empty())
+            }
+            CheckBackend::Fallback => crate::presburger_fallback::implies(
+                &antecedent_string.formula,
+                &consequent_string.formula,
+            ),
+        }
     }
 
     /// Check that the invariant implies the target property (serializability)
@@ -665,37 +1635,253 @@ where
         string_vars.sort();
 
         let string_invariant = invariant.clone().map(|v| v.to_string());
-        let invariant_set = formula_to_presburger(&string_invariant.formula, &string_vars);
-
-        // Convert semilinear set to String type and then to PresburgerSet
         let string_semilinear = semilinear.clone().rename(|v| v.to_string());
-        let mut spresburger =
-            crate::spresburger::SPresburgerSet::from_semilinear(string_semilinear.clone());
-        let semilinear_as_presburger = spresburger.as_presburger();
 
-        // Check if invariant_set ⊆ semilinear_set
-        // This is equivalent to: invariant_set \ semilinear_set = ∅
-        let difference = invariant_set.difference(semilinear_as_presburger);
+        match get_check_backend() {
+            #[cfg(feature = "isl")]
+            CheckBackend::Isl => {
+                let invariant_set = formula_to_presburger(&string_invariant.formula, &string_vars);
+
+                // Convert semilinear set to PresburgerSet
+                let mut spresburger =
+                    crate::spresburger::SPresburgerSet::from_semilinear(string_semilinear.clone());
+                let semilinear_as_presburger = spresburger.as_presburger();
+
+                // Check if invariant_set ⊆ semilinear_set
+                // This is equivalent to: invariant_set \ semilinear_set = ∅
+                let difference = invariant_set.difference(semilinear_as_presburger);
+
+                if difference.is_empty() {
+                    Ok(true)
+                } else {
+                    // Log which values violate the implication for debugging
+                    eprintln!(
+                        "Warning: Invariant for global state {} has values outside serializable set",
+                        global_state
+                    );
+                    eprintln!("  Semilinear set: {}", string_semilinear);
+                    eprintln!("  Projected invariant: {}", string_invariant.formula);
+                    eprintln!("  Projected invariant (ISL): {}", invariant_set);
+                    eprintln!("  Invariant variables: {:?}", string_vars);
+                    eprintln!("  Values outside serializable set: {}", difference);
+                    Ok(false)
+                }
+            }
+            CheckBackend::Fallback => {
+                let included = crate::presburger_fallback::included_in_semilinear(
+                    &string_invariant.formula,
+                    &string_vars,
+                    &string_semilinear,
+                )?;
+                if !included {
+                    eprintln!(
+                        "Warning: Invariant for global state {} has values outside serializable set",
+                        global_state
+                    );
+                    eprintln!("  Semilinear set: {}", string_semilinear);
+                    eprintln!("  Projected invariant: {}", string_invariant.formula);
+                    eprintln!("  Invariant variables: {:?}", string_vars);
+                }
+                Ok(included)
+            }
+        }
+    }
+}
+
+
+/// Enable writing the invariant of a serializability proof as LaTeX (`--latex`)
+pub fn set_latex_export(enabled: bool) {
+    crate::options::update(|options| options.latex_export = enabled);
+}
+
+/// Whether the invariant of a serializability proof is also written as LaTeX
+pub fn latex_export_enabled() -> bool {
+    crate::options::get(|options| options.latex_export)
+}
+
+/// Escape a name for use inside `\text{...}`
+pub fn latex_escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for c in name.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Sum of terms with positive coefficients, as LaTeX; 0 if there are none
+fn latex_sum(terms: &[(i64, String)]) -> String {
+    if terms.is_empty() {
+        return "0".to_string();
+    }
+    terms
+        .iter()
+        .map(|(coeff, term)| match coeff {
+            1 => term.clone(),
+            _ => format!("{} \\cdot {}", coeff, term),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
 
-        if difference.is_empty() {
-            Ok(true)
+/// A constraint `expr op 0` as LaTeX, with negative terms moved to the right-hand side
+fn constraint_to_latex<T>(constraint: &Constraint<T>, var: &impl Fn(&T) -> String) -> String
+where
+    T: Clone + Eq + Hash,
+{
+    let (terms, constant) = constraint.expr.to_linear_combination();
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for (coeff, variable) in terms {
+        let name = match &variable {
+            Variable::Var(t) => var(t),
+            Variable::Existential(n) => format!("e_{{{}}}", n),
+        };
+        match coeff.cmp(&0) {
+            std::cmp::Ordering::Greater => left.push((coeff, name)),
+            std::cmp::Ordering::Less => right.push((-coeff, name)),
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    // Sort terms by name, since the order of the expression is not deterministic
+    left.sort_by(|a, b| a.1.cmp(&b.1));
+    right.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut right = latex_sum(&right);
+    if constant < 0 {
+        right = if right == "0" {
+            (-constant).to_string()
         } else {
-            // Log which values violate the implication for debugging
-            eprintln!(
-                "Warning: Invariant for global state {} has values outside serializable set",
-                global_state
-            );
-            eprintln!("  Semilinear set: {}", string_semilinear);
-            eprintln!("  Projected invariant: {}", string_invariant.formula);
-            eprintln!("  Projected invariant (ISL): {}", invariant_set);
-            eprintln!("  Invariant variables: {:?}", string_vars);
-            eprintln!("  Values outside serializable set: {}", difference);
-            Ok(false)
+            format!("{} + {}", right, -constant)
+        };
+    }
+    let mut left = latex_sum(&left);
+    if constant > 0 {
+        left = if left == "0" {
+            constant.to_string()
+        } else {
+            format!("{} + {}", left, constant)
+        };
+    }
+    let op = match constraint.op {
+        CompOp::Eq => "=",
+        CompOp::Geq => "\\geq",
+    };
+    format!("{} {} {}", left, op, right)
+}
+
+/// A formula as LaTeX math, with variables rendered by `var`
+pub fn formula_to_latex<T>(formula: &Formula<T>, var: &impl Fn(&T) -> String) -> String
+where
+    T: Clone + Eq + Hash,
+{
+    let join = |formulas: &[Formula<T>], op: &str| {
+        let parts: Vec<String> = formulas.iter().map(|f| formula_to_latex(f, var)).collect();
+        format!("\\left({}\\right)", parts.join(op))
+    };
+    match formula {
+        Formula::Constraint(constraint) => constraint_to_latex(constraint, var),
+        Formula::And(formulas) if formulas.is_empty() => "\\top".to_string(),
+        Formula::And(formulas) => join(formulas, " \\land "),
+        Formula::Or(formulas) if formulas.is_empty() => "\\bot".to_string(),
+        Formula::Or(formulas) => join(formulas, " \\lor "),
+        Formula::Exists(n, body) => {
+            format!("\\exists e_{{{}}}.\\, {}", n, formula_to_latex(body, var))
+        }
+        Formula::Forall(n, body) => {
+            format!("\\forall e_{{{}}}.\\, {}", n, formula_to_latex(body, var))
         }
     }
 }
 
+impl<G, L, Req, Resp> NSInvariant<G, L, Req, Resp>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    /// The invariant of each global state, and its projection to completed requests,
+    /// as LaTeX. In-flight requests are written `req@local` and completed ones
+    /// `req/resp`, each standing for the number of such requests.
+    pub fn to_latex(&self) -> String {
+        let in_flight = |pair: &RequestStatePair<Req, L, Resp>| match &pair.1 {
+            RequestState::InFlight(local) => format!(
+                "\\#\\text{{{}}}@\\text{{{}}}",
+                latex_escape(&pair.0.to_string()),
+                latex_escape(&local.to_string())
+            ),
+            RequestState::Completed(resp) => format!(
+                "\\#\\text{{{}}}/\\text{{{}}}",
+                latex_escape(&pair.0.to_string()),
+                latex_escape(&resp.to_string())
+            ),
+        };
+        let completed = |pair: &CompletedRequestPair<Req, Resp>| {
+            format!(
+                "\\#\\text{{{}}}/\\text{{{}}}",
+                latex_escape(&pair.0.to_string()),
+                latex_escape(&pair.1.to_string())
+            )
+        };
+
+        // Global states in the order of their names, for a stable document
+        let mut globals: Vec<&G> = self.global_invariants.keys().collect();
+        globals.sort_by_key(|g| g.to_string());
+
+        let mut latex = String::new();
+        for global in globals {
+            let invariant = &self.global_invariants[global];
+            latex.push_str(&format!(
+                "\\subsection*{{Global state \\texttt{{{}}}}}\n\n",
+                latex_escape(&global.to_string())
+            ));
+            latex.push_str("Invariant:\n\\[\n");
+            latex.push_str(&formula_to_latex(&invariant.formula, &in_flight));
+            latex.push_str("\n\\]\n\n");
+            if let Some(projected) = self.project_to_completed(global) {
+                latex.push_str("Completed requests only:\n\\[\n");
+                latex.push_str(&formula_to_latex(&projected.formula, &completed));
+                latex.push_str("\n\\]\n\n");
+            }
+        }
+        latex
+    }
+
+    /// `to_latex` as a standalone document
+    pub fn to_latex_document(&self, title: &str) -> String {
+        format!(
+            "\\documentclass{{article}}\n\
+             \\usepackage{{amsmath,amssymb}}\n\
+             \\usepackage[margin=2cm]{{geometry}}\n\
+             \\allowdisplaybreaks\n\
+             \n\
+             \\begin{{document}}\n\
+             \\section*{{Serializability invariant of \\texttt{{{}}}}}\n\
+             \n\
+             {}\
+             \\end{{document}}\n",
+            latex_escape(title),
+            self.to_latex()
+        )
+    }
 
+    /// Write the invariant as a LaTeX document to `<out_dir>/invariant.tex`
+    pub fn save_latex(&self, out_dir: &str, title: &str) -> Result<String, std::io::Error> {
+        let path = crate::utils::file::join(out_dir, "invariant.tex");
+        crate::utils::file::safe_write_file(&path, &self.to_latex_document(title))?;
+        Ok(path)
+    }
+}
 
 /// Translate a Petri net proof to NS-level invariants
 pub fn translate_petri_proof_to_ns<G, L, Req, Resp>(
@@ -850,6 +2036,13 @@ where
         Decision::Timeout { message } => {
             NSDecision::Timeout { message }
         }
+        Decision::Unknown {
+            reason,
+            partial_results,
+        } => NSDecision::Unknown {
+            reason,
+            partial_results,
+        },
     }
 }
 
@@ -1062,6 +2255,184 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ns_decision_file_has_version() {
+        use tempfile::NamedTempFile;
+
+        let decision: NSDecision<String, String, String, String> = NSDecision::Timeout {
+            message: "out of time".to_string(),
+        };
+        let temp_file = NamedTempFile::new().unwrap();
+        decision.save_to_file(temp_file.path()).unwrap();
+
+        let json = fs::read_to_string(temp_file.path()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"].as_u64(), Some(CERTIFICATE_FORMAT_VERSION));
+        assert!(value["decision"]["Timeout"].is_object());
+    }
+
+    #[test]
+    fn test_ns_decision_load_legacy_certificate() {
+        use tempfile::NamedTempFile;
+
+        // Version 0 certificates are the bare decision without an envelope
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), r#"{"Timeout":{"message":"legacy"}}"#).unwrap();
+
+        let loaded =
+            NSDecision::<String, String, String, String>::load_from_file(temp_file.path()).unwrap();
+        match loaded {
+            NSDecision::Timeout { message } => assert_eq!(message, "legacy"),
+            _ => panic!("Expected Timeout decision"),
+        }
+    }
+
+    #[test]
+    fn test_ns_decision_load_version_1_certificate() {
+        // Version 1 variables are `[request, {"InFlight": local}]` or
+        // `[request, {"Completed": response}]`
+        let json = r#"{"version": 1, "decision": {"Serializable": {"invariant": {"global_invariants": [
+            ["G1", {
+                "variables": [["req1", {"InFlight": "L1"}], ["req1", {"Completed": "resp1"}]],
+                "formula": {"Constraint": {
+                    "expr": {"terms": [[{"Var": ["req1", {"Completed": "resp1"}]}, -1]], "constant": 1},
+                    "op": "Geq"
+                }}
+            }]
+        ]}}}}"#;
+        let decision = NSDecision::<String, String, String, String>::from_json_str(json).unwrap();
+        let NSDecision::Serializable { invariant } = decision else {
+            panic!("Expected Serializable decision");
+        };
+        let completed = RequestStatePair(
+            "req1".to_string(),
+            RequestState::Completed("resp1".to_string()),
+        );
+        let proof = &invariant.global_invariants["G1"];
+        assert_eq!(
+            proof.variables,
+            vec![
+                RequestStatePair("req1".to_string(), RequestState::InFlight("L1".to_string())),
+                completed.clone(),
+            ]
+        );
+        assert!(proof.formula.collect_free_variables().contains(&completed));
+    }
+
+    #[test]
+    fn test_ns_decision_load_newer_certificate_fails() {
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let json = format!(
+            r#"{{"version":{},"decision":{{"Timeout":{{"message":"future"}}}}}}"#,
+            CERTIFICATE_FORMAT_VERSION + 1
+        );
+        fs::write(temp_file.path(), json).unwrap();
+
+        let err = NSDecision::<String, String, String, String>::load_from_file(temp_file.path())
+            .unwrap_err();
+        assert!(err.to_string().contains("newer than the supported version"));
+    }
+
+    #[test]
+    fn test_ns_decision_load_unrecognized_certificate_fails() {
+        assert!(migrate_certificate(serde_json::json!({"Bogus": {}})).is_err());
+        assert!(migrate_certificate(serde_json::json!([1, 2, 3])).is_err());
+    }
+
+    #[test]
+    fn test_ns_decision_compressed_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let decision: NSDecision<String, String, String, String> = NSDecision::Timeout {
+            message: "compressed".to_string(),
+        };
+
+        for compression in [CertificateCompression::Gzip, CertificateCompression::Zstd] {
+            let path = dir.path().join(compression.file_name());
+            decision.save_to_file(&path).unwrap();
+            // Compressed files are not plain JSON
+            assert!(serde_json::from_slice::<serde_json::Value>(&fs::read(&path).unwrap()).is_err());
+
+            let loaded = NSDecision::<String, String, String, String>::load_from_file(&path).unwrap();
+            match loaded {
+                NSDecision::Timeout { message } => assert_eq!(message, "compressed"),
+                _ => panic!("Expected Timeout decision"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_certificate_compression_from_path() {
+        assert_eq!(
+            CertificateCompression::from_path(Path::new("out/x/certificate.json")),
+            CertificateCompression::None
+        );
+        assert_eq!(
+            CertificateCompression::from_path(Path::new("out/x/certificate.json.gz")),
+            CertificateCompression::Gzip
+        );
+        assert_eq!(
+            CertificateCompression::from_path(Path::new("out/x/certificate.json.zst")),
+            CertificateCompression::Zstd
+        );
+        assert_eq!(CertificateCompression::from_name("zstd"), Some(CertificateCompression::Zstd));
+        assert_eq!(CertificateCompression::from_name("lz4"), None);
+    }
+
+    #[test]
+    fn test_size_limited_writer() {
+        let mut writer = SizeLimited::new(Vec::new(), Some(4));
+        assert!(writer.write_all(b"abcd").is_ok());
+        assert!(writer.write_all(b"e").is_err());
+
+        let mut unlimited = SizeLimited::new(Vec::new(), None);
+        assert!(unlimited.write_all(&[0u8; 1024]).is_ok());
+    }
+
+    #[test]
+    fn test_ns_decision_unknown_round_trip() {
+        use crate::reachability_with_proofs::DisjunctOutcome;
+        use tempfile::NamedTempFile;
+
+        let decision: NSDecision<String, String, String, String> = NSDecision::Unknown {
+            reason: UnknownReason::SolverUnknown {
+                message: "no verdict".to_string(),
+            },
+            partial_results: vec![
+                DisjunctResult {
+                    disjunct: 0,
+                    outcome: DisjunctOutcome::Unreachable,
+                },
+                DisjunctResult {
+                    disjunct: 1,
+                    outcome: DisjunctOutcome::Unknown,
+                },
+            ],
+        };
+        let temp_file = NamedTempFile::new().unwrap();
+        decision.save_to_file(temp_file.path()).unwrap();
+
+        let loaded =
+            NSDecision::<String, String, String, String>::load_from_file(temp_file.path()).unwrap();
+        match loaded {
+            NSDecision::Unknown {
+                reason,
+                partial_results,
+            } => {
+                assert_eq!(
+                    reason,
+                    UnknownReason::SolverUnknown {
+                        message: "no verdict".to_string()
+                    }
+                );
+                assert_eq!(partial_results.len(), 2);
+                assert_eq!(partial_results[1].outcome, DisjunctOutcome::Unknown);
+            }
+            _ => panic!("Expected Unknown decision"),
+        }
+    }
+
     #[test]
     fn test_simple_substitution() {
         // Create a simple proof invariant with mixed Left/Right variables
@@ -1110,6 +2481,106 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "isl")]
+    fn test_transfer_checks_agree() {
+        let ns_invariant = NSInvariant::<String, String, String, String> {
+            global_invariants: HashMap::default(),
+        };
+        let a = RequestStatePair("req".to_string(), RequestState::InFlight("A".to_string()));
+        let b = RequestStatePair("req".to_string(), RequestState::InFlight("B".to_string()));
+
+        // a + b = 1
+        let one_token = ProofInvariant::new(
+            vec![a.clone(), b.clone()],
+            Formula::Constraint(Constraint::new(
+                AffineExpr::from_var(a.clone())
+                    .add(&AffineExpr::from_var(b.clone()))
+                    .sub(&AffineExpr::from_const(1)),
+                CompOp::Eq,
+            )),
+        );
+        // a = 0
+        let a_empty = ProofInvariant::new(
+            vec![a.clone()],
+            Formula::Constraint(Constraint::new(AffineExpr::from_var(a.clone()), CompOp::Eq)),
+        );
+
+        let relation = |from_inv, from_var, to_var, to_inv| {
+            ns_invariant
+                .check_transfer_via_relation(from_inv, from_var, to_var, to_inv)
+                .unwrap()
+        };
+        let formula = |from_inv, from_var, to_var, to_inv| {
+            ns_invariant
+                .check_transfer_via_formula(from_inv, from_var, to_var, to_inv)
+                .unwrap()
+        };
+
+        // Moving the token from a to b keeps a + b = 1
+        assert!(relation(&one_token, Some(&a), &b, &one_token));
+        assert!(formula(&one_token, Some(&a), &b, &one_token));
+        // Creating a new token does not
+        assert!(!relation(&one_token, None, &a, &one_token));
+        assert!(!formula(&one_token, None, &a, &one_token));
+        // Moving a token out of an empty place is impossible, even for a self-loop
+        assert!(relation(&a_empty, Some(&a), &a, &one_token));
+        assert!(relation(&a_empty, Some(&a), &b, &one_token));
+    }
+
+    #[test]
+    fn test_fallback_backend() {
+        use crate::semilinear::SemilinearSet;
+
+        let ns_invariant = NSInvariant::<String, String, String, String> {
+            global_invariants: HashMap::default(),
+        };
+        let a = RequestStatePair("req".to_string(), RequestState::InFlight("A".to_string()));
+        let b = RequestStatePair("req".to_string(), RequestState::InFlight("B".to_string()));
+        // a + b = 1
+        let one_token = ProofInvariant::new(
+            vec![a.clone(), b.clone()],
+            Formula::Constraint(Constraint::new(
+                AffineExpr::from_var(a.clone())
+                    .add(&AffineExpr::from_var(b.clone()))
+                    .sub(&AffineExpr::from_const(1)),
+                CompOp::Eq,
+            )),
+        );
+        let x_equals = |n| {
+            ProofInvariant::new(
+                vec!["x".to_string()],
+                Formula::Constraint(Constraint::new(
+                    AffineExpr::from_var("x".to_string()).sub(&AffineExpr::from_const(n)),
+                    CompOp::Eq,
+                )),
+            )
+        };
+        let semilinear = SemilinearSet::atom("x".to_string());
+
+        let options = crate::options::AnalysisOptions {
+            check_backend: CheckBackend::Fallback,
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            let transfer = |from_var, to_var| {
+                ns_invariant
+                    .check_transfer_preserves(&one_token, from_var, to_var, &one_token)
+                    .unwrap()
+            };
+            assert!(transfer(Some(&a), &b));
+            assert!(!transfer(None, &a));
+
+            let implies = |n| {
+                ns_invariant
+                    .invariant_implies_semilinear(&x_equals(n), &semilinear, &"G1".to_string())
+                    .unwrap()
+            };
+            assert!(implies(1));
+            assert!(!implies(2));
+        });
+    }
+
     #[test]
     fn test_invariant_implies_semilinear_empty_invariant() {
         use crate::kleene::Kleene;
@@ -1410,6 +2881,86 @@ mod tests {
         assert!(!result.unwrap()); // ∃n. a = 2n + 1 (odd) is NOT in (aa)* (even)
     }
 
+    #[test]
+    fn test_initial_state_counts_initial_marking() {
+        // One request is in flight at L1 when the system starts
+        let mut ns = NS::<String, String, String, String>::new("G1".to_string());
+        ns.add_request("req1".to_string(), "L1".to_string());
+        ns.add_response("L1".to_string(), "resp1".to_string());
+        ns.initial_requests
+            .push(("req1".to_string(), "L1".to_string()));
+
+        let var = RequestStatePair(
+            "req1".to_string(),
+            RequestState::<String, String>::InFlight("L1".to_string()),
+        );
+        let in_flight = |count: i64| {
+            let mut global_invariants = HashMap::default();
+            let formula =
+                Formula::Constraint(Constraint::eq(AffineExpr::from_var(var.clone()), count));
+            global_invariants.insert(
+                "G1".to_string(),
+                ProofInvariant::new(vec![var.clone()], formula),
+            );
+            NSInvariant { global_invariants }
+        };
+
+        assert!(in_flight(1).check_initial_state(&ns).is_ok());
+        assert!(in_flight(0).check_initial_state(&ns).is_err());
+    }
+
+    #[test]
+    fn test_default_invariants() {
+        // G3 is only entered from local state L9, which no request reaches
+        let mut ns = NS::<String, String, String, String>::new("G1".to_string());
+        ns.add_request("req1".to_string(), "L1".to_string());
+        ns.add_response("L2".to_string(), "resp1".to_string());
+        for (from_local, from_global, to_local, to_global) in
+            [("L1", "G1", "L2", "G2"), ("L9", "G2", "L9", "G3")]
+        {
+            ns.transitions.push((
+                from_local.to_string(),
+                from_global.to_string(),
+                to_local.to_string(),
+                to_global.to_string(),
+            ));
+        }
+        let invariant = |globals: &[&str]| {
+            let global_invariants = globals
+                .iter()
+                .map(|global| {
+                    (
+                        global.to_string(),
+                        ProofInvariant::new(vec![], Formula::And(vec![])),
+                    )
+                })
+                .collect();
+            NSInvariant::<String, String, String, String> { global_invariants }
+        };
+
+        let partial = invariant(&["G1", "G2"]);
+        let completed = partial.with_default_invariants(&ns).unwrap();
+        assert_eq!(
+            completed.global_invariants["G3"].formula,
+            Formula::Or(vec![])
+        );
+
+        let err = invariant(&["G1"]).with_default_invariants(&ns).unwrap_err();
+        assert!(err.contains("G2 (reachable: G1 -> G2)"), "{}", err);
+        assert!(!err.contains("G3"), "{}", err);
+
+        let options = crate::options::AnalysisOptions {
+            missing_invariants: MissingInvariants::Reject,
+            ..crate::options::AnalysisOptions::default()
+        };
+        crate::options::with_options(options, || {
+            let err = invariant(&["G1", "G2"])
+                .with_default_invariants(&ns)
+                .unwrap_err();
+            assert!(err.contains("G3 (unreachable"), "{}", err);
+        });
+    }
+
     #[test]
     fn test_ns_decision_serialization_serializable() {
         use tempfile::NamedTempFile;
@@ -1507,17 +3058,105 @@ mod tests {
             _ => panic!("Expected NotSerializable decision"),
         }
     }
+
+    #[test]
+    fn test_trace_detail() {
+        assert_eq!(TraceDetail::from_name("source"), Some(TraceDetail::Source));
+        assert_eq!(TraceDetail::from_name("smpt"), None);
+
+        // Every step is one transition of the Petri net
+        let step: NSStep<&str, &str, &str, i64> = NSStep::InternalStep {
+            request: "inc",
+            from_local: "start",
+            from_global: "0",
+            to_local: "done",
+            to_global: "1",
+        };
+        let (inputs, outputs) = step.petri_transition();
+        assert_eq!(
+            inputs,
+            vec![
+                ReqPetriState::Local("inc", "start"),
+                ReqPetriState::Global("0")
+            ]
+        );
+        assert_eq!(
+            outputs,
+            vec![
+                ReqPetriState::Local("inc", "done"),
+                ReqPetriState::Global("1")
+            ]
+        );
+        let complete: NSStep<&str, &str, &str, i64> = NSStep::RequestComplete {
+            request: "inc",
+            final_local: "done",
+            response: 1,
+        };
+        assert_eq!(
+            complete.petri_transition(),
+            (
+                vec![ReqPetriState::Local("inc", "done")],
+                vec![ReqPetriState::Response("inc", 1)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_ns_invariant_to_latex() {
+        assert_eq!(latex_escape("a_b & {c}%"), "a\\_b \\& \\{c\\}\\%");
+
+        // 2 * #inc/ok - #inc@busy_1 >= 1 in global state G_0
+        let pair = |state| RequestStatePair("inc".to_string(), state);
+        let done = pair(RequestState::Completed("ok".to_string()));
+        let busy = pair(RequestState::InFlight("busy_1".to_string()));
+        let expr = AffineExpr::from_var(done.clone())
+            .mul_by_const(2)
+            .sub(&AffineExpr::from_var(busy.clone()))
+            .sub(&AffineExpr::from_const(1));
+        let mut global_invariants = HashMap::default();
+        global_invariants.insert(
+            "G_0".to_string(),
+            ProofInvariant {
+                variables: vec![done, busy],
+                formula: Formula::And(vec![Formula::Constraint(Constraint::new(
+                    expr,
+                    CompOp::Geq,
+                ))]),
+            },
+        );
+        let invariant: NSInvariant<String, String, String, String> =
+            NSInvariant { global_invariants };
+
+        let latex = invariant.to_latex();
+        assert!(latex.contains("\\texttt{G\\_0}"));
+        assert!(latex.contains(
+            "\\left(2 \\cdot \\#\\text{inc}/\\text{ok} \\geq \\#\\text{inc}@\\text{busy\\_1} + 1\\right)"
+        ));
+        // In the projection, in-flight requests count as 0
+        assert!(latex.contains("\\left(2 \\cdot \\#\\text{inc}/\\text{ok} \\geq 1\\right)"));
+
+        let document = invariant.to_latex_document("counter_1");
+        assert!(document.starts_with("\\documentclass{article}"));
+        assert!(document.contains("\\texttt{counter\\_1}"));
+        assert!(document.ends_with("\\end{document}\n"));
+    }
 }
 
 /// Check if a formula with no free variables is satisfied
 /// This is used after substituting all variables with concrete values
-fn is_formula_satisfied_string(formula: &Formula<String>) -> bool {
-    // Convert the formula to a PresburgerSet
-    // Since all variables are substituted, we have an empty mapping
-    let presburger = formula_to_presburger(formula, &[]);
-
-    // A formula is satisfied if the corresponding PresburgerSet is non-empty
-    !presburger.is_empty()
+fn is_formula_satisfied_string(formula: &Formula<String>) -> Result<bool, String> {
+    match get_check_backend() {
+        #[cfg(feature = "isl")]
+        CheckBackend::Isl => {
+            // Convert the formula to a PresburgerSet
+            // Since all variables are substituted, we have an empty mapping
+            let presburger = formula_to_presburger(formula, &[]);
+
+            // A formula is satisfied if the corresponding PresburgerSet is non-empty
+            Ok(!presburger.is_empty())
+        }
+        CheckBackend::Fallback => crate::presburger_fallback::is_satisfiable(formula),
+    }
 }
 
     #[test]
@@ -53,45 +53,17 @@ where
     Req: Clone + Eq + Hash + std::fmt::Display,
     Resp: Clone + Eq + Hash + std::fmt::Display,
 {
-    // Create a new Petri net with initial marking
-    // Start with one token for the initial global state
-    let initial_marking = vec![PetriState::Global(ns.initial_global.clone())];
-    let mut petri = Petri::new(initial_marking);
-
-    // Create transitions for each request transition
-    for (req, local) in &ns.requests {
-        petri.add_transition(
-            vec![PetriState::Request(req.clone())],
-            vec![PetriState::Local(local.clone())],
-        );
-    }
-
-    // Create transitions for each response transition
-    for (local, resp) in &ns.responses {
-        petri.add_transition(
-            vec![PetriState::Local(local.clone())],
-            vec![PetriState::Response(resp.clone())],
-        );
-    }
-
-    // Create transitions for each state transition (l, g) -> (l', g')
-    for (from_local, from_global, to_local, to_global) in &ns.transitions {
-        petri.add_transition(
-            vec![
-                PetriState::Local(from_local.clone()),
-                PetriState::Global(from_global.clone()),
-            ],
-            vec![
-                PetriState::Local(to_local.clone()),
-                PetriState::Global(to_global.clone()),
-            ],
-        );
-    }
-
-    petri
+    ns_to_petri_with_options(ns, PetriOptions::plain()).rename(|place| match place {
+        NSPlace::Local { local, .. } => PetriState::Local(local),
+        NSPlace::Global(g) => PetriState::Global(g),
+        NSPlace::Request(req) => PetriState::Request(req),
+        NSPlace::Response { resp, .. } => PetriState::Response(resp),
+        NSPlace::Fresh(..) => unreachable!("the plain Petri net has no request instances"),
+    })
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+/// Serialized as a stable place identifier, see `place_id`
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd)]
 pub enum ReqPetriState<L, G, Req, Resp> {
     Local(Req, L),
     Global(G),
@@ -138,44 +110,234 @@ where
     Req: Clone + PartialEq + Eq + Hash + std::fmt::Display,
     Resp: Clone + PartialEq + Eq + Hash + std::fmt::Display,
 {
-    // Create a new Petri net with initial marking
-    // Start with one token for the initial global state
-    let initial_marking = vec![ReqPetriState::Global(ns.initial_global.clone())];
+    ns_to_petri_with_options(ns, PetriOptions::with_requests()).rename(|place| match place {
+        NSPlace::Local {
+            req: Some(req),
+            local,
+            ..
+        } => ReqPetriState::Local(req, local),
+        NSPlace::Global(g) => ReqPetriState::Global(g),
+        NSPlace::Request(req) => ReqPetriState::Request(req),
+        NSPlace::Response {
+            req: Some(req),
+            resp,
+            ..
+        } => ReqPetriState::Response(req, resp),
+        _ => unreachable!("the Petri net with requests tracks the request of every token"),
+    })
+}
+
+/// What the places of the Petri net of an NS keep track of. `ns_to_petri` and
+/// `ns_to_petri_with_requests` are the two configurations the analysis uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PetriOptions {
+    /// Keep a copy of the local places for each request, so that a token remembers
+    /// which request it is serving
+    pub track_requests: bool,
+    /// Keep a copy of the response places for each request (needs `track_requests`)
+    pub track_responses: bool,
+    /// Start a request by taking a token from its request place, instead of at any time
+    pub request_places: bool,
+    /// Give each request a fixed number of numbered instances, and keep a copy of the
+    /// local and response places for each instance. Each instance runs at most once.
+    pub instances: Option<usize>,
+}
+
+impl PetriOptions {
+    /// Options of `ns_to_petri`
+    pub fn plain() -> Self {
+        PetriOptions {
+            request_places: true,
+            ..Default::default()
+        }
+    }
+
+    /// Options of `ns_to_petri_with_requests`
+    pub fn with_requests() -> Self {
+        PetriOptions {
+            track_requests: true,
+            track_responses: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Place of the Petri net built with `PetriOptions`. Untracked requests and instances
+/// are `None`. The names of places agree with those of `PetriState` and
+/// `ReqPetriState` in the corresponding configurations.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Ord, PartialOrd)]
+pub enum NSPlace<L, G, Req, Resp> {
+    Local {
+        req: Option<Req>,
+        instance: Option<usize>,
+        local: L,
+    },
+    Global(G),
+    Request(Req),
+    /// An instance of a request that has not started yet
+    Fresh(Req, usize),
+    Response {
+        req: Option<Req>,
+        instance: Option<usize>,
+        resp: Resp,
+    },
+}
+
+fn owner_suffix<Req: std::fmt::Display>(req: &Option<Req>, instance: &Option<usize>) -> String {
+    let mut suffix = String::new();
+    if let Some(req) = req {
+        suffix.push_str(&format!("_REQ_{}", req));
+    }
+    if let Some(instance) = instance {
+        suffix.push_str(&format!("_ID_{}", instance));
+    }
+    suffix
+}
+
+impl<L, G, Req, Resp> std::fmt::Display for NSPlace<L, G, Req, Resp>
+where
+    L: std::fmt::Display,
+    G: std::fmt::Display,
+    Req: std::fmt::Display,
+    Resp: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let raw = match self {
+            NSPlace::Local {
+                req,
+                instance,
+                local,
+            } => format!("L_{}{}", local, owner_suffix(req, instance)),
+            NSPlace::Global(g) => format!("G_{}", g),
+            NSPlace::Request(req) => format!("REQ_{}", req),
+            NSPlace::Fresh(req, instance) => format!("NEW_REQ_{}_ID_{}", req, instance),
+            NSPlace::Response {
+                req,
+                instance,
+                resp,
+            } => format!("RESP_{}{}", resp, owner_suffix(req, instance)),
+        };
+        write!(f, "{}", escape_for_graphviz_id(&raw))
+    }
+}
 
-    // Create a new Petri net with initial marking
+/// Convert an NS to a Petri net. The places are the local states, global states,
+/// requests and responses, with copies for requests and instances as the options ask.
+/// Each transition (l,g) -> (l',g') becomes a transition of the Petri net, and so do
+/// the request transitions req -> l and the response transitions l -> resp.
+pub fn ns_to_petri_with_options<L, G, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    options: PetriOptions,
+) -> Petri<NSPlace<L, G, Req, Resp>>
+where
+    L: Clone + Eq + Hash + std::fmt::Display,
+    G: Clone + Eq + Hash + std::fmt::Display,
+    Req: Clone + Eq + Hash + std::fmt::Display,
+    Resp: Clone + Eq + Hash + std::fmt::Display,
+{
+    // Numbered instances carry their request
+    let track_requests = options.track_requests || options.instances.is_some();
+    let track_responses = track_requests && (options.track_responses || options.instances.is_some());
+    // Requests in flight initially take the first instances of their request
+    let initial_count = |req: &Req| ns.initial_requests.iter().filter(|(r, _)| r == req).count();
+    let instances: Vec<Option<usize>> = match options.instances {
+        Some(count) => {
+            let needed = ns
+                .initial_requests
+                .iter()
+                .map(|(req, _)| initial_count(req))
+                .max()
+                .unwrap_or(0);
+            (0..count.max(needed)).map(Some).collect()
+        }
+        None => vec![None],
+    };
+
+    // The (request, instance) pairs that local places are copied for
+    let owners: Vec<(Option<Req>, Option<usize>)> = if track_requests {
+        ns.get_requests()
+            .into_iter()
+            .flat_map(|req| instances.iter().map(move |instance| (Some(req.clone()), *instance)))
+            .collect()
+    } else {
+        vec![(None, None)]
+    };
+    let local = |(req, instance): &(Option<Req>, Option<usize>), local: &L| NSPlace::Local {
+        req: req.clone(),
+        instance: *instance,
+        local: local.clone(),
+    };
+
+    // Start with one token for the initial global state, one for each instance that
+    // has not started, and one for each request in flight
+    let mut initial_marking = vec![NSPlace::Global(ns.initial_global.clone())];
+    if options.instances.is_some() {
+        for (req, instance) in &owners {
+            if let (Some(req), Some(instance)) = (req, instance) {
+                if *instance >= initial_count(req) {
+                    initial_marking.push(NSPlace::Fresh(req.clone(), *instance));
+                }
+            }
+        }
+    }
+    let mut started: Vec<&Req> = Vec::new();
+    for (req, start) in &ns.initial_requests {
+        let owner = if track_requests {
+            let instance = started.iter().filter(|r| *r == &req).count();
+            started.push(req);
+            (Some(req.clone()), options.instances.map(|_| instance))
+        } else {
+            (None, None)
+        };
+        initial_marking.push(local(&owner, start));
+    }
     let mut petri = Petri::new(initial_marking);
 
     // Create transitions for each request transition
-    for (req, local) in &ns.requests {
-        petri.add_transition(
-            // vec![ReqPetriState::Request(req.clone())],
-            vec![],
-            vec![ReqPetriState::Local(req.clone(), local.clone())],
-        );
+    for (req, start) in &ns.requests {
+        for instance in &instances {
+            let mut input = Vec::new();
+            if options.request_places {
+                input.push(NSPlace::Request(req.clone()));
+            }
+            if let Some(instance) = instance {
+                input.push(NSPlace::Fresh(req.clone(), *instance));
+            }
+            let owner = if track_requests {
+                (Some(req.clone()), *instance)
+            } else {
+                (None, None)
+            };
+            petri.add_transition(input, vec![local(&owner, start)]);
+        }
     }
 
     // Create transitions for each response transition
-    for req in ns.get_requests() {
-        for (local, resp) in &ns.responses {
-            petri.add_transition(
-                vec![ReqPetriState::Local(req.clone(), local.clone())],
-                vec![ReqPetriState::Response(req.clone(), resp.clone())],
-            );
+    for owner in &owners {
+        for (from, resp) in &ns.responses {
+            let response = if track_responses {
+                NSPlace::Response {
+                    req: owner.0.clone(),
+                    instance: owner.1,
+                    resp: resp.clone(),
+                }
+            } else {
+                NSPlace::Response {
+                    req: None,
+                    instance: None,
+                    resp: resp.clone(),
+                }
+            };
+            petri.add_transition(vec![local(owner, from)], vec![response]);
         }
     }
 
     // Create transitions for each state transition (l, g) -> (l', g')
-    for req in ns.get_requests() {
+    for owner in &owners {
         for (from_local, from_global, to_local, to_global) in &ns.transitions {
             petri.add_transition(
-                vec![
-                    ReqPetriState::Local(req.clone(), from_local.clone()),
-                    ReqPetriState::Global(from_global.clone()),
-                ],
-                vec![
-                    ReqPetriState::Local(req.clone(), to_local.clone()),
-                    ReqPetriState::Global(to_global.clone()),
-                ],
+                vec![local(owner, from_local), NSPlace::Global(from_global.clone())],
+                vec![local(owner, to_local), NSPlace::Global(to_global.clone())],
             );
         }
     }
@@ -231,4 +393,45 @@ mod tests {
         // Verify transitions count (one for request, one for response, one for state transition)
         assert_eq!(petri.get_transitions().len(), 3);
     }
+
+    #[test]
+    fn test_petri_options() {
+        let mut ns = NS::<String, String, String, String>::new("G0".to_string());
+        ns.add_request("inc".to_string(), "start".to_string());
+        ns.add_request("get".to_string(), "start".to_string());
+        ns.add_response("start".to_string(), "ok".to_string());
+        ns.add_transition(
+            "start".to_string(),
+            "G0".to_string(),
+            "start".to_string(),
+            "G1".to_string(),
+        );
+
+        // The wrappers name places as the general construction does
+        let names = |places: Vec<String>| places.into_iter().collect::<HashSet<_>>();
+        let with_requests = ns_to_petri_with_requests(&ns).rename(|p| p.to_string());
+        let general = ns_to_petri_with_options(&ns, PetriOptions::with_requests())
+            .rename(|p| p.to_string());
+        assert_eq!(names(with_requests.get_places()), names(general.get_places()));
+        assert!(names(general.get_places()).contains("RESP_ok_REQ_inc"));
+        let plain = ns_to_petri(&ns).rename(|p| p.to_string());
+        let general = ns_to_petri_with_options(&ns, PetriOptions::plain()).rename(|p| p.to_string());
+        assert_eq!(names(plain.get_places()), names(general.get_places()));
+
+        // Two numbered instances of each request
+        let options = PetriOptions {
+            instances: Some(2),
+            ..Default::default()
+        };
+        let petri = ns_to_petri_with_options(&ns, options);
+        assert_eq!(petri.get_initial_marking().len(), 1 + 2 * 2);
+        assert!(
+            petri
+                .get_places()
+                .iter()
+                .any(|p| p.to_string() == "L_start_REQ_inc_ID_1")
+        );
+        // Requests, responses and state transitions for each of the four instances
+        assert_eq!(petri.get_transitions().len(), 4 + 4 + 4);
+    }
 }
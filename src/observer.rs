@@ -0,0 +1,123 @@
+//! Callbacks for embedders.
+//!
+//! Tools that run analyses in-process, like IDE plugins or web services, register an
+//! `AnalysisObserver` to follow an analysis while it runs instead of parsing its
+//! output. The pipeline notifies every registered observer when a stage starts, when
+//! the Petri net of the serializability problem is built, when a complement disjunct
+//! is decided and when a certificate is written. All callbacks do nothing by default,
+//! so an observer only implements the ones it needs.
+//!
+//! Observers are called on the thread that runs the analysis and should return
+//! quickly; an observer that does more should hand the event to a thread of its own.
+
+use crate::initial_states::Verdict;
+use crate::reachability_with_proofs::DisjunctResult;
+use std::sync::{Arc, Mutex};
+
+/// The Petri net of a serializability problem, after it is built and before any
+/// query about it is made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PetriBuilt {
+    pub places: usize,
+    pub transitions: usize,
+    /// The net in the `.net` format that SMPT reads
+    pub net: String,
+}
+
+/// A certificate written to disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateWritten {
+    pub path: String,
+    pub verdict: Verdict,
+}
+
+pub trait AnalysisObserver: Send + Sync {
+    /// A stage of the analysis starts (`petri_net`, `serialized_automaton`,
+    /// `reachability`, ..., the stage names of `stats::time_stage`)
+    fn on_stage_start(&self, _stage: &str) {}
+
+    fn on_petri_built(&self, _petri: &PetriBuilt) {}
+
+    /// A complement disjunct was decided: unreachable disjuncts bring the analysis
+    /// closer to a proof, and a reachable one ends it with a counterexample
+    fn on_disjunct_result(&self, _result: &DisjunctResult) {}
+
+    fn on_certificate(&self, _certificate: &CertificateWritten) {}
+}
+
+lazy_static::lazy_static! {
+    static ref OBSERVERS: Mutex<Vec<Arc<dyn AnalysisObserver>>> = Mutex::new(Vec::new());
+}
+
+/// Notify `observer` of the events of all analyses from now on
+pub fn register(observer: Arc<dyn AnalysisObserver>) {
+    OBSERVERS.lock().unwrap().push(observer);
+}
+
+/// Stop notifying `observer`
+pub fn unregister(observer: &Arc<dyn AnalysisObserver>) {
+    OBSERVERS
+        .lock()
+        .unwrap()
+        .retain(|registered| !Arc::ptr_eq(registered, observer));
+}
+
+/// Whether any observer is registered. Events that are costly to build, like the
+/// `.net` text of a Petri net, are only built if so.
+pub fn is_observed() -> bool {
+    !OBSERVERS.lock().unwrap().is_empty()
+}
+
+/// Call `f` on every registered observer. The observers are called without holding
+/// the lock, so an observer may register or unregister observers itself.
+pub fn notify(f: impl Fn(&dyn AnalysisObserver)) {
+    let observers = OBSERVERS.lock().unwrap().clone();
+    for observer in observers {
+        f(observer.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the events it sees
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl AnalysisObserver for Recorder {
+        fn on_stage_start(&self, stage: &str) {
+            self.0.lock().unwrap().push(format!("stage {}", stage));
+        }
+
+        fn on_certificate(&self, certificate: &CertificateWritten) {
+            let event = format!("certificate {:?} {}", certificate.verdict, certificate.path);
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let recorder = Arc::new(Recorder::default());
+        let observer: Arc<dyn AnalysisObserver> = recorder.clone();
+        register(observer.clone());
+        assert!(is_observed());
+
+        crate::stats::time_stage("observer_test", || ());
+        let decision: crate::ns_decision::NSDecision<String, String, String, String> =
+            crate::ns_decision::NSDecision::Timeout {
+                message: "test".to_string(),
+            };
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_string_lossy().to_string();
+        decision.save_to_file(&path).unwrap();
+        unregister(&observer);
+        crate::stats::time_stage("observer_test_after", || ());
+
+        // Other tests may run at the same time, so only look for our events
+        let events = recorder.0.lock().unwrap().clone();
+        assert!(events.contains(&"stage observer_test".to_string()));
+        assert!(events.contains(&format!("certificate Timeout {}", path)));
+        assert!(!events.contains(&"stage observer_test_after".to_string()));
+    }
+}
@@ -0,0 +1,300 @@
+//! Settings of an analysis.
+//!
+//! All settings that change what an analysis does or writes live in one
+//! `AnalysisOptions` value. The process has default options, which the command line
+//! sets through the `set_*` functions of the modules (`graphviz::set_viz_enabled`,
+//! `smpt::set_smpt_timeout`, ...). Code that runs analyses itself, like tests or a
+//! driver that compares configurations, can instead give an analysis its own options
+//! with `with_options`. These only affect the current thread, so several analyses with
+//! different settings can run in parallel.
+//!
+//! The modules read their settings with `get` and change them with `update`, which
+//! act on the options of the enclosing `with_options` if there is one, and on the
+//! process defaults otherwise.
+
+use crate::dim_order::DimOrder;
+use crate::initial_states::InitialStates;
+use crate::kleene::KleeneOrder;
+use crate::ns::DuplicateResponses;
+use crate::ns_decision::{
+    CertificateCompression, CheckBackend, InductivenessCheck, MissingInvariants, TraceDetail,
+};
+use crate::reachability::DisjunctOrder;
+use crate::semilinear::{SemilinearFormat, SemilinearStrategy};
+use crate::size_logger::SizeThreshold;
+use crate::smpt_offline::OfflineMode;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisOptions {
+    // Input
+    /// Language version that overrides the `#lang` headers of `.ser` files
+    pub lang_version: Option<u32>,
+    /// Simplify request bodies before converting them to an NS
+    pub simplify_exprs: bool,
+    /// Add a request that crashes the server, resetting the globals (`--model-crashes`)
+    pub model_crashes: bool,
+
+    // Analysis
+    /// Leave requests that never touch the global state out of the analysis
+    pub read_only_fast_path: bool,
+    /// Try the abstract interpretation pre-pass first
+    pub absint: bool,
+    /// Random simulations before the analysis
+    pub simulation_runs: usize,
+    /// Guided simulations before the analysis
+    pub guided_simulation_runs: usize,
+    /// Look for a shortest counterexample of at most this many steps first
+    /// (`--bounded-search`)
+    pub bounded_search_depth: Option<usize>,
+    /// Most markings the bounded search visits (`--bounded-search-states`)
+    pub bounded_search_states: usize,
+    pub semilinear_strategy: SemilinearStrategy,
+    /// Remove redundant components and periods of semilinear sets
+    pub remove_redundant: bool,
+    pub kleene_order: KleeneOrder,
+    pub dim_order: DimOrder,
+    pub bidirectional_pruning: bool,
+    pub disjunct_order: DisjunctOrder,
+    /// Run HiGHS on the marking equation before calling SMPT
+    pub lp_prefilter: bool,
+    pub size_thresholds: Vec<SizeThreshold>,
+    /// Only estimate the size of the problem
+    pub dry_run: bool,
+    /// Reuse the disjunct results of an interrupted run
+    pub resume: bool,
+    /// Repeat the analysis from these initial global states
+    pub initial_states: Option<InitialStates>,
+    /// How often a completed (request, response) pair counts
+    pub duplicate_responses: DuplicateResponses,
+    /// Diagnose non-serializable systems
+    pub diagnose: bool,
+    /// Look for a counterexample in every disjunct of non-serializable systems
+    pub all_counterexamples: bool,
+    /// How counterexample traces are shown
+    pub trace_detail: TraceDetail,
+    /// Report the serializable behaviors of non-serializable systems
+    pub serializable_subset: bool,
+    /// Search for a fix of non-serializable programs
+    pub suggest_fix: bool,
+    /// Check serializability certificates with SMPT as well
+    pub cross_validate: bool,
+    pub inductiveness_check: InductivenessCheck,
+    pub check_backend: CheckBackend,
+    /// What certificate checks do with global states without an invariant
+    pub missing_invariants: MissingInvariants,
+
+    // SMPT
+    /// Timeout per SMPT query in seconds, 0 for SMPT's default
+    pub smpt_timeout: u64,
+    /// Global time budget in seconds that SMPT timeouts are allocated from per disjunct
+    pub auto_timeout: Option<u64>,
+    pub smpt_cache: bool,
+    pub smpt_offline: OfflineMode,
+    /// Answer SMPT queries with a fake backend (`--fake-smpt`, for tests)
+    #[cfg(any(test, feature = "fake-smpt"))]
+    pub fake_smpt: Option<crate::smpt_fake::FakeSmpt>,
+
+    // Output
+    /// Generate GraphViz visualizations
+    pub viz: bool,
+    /// Emit the serialized automaton as its own DOT graph
+    pub serialized_automaton_dot: bool,
+    pub explain: bool,
+    pub witness: bool,
+    pub commutativity: bool,
+    /// Show the response counts of serial executions (`--quantitative`)
+    pub quantitative: bool,
+    /// Check that the requests in flight can always complete (`--check quiescence`)
+    pub check_quiescence: bool,
+    pub latex_export: bool,
+    /// Also write the semilinear set as a LaTeX or Markdown table (`--semilinear-format`)
+    pub semilinear_format: SemilinearFormat,
+    pub export_marking_equation: bool,
+    pub certificate_compression: CertificateCompression,
+    /// Maximum size in bytes of a certificate file
+    pub max_certificate_size: Option<u64>,
+    /// File that every ISL operation is traced to
+    pub isl_trace: Option<String>,
+}
+
+impl AnalysisOptions {
+    /// The settings of a run without command-line flags
+    pub const DEFAULT: AnalysisOptions = AnalysisOptions {
+        lang_version: None,
+        simplify_exprs: true,
+        model_crashes: false,
+        read_only_fast_path: true,
+        absint: false,
+        simulation_runs: 0,
+        guided_simulation_runs: 0,
+        bounded_search_depth: None,
+        bounded_search_states: 1_000_000,
+        semilinear_strategy: SemilinearStrategy::GenerateLess,
+        remove_redundant: true,
+        kleene_order: KleeneOrder::Degree,
+        dim_order: DimOrder::Sorted,
+        bidirectional_pruning: true,
+        disjunct_order: DisjunctOrder::AsGiven,
+        lp_prefilter: false,
+        size_thresholds: Vec::new(),
+        dry_run: false,
+        resume: false,
+        initial_states: None,
+        duplicate_responses: DuplicateResponses::Multiset,
+        diagnose: false,
+        all_counterexamples: false,
+        trace_detail: TraceDetail::Ns,
+        serializable_subset: false,
+        suggest_fix: false,
+        cross_validate: false,
+        inductiveness_check: InductivenessCheck::TransitionRelation,
+        check_backend: CheckBackend::DEFAULT,
+        missing_invariants: MissingInvariants::Unreachable,
+        smpt_timeout: 10,
+        auto_timeout: None,
+        smpt_cache: false,
+        smpt_offline: OfflineMode::Off,
+        #[cfg(any(test, feature = "fake-smpt"))]
+        fake_smpt: None,
+        viz: true,
+        serialized_automaton_dot: false,
+        explain: false,
+        witness: false,
+        commutativity: false,
+        quantitative: false,
+        check_quiescence: false,
+        latex_export: false,
+        semilinear_format: SemilinearFormat::Text,
+        export_marking_equation: false,
+        certificate_compression: CertificateCompression::None,
+        max_certificate_size: None,
+        isl_trace: None,
+    };
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Options of the process, used outside of `with_options`
+    static ref PROCESS_OPTIONS: Mutex<Arc<AnalysisOptions>> = Mutex::new(Arc::new(AnalysisOptions::DEFAULT));
+}
+
+thread_local! {
+    /// Options of the innermost `with_options` on this thread
+    static SCOPED_OPTIONS: RefCell<Option<Arc<AnalysisOptions>>> = const { RefCell::new(None) };
+}
+
+/// The options in effect on this thread, shared rather than copied
+fn shared() -> Arc<AnalysisOptions> {
+    let scoped = SCOPED_OPTIONS.with(|scoped| scoped.borrow().clone());
+    scoped.unwrap_or_else(|| PROCESS_OPTIONS.lock().unwrap().clone())
+}
+
+/// Read the options in effect on this thread. No lock is held while `f` runs, so it
+/// may itself read or change the options (changes don't affect what `f` sees).
+pub fn get<R>(f: impl FnOnce(&AnalysisOptions) -> R) -> R {
+    f(&shared())
+}
+
+/// Change the options in effect on this thread. The process options stay locked while
+/// `f` runs, so that concurrent changes are not lost; `f` must not use the options
+/// itself.
+pub fn update(f: impl FnOnce(&mut AnalysisOptions)) {
+    let scoped = SCOPED_OPTIONS.with(|scoped| scoped.borrow().clone());
+    match scoped {
+        Some(mut options) => {
+            f(Arc::make_mut(&mut options));
+            SCOPED_OPTIONS.with(|scoped| *scoped.borrow_mut() = Some(options));
+        }
+        None => f(Arc::make_mut(&mut PROCESS_OPTIONS.lock().unwrap())),
+    }
+}
+
+/// A copy of the options in effect on this thread
+pub fn current() -> AnalysisOptions {
+    AnalysisOptions::clone(&shared())
+}
+
+/// Replace the options of the process
+pub fn set_process_options(options: AnalysisOptions) {
+    *PROCESS_OPTIONS.lock().unwrap() = Arc::new(options);
+}
+
+/// Run `f` with `options` on this thread. Changes that `f` makes to the options are
+/// dropped when it returns.
+pub fn with_options<R>(options: AnalysisOptions, f: impl FnOnce() -> R) -> R {
+    /// Restores the enclosing options, also when `f` panics
+    struct Restore(Option<Arc<AnalysisOptions>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let enclosing = self.0.take();
+            SCOPED_OPTIONS.with(|scoped| *scoped.borrow_mut() = enclosing);
+        }
+    }
+
+    let enclosing = SCOPED_OPTIONS.with(|scoped| scoped.borrow_mut().replace(Arc::new(options)));
+    let _restore = Restore(enclosing);
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_options() {
+        let process_timeout = get(|options| options.smpt_timeout);
+        let options = AnalysisOptions {
+            smpt_timeout: 77,
+            ..AnalysisOptions::default()
+        };
+        with_options(options, || {
+            assert_eq!(crate::smpt::get_smpt_timeout(), 77);
+            crate::smpt::set_smpt_timeout(78);
+            assert_eq!(crate::smpt::get_smpt_timeout(), 78);
+
+            let nested = AnalysisOptions {
+                smpt_timeout: 5,
+                ..AnalysisOptions::default()
+            };
+            with_options(nested, || assert_eq!(crate::smpt::get_smpt_timeout(), 5));
+            assert_eq!(crate::smpt::get_smpt_timeout(), 78);
+        });
+        // Neither the scoped options nor their changes leak out
+        assert_eq!(get(|options| options.smpt_timeout), process_timeout);
+
+        // Scoped options are per thread
+        let other = std::thread::spawn(|| {
+            with_options(
+                AnalysisOptions {
+                    explain: true,
+                    ..AnalysisOptions::default()
+                },
+                crate::explain::explain_enabled,
+            )
+        });
+        assert!(other.join().unwrap());
+    }
+
+    #[test]
+    fn test_reentrant() {
+        // `get` holds no lock while its closure runs, so the closure can use the options
+        let process_timeout = get(|_| get(|options| options.smpt_timeout));
+        assert_eq!(process_timeout, get(|options| options.smpt_timeout));
+        with_options(AnalysisOptions::default(), || {
+            get(|options| {
+                update(|options| options.explain = true);
+                // The closure keeps seeing the options it was called with
+                assert!(!options.explain);
+            });
+            assert!(get(|options| options.explain));
+        });
+    }
+}
@@ -1,5 +1,7 @@
+use crate::deterministic_map::HashMap;
 use hash_cons::{Hc, HcTable};
 use std::fmt;
+use std::ops::Range;
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -16,6 +18,7 @@ pub enum Expr {
     Or(#[serde(with = "hc_expr_serde")] Hc<Expr>, #[serde(with = "hc_expr_serde")] Hc<Expr>),
     Yield,
     Exit,
+    Return(#[serde(with = "hc_expr_serde")] Hc<Expr>),
     Unknown,
     Number(i64),
     Variable(String),
@@ -24,6 +27,37 @@ pub enum Expr {
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
 pub struct Program {
     pub requests: Vec<Request>,
+    /// Initial values of globals declared with `global X := n;` (undeclared globals start at 0)
+    #[serde(default)]
+    pub globals: Vec<(String, i64)>,
+    /// Multiplicity bounds declared with `bound req <= k;` or `bound req/resp <= k;`
+    #[serde(default)]
+    pub bounds: Vec<ResponseBound>,
+    /// Clients declared with `session name { r1; r2; ... }`
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    /// Requests declared with `idempotent request name { ... }`: completions of such a
+    /// request with the same response count once, however often they occur
+    #[serde(default)]
+    pub idempotent: Vec<String>,
+}
+
+/// A client that issues the given requests once each, in order, waiting for each
+/// response before the next request. Requests that occur in a session are only
+/// issued by their sessions.
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub name: String,
+    pub requests: Vec<String>,
+}
+
+/// A request (optionally only with a given response) may complete at most `max`
+/// times in a serializable execution
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct ResponseBound {
+    pub request: String,
+    pub response: Option<i64>,
+    pub max: usize,
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -31,6 +65,9 @@ pub struct Request {
     pub name: String,
     #[serde(with = "hc_expr_serde")]
     pub body: Hc<Expr>,
+    /// Documentation from the `///` comments before the declaration, one line per comment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
 }
 
 impl fmt::Display for Expr {
@@ -50,6 +87,7 @@ impl fmt::Display for Expr {
             Expr::Or(left, right) => write!(f, "{} || {}", left, right),
             Expr::Yield => write!(f, "yield"),
             Expr::Exit => write!(f, "exit"),
+            Expr::Return(expr) => write!(f, "return {}", expr),
             Expr::Unknown => write!(f, "?"),
             Expr::Number(n) => write!(f, "{}", n),
             Expr::Variable(var) => write!(f, "{}", var),
@@ -57,6 +95,109 @@ impl fmt::Display for Expr {
     }
 }
 
+/// How tightly the syntax of an expression binds, loosest first: sequences,
+/// assignments and returns, `||`, `&&`, `==`, `+` and `-`, `!`, and primaries
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Sequence(_, _) => 0,
+        Expr::Assign(_, _) | Expr::Return(_) => 1,
+        Expr::Or(_, _) => 2,
+        Expr::And(_, _) => 3,
+        Expr::Equal(_, _) => 4,
+        Expr::Add(_, _) | Expr::Subtract(_, _) => 5,
+        Expr::Not(_) => 6,
+        Expr::If(_, _, _)
+        | Expr::While(_, _)
+        | Expr::Yield
+        | Expr::Exit
+        | Expr::Unknown
+        | Expr::Number(_)
+        | Expr::Variable(_) => 7,
+    }
+}
+
+/// `expr` as source code that parses back to `expr`. Unlike `Display`, this puts
+/// parentheses where the grammar needs them.
+pub fn to_source(expr: &Expr) -> String {
+    source_at(expr, 0)
+}
+
+/// The source of `expr` where the grammar expects an expression of precedence `min`
+fn source_at(expr: &Expr, min: u8) -> String {
+    let source = match expr {
+        Expr::Sequence(a, b) => format!("{}; {}", source_at(a, 1), source_at(b, 0)),
+        Expr::Assign(var, e) => format!("{} := {}", var, source_at(e, 1)),
+        Expr::Return(e) => format!("return {}", source_at(e, 1)),
+        Expr::Or(a, b) => format!("{} || {}", source_at(a, 2), source_at(b, 3)),
+        Expr::And(a, b) => format!("{} && {}", source_at(a, 3), source_at(b, 4)),
+        Expr::Equal(a, b) => format!("{} == {}", source_at(a, 5), source_at(b, 5)),
+        Expr::Add(a, b) => format!("{} + {}", source_at(a, 5), source_at(b, 6)),
+        Expr::Subtract(a, b) => format!("{} - {}", source_at(a, 5), source_at(b, 6)),
+        Expr::Not(e) => format!("!{}", source_at(e, 6)),
+        Expr::If(c, t, e) => format!(
+            "if ({}) {{ {} }} else {{ {} }}",
+            source_at(c, 0),
+            source_at(t, 0),
+            source_at(e, 0)
+        ),
+        Expr::While(c, b) => format!("while ({}) {{ {} }}", source_at(c, 0), source_at(b, 0)),
+        // There are no negative literals, but `0 - n` is folded into one
+        Expr::Number(n) if *n < 0 => format!("(0 - {})", n.unsigned_abs()),
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {
+            expr.to_string()
+        }
+    };
+    if precedence(expr) < min {
+        format!("({})", source)
+    } else {
+        source
+    }
+}
+
+impl Program {
+    /// The program as the source of a `.ser` file, with a `#lang ser/<version>` header
+    /// if `version` is given
+    pub fn to_source(&self, version: Option<u32>) -> String {
+        let mut source = String::new();
+        if let Some(version) = version {
+            source.push_str(&format!("#lang ser/{}\n", version));
+        }
+        for (name, value) in &self.globals {
+            source.push_str(&format!("global {} := {};\n", name, value));
+        }
+        for request in &self.requests {
+            for line in request.doc.iter().flat_map(|doc| doc.lines()) {
+                source.push_str(&format!("/// {}\n", line));
+            }
+            if self.idempotent.contains(&request.name) {
+                source.push_str("idempotent ");
+            }
+            source.push_str(&format!(
+                "request {} {{\n    {}\n}}\n",
+                request.name,
+                to_source(&request.body)
+            ));
+        }
+        for bound in &self.bounds {
+            match bound.response {
+                Some(response) => source.push_str(&format!(
+                    "bound {}/{} <= {};\n",
+                    bound.request, response, bound.max
+                )),
+                None => source.push_str(&format!("bound {} <= {};\n", bound.request, bound.max)),
+            }
+        }
+        for session in &self.sessions {
+            source.push_str(&format!(
+                "session {} {{ {} }}\n",
+                session.name,
+                session.requests.join("; ")
+            ));
+        }
+        source
+    }
+}
+
 // Custom serialization module for Hc<Expr>
 pub mod hc_expr_serde {
     use super::*;
@@ -93,14 +234,73 @@ pub mod hc_expr_serde {
 // Now we need to tell serde to use our custom module for Hc<Expr> fields
 // We'll need to update the Expr enum to use this
 
+/// Where an expression is in the source: its first line, and the text of the
+/// expression on that line
+#[derive(Hash, Eq, PartialEq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} `{}`", self.line, self.text)
+    }
+}
+
 pub struct ExprHc {
     table: HcTable<Expr>,
+    /// Where the parsed statements are in the source (see `span`)
+    spans: HashMap<Hc<Expr>, Span>,
 }
 
 impl ExprHc {
     pub fn new() -> Self {
         Self {
             table: HcTable::new(),
+            spans: HashMap::default(),
+        }
+    }
+
+    /// Record that `expr` was parsed from `span`. Equal expressions are shared, so an
+    /// expression that occurs more than once keeps the span of its first occurrence.
+    /// Atoms such as `yield` or `0` occur everywhere and get no span.
+    pub fn set_span(&mut self, expr: &Hc<Expr>, span: Span) {
+        if !matches!(
+            expr.get(),
+            Expr::Yield | Expr::Unknown | Expr::Number(_) | Expr::Variable(_)
+        ) {
+            self.spans.entry(expr.clone()).or_insert(span);
+        }
+    }
+
+    /// Where `expr` was parsed from, if it was parsed with a source (see
+    /// `Parser::with_source`)
+    pub fn span(&self, expr: &Hc<Expr>) -> Option<&Span> {
+        self.spans.get(expr)
+    }
+
+    /// Where the execution of `expr` starts in the source: the span of `expr`, or else
+    /// of the part of `expr` that runs first. Expressions built during the execution,
+    /// like the rest of a request after a `yield`, are located by the parsed
+    /// expressions they contain.
+    pub fn location(&self, expr: &Hc<Expr>) -> Option<&Span> {
+        if let Some(span) = self.span(expr) {
+            return Some(span);
+        }
+        match expr.get() {
+            Expr::Assign(_, e)
+            | Expr::Not(e)
+            | Expr::Return(e)
+            | Expr::If(e, _, _)
+            | Expr::While(e, _) => self.location(e),
+            Expr::Equal(a, b)
+            | Expr::Add(a, b)
+            | Expr::Subtract(a, b)
+            | Expr::Sequence(a, b)
+            | Expr::And(a, b)
+            | Expr::Or(a, b) => self.location(a).or_else(|| self.location(b)),
+            Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => None,
         }
     }
     pub fn assign(&mut self, var: String, expr: Hc<Expr>) -> Hc<Expr> {
@@ -217,6 +417,10 @@ impl ExprHc {
         self.table.hashcons(Expr::Exit)
     }
 
+    pub fn return_expr(&mut self, expr: Hc<Expr>) -> Hc<Expr> {
+        self.table.hashcons(Expr::Return(expr))
+    }
+
     pub fn unknown(&mut self) -> Hc<Expr> {
         self.table.hashcons(Expr::Unknown)
     }
@@ -230,55 +434,318 @@ impl ExprHc {
     }
 }
 
+impl Default for ExprHc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Latest version of the `.ser` language. A program selects the version it is written
+/// in with a `#lang ser/<version>` header on its first line; programs without a header
+/// are version 1. New constructs are gated on the version (see `Feature`), so that an
+/// existing program keeps its meaning when the language grows.
+pub const LANG_VERSION: u32 = 2;
+
+/// Version of programs without a `#lang` header
+pub const DEFAULT_LANG_VERSION: u32 = 1;
+
+/// Parse all programs as `version`, whatever their header says (`--lang-version`)
+pub fn set_lang_version(version: Option<u32>) {
+    crate::options::update(|options| options.lang_version = version);
+}
+
+pub fn forced_lang_version() -> Option<u32> {
+    crate::options::get(|options| options.lang_version)
+}
+
+/// The version to parse a program as: the one forced by `--lang-version`, else the one
+/// of its header, else `DEFAULT_LANG_VERSION`
+pub fn effective_lang_version(header_version: Option<u32>) -> u32 {
+    forced_lang_version()
+        .or(header_version)
+        .unwrap_or(DEFAULT_LANG_VERSION)
+}
+
+/// Check that `version` is a version of the language this parser knows
+pub fn check_lang_version(version: u32) -> Result<u32, String> {
+    if (1..=LANG_VERSION).contains(&version) {
+        Ok(version)
+    } else {
+        Err(format!(
+            "Unsupported language version ser/{} (supported: ser/1 to ser/{})",
+            version, LANG_VERSION
+        ))
+    }
+}
+
+/// A construct of the language that is only available from some version on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `while (e) { e }`
+    Loops,
+    /// `exit`
+    Exit,
+    /// `!`, `&&` and `||`
+    BooleanOperators,
+    /// `global X := n;`
+    GlobalDeclarations,
+    /// `bound req <= k;`
+    Bounds,
+    /// `return e`
+    Return,
+    /// `session name { r1; r2; ... }`
+    Sessions,
+    /// `idempotent request name { ... }`
+    Idempotent,
+}
+
+impl Feature {
+    /// First version of the language with this construct
+    pub fn since(self) -> u32 {
+        match self {
+            Feature::Loops
+            | Feature::Exit
+            | Feature::BooleanOperators
+            | Feature::GlobalDeclarations
+            | Feature::Bounds => 1,
+            Feature::Return | Feature::Sessions | Feature::Idempotent => 2,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Feature::Loops => "'while' loops",
+            Feature::Exit => "'exit'",
+            Feature::BooleanOperators => "'!', '&&' and '||'",
+            Feature::GlobalDeclarations => "'global' declarations",
+            Feature::Bounds => "'bound' declarations",
+            Feature::Return => "'return' statements",
+            Feature::Sessions => "'session' declarations",
+            Feature::Idempotent => "'idempotent' annotations",
+        }
+    }
+}
+
+/// Split off the `#lang ser/<version>` header of a program, if it has one. The header
+/// line is replaced by an empty line, so the rest of the source keeps its layout.
+pub fn parse_lang_header(source: &str) -> Result<(Option<u32>, String), String> {
+    let first_line = source.lines().next().unwrap_or("");
+    let Some(header) = first_line.trim().strip_prefix("#lang") else {
+        return Ok((None, source.to_string()));
+    };
+    let header = header.trim();
+    let version = header.strip_prefix("ser/").ok_or_else(|| {
+        format!(
+            "Unknown language '{}' in #lang header, expected ser/<version>",
+            header
+        )
+    })?;
+    let version = version
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid language version '{}' in #lang header", version))?;
+    let version = check_lang_version(version)?;
+    Ok((Some(version), source[first_line.len()..].to_string()))
+}
+
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// Version of the language being parsed
+    version: u32,
+    /// The source of the tokens and the byte range of every token in it, if known
+    source: Option<(String, Vec<Range<usize>>)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Identifier(String),
     Number(i64),
-    Assign,    // :=
-    Equal,     // ==
-    Plus,      // +
-    Minus,     // -
-    Semicolon, // ;
-    If,        // if
-    Else,      // else
-    While,     // while
-    Yield,     // yield
-    Exit,      // exit
-    Question,  // ?
-    Request,   // request
-    Not,       // !
-    And,       // &&
-    Or,        // ||
-    LParen,    // (
-    RParen,    // )
-    LBrace,    // {
-    RBrace,    // }
+    Assign,     // :=
+    Equal,      // ==
+    Plus,       // +
+    Minus,      // -
+    Semicolon,  // ;
+    If,         // if
+    Else,       // else
+    While,      // while
+    Yield,      // yield
+    Exit,       // exit
+    Return,     // return
+    Question,   // ?
+    Request,    // request
+    Global,     // global
+    Bound,      // bound
+    Session,    // session
+    Idempotent, // idempotent
+    Slash,      // /
+    LessEqual,  // <=
+    Not,        // !
+    And,        // &&
+    Or,         // ||
+    LParen,     // (
+    RParen,     // )
+    LBrace,     // {
+    RBrace,     // }
+    /// `/// text`, only kept in front of request declarations
+    DocComment(String),
     Eof,
 }
 
-/// Parse a string directly into an expression
+/// Merge programs that model the services of one system into a single program.
+///
+/// Request names are namespaced as `<namespace>.<name>`, so requests with the same
+/// name in different services stay distinct. Globals are shared between all programs;
+/// declaring the same global with different initial values is an error.
+pub fn compose_programs(parts: Vec<(String, Program)>) -> Result<Program, String> {
+    let mut requests = Vec::new();
+    let mut globals: Vec<(String, i64, String)> = Vec::new();
+    let mut bounds = Vec::new();
+    let mut sessions = Vec::new();
+    let mut idempotent = Vec::new();
+
+    for (namespace, program) in parts {
+        for (name, value) in program.globals {
+            match globals.iter().find(|(g, _, _)| *g == name) {
+                Some((_, old, owner)) if *old != value => {
+                    return Err(format!(
+                        "Conflicting initializations of global {}: {} in {} and {} in {}",
+                        name, old, owner, value, namespace
+                    ));
+                }
+                Some(_) => {}
+                None => globals.push((name, value, namespace.clone())),
+            }
+        }
+        for request in program.requests {
+            requests.push(Request {
+                name: format!("{}.{}", namespace, request.name),
+                ..request
+            });
+        }
+        for bound in program.bounds {
+            bounds.push(ResponseBound {
+                request: format!("{}.{}", namespace, bound.request),
+                ..bound
+            });
+        }
+        for session in program.sessions {
+            sessions.push(Session {
+                name: format!("{}.{}", namespace, session.name),
+                requests: session
+                    .requests
+                    .iter()
+                    .map(|request| format!("{}.{}", namespace, request))
+                    .collect(),
+            });
+        }
+        for request in program.idempotent {
+            idempotent.push(format!("{}.{}", namespace, request));
+        }
+    }
+
+    if requests.is_empty() {
+        return Err("No requests found in composed programs".to_string());
+    }
+
+    Ok(Program {
+        requests,
+        globals: globals
+            .into_iter()
+            .map(|(name, value, _)| (name, value))
+            .collect(),
+        bounds,
+        sessions,
+        idempotent,
+    })
+}
+
+/// Parse a string directly into an expression. Like `parse_program`, the expression is
+/// parsed as the version of its `#lang` header, unless `--lang-version` forces one.
 pub fn parse(source: &str, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
-    let tokens = tokenize(source)?;
-    let mut parser = Parser::new(tokens);
+    let (header_version, source) = parse_lang_header(source)?;
+    let version = effective_lang_version(header_version);
+    let tokens = tokenize_spanned_versioned(&source, version)?;
+    let mut parser = Parser::with_source(tokens, &source, version);
     parser.parse(table)
 }
 
-/// Parse a string into a program containing multiple requests
+/// Parse a string into a program containing multiple requests. The program is parsed
+/// as the version of its `#lang` header, unless `--lang-version` forces one.
 pub fn parse_program(source: &str, table: &mut ExprHc) -> Result<Program, String> {
-    let tokens = tokenize(source)?;
-    let mut parser = Parser::new(tokens);
+    let (header_version, source) = parse_lang_header(source)?;
+    let version = effective_lang_version(header_version);
+    let tokens = tokenize_spanned_versioned(&source, version)?;
+    let mut parser = Parser::with_source(tokens, &source, version);
     parser.parse_program(table)
 }
 
 impl Parser {
+    /// A parser for programs without a `#lang` header
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Self::with_version(tokens, effective_lang_version(None))
+    }
+
+    pub fn with_version(tokens: Vec<Token>, version: u32) -> Self {
+        let tokens = tokens.into_iter().map(|token| (token, 0..0)).collect();
+        let mut parser = Self::with_source(tokens, "", version);
+        parser.source = None;
+        parser
+    }
+
+    /// A parser for tokens read from `source` by `tokenize_spanned`, which records the
+    /// spans of the statements it parses in the `ExprHc`
+    pub fn with_source(tokens: Vec<(Token, Range<usize>)>, source: &str, version: u32) -> Self {
+        // Doc comments document request declarations; anywhere else they are ordinary
+        // comments
+        let documents_request = |rest: &[(Token, Range<usize>)]| {
+            rest.iter()
+                .find(|(token, _)| !matches!(token, Token::DocComment(_)))
+                .is_some_and(|(token, _)| matches!(token, Token::Request | Token::Idempotent))
+        };
+        let (tokens, ranges): (Vec<Token>, Vec<Range<usize>>) = tokens
+            .iter()
+            .enumerate()
+            .filter(|(i, (token, _))| {
+                !matches!(token, Token::DocComment(_)) || documents_request(&tokens[*i..])
+            })
+            .map(|(_, token)| token.clone())
+            .unzip();
+        Parser {
+            tokens,
+            current: 0,
+            version,
+            source: Some((source.to_string(), ranges)),
+        }
+    }
+
+    /// The span of the tokens from `start` up to the current one, if the source is known
+    fn span_from(&self, start: usize) -> Option<Span> {
+        let (source, ranges) = self.source.as_ref()?;
+        if start >= self.current {
+            return None;
+        }
+        let range = ranges[start].start..ranges[self.current - 1].end;
+        let line = source[..range.start].matches('\n').count() + 1;
+        let text = source[range].lines().next().unwrap_or("").trim();
+        Some(Span {
+            line,
+            text: text.to_string(),
+        })
+    }
+
+    /// Fail if `feature` is not part of the version being parsed
+    fn require(&self, feature: Feature) -> Result<(), String> {
+        if self.version >= feature.since() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} require ser/{} or later, but the program is ser/{}",
+                feature.description(),
+                feature.since(),
+                self.version
+            ))
+        }
     }
 
     pub fn parse(&mut self, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
@@ -296,16 +763,52 @@ impl Parser {
 
     pub fn parse_program(&mut self, table: &mut ExprHc) -> Result<Program, String> {
         let mut requests = Vec::new();
+        let mut globals: Vec<(String, i64)> = Vec::new();
+        let mut bounds = Vec::new();
+        let mut sessions: Vec<Session> = Vec::new();
+        let mut idempotent = Vec::new();
 
         while !self.is_at_end() {
+            let doc = self.doc_comment();
             if self.check(&Token::Request) {
-                let request = self.parse_request(table)?;
+                let request = self.parse_request(table, doc)?;
                 requests.push(request);
+            } else if self.match_token(&[Token::Idempotent]) {
+                self.require(Feature::Idempotent)?;
+                if !self.check(&Token::Request) {
+                    return Err("Expected 'request' after 'idempotent'".to_string());
+                }
+                let request = self.parse_request(table, doc)?;
+                idempotent.push(request.name.clone());
+                requests.push(request);
+            } else if self.check(&Token::Global) {
+                self.require(Feature::GlobalDeclarations)?;
+                let (name, value) = self.parse_global()?;
+                match globals.iter().find(|(g, _)| *g == name) {
+                    Some((_, old)) if *old != value => {
+                        return Err(format!(
+                            "Conflicting initializations of global {}: {} and {}",
+                            name, old, value
+                        ));
+                    }
+                    Some(_) => {}
+                    None => globals.push((name, value)),
+                }
+            } else if self.check(&Token::Bound) {
+                self.require(Feature::Bounds)?;
+                bounds.push(self.parse_bound()?);
+            } else if self.check(&Token::Session) {
+                self.require(Feature::Sessions)?;
+                let session = self.parse_session()?;
+                if sessions.iter().any(|s| s.name == session.name) {
+                    return Err(format!("Duplicate session '{}'", session.name));
+                }
+                sessions.push(session);
             } else if self.is_at_end() {
                 break;
             } else {
                 return Err(format!(
-                    "Expected 'request' keyword, found {:?}",
+                    "Expected 'request', 'global', 'bound' or 'session' keyword, found {:?}",
                     self.tokens[self.current]
                 ));
             }
@@ -314,11 +817,133 @@ impl Parser {
         if requests.is_empty() {
             return Err("No requests found in program".to_string());
         }
+        for bound in &bounds {
+            if !requests.iter().any(|r: &Request| r.name == bound.request) {
+                return Err(format!("Bound refers to unknown request '{}'", bound.request));
+            }
+        }
+        for session in &sessions {
+            for request in &session.requests {
+                if !requests.iter().any(|r| r.name == *request) {
+                    return Err(format!(
+                        "Session '{}' refers to unknown request '{}'",
+                        session.name, request
+                    ));
+                }
+            }
+        }
+
+        Ok(Program {
+            requests,
+            globals,
+            bounds,
+            sessions,
+            idempotent,
+        })
+    }
+
+    /// Parse a session `session name { r1; r2; ... }` (the last `;` is optional)
+    fn parse_session(&mut self) -> Result<Session, String> {
+        self.consume(Token::Session, "Expected 'session' keyword")?;
+
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            _ => return Err("Expected session name".to_string()),
+        };
+        self.consume(Token::LBrace, "Expected '{' after session name")?;
+        let mut requests = Vec::new();
+        while !self.match_token(&[Token::RBrace]) {
+            if self.is_at_end() {
+                return Err(format!("Expected '}}' after session '{}'", name));
+            }
+            match self.advance() {
+                Some(Token::Identifier(request)) => requests.push(request.clone()),
+                _ => return Err(format!("Expected request name in session '{}'", name)),
+            }
+            if !self.match_token(&[Token::Semicolon]) && !self.check(&Token::RBrace) {
+                return Err(format!("Expected ';' or '}}' in session '{}'", name));
+            }
+        }
+        if requests.is_empty() {
+            return Err(format!("Session '{}' has no requests", name));
+        }
+
+        Ok(Session { name, requests })
+    }
+
+    /// Parse a multiplicity bound `bound req <= k;` or `bound req/resp <= k;`
+    fn parse_bound(&mut self) -> Result<ResponseBound, String> {
+        self.consume(Token::Bound, "Expected 'bound' keyword")?;
+
+        let request = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            _ => return Err("Expected request name after 'bound'".to_string()),
+        };
+        let response = if self.match_token(&[Token::Slash]) {
+            let negative = self.match_token(&[Token::Minus]);
+            match self.advance() {
+                Some(Token::Number(n)) => Some(if negative { -*n } else { *n }),
+                _ => return Err(format!("Expected a response value after '{}/'", request)),
+            }
+        } else {
+            None
+        };
+
+        self.consume(Token::LessEqual, "Expected '<=' in bound")?;
+        let max = match self.advance() {
+            Some(Token::Number(n)) => *n as usize,
+            _ => return Err(format!("Expected a number as bound for {}", request)),
+        };
+        self.consume(Token::Semicolon, "Expected ';' after bound")?;
+
+        Ok(ResponseBound {
+            request,
+            response,
+            max,
+        })
+    }
+
+    /// Parse a global declaration `global X := n;`
+    fn parse_global(&mut self) -> Result<(String, i64), String> {
+        self.consume(Token::Global, "Expected 'global' keyword")?;
+
+        let name = match self.advance() {
+            Some(Token::Identifier(name)) => name.clone(),
+            _ => return Err("Expected global variable name".to_string()),
+        };
+        if !name.chars().next().is_some_and(|c| c.is_uppercase()) {
+            return Err(format!(
+                "Global variable '{}' must start with an uppercase letter",
+                name
+            ));
+        }
 
-        Ok(Program { requests })
+        self.consume(Token::Assign, "Expected ':=' after global variable name")?;
+        let negative = self.match_token(&[Token::Minus]);
+        let value = match self.advance() {
+            Some(Token::Number(n)) => *n,
+            _ => return Err(format!("Expected a number as initial value of {}", name)),
+        };
+        self.consume(Token::Semicolon, "Expected ';' after global declaration")?;
+
+        Ok((name, if negative { -value } else { value }))
+    }
+
+    /// The lines of the doc comments at the current position, if there are any
+    fn doc_comment(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        while let Some(Token::DocComment(line)) = self.peek() {
+            lines.push(line.clone());
+            self.advance();
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
     }
 
-    fn parse_request(&mut self, table: &mut ExprHc) -> Result<Request, String> {
+    fn parse_request(
+        &mut self,
+        table: &mut ExprHc,
+        doc: Option<String>,
+    ) -> Result<Request, String> {
         self.consume(Token::Request, "Expected 'request' keyword")?;
 
         let name = match self.advance() {
@@ -330,7 +955,7 @@ impl Parser {
         let body = self.expression(table)?;
         self.consume(Token::RBrace, "Expected '}' after request body")?;
 
-        Ok(Request { name, body })
+        Ok(Request { name, body, doc })
     }
 
     fn expression(&mut self, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
@@ -338,17 +963,32 @@ impl Parser {
     }
 
     fn sequence(&mut self, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
+        let start = self.current;
         let expr = self.assignment(table)?;
+        let span = self.span_from(start);
+        if let Some(span) = &span {
+            table.set_span(&expr, span.clone());
+        }
 
         if self.match_token(&[Token::Semicolon]) {
             let right = self.expression(table)?;
-            return Ok(table.sequence(expr, right));
+            let sequence = table.sequence(expr, right);
+            // A sequence starts where its first statement does, also if that is an atom
+            if let Some(span) = span {
+                table.set_span(&sequence, span);
+            }
+            return Ok(sequence);
         }
 
         Ok(expr)
     }
 
     fn assignment(&mut self, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
+        if self.match_token(&[Token::Return]) {
+            self.require(Feature::Return)?;
+            let value = self.assignment(table)?;
+            return Ok(table.return_expr(value));
+        }
         if let Some(Token::Identifier(name)) = self.peek() {
             let name = name.clone();
             if self.peek_next() == Some(&Token::Assign) {
@@ -366,6 +1006,7 @@ impl Parser {
         let mut expr = self.logical_and(table)?;
 
         while self.match_token(&[Token::Or]) {
+            self.require(Feature::BooleanOperators)?;
             let right = self.logical_and(table)?;
             expr = table.or(expr, right);
         }
@@ -377,6 +1018,7 @@ impl Parser {
         let mut expr = self.equality(table)?;
 
         while self.match_token(&[Token::And]) {
+            self.require(Feature::BooleanOperators)?;
             let right = self.equality(table)?;
             expr = table.and(expr, right);
         }
@@ -415,6 +1057,7 @@ impl Parser {
 
     fn unary(&mut self, table: &mut ExprHc) -> Result<Hc<Expr>, String> {
         if self.match_token(&[Token::Not]) {
+            self.require(Feature::BooleanOperators)?;
             let expr = self.unary(table)?;
             return Ok(table.not(expr));
         }
@@ -430,7 +1073,10 @@ impl Parser {
             Some(Token::Identifier(name)) => Ok(table.variable(name.clone())),
             Some(Token::Question) => Ok(table.unknown()),
             Some(Token::Yield) => Ok(table.yield_expr()),
-            Some(Token::Exit) => Ok(table.exit()),
+            Some(Token::Exit) => {
+                self.require(Feature::Exit)?;
+                Ok(table.exit())
+            }
             Some(Token::If) => {
                 self.consume(Token::LParen, "Expected '(' after 'if'")?;
                 let condition = self.expression(table)?;
@@ -446,6 +1092,7 @@ impl Parser {
                 Ok(table.if_expr(condition, then_branch, else_branch))
             }
             Some(Token::While) => {
+                self.require(Feature::Loops)?;
                 self.consume(Token::LParen, "Expected '(' after 'while'")?;
                 let condition = self.expression(table)?;
                 self.consume(Token::RParen, "Expected ')' after condition")?;
@@ -527,10 +1174,88 @@ impl Parser {
 
 // Lexer implementation
 pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    tokenize_versioned(source, DEFAULT_LANG_VERSION)
+}
+
+/// Tokenize `source` as `version` of the language
+pub fn tokenize_versioned(source: &str, version: u32) -> Result<Vec<Token>, String> {
+    Ok(tokenize_spanned_versioned(source, version)?
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect())
+}
+
+/// The characters of a source, which know the byte offset of the next one
+struct SourceChars<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    len: usize,
+}
+
+impl<'a> SourceChars<'a> {
+    fn new(source: &'a str) -> Self {
+        SourceChars {
+            chars: source.char_indices().peekable(),
+            len: source.len(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek().map(|(_, c)| c)
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.len, |(i, _)| *i)
+    }
+}
+
+impl Iterator for SourceChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+}
+
+/// The keyword `identifier` stands for in `version` of the language, if any. Keywords
+/// of later versions are ordinary identifiers, so that older programs can keep using
+/// them as names.
+fn keyword(identifier: &str, version: u32) -> Option<Token> {
+    let (token, feature) = match identifier {
+        "if" => (Token::If, None),
+        "else" => (Token::Else, None),
+        "while" => (Token::While, None),
+        "yield" => (Token::Yield, None),
+        "exit" => (Token::Exit, None),
+        "return" => (Token::Return, Some(Feature::Return)),
+        "request" => (Token::Request, None),
+        "global" => (Token::Global, None),
+        "bound" => (Token::Bound, None),
+        "session" => (Token::Session, Some(Feature::Sessions)),
+        "idempotent" => (Token::Idempotent, Some(Feature::Idempotent)),
+        _ => return None,
+    };
+    feature
+        .is_none_or(|feature| feature.since() <= version)
+        .then_some(token)
+}
+
+/// Tokenize `source`, with the byte range of every token in it
+pub fn tokenize_spanned(source: &str) -> Result<Vec<(Token, Range<usize>)>, String> {
+    tokenize_spanned_versioned(source, DEFAULT_LANG_VERSION)
+}
+
+/// Tokenize `source` as `version` of the language, with the byte range of every token
+/// in it
+pub fn tokenize_spanned_versioned(
+    source: &str,
+    version: u32,
+) -> Result<Vec<(Token, Range<usize>)>, String> {
     let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
+    let mut ranges = Vec::new();
+    let mut chars = SourceChars::new(source);
 
     while let Some(&c) = chars.peek() {
+        let start = chars.offset();
         match c {
             ' ' | '\t' | '\n' | '\r' => {
                 chars.next();
@@ -541,14 +1266,30 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
                     // This is a comment, consume the second '/'
                     chars.next();
                     // Consume all characters until the end of the line
+                    let mut text = String::new();
                     while let Some(&c) = chars.peek() {
                         if c == '\n' {
                             break;
                         }
+                        text.push(c);
                         chars.next();
                     }
+                    // `///` starts a doc comment, but `////` does not
+                    if let Some(doc) = text.strip_prefix('/').filter(|doc| !doc.starts_with('/')) {
+                        let doc = doc.strip_prefix(' ').unwrap_or(doc);
+                        tokens.push(Token::DocComment(doc.trim_end().to_string()));
+                    }
+                } else {
+                    tokens.push(Token::Slash);
+                }
+            }
+            '<' => {
+                chars.next();
+                if let Some(&'=') = chars.peek() {
+                    chars.next();
+                    tokens.push(Token::LessEqual);
                 } else {
-                    return Err("Unexpected character: /".to_string());
+                    return Err("Expected '=' after '<'".to_string());
                 }
             }
             '0'..='9' => {
@@ -574,14 +1315,9 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
                     }
                 }
 
-                match identifier.as_str() {
-                    "if" => tokens.push(Token::If),
-                    "else" => tokens.push(Token::Else),
-                    "while" => tokens.push(Token::While),
-                    "yield" => tokens.push(Token::Yield),
-                    "exit" => tokens.push(Token::Exit),
-                    "request" => tokens.push(Token::Request),
-                    _ => tokens.push(Token::Identifier(identifier)),
+                match keyword(&identifier, version) {
+                    Some(token) => tokens.push(token),
+                    None => tokens.push(Token::Identifier(identifier)),
                 }
             }
             ':' => {
@@ -660,10 +1396,13 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
                 return Err(format!("Unexpected character: {}", c));
             }
         }
+        // Whitespace and comments add no token, the other characters one
+        ranges.resize(tokens.len(), start..chars.offset());
     }
 
     tokens.push(Token::Eof);
-    Ok(tokens)
+    ranges.push(source.len()..source.len());
+    Ok(tokens.into_iter().zip(ranges).collect())
 }
 
 #[cfg(test)]
@@ -1333,12 +2072,18 @@ mod tests {
                 Request {
                     name: "foo".to_string(),
                     body: body.clone(),
+                    doc: Some("Sets x".to_string()),
                 },
                 Request {
                     name: "bar".to_string(),
                     body: x.clone(),
+                    doc: None,
                 },
             ],
+            globals: vec![("X".to_string(), 2)],
+            bounds: vec![],
+            sessions: vec![],
+            idempotent: vec![],
         };
         
         let json = serde_json::to_string_pretty(&program).unwrap();
@@ -1350,5 +2095,300 @@ mod tests {
         assert_eq!(*program.requests[0].body, *deserialized.requests[0].body);
         assert_eq!(program.requests[1].name, deserialized.requests[1].name);
         assert_eq!(*program.requests[1].body, *deserialized.requests[1].body);
+        assert_eq!(program.requests[0].doc, deserialized.requests[0].doc);
+        assert_eq!(program.requests[1].doc, None);
+        assert_eq!(program.globals, deserialized.globals);
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        let mut table = ExprHc::new();
+        let source = "#lang ser/2
+            /// Not a doc comment: it does not document a request
+            global X := 0;
+            //// Neither is this
+            /// Increments X.
+            ///
+            ///Returns the old value
+            request inc { /// ignored
+                X := X + 1 }
+            // An ordinary comment
+            request get { X }
+            /// Idempotent writes
+            idempotent request put { X := 1 }
+            /// Dangling";
+        let program = parse_program(source, &mut table).unwrap();
+        let docs: Vec<Option<&str>> = program
+            .requests
+            .iter()
+            .map(|request| request.doc.as_deref())
+            .collect();
+        assert_eq!(
+            docs,
+            vec![
+                Some("Increments X.\n\nReturns the old value"),
+                None,
+                Some("Idempotent writes"),
+            ]
+        );
+        // Expressions ignore doc comments like other comments
+        assert!(parse("/// doc\nX := 1 /// doc", &mut table).is_ok());
+    }
+
+    #[test]
+    fn test_parse_global_declarations() {
+        let mut table = ExprHc::new();
+        let program = parse_program(
+            "global X := 1; global Y := -2; global X := 1; request foo { X := Y }",
+            &mut table,
+        )
+        .unwrap();
+        assert_eq!(
+            program.globals,
+            vec![("X".to_string(), 1), ("Y".to_string(), -2)]
+        );
+        assert_eq!(program.requests.len(), 1);
+
+        assert!(parse_program("global X := 1; global X := 2; request foo { 0 }", &mut table).is_err());
+        assert!(parse_program("global x := 1; request foo { 0 }", &mut table).is_err());
+    }
+
+    #[test]
+    fn test_compose_programs() {
+        let mut table = ExprHc::new();
+        let a = parse_program("global X := 1; request get { X }", &mut table).unwrap();
+        let b = parse_program("global X := 1; request get { X := 0 }", &mut table).unwrap();
+        let c = parse_program("global X := 3; request put { X := 1 }", &mut table).unwrap();
+
+        let composed =
+            compose_programs(vec![("a".to_string(), a.clone()), ("b".to_string(), b)]).unwrap();
+        let names: Vec<&str> = composed.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["a.get", "b.get"]);
+        assert_eq!(composed.globals, vec![("X".to_string(), 1)]);
+
+        let err = compose_programs(vec![("a".to_string(), a), ("c".to_string(), c)]).unwrap_err();
+        assert!(err.contains("global X"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_bounds() {
+        let mut table = ExprHc::new();
+        let program = parse_program(
+            "bound init <= 1; bound get/0 <= 2; request init { X := 1 } request get { X }",
+            &mut table,
+        )
+        .unwrap();
+        assert_eq!(
+            program.bounds,
+            vec![
+                ResponseBound {
+                    request: "init".to_string(),
+                    response: None,
+                    max: 1
+                },
+                ResponseBound {
+                    request: "get".to_string(),
+                    response: Some(0),
+                    max: 2
+                },
+            ]
+        );
+
+        assert!(parse_program("bound missing <= 1; request get { 0 }", &mut table).is_err());
+        assert!(parse_program("bound get < 1; request get { 0 }", &mut table).is_err());
+    }
+
+    #[test]
+    fn test_parse_sessions() {
+        let mut table = ExprHc::new();
+        let source = "#lang ser/2
+            request login { X := 1 }
+            request read { X }
+            session alice { login; read; read }
+            session bob { read }";
+        let program = parse_program(source, &mut table).unwrap();
+        assert_eq!(
+            program.sessions,
+            vec![
+                Session {
+                    name: "alice".to_string(),
+                    requests: vec!["login".to_string(), "read".to_string(), "read".to_string()],
+                },
+                Session {
+                    name: "bob".to_string(),
+                    requests: vec!["read".to_string()],
+                },
+            ]
+        );
+
+        let parse = |source: &str, table: &mut ExprHc| {
+            parse_program(&format!("#lang ser/2\nrequest read {{ 0 }}\n{}", source), table)
+        };
+        assert!(parse("session s { read; }", &mut table).is_ok());
+        assert!(parse("session s { write }", &mut table).is_err());
+        assert!(parse("session s { }", &mut table).is_err());
+        assert!(parse("session s { read read }", &mut table).is_err());
+        assert!(parse("session s { read", &mut table).is_err());
+        assert!(parse("session s { read } session s { read }", &mut table).is_err());
+        assert!(parse_program("request read { 0 } session s { read }", &mut table).is_err());
+    }
+
+    #[test]
+    fn test_lang_header() {
+        assert_eq!(
+            parse_lang_header("#lang ser/1\nrequest r { 0 }").unwrap(),
+            (Some(1), "\nrequest r { 0 }".to_string())
+        );
+        assert_eq!(
+            parse_lang_header("request r { 0 }").unwrap(),
+            (None, "request r { 0 }".to_string())
+        );
+        let unsupported = parse_lang_header("#lang ser/99\nrequest r { 0 }").unwrap_err();
+        assert!(unsupported.contains("Unsupported language version ser/99"));
+        assert!(parse_lang_header("#lang pluscal/1").is_err());
+        assert!(parse_lang_header("#lang ser/one").is_err());
+
+        let mut table = ExprHc::new();
+        let with_header = parse_program("#lang ser/1\nrequest r { X := 1 }", &mut table).unwrap();
+        let without_header = parse_program("request r { X := 1 }", &mut table).unwrap();
+        assert_eq!(with_header, without_header);
+        assert!(parse_program("#lang ser/3\nrequest r { 0 }", &mut table).is_err());
+    }
+
+    #[test]
+    fn test_feature_gate() {
+        let mut table = ExprHc::new();
+        // A version before all features, to check the gate itself
+        let tokens = tokenize("while (x == 0) { x := 1 }").unwrap();
+        let err = Parser::with_version(tokens.clone(), 0)
+            .parse(&mut table)
+            .unwrap_err();
+        assert_eq!(err, "'while' loops require ser/1 or later, but the program is ser/0");
+        assert!(Parser::with_version(tokens, 1).parse(&mut table).is_ok());
+
+        // `return` is new in ser/2
+        let source = "request r { return X + 1; X := 2 }";
+        let tokens = tokenize_versioned(source, LANG_VERSION).unwrap();
+        let err = Parser::with_version(tokens, 1)
+            .parse_program(&mut table)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "'return' statements require ser/2 or later, but the program is ser/1"
+        );
+        assert!(parse_program(source, &mut table).is_err());
+        let program = parse_program(&format!("#lang ser/2\n{}", source), &mut table).unwrap();
+        assert_eq!(program.requests[0].body.to_string(), "return X + 1; X := 2");
+        // Single expressions default to the same version as programs
+        assert!(parse("return X + 1", &mut table).is_err());
+        assert!(parse("#lang ser/2\nreturn X + 1", &mut table).is_ok());
+
+        // So are `idempotent` annotations
+        let source = "idempotent request put { X := 1 } request get { X }";
+        assert!(parse_program(source, &mut table).is_err());
+        let program = parse_program(&format!("#lang ser/2\n{}", source), &mut table).unwrap();
+        assert_eq!(program.requests.len(), 2);
+        assert_eq!(program.idempotent, vec!["put".to_string()]);
+        assert!(parse_program("#lang ser/2\nidempotent global X := 1;", &mut table).is_err());
+    }
+
+    #[test]
+    fn test_later_keywords_are_names() {
+        // The keywords of ser/2 are ordinary names in ser/1 programs
+        let mut table = ExprHc::new();
+        let source = "request session { return := 1; idempotent := return + 1; idempotent }";
+        for source in [source.to_string(), format!("#lang ser/1\n{}", source)] {
+            let program = parse_program(&source, &mut table).unwrap();
+            assert_eq!(program.requests[0].name, "session");
+            assert_eq!(
+                program.requests[0].body.to_string(),
+                "return := 1; idempotent := return + 1; idempotent"
+            );
+        }
+        assert!(parse_program(&format!("#lang ser/2\n{}", source), &mut table).is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens = tokenize_spanned("x := 42").unwrap();
+        let ranges: Vec<_> = tokens.into_iter().map(|(_, range)| range).collect();
+        assert_eq!(ranges, vec![0..1, 2..4, 5..7, 7..7]);
+
+        let mut table = ExprHc::new();
+        let source = "#lang ser/2
+request inc {
+    x := G;
+    yield;
+    if (x == 0) {
+        G := x + 1
+    } else { G := 0 }
+}";
+        let body = parse_program(source, &mut table).unwrap().requests[0]
+            .body
+            .clone();
+        let span = |expr: &Hc<Expr>, table: &ExprHc| table.span(expr).map(|s| s.to_string());
+        assert_eq!(span(&body, &table), Some("line 3 `x := G`".to_string()));
+        let Expr::Sequence(_, rest) = body.get() else {
+            panic!("expected a sequence: {}", body)
+        };
+        // The sequence starts at its `yield`, though `yield` itself has no span
+        assert_eq!(span(rest, &table), Some("line 4 `yield`".to_string()));
+        let Expr::Sequence(yield_expr, branch) = rest.get() else {
+            panic!("expected a sequence: {}", rest)
+        };
+        assert_eq!(span(yield_expr, &table), None);
+        assert_eq!(
+            span(branch, &table),
+            Some("line 5 `if (x == 0) {`".to_string())
+        );
+
+        // A continuation built during execution is located by what it runs first
+        let Expr::If(_, then_branch, _) = branch.get() else {
+            panic!("expected an if: {}", branch)
+        };
+        let unknown = table.unknown();
+        let continuation = table.sequence(unknown, then_branch.clone());
+        assert_eq!(
+            table.location(&continuation).map(|s| s.to_string()),
+            Some("line 6 `G := x + 1`".to_string())
+        );
+
+        // Without the source, nothing is recorded
+        let mut table = ExprHc::new();
+        let tokens = tokenize("y := 1; z := 2").unwrap();
+        let expr = Parser::with_version(tokens, LANG_VERSION)
+            .parse(&mut table)
+            .unwrap();
+        assert_eq!(table.location(&expr), None);
+    }
+
+    #[test]
+    fn test_to_source() {
+        let mut table = ExprHc::new();
+        let source = "#lang ser/2
+global X := -1;
+/// Adds one
+idempotent request inc {
+    x := (y := X; y + 1); X := x - (1 - y); return x == (0 - 2)
+}
+request other {
+    if (!(X == 1 || X == 2) && ?) { while (X == 0) { yield } } else { exit }
+}
+bound inc/-2 <= 3;
+bound other <= 1;
+session client { inc; other }
+";
+        let program = parse_program(source, &mut table).unwrap();
+        let printed = program.to_source(Some(2));
+        assert_eq!(parse_program(&printed, &mut table).unwrap(), program);
+        assert_eq!(
+            to_source(&program.requests[0].body),
+            "x := (y := X; y + 1); X := x - (1 - y); return x == (0 - 2)"
+        );
+        assert!(
+            printed.starts_with(
+                "#lang ser/2\nglobal X := -1;\n/// Adds one\nidempotent request inc {"
+            )
+        );
     }
 }
@@ -1,6 +1,6 @@
 use crate::deterministic_map::{HashMap, HashSet};
 use crate::graphviz;
-use crate::utils::string::escape_for_graphviz_id;
+use crate::ident::{Target, encode};
 use std::hash::Hash;
 
 #[derive(Clone)]
@@ -53,10 +53,176 @@ where
         self.initial_marking.clone()
     }
 
+    /// The same Petri net with a different initial marking
+    pub fn with_initial_marking(&self, initial_marking: Vec<Place>) -> Self {
+        Petri {
+            initial_marking,
+            transitions: self.transitions.clone(),
+        }
+    }
+
     /// Get all transitions in the Petri net
     pub fn get_transitions(&self) -> Vec<(Vec<Place>, Vec<Place>)> {
         self.transitions.clone()
     }
+
+    /// Play the token game from the initial marking: fire up to `steps` random enabled
+    /// transitions, then up to `steps` more among those that do not add tokens, so
+    /// that tokens created in the first phase get a chance to drain. `visit` is called
+    /// with every marking reached (the initial one included) and the indices of the
+    /// transitions fired to reach it, and ends the run by returning true. The choices
+    /// depend only on `seed`. Returns whether `visit` ended the run.
+    pub fn simulate(
+        &self,
+        steps: usize,
+        seed: u64,
+        mut visit: impl FnMut(&HashMap<Place, usize>, &[usize]) -> bool,
+    ) -> bool {
+        let mut random = xorshift(seed);
+        let mut marking = self.initial_counts();
+        let mut fired = Vec::new();
+        if visit(&marking, &fired) {
+            return true;
+        }
+
+        for step in 0..2 * steps {
+            let draining = step >= steps;
+            let candidates: Vec<usize> = (0..self.transitions.len())
+                .filter(|&i| {
+                    let (input, output) = &self.transitions[i];
+                    (!draining || output.len() <= input.len()) && is_enabled(&marking, input)
+                })
+                .collect();
+            if candidates.is_empty() {
+                if draining {
+                    break;
+                }
+                continue;
+            }
+            let index = candidates[(random() % candidates.len() as u64) as usize];
+            let (input, output) = &self.transitions[index];
+            fire(&mut marking, input, output);
+            fired.push(index);
+            if visit(&marking, &fired) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Like `simulate`, but steered towards markings where `distance` is low (zero
+    /// for the markings sought), as in simulated annealing: each step picks an enabled
+    /// transition with probability proportional to `exp(-d / temperature)`, where `d`
+    /// is the distance after firing it minus the smallest such distance, and the
+    /// temperature drops linearly over the `steps` steps, making the run greedier as
+    /// it goes. `visit` is as in `simulate`.
+    pub fn simulate_guided(
+        &self,
+        steps: usize,
+        seed: u64,
+        mut distance: impl FnMut(&HashMap<Place, usize>) -> u64,
+        mut visit: impl FnMut(&HashMap<Place, usize>, &[usize]) -> bool,
+    ) -> bool {
+        const INITIAL_TEMPERATURE: f64 = 2.0;
+        const FINAL_TEMPERATURE: f64 = 0.05;
+
+        let mut random = xorshift(seed);
+        let mut marking = self.initial_counts();
+        let mut fired = Vec::new();
+        if visit(&marking, &fired) {
+            return true;
+        }
+
+        for step in 0..steps {
+            // Distance after firing each enabled transition
+            let mut candidates: Vec<(usize, u64)> = Vec::new();
+            for (index, (input, output)) in self.transitions.iter().enumerate() {
+                if is_enabled(&marking, input) {
+                    fire(&mut marking, input, output);
+                    candidates.push((index, distance(&marking)));
+                    fire(&mut marking, output, input);
+                }
+            }
+            let Some(closest) = candidates.iter().map(|(_, d)| *d).min() else {
+                break;
+            };
+
+            let progress = step as f64 / steps as f64;
+            let temperature =
+                INITIAL_TEMPERATURE + (FINAL_TEMPERATURE - INITIAL_TEMPERATURE) * progress;
+            let weights: Vec<f64> = candidates
+                .iter()
+                .map(|(_, d)| (-((d - closest) as f64) / temperature).exp())
+                .collect();
+            let mut choice = random() as f64 / u64::MAX as f64 * weights.iter().sum::<f64>();
+            let mut picked = candidates.len() - 1;
+            for (i, weight) in weights.iter().enumerate() {
+                if choice < *weight {
+                    picked = i;
+                    break;
+                }
+                choice -= weight;
+            }
+
+            let index = candidates[picked].0;
+            let (input, output) = &self.transitions[index];
+            fire(&mut marking, input, output);
+            fired.push(index);
+            if visit(&marking, &fired) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The initial marking as token counts
+    fn initial_counts(&self) -> HashMap<Place, usize> {
+        let mut marking: HashMap<Place, usize> = HashMap::default();
+        for place in &self.initial_marking {
+            *marking.entry(place.clone()).or_insert(0) += 1;
+        }
+        marking
+    }
+}
+
+/// xorshift64, so that simulations are reproducible; the state must not be zero
+fn xorshift(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed | 1;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+/// Whether `marking` has the tokens `input` consumes
+fn is_enabled<Place: Eq + Hash>(marking: &HashMap<Place, usize>, input: &[Place]) -> bool {
+    let mut needed: HashMap<&Place, usize> = HashMap::default();
+    for place in input {
+        *needed.entry(place).or_insert(0) += 1;
+    }
+    needed
+        .iter()
+        .all(|(place, count)| marking.get(*place).copied().unwrap_or(0) >= *count)
+}
+
+/// Fire a transition enabled in `marking`
+fn fire<Place: Clone + Eq + Hash>(
+    marking: &mut HashMap<Place, usize>,
+    input: &[Place],
+    output: &[Place],
+) {
+    for place in input {
+        let count = marking.get_mut(place).expect("enabled transition");
+        *count -= 1;
+        if *count == 0 {
+            marking.remove(place);
+        }
+    }
+    for place in output {
+        *marking.entry(place.clone()).or_insert(0) += 1;
+    }
 }
 
 impl<Place> Petri<Place>
@@ -139,7 +305,7 @@ where
                 format!("{}●", count)
             };
             // Escape special characters for GraphViz node ID
-            let escaped_place_id = format!("P_{}", escape_for_graphviz_id(&format!("{}", place)));
+            let escaped_place_id = format!("P_{}", encode(&place.to_string(), Target::Graphviz));
 
             // Prepare HTML label with tokens
             let token_html = if *count > 0 {
@@ -188,7 +354,7 @@ where
             // Connect input places to transition with weights if needed
             for (place, count) in unique_inputs {
                 let escaped_place_id =
-                    format!("P_{}", escape_for_graphviz_id(&format!("{}", place)));
+                    format!("P_{}", encode(&place.to_string(), Target::Graphviz));
 
                 if count == 1 {
                     dot.push_str(&format!(
@@ -213,7 +379,7 @@ where
             // Connect transition to output places with weights if needed
             for (place, count) in unique_outputs {
                 let escaped_place_id =
-                    format!("P_{}", escape_for_graphviz_id(&format!("{}", place)));
+                    format!("P_{}", encode(&place.to_string(), Target::Graphviz));
 
                 if count == 1 {
                     dot.push_str(&format!(
@@ -477,6 +643,48 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_simulate() {
+        // A creates B, and B can only be consumed
+        let mut petri = Petri::new(vec!["A"]);
+        petri.add_transition(vec!["A"], vec!["A", "B"]);
+        petri.add_transition(vec!["B"], vec![]);
+
+        let mut markings = Vec::new();
+        let stopped = petri.simulate(10, 42, |marking, fired| {
+            markings.push((marking.clone(), fired.len()));
+            false
+        });
+        assert!(!stopped);
+        assert_eq!(markings[0].0.get("A"), Some(&1));
+        for (i, (marking, fired)) in markings.iter().enumerate() {
+            assert_eq!(*fired, i);
+            assert_eq!(marking.get("A"), Some(&1));
+        }
+        // The second phase only consumes, so the last marking has no B left
+        assert_eq!(markings.last().unwrap().0.get("B"), None);
+
+        // Returning true ends the run
+        assert!(petri.simulate(10, 42, |_, fired| fired.len() == 3));
+    }
+
+    #[test]
+    fn test_simulate_guided() {
+        // Reaching 5 tokens in B takes five steps that the distance rewards
+        let mut petri = Petri::new(vec!["A"]);
+        petri.add_transition(vec!["A"], vec!["A", "B"]);
+        petri.add_transition(vec!["A"], vec!["A", "C"]);
+        petri.add_transition(vec!["B"], vec![]);
+
+        let distance = |marking: &HashMap<&str, usize>| {
+            5u64.abs_diff(marking.get("B").copied().unwrap_or(0) as u64)
+        };
+        let reached = petri.simulate_guided(50, 7, distance, |marking, _| {
+            marking.get("B") == Some(&5)
+        });
+        assert!(reached);
+    }
+
     #[test]
     fn test_filter_reachable() {
         // Create a simple Petri net: P0 -> P1 -> P2, with P3 isolated
@@ -0,0 +1,175 @@
+//! Stable identifiers of places in saved certificates.
+//!
+//! Certificates name the places they count in a representation of their own, so that
+//! the types of the analysis (`ReqPetriState`, `RequestStatePair`) can be refactored
+//! without invalidating saved certificates. A place is a JSON object tagged by `place`:
+//!
+//! - `{"place": "global", "global": G}`: the token of a global state
+//! - `{"place": "request", "request": Req}`: a request that can still be issued
+//! - `{"place": "in_flight", "request": Req, "local": L}`: a request in flight
+//! - `{"place": "completed", "request": Req, "response": Resp}`: a completed request
+//!
+//! The representation is part of the certificate format (version 2, see
+//! `CERTIFICATE_FORMAT_VERSION`), so changing it needs a new version and a migration.
+
+use crate::ns_decision::{RequestState, RequestStatePair};
+use crate::ns_to_petri::ReqPetriState;
+use serde::de::{Error, IgnoredAny};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A place as it is written to certificates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "place", rename_all = "snake_case")]
+pub enum PlaceId<G, L, Req, Resp> {
+    Global { global: G },
+    Request { request: Req },
+    InFlight { request: Req, local: L },
+    Completed { request: Req, response: Resp },
+}
+
+impl<G, L, Req, Resp> PlaceId<G, L, Req, Resp> {
+    /// The `place` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PlaceId::Global { .. } => "global",
+            PlaceId::Request { .. } => "request",
+            PlaceId::InFlight { .. } => "in_flight",
+            PlaceId::Completed { .. } => "completed",
+        }
+    }
+}
+
+impl<L, G, Req, Resp> From<ReqPetriState<L, G, Req, Resp>> for PlaceId<G, L, Req, Resp> {
+    fn from(state: ReqPetriState<L, G, Req, Resp>) -> Self {
+        match state {
+            ReqPetriState::Global(global) => PlaceId::Global { global },
+            ReqPetriState::Request(request) => PlaceId::Request { request },
+            ReqPetriState::Local(request, local) => PlaceId::InFlight { request, local },
+            ReqPetriState::Response(request, response) => PlaceId::Completed { request, response },
+        }
+    }
+}
+
+impl<L, G, Req, Resp> From<PlaceId<G, L, Req, Resp>> for ReqPetriState<L, G, Req, Resp> {
+    fn from(id: PlaceId<G, L, Req, Resp>) -> Self {
+        match id {
+            PlaceId::Global { global } => ReqPetriState::Global(global),
+            PlaceId::Request { request } => ReqPetriState::Request(request),
+            PlaceId::InFlight { request, local } => ReqPetriState::Local(request, local),
+            PlaceId::Completed { request, response } => ReqPetriState::Response(request, response),
+        }
+    }
+}
+
+impl<L, G, Req, Resp> Serialize for ReqPetriState<L, G, Req, Resp>
+where
+    L: Serialize,
+    G: Serialize,
+    Req: Serialize,
+    Resp: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let id: PlaceId<&G, &L, &Req, &Resp> = match self {
+            ReqPetriState::Global(global) => PlaceId::Global { global },
+            ReqPetriState::Request(request) => PlaceId::Request { request },
+            ReqPetriState::Local(request, local) => PlaceId::InFlight { request, local },
+            ReqPetriState::Response(request, response) => PlaceId::Completed { request, response },
+        };
+        id.serialize(serializer)
+    }
+}
+
+impl<'de, L, G, Req, Resp> Deserialize<'de> for ReqPetriState<L, G, Req, Resp>
+where
+    L: Deserialize<'de>,
+    G: Deserialize<'de>,
+    Req: Deserialize<'de>,
+    Resp: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PlaceId::deserialize(deserializer).map(ReqPetriState::from)
+    }
+}
+
+impl<Req, L, Resp> Serialize for RequestStatePair<Req, L, Resp>
+where
+    Req: Serialize,
+    L: Serialize,
+    Resp: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let request = &self.0;
+        let id: PlaceId<(), &L, &Req, &Resp> = match &self.1 {
+            RequestState::InFlight(local) => PlaceId::InFlight { request, local },
+            RequestState::Completed(response) => PlaceId::Completed { request, response },
+        };
+        id.serialize(serializer)
+    }
+}
+
+impl<'de, Req, L, Resp> Deserialize<'de> for RequestStatePair<Req, L, Resp>
+where
+    Req: Deserialize<'de>,
+    L: Deserialize<'de>,
+    Resp: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match PlaceId::<IgnoredAny, L, Req, Resp>::deserialize(deserializer)? {
+            PlaceId::InFlight { request, local } => {
+                Ok(RequestStatePair(request, RequestState::InFlight(local)))
+            }
+            PlaceId::Completed { request, response } => {
+                Ok(RequestStatePair(request, RequestState::Completed(response)))
+            }
+            other => Err(D::Error::custom(format!(
+                "expected an in_flight or completed place, found a {} place",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type State = ReqPetriState<String, String, String, String>;
+    type Pair = RequestStatePair<String, String, String>;
+
+    #[test]
+    fn test_place_ids() {
+        let pair = RequestStatePair("req".to_string(), RequestState::InFlight("L1".to_string()));
+        let json = serde_json::to_value(&pair).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"place": "in_flight", "request": "req", "local": "L1"})
+        );
+        assert_eq!(serde_json::from_value::<Pair>(json).unwrap(), pair);
+
+        let completed: Pair =
+            RequestStatePair("req".to_string(), RequestState::Completed("ok".to_string()));
+        let json = serde_json::to_value(&completed).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"place": "completed", "request": "req", "response": "ok"})
+        );
+        // The same identifier names the response place of the Petri net
+        assert_eq!(
+            serde_json::from_value::<State>(json).unwrap(),
+            ReqPetriState::Response("req".to_string(), "ok".to_string())
+        );
+
+        for state in [
+            ReqPetriState::Global("G".to_string()),
+            ReqPetriState::Request("req".to_string()),
+            ReqPetriState::Local("req".to_string(), "L1".to_string()),
+        ] {
+            let json = serde_json::to_string(&state).unwrap();
+            assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+        }
+
+        let global = serde_json::json!({"place": "global", "global": "G"});
+        let err = serde_json::from_value::<Pair>(global).unwrap_err();
+        assert!(err.to_string().contains("found a global place"), "{}", err);
+    }
+}
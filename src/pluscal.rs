@@ -0,0 +1,808 @@
+//! Importer for a subset of PlusCal.
+//!
+//! Every process of the algorithm is a request type; the labels of a process delimit
+//! its atomic steps, as in PlusCal. The algorithm is compiled to a `Program` of the
+//! `.ser` language, which `expr_to_ns` turns into a network system:
+//!
+//! - global `variables` become globals, initialized to their declared values, and
+//!   process `variables` become locals of the request;
+//! - a label becomes a `yield` before the labelled statement; a labelled `while` also
+//!   yields at the end of every iteration, since control returns to its label;
+//! - `await e` (or `when e`) must start a step and becomes `while(!e){ yield }`;
+//! - `either S1 or S2 end either` chooses nondeterministically (`if(?)`);
+//! - the response of a request is the final value of the process variable
+//!   `response`, or 0 if the process has none.
+//!
+//! Variables range over integers; `TRUE` and `FALSE` are 1 and 0. Expressions may use
+//! `+`, `-`, `=`, `#` (or `/=`), `/\`, `\/`, `~` and parentheses. Globals are renamed to
+//! start with an upper-case letter and locals with a lower-case one, as `.ser`
+//! distinguishes them that way. Procedures, macros, `goto`, `with` and `define`
+//! blocks are not supported.
+
+use crate::frontend::{FrontEnd, LoadedNS};
+use crate::parser::{Expr, ExprHc, Program, Request};
+use hash_cons::Hc;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(i64),
+    /// Operators and punctuation
+    Sym(&'static str),
+    /// Anything else; an error if the parser reaches it
+    Other(char),
+}
+
+const SYMBOLS: &[&str] = &[
+    "/\\", "\\/", "\\in", ":=", "/=", "..", ":", ";", ",", "(", ")", "=", "#", "+", "-", "~",
+];
+
+fn lex(source: &str) -> Vec<Tok> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        if c.is_whitespace() {
+            i += 1;
+        } else if rest.starts_with("\\*") {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if rest.starts_with("(*") {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&')')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            match digits.parse() {
+                Ok(n) => tokens.push(Tok::Num(n)),
+                Err(_) => tokens.push(Tok::Other(c)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Tok::Ident(chars[start..i].iter().collect()));
+        } else if let Some(sym) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Tok::Sym(sym));
+            i += sym.chars().count();
+        } else {
+            tokens.push(Tok::Other(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PExpr {
+    Num(i64),
+    Var(String),
+    Add(Box<PExpr>, Box<PExpr>),
+    Sub(Box<PExpr>, Box<PExpr>),
+    Eq(Box<PExpr>, Box<PExpr>),
+    Neq(Box<PExpr>, Box<PExpr>),
+    And(Box<PExpr>, Box<PExpr>),
+    Or(Box<PExpr>, Box<PExpr>),
+    Not(Box<PExpr>),
+}
+
+impl PExpr {
+    /// Value of an expression without variables (initial values)
+    fn constant(&self) -> Option<i64> {
+        let b = |x: bool| x as i64;
+        Some(match self {
+            PExpr::Num(n) => *n,
+            PExpr::Var(_) => return None,
+            PExpr::Add(a, c) => a.constant()? + c.constant()?,
+            PExpr::Sub(a, c) => a.constant()? - c.constant()?,
+            PExpr::Eq(a, c) => b(a.constant()? == c.constant()?),
+            PExpr::Neq(a, c) => b(a.constant()? != c.constant()?),
+            PExpr::And(a, c) => b(a.constant()? != 0 && c.constant()? != 0),
+            PExpr::Or(a, c) => b(a.constant()? != 0 || c.constant()? != 0),
+            PExpr::Not(a) => b(a.constant()? == 0),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    /// A label: the statements after it form a new atomic step
+    Label(String),
+    Assign(String, PExpr),
+    Await(PExpr),
+    Skip,
+    If(PExpr, Vec<Stmt>, Vec<Stmt>),
+    While(PExpr, Vec<Stmt>),
+    Either(Vec<Vec<Stmt>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Process {
+    name: String,
+    variables: Vec<(String, PExpr)>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Algorithm {
+    variables: Vec<(String, PExpr)>,
+    processes: Vec<Process>,
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Tok> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(s)) if s == keyword)
+    }
+
+    fn at_sym(&self, sym: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Sym(s)) if *s == sym)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        let at = self.at_sym(sym);
+        if at {
+            self.pos += 1;
+        }
+        at
+    }
+
+    fn describe(tok: Option<&Tok>) -> String {
+        match tok {
+            Some(Tok::Ident(s)) => format!("'{}'", s),
+            Some(Tok::Num(n)) => format!("'{}'", n),
+            Some(Tok::Sym(s)) => format!("'{}'", s),
+            Some(Tok::Other(c)) => format!("'{}'", c),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.at_keyword(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected '{}', found {}",
+                keyword,
+                Self::describe(self.peek())
+            ))
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<(), String> {
+        if self.eat_sym(sym) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected '{}', found {}",
+                sym,
+                Self::describe(self.peek())
+            ))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Tok::Ident(s)) => Ok(s),
+            tok => Err(format!(
+                "Expected a name, found {}",
+                Self::describe(tok.as_ref())
+            )),
+        }
+    }
+
+    fn algorithm(&mut self) -> Result<Algorithm, String> {
+        self.ident()?; // name of the algorithm
+        let variables = if self.at_keyword("variables") || self.at_keyword("variable") {
+            self.pos += 1;
+            self.declarations()?
+        } else {
+            Vec::new()
+        };
+        if self.at_keyword("define") || self.at_keyword("macro") || self.at_keyword("procedure") {
+            return Err(format!(
+                "'{}' blocks are not supported",
+                self.ident().unwrap_or_default()
+            ));
+        }
+        let mut processes = Vec::new();
+        while !self.at_keyword("end") {
+            processes.push(self.process()?);
+        }
+        self.expect_keyword("end")?;
+        self.expect_keyword("algorithm")?;
+        if processes.is_empty() {
+            return Err("The algorithm has no processes".to_string());
+        }
+        Ok(Algorithm {
+            variables,
+            processes,
+        })
+    }
+
+    /// `x = e, y = e;` up to the next keyword
+    fn declarations(&mut self) -> Result<Vec<(String, PExpr)>, String> {
+        let mut declarations = Vec::new();
+        while let Some(Tok::Ident(name)) = self.peek().cloned() {
+            if matches!(
+                name.as_str(),
+                "begin" | "process" | "fair" | "define" | "macro" | "procedure" | "end"
+            ) {
+                break;
+            }
+            self.pos += 1;
+            if self.at_sym("\\in") {
+                return Err(format!(
+                    "Variable {} has a set of initial values; only `{} = <value>` is supported",
+                    name, name
+                ));
+            }
+            self.expect_sym("=")?;
+            declarations.push((name, self.expr()?));
+            if !self.eat_sym(",") {
+                self.eat_sym(";");
+            }
+        }
+        Ok(declarations)
+    }
+
+    fn process(&mut self) -> Result<Process, String> {
+        if self.at_keyword("fair") {
+            self.pos += 1;
+            self.eat_sym("+");
+        }
+        self.expect_keyword("process")?;
+        self.eat_sym("(");
+        let name = self.ident()?;
+        // The process identifiers (`\in S` or `= e`) do not matter: every process is
+        // a request type that can be started any number of times
+        while !(self.at_keyword("variables")
+            || self.at_keyword("variable")
+            || self.at_keyword("begin")
+            || self.peek().is_none())
+        {
+            self.pos += 1;
+        }
+        let variables = if self.at_keyword("variables") || self.at_keyword("variable") {
+            self.pos += 1;
+            self.declarations()?
+        } else {
+            Vec::new()
+        };
+        self.expect_keyword("begin")?;
+        let body = self.statements()?;
+        self.expect_keyword("end")?;
+        self.expect_keyword("process")?;
+        self.eat_sym(";");
+        Ok(Process {
+            name,
+            variables,
+            body,
+        })
+    }
+
+    /// Statements up to `end`, `else`, `elsif` or `or`
+    fn statements(&mut self) -> Result<Vec<Stmt>, String> {
+        let mut statements = Vec::new();
+        loop {
+            if ["end", "else", "elsif", "or"]
+                .iter()
+                .any(|k| self.at_keyword(k))
+            {
+                return Ok(statements);
+            }
+            if let (Some(Tok::Ident(label)), Some(Tok::Sym(":"))) = (self.peek(), self.peek_at(1)) {
+                statements.push(Stmt::Label(label.clone()));
+                self.pos += 2;
+                continue;
+            }
+            statements.push(self.statement()?);
+            self.eat_sym(";");
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        let keyword = match self.peek() {
+            Some(Tok::Ident(s)) => s.clone(),
+            tok => {
+                return Err(format!(
+                    "Expected a statement, found {}",
+                    Self::describe(tok)
+                ));
+            }
+        };
+        match keyword.as_str() {
+            "skip" => {
+                self.pos += 1;
+                Ok(Stmt::Skip)
+            }
+            "await" | "when" => {
+                self.pos += 1;
+                Ok(Stmt::Await(self.expr()?))
+            }
+            "if" => {
+                self.pos += 1;
+                self.if_rest()
+            }
+            "while" => {
+                self.pos += 1;
+                let cond = self.expr()?;
+                self.expect_keyword("do")?;
+                let body = self.statements()?;
+                self.expect_keyword("end")?;
+                self.expect_keyword("while")?;
+                Ok(Stmt::While(cond, body))
+            }
+            "either" => {
+                self.pos += 1;
+                let mut branches = vec![self.statements()?];
+                while self.at_keyword("or") {
+                    self.pos += 1;
+                    branches.push(self.statements()?);
+                }
+                self.expect_keyword("end")?;
+                self.expect_keyword("either")?;
+                Ok(Stmt::Either(branches))
+            }
+            "goto" | "with" | "call" | "return" | "print" | "assert" => {
+                Err(format!("'{}' statements are not supported", keyword))
+            }
+            _ => {
+                self.pos += 1;
+                self.expect_sym(":=")?;
+                Ok(Stmt::Assign(keyword, self.expr()?))
+            }
+        }
+    }
+
+    /// The rest of `if e then S [elsif e then S]* [else S] end if`
+    fn if_rest(&mut self) -> Result<Stmt, String> {
+        let cond = self.expr()?;
+        self.expect_keyword("then")?;
+        let then_branch = self.statements()?;
+        let else_branch = if self.at_keyword("elsif") {
+            self.pos += 1;
+            return Ok(Stmt::If(cond, then_branch, vec![self.if_rest()?]));
+        } else if self.at_keyword("else") {
+            self.pos += 1;
+            self.statements()?
+        } else {
+            Vec::new()
+        };
+        self.expect_keyword("end")?;
+        self.expect_keyword("if")?;
+        Ok(Stmt::If(cond, then_branch, else_branch))
+    }
+
+    fn expr(&mut self) -> Result<PExpr, String> {
+        let mut left = self.conjunction()?;
+        while self.eat_sym("\\/") {
+            left = PExpr::Or(Box::new(left), Box::new(self.conjunction()?));
+        }
+        Ok(left)
+    }
+
+    fn conjunction(&mut self) -> Result<PExpr, String> {
+        let mut left = self.negation()?;
+        while self.eat_sym("/\\") {
+            left = PExpr::And(Box::new(left), Box::new(self.negation()?));
+        }
+        Ok(left)
+    }
+
+    fn negation(&mut self) -> Result<PExpr, String> {
+        if self.eat_sym("~") {
+            return Ok(PExpr::Not(Box::new(self.negation()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<PExpr, String> {
+        let left = self.sum()?;
+        if self.eat_sym("=") {
+            Ok(PExpr::Eq(Box::new(left), Box::new(self.sum()?)))
+        } else if self.eat_sym("#") || self.eat_sym("/=") {
+            Ok(PExpr::Neq(Box::new(left), Box::new(self.sum()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn sum(&mut self) -> Result<PExpr, String> {
+        let mut left = self.atom()?;
+        loop {
+            if self.eat_sym("+") {
+                left = PExpr::Add(Box::new(left), Box::new(self.atom()?));
+            } else if self.eat_sym("-") {
+                left = PExpr::Sub(Box::new(left), Box::new(self.atom()?));
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<PExpr, String> {
+        match self.next() {
+            Some(Tok::Num(n)) => Ok(PExpr::Num(n)),
+            Some(Tok::Sym("-")) => Ok(PExpr::Sub(Box::new(PExpr::Num(0)), Box::new(self.atom()?))),
+            Some(Tok::Sym("(")) => {
+                let e = self.expr()?;
+                self.expect_sym(")")?;
+                Ok(e)
+            }
+            Some(Tok::Ident(s)) if s == "TRUE" => Ok(PExpr::Num(1)),
+            Some(Tok::Ident(s)) if s == "FALSE" => Ok(PExpr::Num(0)),
+            Some(Tok::Ident(s)) => Ok(PExpr::Var(s)),
+            tok => Err(format!(
+                "Expected an expression, found {}",
+                Self::describe(tok.as_ref())
+            )),
+        }
+    }
+}
+
+/// Parse the algorithm in a PlusCal or TLA+ file (between `--algorithm` and
+/// `end algorithm`)
+fn parse_algorithm(source: &str) -> Result<Algorithm, String> {
+    let start = source.find("--algorithm").ok_or("No '--algorithm' found")?;
+    let mut parser = Parser {
+        tokens: lex(&source[start + "--algorithm".len()..]),
+        pos: 0,
+    };
+    parser.algorithm()
+}
+
+/// Name of a PlusCal variable in the compiled program
+fn ser_name(name: &str, global: bool) -> String {
+    let mut chars = name.chars();
+    let first = chars.next().unwrap_or('_');
+    let rest: String = chars.collect();
+    match (global, first.is_alphabetic()) {
+        (true, true) => format!("{}{}", first.to_uppercase(), rest),
+        (false, true) => format!("{}{}", first.to_lowercase(), rest),
+        (true, false) => format!("G{}", name),
+        (false, false) => format!("l{}", name),
+    }
+}
+
+/// (PlusCal name, name in the compiled program) of declared variables
+fn renamed(variables: &[(String, PExpr)], global: bool) -> Result<Vec<(String, String)>, String> {
+    let mut names: Vec<(String, String)> = Vec::new();
+    for (name, _) in variables {
+        let ser = ser_name(name, global);
+        if let Some((other, _)) = names.iter().find(|(_, s)| *s == ser) {
+            return Err(format!(
+                "Variables {} and {} would both be named {}",
+                other, name, ser
+            ));
+        }
+        names.push((name.clone(), ser));
+    }
+    Ok(names)
+}
+
+struct Compiler<'a> {
+    table: &'a mut ExprHc,
+    globals: &'a [(String, String)],
+    locals: Vec<(String, String)>,
+    process: String,
+}
+
+impl Compiler<'_> {
+    fn var(&self, name: &str) -> Result<String, String> {
+        self.locals
+            .iter()
+            .chain(self.globals.iter())
+            .find(|(n, _)| n == name)
+            .map(|(_, ser)| ser.clone())
+            .ok_or_else(|| format!("Undeclared variable {} in process {}", name, self.process))
+    }
+
+    fn expr(&mut self, e: &PExpr) -> Result<Hc<Expr>, String> {
+        Ok(match e {
+            PExpr::Num(n) => self.table.number(*n),
+            PExpr::Var(v) => {
+                let name = self.var(v)?;
+                self.table.variable(name)
+            }
+            PExpr::Add(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                self.table.add(a, b)
+            }
+            PExpr::Sub(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                self.table.subtract(a, b)
+            }
+            PExpr::Eq(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                self.table.equal(a, b)
+            }
+            PExpr::Neq(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                let eq = self.table.equal(a, b);
+                self.table.not(eq)
+            }
+            PExpr::And(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                self.table.and(a, b)
+            }
+            PExpr::Or(a, b) => {
+                let (a, b) = (self.expr(a)?, self.expr(b)?);
+                self.table.or(a, b)
+            }
+            PExpr::Not(a) => {
+                let a = self.expr(a)?;
+                self.table.not(a)
+            }
+        })
+    }
+
+    /// Compile a statement list. `at_step_start` says whether no statement of the
+    /// current step has run yet; it is updated for the statements that follow.
+    fn statements(
+        &mut self,
+        statements: &[Stmt],
+        at_step_start: &mut bool,
+    ) -> Result<Hc<Expr>, String> {
+        let mut compiled = Vec::new();
+        let mut i = 0;
+        while i < statements.len() {
+            match &statements[i] {
+                Stmt::Label(_) => {
+                    if !*at_step_start {
+                        compiled.push(self.table.yield_expr());
+                    }
+                    *at_step_start = true;
+                    // A labelled loop returns to its label after every iteration
+                    if let Some(Stmt::While(cond, body)) = statements.get(i + 1) {
+                        let cond = self.expr(cond)?;
+                        let body = self.statements(body, &mut false)?;
+                        let yield_expr = self.table.yield_expr();
+                        let body = self.table.sequence(body, yield_expr);
+                        compiled.push(self.table.while_expr(cond, body));
+                        *at_step_start = false;
+                        i += 2;
+                        continue;
+                    }
+                }
+                Stmt::Await(cond) => {
+                    if !*at_step_start {
+                        return Err(format!(
+                            "In process {}, 'await' must be the first statement after a label",
+                            self.process
+                        ));
+                    }
+                    let cond = self.expr(cond)?;
+                    let not_cond = self.table.not(cond);
+                    let yield_expr = self.table.yield_expr();
+                    compiled.push(self.table.while_expr(not_cond, yield_expr));
+                    *at_step_start = false;
+                }
+                Stmt::Skip => {}
+                Stmt::Assign(var, e) => {
+                    let var = self.var(var)?;
+                    let e = self.expr(e)?;
+                    compiled.push(self.table.assign(var, e));
+                    *at_step_start = false;
+                }
+                Stmt::If(cond, then_branch, else_branch) => {
+                    let cond = self.expr(cond)?;
+                    let (mut then_start, mut else_start) = (*at_step_start, *at_step_start);
+                    let then_branch = self.statements(then_branch, &mut then_start)?;
+                    let else_branch = self.statements(else_branch, &mut else_start)?;
+                    compiled.push(self.table.if_expr(cond, then_branch, else_branch));
+                    *at_step_start = then_start && else_start;
+                }
+                Stmt::While(cond, body) => {
+                    let cond = self.expr(cond)?;
+                    let body = self.statements(body, &mut false)?;
+                    compiled.push(self.table.while_expr(cond, body));
+                    *at_step_start = false;
+                }
+                Stmt::Either(branches) => {
+                    let mut compiled_branches = Vec::new();
+                    let mut all_at_start = true;
+                    for branch in branches {
+                        let mut start = *at_step_start;
+                        compiled_branches.push(self.statements(branch, &mut start)?);
+                        all_at_start &= start;
+                    }
+                    let mut choice = compiled_branches.pop().expect("either has a branch");
+                    while let Some(branch) = compiled_branches.pop() {
+                        let unknown = self.table.unknown();
+                        choice = self.table.if_expr(unknown, branch, choice);
+                    }
+                    compiled.push(choice);
+                    *at_step_start = all_at_start;
+                }
+            }
+            i += 1;
+        }
+        let mut result = match compiled.pop() {
+            Some(last) => last,
+            None => self.table.number(0),
+        };
+        while let Some(previous) = compiled.pop() {
+            result = self.table.sequence(previous, result);
+        }
+        Ok(result)
+    }
+}
+
+/// Compile the algorithm in a PlusCal or TLA+ file to a `.ser` program
+pub fn compile(source: &str, table: &mut ExprHc) -> Result<Program, String> {
+    let algorithm = parse_algorithm(source)?;
+
+    let globals = renamed(&algorithm.variables, true)?;
+    let mut initial_globals = Vec::new();
+    for ((name, init), (_, ser)) in algorithm.variables.iter().zip(&globals) {
+        let value = init
+            .constant()
+            .ok_or_else(|| format!("Initial value of {} is not a constant", name))?;
+        initial_globals.push((ser.clone(), value));
+    }
+
+    let mut requests: Vec<Request> = Vec::new();
+    for process in &algorithm.processes {
+        if requests.iter().any(|r| r.name == process.name) {
+            return Err(format!("Duplicate process {}", process.name));
+        }
+        let locals = renamed(&process.variables, false)?;
+        let mut compiler = Compiler {
+            table: &mut *table,
+            globals: &globals,
+            locals,
+            process: process.name.clone(),
+        };
+
+        // Locals start at their declared values
+        let mut prologue = Vec::new();
+        for (name, init) in &process.variables {
+            let var = compiler.var(name)?;
+            let init = compiler.expr(init)?;
+            prologue.push(compiler.table.assign(var, init));
+        }
+        let body = compiler.statements(&process.body, &mut true)?;
+        let response = if process.variables.iter().any(|(n, _)| n == "response") {
+            compiler.table.variable(ser_name("response", false))
+        } else {
+            compiler.table.number(0)
+        };
+        let mut request_body = compiler.table.sequence(body, response);
+        while let Some(init) = prologue.pop() {
+            request_body = compiler.table.sequence(init, request_body);
+        }
+        requests.push(Request {
+            name: process.name.clone(),
+            body: request_body,
+            doc: None,
+        });
+    }
+
+    Ok(Program {
+        requests,
+        globals: initial_globals,
+        bounds: vec![],
+        sessions: vec![],
+        idempotent: vec![],
+    })
+}
+
+/// PlusCal algorithms in `.tla` or `.pcal` files
+pub struct PlusCalFrontEnd;
+
+impl FrontEnd for PlusCalFrontEnd {
+    fn name(&self) -> &'static str {
+        "PlusCal"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tla", "pcal"]
+    }
+
+    fn load(&self, path: &Path) -> Result<LoadedNS, String> {
+        let content =
+            std::fs::read_to_string(path).map_err(|err| format!("Error reading file: {}", err))?;
+        let mut table = ExprHc::new();
+        let program = compile(&content, &mut table)?;
+        Ok(LoadedNS::Program(crate::expr_to_ns::program_to_ns(
+            &mut table, &program,
+        )))
+    }
+
+    fn load_program(&self, path: &Path, table: &mut ExprHc) -> Option<Result<Program, String>> {
+        Some(
+            std::fs::read_to_string(path)
+                .map_err(|err| format!("Error reading file: {}", err))
+                .and_then(|content| compile(&content, table)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_lost_update() {
+        let source = std::fs::read_to_string("examples/pluscal/lost_update.tla").unwrap();
+        let mut table = ExprHc::new();
+        let program = compile(&source, &mut table).unwrap();
+        assert_eq!(program.globals, vec![("Counter".to_string(), 0)]);
+        assert_eq!(program.requests.len(), 1);
+        assert_eq!(program.requests[0].name, "Increment");
+        assert_eq!(
+            program.requests[0].body.to_string(),
+            "tmp := 0; response := 0; tmp := Counter; yield; Counter := tmp + 1; response := tmp + 1; response"
+        );
+    }
+
+    #[test]
+    fn test_compile_await_either_and_loops() {
+        let source = "--algorithm a
+            variables lock = FALSE;
+            fair process P = 1
+            begin
+              Acquire: await ~lock; lock := TRUE;
+              Loop: while lock do
+                either lock := FALSE or skip end either;
+              end while;
+            end process;
+            end algorithm";
+        let mut table = ExprHc::new();
+        let program = compile(source, &mut table).unwrap();
+        assert_eq!(program.globals, vec![("Lock".to_string(), 0)]);
+        assert_eq!(
+            program.requests[0].body.to_string(),
+            "while(!!Lock){ yield }; Lock := 1; yield; while(Lock){ if(?){Lock := 0}else{0}; yield }; 0"
+        );
+
+        // await in the middle of a step would make the step non-atomic
+        let mid_step = source.replace(
+            "Acquire: await ~lock; lock := TRUE;",
+            "A: lock := TRUE; await lock;",
+        );
+        assert!(
+            compile(&mid_step, &mut table)
+                .unwrap_err()
+                .contains("await")
+        );
+        let undeclared = source.replace("lock := TRUE", "held := TRUE");
+        assert!(
+            compile(&undeclared, &mut table)
+                .unwrap_err()
+                .contains("held")
+        );
+        assert!(compile("--algorithm a begin end algorithm", &mut table).is_err());
+    }
+}
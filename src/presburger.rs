@@ -1,21 +1,25 @@
 // Use the ISL bindings from the isl module
+pub use crate::constraints::{Constraint, ConstraintType, QuantifiedSet, Variable};
+use crate::deterministic_map::HashMap;
 use crate::isl;
+use crate::isl_trace::record;
+use crate::var_map::VarMap;
+use std::cell::RefCell;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::{
     collections::BTreeSet,
-    ffi::{CStr, CString, c_uint},
+    ffi::{CString, c_uint},
     fmt::{self, Display},
     ptr,
 };
 
 use crate::kleene::Kleene;
-use either::Either;
 
 #[derive(Debug)]
 pub struct PresburgerSet<T> {
     isl_set: *mut isl::isl_set, // raw pointer to the underlying ISL set
-    mapping: Vec<T>,            // mapping of dimensions to atoms of type T
+    mapping: VarMap<T>,         // mapping of dimensions to atoms of type T
 }
 
 // Ensure the ISL set is freed when PresburgerSet goes out of scope
@@ -23,28 +27,44 @@ impl<T> Drop for PresburgerSet<T> {
     fn drop(&mut self) {
         if !self.isl_set.is_null() {
             unsafe { isl::isl_set_free(self.isl_set) }; // free the ISL set pointer
+            isl::track_isl_object(isl::IslObject::Set, -1);
         }
     }
 }
 
+impl<T> PresburgerSet<T> {
+    /// Wrap an ISL set, which the new set owns
+    fn from_raw(isl_set: *mut isl::isl_set, mapping: VarMap<T>) -> Self {
+        if !isl_set.is_null() {
+            isl::track_isl_object(isl::IslObject::Set, 1);
+        }
+        PresburgerSet { isl_set, mapping }
+    }
+
+    /// Give up ownership of the ISL set, e.g. after an ISL function consumed it, so
+    /// that `drop` does not free it again
+    fn disown(&mut self) -> *mut isl::isl_set {
+        let isl_set = std::mem::replace(&mut self.isl_set, ptr::null_mut());
+        if !isl_set.is_null() {
+            isl::track_isl_object(isl::IslObject::Set, -1);
+        }
+        isl_set
+    }
+}
+
 impl<T: Clone> Clone for PresburgerSet<T> {
     fn clone(&self) -> Self {
         let new_ptr = unsafe { isl::isl_set_copy(self.isl_set) }; // increment refcount or duplicate&#8203;:contentReference[oaicite:1]{index=1}
-        PresburgerSet {
-            isl_set: new_ptr,
-            mapping: self.mapping.clone(),
-        }
+        PresburgerSet::from_raw(new_ptr, self.mapping.clone())
     }
 }
 
 impl<T: Ord + Eq + Clone + Debug + ToString> PresburgerSet<T> {
     pub fn harmonize(&mut self, other: &mut PresburgerSet<T>) {
-        // 1. Determine the combined, sorted mapping
-        let mut combined_atoms: BTreeSet<T> = BTreeSet::new();
-        for atom in self.mapping.iter().chain(other.mapping.iter()) {
-            combined_atoms.insert(atom.clone());
-        }
-        let combined_mapping: Vec<T> = combined_atoms.into_iter().collect();
+        // 1. Determine the combined mapping, in the order of `dim_order`
+        self.reorder();
+        other.reorder();
+        let combined_mapping = self.mapping.union(&other.mapping);
 
         // 2. Early exit if already harmonized
         if self.mapping == combined_mapping && other.mapping == combined_mapping {
@@ -70,57 +90,108 @@ impl<T: Ord + Eq + Clone + Debug + ToString> PresburgerSet<T> {
         other.mapping = combined_mapping;
     }
 
+    /// Harmonize all sets at once: the combined mapping is computed once and each set
+    /// is embedded into it a single time, instead of re-embedding the accumulated
+    /// mapping at every step of a pairwise fold
+    pub fn harmonize_all(sets: &mut [PresburgerSet<T>]) {
+        for set in sets.iter_mut() {
+            set.reorder();
+        }
+        let combined_mapping = VarMap::union_all(sets.iter().map(|set| &set.mapping));
+
+        for set in sets.iter_mut() {
+            if set.mapping != combined_mapping {
+                set.isl_set = Self::embed_set_to_mapping(set.isl_set, &set.mapping, &combined_mapping);
+                set.mapping = combined_mapping.clone();
+            }
+        }
+    }
+
+    /// Combine harmonized sets with a binary ISL operation that consumes its arguments.
+    /// `None` if there are no sets.
+    fn combine_all(
+        mut sets: Vec<Self>,
+        name: &str,
+        op: unsafe extern "C" fn(*mut isl::isl_set, *mut isl::isl_set) -> *mut isl::isl_set,
+    ) -> Option<Self> {
+        Self::harmonize_all(&mut sets);
+        let mut iter = sets.into_iter();
+        let mut result = iter.next()?;
+        for mut set in iter {
+            result.isl_set = record(name, &[result.isl_set, set.isl_set], || unsafe {
+                op(result.isl_set, set.isl_set)
+            });
+            // The pointer was consumed by ISL
+            set.disown();
+        }
+        Some(result)
+    }
+
+    /// The union of all sets, harmonizing them once. `None` if there are no sets.
+    pub fn union_all(sets: Vec<Self>) -> Option<Self> {
+        Self::combine_all(sets, "union", isl::isl_set_union)
+    }
+
+    /// The intersection of all sets, harmonizing them once. `None` if there are no sets.
+    pub fn intersection_all(sets: Vec<Self>) -> Option<Self> {
+        Self::combine_all(sets, "intersect", isl::isl_set_intersect)
+    }
+
+    /// Bring the dimensions into the order of `dim_order`, so that embedding the set
+    /// into a bigger mapping only has to insert dimensions
+    fn reorder(&mut self) {
+        let mut ordered = self.mapping.to_vec();
+        crate::dim_order::sort_atoms(&mut ordered);
+        let ordered = VarMap::new(ordered);
+        if ordered == self.mapping {
+            return;
+        }
+        let ma = AffineMap::identity(&self.mapping).to_isl_multi_aff(&self.mapping, &ordered);
+        self.isl_set = unsafe { isl::set_apply(self.isl_set, isl::map_from_multi_aff(ma)) };
+        self.mapping = ordered;
+    }
+
     /// Embed a set from its current mapping into a target mapping using direct ISL operations
     fn embed_set_to_mapping(
         mut isl_set: *mut isl::isl_set,
-        current_mapping: &[T],
-        target_mapping: &[T],
+        current_mapping: &VarMap<T>,
+        target_mapping: &VarMap<T>,
     ) -> *mut isl::isl_set {
-        unsafe {
-            // Algorithm:
-            // 1. For each atom in target_mapping not in current_mapping:
-            //    - Find its position in target_mapping
-            //    - Insert a dimension at that position
-            //    - Constrain that dimension to 0
-            // 2. Handle dimension reordering if needed
-
-            let mut current_pos = 0; // Position in the evolving set
-
-            for (target_pos, target_atom) in target_mapping.iter().enumerate() {
-                if current_mapping.contains(target_atom) {
-                    // This atom exists in current mapping
-                    // Check if it's in the right position
-                    if current_pos < current_mapping.len()
-                        && &current_mapping[current_pos] == target_atom
-                    {
-                        // Atom is in correct position, advance
-                        current_pos += 1;
-                    } else {
-                        // Atom exists but in wrong position - we'd need to reorder
-                        // For now, assume mappings are in sorted order so this shouldn't happen
-                        // If it does, we'll need more complex reordering logic
-                        current_pos += 1;
-                    }
-                } else {
-                    // This atom is missing from current mapping
-                    // Insert a dimension at target_pos and constrain it to 0
-                    isl_set = isl::isl_set_insert_dims(
-                        isl_set,
-                        isl::isl_dim_type_isl_dim_set,
-                        target_pos as c_uint,
-                        1,
-                    );
-                    isl_set = isl::isl_set_fix_si(
-                        isl_set,
-                        isl::isl_dim_type_isl_dim_set,
-                        target_pos as c_uint,
-                        0,
-                    );
-                }
+        // `harmonize` reorders both sets first, so only dimensions have to be inserted
+        let embedding = current_mapping
+            .embedding(target_mapping)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Cannot embed mapping {:?} into {:?}",
+                    current_mapping, target_mapping
+                )
+            });
+        let mut present = vec![false; target_mapping.len()];
+        for dim in embedding {
+            present[dim] = true;
+        }
+        // Insert a dimension constrained to 0 for every atom missing from the set, in
+        // increasing order so the dimensions before it are already in place
+        for (target_pos, &present) in present.iter().enumerate() {
+            if present {
+                continue;
+            }
+            unsafe {
+                isl_set = isl::isl_set_insert_dims(
+                    isl_set,
+                    isl::isl_dim_type_isl_dim_set,
+                    target_pos as c_uint,
+                    1,
+                );
+                isl_set = isl::isl_set_fix_si(
+                    isl_set,
+                    isl::isl_dim_type_isl_dim_set,
+                    target_pos as c_uint,
+                    0,
+                );
             }
-
-            isl_set
         }
+        isl_set
     }
 }
 
@@ -135,10 +206,8 @@ impl<T: Clone + ToString> PresburgerSet<T> {
         // This represents a unit vector for this atom
         set_ptr = unsafe { isl::isl_set_fix_si(set_ptr, isl::isl_dim_type_isl_dim_set, 0, 1) };
 
-        PresburgerSet {
-            isl_set: set_ptr,
-            mapping: vec![atom], // one dimension corresponding to the single atom
-        }
+        // One dimension corresponding to the single atom
+        PresburgerSet::from_raw(set_ptr, VarMap::new(vec![atom]))
     }
 
     /// Rename all variables in this PresburgerSet using the provided function
@@ -151,13 +220,10 @@ impl<T: Clone + ToString> PresburgerSet<T> {
         F: Fn(T) -> U,
     {
         // Take ownership of both the ISL set pointer and mapping to avoid double-free
-        let isl_set = std::mem::replace(&mut self.isl_set, std::ptr::null_mut());
+        let isl_set = self.disown();
         let mapping = std::mem::take(&mut self.mapping);
 
-        PresburgerSet {
-            isl_set,
-            mapping: mapping.into_iter().map(f).collect(),
-        }
+        PresburgerSet::from_raw(isl_set, mapping.map(f))
     }
 
     /// Iterate over all variables in the mapping
@@ -191,10 +257,7 @@ impl<T: Clone> PresburgerSet<T> {
                 )
             };
         }
-        PresburgerSet {
-            isl_set: set_ptr,
-            mapping: atoms,
-        }
+        PresburgerSet::from_raw(set_ptr, VarMap::new(atoms))
     }
 }
 
@@ -208,15 +271,14 @@ impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
         let unified_mapping = a.mapping.clone();
         // Perform the union operation on the underlying isl_set pointers.
         // We pass ownership of a.isl_set and b.isl_set to isl_set_union (so they will be used and freed inside).
-        let result_ptr = unsafe { isl::isl_set_union(a.isl_set, b.isl_set) };
+        let result_ptr = record("union", &[a.isl_set, b.isl_set], || unsafe {
+            isl::isl_set_union(a.isl_set, b.isl_set)
+        });
         // Prevent a and b from freeing the now-consumed pointers in their Drop
-        a.isl_set = ptr::null_mut();
-        b.isl_set = ptr::null_mut();
+        a.disown();
+        b.disown();
         // Wrap the result pointer in a new PresburgerSet
-        PresburgerSet {
-            isl_set: result_ptr,
-            mapping: unified_mapping,
-        }
+        PresburgerSet::from_raw(result_ptr, unified_mapping)
     }
 
     pub fn intersection(&self, other: &Self) -> Self {
@@ -224,13 +286,12 @@ impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
         let mut b = other.clone();
         a.harmonize(&mut b);
         let unified_mapping = a.mapping.clone();
-        let result_ptr = unsafe { isl::isl_set_intersect(a.isl_set, b.isl_set) };
-        a.isl_set = ptr::null_mut();
-        b.isl_set = ptr::null_mut();
-        PresburgerSet {
-            isl_set: result_ptr,
-            mapping: unified_mapping,
-        }
+        let result_ptr = record("intersect", &[a.isl_set, b.isl_set], || unsafe {
+            isl::isl_set_intersect(a.isl_set, b.isl_set)
+        });
+        a.disown();
+        b.disown();
+        PresburgerSet::from_raw(result_ptr, unified_mapping)
     }
 
     pub fn difference(&self, other: &Self) -> Self {
@@ -238,42 +299,118 @@ impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
         let mut b = other.clone();
         a.harmonize(&mut b);
         let unified_mapping = a.mapping.clone();
-        let result_ptr = unsafe { isl::isl_set_subtract(a.isl_set, b.isl_set) };
-        a.isl_set = ptr::null_mut();
-        b.isl_set = ptr::null_mut();
-        PresburgerSet {
-            isl_set: result_ptr,
-            mapping: unified_mapping,
-        }
+        let result_ptr = record("subtract", &[a.isl_set, b.isl_set], || unsafe {
+            isl::isl_set_subtract(a.isl_set, b.isl_set)
+        });
+        a.disown();
+        b.disown();
+        PresburgerSet::from_raw(result_ptr, unified_mapping)
     }
 
     /// Useful for existential quantification. If you want the set of N-tuples `exists t, blah`:
     ///
     ///  * First, you make a set of N+1-tuples, where `t` is a component
-    ///  * Then, you call `.project_out(t)` to get the set of N-tuples, without `t`
+    ///  * Then, you call `.project_out_strict(t)` to get the set of N-tuples, without `t`
     ///
-    /// See also `project_out_test` below
-    pub fn project_out(mut self, variable: T) -> Self {
+    /// Returns an error if `variable` does not occur in the set, which usually means
+    /// that the set was built over the wrong variables. See also `project_out_test`
+    /// below.
+    pub fn project_out_strict(self, variable: T) -> Result<Self, String> {
+        self.project_out_many_strict(&[variable])
+    }
+
+    /// Like `project_out_strict`, but a variable that does not occur in the set is
+    /// skipped, for callers that project out variables the set may not have. Skipped
+    /// variables are logged to the debug report.
+    pub fn project_out_lenient(mut self, variable: T) -> Self {
         // look for the variable in our mapping
-        match self.mapping.iter().position(|x| *x == variable) {
+        match self.mapping.index_of(&variable) {
             Some(idx) => {
                 // found: project it out of the ISL set
-                unsafe {
-                    self.isl_set = isl::isl_set_project_out(
+                self.isl_set = record("project_out", &[self.isl_set], || unsafe {
+                    isl::isl_set_project_out(
                         self.isl_set,
                         isl::isl_dim_type_isl_dim_set,
                         idx as u32,
                         1,
-                    );
-                }
+                    )
+                });
                 // remove it from our mapping
-                self.mapping.remove(idx);
+                self.mapping.remove_dims(idx..idx + 1);
+            }
+            None => self.log_skipped(&[&variable]),
+        }
+        self
+    }
+
+    /// Project out several variables at once. Like `project_out_lenient`, variables
+    /// that do not occur in the set are skipped and logged.
+    pub fn project_out_many_lenient(self, variables: impl IntoIterator<Item = T>) -> Self {
+        let variables: Vec<T> = variables.into_iter().collect();
+        let missing = self.missing(&variables);
+        if !missing.is_empty() {
+            self.log_skipped(&missing);
+        }
+        self.project_matching(|t| variables.contains(t))
+    }
+
+    /// Project out every variable for which `matches` holds, e.g. all temporaries
+    /// introduced for existential quantifiers.
+    pub fn project_matching(mut self, matches: impl Fn(&T) -> bool) -> Self {
+        // Walk backwards so the indices of the remaining dimensions stay valid, and
+        // project out each run of adjacent matching dimensions with a single ISL call
+        let mut end = self.mapping.len();
+        while end > 0 {
+            if !matches(&self.mapping[end - 1]) {
+                end -= 1;
+                continue;
             }
-            None => {
+            let mut start = end - 1;
+            while start > 0 && matches(&self.mapping[start - 1]) {
+                start -= 1;
             }
+            self.isl_set = record("project_out", &[self.isl_set], || unsafe {
+                isl::isl_set_project_out(
+                    self.isl_set,
+                    isl::isl_dim_type_isl_dim_set,
+                    start as u32,
+                    (end - start) as u32,
+                )
+            });
+            self.mapping.remove_dims(start..end);
+            end = start;
         }
         self
     }
+
+    /// Project out several variables at once. Like `project_out_strict`, returns an
+    /// error if any of the variables does not occur in the set.
+    pub fn project_out_many_strict(self, variables: &[T]) -> Result<Self, String> {
+        let missing = self.missing(variables);
+        if !missing.is_empty() {
+            return Err(format!(
+                "Cannot project out {:?}: not in the set's variables {:?}",
+                missing, self.mapping
+            ));
+        }
+        Ok(self.project_matching(|t| variables.contains(t)))
+    }
+
+    /// The variables that do not occur in the set
+    fn missing<'a>(&self, variables: &'a [T]) -> Vec<&'a T> {
+        variables
+            .iter()
+            .filter(|v| !self.mapping.contains(v))
+            .collect()
+    }
+
+    fn log_skipped(&self, missing: &[&T]) {
+        crate::reachability::get_debug_logger().step(
+            "Lenient projection",
+            "Skipped variables that are not in the set",
+            &format!("{:?} not in {:?}", missing, self.mapping),
+        );
+    }
 }
 
 /// Test for `PresburgerSet::project_out`: create the set of even numbers
@@ -291,7 +428,7 @@ fn project_out_test() {
     let ps = PresburgerSet::from_quantified_sets(&[qs], vec!["x", "y"]);
 
     // `evens` is the set { x | exists y, x = 2y }
-    let evens = ps.project_out("y");
+    let evens = ps.project_out_strict("y").unwrap();
     println!("{evens}");
 
     // Test we got the right thing by comparing to a QuantifiedSet
@@ -306,13 +443,245 @@ fn project_out_test() {
     );
 }
 
+/// The projections of several variables at once agree with projecting out one
+/// variable at a time, and only the strict ones fail for missing variables
+#[test]
+fn project_out_many_test() {
+    let vars = vec!["a", "x", "b", "y"];
+    // { (a,x,b,y) | a = x + y, b = 2x }
+    let qs = QuantifiedSet::new(vec![
+        Constraint {
+            linear_combination: vec![(1, Variable::Var("a")), (-1, Variable::Var("x")), (-1, Variable::Var("y"))],
+            constant_term: 0,
+            constraint_type: ConstraintType::EqualToZero,
+        },
+        Constraint {
+            linear_combination: vec![(1, Variable::Var("b")), (-2, Variable::Var("x"))],
+            constant_term: 0,
+            constraint_type: ConstraintType::EqualToZero,
+        },
+    ]);
+    let ps = PresburgerSet::from_quantified_sets(&[qs], vars);
+
+    let one_by_one = ps
+        .clone()
+        .project_out_strict("x")
+        .unwrap()
+        .project_out_strict("y")
+        .unwrap();
+    assert_eq!(
+        ps.clone()
+            .project_out_many_lenient(vec!["x", "y", "missing"]),
+        one_by_one
+    );
+    assert_eq!(ps.clone().project_matching(|v| *v == "x" || *v == "y"), one_by_one);
+    assert_eq!(
+        ps.clone().project_out_many_strict(&["y", "x"]).unwrap(),
+        one_by_one
+    );
+    // Adjacent dimensions are projected together
+    let adjacent = ps.clone().project_matching(|v| *v == "a" || *v == "x");
+    assert_eq!(
+        adjacent,
+        ps.clone().project_out_lenient("a").project_out_lenient("x")
+    );
+
+    let err = ps.clone().project_out_many_strict(&["x", "z"]).unwrap_err();
+    assert!(err.contains("\"z\""), "{}", err);
+    assert!(ps.clone().project_out_strict("z").is_err());
+    assert_eq!(ps.clone().project_out_lenient("z"), ps);
+}
+
+/// An affine map between vectors indexed by atoms: each output atom is an affine
+/// expression `Σ coeff·input + constant` over the input atoms. Output atoms that are
+/// not listed are 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffineMap<T, U> {
+    pub outputs: Vec<(U, Vec<(i32, T)>, i32)>,
+}
+
+impl<T: Clone + Ord> AffineMap<T, T> {
+    /// The identity on the given atoms
+    pub fn identity(atoms: &[T]) -> Self {
+        AffineMap {
+            outputs: atoms
+                .iter()
+                .map(|a| (a.clone(), vec![(1, a.clone())], 0))
+                .collect(),
+        }
+    }
+
+    /// Add `delta` to the given output atom (adding it as an identity output if needed)
+    pub fn with_offset(mut self, atom: T, delta: i32) -> Self {
+        match self.outputs.iter_mut().find(|(u, _, _)| *u == atom) {
+            Some((_, _, constant)) => *constant += delta,
+            None => self.outputs.push((atom.clone(), vec![(1, atom)], delta)),
+        }
+        self
+    }
+}
+
+impl<T: Clone + Ord, U: Clone + Ord> AffineMap<T, U> {
+    /// All input atoms the map refers to, sorted
+    pub fn input_atoms(&self) -> Vec<T> {
+        let mut atoms: Vec<T> = self
+            .outputs
+            .iter()
+            .flat_map(|(_, terms, _)| terms.iter().map(|(_, t)| t.clone()))
+            .collect();
+        atoms.sort();
+        atoms.dedup();
+        atoms
+    }
+
+    /// Build the ISL multi_aff from the `domain` dimensions to the `range` dimensions.
+    /// Range atoms without an output expression are mapped to 0.
+    fn to_isl_multi_aff(&self, domain: &VarMap<T>, range: &VarMap<U>) -> *mut isl::isl_multi_aff {
+        unsafe {
+            let ctx = isl::get_ctx();
+            let domain_space = isl::isl_space_set_alloc(ctx, 0, domain.len() as c_uint);
+            let range_space = isl::isl_space_set_alloc(ctx, 0, range.len() as c_uint);
+            let map_space = isl::isl_space_map_from_domain_and_range(
+                isl::isl_space_copy(domain_space),
+                range_space,
+            );
+            let ls = isl::isl_local_space_from_space(domain_space);
+            let mut aff_list = isl::isl_aff_list_alloc(ctx, range.len() as i32);
+            for u in range {
+                let mut aff = isl::isl_aff_zero_on_domain(isl::isl_local_space_copy(ls));
+                if let Some((_, terms, constant)) = self.outputs.iter().find(|(o, _, _)| o == u) {
+                    // Sum up the coefficients first, an input atom may occur more than once
+                    let mut coefficients = vec![0; domain.len()];
+                    for (coeff, t) in terms {
+                        let idx = domain
+                            .index_of(t)
+                            .expect("affine map input atom missing from the domain");
+                        coefficients[idx] += coeff;
+                    }
+                    aff = isl::isl_aff_set_constant_si(aff, *constant);
+                    for (idx, coeff) in coefficients.into_iter().enumerate() {
+                        if coeff != 0 {
+                            aff = isl::isl_aff_set_coefficient_si(
+                                aff,
+                                isl::isl_dim_type_isl_dim_in,
+                                idx as i32,
+                                coeff,
+                            );
+                        }
+                    }
+                }
+                aff_list = isl::isl_aff_list_add(aff_list, aff);
+            }
+            isl::isl_local_space_free(ls);
+            isl::isl_multi_aff_from_aff_list(map_space, aff_list)
+        }
+    }
+}
+impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
+    /// The image `{ f(x) | x ∈ self }` of the set under an affine map.
+    ///
+    /// The result is over exactly the output atoms of `f`. Note that the image of a
+    /// set of nonnegative vectors may contain negative entries (e.g. when `f`
+    /// subtracts a token); intersect with a universe if that matters.
+    pub fn apply_affine_map<U>(&self, f: &AffineMap<T, U>) -> PresburgerSet<U>
+    where
+        U: Eq + Clone + Ord + Debug + ToString,
+    {
+        // Make sure every input atom of f is a dimension of the set
+        let mut set = self.clone();
+        let mut inputs = PresburgerSet::universe(f.input_atoms());
+        set.harmonize(&mut inputs);
+
+        let range = VarMap::sorted(f.outputs.iter().map(|(u, _, _)| u.clone()));
+
+        let ma = f.to_isl_multi_aff(&set.mapping, &range);
+        let result_ptr = record("apply", &[set.isl_set], || unsafe {
+            isl::set_apply(set.isl_set, isl::map_from_multi_aff(ma))
+        });
+        set.disown();
+        PresburgerSet::from_raw(result_ptr, range)
+    }
+
+    /// The preimage `{ x ∈ ℤ^domain | f(x) ∈ self }` of the set under an affine map.
+    ///
+    /// `domain` lists the input atoms of the result (the input atoms of `f` are added
+    /// if missing). Atoms of the set that are not outputs of `f` are taken to be 0.
+    pub fn preimage_affine_map<U>(&self, f: &AffineMap<U, T>, domain: &[U]) -> PresburgerSet<U>
+    where
+        U: Eq + Clone + Ord + Debug + ToString,
+    {
+        let domain = VarMap::sorted(domain.iter().cloned().chain(f.input_atoms()));
+
+        // Make sure every output atom of f is a dimension of the set
+        let mut set = self.clone();
+        let mut outputs = PresburgerSet::universe(f.outputs.iter().map(|(t, _, _)| t.clone()).collect());
+        set.harmonize(&mut outputs);
+
+        let ma = f.to_isl_multi_aff(&domain, &set.mapping);
+        let result_ptr = record("preimage", &[set.isl_set], || unsafe {
+            isl::isl_set_preimage_multi_aff(set.isl_set, ma)
+        });
+        set.disown();
+        PresburgerSet::from_raw(result_ptr, domain)
+    }
+}
+
+/// Moving a token with `apply_affine_map` and undoing it with `preimage_affine_map`
+#[test]
+fn affine_map_test() {
+    // { (a, b) | a = 1, b = 2 }
+    let set = PresburgerSet::atom("a").times(PresburgerSet::atom("b").times(PresburgerSet::atom("b")));
+
+    // Move one token from a to b
+    let f = AffineMap::identity(&["a", "b"]).with_offset("a", -1).with_offset("b", 1);
+    let moved = set.apply_affine_map(&f);
+    let bbb = PresburgerSet::atom("b")
+        .times(PresburgerSet::atom("b"))
+        .times(PresburgerSet::atom("b"));
+    assert_eq!(moved, bbb);
+
+    // The preimage under the same move gives back the original set
+    assert_eq!(moved.preimage_affine_map(&f, &["a", "b"]), set);
+
+    // Projection onto a single atom: (a, b) -> a + b
+    let sum = AffineMap {
+        outputs: vec![("n", vec![(1, "a"), (1, "b")], 0)],
+    };
+    let total = set.apply_affine_map(&sum);
+    let three = PresburgerSet::atom("n").times(PresburgerSet::atom("n")).times(PresburgerSet::atom("n"));
+    assert_eq!(total, three);
+}
+
+#[test]
+fn harmonize_all_test() {
+    let sets = vec![
+        PresburgerSet::atom("c"),
+        PresburgerSet::atom("a").times(PresburgerSet::atom("b")),
+        PresburgerSet::universe(vec!["b", "d"]),
+    ];
+    let folded_union = sets[0].union(&sets[1]).union(&sets[2]);
+    let folded_intersection = sets[0].intersection(&sets[1]).intersection(&sets[2]);
+
+    let mut harmonized = sets.clone();
+    PresburgerSet::harmonize_all(&mut harmonized);
+    for set in &harmonized {
+        assert_eq!(set.mapping, vec!["a", "b", "c", "d"]);
+    }
+
+    assert_eq!(PresburgerSet::union_all(sets.clone()).unwrap(), folded_union);
+    assert_eq!(PresburgerSet::intersection_all(sets).unwrap(), folded_intersection);
+    assert!(PresburgerSet::<&str>::union_all(Vec::new()).is_none());
+}
+
 impl<T: Eq + Clone + Ord + Debug + ToString> PartialEq for PresburgerSet<T> {
     fn eq(&self, other: &Self) -> bool {
         let mut a = self.clone();
         let mut b = other.clone();
         a.harmonize(&mut b);
         // isl_set_is_equal returns isl_bool (1 = true, 0 = false, -1 = error)
-        let result_bool = unsafe { isl::isl_set_is_equal(a.isl_set, b.isl_set) };
+        let result_bool = record("is_equal", &[a.isl_set, b.isl_set], || unsafe {
+            isl::isl_set_is_equal(a.isl_set, b.isl_set)
+        });
         // No need to null out a.isl_set and b.isl_set here, because is_equal does not consume (it uses __isl_keep).
         // We can directly drop a and b, which will free their pointers.
         result_bool == 1 // return true if ISL indicated equality (isl_bool_true)
@@ -324,14 +693,99 @@ impl<T: Eq + Clone + Ord + Debug + ToString> Eq for PresburgerSet<T> {}
 // Implement .is_empty() for PresburgerSet<T>
 impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
     pub fn is_empty(&self) -> bool {
-        unsafe { isl::isl_set_is_empty(self.isl_set) == 1 }
+        record("is_empty", &[self.isl_set], || unsafe {
+            isl::isl_set_is_empty(self.isl_set) == 1
+        })
+    }
+}
+
+// Counting the elements of a set
+impl<T: Eq + Clone + Ord + Debug + ToString> PresburgerSet<T> {
+    /// Whether the set has finitely many elements
+    pub fn is_bounded(&self) -> bool {
+        record("is_bounded", &[self.isl_set], || unsafe {
+            isl::isl_set_is_bounded(self.isl_set) == 1
+        })
+    }
+
+    /// The number of elements of the set, or `None` if it is unbounded
+    pub fn count(&self) -> Option<u64> {
+        if !self.is_bounded() {
+            return None;
+        }
+        Some(record("count", &[self.isl_set], || {
+            count_points(self.isl_set)
+        }))
+    }
+
+    /// The number of elements with nonnegative atoms that sum to at most `bound`. For a
+    /// set of completed multisets, these are the multisets of at most `bound` requests.
+    pub fn count_up_to(&self, bound: u32) -> u64
+    where
+        T: Display,
+    {
+        let atoms = self.mapping.to_vec();
+        let mut constraints: Vec<Constraint<Variable<T>>> = atoms
+            .iter()
+            .map(|atom| {
+                Constraint::new(
+                    vec![(1, Variable::Var(atom.clone()))],
+                    0,
+                    ConstraintType::NonNegative,
+                )
+            })
+            .collect();
+        constraints.push(Constraint::new(
+            atoms
+                .iter()
+                .map(|atom| (-1, Variable::Var(atom.clone())))
+                .collect(),
+            i32::try_from(bound).unwrap_or(i32::MAX),
+            ConstraintType::NonNegative,
+        ));
+        let up_to = PresburgerSet::from_quantified_sets(&[QuantifiedSet::new(constraints)], atoms);
+        self.intersection(&up_to)
+            .count()
+            .expect("sets of nonnegative atoms with a bounded sum are bounded")
+    }
+}
+
+/// The number of points of a bounded ISL set, with `isl_set_count_val` if the linked
+/// ISL has it and by enumerating the points otherwise
+fn count_points(set: *mut isl::isl_set) -> u64 {
+    #[cfg(isl_has_set_count_val)]
+    unsafe {
+        let val = isl::isl_set_count_val(set);
+        let count = isl::isl_val_get_num_si(val);
+        isl::isl_val_free(val);
+        count as u64
+    }
+    #[cfg(not(isl_has_set_count_val))]
+    unsafe {
+        extern "C" fn count_point(
+            point: *mut isl::isl_point,
+            user: *mut std::os::raw::c_void,
+        ) -> isl::isl_stat {
+            unsafe {
+                isl::isl_point_free(point);
+                *(user as *mut u64) += 1;
+            }
+            0 // isl_stat_ok
+        }
+        let mut count: u64 = 0;
+        isl::isl_set_foreach_point(
+            set,
+            Some(count_point),
+            &mut count as *mut u64 as *mut std::os::raw::c_void,
+        );
+        count
     }
 }
 
 // Implementing display for PresburgerSet<T> using ISL's to_str function
 impl<T: Display> Display for PresburgerSet<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let str: *mut i8 = unsafe { isl::isl_set_to_str(self.isl_set) };
+        let set_str = unsafe { isl::IslSetRef::new(self.isl_set) };
         let mapping_str = self
             .mapping
             .iter()
@@ -341,7 +795,7 @@ impl<T: Display> Display for PresburgerSet<T> {
         write!(
             f,
             "{} (mapping: {})",
-            unsafe { CStr::from_ptr(str).to_string_lossy() },
+            set_str,
             mapping_str
         )
     }
@@ -352,10 +806,7 @@ impl<T: Eq + Clone + Ord + Debug + ToString> Kleene for PresburgerSet<T> {
         // For a Kleene algebra, zero represents the empty set
         let space = unsafe { isl::isl_space_set_alloc(isl::get_ctx(), 0, 0) };
         let set_ptr = unsafe { isl::isl_set_empty(space) };
-        PresburgerSet {
-            isl_set: set_ptr,
-            mapping: Vec::new(),
-        }
+        PresburgerSet::from_raw(set_ptr, VarMap::empty())
     }
 
     fn one() -> Self {
@@ -365,10 +816,7 @@ impl<T: Eq + Clone + Ord + Debug + ToString> Kleene for PresburgerSet<T> {
         // Create a universe (all points), then constrain it to just the origin (0)
         let set_ptr = unsafe { isl::isl_set_universe(space) };
 
-        PresburgerSet {
-            isl_set: set_ptr,
-            mapping: Vec::new(),
-        }
+        PresburgerSet::from_raw(set_ptr, VarMap::empty())
     }
 
     fn plus(self, other: Self) -> Self {
@@ -382,13 +830,12 @@ impl<T: Eq + Clone + Ord + Debug + ToString> Kleene for PresburgerSet<T> {
         let mut b = other.clone();
         a.harmonize(&mut b);
         let unified_mapping = a.mapping.clone();
-        let result_ptr = unsafe { isl::isl_set_sum(a.isl_set, b.isl_set) };
-        a.isl_set = ptr::null_mut();
-        b.isl_set = ptr::null_mut();
-        PresburgerSet {
-            isl_set: result_ptr,
-            mapping: unified_mapping,
-        }
+        let result_ptr = record("sum", &[a.isl_set, b.isl_set], || unsafe {
+            isl::isl_set_sum(a.isl_set, b.isl_set)
+        });
+        a.disown();
+        b.disown();
+        PresburgerSet::from_raw(result_ptr, unified_mapping)
     }
 
     fn star(self) -> Self {
@@ -398,377 +845,112 @@ impl<T: Eq + Clone + Ord + Debug + ToString> Kleene for PresburgerSet<T> {
     }
 }
 
-// The types below are to convert an ISL to a Rust type that can be consumed by Rust code
-
-/// Represents an existentially quantified conjunction of linear constraints
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct QuantifiedSet<T> {
-    // The variables T are from the original set, and the variables usize are the existential variables
-    // The existential quantification is over *natural numbers* not integers
-    // Note: the existential quantification is at the top-level: it's an existential of conjunctions,
-    // not a conjunction of existentials.
-    constraints: Vec<Constraint<Variable<T>>>,
-}
-
-impl<T: Clone> QuantifiedSet<T> {
-    /// Create a new QuantifiedSet with the given constraints
-    pub fn new(constraints: Vec<Constraint<Variable<T>>>) -> Self {
-        QuantifiedSet { constraints }
-    }
-
-    /// Get the constraints in this quantified set
-    pub fn constraints(&self) -> &[Constraint<Variable<T>>] {
-        &self.constraints
-    }
-
-    pub fn extract_and_reify_existential_variables(
-        &self,
-    ) -> (Vec<Either<usize, T>>, Vec<Constraint<Either<usize, T>>>) {
-        // Collect all existential variables
-        let mut existential_vars = std::collections::BTreeSet::new();
-        for constraint in &self.constraints {
-            for (_, var) in &constraint.linear_combination {
-                if let Variable::Existential(n) = var {
-                    existential_vars.insert(*n);
-                }
-            }
-        }
-
-        // Convert existential variables to Either::Left format
-        let existential_places: Vec<Either<usize, T>> = existential_vars
-            .into_iter()
-            .map(|n| Either::Left(n))
-            .collect();
-
-        // Transform constraints by converting Variable<T> to Either<usize, T>
-        let transformed_constraints: Vec<Constraint<Either<usize, T>>> = self
-            .constraints
-            .iter()
-            .map(|constraint| {
-                let transformed_linear_combination: Vec<(i32, Either<usize, T>)> = constraint
-                    .linear_combination
-                    .iter()
-                    .map(|(coeff, var)| {
-                        let new_var = match var {
-                            Variable::Var(t) => Either::Right(t.clone()),
-                            Variable::Existential(n) => Either::Left(*n),
-                        };
-                        (*coeff, new_var)
-                    })
-                    .collect();
-
-                Constraint::new(
-                    transformed_linear_combination,
-                    constraint.constant_term,
-                    constraint.constraint_type,
-                )
-            })
-            .collect();
-
-        (existential_places, transformed_constraints)
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-pub enum Variable<T> {
-    Var(T),
-    Existential(usize),
-}
-
-// Pretty printing for Variable<T>: Var(T) is printed as V{T} and Existential(n) is printed as E{n}
-impl<T: Display> Display for Variable<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Variable::Var(t) => write!(f, "V{}", t),
-            Variable::Existential(n) => write!(f, "E{}", n),
-        }
-    }
-}
-
-impl<T> Variable<T> {
-    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Variable<U> {
-        match self {
-            Variable::Var(t) => Variable::Var(f(t)),
-            Variable::Existential(n) => Variable::Existential(n),
-        }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Constraint<T> {
-    linear_combination: Vec<(i32, T)>,
-    constant_term: i32,
-    constraint_type: ConstraintType,
-}
-
-impl<T> Constraint<T> {
-    /// Create a new constraint
-    pub fn new(
-        linear_combination: Vec<(i32, T)>,
-        constant_term: i32,
-        constraint_type: ConstraintType,
-    ) -> Self {
-        Constraint {
-            linear_combination,
-            constant_term,
-            constraint_type,
-        }
-    }
-
-    /// Get the linear combination of variables in this constraint
-    pub fn linear_combination(&self) -> &[(i32, T)] {
-        &self.linear_combination
-    }
-
-    /// Get the constant term in this constraint
-    pub fn constant_term(&self) -> i32 {
-        self.constant_term
-    }
-
-    /// Get the constraint type
-    pub fn constraint_type(&self) -> ConstraintType {
-        self.constraint_type
-    }
-
-    /// Extracts all variables from a clause that have constraints of the form "coeff*var = 0"
-    /// (EqualToZero with single variable and zero constant term, any coefficient)
-    pub fn extract_zero_variables(clause: &[Constraint<T>]) -> Vec<T>
-    where
-        T: Clone,
-    {
-        let mut zero_vars = Vec::new();
-
-        for constraint in clause {
-            if constraint.constraint_type == ConstraintType::EqualToZero
-                && constraint.linear_combination.len() == 1
-                && constraint.constant_term == 0
-            {
-                zero_vars.push(constraint.linear_combination[0].1.clone());
-            }
-        }
-
-        zero_vars
-    }
-
-    /// Extracts all variables from a clause that have constraints requiring them to be nonzero.
-    /// This includes:
-    /// - Variables with NonNegative constraints where constant_term < 0 (i.e., var >= positive_value)
-    /// - Any variables that appear in constraints that are not of the form "var = 0"
-    ///
-    /// This is the complement of extract_zero_variables and is useful for identifying
-    /// which variables must have nonzero values in the solution.
-    pub fn extract_nonzero_variables(clause: &[Constraint<T>]) -> Vec<T>
-    where
-        T: Clone + Eq + std::hash::Hash,
-    {
-        let mut nonzero_vars = Vec::new();
-        let mut seen_vars = std::collections::HashSet::new();
-
-        for constraint in clause {
-            match constraint.constraint_type {
-                ConstraintType::NonNegative => {
-                    // For constraints of the form: linear_combination + constant_term >= 0
-                    // If constant_term < 0, then linear_combination >= -constant_term > 0
-                    if constraint.constant_term < 0 {
-                        // All variables in this constraint must contribute to making it positive
-                        for (_, var) in &constraint.linear_combination {
-                            if seen_vars.insert(var.clone()) {
-                                nonzero_vars.push(var.clone());
-                            }
-                        }
-                    }
-                    // Note: If constant_term >= 0, the constraint might be satisfiable with zeros
-                }
-                ConstraintType::EqualToZero => {
-                    // Skip pure zero constraints (handled by extract_zero_variables)
-                    if constraint.linear_combination.len() == 1 && constraint.constant_term == 0 {
-                        continue;
-                    }
-                    // For more complex equality constraints, all variables might need to be nonzero
-                    // to satisfy the constraint (conservative approach)
-                    if constraint.constant_term != 0 || constraint.linear_combination.len() > 1 {
-                        for (_, var) in &constraint.linear_combination {
-                            if seen_vars.insert(var.clone()) {
-                                nonzero_vars.push(var.clone());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        nonzero_vars
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ConstraintType {
-    NonNegative,
-    EqualToZero,
-}
-
-// Pretty printing for Constraint<T>
-impl<T: Display> Display for Constraint<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Format each term in the linear combination
-        let mut first = true;
-        for &(coef, ref var) in &self.linear_combination {
-            if coef == 0 {
-                continue; // Skip zero coefficients
-            }
-
-            if !first {
-                if coef > 0 {
-                    write!(f, " + ")?;
-                } else {
-                    write!(f, " -")?;
-                }
-            } else if coef < 0 {
-                // First term and negative
-                write!(f, "-")?;
-            }
-
-            let abs_coef = coef.abs();
-            if abs_coef == 1 {
-                write!(f, "{}", var)?;
-            } else {
-                write!(f, "{}{}", abs_coef, var)?;
-            }
+// Implement conversions between SemilinearSet and PresburgerSet
 
-            first = false;
-        }
+use crate::semilinear::{LinearSet, SemilinearSet};
 
-        // Add the constant term if non-zero or if there are no terms
-        if self.constant_term != 0 || first {
-            if !first {
-                match self.constant_term.cmp(&0) {
-                    std::cmp::Ordering::Greater => write!(f, " + ")?,
-                    std::cmp::Ordering::Less => write!(f, " -")?,
-                    std::cmp::Ordering::Equal => {}
-                }
-            } else if self.constant_term < 0 {
-                // First term and negative
-                write!(f, "-")?;
-            }
+/// Most ISL sets kept by the conversion cache; it is emptied when it is full
+const CONVERSION_CACHE_CAPACITY: usize = 4096;
 
-            write!(f, "{}", self.constant_term.abs())?;
-        }
+/// An ISL set owned by the conversion cache
+struct CachedIslSet(*mut isl::isl_set);
 
-        // Add the constraint type
-        match self.constraint_type {
-            ConstraintType::NonNegative => write!(f, " ≥ 0"),
-            ConstraintType::EqualToZero => write!(f, " = 0"),
-        }
+impl Drop for CachedIslSet {
+    fn drop(&mut self) {
+        unsafe { isl::isl_set_free(self.0) };
     }
 }
 
-// Pretty printing for QuantifiedSet<T>
-impl<T: Display> Display for QuantifiedSet<T> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Check if there are existential variables
-        let has_existentials = self.constraints.iter().any(|c| {
-            c.linear_combination
-                .iter()
-                .any(|(_, var)| matches!(var, Variable::Existential(_)))
-        });
-
-        // Write existential quantifier if needed
-        if has_existentials {
-            write!(f, "∃ ")?;
-        }
-
-        // Write all constraints connected by '∧'
-        for (i, constraint) in self.constraints.iter().enumerate() {
-            if i > 0 {
-                write!(f, " ∧ ")?;
-            }
-            write!(f, "{}", constraint)?;
-        }
-
-        Ok(())
-    }
+thread_local! {
+    /// Converted semilinear sets by their ISL string. The string only numbers the
+    /// dimensions, so structurally equal sets over different atoms share an entry.
+    /// Thread-local like the ISL ctx the sets belong to.
+    static CONVERSION_CACHE: RefCell<HashMap<String, CachedIslSet>> =
+        RefCell::new(HashMap::default());
 }
 
-// Implement conversions between SemilinearSet and PresburgerSet
-
-use crate::semilinear::{LinearSet, SemilinearSet};
 impl<T: Clone + Ord + Debug + ToString + Eq + Hash> PresburgerSet<T> {
     /// Convert a SemilinearSet to a PresburgerSet
     ///
-    /// This processes each LinearSet component of the SemilinearSet and represents it
-    /// as a set of constraints in the PresburgerSet.
+    /// All LinearSet components are written over the combined keys as one ISL string,
+    /// a disjunction with one disjunct per component, which ISL parses at once instead
+    /// of parsing and unioning the components one by one. The same sets are converted
+    /// many times during the analysis and the certificate checks, so the parsed sets
+    /// are cached by their string.
     pub fn from_semilinear_set(semilinear_set: &SemilinearSet<T>) -> Self {
-        // First, collect all keys used in the semilinear set
-        let mut all_keys = BTreeSet::new();
-        for component in &semilinear_set.components {
-            // Add keys from the base vector
-            for key in component.base.values.keys() {
-                all_keys.insert(key.clone());
-            }
-
-            // Add keys from the period vectors
-            for period in &component.periods {
-                for key in period.values.keys() {
-                    all_keys.insert(key.clone());
-                }
-            }
-        }
-
-        // Convert BTreeSet to Vec for consistent ordering
-        let mapping: Vec<T> = all_keys.into_iter().collect();
-
-        // Create a context and an empty result set
         let ctx = isl::get_ctx();
-        let mut result_set: *mut isl::isl_set = std::ptr::null_mut();
 
-        // Process each linear set component
-        for component in &semilinear_set.components {
-            // Convert the linear set to an ISL set string and parse it
-            let set_string = generate_linear_set_string(component, &mapping);
+        // If no components, return the empty set
+        if semilinear_set.components.is_empty() {
+            let space = unsafe { isl::isl_space_set_alloc(ctx, 0, 0) };
+            return PresburgerSet::from_raw(unsafe { isl::isl_set_empty(space) }, VarMap::empty());
+        }
 
-            // Parse the ISL set string
-            let component_set = unsafe {
-                let cstr = CString::new(set_string).unwrap();
+        // Order the keys like all other sets (see `dim_order`)
+        let mapping = VarMap::sorted(semilinear_set.components.iter().flat_map(|component| {
+            std::iter::once(&component.base)
+                .chain(&component.periods)
+                .flat_map(|vector| vector.values.keys().cloned())
+        }));
+
+        let set_string = generate_semilinear_set_string(semilinear_set, &mapping);
+        let cached = CONVERSION_CACHE.with(|cache| {
+            cache
+                .borrow()
+                .get(&set_string)
+                .map(|set| unsafe { isl::isl_set_copy(set.0) })
+        });
+        let isl_set = cached.unwrap_or_else(|| {
+            let isl_set = record("read_from_str", &[], || unsafe {
+                let cstr = CString::new(set_string.as_str()).unwrap();
                 isl::isl_set_read_from_str(ctx, cstr.as_ptr())
-            };
-
-            // Union with the result set
-            unsafe {
-                if result_set.is_null() {
-                    result_set = component_set;
-                } else {
-                    result_set = isl::isl_set_union(result_set, component_set);
+            });
+            CONVERSION_CACHE.with(|cache| {
+                let mut cache = cache.borrow_mut();
+                if cache.len() >= CONVERSION_CACHE_CAPACITY {
+                    cache.clear();
                 }
-            }
-        }
-
-        // If no components, return the empty set
-        if result_set.is_null() || semilinear_set.components.is_empty() {
-            let space = unsafe { isl::isl_space_set_alloc(ctx, 0, mapping.len() as c_uint) };
-            result_set = unsafe { isl::isl_set_empty(space) };
-        }
+                cache.insert(
+                    set_string,
+                    CachedIslSet(unsafe { isl::isl_set_copy(isl_set) }),
+                );
+            });
+            isl_set
+        });
+        PresburgerSet::from_raw(isl_set, mapping)
+    }
+}
 
-        PresburgerSet {
-            isl_set: result_set,
-            mapping,
-        }
+/// Helper function to generate an ISL set string from a SemilinearSet, with one
+/// disjunct per component
+fn generate_semilinear_set_string<T: ToString + Clone + Ord + Eq + Hash>(
+    semilinear_set: &SemilinearSet<T>,
+    mapping: &[T],
+) -> String {
+    let dimensions = (0..mapping.len())
+        .map(|i| format!("p{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let disjuncts: Option<Vec<String>> = semilinear_set
+        .components
+        .iter()
+        .map(|component| generate_linear_set_formula(component, mapping))
+        .collect();
+    match disjuncts {
+        Some(disjuncts) => format!("{{ [{}] : ({}) }}", dimensions, disjuncts.join(") or (")),
+        // A component without constraints covers the whole space
+        None => format!("{{ [{}] }}", dimensions),
     }
 }
 
-/// Helper function to generate an ISL set string from a LinearSet
-fn generate_linear_set_string<T: ToString + Clone + Ord + Eq + Hash>(
+/// Helper function to generate the ISL formula of a LinearSet over the dimensions
+/// `p0, p1, ...` of `mapping`. `None` if the formula has no constraints.
+fn generate_linear_set_formula<T: ToString + Clone + Ord + Eq + Hash>(
     linear_set: &LinearSet<T>,
     mapping: &[T],
-) -> String {
+) -> Option<String> {
     let mut constraints = Vec::new();
 
-    // // Find indices for keys in the mapping
-    // let key_indices: HashMap<&T, usize> = mapping
-    //     .iter()
-    //     .enumerate()
-    //     .map(|(i, key)| (key, i))
-    //     .collect();
-
     // Generate constraints for each dimension in the mapping
     for (i, key) in mapping.iter().enumerate() {
         // Get base value for this key (0 if not present)
@@ -793,31 +975,21 @@ fn generate_linear_set_string<T: ToString + Clone + Ord + Eq + Hash>(
         constraints.push(format!("e{} >= 0", period_idx));
     }
 
-    // Build the complete ISL set string
-    if linear_set.periods.is_empty() {
+    if constraints.is_empty() {
+        None
+    } else if linear_set.periods.is_empty() {
         // No existential variables needed
-        format!(
-            "{{ [{}] : {} }}",
-            (0..mapping.len())
-                .map(|i| format!("p{}", i))
-                .collect::<Vec<_>>()
-                .join(", "),
-            constraints.join(" and ")
-        )
+        Some(constraints.join(" and "))
     } else {
         // With existential variables
-        format!(
-            "{{ [{}] : exists ({} : {}) }}",
-            (0..mapping.len())
-                .map(|i| format!("p{}", i))
-                .collect::<Vec<_>>()
-                .join(", "),
+        Some(format!(
+            "exists ({} : {})",
             (0..linear_set.periods.len())
                 .map(|i| format!("e{}", i))
                 .collect::<Vec<_>>()
                 .join(", "),
             constraints.join(" and ")
-        )
+        ))
     }
 }
 
@@ -852,6 +1024,38 @@ fn test_semilinear_to_presburger_conversion() {
     println!("Converted to PresburgerSet: {}", presburger_set);
 }
 
+#[test]
+fn test_semilinear_conversion_batching_and_cache() {
+    use crate::semilinear::SemilinearSet;
+
+    // a + b* + (ac)*, with components over different keys
+    let set = SemilinearSet::atom('a')
+        .plus(SemilinearSet::atom('b').star())
+        .plus(
+            SemilinearSet::atom('a')
+                .times(SemilinearSet::atom('c'))
+                .star(),
+        );
+    let components: Vec<PresburgerSet<char>> = set
+        .components
+        .iter()
+        .map(|component| {
+            PresburgerSet::from_semilinear_set(&SemilinearSet::new(vec![component.clone()]))
+        })
+        .collect();
+    let unioned = PresburgerSet::union_all(components).unwrap();
+    assert_eq!(PresburgerSet::from_semilinear_set(&set), unioned);
+
+    // Converted again from the cache
+    assert_eq!(PresburgerSet::from_semilinear_set(&set), unioned);
+
+    // Same structure over other atoms: shares the cached ISL set, but not the atoms
+    let x = PresburgerSet::from_semilinear_set(&SemilinearSet::atom('x'));
+    let y = PresburgerSet::from_semilinear_set(&SemilinearSet::atom('y'));
+    assert_eq!(x, PresburgerSet::atom('x'));
+    assert_ne!(x, y);
+}
+
 // Comprehensive test suite for PresburgerSet equality issues
 //
 // INVESTIGATION RESULTS:
@@ -1018,17 +1222,9 @@ mod presburger_equality_tests {
         let atom99 = PresburgerSet::atom(99);
 
         // Get string representations
-        let str42 = unsafe {
-            let str_ptr = isl::isl_set_to_str(atom42.isl_set);
-            let c_str = std::ffi::CStr::from_ptr(str_ptr);
-            c_str.to_string_lossy().into_owned()
-        };
+        let str42 = unsafe { isl::IslSetRef::new(atom42.isl_set).to_string() };
 
-        let str99 = unsafe {
-            let str_ptr = isl::isl_set_to_str(atom99.isl_set);
-            let c_str = std::ffi::CStr::from_ptr(str_ptr);
-            c_str.to_string_lossy().into_owned()
-        };
+        let str99 = unsafe { isl::IslSetRef::new(atom99.isl_set).to_string() };
 
         println!("ISL string for atom(42): {}", str42);
         println!("ISL string for atom(99): {}", str99);
@@ -1038,6 +1234,35 @@ mod presburger_equality_tests {
         assert_eq!(str42, str99);
     }
 
+    #[test]
+    fn test_display_does_not_leak_isl_strings() {
+        let _tracking = isl::track_isl_objects();
+        let before = isl::live_isl_objects(isl::IslObject::String);
+        let set = PresburgerSet::atom(1).union(&PresburgerSet::atom(2));
+        for _ in 0..10 {
+            assert!(set.to_string().contains("mapping: 1, 2"));
+        }
+        assert_eq!(isl::live_isl_objects(isl::IslObject::String), before);
+    }
+
+    #[test]
+    fn test_operations_do_not_leak_isl_sets() {
+        use isl::{IslObject, live_isl_objects};
+        let _tracking = isl::track_isl_objects();
+        let sets = live_isl_objects(IslObject::Set);
+        let maps = live_isl_objects(IslObject::Map);
+        {
+            let a = PresburgerSet::atom(1).union(&PresburgerSet::atom(2));
+            let b = PresburgerSet::universe(vec![2, 1]);
+            let c = a.intersection(&b).difference(&PresburgerSet::atom(2));
+            let renamed = c.clone().rename(|x| x + 1);
+            assert_eq!(live_isl_objects(IslObject::Set), sets + 4);
+            assert!(!renamed.is_empty());
+        }
+        assert_eq!(live_isl_objects(IslObject::Set), sets);
+        assert_eq!(live_isl_objects(IslObject::Map), maps);
+    }
+
     #[test]
     fn test_union_operations() {
         println!("\n=== Testing Union Operations ===");
@@ -1145,11 +1370,7 @@ mod presburger_equality_tests {
             let mut set_1d = isl::isl_set_universe(space_1d);
             set_1d = isl::isl_set_fix_si(set_1d, isl::isl_dim_type_isl_dim_set, 0, 1);
 
-            let original_str = {
-                let str_ptr = isl::isl_set_to_str(set_1d);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let original_str = isl::IslSetRef::new(set_1d).to_string();
             println!("Original 1D set: {}", original_str);
 
             // Insert dimension at position 1 (after existing dimension)
@@ -1158,11 +1379,7 @@ mod presburger_equality_tests {
             // Fix the new dimension to 0
             let set_embedded = isl::isl_set_fix_si(set_2d, isl::isl_dim_type_isl_dim_set, 1, 0);
 
-            let embedded_str = {
-                let str_ptr = isl::isl_set_to_str(set_embedded);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let embedded_str = isl::IslSetRef::new(set_embedded).to_string();
             println!("Embedded to 2D: {}", embedded_str);
             println!("Expected: {{ [1, 0] }}");
 
@@ -1179,11 +1396,7 @@ mod presburger_equality_tests {
             // Fix the new dimension to 0
             let set_embedded2 = isl::isl_set_fix_si(set_2d2, isl::isl_dim_type_isl_dim_set, 0, 0);
 
-            let embedded_str2 = {
-                let str_ptr = isl::isl_set_to_str(set_embedded2);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let embedded_str2 = isl::IslSetRef::new(set_embedded2).to_string();
             println!("Embedded to 2D (different position): {}", embedded_str2);
             println!("Expected: {{ [0, 1] }}");
 
@@ -1195,11 +1408,7 @@ mod presburger_equality_tests {
                 isl::isl_set_copy(set_embedded2),
             );
 
-            let union_str = {
-                let str_ptr = isl::isl_set_to_str(union_set);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let union_str = isl::IslSetRef::new(union_set).to_string();
             println!("Union result: {}", union_str);
             println!("Expected: {{ [1, 0]; [0, 1] }}");
 
@@ -1211,11 +1420,7 @@ mod presburger_equality_tests {
                 isl::isl_set_copy(set_embedded2),
             );
 
-            let sum_str = {
-                let str_ptr = isl::isl_set_to_str(sum_set);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let sum_str = isl::IslSetRef::new(sum_set).to_string();
             println!("Minkowski sum result: {}", sum_str);
             println!("Expected: {{ [1, 1] }}");
 
@@ -1310,11 +1515,7 @@ mod presburger_equality_tests {
             let mut set_1d = isl::isl_set_universe(space_1d);
             set_1d = isl::isl_set_fix_si(set_1d, isl::isl_dim_type_isl_dim_set, 0, 2);
 
-            let set_1d_str = {
-                let str_ptr = isl::isl_set_to_str(set_1d);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let set_1d_str = isl::IslSetRef::new(set_1d).to_string();
             println!("Original 1D set: {}", set_1d_str);
 
             // Create a map from 2D space to 1D space: (x,y) -> x+y
@@ -1345,11 +1546,7 @@ mod presburger_equality_tests {
             // Apply preimage: should give us { [x,y] : x+y = 2 }
             let result_set = isl::isl_set_preimage_multi_aff(set_1d, ma);
 
-            let result_str = {
-                let str_ptr = isl::isl_set_to_str(result_set);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let result_str = isl::IslSetRef::new(result_set).to_string();
             println!("Preimage result: {}", result_str);
             println!("Expected: Set of all (x,y) where x+y=2");
 
@@ -1369,11 +1566,7 @@ mod presburger_equality_tests {
             let space_0d = isl::isl_space_set_alloc(ctx, 0, 0);
             let set_0d = isl::isl_set_universe(space_0d);
 
-            let set_0d_str = {
-                let str_ptr = isl::isl_set_to_str(set_0d);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let set_0d_str = isl::IslSetRef::new(set_0d).to_string();
             println!("Original 0D set: {}", set_0d_str);
 
             // Create map from 1D to 0D: x -> [] (constant map)
@@ -1389,11 +1582,7 @@ mod presburger_equality_tests {
 
             let result_set = isl::isl_set_preimage_multi_aff(set_0d, ma);
 
-            let result_str = {
-                let str_ptr = isl::isl_set_to_str(result_set);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let result_str = isl::IslSetRef::new(result_set).to_string();
             println!("0D->1D preimage result: {}", result_str);
             println!("Expected: All of 1D space or error");
 
@@ -1418,11 +1607,7 @@ mod presburger_equality_tests {
             let mut zero_1d = isl::isl_set_universe(isl::isl_space_copy(space_1d));
             zero_1d = isl::isl_set_fix_si(zero_1d, isl::isl_dim_type_isl_dim_set, 0, 0);
 
-            let zero_str = {
-                let str_ptr = isl::isl_set_to_str(zero_1d);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let zero_str = isl::IslSetRef::new(zero_1d).to_string();
             println!("1D zero point: {}", zero_str);
 
             // What we really want is the reverse: embed 0D -> 1D
@@ -1446,11 +1631,7 @@ mod presburger_equality_tests {
             set_2d = isl::isl_set_fix_si(set_2d, isl::isl_dim_type_isl_dim_set, 0, 1);
             set_2d = isl::isl_set_fix_si(set_2d, isl::isl_dim_type_isl_dim_set, 1, 0);
 
-            let set_2d_str = {
-                let str_ptr = isl::isl_set_to_str(set_2d);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let set_2d_str = isl::IslSetRef::new(set_2d).to_string();
             println!("Original 2D set: {}", set_2d_str);
 
             // Create identity map from 2D to 2D
@@ -1478,11 +1659,7 @@ mod presburger_equality_tests {
 
             let result_set = isl::isl_set_preimage_multi_aff(set_2d, ma);
 
-            let result_str = {
-                let str_ptr = isl::isl_set_to_str(result_set);
-                let c_str = std::ffi::CStr::from_ptr(str_ptr);
-                c_str.to_string_lossy().into_owned()
-            };
+            let result_str = isl::IslSetRef::new(result_set).to_string();
             println!("Identity preimage result: {}", result_str);
             println!("Expected: Same as original");
 
@@ -1615,9 +1792,7 @@ mod presburger_equality_tests {
             let space = isl::isl_space_set_alloc(ctx, 0, 1);
             let mut set = isl::isl_set_universe(space);
             set = isl::isl_set_fix_si(set, isl::isl_dim_type_isl_dim_set, 0, 0);
-            let str_ptr = isl::isl_set_to_str(set);
-            let c_str = std::ffi::CStr::from_ptr(str_ptr);
-            let result = c_str.to_string_lossy().into_owned();
+            let result = isl::IslSetRef::new(set).to_string();
             isl::isl_set_free(set);
             result
         };
@@ -1808,7 +1983,7 @@ impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
             // Prepare user data structure
             let mut user_data = UserData {
                 result_sets: Vec::new(),
-                mapping: self.mapping.clone(),
+                mapping: self.mapping.to_vec(),
             };
 
             // Iterate through each basic set
@@ -1833,10 +2008,12 @@ impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
 ///
 /// This function converts a Rust representation back to an ISL-based representation.
 impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
-    pub fn from_quantified_sets(sets: &[QuantifiedSet<T>], mapping: Vec<T>) -> Self 
+    pub fn from_quantified_sets(sets: &[QuantifiedSet<T>], mapping: impl Into<VarMap<T>>) -> Self
     where
         T: Display,
     {
+        let mapping = mapping.into();
+
         // Using the ISL context
         let ctx = isl::get_ctx();
 
@@ -1851,16 +2028,18 @@ impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
             // Parse the ISL set string
             let set = unsafe {
                 let cstr = CString::new(set_string.clone()).unwrap();
-                let parsed_set = isl::isl_set_read_from_str(ctx, cstr.as_ptr());
+                let parsed_set = record("read_from_str", &[], || {
+                    isl::isl_set_read_from_str(ctx, cstr.as_ptr())
+                });
 
                 // Check if ISL returned NULL (syntax error)
                 if parsed_set.is_null() {
-                    panic!(
+                    isl::fail(format!(
                         "ISL syntax error while parsing set string. This likely indicates a bug in constraint generation.\n\
                          Set string: {}\n\
                          Mapping: {:?}",
                         set_string, mapping
-                    );
+                    ));
                 }
 
                 parsed_set
@@ -1871,7 +2050,9 @@ impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
                 if result_set.is_null() {
                     result_set = set;
                 } else {
-                    result_set = isl::isl_set_union(result_set, set);
+                    result_set = record("union", &[result_set, set], || {
+                        isl::isl_set_union(result_set, set)
+                    });
                 }
             }
         }
@@ -1882,10 +2063,7 @@ impl<T: Clone + Ord + Debug + ToString> PresburgerSet<T> {
             result_set = unsafe { isl::isl_set_universe(space) };
         }
 
-        PresburgerSet {
-            isl_set: result_set,
-            mapping,
-        }
+        PresburgerSet::from_raw(result_set, mapping)
     }
 }
 
@@ -1905,12 +2083,11 @@ fn create_isl_set_string<T: ToString + Display + Debug>(quantified_set: &Quantif
         })
         .collect();
 
-    // ISL expects dimension names in the format [p0, p1, ...] or similar
-    // We don't actually need this vector, just keeping the format for clarity
-    let _var_names: Vec<String> = mapping
-        .iter()
-        .map(|var| format!("p{}", var.to_string()))
-        .collect();
+    // Dimensions are named positionally [p0, p1, ...], so names of T never reach
+    // ISL and need no escaping (see `ident`)
+
+    // Variables are matched by their string form, which must then be unique
+    let mapping_index = crate::utils::string::display_index(mapping, "ISL set mapping");
 
     // Create variable names for existential variables
     let existential_names: Vec<String> = existential_vars
@@ -1938,10 +2115,8 @@ fn create_isl_set_string<T: ToString + Display + Debug>(quantified_set: &Quantif
                 Variable::Var(t) => {
                     // Find the index of this variable in the mapping
                     // We need to compare by string representation
-                    let t_str = t.to_string();
-                    let idx = mapping
-                        .iter()
-                        .position(|x| x.to_string() == t_str)
+                    let idx = *mapping_index
+                        .get(&t.to_string())
                         .unwrap_or_else(|| panic!("Variable {} not found in mapping {:?}", t, mapping));
                     expr.push_str(&format!("*p{}", idx));
                 }
@@ -2383,7 +2558,7 @@ mod tests {
         }
 
         assert!(!presburger.is_empty());
-        assert_eq!(mapping, presburger.mapping);
+        assert_eq!(presburger.mapping, mapping);
     }
 
     #[test]
@@ -2420,4 +2595,23 @@ mod tests {
         assert!(!zero_vars.contains(&"u")); // Multiple variables in constraint  
         assert!(!zero_vars.contains(&"v")); // Non-zero constant term
     }
+
+    #[test]
+    fn test_count() {
+        let atom = |c: char| SemilinearSet::atom(c);
+        let b_or_c = PresburgerSet::from_semilinear_set(&atom('b').plus(atom('c')));
+        assert!(b_or_c.is_bounded());
+        assert_eq!(b_or_c.count(), Some(2));
+
+        // a^n b and a^n c, with at most two elements: b, c, ab, ac
+        let set =
+            PresburgerSet::from_semilinear_set(&atom('a').star().times(atom('b').plus(atom('c'))));
+        assert_eq!(set.count(), None);
+        assert_eq!(set.count_up_to(2), 4);
+        assert_eq!(set.count_up_to(0), 0);
+
+        // Even numbers of a (an existentially quantified set)
+        let even = PresburgerSet::from_semilinear_set(&atom('a').times(atom('a')).star());
+        assert_eq!(even.count_up_to(5), 3);
+    }
 }
@@ -0,0 +1,711 @@
+//! Deciding Presburger formulas without ISL.
+//!
+//! Checking a certificate comes down to a few questions about formulas: is a closed
+//! formula true, does one formula imply another, and is a formula included in a
+//! semilinear set. With the `isl` feature these go through ISL sets; this module
+//! answers them in pure Rust with Cooper's quantifier elimination, so that certificates
+//! can also be checked where ISL is not available, like in a WebAssembly build.
+//!
+//! Like in `formula_to_presburger`, every variable, free or bound, ranges over the
+//! natural numbers. Cooper's method is exponential in the number of eliminated
+//! variables, so this is much slower than ISL on large certificates.
+
+use crate::constraints::Variable;
+use crate::deterministic_map::HashMap;
+use crate::proof_parser::{CompOp, Formula};
+use crate::semilinear::SemilinearSet;
+use std::collections::BTreeMap;
+
+/// Coefficients grow with every eliminated variable. A wrapped overflow would make the
+/// checker unsound, so an overflow fails the check instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Overflow;
+
+impl From<Overflow> for String {
+    fn from(_: Overflow) -> String {
+        "Coefficient overflow while deciding a Presburger formula".to_string()
+    }
+}
+
+// `i128::MIN` counts as an overflow too, so that coefficients can always be negated
+fn add_coeff(a: i128, b: i128) -> Result<i128, Overflow> {
+    a.checked_add(b).filter(|&c| c != i128::MIN).ok_or(Overflow)
+}
+
+fn mul_coeff(a: i128, b: i128) -> Result<i128, Overflow> {
+    a.checked_mul(b).filter(|&c| c != i128::MIN).ok_or(Overflow)
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Least common multiple of two positive numbers
+fn lcm(a: i128, b: i128) -> Result<i128, Overflow> {
+    mul_coeff(a / gcd(a, b), b)
+}
+
+/// `Σ coefficient·variable + constant`, over numbered variables
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Linear {
+    terms: BTreeMap<usize, i128>,
+    constant: i128,
+}
+
+impl Linear {
+    fn constant(constant: i128) -> Self {
+        Linear {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+
+    fn var(v: usize) -> Self {
+        Linear {
+            terms: BTreeMap::from([(v, 1)]),
+            constant: 0,
+        }
+    }
+
+    fn coeff(&self, v: usize) -> i128 {
+        self.terms.get(&v).copied().unwrap_or(0)
+    }
+
+    fn add(&self, other: &Linear) -> Result<Linear, Overflow> {
+        let mut result = self.clone();
+        result.constant = add_coeff(result.constant, other.constant)?;
+        for (&v, &c) in &other.terms {
+            let sum = add_coeff(result.coeff(v), c)?;
+            if sum == 0 {
+                result.terms.remove(&v);
+            } else {
+                result.terms.insert(v, sum);
+            }
+        }
+        Ok(result)
+    }
+
+    fn scale(&self, k: i128) -> Result<Linear, Overflow> {
+        if k == 0 {
+            return Ok(Linear::constant(0));
+        }
+        Ok(Linear {
+            terms: self
+                .terms
+                .iter()
+                .map(|(&v, &c)| Ok((v, mul_coeff(c, k)?)))
+                .collect::<Result<_, Overflow>>()?,
+            constant: mul_coeff(self.constant, k)?,
+        })
+    }
+
+    fn offset(&self, k: i128) -> Result<Linear, Overflow> {
+        Ok(Linear {
+            terms: self.terms.clone(),
+            constant: add_coeff(self.constant, k)?,
+        })
+    }
+
+    /// The expression without the term of `v`, and the coefficient of that term
+    fn split(&self, v: usize) -> (Linear, i128) {
+        let mut rest = self.clone();
+        let c = rest.terms.remove(&v).unwrap_or(0);
+        (rest, c)
+    }
+
+    /// The expression with `v` replaced by `value`
+    fn substitute(&self, v: usize, value: &Linear) -> Result<Linear, Overflow> {
+        match self.split(v) {
+            (_, 0) => Ok(self.clone()),
+            (rest, c) => rest.add(&value.scale(c)?),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Atom {
+    /// `linear ≥ 0`
+    Geq(Linear),
+    /// `linear = 0`
+    Eq(Linear),
+    /// `modulus | linear`
+    Divides(i128, Linear),
+    /// `¬(modulus | linear)`
+    NotDivides(i128, Linear),
+}
+
+impl Atom {
+    fn linear(&self) -> &Linear {
+        match self {
+            Atom::Geq(l) | Atom::Eq(l) | Atom::Divides(_, l) | Atom::NotDivides(_, l) => l,
+        }
+    }
+
+    /// The same kind of atom over another expression
+    fn with(&self, l: Linear) -> Atom {
+        match self {
+            Atom::Geq(_) => Atom::Geq(l),
+            Atom::Eq(_) => Atom::Eq(l),
+            Atom::Divides(m, _) => Atom::Divides(*m, l),
+            Atom::NotDivides(m, _) => Atom::NotDivides(*m, l),
+        }
+    }
+}
+
+/// A quantifier-free formula. Formulas are built with `and`, `or` and `atom`, which
+/// keep them flat and free of constant subformulas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Qf {
+    True,
+    False,
+    Atom(Atom),
+    And(Vec<Qf>),
+    Or(Vec<Qf>),
+}
+
+fn truth(value: bool) -> Qf {
+    if value { Qf::True } else { Qf::False }
+}
+
+/// The atom with its coefficients divided by their gcd, or its truth value if it has
+/// no variables
+fn atom(atom: Atom) -> Qf {
+    match atom {
+        Atom::Geq(l) if l.terms.is_empty() => truth(l.constant >= 0),
+        Atom::Eq(l) if l.terms.is_empty() => truth(l.constant == 0),
+        Atom::Geq(l) => {
+            // Σ a·x + c ≥ 0 is Σ (a/g)·x + ⌊c/g⌋ ≥ 0 over the integers
+            let g = l.terms.values().fold(0, |g, &c| gcd(g, c));
+            Qf::Atom(Atom::Geq(Linear {
+                terms: l.terms.iter().map(|(&v, &c)| (v, c / g)).collect(),
+                constant: l.constant.div_euclid(g),
+            }))
+        }
+        Atom::Eq(l) => {
+            let g = l.terms.values().fold(0, |g, &c| gcd(g, c));
+            if l.constant % g != 0 {
+                return Qf::False;
+            }
+            // One sign for `x - y = 0` and `y - x = 0`, so that they are the same atom
+            let first = l.terms.values().next().copied().unwrap_or(1);
+            let g = if first < 0 { -g } else { g };
+            Qf::Atom(Atom::Eq(Linear {
+                terms: l.terms.iter().map(|(&v, &c)| (v, c / g)).collect(),
+                constant: l.constant / g,
+            }))
+        }
+        Atom::Divides(m, l) => divisibility(m, &l, true),
+        Atom::NotDivides(m, l) => divisibility(m, &l, false),
+    }
+}
+
+/// `m | l` (or `¬(m | l)` if not `divides`) with the coefficients reduced modulo `m`
+fn divisibility(m: i128, l: &Linear, divides: bool) -> Qf {
+    let m = m.abs();
+    let reduced = Linear {
+        terms: l
+            .terms
+            .iter()
+            .map(|(&v, &c)| (v, c.rem_euclid(m)))
+            .filter(|&(_, c)| c != 0)
+            .collect(),
+        constant: l.constant.rem_euclid(m),
+    };
+    if reduced.terms.is_empty() {
+        truth((reduced.constant == 0) == divides)
+    } else if divides {
+        Qf::Atom(Atom::Divides(m, reduced))
+    } else {
+        Qf::Atom(Atom::NotDivides(m, reduced))
+    }
+}
+
+/// Add `part` to the conjuncts (or disjuncts) `parts`, unless it is already there
+fn push_unique(parts: &mut Vec<Qf>, part: Qf) {
+    if !parts.contains(&part) {
+        parts.push(part);
+    }
+}
+
+fn and(parts: Vec<Qf>) -> Qf {
+    let mut conjuncts = Vec::new();
+    for part in parts {
+        match part {
+            Qf::True => {}
+            Qf::False => return Qf::False,
+            Qf::And(inner) => inner
+                .into_iter()
+                .for_each(|q| push_unique(&mut conjuncts, q)),
+            q => push_unique(&mut conjuncts, q),
+        }
+    }
+    match conjuncts.len() {
+        0 => Qf::True,
+        1 => conjuncts.pop().unwrap(),
+        _ => Qf::And(conjuncts),
+    }
+}
+
+fn or(parts: Vec<Qf>) -> Qf {
+    let mut disjuncts = Vec::new();
+    for part in parts {
+        match part {
+            Qf::False => {}
+            Qf::True => return Qf::True,
+            Qf::Or(inner) => inner
+                .into_iter()
+                .for_each(|q| push_unique(&mut disjuncts, q)),
+            q => push_unique(&mut disjuncts, q),
+        }
+    }
+    match disjuncts.len() {
+        0 => Qf::False,
+        1 => disjuncts.pop().unwrap(),
+        _ => Qf::Or(disjuncts),
+    }
+}
+
+fn negate(qf: Qf) -> Result<Qf, Overflow> {
+    Ok(match qf {
+        Qf::True => Qf::False,
+        Qf::False => Qf::True,
+        Qf::And(parts) => or(parts.into_iter().map(negate).collect::<Result<_, _>>()?),
+        Qf::Or(parts) => and(parts.into_iter().map(negate).collect::<Result<_, _>>()?),
+        // ¬(l ≥ 0) is -l - 1 ≥ 0, and ¬(l = 0) is l ≥ 1 ∨ -l ≥ 1
+        Qf::Atom(Atom::Geq(l)) => atom(Atom::Geq(l.scale(-1)?.offset(-1)?)),
+        Qf::Atom(Atom::Eq(l)) => or(vec![
+            atom(Atom::Geq(l.offset(-1)?)),
+            atom(Atom::Geq(l.scale(-1)?.offset(-1)?)),
+        ]),
+        Qf::Atom(Atom::Divides(m, l)) => atom(Atom::NotDivides(m, l)),
+        Qf::Atom(Atom::NotDivides(m, l)) => atom(Atom::Divides(m, l)),
+    })
+}
+
+/// Rebuild the formula with every atom replaced by `f(atom)`
+fn map_atoms<F: FnMut(Atom) -> Result<Qf, Overflow>>(qf: Qf, f: &mut F) -> Result<Qf, Overflow> {
+    match qf {
+        Qf::And(parts) => {
+            let mut mapped = Vec::new();
+            for part in parts {
+                mapped.push(map_atoms(part, f)?);
+            }
+            Ok(and(mapped))
+        }
+        Qf::Or(parts) => {
+            let mut mapped = Vec::new();
+            for part in parts {
+                mapped.push(map_atoms(part, f)?);
+            }
+            Ok(or(mapped))
+        }
+        Qf::Atom(a) => f(a),
+        qf => Ok(qf),
+    }
+}
+
+fn mentions(qf: &Qf, v: usize) -> bool {
+    match qf {
+        Qf::And(parts) | Qf::Or(parts) => parts.iter().any(|q| mentions(q, v)),
+        Qf::Atom(a) => a.linear().coeff(v) != 0,
+        Qf::True | Qf::False => false,
+    }
+}
+
+/// The atoms of the formula that mention `v`
+fn atoms_with<'a>(qf: &'a Qf, v: usize, atoms: &mut Vec<&'a Atom>) {
+    match qf {
+        Qf::And(parts) | Qf::Or(parts) => parts.iter().for_each(|q| atoms_with(q, v, atoms)),
+        Qf::Atom(a) if a.linear().coeff(v) != 0 => atoms.push(a),
+        _ => {}
+    }
+}
+
+fn substitute(qf: Qf, v: usize, value: &Linear) -> Result<Qf, Overflow> {
+    map_atoms(qf, &mut |a| {
+        Ok(atom(a.with(a.linear().substitute(v, value)?)))
+    })
+}
+
+/// `∃v. qf` over the integers, without quantifiers
+fn exists(v: usize, qf: Qf) -> Result<Qf, Overflow> {
+    // ∃ distributes over ∨, and conjuncts without `v` move out of it
+    let conjuncts = match qf {
+        Qf::Or(disjuncts) => {
+            return Ok(or(disjuncts
+                .into_iter()
+                .map(|d| exists(v, d))
+                .collect::<Result<_, _>>()?));
+        }
+        Qf::And(conjuncts) => conjuncts,
+        qf => vec![qf],
+    };
+    let (inner, mut outer): (Vec<Qf>, Vec<Qf>) =
+        conjuncts.into_iter().partition(|q| mentions(q, v));
+    match and(inner) {
+        Qf::True => {}
+        Qf::Or(disjuncts) => outer.push(exists(v, Qf::Or(disjuncts))?),
+        inner => outer.push(eliminate(v, inner)?),
+    }
+    Ok(and(outer))
+}
+
+/// Cooper's method for `∃v. qf`, where `qf` mentions `v`
+fn eliminate(v: usize, qf: Qf) -> Result<Qf, Overflow> {
+    // Scale the atoms so that `v` has the same coefficient L everywhere; then ∃v. φ(L·v)
+    // is ∃v. φ(v) ∧ L | v, in which `v` has coefficient ±1
+    let mut atoms = Vec::new();
+    atoms_with(&qf, v, &mut atoms);
+    let scale = atoms
+        .iter()
+        .try_fold(1, |l, a| lcm(l, a.linear().coeff(v).abs()))?;
+    let qf = if scale == 1 {
+        qf
+    } else {
+        let scaled = map_atoms(qf, &mut |a| {
+            let c = a.linear().coeff(v);
+            if c == 0 {
+                return Ok(Qf::Atom(a));
+            }
+            let k = scale / c.abs();
+            let mut l = a.linear().scale(k)?;
+            l.terms.insert(v, c.signum());
+            Ok(Qf::Atom(match a {
+                Atom::Divides(m, _) => Atom::Divides(mul_coeff(m, k)?, l),
+                Atom::NotDivides(m, _) => Atom::NotDivides(mul_coeff(m, k)?, l),
+                a => a.with(l),
+            }))
+        })?;
+        and(vec![scaled, atom(Atom::Divides(scale, Linear::var(v)))])
+    };
+
+    // An equation ±v + t = 0 among the conjuncts fixes v to ∓t
+    let conjuncts = match &qf {
+        Qf::And(conjuncts) => conjuncts.as_slice(),
+        qf => std::slice::from_ref(qf),
+    };
+    let fixed = conjuncts.iter().find_map(|q| match q {
+        Qf::Atom(Atom::Eq(l)) if l.coeff(v) != 0 => {
+            let (rest, c) = l.split(v);
+            Some(rest.scale(-c))
+        }
+        _ => None,
+    });
+    if let Some(value) = fixed {
+        return substitute(qf, v, &value?);
+    }
+
+    // Otherwise equations are a lower and an upper bound
+    let qf = map_atoms(qf, &mut |a| {
+        Ok(match a {
+            Atom::Eq(l) if l.coeff(v) != 0 => and(vec![
+                Qf::Atom(Atom::Geq(l.scale(-1)?)),
+                Qf::Atom(Atom::Geq(l)),
+            ]),
+            a => Qf::Atom(a),
+        })
+    })?;
+    let mut atoms = Vec::new();
+    atoms_with(&qf, v, &mut atoms);
+    let mut lower = Vec::new();
+    let mut upper = Vec::new();
+    let mut period = 1;
+    for a in atoms {
+        match a {
+            // v + t ≥ 0 is v ≥ -t, and -v + t ≥ 0 is v ≤ t
+            Atom::Geq(l) => match l.split(v) {
+                (t, c) if c > 0 => lower.push(t.scale(-1)?),
+                (t, _) => upper.push(t),
+            },
+            Atom::Divides(m, _) | Atom::NotDivides(m, _) => period = lcm(period, *m)?,
+            Atom::Eq(_) => unreachable!("equations on the eliminated variable were split"),
+        }
+    }
+    lower.sort();
+    lower.dedup();
+    upper.sort();
+    upper.dedup();
+
+    // The divisibility atoms repeat with the period, so if some v is a solution, then
+    // either all small enough v are, up to the period, or one of the first `period`
+    // values from a lower bound is. Bounds from above work the same way; take the side
+    // with fewer bounds.
+    let from_below = lower.len() <= upper.len();
+    let (bounds, direction) = if from_below { (lower, 1) } else { (upper, -1) };
+    let unbounded = map_atoms(qf.clone(), &mut |a| {
+        let c = a.linear().coeff(v);
+        Ok(match a {
+            Atom::Geq(_) if c != 0 => truth((c > 0) != from_below),
+            a => Qf::Atom(a),
+        })
+    })?;
+    let mut disjuncts = Vec::new();
+    for j in 0..period {
+        let j = direction * j;
+        disjuncts.push(substitute(unbounded.clone(), v, &Linear::constant(j))?);
+        for bound in &bounds {
+            disjuncts.push(substitute(qf.clone(), v, &bound.offset(j)?)?);
+        }
+    }
+    Ok(or(disjuncts))
+}
+
+/// Translates formulas over named variables into quantifier-free formulas over
+/// numbered ones, eliminating the quantifiers on the way
+#[derive(Default)]
+struct Translation {
+    /// Numbers of the free variables
+    named: HashMap<String, usize>,
+    /// Numbers of the bound variables in scope, by binder index, innermost last
+    bound: Vec<(usize, usize)>,
+    next: usize,
+}
+
+impl Translation {
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+
+    fn named(&mut self, name: &str) -> usize {
+        if let Some(&v) = self.named.get(name) {
+            return v;
+        }
+        let v = self.fresh();
+        self.named.insert(name.to_string(), v);
+        v
+    }
+
+    fn bound(&self, index: usize) -> usize {
+        self.bound
+            .iter()
+            .rev()
+            .find(|(i, _)| *i == index)
+            .map(|(_, v)| *v)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Existential variable e{} used but not bound by quantifier",
+                    index
+                )
+            })
+    }
+
+    fn formula(&mut self, formula: &Formula<String>) -> Result<Qf, Overflow> {
+        Ok(match formula {
+            Formula::Constraint(constraint) => {
+                let (terms, constant) = constraint.expr().to_linear_combination();
+                let mut l = Linear::constant(constant as i128);
+                for (coeff, var) in terms {
+                    let v = match var {
+                        Variable::Var(name) => self.named(&name),
+                        Variable::Existential(index) => self.bound(index),
+                    };
+                    l = l.add(&Linear::var(v).scale(coeff as i128)?)?;
+                }
+                match constraint.op() {
+                    CompOp::Eq => atom(Atom::Eq(l)),
+                    CompOp::Geq => atom(Atom::Geq(l)),
+                }
+            }
+            Formula::And(formulas) => and(formulas
+                .iter()
+                .map(|f| self.formula(f))
+                .collect::<Result<_, _>>()?),
+            Formula::Or(formulas) => or(formulas
+                .iter()
+                .map(|f| self.formula(f))
+                .collect::<Result<_, _>>()?),
+            Formula::Exists(index, body) => {
+                let v = self.fresh();
+                self.bound.push((*index, v));
+                let body = self.formula(body);
+                self.bound.pop();
+                exists(v, and(vec![natural(v), body?]))?
+            }
+            Formula::Forall(index, body) => {
+                // ∀v ≥ 0. φ is ¬∃v ≥ 0. ¬φ
+                let v = self.fresh();
+                self.bound.push((*index, v));
+                let body = self.formula(body);
+                self.bound.pop();
+                negate(exists(v, and(vec![natural(v), negate(body?)?]))?)?
+            }
+        })
+    }
+
+    /// Whether the formula holds for some natural values of the free variables
+    fn decide(&self, mut qf: Qf) -> Result<bool, Overflow> {
+        for &v in self.named.values() {
+            qf = exists(v, and(vec![natural(v), qf]))?;
+        }
+        match qf {
+            Qf::True => Ok(true),
+            Qf::False => Ok(false),
+            qf => unreachable!("variables left after elimination: {:?}", qf),
+        }
+    }
+}
+
+/// `v ≥ 0`
+fn natural(v: usize) -> Qf {
+    Qf::Atom(Atom::Geq(Linear::var(v)))
+}
+
+/// Whether the formula holds for some natural values of its free variables. Fails if
+/// the coefficients get too large to decide it.
+pub fn is_satisfiable(formula: &Formula<String>) -> Result<bool, String> {
+    let mut translation = Translation::default();
+    let qf = translation.formula(formula)?;
+    Ok(translation.decide(qf)?)
+}
+
+/// Whether every natural valuation that satisfies `antecedent` satisfies `consequent`
+pub fn implies(antecedent: &Formula<String>, consequent: &Formula<String>) -> Result<bool, String> {
+    let mut translation = Translation::default();
+    let antecedent = translation.formula(antecedent)?;
+    let consequent = translation.formula(consequent)?;
+    Ok(!translation.decide(and(vec![antecedent, negate(consequent)?]))?)
+}
+
+/// Whether every natural valuation of `variables` that satisfies `formula` is in `set`.
+/// Keys of the set that are not among the variables are 0 in the valuation.
+pub fn included_in_semilinear(
+    formula: &Formula<String>,
+    variables: &[String],
+    set: &SemilinearSet<String>,
+) -> Result<bool, String> {
+    let mut keys: Vec<&String> = variables.iter().collect();
+    for component in &set.components {
+        for period in std::iter::once(&component.base).chain(&component.periods) {
+            for key in period.values.keys() {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+    }
+
+    let mut translation = Translation::default();
+    let mut parts = vec![translation.formula(formula)?];
+    for key in &keys[variables.len()..] {
+        parts.push(atom(Atom::Eq(Linear::var(translation.named(key)))));
+    }
+
+    // The valuation is base + Σ k·period for some component and natural k
+    let mut members = Vec::new();
+    for component in &set.components {
+        let ks: Vec<usize> = component
+            .periods
+            .iter()
+            .map(|_| translation.fresh())
+            .collect();
+        let mut equations = Vec::new();
+        for key in &keys {
+            let mut l =
+                Linear::var(translation.named(key)).offset(-(component.base.get(key) as i128))?;
+            for (period, &k) in component.periods.iter().zip(&ks) {
+                l = l.add(&Linear::var(k).scale(-(period.get(key) as i128))?)?;
+            }
+            equations.push(atom(Atom::Eq(l)));
+        }
+        let mut member = and(equations);
+        for &k in &ks {
+            member = exists(k, and(vec![natural(k), member]))?;
+        }
+        members.push(member);
+    }
+    parts.push(negate(or(members))?);
+    Ok(!translation.decide(and(parts))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof_parser::{AffineExpr, Constraint};
+    use crate::semilinear::{LinearSet, SparseVector};
+
+    fn var(name: &str) -> AffineExpr<String> {
+        AffineExpr::from_var(name.to_string())
+    }
+
+    /// `∃k. x = m·k`
+    fn multiple_of(x: &str, m: i64) -> Formula<String> {
+        let equation = Constraint::eq(var(x).sub(&AffineExpr::term(m, "k".to_string())), 0);
+        Formula::exists("k".to_string(), equation.into())
+    }
+
+    #[test]
+    fn test_is_satisfiable() {
+        let x_is_3 = Formula::from(Constraint::eq(var("x"), 3));
+        let x_is_4 = Formula::from(Constraint::eq(var("x"), 4));
+        assert!(!is_satisfiable(&Formula::and([multiple_of("x", 2), x_is_3.clone()])).unwrap());
+        assert!(is_satisfiable(&Formula::and([multiple_of("x", 2), x_is_4])).unwrap());
+        // Variables are natural numbers
+        assert!(!is_satisfiable(&Constraint::lt(var("x"), 0).into()).unwrap());
+        // 2x + 3y = 1 has integer solutions (x = 2, y = -1), but no natural ones
+        let sum = var("x").mul_by_const(2).add(&var("y").mul_by_const(3));
+        assert!(!is_satisfiable(&Constraint::eq(sum.clone(), 1).into()).unwrap());
+        assert!(is_satisfiable(&Constraint::eq(sum, 5).into()).unwrap());
+        // ∀y. x ≤ y holds only for x = 0
+        let below_all = Formula::forall(
+            "y".to_string(),
+            Constraint::ge(var("y").sub(&var("x")), 0).into(),
+        );
+        assert!(is_satisfiable(&below_all).unwrap());
+        assert!(!is_satisfiable(&Formula::and([below_all, x_is_3])).unwrap());
+    }
+
+    #[test]
+    fn test_implies() {
+        let at_least = |c| Formula::from(Constraint::ge(var("x"), c));
+        assert!(implies(&at_least(2), &at_least(1)).unwrap());
+        assert!(!implies(&at_least(1), &at_least(2)).unwrap());
+        assert!(implies(&multiple_of("x", 4), &multiple_of("x", 2)).unwrap());
+        assert!(!implies(&multiple_of("x", 2), &multiple_of("x", 4)).unwrap());
+        // Even numbers of at least 1 are at least 2
+        let even_positive = Formula::and([multiple_of("x", 2), at_least(1)]);
+        assert!(implies(&even_positive, &at_least(2)).unwrap());
+        assert!(!implies(&even_positive, &at_least(3)).unwrap());
+    }
+
+    #[test]
+    fn test_included_in_semilinear() {
+        let s = |x: &str| x.to_string();
+        // { a^(2k) b^k }
+        let period = SparseVector {
+            values: [(s("a"), 2), (s("b"), 1)].into_iter().collect(),
+        };
+        let set = SemilinearSet {
+            components: vec![LinearSet {
+                base: SparseVector::new(),
+                periods: vec![period],
+            }],
+        };
+        let a_is_2b = Formula::from(Constraint::eq(var("a").sub(&var("b").mul_by_const(2)), 0));
+        let variables = vec![s("a"), s("b")];
+        assert!(included_in_semilinear(&a_is_2b, &variables, &set).unwrap());
+        let a_is_b = Formula::from(Constraint::eq(var("a").sub(&var("b")), 0));
+        assert!(!included_in_semilinear(&a_is_b, &variables, &set).unwrap());
+        // Without `b` among the variables, it is 0, so only a = 0 is in the set
+        let a_is_0 = Formula::from(Constraint::eq(var("a"), 0));
+        assert!(included_in_semilinear(&a_is_0, &variables[..1], &set).unwrap());
+        assert!(!included_in_semilinear(&multiple_of("a", 2), &variables[..1], &set).unwrap());
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Eliminating y scales its coefficients to their lcm, which is about 2^183
+        let p: i64 = (1 << 61) + 1;
+        let bounds = (p..p + 3).map(|c| {
+            let l = AffineExpr::term(c, "y".to_string()).sub(&var("x"));
+            Formula::from(Constraint::ge(l, 0))
+        });
+        let formula = Formula::exists("y".to_string(), Formula::and(bounds));
+        assert!(is_satisfiable(&formula).is_err());
+        assert!(implies(&formula, &formula).is_err());
+    }
+}
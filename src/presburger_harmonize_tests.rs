@@ -276,8 +276,8 @@ mod tests {
         let universe2 = PresburgerSet::universe(atoms2);
         
         // Project out 'b' from both
-        let proj1 = universe1.project_out('b');
-        let proj2 = universe2.project_out('b');
+        let proj1 = universe1.project_out_strict('b').unwrap();
+        let proj2 = universe2.project_out_strict('b').unwrap();
         
         // Both should have the same result (universe over {a, c})
         assert_eq!(proj1, proj2, "Project out should work correctly regardless of initial ordering");
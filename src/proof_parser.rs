@@ -1,11 +1,18 @@
+use crate::constraints::Variable;
+#[cfg(feature = "isl")]
+use crate::constraints::{Constraint as PConstraint, QuantifiedSet};
 use crate::deterministic_map::HashMap;
+#[cfg(feature = "isl")]
 use crate::kleene::Kleene; // <-- bring in zero()
-use crate::presburger::{Constraint as PConstraint, PresburgerSet, QuantifiedSet, Variable};
+#[cfg(feature = "isl")]
+use crate::presburger::PresburgerSet;
 use either::Either;
 use serde::{Serialize, Deserialize};
 use std::fmt::{self, Display};
+#[cfg(any(test, feature = "isl"))]
 use std::fs;
 use std::hash::Hash;
+#[cfg(any(test, feature = "isl"))]
 use std::path::Path;
 
 // Helper module for serializing HashMap with non-string keys
@@ -88,6 +95,11 @@ impl<T: Clone + Eq + Hash> AffineExpr<T> {
         AffineExpr { terms, constant: 0 }
     }
 
+    /// Create the expression `coeff * var`
+    pub fn term(coeff: i64, var: T) -> Self {
+        AffineExpr::from_var(var).mul_by_const(coeff)
+    }
+
     /// Add two expressions
     pub fn add(&self, other: &AffineExpr<T>) -> AffineExpr<T> {
         let mut result = self.clone();
@@ -170,6 +182,12 @@ impl<T: Clone + Eq + Hash> AffineExpr<T> {
     }
 }
 
+impl<T: Clone + Eq + Hash> Default for AffineExpr<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: fmt::Display + Eq + Hash> fmt::Display for AffineExpr<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.terms.is_empty() && self.constant == 0 {
@@ -272,8 +290,8 @@ impl fmt::Display for CompOp {
 /// Linear constraint: expr op 0
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Constraint<T: Eq + Hash> {
-    pub expr: AffineExpr<T>,
-    pub op: CompOp,
+    pub(crate) expr: AffineExpr<T>,
+    pub(crate) op: CompOp,
 }
 
 impl<T: Eq + Hash> Constraint<T> {
@@ -298,6 +316,40 @@ impl<T: Clone + Eq + Hash> Constraint<T> {
     pub fn new(expr: AffineExpr<T>, op: CompOp) -> Self {
         Constraint { expr, op }
     }
+
+    /// `expr = c`
+    pub fn eq(expr: AffineExpr<T>, c: i64) -> Self {
+        Constraint::new(expr.sub(&AffineExpr::from_const(c)), CompOp::Eq)
+    }
+
+    /// `expr >= c`
+    pub fn ge(expr: AffineExpr<T>, c: i64) -> Self {
+        Constraint::new(expr.sub(&AffineExpr::from_const(c)), CompOp::Geq)
+    }
+
+    /// `expr > c`, as `expr >= c + 1`
+    pub fn gt(expr: AffineExpr<T>, c: i64) -> Self {
+        Constraint::ge(expr, c + 1)
+    }
+
+    /// `expr <= c`, as `-expr >= -c`
+    pub fn le(expr: AffineExpr<T>, c: i64) -> Self {
+        Constraint::ge(expr.negate(), -c)
+    }
+
+    /// `expr < c`, as `-expr >= 1 - c`
+    pub fn lt(expr: AffineExpr<T>, c: i64) -> Self {
+        Constraint::ge(expr.negate(), 1 - c)
+    }
+
+    /// The left-hand side of `expr op 0`
+    pub fn expr(&self) -> &AffineExpr<T> {
+        &self.expr
+    }
+
+    pub fn op(&self) -> CompOp {
+        self.op
+    }
 }
 
 impl<L, R> Constraint<Either<L, R>>
@@ -331,6 +383,17 @@ pub enum Formula<T: Eq + Hash> {
 }
 
 impl<T: Eq + Hash> Formula<T> {
+    /// Number of atomic constraints in the formula, used as its size in statistics
+    pub fn num_constraints(&self) -> usize {
+        match self {
+            Formula::Constraint(_) => 1,
+            Formula::And(formulas) | Formula::Or(formulas) => {
+                formulas.iter().map(|f| f.num_constraints()).sum()
+            }
+            Formula::Exists(_, body) | Formula::Forall(_, body) => body.num_constraints(),
+        }
+    }
+
     /// Collect all free variables in the formula, properly handling shadowing
     /// by existential and universal quantifiers
     pub fn collect_free_variables(&self) -> std::collections::HashSet<T>
@@ -503,13 +566,99 @@ impl<T: fmt::Display + Eq + Hash> fmt::Display for Formula<T> {
     }
 }
 
+// Builders, for constructing formulas directly rather than through SMT-LIB
+
+impl<T: Clone + Eq + Hash> Formula<T> {
+    /// Conjunction of the formulas; true if there are none
+    pub fn and(formulas: impl IntoIterator<Item = Formula<T>>) -> Self {
+        Formula::And(formulas.into_iter().collect())
+    }
+
+    /// Disjunction of the formulas; false if there are none
+    pub fn or(formulas: impl IntoIterator<Item = Formula<T>>) -> Self {
+        Formula::Or(formulas.into_iter().collect())
+    }
+
+    /// `∃var. body`
+    pub fn exists(var: T, body: Formula<T>) -> Self {
+        body.mk_exists(var)
+    }
+
+    /// `∀var. body`
+    pub fn forall(var: T, body: Formula<T>) -> Self {
+        body.mk_forall(var)
+    }
+
+    /// `self => other`, as `¬self ∨ other`
+    pub fn implies(self, other: Formula<T>) -> Self {
+        Formula::Or(vec![self.negate(), other])
+    }
+
+    /// Negate the formula using De Morgan's laws, keeping it normalized
+    pub fn negate(self) -> Self {
+        match self {
+            Formula::Constraint(c) => {
+                match c.op {
+                    CompOp::Eq => {
+                        // ¬(expr = 0) becomes (expr > 0) ∨ (expr < 0)
+                        // which is (expr >= 1) ∨ (-expr >= 1)
+                        let pos_expr = c.expr.clone();
+                        let mut pos_constraint = Constraint::new(pos_expr, CompOp::Geq);
+                        pos_constraint.expr.constant -= 1;
+
+                        let neg_expr = c.expr.negate();
+                        let mut neg_constraint = Constraint::new(neg_expr, CompOp::Geq);
+                        neg_constraint.expr.constant -= 1;
+
+                        Formula::Or(vec![
+                            Formula::Constraint(pos_constraint),
+                            Formula::Constraint(neg_constraint),
+                        ])
+                    }
+                    CompOp::Geq => {
+                        // ¬(expr >= 0) becomes expr < 0 which is -expr - 1 >= 0
+                        let mut neg_expr = c.expr.negate();
+                        neg_expr.constant -= 1;
+                        Formula::Constraint(Constraint::new(neg_expr, CompOp::Geq))
+                    }
+                }
+            }
+            Formula::And(formulas) => {
+                // ¬(A ∧ B) = ¬A ∨ ¬B
+                let negated: Vec<Formula<T>> = formulas.into_iter().map(Formula::negate).collect();
+                Formula::Or(negated)
+            }
+            Formula::Or(formulas) => {
+                // ¬(A ∨ B) = ¬A ∧ ¬B
+                let negated: Vec<Formula<T>> = formulas.into_iter().map(Formula::negate).collect();
+                Formula::And(negated)
+            }
+            Formula::Exists(var, body) => {
+                // ¬∃x.P = ∀x.¬P
+                Formula::Forall(var, Box::new(body.negate()))
+            }
+            Formula::Forall(var, body) => {
+                // ¬∀x.P = ∃x.¬P
+                Formula::Exists(var, Box::new(body.negate()))
+            }
+        }
+    }
+
+}
+
+impl<T: Eq + Hash> From<Constraint<T>> for Formula<T> {
+    fn from(constraint: Constraint<T>) -> Self {
+        Formula::Constraint(constraint)
+    }
+}
+
 /// The proof invariant extracted from an SMT-LIB file
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ProofInvariant<T: Eq + Hash> {
     /// Variables declared in the cert function
-    pub variables: Vec<T>,
+    pub(crate) variables: Vec<T>,
     /// The invariant formula
-    pub formula: Formula<T>,
+    pub(crate) formula: Formula<T>,
 }
 
 impl<T: Eq + Hash> ProofInvariant<T> {
@@ -517,6 +666,18 @@ impl<T: Eq + Hash> ProofInvariant<T> {
     /// are present in the variables list. Properly handles shadowing by existential/universal quantifiers.
     /// Panics if validation fails.
     pub fn new(variables: Vec<T>, formula: Formula<T>) -> Self
+    where
+        T: Clone + Display,
+    {
+        match Self::try_new(variables, formula) {
+            Ok(invariant) => invariant,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `new`, but returns an error instead of panicking if the formula uses
+    /// undeclared variables
+    pub fn try_new(variables: Vec<T>, formula: Formula<T>) -> std::result::Result<Self, String>
     where
         T: Clone + Display,
     {
@@ -536,13 +697,27 @@ impl<T: Eq + Hash> ProofInvariant<T> {
         
         if !missing_vars.is_empty() {
             let missing_str: Vec<String> = missing_vars.iter().map(|v| v.to_string()).collect();
-            panic!(
+            return Err(format!(
                 "Variables used in formula but not declared: {}",
                 missing_str.join(", ")
-            );
+            ));
         }
         
-        ProofInvariant { variables, formula }
+        Ok(ProofInvariant { variables, formula })
+    }
+
+    /// Variables declared in the cert function
+    pub fn variables(&self) -> &[T] {
+        &self.variables
+    }
+
+    /// The invariant formula
+    pub fn formula(&self) -> &Formula<T> {
+        &self.formula
+    }
+
+    pub fn into_parts(self) -> (Vec<T>, Formula<T>) {
+        (self.variables, self.formula)
     }
 
     /// Map variable type from T to U
@@ -585,11 +760,22 @@ impl<T: Eq + Hash> ProofInvariant<T> {
 }
 
 impl<T: Clone + Eq + Hash + Display> ProofInvariant<Either<usize, T>> {
+    /// Left index not used by any variable of the invariant
+    fn fresh_left_index(&self) -> usize {
+        let free = self.formula.collect_free_variables();
+        self.variables
+            .iter()
+            .chain(&free)
+            .filter_map(|var| var.as_ref().left().map(|i| i + 1))
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Add one token of the given variable to all multisets satisfying this invariant
-    /// Q(n_a, n_b, ...) = ∃e0. P(n_a, n_b, ...) ∧ n_a = e0 + 1
+    /// Q(n_a, n_b, ...) = ∃e0. P(e0, n_b, ...) ∧ n_a = e0 + 1
     pub fn add_one(&self, var: &T) -> ProofInvariant<Either<usize, T>> {
         // Create a fresh existential variable index
-        let fresh_idx = 0usize;
+        let fresh_idx = self.fresh_left_index();
         let fresh_var = Either::Left(fresh_idx);
 
         // Add the fresh variable to the variable list
@@ -603,9 +789,15 @@ impl<T: Clone + Eq + Hash + Display> ProofInvariant<Either<usize, T>> {
         let constraint_expr = var_expr.sub(&fresh_expr).sub(&one);
         let constraint = Constraint::new(constraint_expr, CompOp::Eq);
 
+        // The original formula talks about the value before the step
+        let substituted_formula = self.formula.substitute_var(
+            &Either::Right(var.clone()),
+            Variable::Var(fresh_var.clone()),
+        );
+
         // Combine original formula with the constraint
         let combined_formula =
-            Formula::And(vec![self.formula.clone(), Formula::Constraint(constraint)]);
+            Formula::And(vec![substituted_formula, Formula::Constraint(constraint)]);
 
         // Create the proof invariant with the existential variable, then quantify it
         let proof_with_existential = ProofInvariant::new(new_variables, combined_formula);
@@ -621,7 +813,7 @@ impl<T: Clone + Eq + Hash + Display> ProofInvariant<Either<usize, T>> {
     /// Q(n_a, n_b, ...) = ∃e0. P(e0, n_b, ...) ∧ e0 = n_a + 1 ∧ e0 ≥ 1
     pub fn filter_and_subtract_one(&self, var: &T) -> ProofInvariant<Either<usize, T>> {
         // Create a fresh existential variable index
-        let fresh_idx = 0usize;
+        let fresh_idx = self.fresh_left_index();
         let fresh_var = Either::Left(fresh_idx);
 
         // Add the fresh variable to the variable list
@@ -880,32 +1072,74 @@ impl<T: Clone + Eq + Hash> Formula<T> {
         }
     }
 
+    /// Replace the bound variable `Existential(idx)` by `value`, leaving occurrences
+    /// under a nested binder of the same index alone (those refer to that binder)
+    pub(crate) fn instantiate(self, idx: usize, value: &Variable<T>) -> Self {
+        match self {
+            Formula::Constraint(c) => Formula::Constraint(c.rename_vars(|v| match v {
+                Variable::Existential(n) if n == idx => value.clone(),
+                v => v,
+            })),
+            Formula::And(formulas) => Formula::And(
+                formulas
+                    .into_iter()
+                    .map(|f| f.instantiate(idx, value))
+                    .collect(),
+            ),
+            Formula::Or(formulas) => Formula::Or(
+                formulas
+                    .into_iter()
+                    .map(|f| f.instantiate(idx, value))
+                    .collect(),
+            ),
+            Formula::Exists(n, body) if n == idx => Formula::Exists(n, body),
+            Formula::Forall(n, body) if n == idx => Formula::Forall(n, body),
+            Formula::Exists(n, body) => Formula::Exists(n, Box::new(body.instantiate(idx, value))),
+            Formula::Forall(n, body) => Formula::Forall(n, Box::new(body.instantiate(idx, value))),
+        }
+    }
+
+    /// A binder index not used anywhere in the formula, so that binding it cannot
+    /// capture an existing occurrence
+    pub(crate) fn fresh_existential_index(&self) -> usize {
+        self.max_existential_index().map(|n| n + 1).unwrap_or(0)
+    }
+
     /// Create an existentially quantified formula
     pub fn mk_exists(self, var_to_bind: T) -> Self {
-        let fresh_idx = self.max_existential_index().map(|n| n + 1).unwrap_or(0);
+        let fresh_idx = self.fresh_existential_index();
         let substituted = self.substitute_var(&var_to_bind, Variable::Existential(fresh_idx));
         Formula::Exists(fresh_idx, Box::new(substituted))
     }
 
     /// Create a universally quantified formula
     pub fn mk_forall(self, var_to_bind: T) -> Self {
-        let fresh_idx = self.max_existential_index().map(|n| n + 1).unwrap_or(0);
+        let fresh_idx = self.fresh_existential_index();
         let substituted = self.substitute_var(&var_to_bind, Variable::Existential(fresh_idx));
         Formula::Forall(fresh_idx, Box::new(substituted))
     }
 }
 
-/// Parser for SMT-LIB proof certificates
-pub struct Parser {
-    input: Vec<char>,
+/// Parser for SMT-LIB proof certificates.
+///
+/// Proofs from SMPT can be several megabytes and deeply nested. The parser reads
+/// tokens straight from the input string, and keeps the lists of a formula that are
+/// still open on an explicit stack (`Frame`) rather than on the call stack, so the
+/// nesting depth of a proof is only limited by memory.
+pub struct Parser<'a> {
+    input: &'a str,
+    /// Byte offset of the next token
     pos: usize,
-    /// Variables declared in the current scope
-    declared_vars: Vec<String>,
+    /// Variables in scope, innermost last
+    scope: Vec<&'a str>,
+    /// Number of occurrences of each variable in `scope`
+    declared: HashMap<&'a str, usize>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub message: String,
+    /// Byte offset in the input
     pub position: usize,
 }
 
@@ -923,691 +1157,459 @@ impl std::error::Error for ParseError {}
 
 type Result<T> = std::result::Result<T, ParseError>;
 
-impl Parser {
-    fn new(input: &str) -> Self {
-        Parser {
-            input: input.chars().collect(),
-            pos: 0,
-            declared_vars: Vec::new(),
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    Atom(&'a str),
+}
 
-    fn error(&self, msg: &str) -> ParseError {
-        ParseError {
-            message: msg.to_string(),
-            position: self.pos,
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Atom(atom) => write!(f, "{}", atom),
         }
     }
+}
+
+/// A list whose arguments are still being parsed
+enum Frame<'a> {
+    And(Vec<Formula<String>>),
+    Or(Vec<Formula<String>>),
+    Not,
+    Implies(Option<Formula<String>>),
+    Quantifier {
+        exists: bool,
+        vars: Vec<String>,
+        /// Length of the scope outside the quantifier
+        outer_scope: usize,
+    },
+    Comparison {
+        op: &'a str,
+        lhs: Option<AffineExpr<String>>,
+    },
+    Sum(AffineExpr<String>),
+    Difference(Option<AffineExpr<String>>),
+    Product(Option<AffineExpr<String>>),
+}
 
-    fn peek(&self) -> Option<char> {
-        self.input.get(self.pos).copied()
+impl Frame<'_> {
+    /// Whether the arguments are arithmetic expressions rather than formulas
+    fn takes_exprs(&self) -> bool {
+        matches!(
+            self,
+            Frame::Comparison { .. } | Frame::Sum(_) | Frame::Difference(_) | Frame::Product(_)
+        )
     }
 
-    fn advance(&mut self) {
-        self.pos += 1;
+    /// Whether the list takes any number of arguments
+    fn is_variadic(&self) -> bool {
+        matches!(self, Frame::And(_) | Frame::Or(_) | Frame::Sum(_))
     }
+}
 
-    fn skip_whitespace(&mut self) {
-        while let Some(ch) = self.peek() {
-            if ch.is_whitespace() {
-                self.advance();
-            } else {
-                break;
-            }
+/// A parsed argument of a list
+enum Value {
+    Formula(Formula<String>),
+    Expr(AffineExpr<String>),
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            scope: Vec::new(),
+            declared: HashMap::default(),
         }
     }
 
-    fn skip_comment(&mut self) {
-        if self.peek() == Some(';') {
-            while let Some(ch) = self.peek() {
-                self.advance();
-                if ch == '\n' {
-                    break;
-                }
-            }
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError {
+            message: msg.to_string(),
+            position: self.pos,
         }
     }
 
     fn skip_ws_and_comments(&mut self) {
         loop {
-            self.skip_whitespace();
-            if self.peek() == Some(';') {
-                self.skip_comment();
-            } else {
+            let rest = &self.input[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with(';') {
                 break;
             }
+            self.pos += trimmed.find('\n').map_or(trimmed.len(), |i| i + 1);
         }
     }
 
-    fn expect_char(&mut self, expected: char) -> Result<()> {
+    fn next_token(&mut self) -> Option<Token<'a>> {
         self.skip_ws_and_comments();
-        match self.peek() {
-            Some(ch) if ch == expected => {
-                self.advance();
-                Ok(())
+        let rest = &self.input[self.pos..];
+        match rest.chars().next()? {
+            '(' => {
+                self.pos += 1;
+                Some(Token::Open)
             }
-            Some(ch) => Err(self.error(&format!("Expected '{}', found '{}'", expected, ch))),
-            None => Err(self.error(&format!("Expected '{}', found EOF", expected))),
-        }
-    }
-
-    fn parse_atom(&mut self) -> Result<String> {
-        self.skip_ws_and_comments();
-
-        let mut token = String::new();
-
-        // Check for negative numbers
-        if self.peek() == Some('-') {
-            token.push('-');
-            self.advance();
-        }
-
-        // Collect the rest
-        while let Some(ch) = self.peek() {
-            if ch.is_whitespace() || ch == '(' || ch == ')' {
-                break;
+            ')' => {
+                self.pos += 1;
+                Some(Token::Close)
+            }
+            _ => {
+                let len = rest
+                    .find(|ch: char| ch.is_whitespace() || ch == '(' || ch == ')')
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Some(Token::Atom(&rest[..len]))
             }
-            token.push(ch);
-            self.advance();
-        }
-
-        if token.is_empty() {
-            Err(self.error("Expected atom"))
-        } else {
-            Ok(token)
         }
     }
 
-    fn parse_integer(&mut self) -> Result<i64> {
-        let atom = self.parse_atom()?;
-        atom.parse::<i64>()
-            .map_err(|_| self.error(&format!("Invalid integer: {}", atom)))
-    }
-
-    fn peek_atom(&mut self) -> Result<Option<String>> {
+    fn peek_token(&mut self) -> Option<Token<'a>> {
         let saved_pos = self.pos;
-        self.skip_ws_and_comments();
-
-        if self.peek() == Some('(') {
-            self.pos = saved_pos;
-            return Ok(None);
-        }
-
-        match self.parse_atom() {
-            Ok(atom) => {
-                self.pos = saved_pos;
-                Ok(Some(atom))
-            }
-            Err(_) => {
-                self.pos = saved_pos;
-                Ok(None)
-            }
-        }
+        let token = self.next_token();
+        self.pos = saved_pos;
+        token
     }
 
-    /// Parse an affine expression
-    fn parse_affine_expr(&mut self) -> Result<AffineExpr<String>> {
-        self.skip_ws_and_comments();
-
-        // Check if it's a list or atom
-        if self.peek() != Some('(') {
-            // It's an atom - either integer or variable
-            let atom = self.parse_atom()?;
-            if let Ok(n) = atom.parse::<i64>() {
-                Ok(AffineExpr::from_const(n))
-            } else {
-                // Variables with @ are allowed - they come from SMPT output
-                // Check if variable is declared (without the @suffix if present)
-                let base_var = atom.split('@').next().unwrap_or(&atom);
-                if !self.declared_vars.contains(&base_var.to_string())
-                    && !self.declared_vars.contains(&atom)
-                {
-                    return Err(self.error(&format!("Undefined variable: {}", atom)));
-                }
-                Ok(AffineExpr::from_var(atom))
-            }
-        } else {
-            // It's a list - parse operation
-            self.expect_char('(')?;
-            let op = self.parse_atom()?;
-
-            match op.as_str() {
-                "+" => {
-                    let mut result = AffineExpr::new();
-
-                    // Parse all arguments
-                    while self.peek() != Some(')') {
-                        let arg = self.parse_affine_expr()?;
-                        result = result.add(&arg);
-                    }
-
-                    self.expect_char(')')?;
-                    Ok(result)
-                }
-                "-" => {
-                    let lhs = self.parse_affine_expr()?;
-                    let rhs = self.parse_affine_expr()?;
-                    self.expect_char(')')?;
-                    Ok(lhs.sub(&rhs))
-                }
-                "*" => {
-                    // Parse first argument
-                    let arg1 = self.parse_affine_expr()?;
-                    let arg2 = self.parse_affine_expr()?;
-                    self.expect_char(')')?;
-
-                    // One must be constant
-                    if arg1.is_constant() {
-                        Ok(arg2.mul_by_const(arg1.get_constant()))
-                    } else if arg2.is_constant() {
-                        Ok(arg1.mul_by_const(arg2.get_constant()))
-                    } else {
-                        Err(self.error("Multiplication requires at least one constant"))
-                    }
-                }
-                _ => Err(self.error(&format!("Unknown arithmetic operation: {}", op))),
-            }
+    fn expect(&mut self, expected: Token<'_>) -> Result<()> {
+        match self.next_token() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(self.error(&format!("Expected '{}', found '{}'", expected, token))),
+            None => Err(self.error(&format!("Expected '{}', found EOF", expected))),
         }
     }
 
-    /// Parse a constraint (comparison)
-    fn parse_constraint(&mut self) -> Result<Constraint<String>> {
-        self.expect_char('(')?;
-        let op = self.parse_atom()?;
-
-        let comp_op = match op.as_str() {
-            "=" => CompOp::Eq,
-            ">=" => CompOp::Geq,
-            ">" => {
-                // Convert > to >= by adjusting constant
-                let lhs = self.parse_affine_expr()?;
-                let rhs = self.parse_affine_expr()?;
-                self.expect_char(')')?;
-
-                // lhs > rhs becomes lhs - rhs > 0 becomes lhs - rhs - 1 >= 0
-                let mut expr = lhs.sub(&rhs);
-                expr.constant -= 1;
-                return Ok(Constraint::new(expr, CompOp::Geq));
-            }
-            "<=" => {
-                // Convert <= to >= by negation
-                let lhs = self.parse_affine_expr()?;
-                let rhs = self.parse_affine_expr()?;
-                self.expect_char(')')?;
-
-                // lhs <= rhs becomes rhs - lhs >= 0
-                let expr = rhs.sub(&lhs);
-                return Ok(Constraint::new(expr, CompOp::Geq));
-            }
-            "<" => {
-                // Convert < to >= by negation and adjustment
-                let lhs = self.parse_affine_expr()?;
-                let rhs = self.parse_affine_expr()?;
-                self.expect_char(')')?;
-
-                // lhs < rhs becomes rhs - lhs > 0 becomes rhs - lhs - 1 >= 0
-                let mut expr = rhs.sub(&lhs);
-                expr.constant -= 1;
-                return Ok(Constraint::new(expr, CompOp::Geq));
-            }
-            _ => return Err(self.error(&format!("Unknown comparison operator: {}", op))),
-        };
-
-        // For = and >=, parse normally
-        let lhs = self.parse_affine_expr()?;
-        let rhs = self.parse_affine_expr()?;
-        self.expect_char(')')?;
-
-        // Convert to expr op 0 form
-        let expr = lhs.sub(&rhs);
-        Ok(Constraint::new(expr, comp_op))
+    fn parse_atom(&mut self) -> Result<&'a str> {
+        match self.next_token() {
+            Some(Token::Atom(atom)) => Ok(atom),
+            _ => Err(self.error("Expected atom")),
+        }
     }
 
-    /// Parse quantified variables list: ((x Int) (y Int) ...) or empty ()
-    fn parse_var_list(&mut self) -> Result<Vec<String>> {
-        self.expect_char('(')?;
-        self.skip_ws_and_comments();
-
+    /// Parse a variable list `((x Int) (y Int) ...)`, which may be empty
+    fn parse_var_list(&mut self) -> Result<Vec<&'a str>> {
+        self.expect(Token::Open)?;
         let mut vars = Vec::new();
-
-        // Check for empty variable list
-        if self.peek() == Some(')') {
-            self.advance();
-            return Ok(vars); // Empty variable list
-        }
-
-        while self.peek() != Some(')') {
-            self.expect_char('(')?;
+        while self.peek_token() != Some(Token::Close) {
+            self.expect(Token::Open)?;
             let var_name = self.parse_atom()?;
             let var_type = self.parse_atom()?;
-            self.expect_char(')')?;
+            self.expect(Token::Close)?;
 
             if var_type != "Int" {
                 return Err(self.error(&format!("Expected Int type, got {}", var_type)));
             }
-
             vars.push(var_name);
-            self.skip_ws_and_comments();
         }
-
-        self.expect_char(')')?;
+        self.expect(Token::Close)?;
         Ok(vars)
     }
 
-    /// Negate a normalized formula using De Morgan's laws
-    fn negate_formula(formula: Formula<String>) -> Formula<String> {
-        match formula {
-            Formula::Constraint(c) => {
-                match c.op {
-                    CompOp::Eq => {
-                        // ¬(expr = 0) becomes (expr > 0) ∨ (expr < 0)
-                        // which is (expr >= 1) ∨ (-expr >= 1)
-                        let pos_expr = c.expr.clone();
-                        let mut pos_constraint = Constraint::new(pos_expr, CompOp::Geq);
-                        pos_constraint.expr.constant -= 1;
-
-                        let neg_expr = c.expr.negate();
-                        let mut neg_constraint = Constraint::new(neg_expr, CompOp::Geq);
-                        neg_constraint.expr.constant -= 1;
+    fn declare(&mut self, vars: &[&'a str]) {
+        for &var in vars {
+            self.scope.push(var);
+            *self.declared.entry(var).or_insert(0) += 1;
+        }
+    }
 
-                        Formula::Or(vec![
-                            Formula::Constraint(pos_constraint),
-                            Formula::Constraint(neg_constraint),
-                        ])
-                    }
-                    CompOp::Geq => {
-                        // ¬(expr >= 0) becomes expr < 0 which is -expr - 1 >= 0
-                        let mut neg_expr = c.expr.negate();
-                        neg_expr.constant -= 1;
-                        Formula::Constraint(Constraint::new(neg_expr, CompOp::Geq))
-                    }
+    /// Drop the variables declared after the scope had length `len`
+    fn restore_scope(&mut self, len: usize) {
+        for var in self.scope.drain(len..) {
+            if let Some(count) = self.declared.get_mut(var) {
+                *count -= 1;
+                if *count == 0 {
+                    self.declared.remove(var);
                 }
             }
-            Formula::And(formulas) => {
-                // ¬(A ∧ B) = ¬A ∨ ¬B
-                let negated: Vec<Formula<String>> =
-                    formulas.into_iter().map(Self::negate_formula).collect();
-                Formula::Or(negated)
-            }
-            Formula::Or(formulas) => {
-                // ¬(A ∨ B) = ¬A ∧ ¬B
-                let negated: Vec<Formula<String>> =
-                    formulas.into_iter().map(Self::negate_formula).collect();
-                Formula::And(negated)
-            }
-            Formula::Exists(var, body) => {
-                // ¬∃x.P = ∀x.¬P
-                Formula::Forall(var, Box::new(Self::negate_formula(*body)))
+        }
+    }
+
+    /// An integer or a declared variable
+    fn parse_expr_atom(&self, atom: &str) -> Result<AffineExpr<String>> {
+        if let Ok(n) = atom.parse::<i64>() {
+            return Ok(AffineExpr::from_const(n));
+        }
+        // Variables with @ are allowed - they come from SMPT output
+        // Check if variable is declared (without the @suffix if present)
+        let base_var = atom.split('@').next().unwrap_or(atom);
+        if !self.declared.contains_key(base_var) && !self.declared.contains_key(atom) {
+            return Err(self.error(&format!("Undefined variable: {}", atom)));
+        }
+        Ok(AffineExpr::from_var(atom.to_string()))
+    }
+
+    /// Normalize `lhs op rhs` to `expr >= 0` or `expr = 0`
+    fn comparison(
+        op: &str,
+        lhs: AffineExpr<String>,
+        rhs: AffineExpr<String>,
+    ) -> Constraint<String> {
+        match op {
+            "=" => Constraint::new(lhs.sub(&rhs), CompOp::Eq),
+            ">=" => Constraint::new(lhs.sub(&rhs), CompOp::Geq),
+            ">" => {
+                // lhs > rhs becomes lhs - rhs - 1 >= 0
+                let mut expr = lhs.sub(&rhs);
+                expr.constant -= 1;
+                Constraint::new(expr, CompOp::Geq)
             }
-            Formula::Forall(var, body) => {
-                // ¬∀x.P = ∃x.¬P
-                Formula::Exists(var, Box::new(Self::negate_formula(*body)))
+            "<=" => Constraint::new(rhs.sub(&lhs), CompOp::Geq),
+            "<" => {
+                // lhs < rhs becomes rhs - lhs - 1 >= 0
+                let mut expr = rhs.sub(&lhs);
+                expr.constant -= 1;
+                Constraint::new(expr, CompOp::Geq)
             }
+            _ => unreachable!("comparison operators are checked when the list is opened"),
         }
     }
 
+    /// Parse a formula
     /// Parse a formula
     fn parse_formula(&mut self) -> Result<Formula<String>> {
-        self.skip_ws_and_comments();
-
-        // Check for bare atoms first (true/false)
-        if self.peek() != Some('(') {
-            // Try to parse an atom
-            if let Ok(atom) = self.parse_atom() {
-                match atom.as_str() {
-                    "true" => return Ok(Formula::And(vec![])), // Empty AND
-                    "false" => return Ok(Formula::Or(vec![])), // Empty OR
-                    _ => {
-                        return Err(self.error(&format!("Expected formula, found atom '{}'", atom)));
-                    }
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            let mut value = if stack.last().is_some_and(Frame::is_variadic)
+                && self.peek_token() == Some(Token::Close)
+            {
+                self.next_token();
+                Some(match stack.pop() {
+                    Some(Frame::And(formulas)) => Value::Formula(Formula::And(formulas)),
+                    Some(Frame::Or(formulas)) => Value::Formula(Formula::Or(formulas)),
+                    Some(Frame::Sum(expr)) => Value::Expr(expr),
+                    _ => unreachable!(),
+                })
+            } else {
+                self.parse_argument(&mut stack)?
+            };
+
+            // Hand finished arguments to the lists they belong to
+            while let Some(argument) = value.take() {
+                match stack.pop() {
+                    None => match argument {
+                        Value::Formula(formula) => return Ok(formula),
+                        Value::Expr(_) => unreachable!("the outermost argument is a formula"),
+                    },
+                    Some(frame) => value = self.add_argument(frame, argument, &mut stack)?,
                 }
             }
-            return Err(self.error("Expected '(' to start formula"));
         }
+    }
 
-        self.expect_char('(')?;
-
-        // Peek ahead to see what we have
-        let op = if let Ok(Some(atom)) = self.peek_atom() {
-            self.parse_atom()?;
-            atom
-        } else {
-            // Empty list or other issue
-            if self.peek() == Some(')') {
-                self.advance();
-                // Empty list - treat as empty AND (true)
-                return Ok(Formula::And(vec![]));
-            }
-            return Err(self.error("Expected operator or closing parenthesis"));
+    /// Parse the next argument of the innermost open list (or the formula itself if
+    /// there is none). Returns `None` if the argument is a list, which is pushed on the
+    /// stack.
+    fn parse_argument(&mut self, stack: &mut Vec<Frame<'a>>) -> Result<Option<Value>> {
+        let exprs = stack.last().is_some_and(Frame::takes_exprs);
+        let token = match self.next_token() {
+            Some(token) => token,
+            None if exprs => return Err(self.error("Expected expression, found EOF")),
+            None => return Err(self.error("Expected formula, found EOF")),
         };
-
-        match op.as_str() {
-            "and" => {
-                self.skip_ws_and_comments();
-
-                // Check for empty (and )
-                if self.peek() == Some(')') {
-                    self.advance();
-                    return Ok(Formula::And(vec![])); // Empty AND = true
+        let atom = match token {
+            Token::Close => return Err(self.error("Unexpected ')'")),
+            Token::Atom(atom) if exprs => {
+                return Ok(Some(Value::Expr(self.parse_expr_atom(atom)?)));
+            }
+            Token::Atom("true") => return Ok(Some(Value::Formula(Formula::And(vec![])))), // Empty AND
+            Token::Atom("false") => return Ok(Some(Value::Formula(Formula::Or(vec![])))), // Empty OR
+            Token::Atom(atom) => {
+                return Err(self.error(&format!("Expected formula, found atom '{}'", atom)));
+            }
+            Token::Open => match self.next_token() {
+                Some(Token::Atom(op)) => op,
+                // Empty list - treat as empty AND (true)
+                Some(Token::Close) if !exprs => {
+                    return Ok(Some(Value::Formula(Formula::And(vec![]))));
                 }
+                _ => return Err(self.error("Expected operator or closing parenthesis")),
+            },
+        };
 
-                let mut formulas = Vec::new();
-                while self.peek() != Some(')') {
-                    let formula = self.parse_formula()?;
-
-                    // Skip empty AND (true) and empty OR (false) in AND context
-                    // According to the test, these should just be ignored
-                    if let Formula::And(ref parts) = formula {
-                        if parts.is_empty() {
-                            self.skip_ws_and_comments();
-                            continue; // Skip empty AND
-                        }
-                    }
-
-                    if let Formula::Or(ref parts) = formula {
-                        if parts.is_empty() {
-                            self.skip_ws_and_comments();
-                            continue; // Skip empty OR
-                        }
+        let frame = if exprs {
+            match atom {
+                "+" => Frame::Sum(AffineExpr::new()),
+                "-" => Frame::Difference(None),
+                "*" => Frame::Product(None),
+                _ => return Err(self.error(&format!("Unknown arithmetic operation: {}", atom))),
+            }
+        } else {
+            match atom {
+                "and" => Frame::And(Vec::new()),
+                "or" => Frame::Or(Vec::new()),
+                "not" => Frame::Not,
+                "=>" | "implies" => Frame::Implies(None),
+                "exists" | "forall" => {
+                    let vars = self.parse_var_list()?;
+                    let outer_scope = self.scope.len();
+                    self.declare(&vars);
+                    Frame::Quantifier {
+                        exists: atom == "exists",
+                        vars: vars.into_iter().map(str::to_string).collect(),
+                        outer_scope,
                     }
-
-                    formulas.push(formula);
-                    self.skip_ws_and_comments();
                 }
-
-                self.expect_char(')')?;
-                Ok(Formula::And(formulas))
+                "=" | ">=" | ">" | "<=" | "<" => Frame::Comparison {
+                    op: atom,
+                    lhs: None,
+                },
+                _ => return Err(self.error(&format!("Unknown formula operator: {}", atom))),
             }
-            "or" => {
-                self.skip_ws_and_comments();
-
-                // Check for empty (or )
-                if self.peek() == Some(')') {
-                    self.advance();
-                    return Ok(Formula::Or(vec![])); // Empty OR = false
+        };
+        stack.push(frame);
+        Ok(None)
+    }
+
+    /// Add an argument to a list. If that was its last argument, consume the closing
+    /// parenthesis and return the value of the list; otherwise put the list back on the
+    /// stack.
+    fn add_argument(
+        &mut self,
+        frame: Frame<'a>,
+        argument: Value,
+        stack: &mut Vec<Frame<'a>>,
+    ) -> Result<Option<Value>> {
+        // Empty AND (true) and empty OR (false) are skipped in AND and OR lists
+        let trivial = |formula: &Formula<String>| matches!(formula, Formula::And(parts) | Formula::Or(parts) if parts.is_empty());
+        let value = match (frame, argument) {
+            (Frame::And(mut formulas), Value::Formula(formula)) => {
+                if !trivial(&formula) {
+                    formulas.push(formula);
                 }
-
-                let mut formulas = Vec::new();
-                while self.peek() != Some(')') {
-                    let formula = self.parse_formula()?;
-
-                    // Skip empty OR (false) and empty AND (true) in OR context
-                    if let Formula::Or(ref parts) = formula {
-                        if parts.is_empty() {
-                            self.skip_ws_and_comments();
-                            continue; // Skip empty OR
-                        }
-                    }
-
-                    if let Formula::And(ref parts) = formula {
-                        if parts.is_empty() {
-                            self.skip_ws_and_comments();
-                            continue; // Skip empty AND
-                        }
-                    }
-
+                stack.push(Frame::And(formulas));
+                return Ok(None);
+            }
+            (Frame::Or(mut formulas), Value::Formula(formula)) => {
+                if !trivial(&formula) {
                     formulas.push(formula);
-                    self.skip_ws_and_comments();
                 }
-
-                self.expect_char(')')?;
-                Ok(Formula::Or(formulas))
+                stack.push(Frame::Or(formulas));
+                return Ok(None);
             }
-            "not" => {
-                let inner = self.parse_formula()?;
-                self.expect_char(')')?;
-                Ok(Self::negate_formula(inner))
+            (Frame::Sum(sum), Value::Expr(expr)) => {
+                stack.push(Frame::Sum(sum.add(&expr)));
+                return Ok(None);
             }
-            "=>" | "implies" => {
-                let lhs = self.parse_formula()?;
-                let rhs = self.parse_formula()?;
-                self.expect_char(')')?;
-
+            (Frame::Implies(None), Value::Formula(lhs)) => {
+                stack.push(Frame::Implies(Some(lhs)));
+                return Ok(None);
+            }
+            (Frame::Comparison { op, lhs: None }, Value::Expr(lhs)) => {
+                stack.push(Frame::Comparison { op, lhs: Some(lhs) });
+                return Ok(None);
+            }
+            (Frame::Difference(None), Value::Expr(lhs)) => {
+                stack.push(Frame::Difference(Some(lhs)));
+                return Ok(None);
+            }
+            (Frame::Product(None), Value::Expr(lhs)) => {
+                stack.push(Frame::Product(Some(lhs)));
+                return Ok(None);
+            }
+            (Frame::Not, Value::Formula(inner)) => Value::Formula(inner.negate()),
+            (Frame::Implies(Some(lhs)), Value::Formula(rhs)) => {
                 // A => B is ¬A ∨ B
-                Ok(Formula::Or(vec![Self::negate_formula(lhs), rhs]))
+                Value::Formula(Formula::Or(vec![lhs.negate(), rhs]))
             }
-            "exists" => {
-                // Save current declared vars
-                let saved_vars = self.declared_vars.clone();
-
-                let vars = self.parse_var_list()?;
-                // Add to declared vars
-                self.declared_vars.extend(vars.clone());
-
-                let body = self.parse_formula()?;
-                self.expect_char(')')?;
-
-                // Restore declared vars
-                self.declared_vars = saved_vars;
-
-                // If no variables, just return the body
-                if vars.is_empty() {
-                    return Ok(body);
-                }
-
+            (
+                Frame::Quantifier {
+                    exists,
+                    vars,
+                    outer_scope,
+                },
+                Value::Formula(body),
+            ) => {
+                self.restore_scope(outer_scope);
                 // Convert multiple variables to nested single quantifiers using smart constructor
                 let mut result = body;
                 for var in vars.into_iter().rev() {
-                    result = result.mk_exists(var);
-                }
-                Ok(result)
-            }
-            "forall" => {
-                // Save current declared vars
-                let saved_vars = self.declared_vars.clone();
-
-                let vars = self.parse_var_list()?;
-                // Add to declared vars
-                self.declared_vars.extend(vars.clone());
-
-                let body = self.parse_formula()?;
-                self.expect_char(')')?;
-
-                // Restore declared vars
-                self.declared_vars = saved_vars;
-
-                // If no variables, just return the body
-                if vars.is_empty() {
-                    return Ok(body);
+                    result = if exists {
+                        result.mk_exists(var)
+                    } else {
+                        result.mk_forall(var)
+                    };
                 }
-
-                // Convert multiple variables to nested single quantifiers using smart constructor
-                let mut result = body;
-                for var in vars.into_iter().rev() {
-                    result = result.mk_forall(var);
+                Value::Formula(result)
+            }
+            (Frame::Comparison { op, lhs: Some(lhs) }, Value::Expr(rhs)) => {
+                Value::Formula(Formula::Constraint(Self::comparison(op, lhs, rhs)))
+            }
+            (Frame::Difference(Some(lhs)), Value::Expr(rhs)) => Value::Expr(lhs.sub(&rhs)),
+            (Frame::Product(Some(lhs)), Value::Expr(rhs)) => {
+                // One must be constant
+                if lhs.is_constant() {
+                    Value::Expr(rhs.mul_by_const(lhs.get_constant()))
+                } else if rhs.is_constant() {
+                    Value::Expr(lhs.mul_by_const(rhs.get_constant()))
+                } else {
+                    return Err(self.error("Multiplication requires at least one constant"));
                 }
-                Ok(result)
-            }
-            "=" | ">=" | ">" | "<=" | "<" => {
-                // It's a constraint - we already consumed '(' and the operator
-                // So we need to parse it inline
-                let comp_op = match op.as_str() {
-                    "=" => CompOp::Eq,
-                    ">=" => CompOp::Geq,
-                    ">" => {
-                        // Convert > to >= by adjusting constant
-                        let lhs = self.parse_affine_expr()?;
-                        let rhs = self.parse_affine_expr()?;
-                        self.expect_char(')')?;
-
-                        // lhs > rhs becomes lhs - rhs > 0 becomes lhs - rhs - 1 >= 0
-                        let mut expr = lhs.sub(&rhs);
-                        expr.constant -= 1;
-                        return Ok(Formula::Constraint(Constraint::new(expr, CompOp::Geq)));
-                    }
-                    "<=" => {
-                        // Convert <= to >= by negation
-                        let lhs = self.parse_affine_expr()?;
-                        let rhs = self.parse_affine_expr()?;
-                        self.expect_char(')')?;
-
-                        // lhs <= rhs becomes rhs - lhs >= 0
-                        let expr = rhs.sub(&lhs);
-                        return Ok(Formula::Constraint(Constraint::new(expr, CompOp::Geq)));
-                    }
-                    "<" => {
-                        // Convert < to >= by negation and adjustment
-                        let lhs = self.parse_affine_expr()?;
-                        let rhs = self.parse_affine_expr()?;
-                        self.expect_char(')')?;
-
-                        // lhs < rhs becomes rhs - lhs > 0 becomes rhs - lhs - 1 >= 0
-                        let mut expr = rhs.sub(&lhs);
-                        expr.constant -= 1;
-                        return Ok(Formula::Constraint(Constraint::new(expr, CompOp::Geq)));
-                    }
-                    _ => unreachable!(),
-                };
-
-                // For = and >=, parse normally
-                let lhs = self.parse_affine_expr()?;
-                let rhs = self.parse_affine_expr()?;
-                self.expect_char(')')?;
-
-                // Convert to expr op 0 form
-                let expr = lhs.sub(&rhs);
-                Ok(Formula::Constraint(Constraint::new(expr, comp_op)))
             }
-            _ => Err(self.error(&format!("Unknown formula operator: {}", op))),
-        }
+            _ => unreachable!(
+                "arguments are parsed as formulas or expressions as their list requires"
+            ),
+        };
+        self.expect(Token::Close)?;
+        Ok(Some(value))
     }
 
     /// Parse a complete SMT-LIB file to extract the cert function
     fn parse_smtlib(&mut self) -> Result<ProofInvariant<String>> {
-        let mut cert_found = false;
-        let mut variables = Vec::new();
-        let mut formula = None;
-
-        while self.pos < self.input.len() {
+        loop {
             self.skip_ws_and_comments();
-
-            if self.pos >= self.input.len() {
-                break;
-            }
-
-            // Each top-level form should be a list
-            if self.peek() != Some('(') {
-                // If we already found cert, we're done
-                if cert_found {
-                    break;
+            let start = self.pos;
+            match self.next_token() {
+                None => return Err(self.error("No cert function found in proof file")),
+                Some(Token::Open) => {}
+                // Each top-level form should be a list
+                Some(_) => {
+                    self.pos = start;
+                    return Err(self.error("Expected '(' at top level"));
                 }
-                return Err(self.error("Expected '(' at top level"));
-            }
-
-            // Check if this is define-fun cert
-            let saved_pos = self.pos;
-            self.advance(); // skip '('
-
-            if let Ok(cmd) = self.parse_atom() {
-                if cmd == "define-fun" {
-                    if let Ok(name) = self.parse_atom() {
-                        if name == "cert" {
-                            // Parse the cert function
-                            cert_found = true;
-
-                            // Parse parameters
-                            self.expect_char('(')?;
-                            while self.peek() != Some(')') {
-                                self.expect_char('(')?;
-                                let var_name = self.parse_atom()?;
-                                let var_type = self.parse_atom()?;
-                                self.expect_char(')')?;
-
-                                if var_type != "Int" {
-                                    return Err(
-                                        self.error(&format!("Expected Int type, got {}", var_type))
-                                    );
-                                }
-
-                                variables.push(var_name);
-                            }
-                            self.expect_char(')')?;
-
-                            // Parse return type
-                            let ret_type = self.parse_atom()?;
-                            if ret_type != "Bool" {
-                                return Err(self.error(&format!(
-                                    "Expected Bool return type, got {}",
-                                    ret_type
-                                )));
-                            }
-
-                            // Set declared variables for body parsing
-                            self.declared_vars = variables.clone();
-
-                            // Parse body
-                            formula = Some(self.parse_formula()?);
-
-                            // Clear declared variables
-                            self.declared_vars.clear();
-
-                            self.expect_char(')')?; // close define-fun
+            }
 
-                            // Once we found cert, we can stop parsing
-                            break;
-                        } else {
-                            // Not cert, skip to end of this form
-                            self.pos = saved_pos;
-                            self.skip_form()?;
-                        }
-                    } else {
-                        self.pos = saved_pos;
-                        self.skip_form()?;
-                    }
-                } else {
-                    // Not define-fun, skip
-                    self.pos = saved_pos;
-                    self.skip_form()?;
-                }
-            } else {
-                self.pos = saved_pos;
+            let is_cert = self.next_token() == Some(Token::Atom("define-fun"))
+                && self.next_token() == Some(Token::Atom("cert"));
+            if !is_cert {
+                self.pos = start;
                 self.skip_form()?;
+                continue;
             }
-        }
 
-        if !cert_found {
-            return Err(self.error("No cert function found in proof file"));
-        }
+            let variables = self.parse_var_list()?;
+            let ret_type = self.parse_atom()?;
+            if ret_type != "Bool" {
+                return Err(self.error(&format!("Expected Bool return type, got {}", ret_type)));
+            }
 
-        Ok(ProofInvariant::new(variables, formula.unwrap()))
+            self.declare(&variables);
+            let formula = self.parse_formula()?;
+            self.restore_scope(0);
+            self.expect(Token::Close)?; // close define-fun
+
+            // Once we found cert, we can stop parsing
+            let variables = variables.into_iter().map(str::to_string).collect();
+            return Ok(ProofInvariant::new(variables, formula));
+        }
     }
 
-    /// Skip an S-expression form
+    /// Skip an S-expression form, starting at its opening parenthesis
     fn skip_form(&mut self) -> Result<()> {
-        self.skip_ws_and_comments();
-
-        if self.peek() == Some('(') {
-            self.advance();
-            let mut depth = 1;
-
-            while depth > 0 && self.pos < self.input.len() {
-                match self.peek() {
-                    Some('(') => {
-                        depth += 1;
-                        self.advance();
-                    }
-                    Some(')') => {
-                        depth -= 1;
-                        self.advance();
-                    }
-                    Some(';') => {
-                        self.skip_comment();
-                    }
-                    _ => {
-                        self.advance();
-                    }
-                }
+        let mut depth = 0usize;
+        loop {
+            match self.next_token() {
+                None => return Err(self.error("Unclosed parenthesis")),
+                Some(Token::Open) => depth += 1,
+                Some(Token::Close) => depth -= 1,
+                Some(Token::Atom(_)) => {}
             }
-
-            if depth > 0 {
-                return Err(self.error("Unclosed parenthesis"));
+            if depth == 0 {
+                return Ok(());
             }
-        } else {
-            // Skip atom
-            self.parse_atom()?;
         }
-
-        Ok(())
     }
 }
 
@@ -1620,8 +1622,8 @@ pub fn parse_proof_file(content: &str) -> Result<ProofInvariant<String>> {
 /// Convert to presburger constraint representation
 pub fn to_presburger_constraint(
     constraint: &Constraint<String>,
-) -> crate::presburger::Constraint<crate::presburger::Variable<String>> {
-    use crate::presburger::{Constraint as PConstraint, ConstraintType, Variable};
+) -> crate::constraints::Constraint<Variable<String>> {
+    use crate::constraints::{Constraint as PConstraint, ConstraintType};
 
     let (terms, constant) = constraint.expr.to_linear_combination();
     let linear_combination: Vec<(i32, Variable<String>)> = terms
@@ -1661,6 +1663,24 @@ where
     Some(ProofInvariant::new(mapped_variables, mapped_formula))
 }
 
+/// Rename the variables of a parsed SMPT proof back to the `Display` output of the
+/// places, using the name map saved next to the SMPT inputs (see `naming`)
+pub fn restore_place_names(
+    proof: ProofInvariant<String>,
+    names: &crate::naming::NameMap,
+) -> Option<ProofInvariant<String>> {
+    let mut name_to_display: HashMap<String, String> = names.names.iter().cloned().collect();
+    // Names missing from the map were written with the default `ident` encoding
+    for var in &proof.variables {
+        if !name_to_display.contains_key(var) {
+            if let Some(display) = crate::ident::decode(var) {
+                name_to_display.insert(var.clone(), display);
+            }
+        }
+    }
+    map_proof_variables(proof, &name_to_display)
+}
+
 /// Helper function to map Formula<String> to Formula<P>
 fn map_formula_variables<P>(
     formula: Formula<String>,
@@ -1820,6 +1840,7 @@ fn print_formula_tree<T: fmt::Display + Eq + Hash>(formula: &Formula<T>, indent:
 /// - `And` → intersection of children’s sets.
 /// - `Or`  → union of children’s sets.
 /// - `Exists(x, body)` → project out `x` (we push `x` into the mapping before descending).
+#[cfg(feature = "isl")]
 fn formula_to_presburger(formula: &Formula<String>, mapping: Vec<String>) -> PresburgerSet<String> {
     match formula {
         Formula::Constraint(c) => {
@@ -1829,22 +1850,21 @@ fn formula_to_presburger(formula: &Formula<String>, mapping: Vec<String>) -> Pre
             PresburgerSet::from_quantified_sets(&[qs], mapping)
         }
         Formula::And(children) => {
-            // intersection of all children
-            let mut iter = children
+            // intersection of all children, harmonized once
+            let sets = children
                 .iter()
-                .map(|f| formula_to_presburger(f, mapping.clone()));
-            let first = iter
-                .next()
-                .unwrap_or_else(|| PresburgerSet::universe(mapping.clone()));
-            iter.fold(first, |acc, next| acc.intersection(&next))
+                .map(|f| formula_to_presburger(f, mapping.clone()))
+                .collect();
+            PresburgerSet::intersection_all(sets)
+                .unwrap_or_else(|| PresburgerSet::universe(mapping.clone()))
         }
         Formula::Or(children) => {
-            // union of all children
-            let mut iter = children
+            // union of all children, harmonized once
+            let sets = children
                 .iter()
-                .map(|f| formula_to_presburger(f, mapping.clone()));
-            let first = iter.next().unwrap_or_else(PresburgerSet::zero);
-            iter.fold(first, |acc, next| acc.union(&next))
+                .map(|f| formula_to_presburger(f, mapping.clone()))
+                .collect();
+            PresburgerSet::union_all(sets).unwrap_or_else(PresburgerSet::zero)
         }
         Formula::Exists(_idx, body) => {
             // Existential variables are already handled in the constraints
@@ -1858,6 +1878,7 @@ fn formula_to_presburger(formula: &Formula<String>, mapping: Vec<String>) -> Pre
 }
 
 /// Parse a certificate from disk and build its Presburger set.
+#[cfg(feature = "isl")]
 pub fn parse_and_build_presburger_set<P: AsRef<Path>>(
     path: P,
 ) -> std::result::Result<PresburgerSet<String>, Box<dyn std::error::Error>> {
@@ -1920,6 +1941,7 @@ mod tests {
             }
             _ => panic!("Expected AND formula"),
         }
+        assert_eq!(result.formula.num_constraints(), 3);
     }
 
     #[test]
@@ -1976,6 +1998,156 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_builder() {
+        // Through the crate root, as code built on top of the crate would
+        use crate::{AffineExpr, Constraint, Formula, ProofInvariant};
+
+        let x = || AffineExpr::from_var("x".to_string());
+        let y = || AffineExpr::from_var("y".to_string());
+        let built = ProofInvariant::new(
+            vec!["x".to_string(), "y".to_string()],
+            Formula::and([
+                Constraint::ge(x(), 0).into(),
+                Constraint::ge(y(), 0).into(),
+                Constraint::eq(x().add(&y()), 10).into(),
+            ]),
+        );
+        let parsed = parse_proof_file(
+            "(define-fun cert ((x Int)(y Int)) Bool (and (>= x 0) (>= y 0) (= (+ x y) 10)))",
+        )
+        .unwrap();
+        assert_eq!(built.variables(), parsed.variables());
+        assert_eq!(built.formula(), parsed.formula());
+
+        // The comparisons and quantifiers normalize like their SMT-LIB counterparts
+        let built: Formula<String> = Formula::and([
+            Constraint::lt(x(), 5).into(),
+            Constraint::le(x(), 5).into(),
+            Constraint::gt(x(), 5).into(),
+            Formula::exists(
+                "t".to_string(),
+                Constraint::eq(x().sub(&AffineExpr::term(2, "t".to_string())), 0).into(),
+            ),
+        ]);
+        let parsed = parse_proof_file(
+            "(define-fun cert ((x Int)) Bool \
+             (and (< x 5) (<= x 5) (> x 5) (exists ((t Int)) (= x (* 2 t)))))",
+        )
+        .unwrap();
+        assert_eq!(&built, parsed.formula());
+
+        assert!(ProofInvariant::try_new(vec![], Constraint::ge(x(), 0).into()).is_err());
+    }
+
+    #[test]
+    fn test_binders_avoid_capture() {
+        let a = || Either::<usize, String>::Right("a".to_string());
+
+        // a is tied to a free Left(0); add_one must not reuse that index for its
+        // own existential, and binds it to a fresh bound index
+        let inv = ProofInvariant::new(
+            vec![Either::Left(0), a()],
+            Constraint::eq(
+                AffineExpr::from_var(a()).sub(&AffineExpr::from_var(Either::Left(0))),
+                0,
+            )
+            .into(),
+        );
+        let added = inv.add_one(&"a".to_string());
+        assert_eq!(added.variables(), &[Either::Left(0), a()]);
+        assert_eq!(
+            added.formula().collect_free_variables(),
+            [Either::Left(0), a()].into_iter().collect()
+        );
+        match added.formula() {
+            Formula::Exists(idx, body) => assert!(body.max_existential_index() >= Some(*idx)),
+            other => panic!("Expected Exists, got {:?}", other),
+        }
+
+        // Binding a variable around a formula that already has a binder picks a new index
+        let x = || AffineExpr::from_var("x".to_string());
+        let inner: Formula<String> = Formula::exists(
+            "t".to_string(),
+            Constraint::eq(x().sub(&AffineExpr::term(2, "t".to_string())), 0).into(),
+        );
+        assert!(matches!(inner, Formula::Exists(0, _)));
+        let outer = Formula::exists(
+            "x".to_string(),
+            Formula::and([
+                inner,
+                Constraint::ge(AffineExpr::from_var("y".to_string()).sub(&x()), 0).into(),
+            ]),
+        );
+        assert!(matches!(outer, Formula::Exists(1, _)));
+        assert_eq!(
+            outer.collect_free_variables(),
+            ["y".to_string()].into_iter().collect()
+        );
+
+        // Instantiating a binder leaves occurrences under a shadowing binder alone
+        let e0 = Variable::Existential(0);
+        let shadowed = Formula::Exists(
+            0,
+            Box::new(
+                Constraint::eq(AffineExpr::from_var("x".to_string()), 0)
+                    .rename_vars(|_| e0.clone())
+                    .into(),
+            ),
+        );
+        assert_eq!(
+            shadowed
+                .clone()
+                .instantiate(0, &Variable::Var("z".to_string())),
+            shadowed
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_proof() {
+        // Far deeper than the call stack would allow a recursive parser to go
+        let depth = 100_000;
+        let proof = format!(
+            "(define-fun cert ((x Int)) Bool {}(>= {}x{} 0){})",
+            "(not ".repeat(depth),
+            "(+ 1 ".repeat(depth),
+            ")".repeat(depth),
+            ")".repeat(depth)
+        );
+
+        let result = parse_proof_file(&proof).unwrap();
+        // An even number of negations cancel out
+        match &result.formula {
+            Formula::Constraint(c) => {
+                assert_eq!(c.expr.get_coeff(&Variable::Var("x".to_string())), 1);
+                assert_eq!(c.expr.get_constant(), depth as i64);
+                assert_eq!(c.op, CompOp::Geq);
+            }
+            _ => panic!("Expected constraint"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let unclosed = "(define-fun cert ((x Int)) Bool (and (>= x 0)";
+        let err = parse_proof_file(unclosed).unwrap_err();
+        assert_eq!(err.message, "Expected formula, found EOF");
+
+        // Positions are byte offsets, just after the offending token
+        let err = parse_proof_file("; comment\n(define-fun cert ((x Int)) Bool (>= y 0))")
+            .unwrap_err();
+        assert_eq!(err.message, "Undefined variable: y");
+        assert_eq!(err.position, 47);
+
+        // Quantified variables go out of scope after their quantifier
+        let escaped = "(define-fun cert ((x Int)) Bool (and (exists ((t Int)) (>= t 0)) (>= t x)))";
+        let err = parse_proof_file(escaped).unwrap_err();
+        assert_eq!(err.message, "Undefined variable: t");
+
+        let err = parse_proof_file("(set-logic LIA) (define-fun other () Bool true)").unwrap_err();
+        assert!(err.message.contains("No cert function"));
+    }
+
     #[test]
     fn test_normalization() {
         // Test > becomes >=
@@ -2353,6 +2525,7 @@ fn test_parse_and_print_specific_proof_file() {
 }
 
 #[test]
+#[cfg(feature = "isl")]
 fn test_parse_and_build_set() {
     let proof_path =
         Path::new("out/simple_nonser2_turned_ser_with_locks/smpt_constraints_disjunct_0_proof.txt");
@@ -1,19 +1,29 @@
+#[cfg(feature = "isl")]
+use crate::constraints::Variable;
+#[cfg(feature = "isl")]
 use crate::kleene::Kleene;
-use crate::presburger::{PresburgerSet, QuantifiedSet, Variable};
+#[cfg(feature = "isl")]
+use crate::presburger::{PresburgerSet, QuantifiedSet};
 use crate::proof_parser::{Constraint as ProofConstraint, Formula, ProofInvariant};
+#[cfg(feature = "isl")]
+use crate::var_map::VarMap;
 use either::Either;
 use std::fmt::Display;
 use std::hash::Hash;
+#[cfg(feature = "isl")]
 use std::cell::RefCell;
+#[cfg(feature = "isl")]
 use std::collections::HashMap;
 
 // Thread-local cache for formula_to_presburger
 // Key is a string representation of (formula, mapping)
+#[cfg(feature = "isl")]
 thread_local! {
     static FORMULA_CACHE: RefCell<HashMap<String, PresburgerSet<String>>> = RefCell::new(HashMap::new());
 }
 
 /// Clear the formula_to_presburger cache
+#[cfg(feature = "isl")]
 pub fn clear_formula_cache() {
     FORMULA_CACHE.with(|cache| {
         cache.borrow_mut().clear();
@@ -21,6 +31,7 @@ pub fn clear_formula_cache() {
 }
 
 /// Get the current size of the formula_to_presburger cache
+#[cfg(feature = "isl")]
 pub fn formula_cache_size() -> usize {
     FORMULA_CACHE.with(|cache| {
         cache.borrow().len()
@@ -29,6 +40,7 @@ pub fn formula_cache_size() -> usize {
 
 /// Convert a single affine constraint to a PresburgerSet
 /// Note: This only works when T is String since that's what the proof parser uses
+#[cfg(feature = "isl")]
 pub fn from_affine_constraint(
     constraint: &ProofConstraint<String>,
     mapping: Vec<String>,
@@ -44,10 +56,14 @@ pub fn from_affine_constraint(
 }
 
 /// Convert a Formula to PresburgerSet
+#[cfg(feature = "isl")]
 pub fn formula_to_presburger(
     formula: &Formula<String>,
     mapping: &[String],
 ) -> PresburgerSet<String> {
+    // Order the dimensions like all other sets (see `dim_order`)
+    let mapping = &VarMap::sorted(mapping.iter().cloned());
+
     // Create a cache key from the formula and mapping
     let cache_key = format!("{:?}|{:?}", formula, mapping);
     
@@ -72,9 +88,10 @@ pub fn formula_to_presburger(
 }
 
 /// Internal implementation of formula_to_presburger (not memoized)
+#[cfg(feature = "isl")]
 fn formula_to_presburger_impl(
     formula: &Formula<String>,
-    mapping: &[String],
+    mapping: &VarMap<String>,
 ) -> PresburgerSet<String> {
     match formula {
         Formula::Constraint(constraint) => {
@@ -83,49 +100,56 @@ fn formula_to_presburger_impl(
         }
 
         Formula::And(formulas) => {
-            // AND = intersection of all subformulas
-            formulas
-                .iter()
-                .map(|f| formula_to_presburger(f, mapping))
-                .reduce(|a, b| a.intersection(&b))
-                .unwrap_or_else(|| PresburgerSet::universe(mapping.to_vec()))
+            // AND = intersection of all subformulas, harmonized once
+            PresburgerSet::intersection_all(
+                formulas
+                    .iter()
+                    .map(|f| formula_to_presburger(f, mapping))
+                    .collect(),
+            )
+            .unwrap_or_else(|| PresburgerSet::universe(mapping.to_vec()))
         }
 
         Formula::Or(formulas) => {
-            // OR = union of all subformulas
-            formulas
-                .iter()
-                .map(|f| formula_to_presburger(f, mapping))
-                .reduce(|a, b| a.union(&b))
-                .unwrap_or_else(PresburgerSet::<String>::zero)
+            // OR = union of all subformulas, harmonized once
+            PresburgerSet::union_all(
+                formulas
+                    .iter()
+                    .map(|f| formula_to_presburger(f, mapping))
+                    .collect(),
+            )
+            .unwrap_or_else(PresburgerSet::<String>::zero)
         }
 
-        &Formula::Exists(id, ref form) => {
-            // Generate a fresh name + use it
-            let mut name = format!("tmp{id}");
-            while mapping.contains(&name) {
-                name += "_fresh";
-            }
-            let new_form = form.clone().rename_vars(&mut |v| {
-                if v == Variable::Existential(id) {
-                    Variable::Var(name.clone())
-                } else {
-                    v
+        Formula::Exists(_, _) => {
+            // Collect the whole chain of directly nested quantifiers, so that all of
+            // them are projected out with a single call
+            let mut body = formula;
+            let mut new_mapping = mapping.clone();
+            let mut renaming: Vec<(usize, String)> = Vec::new();
+            while let Formula::Exists(id, inner) = body {
+                // Generate a fresh name + use it
+                let mut name = format!("tmp{id}");
+                while new_mapping.contains(&name) {
+                    name += "_fresh";
                 }
-            });
-            let mut new_mapping = mapping.to_owned();
-            new_mapping.push(name.clone());
-
-            // todo delete - start
-
-            // <-- debug prints! -->
-            // println!("⮕ [debug] new_mapping = {:?}", new_mapping);
-            // println!("⮕ [debug] new_form    = {:?}", new_form);
-
-            // todo delete - end
+                new_mapping.push(name.clone());
+                renaming.push((*id, name));
+                body = inner.as_ref();
+            }
+            // Instantiate innermost binders first: an inner binder with the same index
+            // shadows the outer one, and `instantiate` does not look under it
+            let mut new_form = body.clone();
+            for (id, name) in renaming.iter().rev() {
+                new_form = new_form.instantiate(*id, &Variable::Var(name.clone()));
+            }
 
-            // Recursive call + project out the existential variable
-            formula_to_presburger(&new_form, &new_mapping).project_out(name)
+            // Recursive call + project out the existential variables, which are in
+            // `new_mapping`, so a missing one is a bug in this translation
+            let names: Vec<String> = renaming.into_iter().map(|(_, name)| name).collect();
+            formula_to_presburger(&new_form, &new_mapping)
+                .project_out_many_strict(&names)
+                .unwrap_or_else(|err| panic!("Bug in proof translation: {}", err))
         }
 
         Formula::Forall(_, _) => {
@@ -135,6 +159,7 @@ fn formula_to_presburger_impl(
 }
 
 /// Convert a ProofInvariant to PresburgerSet
+#[cfg(feature = "isl")]
 pub fn proof_invariant_to_presburger(
     proof_invariant: &ProofInvariant<String>,
     mapping: Vec<String>,
@@ -240,6 +265,9 @@ where
 /// Existentially quantify over the given variables
 /// This function wraps the formula in existential quantifiers but keeps the Either type
 /// to avoid type mismatches. The actual projection happens later.
+/// Each `Left(i)` in `existential_vars` is bound to a binder index that is fresh for the
+/// whole formula, so quantifiers already inside the formula cannot capture it.
+/// Other `Left` variables stay free.
 pub fn existentially_quantify_keep_either<T>(
     proof: ProofInvariant<Either<usize, T>>,
     existential_vars: &[usize],
@@ -247,41 +275,21 @@ pub fn existentially_quantify_keep_either<T>(
 where
     T: Clone + PartialEq + Eq + Hash + std::fmt::Display,
 {
-    // Separate variables into existential (Left) and regular (Right)
+    // Separate variables into existential (listed Left) and remaining ones
     let mut existential_in_proof = Vec::new();
     let mut remaining_vars = Vec::new();
 
     for var in proof.variables {
         match &var {
-            Either::Left(i) => {
-                if existential_vars.contains(i) {
-                    existential_in_proof.push(var);
-                } else {
-                    // This shouldn't happen - Left variables should all be existential
-                    panic!(
-                        "Found Left({}) variable that's not in existential_vars list",
-                        i
-                    );
-                }
-            }
-            Either::Right(_) => {
-                remaining_vars.push(var);
-            }
+            Either::Left(i) if existential_vars.contains(i) => existential_in_proof.push(var),
+            _ => remaining_vars.push(var),
         }
     }
 
-    // Wrap the formula with existential quantifiers for each Left(i) variable
+    // Bind each existential variable, innermost last, like the variable order
     let mut formula = proof.formula;
     for ex_var in existential_in_proof.into_iter().rev() {
-        // Extract the usize from Either::Left
-        match ex_var {
-            Either::Left(idx) => {
-                formula = Formula::Exists(idx, Box::new(formula));
-            }
-            Either::Right(_) => {
-                panic!("Expected Left variant for existential variable");
-            }
-        }
+        formula = formula.mk_exists(ex_var);
     }
 
     ProofInvariant {
@@ -349,6 +357,41 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
+    fn test_shadowed_binder() {
+        // ∃e0. x = e0 ∧ (∃e0. e0 = 5): the inner e0 is a different variable, so
+        // this holds for every x, not just x = 5
+        let bound = |expr: AffineExpr<String>| {
+            ProofConstraint::new(expr, CompOp::Eq).rename_vars(|v| match v {
+                Variable::Var(name) if name == "e" => Variable::Existential(0),
+                v => v,
+            })
+        };
+        let x = || AffineExpr::from_var("x".to_string());
+        let e = || AffineExpr::from_var("e".to_string());
+        let formula = Formula::Exists(
+            0,
+            Box::new(Formula::And(vec![
+                Formula::Constraint(bound(x().sub(&e()))),
+                Formula::Exists(
+                    0,
+                    Box::new(Formula::Constraint(bound(
+                        e().sub(&AffineExpr::from_const(5)),
+                    ))),
+                ),
+            ])),
+        );
+        let x_is_7 = Formula::Constraint(ProofConstraint::new(
+            x().sub(&AffineExpr::from_const(7)),
+            CompOp::Eq,
+        ));
+
+        let mapping = vec!["x".to_string()];
+        assert!(!formula_to_presburger(&Formula::And(vec![formula, x_is_7]), &mapping).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "isl")]
     fn test_single_equality_constraint() {
         // Test: x = 5
         let mut expr = AffineExpr::new();
@@ -366,6 +409,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_single_inequality_constraint() {
         // Test: x >= 3 (or x - 3 >= 0)
         let mut expr = AffineExpr::new();
@@ -383,6 +427,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_multi_variable_constraint() {
         // Test: 2x + 3y - 10 = 0
         let mut expr = AffineExpr::new();
@@ -400,6 +445,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_and_formula() {
         // Test: x >= 0 AND x <= 10 (represented as x >= 0 AND -x + 10 >= 0)
         let constraint1 = ProofConstraint::new(AffineExpr::from_var("x".to_string()), CompOp::Geq);
@@ -423,6 +469,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_or_formula() {
         // Test: x = 0 OR x = 5
         let constraint1 = ProofConstraint::new(AffineExpr::from_var("x".to_string()), CompOp::Eq);
@@ -446,6 +493,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_complex_formula() {
         // Test: (x >= 0 AND y >= 0) OR (x = 10 AND y = 20)
         let x_geq_0 = ProofConstraint::new(AffineExpr::from_var("x".to_string()), CompOp::Geq);
@@ -481,6 +529,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_empty_and() {
         // Empty AND should return universe
         let formula = Formula::And(vec![]);
@@ -493,6 +542,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_empty_or() {
         // Empty OR should return empty set
         let formula = Formula::Or(vec![]);
@@ -505,6 +555,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_proof_invariant() {
         // Test converting a full ProofInvariant
         let mut expr = AffineExpr::new();
@@ -527,6 +578,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     #[should_panic(expected = "Universal quantification not supported in PresburgerSet conversion")]
     fn test_forall_formula_panics() {
         let formula = Formula::Forall(
@@ -542,6 +594,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_formula_with_different_variable_order() {
         // Test that variable ordering in mapping matters
         let mut expr = AffineExpr::new();
@@ -566,6 +619,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_eliminate_forward() {
         // Test with simple formula x >= 5
         let mut expr = AffineExpr::new();
@@ -602,6 +656,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_eliminate_backward() {
         // Test with simple formula x >= 5
         let mut expr = AffineExpr::new();
@@ -730,6 +785,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_true_false_formulas() {
         // Test that true (empty AND) converts to universe
         let true_formula = Formula::And(vec![]);
@@ -756,6 +812,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_variable_mapping_permutation() {
         // Test that different mappings create different coordinate systems
         // Create constraint: x + 2y = 10
@@ -795,6 +852,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_mapping_with_extra_variables() {
         // Test what happens when mapping contains variables not in the formula
         let constraint = ProofConstraint::new(
@@ -823,6 +881,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_request_response_mapping_order() {
         // Test the specific bug case with request/response pairs
         // Create constraint: decr/0 + incr/1 = decr/1 + incr/0
@@ -873,6 +932,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "isl")]
     fn test_mapping_consistency_for_comparison() {
         // This test demonstrates why consistent variable ordering is crucial
         // when comparing or checking implications between proof invariants
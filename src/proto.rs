@@ -0,0 +1,234 @@
+//! Importer for RPC service definitions.
+//!
+//! Reads the `service` blocks of a protobuf (`.proto`) file and turns every `rpc`
+//! method into a request. Only the shared-state accesses of a handler matter for
+//! serializability, so handlers are written in the `.ser` language, in `// @ser`
+//! comments that keep the file a valid protobuf definition:
+//!
+//! ```text
+//! // @ser global STOCK := 2;
+//! service Orders {
+//!   // @ser
+//!   //   if (STOCK == 0) { 0 } else { yield; STOCK := STOCK - 1; 1 }
+//!   rpc Buy(BuyRequest) returns (BuyReply);
+//! }
+//! ```
+//!
+//! A `// @ser` block right before an `rpc` (without a blank line in between) is the
+//! body of its handler; the value of the body is the response. Blocks elsewhere in a
+//! service hold declarations (`global`, `bound`) for it, and blocks outside services
+//! hold `global` declarations shared by all services. Continuation lines of a block are the `//` comment lines that follow it.
+//! Methods without a handler are requests that respond 0 without touching any state.
+//!
+//! Requests are named `<service>.<method>`, as in `ser compose`.
+
+use crate::frontend::{FrontEnd, LoadedNS};
+use crate::parser::{ExprHc, Program, compose_programs, parse_program};
+use std::path::Path;
+
+/// Marker of annotation comments
+const MARKER: &str = "@ser";
+
+/// A service with its methods and their handlers in `.ser` syntax
+#[derive(Debug, Clone, PartialEq)]
+struct Service {
+    name: String,
+    declarations: Vec<String>,
+    /// (method, handler body)
+    methods: Vec<(String, Option<String>)>,
+}
+
+/// First identifier in `s`
+fn identifier(s: &str) -> Option<String> {
+    let name: String = s
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Collect the services and annotations of a `.proto` file. Returns the file-level
+/// declarations and the services.
+fn scan(source: &str) -> Result<(Vec<String>, Vec<Service>), String> {
+    let mut file_declarations = Vec::new();
+    let mut services: Vec<Service> = Vec::new();
+    // Annotation being collected, and whether it is still open for continuation lines
+    let mut pending: Option<String> = None;
+    let mut open = false;
+    let mut in_service = false;
+    let mut depth = 0i64;
+
+    for (number, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(comment) = trimmed.strip_prefix("//") {
+            let comment = comment.trim();
+            if let Some(annotation) = comment.strip_prefix(MARKER) {
+                if let Some(previous) = pending.take() {
+                    declare(previous, in_service, &mut services, &mut file_declarations);
+                }
+                pending = Some(annotation.trim().to_string());
+                open = true;
+            } else if open {
+                if let Some(text) = pending.as_mut() {
+                    text.push('\n');
+                    text.push_str(comment);
+                }
+            }
+            continue;
+        }
+        open = false;
+        if trimmed.is_empty() {
+            // Only an annotation right before an rpc is its handler
+            if let Some(previous) = pending.take() {
+                declare(previous, in_service, &mut services, &mut file_declarations);
+            }
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("service ") {
+            if in_service {
+                return Err(format!("Line {}: nested service", number + 1));
+            }
+            let name = identifier(rest)
+                .ok_or_else(|| format!("Line {}: expected a service name", number + 1))?;
+            if let Some(previous) = pending.take() {
+                declare(previous, false, &mut services, &mut file_declarations);
+            }
+            services.push(Service {
+                name,
+                declarations: Vec::new(),
+                methods: Vec::new(),
+            });
+            in_service = true;
+            depth = 0;
+        } else if let (true, Some(rest)) = (in_service, trimmed.strip_prefix("rpc ")) {
+            let name = identifier(rest)
+                .ok_or_else(|| format!("Line {}: expected a method name", number + 1))?;
+            let service = services.last_mut().expect("inside a service");
+            if service.methods.iter().any(|(m, _)| *m == name) {
+                return Err(format!(
+                    "Line {}: duplicate method {}.{}",
+                    number + 1,
+                    service.name,
+                    name
+                ));
+            }
+            service.methods.push((name, pending.take()));
+        } else if let Some(previous) = pending.take() {
+            declare(previous, in_service, &mut services, &mut file_declarations);
+        }
+
+        if in_service {
+            depth += trimmed.matches('{').count() as i64 - trimmed.matches('}').count() as i64;
+            if depth <= 0 && trimmed.contains('}') {
+                in_service = false;
+            }
+        }
+    }
+    if let Some(previous) = pending.take() {
+        declare(previous, in_service, &mut services, &mut file_declarations);
+    }
+    Ok((file_declarations, services))
+}
+
+/// File an annotation that is not a handler as a declaration
+fn declare(
+    annotation: String,
+    in_service: bool,
+    services: &mut [Service],
+    file_declarations: &mut Vec<String>,
+) {
+    match services.last_mut() {
+        Some(service) if in_service => service.declarations.push(annotation),
+        _ => file_declarations.push(annotation),
+    }
+}
+
+/// Compile the services of a `.proto` file to a `.ser` program
+pub fn compile(source: &str, table: &mut ExprHc) -> Result<Program, String> {
+    let (file_declarations, services) = scan(source)?;
+    if services.iter().all(|service| service.methods.is_empty()) {
+        return Err("No rpc methods found".to_string());
+    }
+
+    let mut parts = Vec::new();
+    for service in services.into_iter().filter(|s| !s.methods.is_empty()) {
+        let mut text = file_declarations.join("\n");
+        for declaration in &service.declarations {
+            text.push('\n');
+            text.push_str(declaration);
+        }
+        for (method, handler) in &service.methods {
+            text.push_str(&format!(
+                "\nrequest {} {{\n{}\n}}",
+                method,
+                handler.as_deref().unwrap_or("0")
+            ));
+        }
+        let program = parse_program(&text, table)
+            .map_err(|err| format!("In service {}: {}", service.name, err))?;
+        parts.push((service.name, program));
+    }
+    compose_programs(parts)
+}
+
+/// RPC services in `.proto` files with `// @ser` handler annotations
+pub struct ProtoFrontEnd;
+
+impl FrontEnd for ProtoFrontEnd {
+    fn name(&self) -> &'static str {
+        "RPC service"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["proto"]
+    }
+
+    fn load(&self, path: &Path) -> Result<LoadedNS, String> {
+        let content =
+            std::fs::read_to_string(path).map_err(|err| format!("Error reading file: {}", err))?;
+        let mut table = ExprHc::new();
+        let program = compile(&content, &mut table)?;
+        Ok(LoadedNS::Program(crate::expr_to_ns::program_to_ns(
+            &mut table, &program,
+        )))
+    }
+
+    fn load_program(&self, path: &Path, table: &mut ExprHc) -> Option<Result<Program, String>> {
+        Some(
+            std::fs::read_to_string(path)
+                .map_err(|err| format!("Error reading file: {}", err))
+                .and_then(|content| compile(&content, table)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_services() {
+        let source = std::fs::read_to_string("examples/proto/shop.proto").unwrap();
+        let mut table = ExprHc::new();
+        let program = compile(&source, &mut table).unwrap();
+        let names: Vec<&str> = program.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Orders.Buy", "Orders.Status", "Warehouse.Restock"]
+        );
+        assert_eq!(program.globals, vec![("STOCK".to_string(), 2)]);
+        assert_eq!(program.requests[1].body.to_string(), "0");
+        assert_eq!(program.bounds.len(), 1);
+        assert_eq!(program.bounds[0].request, "Warehouse.Restock");
+
+        assert!(compile("service Empty {}\n", &mut table).is_err());
+        let broken = "service S {\n  // @ser X :=\n  rpc M(A) returns (B);\n}\n";
+        assert!(
+            compile(broken, &mut table)
+                .unwrap_err()
+                .contains("In service S")
+        );
+    }
+}
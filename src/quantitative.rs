@@ -0,0 +1,189 @@
+//! Response counts of serial executions (`--quantitative`).
+//!
+//! The analysis already computes the completed multisets of serial executions as a
+//! semilinear set over `request/response` pairs. Some facts about the counts in these
+//! multisets follow from its linear sets alone:
+//!
+//! - The most times a pair completes in one serial execution: unbounded if a period of
+//!   some linear set contains it, and otherwise its largest count in a base.
+//! - Which pairs are mutually exclusive, i.e. never complete in the same serial
+//!   execution. A linear set has a vector with both pairs exactly if its base and
+//!   periods mention both (add each period that mentions one of them to the base), so
+//!   two pairs are exclusive if no linear set mentions both.
+//!
+//! The report also counts the distinct completed multisets of at most `B` requests for
+//! small `B`, with the Presburger set of the semilinear set.
+//!
+//! If the system is serializable, every execution completes the multiset of a serial
+//! one, so the facts hold for all executions.
+
+use crate::presburger::PresburgerSet;
+use crate::semilinear::{LinearSet, SemilinearSet};
+use colored::*;
+use serde::Serialize;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// File in the output directory with the report
+pub const QUANTITATIVE_FILE: &str = "quantitative.json";
+
+/// The largest number of requests the multisets are counted up to
+pub const COUNT_BOUND: u32 = 5;
+
+/// Show response counts in the report
+pub fn set_quantitative(enabled: bool) {
+    crate::options::update(|options| options.quantitative = enabled);
+}
+
+pub fn quantitative_enabled() -> bool {
+    crate::options::get(|options| options.quantitative)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuantitativeReport<K> {
+    /// The most times each pair completes in a serial execution, `None` if unbounded
+    pub max_counts: Vec<(K, Option<usize>)>,
+    /// Pairs that never complete in the same serial execution, each listed once
+    pub exclusive: Vec<(K, K)>,
+    /// The number of distinct completed multisets of at most `B` requests, for `B` up
+    /// to `COUNT_BOUND`
+    pub multisets_up_to: Vec<(u32, u64)>,
+}
+
+/// Whether `linear_set` mentions `key` in its base or a period
+fn mentions<K: Eq + Hash + Clone + Ord>(linear_set: &LinearSet<K>, key: &K) -> bool {
+    linear_set.base.get(key) > 0 || linear_set.periods.iter().any(|p| p.get(key) > 0)
+}
+
+/// The largest count of `key` in a vector of `set`, `None` if there is no largest
+pub fn max_count<K: Eq + Hash + Clone + Ord>(set: &SemilinearSet<K>, key: &K) -> Option<usize> {
+    let mut max = 0;
+    for linear_set in &set.components {
+        if linear_set.periods.iter().any(|p| p.get(key) > 0) {
+            return None;
+        }
+        max = max.max(linear_set.base.get(key));
+    }
+    Some(max)
+}
+
+/// Whether a vector of `set` counts both `a` and `b`
+pub fn can_co_occur<K: Eq + Hash + Clone + Ord>(set: &SemilinearSet<K>, a: &K, b: &K) -> bool {
+    set.components
+        .iter()
+        .any(|linear_set| mentions(linear_set, a) && mentions(linear_set, b))
+}
+
+/// The counts of all keys of `set`, the pairs of them that never occur together, and
+/// the number of small multisets in `set`
+pub fn analyze<K>(set: &SemilinearSet<K>) -> QuantitativeReport<K>
+where
+    K: Eq + Hash + Clone + Ord + Debug + Display,
+{
+    let mut keys = Vec::new();
+    set.for_each_key(|key| keys.push(key.clone()));
+    keys.sort();
+    keys.dedup();
+
+    let max_counts = keys
+        .iter()
+        .map(|key| (key.clone(), max_count(set, key)))
+        .collect();
+    let mut exclusive = Vec::new();
+    for (i, a) in keys.iter().enumerate() {
+        for b in &keys[i + 1..] {
+            if !can_co_occur(set, a, b) {
+                exclusive.push((a.clone(), b.clone()));
+            }
+        }
+    }
+    let presburger = PresburgerSet::from_semilinear_set(set);
+    let multisets_up_to = (1..=COUNT_BOUND)
+        .map(|bound| (bound, presburger.count_up_to(bound)))
+        .collect();
+    QuantitativeReport {
+        max_counts,
+        exclusive,
+        multisets_up_to,
+    }
+}
+
+/// Print the report as part of the analysis report
+pub fn print_report<K: Display>(report: &QuantitativeReport<K>) {
+    println!();
+    println!("Response counts per serial execution:");
+    if report.max_counts.is_empty() {
+        println!("  No request completes in a serial execution");
+        return;
+    }
+    for (key, max) in &report.max_counts {
+        match max {
+            Some(max) => println!("  {} at most {}", key, max),
+            None => println!("  {} {}", key, "unbounded".bright_black()),
+        }
+    }
+    if !report.exclusive.is_empty() {
+        println!("Mutually exclusive responses:");
+        for (a, b) in &report.exclusive {
+            println!("  {} {} {}", a, "✗".red(), b);
+        }
+    }
+    println!("Distinct completed multisets:");
+    for (bound, count) in &report.multisets_up_to {
+        println!("  at most {} requests: {}", bound, count);
+    }
+}
+
+/// Write the report to `quantitative.json` in `out_dir`, returning its path
+pub fn save_report<K: Serialize>(
+    out_dir: &str,
+    report: &QuantitativeReport<K>,
+) -> Result<String, String> {
+    let path = crate::utils::file::join(out_dir, QUANTITATIVE_FILE);
+    let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())?;
+    crate::utils::file::safe_write_file(&path, &json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kleene::Kleene;
+
+    #[test]
+    fn test_analyze() {
+        let atom = |name: &str| SemilinearSet::atom(name.to_string());
+        // Either a lock and at most two reads, or any number of writes
+        let set = atom("lock")
+            .times(atom("read").plus(SemilinearSet::one()))
+            .times(atom("read").plus(SemilinearSet::one()))
+            .plus(atom("write").star());
+
+        assert_eq!(max_count(&set, &"read".to_string()), Some(2));
+        assert_eq!(max_count(&set, &"write".to_string()), None);
+        assert_eq!(max_count(&set, &"other".to_string()), Some(0));
+        assert!(can_co_occur(&set, &"lock".to_string(), &"read".to_string()));
+
+        let report = analyze(&set);
+        assert_eq!(
+            report.max_counts,
+            vec![
+                ("lock".to_string(), Some(1)),
+                ("read".to_string(), Some(2)),
+                ("write".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            report.exclusive,
+            vec![
+                ("lock".to_string(), "write".to_string()),
+                ("read".to_string(), "write".to_string()),
+            ]
+        );
+        // {lock, lock read, lock read read} and one for each number of writes
+        assert_eq!(
+            report.multisets_up_to,
+            vec![(1, 3), (2, 5), (3, 7), (4, 8), (5, 9)]
+        );
+    }
+}
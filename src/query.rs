@@ -0,0 +1,474 @@
+//! `ser query <out dir>`: interactive queries over the completed multisets of an
+//! earlier analysis.
+//!
+//! The analysis saves the serialized automaton as a semilinear set over
+//! `request/response` counters. Serial executions are executions, so the set is
+//! contained in the reachable completed multisets; for a serializable system the two
+//! coincide. A query is a conjunction of linear constraints over the counters, and the
+//! REPL reports whether some completed multiset satisfies it. `count <B>` counts the
+//! distinct completed multisets of at most `B` requests, optionally only those that
+//! satisfy a query.
+
+use crate::constraints::{Constraint, ConstraintType, QuantifiedSet, Variable};
+use crate::deterministic_map::HashMap;
+use crate::ns_decision::NSDecision;
+use crate::presburger::PresburgerSet;
+use crate::semilinear::{LinearSet, LinearSetTerms, SemilinearSet};
+use colored::*;
+use std::io::{BufRead, Write};
+
+/// File in the output directory that holds the saved semilinear set
+pub const SEMILINEAR_FILE: &str = "completed_multisets.json";
+
+/// Save the serialized automaton semilinear set (over `request/response` counters)
+pub fn save_semilinear(out_dir: &str, set: &SemilinearSet<String>) -> Result<String, String> {
+    let path = crate::utils::file::join(out_dir, SEMILINEAR_FILE);
+    let json = serde_json::to_string_pretty(&set.decomposition()).map_err(|e| e.to_string())?;
+    crate::utils::file::safe_write_file(&path, &json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn load_semilinear(out_dir: &str) -> Result<SemilinearSet<String>, String> {
+    let path = crate::utils::file::join(out_dir, SEMILINEAR_FILE);
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Cannot read {} (run the analysis first): {}", path, e))?;
+    let saved: Vec<LinearSetTerms<String>> =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid {}: {}", path, e))?;
+    Ok(SemilinearSet {
+        components: saved.into_iter().map(LinearSet::from).collect(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Int(i32),
+    Name(String),
+    Plus,
+    Minus,
+    Star,
+    Op(&'static str),
+    And,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            ' ' | '\t' => i += 1,
+            '+' => {
+                tokens.push(Token::Plus);
+                i += 1;
+            }
+            '-' => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            '*' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::And);
+                i += 1;
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '<' | '>' | '=' => {
+                let op = match (c, next) {
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('=', Some('=')) => "=",
+                    ('<', _) => "<",
+                    ('>', _) => ">",
+                    _ => "=",
+                };
+                i += if next == Some('=') { 2 } else { 1 };
+                tokens.push(Token::Op(op));
+            }
+            _ if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let digits: String = chars[start..i].iter().collect();
+                let n = digits
+                    .parse()
+                    .map_err(|_| format!("Number too large: {}", digits))?;
+                tokens.push(Token::Int(n));
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | '/'))
+                {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                tokens.push(if name == "and" {
+                    Token::And
+                } else {
+                    Token::Name(name)
+                });
+            }
+            _ => return Err(format!("Unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse `lhs op rhs, ...` into constraints over the given counters. A bare request
+/// name stands for the sum of its `request/response` counters.
+fn parse_query(input: &str, counters: &[String]) -> Result<Vec<Constraint<String>>, String> {
+    let tokens = tokenize(input)?;
+    let mut pos = 0;
+    let mut constraints = Vec::new();
+    loop {
+        let (lhs, lhs_const) = parse_expr(&tokens, &mut pos, counters)?;
+        let op = match tokens.get(pos) {
+            Some(Token::Op(op)) => *op,
+            _ => return Err("Expected a comparison (<=, >=, =, <, >)".to_string()),
+        };
+        pos += 1;
+        let (rhs, rhs_const) = parse_expr(&tokens, &mut pos, counters)?;
+
+        // lhs - rhs op 0
+        let mut terms: HashMap<String, i32> = lhs;
+        for (name, coeff) in rhs {
+            *terms.entry(name).or_insert(0) -= coeff;
+        }
+        let mut linear: Vec<(i32, String)> = terms
+            .into_iter()
+            .filter(|(_, k)| *k != 0)
+            .map(|(v, k)| (k, v))
+            .collect();
+        linear.sort_by(|a, b| a.1.cmp(&b.1));
+        let constant = lhs_const - rhs_const;
+        let negate = |linear: &[(i32, String)]| -> Vec<(i32, String)> {
+            linear.iter().map(|(k, v)| (-k, v.clone())).collect()
+        };
+        match op {
+            ">=" => constraints.push(Constraint::new(
+                linear,
+                constant,
+                ConstraintType::NonNegative,
+            )),
+            ">" => constraints.push(Constraint::new(
+                linear,
+                constant - 1,
+                ConstraintType::NonNegative,
+            )),
+            "<=" => constraints.push(Constraint::new(
+                negate(&linear),
+                -constant,
+                ConstraintType::NonNegative,
+            )),
+            "<" => constraints.push(Constraint::new(
+                negate(&linear),
+                -constant - 1,
+                ConstraintType::NonNegative,
+            )),
+            _ => constraints.push(Constraint::new(
+                linear,
+                constant,
+                ConstraintType::EqualToZero,
+            )),
+        }
+
+        match tokens.get(pos) {
+            None => return Ok(constraints),
+            Some(Token::And) => pos += 1,
+            Some(t) => return Err(format!("Unexpected {:?} after constraint", t)),
+        }
+    }
+}
+
+/// Parse a linear expression into counter coefficients and a constant
+fn parse_expr(
+    tokens: &[Token],
+    pos: &mut usize,
+    counters: &[String],
+) -> Result<(HashMap<String, i32>, i32), String> {
+    let mut terms: HashMap<String, i32> = HashMap::default();
+    let mut constant = 0;
+    let mut sign = 1;
+    if tokens.get(*pos) == Some(&Token::Minus) {
+        sign = -1;
+        *pos += 1;
+    }
+    loop {
+        let coeff = match tokens.get(*pos) {
+            Some(Token::Int(n)) if tokens.get(*pos + 1) == Some(&Token::Star) => {
+                *pos += 2;
+                *n
+            }
+            Some(Token::Int(n)) => {
+                *pos += 1;
+                constant += sign * n;
+                0
+            }
+            _ => 1,
+        };
+        if coeff != 0 {
+            let name = match tokens.get(*pos) {
+                Some(Token::Name(name)) => name.clone(),
+                _ => return Err("Expected a counter or a number".to_string()),
+            };
+            *pos += 1;
+            let matching: Vec<&String> = if name.contains('/') {
+                counters.iter().filter(|c| **c == name).collect()
+            } else {
+                let prefix = format!("{}/", name);
+                counters.iter().filter(|c| c.starts_with(&prefix)).collect()
+            };
+            if matching.is_empty() {
+                return Err(format!(
+                    "Unknown counter '{}' (type 'counters' to list them)",
+                    name
+                ));
+            }
+            for counter in matching {
+                *terms.entry(counter.clone()).or_insert(0) += sign * coeff;
+            }
+        }
+        sign = match tokens.get(*pos) {
+            Some(Token::Plus) => 1,
+            Some(Token::Minus) => -1,
+            _ => return Ok((terms, constant)),
+        };
+        *pos += 1;
+    }
+}
+
+/// The multisets over `counters` that satisfy all constraints
+fn query_set(constraints: &[Constraint<String>], counters: &[String]) -> PresburgerSet<String> {
+    let query: Vec<Constraint<Variable<String>>> = constraints
+        .iter()
+        .map(|c| {
+            Constraint::new(
+                c.linear_combination()
+                    .iter()
+                    .map(|(k, v)| (*k, Variable::Var(v.clone())))
+                    .collect(),
+                c.constant_term(),
+                c.constraint_type(),
+            )
+        })
+        .collect();
+    PresburgerSet::from_quantified_sets(&[QuantifiedSet::new(query)], counters.to_vec())
+}
+
+/// Whether some multiset in `set` satisfies all constraints
+fn intersects(
+    set: &SemilinearSet<String>,
+    constraints: &[Constraint<String>],
+    counters: &[String],
+) -> bool {
+    !PresburgerSet::from_semilinear_set(set)
+        .intersection(&query_set(constraints, counters))
+        .is_empty()
+}
+
+/// Parse `count <B> [where <query>]` into the bound and the query constraints
+fn parse_count(input: &str, counters: &[String]) -> Result<(u32, Vec<Constraint<String>>), String> {
+    let (bound, query) = match input.split_once(" where ") {
+        Some((bound, query)) => (bound, Some(query)),
+        None => (input, None),
+    };
+    let bound = bound
+        .trim()
+        .parse()
+        .map_err(|_| format!("Expected a bound, e.g. 'count 5', not '{}'", bound.trim()))?;
+    let constraints = match query {
+        Some(query) => parse_query(query, counters)?,
+        None => Vec::new(),
+    };
+    Ok((bound, constraints))
+}
+
+/// The number of multisets in `set` of at most `bound` requests that satisfy all
+/// constraints
+fn count(
+    set: &SemilinearSet<String>,
+    bound: u32,
+    constraints: &[Constraint<String>],
+    counters: &[String],
+) -> u64 {
+    PresburgerSet::from_semilinear_set(set)
+        .intersection(&query_set(constraints, counters))
+        .count_up_to(bound)
+}
+
+fn print_help() {
+    println!("Enter constraints over request/response counters, e.g.");
+    println!("  {}", "transfer/ok >= 2, write = 0".cyan());
+    println!("  {}", "2*read - write > 1".cyan());
+    println!("A bare request name is the sum of its counters. Commands:");
+    println!("  {}   list the counters", "counters".green());
+    println!(
+        "  {}  count the multisets of at most B requests",
+        "count <B>".green()
+    );
+    println!(
+        "  {}  ... that satisfy the constraints",
+        "count <B> where <constraints>".green()
+    );
+    println!("  {}       show this help", "help".green());
+    println!("  {}       leave", "quit".green());
+}
+
+/// Entry point of the `query` subcommand
+pub fn run(args: &[String]) -> Result<(), String> {
+    let [out_dir] = args else {
+        return Err("Usage: ser query <output directory>".to_string());
+    };
+    let set = load_semilinear(out_dir)?;
+    let mut counters: Vec<String> = set
+        .components
+        .iter()
+        .flat_map(|c| {
+            c.base
+                .values
+                .keys()
+                .chain(c.periods.iter().flat_map(|p| p.values.keys()))
+        })
+        .cloned()
+        .collect();
+    counters.sort();
+    counters.dedup();
+
+    // With a proof of serializability the saved set is exactly the reachable one
+    let certificate = crate::ns_decision::certificate_path(out_dir);
+    let exact = matches!(
+        NSDecision::<String, String, String, String>::load_from_file(&certificate),
+        Ok(NSDecision::Serializable { .. })
+    );
+    if exact {
+        println!("{}", "System is serializable: answers are exact".green());
+    } else {
+        println!(
+            "{}",
+            "System not proven serializable: 'yes' answers are exact, 'no' only covers serial executions"
+                .yellow()
+        );
+    }
+    print_help();
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("{} ", "query>".bold());
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Ok(());
+        }
+        let line = line.trim();
+        match line {
+            "" => {}
+            "quit" | "exit" => return Ok(()),
+            "help" => print_help(),
+            "counters" => println!("{}", counters.join(" ")),
+            _ if line == "count" || line.starts_with("count ") => {
+                match parse_count(&line["count".len()..], &counters) {
+                    Ok((bound, constraints)) => {
+                        let n = count(&set, bound, &constraints, &counters);
+                        let kind = if exact {
+                            "reachable completed multisets"
+                        } else {
+                            "completed multisets of serial executions"
+                        };
+                        println!("{} {} of at most {} requests", n, kind, bound);
+                    }
+                    Err(err) => eprintln!("{}: {}", "Error".red().bold(), err),
+                }
+            }
+            _ => match parse_query(line, &counters) {
+                Ok(constraints) => {
+                    if intersects(&set, &constraints, &counters) {
+                        println!("{}", "yes: some completed multiset satisfies this".green());
+                    } else if exact {
+                        println!(
+                            "{}",
+                            "no: no reachable completed multiset satisfies this".red()
+                        );
+                    } else {
+                        println!("{}", "no serial execution satisfies this".red());
+                    }
+                }
+                Err(err) => eprintln!("{}: {}", "Error".red().bold(), err),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kleene::Kleene;
+
+    fn counters() -> Vec<String> {
+        ["read/1", "write/ok", "write/err"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let constraints = parse_query("write >= 2, read/1 = 0", &counters()).unwrap();
+        assert_eq!(constraints.len(), 2);
+        // write/err + write/ok - 2 >= 0
+        assert_eq!(
+            constraints[0].linear_combination(),
+            &[(1, "write/err".to_string()), (1, "write/ok".to_string())]
+        );
+        assert_eq!(constraints[0].constant_term(), -2);
+        assert_eq!(
+            constraints[1].constraint_type(),
+            ConstraintType::EqualToZero
+        );
+
+        // 2*read/1 < write/ok + 1  becomes  -2*read/1 + write/ok >= 0
+        let strict = parse_query("2*read/1 < write/ok + 1", &counters()).unwrap();
+        assert_eq!(
+            strict[0].linear_combination(),
+            &[(-2, "read/1".to_string()), (1, "write/ok".to_string())]
+        );
+        assert_eq!(strict[0].constant_term(), 0);
+
+        assert!(parse_query("delete >= 1", &counters()).is_err());
+        assert!(parse_query("write", &counters()).is_err());
+        assert!(parse_query("write >= 1 read", &counters()).is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        let atom = |name: &str| SemilinearSet::atom(name.to_string());
+        // Any number of reads, then a write that succeeds or fails
+        let set = atom("read/1")
+            .star()
+            .times(atom("write/ok").plus(atom("write/err")));
+        let counters = counters();
+
+        let (bound, constraints) = parse_count(" 3", &counters).unwrap();
+        assert_eq!((bound, constraints.len()), (3, 0));
+        assert_eq!(count(&set, bound, &constraints, &counters), 6);
+
+        let (bound, constraints) = parse_count(" 3 where write/ok = 1", &counters).unwrap();
+        assert_eq!(count(&set, bound, &constraints, &counters), 3);
+
+        assert!(parse_count("", &counters).is_err());
+        assert!(parse_count(" many", &counters).is_err());
+        assert!(parse_count(" 3 where delete >= 1", &counters).is_err());
+    }
+}
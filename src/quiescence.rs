@@ -0,0 +1,368 @@
+//! Quiescence: from every reachable state, the requests in flight can all complete
+//! (`--check quiescence`).
+//!
+//! A request is *solo-completable* at local state `l` and global state `g` if it can
+//! reach a local state with a response by its own steps from `(l, g)`. If every pair
+//! of a local and a global state of the system is solo-completable, the system is
+//! quiescent: from any state, the requests in flight can complete one after the
+//! other, whatever global state the earlier ones leave behind.
+//!
+//! Otherwise the explicit search looks for a reachable state, with at most
+//! `MAX_IN_FLIGHT` requests in flight, from which no sequence of steps and completions
+//! (without new requests) completes all of them. Such a state is a genuine
+//! counterexample, reported with the trace that reaches it. If the search finds none
+//! within its bounds, the result is inconclusive and lists the pairs that are not
+//! solo-completable.
+
+use crate::deterministic_map::{HashMap, HashSet};
+use crate::ns::NS;
+use crate::ns_decision::{NSStep, NSTrace};
+use colored::*;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// Most requests in flight in the states of the explicit search
+pub const MAX_IN_FLIGHT: usize = 3;
+
+/// Most states the explicit search visits
+pub const MAX_EXPLORED_STATES: usize = 10_000;
+
+/// Check quiescence in the report (`--check quiescence`)
+pub fn set_check_quiescence(enabled: bool) {
+    crate::options::update(|options| options.check_quiescence = enabled);
+}
+
+pub fn check_quiescence_enabled() -> bool {
+    crate::options::get(|options| options.check_quiescence)
+}
+
+/// A state of the explicit search: the global state and the requests in flight,
+/// sorted so that equal multisets are equal vectors
+type State<G, L, Req> = (G, Vec<(Req, L)>);
+
+/// A step and the state it leads to, or in the search, the state it was taken from
+type Step<G, L, Req, Resp> = (NSStep<G, L, Req, Resp>, State<G, L, Req>);
+
+#[derive(Debug, Clone)]
+pub enum Quiescence<G, L, Req, Resp> {
+    /// Every request can complete on its own from every local and global state
+    Holds,
+    /// The requests in flight at the end of `trace` can never all complete
+    Violated {
+        trace: NSTrace<G, L, Req, Resp>,
+        global: G,
+        in_flight: Vec<(Req, L)>,
+    },
+    /// The explicit search found no counterexample within its bounds; `stuck` are the
+    /// (local, global) pairs that are not solo-completable
+    Unknown { stuck: Vec<(L, G)> },
+}
+
+/// Check whether `ns` is quiescent, see the module documentation
+pub fn check<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>) -> Quiescence<G, L, Req, Resp>
+where
+    G: Clone + Eq + Hash + Ord + Display,
+    L: Clone + Eq + Hash + Ord + Display,
+    Req: Clone + Eq + Hash + Ord + Display,
+    Resp: Clone + Eq + Hash + Ord + Display,
+{
+    let completable = solo_completable(ns);
+    let mut stuck = Vec::new();
+    let mut locals: Vec<&L> = ns.get_local_states();
+    locals.sort();
+    let mut globals: Vec<&G> = ns.get_global_states();
+    globals.sort();
+    for &local in &locals {
+        for &global in &globals {
+            if !completable.contains(&(local.clone(), global.clone())) {
+                stuck.push((local.clone(), global.clone()));
+            }
+        }
+    }
+    if stuck.is_empty() {
+        return Quiescence::Holds;
+    }
+
+    match find_violation(ns) {
+        Some((trace, (global, in_flight))) => Quiescence::Violated {
+            trace,
+            global,
+            in_flight,
+        },
+        None => Quiescence::Unknown { stuck },
+    }
+}
+
+/// The solo-completable (local, global) pairs of `ns`, computed backwards from the
+/// local states with a response
+fn solo_completable<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>) -> HashSet<(L, G)>
+where
+    G: Clone + Eq + Hash + Display,
+    L: Clone + Eq + Hash + Display,
+    Req: Clone + Eq + Hash + Display,
+    Resp: Clone + Eq + Hash + Display,
+{
+    let responding: HashSet<&L> = ns.responses.iter().map(|(local, _)| local).collect();
+    let mut completable: HashSet<(L, G)> = HashSet::default();
+    for &local in &ns.get_local_states() {
+        if responding.contains(local) {
+            for &global in &ns.get_global_states() {
+                completable.insert((local.clone(), global.clone()));
+            }
+        }
+    }
+    loop {
+        let mut changed = false;
+        for (from_local, from_global, to_local, to_global) in &ns.transitions {
+            let from = (from_local.clone(), from_global.clone());
+            if !completable.contains(&from)
+                && completable.contains(&(to_local.clone(), to_global.clone()))
+            {
+                completable.insert(from);
+                changed = true;
+            }
+        }
+        if !changed {
+            return completable;
+        }
+    }
+}
+
+/// The states reachable in one step from `state`: internal steps and completions, and
+/// new requests if `arrivals` is set
+fn successors<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    (global, in_flight): &State<G, L, Req>,
+    arrivals: bool,
+) -> Vec<Step<G, L, Req, Resp>>
+where
+    G: Clone + Eq + Hash + Ord + Display,
+    L: Clone + Eq + Hash + Ord + Display,
+    Req: Clone + Eq + Hash + Ord + Display,
+    Resp: Clone + Eq + Hash + Ord + Display,
+{
+    let mut next = Vec::new();
+    let replace = |i: usize, with: Option<(Req, L)>| {
+        let mut requests = in_flight.clone();
+        requests.remove(i);
+        requests.extend(with);
+        requests.sort();
+        requests
+    };
+    for (i, (request, local)) in in_flight.iter().enumerate() {
+        // Instances at the same local state have the same successors
+        if i > 0 && in_flight[i - 1] == (request.clone(), local.clone()) {
+            continue;
+        }
+        for (from_local, from_global, to_local, to_global) in &ns.transitions {
+            if from_local == local && from_global == global {
+                let step = NSStep::InternalStep {
+                    request: request.clone(),
+                    from_local: from_local.clone(),
+                    from_global: from_global.clone(),
+                    to_local: to_local.clone(),
+                    to_global: to_global.clone(),
+                };
+                let requests = replace(i, Some((request.clone(), to_local.clone())));
+                next.push((step, (to_global.clone(), requests)));
+            }
+        }
+        for (final_local, response) in &ns.responses {
+            if final_local == local {
+                let step = NSStep::RequestComplete {
+                    request: request.clone(),
+                    final_local: final_local.clone(),
+                    response: response.clone(),
+                };
+                next.push((step, (global.clone(), replace(i, None))));
+            }
+        }
+    }
+    if arrivals && in_flight.len() < MAX_IN_FLIGHT {
+        for (request, initial_local) in &ns.requests {
+            let step = NSStep::RequestStart {
+                request: request.clone(),
+                initial_local: initial_local.clone(),
+            };
+            let mut requests = in_flight.clone();
+            requests.push((request.clone(), initial_local.clone()));
+            requests.sort();
+            next.push((step, (global.clone(), requests)));
+        }
+    }
+    next
+}
+
+/// Whether the requests in flight at `state` can all complete without new requests.
+/// Without arrivals the number of requests never grows, so the search is finite.
+fn can_quiesce<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>, state: &State<G, L, Req>) -> bool
+where
+    G: Clone + Eq + Hash + Ord + Display,
+    L: Clone + Eq + Hash + Ord + Display,
+    Req: Clone + Eq + Hash + Ord + Display,
+    Resp: Clone + Eq + Hash + Ord + Display,
+{
+    let mut seen: HashSet<State<G, L, Req>> = HashSet::default();
+    let mut todo = vec![state.clone()];
+    seen.insert(state.clone());
+    while let Some(state) = todo.pop() {
+        if state.1.is_empty() {
+            return true;
+        }
+        for (_, next) in successors(ns, &state, false) {
+            if seen.insert(next.clone()) {
+                todo.push(next);
+            }
+        }
+    }
+    false
+}
+
+/// Breadth-first search for a reachable state that cannot quiesce
+fn find_violation<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+) -> Option<(NSTrace<G, L, Req, Resp>, State<G, L, Req>)>
+where
+    G: Clone + Eq + Hash + Ord + Display,
+    L: Clone + Eq + Hash + Ord + Display,
+    Req: Clone + Eq + Hash + Ord + Display,
+    Resp: Clone + Eq + Hash + Ord + Display,
+{
+    let mut initial_requests = ns.initial_requests.clone();
+    initial_requests.sort();
+    let initial: State<G, L, Req> = (ns.initial_global.clone(), initial_requests);
+
+    // The step that first reached each state, and the state it was taken from
+    let mut parent: HashMap<State<G, L, Req>, Option<Step<G, L, Req, Resp>>> = HashMap::default();
+    parent.insert(initial.clone(), None);
+    let mut queue = VecDeque::from([initial]);
+    while let Some(state) = queue.pop_front() {
+        if !can_quiesce(ns, &state) {
+            let mut steps = Vec::new();
+            let mut at = &state;
+            while let Some(Some((step, previous))) = parent.get(at) {
+                steps.push(step.clone());
+                at = previous;
+            }
+            steps.reverse();
+            return Some((NSTrace { steps }, state));
+        }
+        for (step, next) in successors(ns, &state, true) {
+            if parent.len() >= MAX_EXPLORED_STATES {
+                return None;
+            }
+            if !parent.contains_key(&next) {
+                parent.insert(next.clone(), Some((step, state.clone())));
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Print the result of the check as part of the report
+pub fn print_report<G, L, Req, Resp>(result: &Quiescence<G, L, Req, Resp>)
+where
+    G: Display,
+    L: Display,
+    Req: Display,
+    Resp: Display,
+{
+    println!();
+    println!("Quiescence:");
+    match result {
+        Quiescence::Holds => {
+            println!(
+                "  {} From every reachable state, all requests in flight can complete",
+                "✓".green()
+            );
+        }
+        Quiescence::Violated {
+            trace,
+            global,
+            in_flight,
+        } => {
+            let requests: Vec<String> = in_flight
+                .iter()
+                .map(|(request, local)| format!("{} at {}", request, local))
+                .collect();
+            println!(
+                "  {} The requests in flight can never all complete from this reachable state:",
+                "✗".red()
+            );
+            println!("    global: {}", global);
+            println!("    in flight: {}", requests.join(", "));
+            println!("  Reached by:");
+            for step in &trace.steps {
+                match step {
+                    NSStep::RequestStart {
+                        request,
+                        initial_local,
+                    } => println!("    start {} at {}", request, initial_local),
+                    NSStep::InternalStep {
+                        request,
+                        from_local,
+                        from_global,
+                        to_local,
+                        to_global,
+                    } => println!(
+                        "    {}: ({}, {}) → ({}, {})",
+                        request, from_local, from_global, to_local, to_global
+                    ),
+                    NSStep::RequestComplete {
+                        request, response, ..
+                    } => println!("    {} responds {}", request, response),
+                }
+            }
+        }
+        Quiescence::Unknown { stuck } => {
+            println!(
+                "  {} No counterexample with at most {} requests in flight, but requests cannot \
+                 complete on their own from these (local, global) states:",
+                "?".yellow(),
+                MAX_IN_FLIGHT
+            );
+            for (local, global) in stuck {
+                println!("    ({}, {})", local, global);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiescence() {
+        let s = |x: &str| x.to_string();
+        // `wait` spins until the global state is 1, which only `set` makes it
+        let mut ns = NS::<String, String, String, String>::new(s("0"));
+        ns.add_request(s("wait"), s("w"));
+        ns.add_transition(s("w"), s("1"), s("w_done"), s("1"));
+        ns.add_response(s("w_done"), s("ok"));
+        ns.add_request(s("set"), s("s"));
+        ns.add_transition(s("s"), s("0"), s("s_done"), s("1"));
+        ns.add_transition(s("s"), s("1"), s("s_done"), s("1"));
+        ns.add_response(s("s_done"), s("ok"));
+
+        // A `wait` in flight can complete if a `set` comes along, but not on its own
+        match check(&ns) {
+            Quiescence::Violated {
+                trace,
+                global,
+                in_flight,
+            } => {
+                assert_eq!(global, "0");
+                assert_eq!(in_flight, vec![(s("wait"), s("w"))]);
+                assert_eq!(trace.steps.len(), 1);
+            }
+            other => panic!("Expected a violation, got {:?}", other),
+        }
+
+        // Once `wait` also completes at 0, every request completes on its own
+        ns.add_transition(s("w"), s("0"), s("w_done"), s("0"));
+        assert!(matches!(check(&ns), Quiescence::Holds));
+    }
+}
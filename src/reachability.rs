@@ -1,21 +1,30 @@
+use crate::constraints::{Constraint, QuantifiedSet};
 use crate::debug_report::DebugLogger;
 use crate::deterministic_map::HashSet;
+#[cfg(feature = "isl")]
 use crate::kleene::Kleene;
 use crate::petri::*;
+use crate::proof_parser::ProofInvariant;
+#[cfg(feature = "isl")]
 use crate::semilinear::*;
+#[cfg(feature = "isl")]
 use crate::spresburger::SPresburgerSet;
+use crate::stats::{BudgetAttempt, TimeoutBudgetStats};
 use colored::Colorize;
-use either::{Either, Left, Right};
+use either::Either;
+#[cfg(feature = "isl")]
+use either::{Left, Right};
+use std::collections::VecDeque;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 /// Global debug logger for reachability analysis
 static DEBUG_LOGGER: Mutex<Option<DebugLogger>> = Mutex::new(None);
 
-pub static BIDIRECTIONAL_PRUNING_ENABLED: AtomicBool = AtomicBool::new(true);
-
 /// Initialize the global debug logger
 pub fn init_debug_logger(program_name: String, program_content: String) {
     let logger = DebugLogger::new(program_name, program_content);
@@ -36,12 +45,304 @@ pub fn get_debug_logger() -> DebugLogger {
 
 /// Set the optimize flag (called from `main.rs`)
 pub fn set_optimize_flag(enabled: bool) {
-    BIDIRECTIONAL_PRUNING_ENABLED.store(enabled, Ordering::SeqCst);
+    crate::options::update(|options| options.bidirectional_pruning = enabled);
 }
 
 /// Helper to check whether optimization should run
 pub fn optimize_enabled() -> bool {
-    BIDIRECTIONAL_PRUNING_ENABLED.load(Ordering::SeqCst)
+    crate::options::get(|options| options.bidirectional_pruning)
+}
+
+/// Set the dry-run flag (called from `main.rs`)
+pub fn set_dry_run(enabled: bool) {
+    crate::options::update(|options| options.dry_run = enabled);
+}
+
+/// Helper to check whether this is a dry run
+pub fn dry_run_enabled() -> bool {
+    crate::options::get(|options| options.dry_run)
+}
+
+/// Set the resume flag (called from `main.rs`)
+pub fn set_resume(enabled: bool) {
+    crate::options::update(|options| options.resume = enabled);
+}
+
+/// Helper to check whether journaled disjunct results should be reused
+pub fn resume_enabled() -> bool {
+    crate::options::get(|options| options.resume)
+}
+
+/// File in the output directory where decided disjuncts are recorded
+const JOURNAL_FILE: &str = "disjunct_journal.jsonl";
+
+/// A disjunct that was proven unreachable, together with its proof
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(bound(serialize = "P: serde::Serialize"))]
+#[serde(bound(deserialize = "P: serde::Deserialize<'de>"))]
+pub struct JournalEntry<P: Eq + Hash> {
+    pub disjunct: usize,
+    /// Fingerprint of the Petri net and the disjunct's constraints (see
+    /// `disjunct_fingerprint`), so a changed input is never resumed
+    pub fingerprint: u64,
+    pub proof: Option<ProofInvariant<P>>,
+}
+
+/// On-disk journal of decided disjuncts (one JSON entry per line).
+///
+/// Only unreachable disjuncts are journaled: a reachable disjunct ends the analysis,
+/// and timeouts or inconclusive results should be retried when resuming.
+pub struct DisjunctJournal<P: Eq + Hash> {
+    path: PathBuf,
+    entries: Vec<JournalEntry<P>>,
+}
+
+impl<P> DisjunctJournal<P>
+where
+    P: Eq + Hash + Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Open the journal in `out_dir`. When resuming, previously journaled entries are
+    /// loaded (malformed lines, e.g. from a killed run, are skipped); otherwise any
+    /// old journal is discarded.
+    pub fn open(out_dir: &str) -> Self {
+        let path = Path::new(out_dir).join(JOURNAL_FILE);
+        let mut entries = Vec::new();
+        if resume_enabled() {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                entries = content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect();
+            }
+        } else {
+            let _ = std::fs::remove_file(&path);
+        }
+        DisjunctJournal { path, entries }
+    }
+
+    /// Look up the proof of a previously decided disjunct
+    pub fn lookup(&self, disjunct: usize, fingerprint: u64) -> Option<Option<ProofInvariant<P>>> {
+        self.entries
+            .iter()
+            .find(|e| e.disjunct == disjunct && e.fingerprint == fingerprint)
+            .map(|e| e.proof.clone())
+    }
+
+    /// Append an unreachable disjunct and its proof to the journal
+    pub fn record(&mut self, disjunct: usize, fingerprint: u64, proof: Option<ProofInvariant<P>>) {
+        let entry = JournalEntry {
+            disjunct,
+            fingerprint,
+            proof,
+        };
+        match serde_json::to_string(&entry) {
+            Ok(line) => {
+                let written = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .and_then(|mut f| writeln!(f, "{}", line));
+                if let Err(e) = written {
+                    eprintln!("Warning: Failed to write disjunct journal: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Warning: Failed to serialize journal entry: {}", e),
+        }
+        self.entries.push(entry);
+    }
+}
+
+/// Fingerprint of a Petri net, computed from its initial marking and transitions.
+/// Like `disjunct_fingerprint` it uses FNV-1a, so journals of earlier runs (and
+/// earlier builds) can be compared against it.
+pub fn net_fingerprint<P>(petri: &Petri<P>) -> u64
+where
+    P: Clone + Eq + Hash + Display,
+{
+    let places = |places: &[P]| {
+        places
+            .iter()
+            .map(|place| place.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut text = format!("[{}]\n", places(&petri.get_initial_marking()));
+    for (input, output) in petri.get_transitions() {
+        text.push_str(&format!("[{}] -> [{}]\n", places(&input), places(&output)));
+    }
+    crate::naming::fnv1a(&text)
+}
+
+/// Fingerprint of a disjunct of a query on the net with fingerprint `net`, computed
+/// from the textual representation of the disjunct. A proof of the disjunct only
+/// carries over to a run with the same net, so the net is part of the fingerprint.
+pub fn disjunct_fingerprint(net: u64, disjunct: &impl Display) -> u64 {
+    crate::naming::fnv1a(&format!("{:016x}\n{}", net, disjunct))
+}
+
+/// Strategy for the order in which complement disjuncts are queried.
+///
+/// Any disjunct being reachable yields a counterexample, so trying likely-reachable
+/// (weakly constrained) disjuncts first shortens the time to a counterexample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisjunctOrder {
+    /// The order produced by the conversion to disjunctive normal form
+    AsGiven,
+    /// Disjuncts with the fewest constraints first
+    FewestConstraints,
+    /// Disjuncts that force the fewest places to be nonzero first
+    FewestNonzeroPlaces,
+}
+
+impl DisjunctOrder {
+    /// Parse a strategy name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "as-given" => Some(DisjunctOrder::AsGiven),
+            "fewest-constraints" => Some(DisjunctOrder::FewestConstraints),
+            "fewest-nonzero" => Some(DisjunctOrder::FewestNonzeroPlaces),
+            _ => None,
+        }
+    }
+
+    /// Difficulty score of a disjunct; lower scores are queried first
+    pub fn score<T: Clone + Eq + Hash>(&self, disjunct: &QuantifiedSet<T>) -> usize {
+        match self {
+            DisjunctOrder::AsGiven => 0,
+            DisjunctOrder::FewestConstraints => disjunct.constraints().len(),
+            DisjunctOrder::FewestNonzeroPlaces => {
+                Constraint::extract_nonzero_variables(disjunct.constraints()).len()
+            }
+        }
+    }
+}
+
+/// Set the disjunct ordering strategy (called from `main.rs`)
+pub fn set_disjunct_order(order: DisjunctOrder) {
+    crate::options::update(|options| options.disjunct_order = order);
+}
+
+/// Get the current disjunct ordering strategy
+pub fn get_disjunct_order() -> DisjunctOrder {
+    crate::options::get(|options| options.disjunct_order)
+}
+
+/// Indices of `disjuncts` in the order they should be queried, according to the
+/// current strategy. Ties keep their original relative order.
+pub fn order_disjuncts<T: Clone + Eq + Hash>(disjuncts: &[QuantifiedSet<T>]) -> Vec<usize> {
+    let order = get_disjunct_order();
+    let mut indices: Vec<usize> = (0..disjuncts.len()).collect();
+    indices.sort_by_key(|&i| order.score(&disjuncts[i]));
+    indices
+}
+
+/// Set the global time budget of `--auto-timeout`, in seconds (called from `main.rs`)
+pub fn set_auto_timeout(budget_seconds: Option<u64>) {
+    crate::options::update(|options| options.auto_timeout = budget_seconds);
+}
+
+/// Get the global time budget of `--auto-timeout`, if any
+pub fn auto_timeout() -> Option<u64> {
+    crate::options::get(|options| options.auto_timeout)
+}
+
+/// Share of the global budget that the first round of queries may use together
+const FIRST_ROUND_BUDGET_SHARE: u64 = 4;
+
+/// Chooses which disjunct to query next, and with which SMPT timeout.
+///
+/// Without a budget every disjunct is queried once, in order, with the configured
+/// timeout. With a budget (`--auto-timeout`) every disjunct is first queried with a
+/// small timeout, so the easy disjuncts are decided (and a reachable one is found)
+/// before time goes to the hard ones. A disjunct that stays undecided is queued again
+/// with twice the timeout, behind the other undecided disjuncts, until the budget is
+/// spent.
+pub struct TimeoutScheduler {
+    budget_seconds: Option<u64>,
+    /// Undecided disjuncts with the timeout of their next query
+    queue: VecDeque<(usize, Option<u64>)>,
+    attempts: Vec<BudgetAttempt>,
+    spent: Duration,
+}
+
+impl TimeoutScheduler {
+    /// Schedule the disjuncts in `order`, within `budget_seconds` if given
+    pub fn new(order: Vec<usize>, budget_seconds: Option<u64>) -> Self {
+        let initial = budget_seconds
+            .map(|budget| (budget / (FIRST_ROUND_BUDGET_SHARE * order.len().max(1) as u64)).max(1));
+        TimeoutScheduler {
+            budget_seconds,
+            queue: order.into_iter().map(|i| (i, initial)).collect(),
+            attempts: Vec::new(),
+            spent: Duration::ZERO,
+        }
+    }
+
+    /// The next disjunct to query with its timeout (`None` for the configured one), or
+    /// `None` once every disjunct is decided or the budget is spent
+    pub fn next_query(&mut self) -> Option<(usize, Option<u64>)> {
+        let remaining = self.remaining_seconds();
+        if remaining == Some(0) {
+            return None;
+        }
+        let (disjunct, timeout) = self.queue.pop_front()?;
+        Some((disjunct, timeout.zip(remaining).map(|(t, r)| t.min(r))))
+    }
+
+    /// Record the query of `disjunct`. Returns whether it was queued again, which only
+    /// happens to undecided disjuncts under a budget.
+    pub fn finish(
+        &mut self,
+        disjunct: usize,
+        timeout: Option<u64>,
+        spent: Duration,
+        decided: bool,
+    ) -> bool {
+        let Some(timeout) = timeout else {
+            return false;
+        };
+        self.spent += spent;
+        self.attempts.push(BudgetAttempt {
+            disjunct,
+            timeout_seconds: timeout,
+            spent_ms: spent.as_millis() as u64,
+            decided,
+        });
+        if !decided {
+            self.queue.push_back((disjunct, Some(timeout * 2)));
+        }
+        !decided
+    }
+
+    /// Seconds left of the budget, `None` without a budget
+    pub fn remaining_seconds(&self) -> Option<u64> {
+        self.budget_seconds
+            .map(|budget| budget.saturating_sub(self.spent.as_secs()))
+    }
+
+    /// Number of disjuncts still waiting for a query
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Disjuncts that were queued again but not decided, in index order
+    pub fn undecided(&self) -> Vec<usize> {
+        let mut undecided: Vec<usize> = self.queue.iter().map(|&(i, _)| i).collect();
+        undecided.sort();
+        undecided
+    }
+
+    /// Report how the budget was spent in the stats (nothing without a budget)
+    pub fn record_stats(&self) {
+        if let Some(budget_seconds) = self.budget_seconds {
+            crate::stats::record_timeout_budget(TimeoutBudgetStats {
+                budget_seconds,
+                spent_ms: self.spent.as_millis() as u64,
+                attempts: self.attempts.clone(),
+                undecided: self.undecided(),
+            });
+        }
+    }
 }
 
 /// Execute a closure with the debug logger
@@ -54,6 +355,7 @@ where
 }
 
 /// Alias for the new implementation - uses the new SPresburgerSet-based architecture
+#[cfg(feature = "isl")]
 pub fn is_petri_reachability_set_subset_of_semilinear<P, Q>(
     petri: Petri<Either<P, Q>>,
     places_that_must_be_zero: &[P],
@@ -82,6 +384,7 @@ where
 /// GOAL: Check if Reachable(petri) ⊆ semilinear when places_that_must_be_zero = 0
 /// APPROACH: Check if ¬semilinear ∩ {places_that_must_be_zero = 0} is reachable
 ///          If this intersection is reachable, then the subset property is violated
+#[cfg(feature = "isl")]
 #[must_use]
 pub fn is_petri_reachability_set_subset_of_semilinear_new<P, Q>(
     petri: Petri<Either<P, Q>>,
@@ -195,6 +498,7 @@ where
 /// APPROACH: Convert SPresburgerSet to disjunctive normal form and check each disjunct.
 /// A SPresburgerSet represents a union of constraint sets (disjuncts).
 /// The Petri net can reach the SPresburgerSet if it can reach ANY of the disjuncts.
+#[cfg(feature = "isl")]
 pub fn can_reach_presburger<P>(
     petri: Petri<P>,
     mut presburger: SPresburgerSet<P>,
@@ -298,7 +602,7 @@ where
 /// `true` if the Petri net can reach a state satisfying the constraints
 pub fn can_reach_quantified_set<P>(
     petri: Petri<P>,
-    quantified_set: super::presburger::QuantifiedSet<P>,
+    quantified_set: crate::constraints::QuantifiedSet<P>,
     out_dir: &str,
     disjunct_id: usize,
 ) -> bool
@@ -371,7 +675,7 @@ where
 /// Panics if SMPT verification fails, as we cannot safely assume serializability
 pub fn can_reach_constraint_set_with_debug<P>(
     mut petri: Petri<P>,
-    constraints: Vec<super::presburger::Constraint<P>>,
+    constraints: Vec<crate::constraints::Constraint<P>>,
     out_dir: &str,
     disjunct_id: usize,
 ) -> bool
@@ -395,7 +699,7 @@ where
         // of the zero variables)
 
         // Extract zero variables from constraints
-        let zero_variables = super::presburger::Constraint::extract_zero_variables(&constraints);
+        let zero_variables = crate::constraints::Constraint::extract_zero_variables(&constraints);
         let zero_variables_set: HashSet<P> = zero_variables.into_iter().collect();
 
         debug_logger.step(
@@ -494,8 +798,13 @@ where
             &petri,
         );
 
-        let result =
-            crate::smpt::can_reach_constraint_set(petri, constraints, out_dir, disjunct_id);
+        let result = crate::smpt::can_reach_constraint_set(
+            petri,
+            constraints,
+            out_dir,
+            disjunct_id,
+            &crate::smpt::CancellationToken::new(),
+        );
         match result.outcome {
             crate::smpt::SmptVerificationOutcome::Reachable { .. } => true, // Reachable means not serializable
             crate::smpt::SmptVerificationOutcome::Unreachable { .. } => false, // Unreachable means serializable
@@ -518,7 +827,117 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::presburger::{Constraint, ConstraintType};
+    use crate::constraints::{ConstraintType, Variable};
+
+    #[test]
+    fn test_disjunct_order_scores() {
+        let x = || Variable::Var("x");
+        let y = || Variable::Var("y");
+        // x >= 1, y >= 1
+        let strong = QuantifiedSet::new(vec![
+            Constraint::new(vec![(1, x())], -1, ConstraintType::NonNegative),
+            Constraint::new(vec![(1, y())], -1, ConstraintType::NonNegative),
+        ]);
+        // x >= 1
+        let weak = QuantifiedSet::new(vec![Constraint::new(
+            vec![(1, x())],
+            -1,
+            ConstraintType::NonNegative,
+        )]);
+        // x = 0, y = 0, x + y >= 0
+        let zeros = QuantifiedSet::new(vec![
+            Constraint::new(vec![(1, x())], 0, ConstraintType::EqualToZero),
+            Constraint::new(vec![(1, y())], 0, ConstraintType::EqualToZero),
+            Constraint::new(vec![(1, x()), (1, y())], 0, ConstraintType::NonNegative),
+        ]);
+
+        assert_eq!(DisjunctOrder::AsGiven.score(&strong), 0);
+        assert_eq!(DisjunctOrder::FewestConstraints.score(&strong), 2);
+        assert_eq!(DisjunctOrder::FewestConstraints.score(&weak), 1);
+        assert_eq!(DisjunctOrder::FewestNonzeroPlaces.score(&strong), 2);
+        assert_eq!(DisjunctOrder::FewestNonzeroPlaces.score(&weak), 1);
+        assert_eq!(DisjunctOrder::FewestNonzeroPlaces.score(&zeros), 0);
+        assert_eq!(
+            DisjunctOrder::from_name("fewest-nonzero"),
+            Some(DisjunctOrder::FewestNonzeroPlaces)
+        );
+        assert_eq!(DisjunctOrder::from_name("random"), None);
+    }
+
+    #[test]
+    fn test_timeout_scheduler() {
+        let second = Duration::from_secs(1);
+
+        // Without a budget every disjunct is queried once with the configured timeout
+        let mut unbudgeted = TimeoutScheduler::new(vec![1, 0], None);
+        assert_eq!(unbudgeted.next_query(), Some((1, None)));
+        assert!(!unbudgeted.finish(1, None, second, false));
+        assert_eq!(unbudgeted.next_query(), Some((0, None)));
+        assert_eq!(unbudgeted.next_query(), None);
+
+        // 40s for 2 disjuncts: the first round gets 40 / (4 * 2) = 5s per disjunct
+        let mut scheduler = TimeoutScheduler::new(vec![1, 0], Some(40));
+        assert_eq!(scheduler.next_query(), Some((1, Some(5))));
+        assert!(scheduler.finish(1, Some(5), 5 * second, false));
+        assert_eq!(scheduler.next_query(), Some((0, Some(5))));
+        assert!(!scheduler.finish(0, Some(5), second, true));
+        // The undecided disjunct is retried with twice the timeout
+        assert_eq!(scheduler.next_query(), Some((1, Some(10))));
+        assert!(scheduler.finish(1, Some(10), 10 * second, false));
+        assert_eq!(scheduler.remaining_seconds(), Some(24));
+        // ... but never beyond the rest of the budget
+        assert_eq!(scheduler.next_query(), Some((1, Some(20))));
+        assert!(scheduler.finish(1, Some(20), 24 * second, false));
+        assert_eq!(scheduler.next_query(), None);
+        assert_eq!(scheduler.undecided(), vec![1]);
+        assert_eq!(scheduler.attempts.len(), 4);
+    }
+
+    #[test]
+    fn test_disjunct_journal_resume() {
+        use crate::proof_parser::Formula;
+
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().to_str().unwrap();
+        let mut petri = Petri::new(vec!["x"]);
+        petri.add_transition(vec!["x"], vec!["y"]);
+        let net = net_fingerprint(&petri);
+        let fingerprint = disjunct_fingerprint(net, &"x = 0");
+        assert_eq!(fingerprint, disjunct_fingerprint(net, &"x = 0"));
+        assert_ne!(fingerprint, disjunct_fingerprint(net, &"x = 1"));
+        // The fingerprint does not depend on the build
+        assert_eq!(net, 0x0af6_3807_5088_bbbf);
+
+        let mut journal = DisjunctJournal::<String>::open(out_dir);
+        assert!(journal.lookup(0, fingerprint).is_none());
+        let proof = ProofInvariant::new(vec!["x".to_string()], Formula::And(vec![]));
+        journal.record(0, fingerprint, Some(proof));
+
+        let resume = crate::options::AnalysisOptions {
+            resume: true,
+            ..crate::options::AnalysisOptions::default()
+        };
+        let resumed =
+            crate::options::with_options(resume, || DisjunctJournal::<String>::open(out_dir));
+        let proof = resumed
+            .lookup(0, fingerprint)
+            .expect("disjunct should be journaled");
+        assert_eq!(proof.unwrap().variables, vec!["x".to_string()]);
+        // A different disjunct at the same index is not resumed
+        assert!(
+            resumed
+                .lookup(0, disjunct_fingerprint(net, &"x = 1"))
+                .is_none()
+        );
+        // Neither is the same disjunct of a changed net
+        petri.add_transition(vec!["y"], vec!["x"]);
+        let changed = disjunct_fingerprint(net_fingerprint(&petri), &"x = 0");
+        assert!(resumed.lookup(0, changed).is_none());
+
+        // Without --resume the journal starts over
+        let fresh = DisjunctJournal::<String>::open(out_dir);
+        assert!(fresh.lookup(0, fingerprint).is_none());
+    }
 
     #[test]
     fn test_petri_net_pruning_with_zero_constraints() {
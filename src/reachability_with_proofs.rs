@@ -1,13 +1,19 @@
 use crate::debug_report::DebugLogger;
 use crate::deterministic_map::{HashMap, HashSet};
+#[cfg(feature = "isl")]
 use crate::kleene::Kleene;
 use crate::petri::*;
 use crate::proof_parser::ProofInvariant;
+#[cfg(feature = "isl")]
 use crate::semilinear::*;
 use crate::size_logger::{PetriNetSize, log_petri_size_csv};
+use crate::smpt::CancellationToken;
+#[cfg(feature = "isl")]
 use crate::spresburger::SPresburgerSet;
 use colored::*;
-use either::{Either, Left, Right};
+use either::Either;
+#[cfg(feature = "isl")]
+use either::{Left, Right};
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use std::path::Path;
@@ -19,6 +25,57 @@ pub enum Decision<P: Eq + Hash> {
     CounterExample { trace: Vec<(Vec<P>, Vec<P>)> },
     Proof { proof: Option<ProofInvariant<P>> },
     Timeout { message: String },
+    /// Analysis was inconclusive; `partial_results` records the disjuncts that were decided
+    Unknown {
+        reason: UnknownReason,
+        partial_results: Vec<DisjunctResult>,
+    },
+}
+
+/// Why an analysis ended without a definite answer (other than a timeout)
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum UnknownReason {
+    /// The solver returned neither "reachable" nor "unreachable"
+    SolverUnknown { message: String },
+    /// An approximation was too coarse to decide the query
+    ApproximationLoss { message: String },
+    /// The user pressed Ctrl-C while the SMPT queries ran
+    Interrupted,
+}
+
+impl Display for UnknownReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnknownReason::SolverUnknown { message } => write!(f, "solver unknown: {}", message),
+            UnknownReason::ApproximationLoss { message } => {
+                write!(f, "approximation loss: {}", message)
+            }
+            UnknownReason::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// Outcome of the reachability query for a single complement disjunct
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DisjunctOutcome {
+    Reachable,
+    Unreachable,
+    Timeout,
+    Unknown,
+}
+
+/// Result of one complement disjunct, identified by its index
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DisjunctResult {
+    pub disjunct: usize,
+    pub outcome: DisjunctOutcome,
+}
+
+/// The result of disjunct `disjunct`, after telling the observers about it
+fn decided(disjunct: usize, outcome: DisjunctOutcome) -> DisjunctResult {
+    let result = DisjunctResult { disjunct, outcome };
+    crate::observer::notify(|observer| observer.on_disjunct_result(&result));
+    result
 }
 
 /// Global debug logger for reachability analysis
@@ -52,6 +109,7 @@ where
 }
 
 /// Alias for the new implementation - uses the new SPresburgerSet-based architecture
+#[cfg(feature = "isl")]
 pub fn is_petri_reachability_set_subset_of_semilinear<P, Q>(
     petri: Petri<Either<P, Q>>,
     places_that_must_be_zero: &[P],
@@ -59,8 +117,8 @@ pub fn is_petri_reachability_set_subset_of_semilinear<P, Q>(
     out_dir: &str,
 ) -> Decision<Either<P, Q>>
 where
-    P: Clone + Hash + Ord + Display + Debug,
-    Q: Clone + Hash + Ord + Display + Debug,
+    P: Clone + Hash + Ord + Display + Debug + serde::Serialize + serde::de::DeserializeOwned,
+    Q: Clone + Hash + Ord + Display + Debug + serde::Serialize + serde::de::DeserializeOwned,
 {
     is_petri_reachability_set_subset_of_semilinear_new(
         petri,
@@ -80,6 +138,7 @@ where
 /// GOAL: Check if Reachable(petri) ⊆ semilinear when places_that_must_be_zero = 0
 /// APPROACH: Check if ¬semilinear ∩ {places_that_must_be_zero = 0} is reachable
 ///          If this intersection is reachable, then the subset property is violated
+#[cfg(feature = "isl")]
 pub fn is_petri_reachability_set_subset_of_semilinear_new<P, Q>(
     petri: Petri<Either<P, Q>>,
     places_that_must_be_zero: &[P],
@@ -87,8 +146,8 @@ pub fn is_petri_reachability_set_subset_of_semilinear_new<P, Q>(
     out_dir: &str,
 ) -> Decision<Either<P, Q>>
 where
-    P: Clone + Hash + Ord + Display + Debug,
-    Q: Clone + Hash + Ord + Display + Debug,
+    P: Clone + Hash + Ord + Display + Debug + serde::Serialize + serde::de::DeserializeOwned,
+    Q: Clone + Hash + Ord + Display + Debug + serde::Serialize + serde::de::DeserializeOwned,
 {
     with_debug_logger(|debug_logger| {
         debug_logger.step(
@@ -111,66 +170,11 @@ where
             ),
         );
 
-        // Step 1: Convert semilinear set to SPresburgerSet and embed it in Either<P,Q> domain
-        let q_spresburger = SPresburgerSet::from_semilinear(semilinear);
-
-        // Step 2: Create universe over places that can vary (filter out places_that_must_be_zero)
-        // Since places_that_must_be_zero are constrained to 0, they don't participate in the analysis
-        let all_places = petri.get_places();
-        let places_that_can_vary: Vec<_> = all_places
-            .into_iter()
-            .filter(|place| {
-                // Keep the place if it's not in places_that_must_be_zero
-                match place {
-                    Left(p) => !places_that_must_be_zero.contains(p),
-                    Right(_) => false, // All Q-places can vary
-                }
-            })
-            .collect();
-
-        let varying_universe = SPresburgerSet::universe(places_that_can_vary);
-        debug_logger.step(
-            "Varying Universe",
-            "Varying universe",
-            &format!("Varying universe: {}", varying_universe),
-        );
-
-        let response_places = petri
-            .get_places()
-            .iter()
-            .filter_map(|place| match place {
-                Right(q) => Some(q.clone()),
-                Left(_) => None,
-            })
-            .collect::<Vec<_>>();
-
-        let response_universe = SPresburgerSet::universe(response_places);
-        debug_logger.step(
-            "Response Universe",
-            "Response universe",
-            &format!("Response universe: {}", response_universe),
-        );
-
-        // Step 3: Compute complement: universe - embedded_semilinear
-        let complement = response_universe.difference(q_spresburger);
-        debug_logger.step(
-            "Compute Complement",
-            "Computing complement (universe - embedded_semilinear)",
-            &format!("Complement: {}", complement),
-        );
-
-        let complement_embedded = complement.rename(|q| Right(q));
-        debug_logger.step(
-            "Complement Embedded",
-            "Complement embedded in Either<P,Q> domain",
-            &format!("Complement embedded: {}", complement_embedded),
-        );
-
-        let end_result_set = varying_universe.times(complement_embedded);
-        debug_logger.step(
-            "End Result Set",
-            "End result set",
-            &format!("End result set: {}", end_result_set),
+        let end_result_set = serializability_complement(
+            &petri,
+            places_that_must_be_zero,
+            semilinear,
+            debug_logger,
         );
 
         // Step 4: Check if this constraint set is reachable
@@ -210,22 +214,232 @@ where
                 );
                 Decision::Timeout { message }
             }
+            Decision::Unknown {
+                reason,
+                partial_results,
+            } => {
+                // Analysis was inconclusive
+                debug_logger.step(
+                    "Final Result",
+                    "Analysis INCONCLUSIVE",
+                    &reason.to_string(),
+                );
+                Decision::Unknown {
+                    reason,
+                    partial_results,
+                }
+            }
         }
     })
 }
 
+/// The target of the serializability check: markings where the places that must be
+/// zero are empty and the responses lie outside the semilinear set
+#[cfg(feature = "isl")]
+fn serializability_complement<P, Q>(
+    petri: &Petri<Either<P, Q>>,
+    places_that_must_be_zero: &[P],
+    semilinear: SemilinearSet<Q>,
+    debug_logger: &DebugLogger,
+) -> SPresburgerSet<Either<P, Q>>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+    Q: Clone + Hash + Ord + Display + Debug,
+{
+    // Step 1: Convert semilinear set to SPresburgerSet and embed it in Either<P,Q> domain
+    let q_spresburger = SPresburgerSet::from_semilinear(semilinear);
+
+    // Step 2: Create universe over places that can vary (filter out places_that_must_be_zero)
+    // Since places_that_must_be_zero are constrained to 0, they don't participate in the analysis
+    let all_places = petri.get_places();
+    let places_that_can_vary: Vec<_> = all_places
+        .into_iter()
+        .filter(|place| {
+            // Keep the place if it's not in places_that_must_be_zero
+            match place {
+                Left(p) => !places_that_must_be_zero.contains(p),
+                Right(_) => false, // All Q-places can vary
+            }
+        })
+        .collect();
+
+    let varying_universe = SPresburgerSet::universe(places_that_can_vary);
+    debug_logger.step(
+        "Varying Universe",
+        "Varying universe",
+        &format!("Varying universe: {}", varying_universe),
+    );
+
+    let response_places = petri
+        .get_places()
+        .iter()
+        .filter_map(|place| match place {
+            Right(q) => Some(q.clone()),
+            Left(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let response_universe = SPresburgerSet::universe(response_places);
+    debug_logger.step(
+        "Response Universe",
+        "Response universe",
+        &format!("Response universe: {}", response_universe),
+    );
+
+    // Step 3: Compute complement: universe - embedded_semilinear
+    let complement = response_universe.difference(q_spresburger);
+    debug_logger.step(
+        "Compute Complement",
+        "Computing complement (universe - embedded_semilinear)",
+        &format!("Complement: {}", complement),
+    );
+
+    let complement_embedded = complement.rename(|q| Right(q));
+    debug_logger.step(
+        "Complement Embedded",
+        "Complement embedded in Either<P,Q> domain",
+        &format!("Complement embedded: {}", complement_embedded),
+    );
+
+    let end_result_set = varying_universe.times(complement_embedded);
+    debug_logger.step(
+        "End Result Set",
+        "End result set",
+        &format!("End result set: {}", end_result_set),
+    );
+
+    end_result_set
+}
+
+/// Split a target set into the disjuncts that are each sent to SMPT as one query
+#[cfg(feature = "isl")]
+fn reachability_queries<P>(
+    petri: &Petri<P>,
+    mut presburger: SPresburgerSet<P>,
+    debug_logger: &DebugLogger,
+) -> Vec<crate::constraints::QuantifiedSet<P>>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
+    // First step: Expand the domain of the presburger set to include all places in the Petri net
+    let all_petri_places = petri.get_places();
+    debug_logger.step(
+        "Domain Expansion",
+        "Expanding presburger set domain to match Petri net",
+        &format!(
+            "Petri net places: [{}]",
+            all_petri_places
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    );
+
+    presburger = presburger.expand_domain(all_petri_places);
+    debug_logger.step(
+        "Domain Expanded",
+        "Presburger set domain expanded",
+        &format!("Expanded presburger set: {}", presburger),
+    );
+
+    // Convert SPresburgerSet to disjunctive normal form (list of quantified sets)
+    let disjuncts = presburger.extract_constraint_disjuncts();
+
+    debug_logger.step(
+        "Disjunct Conversion",
+        "SPresburgerSet converted to disjuncts",
+        &format!(
+            "Number of disjuncts: {}\nDisjuncts: {}",
+            disjuncts.len(),
+            disjuncts
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    );
+
+    disjuncts
+}
+
+/// Size of the reachability queries of a serializability check, computed without
+/// running them (`--dry-run`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryEstimate {
+    pub num_disjuncts: usize,
+    /// Most constraints in a single disjunct
+    pub largest_constraint_system: usize,
+    /// Most existential variables in a single disjunct
+    pub max_existentials: usize,
+    /// SMPT calls if every disjunct has to be queried (fewer if a counterexample is
+    /// found early or results are cached)
+    pub predicted_smpt_calls: usize,
+}
+
+/// Generate the queries of `is_petri_reachability_set_subset_of_semilinear_new` and
+/// summarize their size, without invoking SMPT
+#[cfg(feature = "isl")]
+pub fn estimate_queries<P, Q>(
+    petri: &Petri<Either<P, Q>>,
+    places_that_must_be_zero: &[P],
+    semilinear: SemilinearSet<Q>,
+) -> QueryEstimate
+where
+    P: Clone + Hash + Ord + Display + Debug,
+    Q: Clone + Hash + Ord + Display + Debug,
+{
+    let disjuncts = complement_disjuncts(petri, places_that_must_be_zero, semilinear);
+    let max_existentials = disjuncts
+        .iter()
+        .map(|d| d.extract_and_reify_existential_variables().0.len())
+        .max()
+        .unwrap_or(0);
+    QueryEstimate {
+        num_disjuncts: disjuncts.len(),
+        largest_constraint_system: disjuncts
+            .iter()
+            .map(|d| d.constraints().len())
+            .max()
+            .unwrap_or(0),
+        max_existentials,
+        predicted_smpt_calls: disjuncts.len(),
+    }
+}
+
+/// The disjuncts of the complement of the serializable set that
+/// `is_petri_reachability_set_subset_of_semilinear_new` asks SMPT about, over all
+/// places of the net, without asking
+#[cfg(feature = "isl")]
+pub fn complement_disjuncts<P, Q>(
+    petri: &Petri<Either<P, Q>>,
+    places_that_must_be_zero: &[P],
+    semilinear: SemilinearSet<Q>,
+) -> Vec<crate::constraints::QuantifiedSet<Either<P, Q>>>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+    Q: Clone + Hash + Ord + Display + Debug,
+{
+    with_debug_logger(|debug_logger| {
+        let target =
+            serializability_complement(petri, places_that_must_be_zero, semilinear, debug_logger);
+        reachability_queries(petri, target, debug_logger)
+    })
+}
+
 /// Checks if a Petri net can reach any state satisfying the given SPresburgerSet constraints.
 ///
 /// APPROACH: Convert SPresburgerSet to disjunctive normal form and check each disjunct.
 /// A SPresburgerSet represents a union of constraint sets (disjuncts).
 /// The Petri net can reach the SPresburgerSet if it can reach ANY of the disjuncts.
+#[cfg(feature = "isl")]
 pub fn can_reach_presburger<P>(
     petri: Petri<P>,
-    mut presburger: SPresburgerSet<P>,
+    presburger: SPresburgerSet<P>,
     out_dir: &str,
 ) -> Decision<P>
 where
-    P: Clone + Hash + Ord + Display + Debug,
+    P: Clone + Hash + Ord + Display + Debug + serde::Serialize + serde::de::DeserializeOwned,
 {
     with_debug_logger(|debug_logger| {
         debug_logger.step(
@@ -234,51 +448,57 @@ where
             &format!("SPresburgerSet to be checked: {}", presburger),
         );
 
-        // First step: Expand the domain of the presburger set to include all places in the Petri net
-        let all_petri_places = petri.get_places();
-        debug_logger.step(
-            "Domain Expansion",
-            "Expanding presburger set domain to match Petri net",
-            &format!(
-                "Petri net places: [{}]",
-                all_petri_places
-                    .iter()
-                    .map(|p| p.to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ),
-        );
-
-        presburger = presburger.expand_domain(all_petri_places);
-        debug_logger.step(
-            "Domain Expanded",
-            "Presburger set domain expanded",
-            &format!("Expanded presburger set: {}", presburger),
-        );
-
-        // Convert SPresburgerSet to disjunctive normal form (list of quantified sets)
-        let disjuncts = presburger.extract_constraint_disjuncts();
-
-        debug_logger.step(
-            "Disjunct Conversion",
-            "SPresburgerSet converted to disjuncts",
-            &format!(
-                "Number of disjuncts: {}\nDisjuncts: {}",
-                disjuncts.len(),
-                disjuncts
-                    .iter()
-                    .map(|d| d.to_string())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ),
-        );
+        let disjuncts = reachability_queries(&petri, presburger, debug_logger);
 
         // Check if ANY disjunct is reachable, collecting proofs along the way
         let mut disjunct_proofs = Vec::new();
-
-        for (i, quantified_set) in disjuncts.iter().enumerate() {
+        // Inconclusive disjuncts don't stop the search: a later disjunct may still be reachable
+        let mut partial_results = Vec::new();
+        let mut unknown_reason = None;
+        // Unreachable disjuncts are journaled so an interrupted run can be resumed
+        let mut journal = crate::reachability::DisjunctJournal::open(out_dir);
+        let net = crate::reachability::net_fingerprint(&petri);
+        // Cancelled by Ctrl-C: the running SMPT query is killed and no further ones start
+        let cancel = CancellationToken::new();
+        let _interrupts = crate::smpt::catch_interrupts();
+        let queries_start = std::time::Instant::now();
+        let mut queried = 0;
+
+        // Disjuncts keep their original index (for stats, files and the journal),
+        // but are queried in the order chosen by the disjunct ordering strategy, and
+        // retried with longer timeouts under `--auto-timeout`
+        let mut scheduler = crate::reachability::TimeoutScheduler::new(
+            crate::reachability::order_disjuncts(&disjuncts),
+            crate::reachability::auto_timeout(),
+        );
+        while let Some((i, timeout)) = scheduler.next_query() {
+            if cancel.is_cancelled() {
+                scheduler.record_stats();
+                return interrupted_decision(partial_results);
+            }
+            let quantified_set = &disjuncts[i];
             debug_logger.log_disjunct_start(i, quantified_set);
-            println!("Checking disjunct {}: {}", i, quantified_set);
+            let fingerprint = crate::reachability::disjunct_fingerprint(net, quantified_set);
+            if let Some(proof) = journal.lookup(i, fingerprint) {
+                println!("Disjunct {} already proven unreachable (resumed from journal)", i);
+                debug_logger.step(
+                    &format!("Disjunct {} Result", i),
+                    "Disjunct is UNREACHABLE (resumed from journal)",
+                    &format!("Disjunct {}: UNREACHABLE", i),
+                );
+                if let Some(p) = proof {
+                    disjunct_proofs.push(p);
+                }
+                partial_results.push(decided(i, DisjunctOutcome::Unreachable));
+                continue;
+            }
+            match timeout {
+                Some(seconds) => println!(
+                    "Checking disjunct {} (timeout {}s): {}",
+                    i, seconds, quantified_set
+                ),
+                None => println!("Checking disjunct {}: {}", i, quantified_set),
+            }
             
             // Record initial petri net size for this disjunct
             let initial_places = petri.get_places().len();
@@ -286,8 +506,39 @@ where
             
             // Start disjunct stats collection
             crate::stats::start_disjunct_analysis(i, initial_places, initial_transitions);
+            queried += 1;
 
-            match can_reach_quantified_set(petri.clone(), quantified_set.clone(), out_dir, i) {
+            let query_start = std::time::Instant::now();
+            let query = || {
+                can_reach_quantified_set(petri.clone(), quantified_set.clone(), out_dir, i, &cancel)
+            };
+            let decision = match timeout {
+                Some(smpt_timeout) => crate::options::with_options(
+                    crate::options::AnalysisOptions {
+                        smpt_timeout,
+                        ..crate::options::current()
+                    },
+                    query,
+                ),
+                None => query(),
+            };
+            // Only solver timeouts and unknowns may go away with more time
+            let settled = match &decision {
+                Decision::Timeout { .. } => false,
+                Decision::Unknown { reason, .. } => {
+                    !matches!(reason, UnknownReason::SolverUnknown { .. })
+                }
+                _ => true,
+            };
+            if scheduler.finish(i, timeout, query_start.elapsed(), settled) {
+                println!(
+                    "Disjunct {} is undecided, retrying later with a longer timeout",
+                    i
+                );
+                continue;
+            }
+
+            match decision {
                 Decision::CounterExample { trace } => {
                     println!(
                         "Disjunct {} is reachable - constraint set is satisfiable",
@@ -298,6 +549,9 @@ where
                         "Disjunct is REACHABLE - constraint set is satisfiable",
                         &format!("Disjunct {}: REACHABLE", i),
                     );
+                    partial_results.push(decided(i, DisjunctOutcome::Reachable));
+                    scheduler.record_stats();
+                    record_cancelled_disjuncts(scheduler.pending(), queried, queries_start);
                     return Decision::CounterExample { trace };
                 }
                 Decision::Proof { proof } => {
@@ -306,9 +560,11 @@ where
                         "Disjunct is UNREACHABLE",
                         &format!("Disjunct {}: UNREACHABLE", i),
                     );
+                    journal.record(i, fingerprint, proof.clone());
                     if let Some(p) = proof {
                         disjunct_proofs.push(p);
                     }
+                    partial_results.push(decided(i, DisjunctOutcome::Unreachable));
                 }
                 Decision::Timeout { message } => {
                     debug_logger.step(
@@ -316,11 +572,56 @@ where
                         "Analysis TIMED OUT",
                         &format!("Disjunct {}: TIMEOUT - {}", i, message),
                     );
+                    partial_results.push(decided(i, DisjunctOutcome::Timeout));
+                    scheduler.record_stats();
+                    record_cancelled_disjuncts(scheduler.pending(), queried, queries_start);
                     return Decision::Timeout { message };
                 }
+                Decision::Unknown {
+                    reason: UnknownReason::Interrupted,
+                    ..
+                } => {
+                    partial_results.push(decided(i, DisjunctOutcome::Unknown));
+                    scheduler.record_stats();
+                    return interrupted_decision(partial_results);
+                }
+                Decision::Unknown { reason, .. } => {
+                    println!("Disjunct {} is inconclusive: {}", i, reason);
+                    debug_logger.step(
+                        &format!("Disjunct {} Result", i),
+                        "Analysis INCONCLUSIVE",
+                        &format!("Disjunct {}: UNKNOWN - {}", i, reason),
+                    );
+                    partial_results.push(decided(i, DisjunctOutcome::Unknown));
+                    unknown_reason.get_or_insert(reason);
+                }
             }
         }
 
+        scheduler.record_stats();
+        let undecided = scheduler.undecided();
+        if !undecided.is_empty() {
+            partial_results.extend(undecided.iter().map(|&i| decided(i, DisjunctOutcome::Timeout)));
+            let message = format!(
+                "time budget of {}s spent with {} undecided disjunct(s)",
+                crate::reachability::auto_timeout().unwrap_or_default(),
+                undecided.len()
+            );
+            debug_logger.step(
+                "Time Budget Spent",
+                "Analysis TIMED OUT",
+                &format!("Undecided disjuncts: {:?}", undecided),
+            );
+            return Decision::Timeout { message };
+        }
+
+        if let Some(reason) = unknown_reason {
+            return Decision::Unknown {
+                reason,
+                partial_results,
+            };
+        }
+
         println!("No disjuncts are reachable - constraint set is unsatisfiable");
         debug_logger.step(
             "All Disjuncts Checked",
@@ -328,43 +629,109 @@ where
             &format!("Checked {} disjuncts, all UNREACHABLE", disjuncts.len()),
         );
 
-        // Combine all disjunct proofs by ANDing them together
-        // This handles all cases: empty (And([])), single element (And([x])), and multiple elements
-        use crate::proof_parser::Formula;
+        let combined_proof = Some(compose_disjunct_proofs(disjunct_proofs));
 
-        // Collect all variables from all proofs
-        let mut all_variables = HashSet::default();
-        for proof in &disjunct_proofs {
-            all_variables.extend(proof.variables.iter().cloned());
+        Decision::Proof {
+            proof: combined_proof,
         }
+    })
+}
 
-        // Create AND of all formulas
-        let formulas: Vec<Formula<P>> = disjunct_proofs
-            .into_iter()
-            .map(|proof| proof.formula)
-            .collect();
+/// Compose the proofs of all unreachable disjuncts into a single invariant.
+///
+/// Each disjunct proof is an inductive invariant that holds initially and excludes
+/// its disjunct. The conjunction of inductive invariants is again inductive, and it
+/// excludes every disjunct, i.e. the whole (union) target set. The result is the
+/// conjunction over the union of the proofs' variables; composing no proofs yields
+/// `And([])`, i.e. true (nothing was reachable to begin with).
+pub fn compose_disjunct_proofs<P>(proofs: Vec<ProofInvariant<P>>) -> ProofInvariant<P>
+where
+    P: Clone + Hash + Ord + Display,
+{
+    use crate::proof_parser::Formula;
 
-        let combined_formula = Formula::And(formulas);
-        let mut combined_variables: Vec<P> = all_variables.into_iter().collect();
-        combined_variables.sort();
-        combined_variables.dedup();
+    // Collect all variables from all proofs
+    let mut all_variables = HashSet::default();
+    for proof in &proofs {
+        all_variables.extend(proof.variables.iter().cloned());
+    }
+    let mut variables: Vec<P> = all_variables.into_iter().collect();
+    variables.sort();
 
-        let combined_proof = Some(ProofInvariant::new(
-            combined_variables,
-            combined_formula,
-        ));
+    // Create AND of all formulas
+    let formulas: Vec<Formula<P>> = proofs.into_iter().map(|proof| proof.formula).collect();
 
-        Decision::Proof {
-            proof: combined_proof,
-        }
-    })
+    // ProofInvariant::new validates that the composed formula is closed over `variables`
+    ProofInvariant::new(variables, Formula::And(formulas))
+}
+
+/// The decision of a query loop that was stopped by Ctrl-C
+fn interrupted_decision<P: Eq + Hash>(partial_results: Vec<DisjunctResult>) -> Decision<P> {
+    println!("Analysis interrupted; decided disjuncts are kept in the journal for --resume");
+    Decision::Unknown {
+        reason: UnknownReason::Interrupted,
+        partial_results,
+    }
+}
+
+/// Record in the stats that `skipped` pending disjuncts were not queried because the
+/// analysis ended early, estimating the saved time from the average time of the queries
+/// that did run.
+fn record_cancelled_disjuncts(skipped: usize, queried: usize, queries_start: std::time::Instant) {
+    if skipped == 0 {
+        return;
+    }
+    let average_ms = queries_start.elapsed().as_millis() as u64 / queried.max(1) as u64;
+    println!(
+        "Skipping {} remaining disjunct(s) (estimated {} ms saved)",
+        skipped,
+        average_ms * skipped as u64
+    );
+    crate::stats::record_cancelled_disjuncts(skipped, average_ms * skipped as u64);
+}
+
+/// The reachability query of a disjunct without quantifiers: existential variables
+/// become places `Left(i)` that can hold any number of tokens, and the places of
+/// `petri` become `Right(p)`. Returns the indices of the existential variables too.
+#[allow(clippy::type_complexity)]
+pub fn reify_existentials<P>(
+    petri: Petri<P>,
+    quantified_set: &crate::constraints::QuantifiedSet<P>,
+) -> (
+    Vec<usize>,
+    Petri<Either<usize, P>>,
+    Vec<crate::constraints::Constraint<Either<usize, P>>>,
+)
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
+    let (existential_places, constraints) =
+        quantified_set.extract_and_reify_existential_variables();
+
+    // Extract just the usize indices from the Either<usize, P> type
+    let existential_indices: Vec<usize> = existential_places
+        .iter()
+        .filter_map(|place| match place {
+            Either::Left(idx) => Some(*idx),
+            Either::Right(_) => None,
+        })
+        .collect();
+
+    // Transform the Petri net from Petri<P> to Petri<Either<usize, P>>
+    // by mapping all existing places to Right(p) and adding existential places as Left(i)
+    let mut new_petri = petri.rename(|p| Either::Right(p));
+    for idx in &existential_indices {
+        new_petri.add_existential_place(Either::Left(*idx));
+    }
+    (existential_indices, new_petri, constraints)
 }
 
 pub fn can_reach_quantified_set<P>(
     petri: Petri<P>,
-    quantified_set: super::presburger::QuantifiedSet<P>,
+    quantified_set: crate::constraints::QuantifiedSet<P>,
     out_dir: &str,
     disjunct_id: usize,
+    cancel: &CancellationToken,
 ) -> Decision<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
@@ -376,17 +743,8 @@ where
             &format!("Quantified set: {}", quantified_set),
         );
 
-        let (existential_places, basic_constraint_set) =
-            quantified_set.extract_and_reify_existential_variables();
-
-        // Extract just the usize indices from the Either<usize, P> type
-        let existential_indices: Vec<usize> = existential_places
-            .iter()
-            .filter_map(|place| match place {
-                Either::Left(idx) => Some(*idx),
-                Either::Right(_) => None,
-            })
-            .collect();
+        let (existential_indices, new_petri, basic_constraint_set) =
+            reify_existentials(petri, &quantified_set);
 
         debug_logger.step(
             &format!("Quantified Set {} Variables", disjunct_id),
@@ -402,13 +760,6 @@ where
             ),
         );
 
-        // Transform the Petri net from Petri<P> to Petri<Either<usize, P>>
-        // by mapping all existing places to Right(p) and adding existential places as Left(i)
-        let mut new_petri = petri.rename(|p| Either::Right(p));
-        for idx in &existential_indices {
-            new_petri.add_existential_place(Either::Left(*idx));
-        }
-
         debug_logger.log_petri_net(
             &format!("Transformed Petri Net {}", disjunct_id),
             "Petri net with existential variables added",
@@ -420,11 +771,13 @@ where
             &basic_constraint_set,
         );
 
-        // Build mapping from sanitized names to Either<usize, P> for proof conversion
+        // Build mapping from place names in SMPT files to Either<usize, P> for proof conversion
+        let places = new_petri.get_places();
+        crate::utils::string::display_index(&places, "Petri net places");
         let mut name_to_place: HashMap<String, Either<usize, P>> = HashMap::default();
-        for place in new_petri.get_places() {
-            let sanitized_name = crate::utils::string::sanitize(&place.to_string());
-            name_to_place.insert(sanitized_name, place);
+        for place in places {
+            let name = crate::naming::place_name(&place.to_string());
+            name_to_place.insert(name, place);
         }
 
         // Get the result with Either<usize, P> type
@@ -434,6 +787,7 @@ where
             out_dir,
             disjunct_id,
             name_to_place,
+            cancel,
         );
 
         // Handle existential quantification for proofs and traces
@@ -483,6 +837,13 @@ where
             Decision::Timeout { message } => {
                 Decision::Timeout { message }
             }
+            Decision::Unknown {
+                reason,
+                partial_results,
+            } => Decision::Unknown {
+                reason,
+                partial_results,
+            },
         }
     })
 }
@@ -490,9 +851,10 @@ where
 /// Reachability check with constraints using SMPT with pruning and debug logging
 pub fn can_reach_constraint_set_with_debug<P>(
     petri: Petri<P>,
-    constraints: Vec<super::presburger::Constraint<P>>,
+    constraints: Vec<crate::constraints::Constraint<P>>,
     out_dir: &str,
     disjunct_id: usize,
+    cancel: &CancellationToken,
 ) -> Decision<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
@@ -505,16 +867,18 @@ where
         out_dir,
         disjunct_id,
         name_to_place,
+        cancel,
     )
 }
 
 /// Reachability check with constraints using SMPT with pruning, debug logging, and name mapping
 fn can_reach_constraint_set_with_debug_mapped<P>(
     petri: Petri<P>,
-    constraints: Vec<super::presburger::Constraint<P>>,
+    constraints: Vec<crate::constraints::Constraint<P>>,
     out_dir: &str,
     disjunct_id: usize,
     name_to_place: HashMap<String, P>,
+    cancel: &CancellationToken,
 ) -> Decision<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
@@ -548,10 +912,31 @@ where
             num_places: petri.get_places().len(),
             num_transitions: petri.get_transitions().len(),
         };
-        log_petri_size_csv(&csv_path, &before).expect("Failed to log Petri‐net size (pre‐pruning)");
+        if let Err(err) = log_petri_size_csv(&csv_path, &before) {
+            eprintln!(
+                "Warning: Failed to log Petri net size (pre-pruning): {}",
+                err
+            );
+        }
+
+        // The marking equation is a cheap necessary condition for reachability
+        if let Some(proof) =
+            crate::marking_equation::check_disjunct(&petri, &constraints, out_dir, disjunct_id)
+        {
+            return Decision::Proof { proof: Some(proof) };
+        }
+
+        // Without SMPT, decide the disjunct with the pure-Rust checks (before pruning,
+        // whose proof translation is only needed for SMPT proofs). Exported and imported
+        // queries go to SMPT even if it is not installed here.
+        if !crate::smpt::smpt_available()
+            && crate::smpt_offline::offline_mode() == crate::smpt_offline::OfflineMode::Off
+        {
+            return crate::smpt_fallback::decide(&petri, &constraints, disjunct_id, cancel);
+        }
 
         // Extract zero variables from constraints
-        let zero_variables = super::presburger::Constraint::extract_zero_variables(&constraints);
+        let zero_variables = crate::constraints::Constraint::extract_zero_variables(&constraints);
         let zero_variables_set: HashSet<P> = zero_variables.into_iter().collect();
 
         debug_logger.step(
@@ -600,6 +985,7 @@ where
                 disjunct_id,
                 name_to_place,
                 0, // Start at iteration 0
+                cancel,
             )
         } else {
             // Optimization disabled, call SMPT directly without pruning
@@ -610,7 +996,13 @@ where
             );
 
             let result =
-                crate::smpt::can_reach_constraint_set(petri, constraints, out_dir, disjunct_id);
+                crate::smpt::can_reach_constraint_set(
+                    petri,
+                    constraints,
+                    out_dir,
+                    disjunct_id,
+                    cancel,
+                );
             convert_smpt_result_to_decision(result, &name_to_place)
         }
     })
@@ -645,16 +1037,19 @@ where
         SmptVerificationOutcome::Error { message } => {
             eprintln!("SMPT verification error: {}", message);
             // Check if this is a timeout error
-            if message.contains("timeout") || message.contains("timed out") {
+            if message.starts_with(crate::smpt::TIMEOUT_MESSAGE_PREFIX) {
                 Decision::Timeout { message }
+            } else if message == crate::smpt::CANCELLED_MESSAGE {
+                Decision::Unknown {
+                    reason: UnknownReason::Interrupted,
+                    partial_results: Vec::new(),
+                }
             } else {
-                eprintln!("CRITICAL ERROR: SMPT verification failed: {}", message);
-                eprintln!("Cannot determine serializability - analysis is inconclusive");
-                eprintln!("This could indicate a bug in the verification pipeline");
-                // Log this as an error to the JSONL file before panicking
-                crate::stats::set_analysis_result("error");
-                crate::stats::finalize_stats();
-                panic!("SMPT verification failed: {}", message);
+                eprintln!("Cannot decide this query - analysis is inconclusive");
+                Decision::Unknown {
+                    reason: UnknownReason::SolverUnknown { message },
+                    partial_results: Vec::new(),
+                }
             }
         }
     }
@@ -665,14 +1060,16 @@ where
 /// This function implements the recursive approach where:
 /// 1. Pruning happens top-down (forward->backward on each recursive call)
 /// 2. Proof translation happens bottom-up (backward->forward as we return)
+#[allow(clippy::too_many_arguments)]
 fn can_reach_constraint_set_recursive_with_proof<P>(
     mut petri: Petri<P>,
-    constraints: Vec<super::presburger::Constraint<P>>,
+    constraints: Vec<crate::constraints::Constraint<P>>,
     target_places: &[P],
     out_dir: &str,
     disjunct_id: usize,
     name_to_place: HashMap<String, P>,
     iteration: usize,
+    cancel: &CancellationToken,
 ) -> Decision<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
@@ -694,7 +1091,13 @@ where
         if iteration > 100 {
             eprintln!("WARNING: Pruning recursion exceeded 100 iterations, stopping");
             let result =
-                crate::smpt::can_reach_constraint_set(petri, constraints, out_dir, disjunct_id);
+                crate::smpt::can_reach_constraint_set(
+                    petri,
+                    constraints,
+                    out_dir,
+                    disjunct_id,
+                    cancel,
+                );
             return convert_smpt_result_to_decision(result, &name_to_place);
         }
 
@@ -707,7 +1110,13 @@ where
             );
 
             let result =
-                crate::smpt::can_reach_constraint_set(petri, constraints, out_dir, disjunct_id);
+                crate::smpt::can_reach_constraint_set(
+                    petri,
+                    constraints,
+                    out_dir,
+                    disjunct_id,
+                    cancel,
+                );
 
             return convert_smpt_result_to_decision(result, &name_to_place);
         }
@@ -804,13 +1213,24 @@ where
             };
 
             let csv_path = Path::new(out_dir).join("petri_size_stats.csv");
-            log_petri_size_csv(&csv_path, &after).expect("Failed to log Petri‐net size (post‐pruning)");
+            if let Err(err) = log_petri_size_csv(&csv_path, &after) {
+                eprintln!(
+                    "Warning: Failed to log Petri net size (post-pruning): {}",
+                    err
+                );
+            }
             
             // Finalize disjunct stats
             crate::stats::finalize_disjunct(after.num_places, after.num_transitions);
 
             let result =
-                crate::smpt::can_reach_constraint_set(petri, constraints, out_dir, disjunct_id);
+                crate::smpt::can_reach_constraint_set(
+                    petri,
+                    constraints,
+                    out_dir,
+                    disjunct_id,
+                    cancel,
+                );
 
             return convert_smpt_result_to_decision(result, &name_to_place);
         }
@@ -830,6 +1250,7 @@ where
             disjunct_id,
             name_to_place,
             iteration + 1,
+            cancel,
         );
 
         // Transform the proof or trace on the way back up
@@ -882,7 +1303,36 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::presburger::{Constraint, ConstraintType};
+    use crate::constraints::{Constraint, ConstraintType};
+
+    #[test]
+    fn test_compose_disjunct_proofs() {
+        use crate::proof_parser::{AffineExpr, CompOp, Constraint as ProofConstraint, Formula};
+
+        // var <= n, i.e. n - var >= 0
+        let at_most = |var: &str, n: i64| {
+            Formula::Constraint(ProofConstraint::new(
+                AffineExpr::from_const(n).sub(&AffineExpr::from_var(var.to_string())),
+                CompOp::Geq,
+            ))
+        };
+        let p1 = ProofInvariant::new(vec!["y".to_string(), "x".to_string()], at_most("x", 1));
+        let p2 = ProofInvariant::new(vec!["z".to_string(), "x".to_string()], at_most("z", 0));
+
+        let composed = compose_disjunct_proofs(vec![p1, p2]);
+        assert_eq!(
+            composed.variables,
+            vec!["x".to_string(), "y".to_string(), "z".to_string()]
+        );
+        assert_eq!(
+            composed.formula,
+            Formula::And(vec![at_most("x", 1), at_most("z", 0)])
+        );
+
+        let empty = compose_disjunct_proofs::<String>(vec![]);
+        assert!(empty.variables.is_empty());
+        assert_eq!(empty.formula, Formula::And(vec![]));
+    }
 
     #[test]
     fn test_petri_net_pruning_with_zero_constraints() {
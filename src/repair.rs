@@ -0,0 +1,553 @@
+//! Suggested fixes for non-serializable programs (`--suggest-fix`, experimental).
+//!
+//! Tries small changes to the request bodies of a program and checks each changed
+//! program again, until one is serializable. There are two kinds of change:
+//!
+//! - `Atomic`: drop one `yield`, so that the segments before and after it run as one
+//!   atomic step. Yields inside loops are left alone, since a loop without a yield
+//!   might never give up its turn.
+//! - `Lock`: wrap a whole request in a global lock `REPAIR_LOCK`. The request waits
+//!   (yielding) until the lock is free, takes it in the same step, and releases it
+//!   after its body, so locked requests never interleave with each other.
+//!
+//! Fixes only consider the requests that occur in the counterexample. Sets of up to
+//! `MAX_FIXES` fixes are tried cheapest first, where dropping a yield costs 1 and a
+//! lock costs the number of yields it covers. At most `MAX_CHECKS` distinct programs
+//! are checked, and the verdicts are cached by program, since different fixes can give
+//! the same program. The first serializable program is reported; it is the cheapest
+//! fix within these bounds, not necessarily the cheapest one overall.
+
+use crate::deterministic_map::HashMap;
+use crate::expr_to_ns;
+#[cfg(feature = "isl")]
+use crate::expr_to_ns::{ExprRequest, Global, LocalExpr};
+#[cfg(feature = "isl")]
+use crate::ns_decision::NSDecision;
+use crate::parser::{Expr, ExprHc, Program};
+#[cfg(feature = "isl")]
+use colored::*;
+use hash_cons::Hc;
+use std::fmt;
+use std::hash::Hash;
+#[cfg(feature = "isl")]
+use std::path::Path;
+
+/// Search for a fix of non-serializable programs
+pub fn set_suggest_fix(enabled: bool) {
+    crate::options::update(|options| options.suggest_fix = enabled);
+}
+
+pub fn suggest_fix_enabled() -> bool {
+    crate::options::get(|options| options.suggest_fix)
+}
+
+/// Largest number of fixes combined into one candidate
+pub const MAX_FIXES: usize = 3;
+
+/// Largest number of distinct programs checked
+pub const MAX_CHECKS: usize = 64;
+
+/// Global used by `Fix::Lock`, 1 while a locked request runs
+pub const LOCK_VAR: &str = "REPAIR_LOCK";
+
+/// Local that holds the response of a locked request while it releases the lock
+const RESULT_VAR: &str = "repair_result";
+
+/// A change to one request
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Fix {
+    /// Drop the `yield`-th yield of the request body (in reading order)
+    Atomic { request: String, yield_index: usize },
+    /// Run the request while holding `LOCK_VAR`
+    Lock { request: String, yields: usize },
+}
+
+impl Fix {
+    pub fn cost(&self) -> usize {
+        match self {
+            Fix::Atomic { .. } => 1,
+            Fix::Lock { yields, .. } => *yields,
+        }
+    }
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fix::Atomic {
+                request,
+                yield_index,
+            } => write!(
+                f,
+                "make {} atomic across its yield #{}",
+                request,
+                yield_index + 1
+            ),
+            Fix::Lock { request, .. } => write!(f, "run {} under the global lock", request),
+        }
+    }
+}
+
+/// Outcome of checking a changed program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Serializable,
+    NotSerializable,
+    /// Timeout or unknown
+    Inconclusive,
+}
+
+/// Result of a successful search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair<K> {
+    pub fixes: Vec<Fix>,
+    pub cost: usize,
+    /// The fixed program
+    pub program: K,
+    /// Number of distinct programs that were checked
+    pub checks: usize,
+}
+
+/// For each yield of `expr` in reading order, whether it is inside a loop
+fn yield_sites(expr: &Expr, in_loop: bool, sites: &mut Vec<bool>) {
+    match expr {
+        Expr::Yield => sites.push(in_loop),
+        Expr::Assign(_, e) | Expr::Not(e) | Expr::Return(e) => yield_sites(e, in_loop, sites),
+        Expr::While(c, b) => {
+            yield_sites(c, true, sites);
+            yield_sites(b, true, sites);
+        }
+        Expr::Equal(a, b)
+        | Expr::Add(a, b)
+        | Expr::Subtract(a, b)
+        | Expr::Sequence(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b) => {
+            yield_sites(a, in_loop, sites);
+            yield_sites(b, in_loop, sites);
+        }
+        Expr::If(c, t, e) => {
+            yield_sites(c, in_loop, sites);
+            yield_sites(t, in_loop, sites);
+            yield_sites(e, in_loop, sites);
+        }
+        Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {}
+    }
+}
+
+/// The fixes to try for the given requests of `program`
+pub fn candidate_fixes(program: &Program, requests: &[String]) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+    for request in &program.requests {
+        if !requests.contains(&request.name) {
+            continue;
+        }
+        let mut sites = Vec::new();
+        yield_sites(&request.body, false, &mut sites);
+        for (yield_index, in_loop) in sites.iter().enumerate() {
+            if !in_loop {
+                fixes.push(Fix::Atomic {
+                    request: request.name.clone(),
+                    yield_index,
+                });
+            }
+        }
+        if !sites.is_empty() {
+            fixes.push(Fix::Lock {
+                request: request.name.clone(),
+                yields: sites.len(),
+            });
+        }
+    }
+    fixes
+}
+
+/// Replace the yields of `expr` whose index is in `drop` by 0, the value of a yield.
+/// `next` counts the yields seen so far.
+fn drop_yields(table: &mut ExprHc, expr: &Hc<Expr>, drop: &[usize], next: &mut usize) -> Hc<Expr> {
+    match expr.as_ref() {
+        Expr::Yield => {
+            *next += 1;
+            if drop.contains(&(*next - 1)) {
+                table.number(0)
+            } else {
+                expr.clone()
+            }
+        }
+        Expr::Assign(var, e) => {
+            let e = drop_yields(table, e, drop, next);
+            table.assign(var.clone(), e)
+        }
+        Expr::Not(e) => {
+            let e = drop_yields(table, e, drop, next);
+            table.not(e)
+        }
+        Expr::Return(e) => {
+            let e = drop_yields(table, e, drop, next);
+            table.return_expr(e)
+        }
+        Expr::Equal(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.equal(a, b)
+        }
+        Expr::Add(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.add(a, b)
+        }
+        Expr::Subtract(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.subtract(a, b)
+        }
+        Expr::Sequence(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.sequence(a, b)
+        }
+        Expr::And(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.and(a, b)
+        }
+        Expr::Or(a, b) => {
+            let (a, b) = (
+                drop_yields(table, a, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.or(a, b)
+        }
+        Expr::While(c, b) => {
+            let (c, b) = (
+                drop_yields(table, c, drop, next),
+                drop_yields(table, b, drop, next),
+            );
+            table.while_expr(c, b)
+        }
+        Expr::If(c, t, e) => {
+            let c = drop_yields(table, c, drop, next);
+            let t = drop_yields(table, t, drop, next);
+            let e = drop_yields(table, e, drop, next);
+            table.if_expr(c, t, e)
+        }
+        Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => expr.clone(),
+    }
+}
+
+/// `while (REPAIR_LOCK == 1) { yield }; REPAIR_LOCK := 1; repair_result := body;
+/// REPAIR_LOCK := 0; repair_result`. The loop has no yield after its last test, so
+/// testing and taking the lock happen in one step. Early returns in `body` release the
+/// lock too.
+fn with_lock(table: &mut ExprHc, body: Hc<Expr>) -> Hc<Expr> {
+    // `return e` becomes `return (repair_result := e; REPAIR_LOCK := 0; repair_result)`
+    let body = expr_to_ns::map_returns(table, &body, &mut |table, e| {
+        let zero = table.number(0);
+        let save = table.assign(RESULT_VAR.to_string(), e);
+        let release = table.assign(LOCK_VAR.to_string(), zero);
+        let result = table.variable(RESULT_VAR.to_string());
+        let value = table.sequence(release, result);
+        table.sequence(save, value)
+    });
+    let lock = table.variable(LOCK_VAR.to_string());
+    let (zero, one) = (table.number(0), table.number(1));
+    let held = table.equal(lock, one.clone());
+    let wait_body = table.yield_expr();
+    let wait = table.while_expr(held, wait_body);
+    let acquire = table.assign(LOCK_VAR.to_string(), one);
+    let run = table.assign(RESULT_VAR.to_string(), body);
+    let release = table.assign(LOCK_VAR.to_string(), zero);
+    let result = table.variable(RESULT_VAR.to_string());
+    let rest = table.sequence(release, result);
+    let rest = table.sequence(run, rest);
+    let rest = table.sequence(acquire, rest);
+    table.sequence(wait, rest)
+}
+
+/// `program` with `fixes` applied
+pub fn apply_fixes(table: &mut ExprHc, program: &Program, fixes: &[Fix]) -> Program {
+    let mut fixed = program.clone();
+    for request in &mut fixed.requests {
+        let drop: Vec<usize> = fixes
+            .iter()
+            .filter_map(|fix| match fix {
+                Fix::Atomic {
+                    request: name,
+                    yield_index,
+                } if *name == request.name => Some(*yield_index),
+                _ => None,
+            })
+            .collect();
+        if !drop.is_empty() {
+            request.body = drop_yields(table, &request.body, &drop, &mut 0);
+        }
+        let locked = fixes
+            .iter()
+            .any(|fix| matches!(fix, Fix::Lock { request: name, .. } if *name == request.name));
+        if locked {
+            request.body = with_lock(table, request.body.clone());
+        }
+    }
+    fixed
+}
+
+/// Sets of up to `max_fixes` fixes, cheapest first (ties: fewer fixes first)
+fn fix_sets(fixes: &[Fix], max_fixes: usize) -> Vec<Vec<Fix>> {
+    let mut sets: Vec<Vec<Fix>> = vec![vec![]];
+    let mut all = Vec::new();
+    for _ in 0..max_fixes {
+        let mut larger = Vec::new();
+        for set in &sets {
+            // Extend with fixes after the last one, so every set occurs once
+            let start = set
+                .last()
+                .and_then(|last| fixes.iter().position(|f| f == last))
+                .map_or(0, |i| i + 1);
+            for fix in &fixes[start..] {
+                let mut extended = set.clone();
+                extended.push(fix.clone());
+                larger.push(extended);
+            }
+        }
+        all.extend(larger.iter().cloned());
+        sets = larger;
+    }
+    // Stable, so sets of equal cost and size stay in generation order
+    all.sort_by_key(|set| (set.iter().map(Fix::cost).sum::<usize>(), set.len()));
+    all
+}
+
+/// Try sets of `fixes` cheapest first. `apply` builds the program of a set, and
+/// `check` decides it; verdicts are cached by program. Stops at the first
+/// serializable program, or after `max_checks` distinct programs.
+pub fn cheapest_fix<K: Clone + Eq + Hash>(
+    fixes: &[Fix],
+    max_fixes: usize,
+    max_checks: usize,
+    mut apply: impl FnMut(&[Fix]) -> K,
+    mut check: impl FnMut(&K) -> Verdict,
+) -> Option<Repair<K>> {
+    let mut cache: HashMap<K, Verdict> = HashMap::default();
+    for set in fix_sets(fixes, max_fixes) {
+        let program = apply(&set);
+        let verdict = match cache.get(&program) {
+            Some(verdict) => *verdict,
+            None => {
+                if cache.len() >= max_checks {
+                    return None;
+                }
+                let verdict = check(&program);
+                cache.insert(program.clone(), verdict);
+                verdict
+            }
+        };
+        if verdict == Verdict::Serializable {
+            return Some(Repair {
+                cost: set.iter().map(Fix::cost).sum(),
+                fixes: set,
+                program,
+                checks: cache.len(),
+            });
+        }
+    }
+    None
+}
+
+/// Search for a fix of the non-serializable program in `path`, print it, and save the
+/// fixed request bodies to `<out_dir>/repair/suggested_fix.txt`. Checks write to
+/// `<out_dir>/repair/check_<i>`.
+#[cfg(feature = "isl")]
+pub fn suggest_fix(path: &Path, out_dir: &str) -> Option<Repair<Program>> {
+    let decision: NSDecision<Global, LocalExpr, ExprRequest, i64> =
+        NSDecision::load_from_file(crate::ns_decision::certificate_path(out_dir)).ok()?;
+    let NSDecision::NotSerializable { trace } = &decision else {
+        return None;
+    };
+
+    println!();
+    println!("{}", "Searching for a fix (experimental)...".blue().bold());
+    let mut table = ExprHc::new();
+    let program = match crate::frontend::find(path)
+        .and_then(|front_end| front_end.load_program(path, &mut table))
+    {
+        Some(Ok(program)) => program,
+        Some(Err(err)) => {
+            eprintln!("Failed to load the program: {}", err);
+            return None;
+        }
+        None => {
+            println!("  --suggest-fix needs a program with request bodies, like a .ser file");
+            return None;
+        }
+    };
+
+    let requests: Vec<String> = crate::diagnosis::counterexample_requests(&trace.steps)
+        .into_iter()
+        .map(|request| request.name)
+        .collect();
+    let fixes = candidate_fixes(&program, &requests);
+    let mut checks = 0;
+    let repair = cheapest_fix(
+        &fixes,
+        MAX_FIXES,
+        MAX_CHECKS,
+        |set| apply_fixes(&mut table, &program, set),
+        |fixed| {
+            checks += 1;
+            let sub_dir = crate::utils::file::join(out_dir, &format!("repair/check_{}", checks));
+            if let Err(err) = crate::utils::file::ensure_dir_exists(&sub_dir) {
+                eprintln!("Failed to create {}: {}", sub_dir, err);
+                return Verdict::Inconclusive;
+            }
+            let mut check_table = ExprHc::new();
+            let ns = expr_to_ns::program_to_ns(&mut check_table, fixed);
+            match ns.create_certificate(&sub_dir) {
+                NSDecision::Serializable { .. } => Verdict::Serializable,
+                NSDecision::NotSerializable { .. } => Verdict::NotSerializable,
+                NSDecision::Timeout { .. } | NSDecision::Unknown { .. } => Verdict::Inconclusive,
+            }
+        },
+    );
+
+    println!();
+    let Some(repair) = repair else {
+        println!(
+            "{} ({} programs checked, up to {} fixes each)",
+            "No fix found".yellow().bold(),
+            checks,
+            MAX_FIXES
+        );
+        return None;
+    };
+    println!(
+        "{} (cost {}, {} programs checked):",
+        "Suggested fix".green().bold(),
+        repair.cost,
+        repair.checks
+    );
+    let mut content = String::new();
+    for fix in &repair.fixes {
+        println!("  - {}", fix);
+        content.push_str(&format!("// {}\n", fix));
+    }
+    for (before, after) in program.requests.iter().zip(&repair.program.requests) {
+        if before.body != after.body {
+            println!("  {} {{ {} }}", after.name.cyan(), after.body);
+            for line in after.doc.iter().flat_map(|doc| doc.lines()) {
+                content.push_str(&format!("/// {}\n", line));
+            }
+            content.push_str(&format!(
+                "request {} {{\n  {}\n}}\n",
+                after.name, after.body
+            ));
+        }
+    }
+    let file = crate::utils::file::join(out_dir, "repair/suggested_fix.txt");
+    match crate::utils::file::safe_write_file(&file, &content) {
+        Ok(_) => println!("- {}", file.green()),
+        Err(err) => eprintln!("Failed to write {}: {}", file, err),
+    }
+    Some(repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_apply_fixes() {
+        let mut table = ExprHc::new();
+        let program = parse_program(
+            "request inc { x := X; yield; X := x + 1; while (X == 5) { yield }; x }",
+            &mut table,
+        )
+        .unwrap();
+        let fixes = candidate_fixes(&program, &["inc".to_string()]);
+        assert_eq!(
+            fixes,
+            vec![
+                Fix::Atomic {
+                    request: "inc".to_string(),
+                    yield_index: 0
+                },
+                Fix::Lock {
+                    request: "inc".to_string(),
+                    yields: 2
+                },
+            ]
+        );
+        assert!(candidate_fixes(&program, &[]).is_empty());
+
+        let atomic = apply_fixes(&mut table, &program, &fixes[..1]);
+        assert_eq!(
+            atomic.requests[0].body.to_string(),
+            "x := X; X := x + 1; while(X == 5){ yield }; x"
+        );
+        let locked = apply_fixes(&mut table, &program, &fixes[1..]);
+        assert_eq!(
+            locked.requests[0].body.to_string(),
+            "while(REPAIR_LOCK == 1){ yield }; REPAIR_LOCK := 1; repair_result := x := X; yield; \
+             X := x + 1; while(X == 5){ yield }; x; REPAIR_LOCK := 0; repair_result"
+        );
+    }
+
+    #[test]
+    fn test_cheapest_fix() {
+        let atomic = |i| Fix::Atomic {
+            request: "a".to_string(),
+            yield_index: i,
+        };
+        let lock = Fix::Lock {
+            request: "a".to_string(),
+            yields: 2,
+        };
+        let fixes = vec![atomic(0), atomic(1), lock.clone()];
+
+        // Serializable once both yields are gone, or under the lock
+        let repair = cheapest_fix(
+            &fixes,
+            3,
+            100,
+            |set| set.to_vec(),
+            |set| {
+                if set.contains(&lock) || set.len() == 2 {
+                    Verdict::Serializable
+                } else {
+                    Verdict::NotSerializable
+                }
+            },
+        )
+        .unwrap();
+        // Cost 2 either way; the lock is a single fix, so it is tried first
+        assert_eq!(repair.fixes, vec![lock]);
+        assert_eq!(repair.cost, 2);
+        assert_eq!(repair.checks, 3);
+
+        // Programs that come out the same are checked once: here all sets of two or
+        // more fixes give the same program
+        let mut checks = 0;
+        let none = cheapest_fix(
+            &fixes,
+            3,
+            100,
+            |set| set.len().min(2),
+            |_| {
+                checks += 1;
+                Verdict::NotSerializable
+            },
+        );
+        assert!(none.is_none());
+        assert_eq!(checks, 2);
+    }
+}
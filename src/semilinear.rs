@@ -6,18 +6,76 @@ pub use std::hash::Hash;
 
 use crate::kleene::Kleene;
 
-use std::sync::atomic::{AtomicBool, Ordering};
+pub fn set_remove_redundant(on: bool) {
+    crate::options::update(|options| options.remove_redundant = on);
+}
 
-pub static REMOVE_REDUNDANT: AtomicBool = AtomicBool::new(true);
+pub fn remove_redundant_enabled() -> bool {
+    crate::options::get(|options| options.remove_redundant)
+}
 
-pub fn set_remove_redundant(on: bool) {
-    REMOVE_REDUNDANT.store(on, Ordering::SeqCst);
+/// How much `plus`, `times` and `star` simplify the sets they generate.
+///
+/// All strategies denote the same set; they differ only in how many (redundant)
+/// components and periods the result carries, which is what blows up in `star`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemilinearStrategy {
+    /// Plain constructions: no merging of components, and `star` expands every
+    /// subset of components.
+    Exact,
+    /// Merge components, and in `star` pull out components that are periods
+    /// ((p* + R)* = p* R*) or bases without periods ((b + R)* = b* R*) before
+    /// expanding the rest. Periods already pulled out are dropped from the
+    /// remaining components.
+    GenerateLess,
+    /// `GenerateLess`, and additionally drop every period of a remaining component
+    /// that is a nonnegative combination of the pulled out periods, not only the
+    /// ones equal to one of them.
+    Aggressive,
+}
+
+impl SemilinearStrategy {
+    /// Parse a strategy name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "exact" => Some(SemilinearStrategy::Exact),
+            "generate-less" => Some(SemilinearStrategy::GenerateLess),
+            "aggressive" => Some(SemilinearStrategy::Aggressive),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SemilinearStrategy::Exact => "exact",
+            SemilinearStrategy::GenerateLess => "generate-less",
+            SemilinearStrategy::Aggressive => "aggressive",
+        }
+    }
+
+    /// Whether results are simplified at all
+    pub fn simplifies(&self) -> bool {
+        *self != SemilinearStrategy::Exact
+    }
 }
 
-pub static GENERATE_LESS: AtomicBool = AtomicBool::new(true);
+/// Set the strategy used by the `Kleene` operations (called from `main.rs`)
+pub fn set_semilinear_strategy(strategy: SemilinearStrategy) {
+    crate::options::update(|options| options.semilinear_strategy = strategy);
+}
 
+/// Get the strategy used by the `Kleene` operations
+pub fn semilinear_strategy() -> SemilinearStrategy {
+    crate::options::get(|options| options.semilinear_strategy)
+}
+
+/// Switch between `GenerateLess` (on) and `Exact` (off)
 pub fn set_generate_less(on: bool) {
-    GENERATE_LESS.store(on, Ordering::SeqCst);
+    set_semilinear_strategy(if on {
+        SemilinearStrategy::GenerateLess
+    } else {
+        SemilinearStrategy::Exact
+    });
 }
 
 /// A sparse vector in d-dimensional nonnegative integer space.
@@ -117,12 +175,30 @@ impl<K: Eq + Hash + Clone + Ord> SparseVector<K> {
         SparseVector { values: new_map }
     }
 
+    /// The nonzero entries, sorted by key
+    pub fn entries(&self) -> Vec<(K, usize)> {
+        let mut entries: Vec<_> = self
+            .values
+            .iter()
+            .filter(|(_, n)| **n > 0)
+            .map(|(k, n)| (k.clone(), *n))
+            .collect();
+        entries.sort();
+        entries
+    }
+
     /// Check if the vector is zero
     pub fn is_zero(&self) -> bool {
         self.values.is_empty()
     }
 }
 
+impl<K: Eq + Hash + Clone + Ord> Default for SparseVector<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LinearSet<K: Eq + Hash + Clone + Ord> {
     pub base: SparseVector<K>,         // u0: the base vector
@@ -165,6 +241,39 @@ impl<K: Eq + Hash + Clone + Ord> LinearSet<K> {
     }
 }
 
+/// A linear set as sorted lists of (key, count) entries, the structured form used in
+/// reports and saved files
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LinearSetTerms<K> {
+    pub base: Vec<(K, usize)>,
+    pub periods: Vec<Vec<(K, usize)>>,
+}
+
+impl<K: Eq + Hash + Clone + Ord> From<&LinearSet<K>> for LinearSetTerms<K> {
+    fn from(linear_set: &LinearSet<K>) -> Self {
+        LinearSetTerms {
+            base: linear_set.base.entries(),
+            periods: linear_set.periods.iter().map(SparseVector::entries).collect(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone + Ord> From<LinearSetTerms<K>> for LinearSet<K> {
+    fn from(terms: LinearSetTerms<K>) -> Self {
+        let vector = |entries: Vec<(K, usize)>| {
+            let mut v = SparseVector::new();
+            for (k, n) in entries {
+                v.set(k, n);
+            }
+            v
+        };
+        LinearSet {
+            base: vector(terms.base),
+            periods: terms.periods.into_iter().map(vector).collect(),
+        }
+    }
+}
+
 /// Display a linear set as a string of the form "base(period1 + period2 + ...)*"
 impl<K: Eq + Hash + Clone + Ord + std::fmt::Display> std::fmt::Display for LinearSet<K> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -215,18 +324,312 @@ impl<K: Eq + Hash + Clone + Ord + std::fmt::Display> std::fmt::Display for Semil
     }
 }
 
+/// Layout of `SemilinearSet::pretty`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemilinearFormat {
+    /// One linear set per line, `base + k1·{a/b} + k2·{...}`, with aligned columns
+    Text,
+    /// A LaTeX `tabular` with the base and the periods in columns
+    Latex,
+    /// A Markdown table with the base and the periods in columns
+    Markdown,
+}
+
+impl SemilinearFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(SemilinearFormat::Text),
+            "latex" => Some(SemilinearFormat::Latex),
+            "markdown" => Some(SemilinearFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SemilinearFormat::Text => "text",
+            SemilinearFormat::Latex => "latex",
+            SemilinearFormat::Markdown => "markdown",
+        }
+    }
+
+    /// Extension of a file in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SemilinearFormat::Text => "txt",
+            SemilinearFormat::Latex => "tex",
+            SemilinearFormat::Markdown => "md",
+        }
+    }
+}
+
+/// Also write the semilinear set as a table in this format (called from `main.rs`);
+/// `Text` writes only `semilinear.txt`
+pub fn set_semilinear_format(format: SemilinearFormat) {
+    crate::options::update(|options| options.semilinear_format = format);
+}
+
+pub fn semilinear_format() -> SemilinearFormat {
+    crate::options::get(|options| options.semilinear_format)
+}
+
+/// Display adapter of `SemilinearSet::pretty`
+pub struct Pretty<'a, K: Eq + Hash + Clone + Ord> {
+    set: &'a SemilinearSet<K>,
+    format: SemilinearFormat,
+}
+
+impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
+    /// The set laid out in `format`, with one row per linear set: its base and its
+    /// periods, each a vector of named unit vectors like `{a/b, 2·c/d}`
+    pub fn pretty(&self, format: SemilinearFormat) -> Pretty<'_, K> {
+        Pretty { set: self, format }
+    }
+}
+
+impl<K: Eq + Hash + Clone + Ord + std::fmt::Display> Pretty<'_, K> {
+    /// The cells of each row: the base, then one cell per period
+    fn rows(&self, vector: impl Fn(&SparseVector<K>) -> String) -> Vec<Vec<String>> {
+        self.set
+            .components
+            .iter()
+            .map(|linear_set| {
+                std::iter::once(&linear_set.base)
+                    .chain(&linear_set.periods)
+                    .map(&vector)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn fmt_text(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rows = self.rows(|v| vector_terms(v, "·", "{", "}", |k| k.to_string()));
+        for row in &mut rows {
+            for (i, cell) in row.iter_mut().enumerate().skip(1) {
+                *cell = format!("+ k{}·{}", i, cell);
+            }
+        }
+        let widths = column_widths(&rows);
+        for row in &rows {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| pad(cell, *width))
+                .collect();
+            writeln!(f, "{}", cells.join(" ").trim_end())?;
+        }
+        Ok(())
+    }
+
+    fn fmt_markdown(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows =
+            self.rows(|v| vector_terms(v, "·", "{", "}", |k| k.to_string().replace('|', "\\|")));
+        let header = header(&rows);
+        let columns = header.len();
+        let mut table = vec![header];
+        table.extend(rows.into_iter().map(|mut row| {
+            row.resize(columns, String::new());
+            row
+        }));
+        let widths = column_widths(&table);
+        for (i, row) in table.iter().enumerate() {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| pad(cell, *width))
+                .collect();
+            writeln!(f, "| {} |", cells.join(" | "))?;
+            if i == 0 {
+                let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+                writeln!(f, "| {} |", rule.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_latex(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows = self.rows(|v| {
+            let terms = vector_terms(v, " \\cdot ", "\\{", "\\}", |k| {
+                format!(
+                    "\\text{{{}}}",
+                    crate::ns_decision::latex_escape(&k.to_string())
+                )
+            });
+            format!("${}$", terms)
+        });
+        let header: Vec<String> = header(&rows)
+            .into_iter()
+            .map(|cell| match cell.strip_prefix('k') {
+                Some(i) => format!("$k_{{{}}}$", i),
+                None => cell,
+            })
+            .collect();
+        writeln!(
+            f,
+            "\\begin{{tabular}}{{l|{}}}",
+            "l".repeat(header.len() - 1)
+        )?;
+        writeln!(f, "{} \\\\", header.join(" & "))?;
+        writeln!(f, "\\hline")?;
+        for mut row in rows {
+            row.resize(header.len(), String::new());
+            writeln!(f, "{} \\\\", row.join(" & "))?;
+        }
+        writeln!(f, "\\end{{tabular}}")
+    }
+}
+
+impl<K: Eq + Hash + Clone + Ord + std::fmt::Display> std::fmt::Display for Pretty<'_, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.format {
+            SemilinearFormat::Text => self.fmt_text(f),
+            SemilinearFormat::Latex => self.fmt_latex(f),
+            SemilinearFormat::Markdown => self.fmt_markdown(f),
+        }
+    }
+}
+
+/// A vector as `{a, 2·b}`, `0` if it is zero
+fn vector_terms<K: Eq + Hash + Clone + Ord>(
+    vector: &SparseVector<K>,
+    times: &str,
+    open: &str,
+    close: &str,
+    key: impl Fn(&K) -> String,
+) -> String {
+    if vector.is_zero() {
+        return "0".to_string();
+    }
+    let terms: Vec<String> = vector
+        .entries()
+        .iter()
+        .map(|(k, n)| match n {
+            1 => key(k),
+            _ => format!("{}{}{}", n, times, key(k)),
+        })
+        .collect();
+    format!("{}{}{}", open, terms.join(", "), close)
+}
+
+/// Column titles of a table of `rows`: the base and `k1`, `k2`, ... for the periods
+fn header(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(1);
+    std::iter::once("base".to_string())
+        .chain((1..columns).map(|i| format!("k{}", i)))
+        .collect()
+}
+
+/// The width of each column, in characters
+fn column_widths(rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = width.max(*w),
+                None => widths.push(width),
+            }
+        }
+    }
+    widths
+}
+
+/// `cell` padded with spaces to `width` characters
+fn pad(cell: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        cell,
+        " ".repeat(width.saturating_sub(cell.chars().count()))
+    )
+}
+
+/// Why a linear set dropped by `SemilinearSet::minimize` adds nothing: it is contained
+/// in a component that was kept. With `C` that component, the removed base is
+/// `C.base + Σ base_coefficients[i] * C.periods[i]`, and its j-th period is
+/// `Σ period_coefficients[j][i] * C.periods[i]`, so checking it is plain arithmetic.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ContainmentWitness<K> {
+    pub removed: LinearSetTerms<K>,
+    /// Index of the covering component in the minimized set
+    pub covered_by: usize,
+    pub base_coefficients: Vec<usize>,
+    pub period_coefficients: Vec<Vec<usize>>,
+}
+
+impl<K: Eq + Hash + Clone + Ord> ContainmentWitness<K> {
+    /// Check that the witness shows that `removed` is contained in `cover`
+    pub fn check(&self, cover: &LinearSet<K>) -> Result<(), String> {
+        let removed = LinearSet::from(self.removed.clone());
+        if self.base_coefficients.len() != cover.periods.len()
+            || self.period_coefficients.len() != removed.periods.len()
+            || self
+                .period_coefficients
+                .iter()
+                .any(|c| c.len() != cover.periods.len())
+        {
+            return Err("Coefficients do not match the number of periods".to_string());
+        }
+        if combination(&cover.base, &cover.periods, &self.base_coefficients) != removed.base {
+            return Err("Base is not covered".to_string());
+        }
+        for (j, (period, coefficients)) in removed
+            .periods
+            .iter()
+            .zip(&self.period_coefficients)
+            .enumerate()
+        {
+            if combination(&SparseVector::new(), &cover.periods, coefficients) != *period {
+                return Err(format!("Period {} is not covered", j));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A semilinear set without components that are contained in others, with the reason
+/// why each removed component was redundant
+#[derive(Debug, Clone)]
+pub struct Minimized<K: Eq + Hash + Clone + Ord> {
+    pub set: SemilinearSet<K>,
+    pub witnesses: Vec<ContainmentWitness<K>>,
+}
+
+impl<K: Eq + Hash + Clone + Ord> Minimized<K> {
+    /// Check every witness against the component it points to, so that the minimized
+    /// set is known to contain the original one (the converse holds by construction)
+    pub fn check(&self) -> Result<(), String> {
+        for (i, witness) in self.witnesses.iter().enumerate() {
+            let cover = self.set.components.get(witness.covered_by).ok_or_else(|| {
+                format!(
+                    "Witness {} points to missing component {}",
+                    i, witness.covered_by
+                )
+            })?;
+            witness
+                .check(cover)
+                .map_err(|err| format!("Witness {}: {}", i, err))?;
+        }
+        Ok(())
+    }
+}
+
 impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
+    /// The linear sets of this set in structured form
+    pub fn decomposition(&self) -> Vec<LinearSetTerms<K>> {
+        self.components.iter().map(LinearSetTerms::from).collect()
+    }
+
     /// Create a new semilinear set from a list of LinearSet components.
     pub fn new(mut components: Vec<LinearSet<K>>) -> Self {
         // Filter out duplicate period vectors
-        if REMOVE_REDUNDANT.load(Ordering::SeqCst) {
+        if remove_redundant_enabled() {
             for lin in &mut components {
                 lin.dedup_periods();
             }
         }
 
         // Try merging any of the new_components into another
-        if REMOVE_REDUNDANT.load(Ordering::SeqCst) {
+        if remove_redundant_enabled() {
             'fixpoint: loop {
                 for i in 0..components.len() {
                     for j in i + 1..components.len() {
@@ -244,6 +647,51 @@ impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
         SemilinearSet { components }
     }
 
+    /// Drop the components that are contained in another component, the standalone
+    /// version of what `set_remove_redundant` does while sets are built. Unlike that
+    /// optimization it keeps a `ContainmentWitness` for every dropped component, so
+    /// consumers of a certificate can check the step with `Minimized::check`.
+    pub fn minimize(&self) -> Minimized<K> {
+        let components = &self.components;
+
+        // A component is dropped if a component that is still there contains it. Of
+        // two equal components, the first goes.
+        let mut kept = vec![true; components.len()];
+        for i in 0..components.len() {
+            let covered = (0..components.len())
+                .any(|j| j != i && kept[j] && linear_set_subset(&components[i], &components[j]));
+            kept[i] = !covered;
+        }
+
+        // Containment is transitive, so a kept component covers every dropped one
+        let set = SemilinearSet {
+            components: (0..components.len())
+                .filter(|&i| kept[i])
+                .map(|i| components[i].clone())
+                .collect(),
+        };
+        let witnesses = (0..components.len())
+            .filter(|&i| !kept[i])
+            .map(|i| {
+                let (covered_by, (base_coefficients, period_coefficients)) = set
+                    .components
+                    .iter()
+                    .enumerate()
+                    .find_map(|(j, cover)| {
+                        containment_coefficients(&components[i], cover).map(|c| (j, c))
+                    })
+                    .expect("a dropped component is contained in a kept one");
+                ContainmentWitness {
+                    removed: LinearSetTerms::from(&components[i]),
+                    covered_by,
+                    base_coefficients,
+                    period_coefficients,
+                }
+            })
+            .collect();
+        Minimized { set, witnesses }
+    }
+
     /// Check if the semilinear set is empty.
     fn is_empty(&self) -> bool {
         self.components.is_empty()
@@ -288,6 +736,13 @@ impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
         SemilinearSet::new(vec![LinearSet { base, periods }])
     }
 
+    /// Check whether a vector is a member of the semilinear set.
+    pub fn contains(&self, vec: &SparseVector<K>) -> bool {
+        self.components
+            .iter()
+            .any(|lin| vector_in_linear_set(vec, lin))
+    }
+
     /// Run an operation on all keys mentioned in the semilinear set
     pub fn for_each_key(&self, mut f: impl for<'a> FnMut(&'a K)) {
         for c in &self.components {
@@ -307,15 +762,211 @@ impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
     }
 }
 
+impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
+    /// Complement of the set with respect to `ℕ^alphabet`.
+    ///
+    /// Semilinear sets are closed under complement, but we only compute it directly
+    /// when every component is a "box" (all periods are unit vectors over the
+    /// alphabet). Returns `None` outside that fragment, or when the result would be
+    /// too large, so that the caller can fall back to the Presburger (ISL) route.
+    pub fn complement(&self, alphabet: &[K]) -> Option<Self> {
+        Self::universe(alphabet.to_vec()).difference(self, alphabet)
+    }
+
+    /// Difference `self \ other`, with both sets viewed over `ℕ^alphabet`.
+    /// Same fragment restrictions as `complement`.
+    pub fn difference(&self, other: &Self, alphabet: &[K]) -> Option<Self> {
+        let left = self
+            .components
+            .iter()
+            .map(|lin| linear_set_to_cuboid(lin, alphabet))
+            .collect::<Option<Vec<_>>>()?;
+        let right = other
+            .components
+            .iter()
+            .map(|lin| linear_set_to_cuboid(lin, alphabet))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut result = left;
+        for cuboid in &right {
+            let complement = cuboid_complement(cuboid);
+            let mut next: Vec<Cuboid> = Vec::new();
+            for a in &result {
+                for b in &complement {
+                    if let Some(c) = cuboid_intersection(a, b) {
+                        next.push(c);
+                    }
+                }
+            }
+            result = remove_subsumed_cuboids(next);
+            if result.len() > MAX_CUBOIDS {
+                return None;
+            }
+        }
+
+        let mut components = Vec::new();
+        for cuboid in &result {
+            cuboid_to_linear_sets(cuboid, alphabet, &mut components);
+            if components.len() > MAX_CUBOIDS {
+                return None;
+            }
+        }
+        Some(SemilinearSet { components })
+    }
+}
+
+/// A product of intervals `[lo, hi]` (or `[lo, ∞)`), one per alphabet position
+type Cuboid = Vec<(usize, Option<usize>)>;
+
+/// Bail out of the direct difference computation beyond this many components
+const MAX_CUBOIDS: usize = 10_000;
+
+fn linear_set_to_cuboid<K: Eq + Hash + Clone + Ord>(
+    lin: &LinearSet<K>,
+    alphabet: &[K],
+) -> Option<Cuboid> {
+    let mut unbounded = vec![false; alphabet.len()];
+    for period in &lin.periods {
+        if period.values.len() != 1 {
+            return None;
+        }
+        let (key, value) = period.values.iter().next().unwrap();
+        let idx = alphabet.iter().position(|k| k == key)?;
+        if *value != 1 {
+            return None;
+        }
+        unbounded[idx] = true;
+    }
+    if lin.base.values.keys().any(|k| !alphabet.contains(k)) {
+        return None;
+    }
+    Some(
+        alphabet
+            .iter()
+            .zip(unbounded)
+            .map(|(k, unbounded)| {
+                let lo = lin.base.get(k);
+                (lo, if unbounded { None } else { Some(lo) })
+            })
+            .collect(),
+    )
+}
+
+/// Complement of a single cuboid, as a (possibly overlapping) union of cuboids
+fn cuboid_complement(cuboid: &Cuboid) -> Vec<Cuboid> {
+    let free: Cuboid = vec![(0, None); cuboid.len()];
+    let mut result = Vec::new();
+    for (i, (lo, hi)) in cuboid.iter().enumerate() {
+        if *lo > 0 {
+            let mut below = free.clone();
+            below[i] = (0, Some(lo - 1));
+            result.push(below);
+        }
+        if let Some(hi) = hi {
+            let mut above = free.clone();
+            above[i] = (hi + 1, None);
+            result.push(above);
+        }
+    }
+    result
+}
+
+fn cuboid_intersection(a: &Cuboid, b: &Cuboid) -> Option<Cuboid> {
+    a.iter()
+        .zip(b)
+        .map(|((lo1, hi1), (lo2, hi2))| {
+            let lo = *lo1.max(lo2);
+            let hi = match (hi1, hi2) {
+                (Some(h1), Some(h2)) => Some(*h1.min(h2)),
+                (h, None) | (None, h) => *h,
+            };
+            match hi {
+                Some(hi) if hi < lo => None,
+                _ => Some((lo, hi)),
+            }
+        })
+        .collect()
+}
+
+fn cuboid_subset(a: &Cuboid, b: &Cuboid) -> bool {
+    a.iter().zip(b).all(|((lo1, hi1), (lo2, hi2))| {
+        lo1 >= lo2
+            && match (hi1, hi2) {
+                (_, None) => true,
+                (Some(h1), Some(h2)) => h1 <= h2,
+                (None, Some(_)) => false,
+            }
+    })
+}
+
+fn remove_subsumed_cuboids(cuboids: Vec<Cuboid>) -> Vec<Cuboid> {
+    let mut kept: Vec<Cuboid> = Vec::new();
+    for c in cuboids {
+        if kept.iter().any(|k| cuboid_subset(&c, k)) {
+            continue;
+        }
+        kept.retain(|k| !cuboid_subset(k, &c));
+        kept.push(c);
+    }
+    kept
+}
+
+/// Expand a cuboid into linear sets; bounded intervals are enumerated value by value
+fn cuboid_to_linear_sets<K: Eq + Hash + Clone + Ord>(
+    cuboid: &Cuboid,
+    alphabet: &[K],
+    out: &mut Vec<LinearSet<K>>,
+) {
+    let mut partial = vec![LinearSet {
+        base: SparseVector::new(),
+        periods: vec![],
+    }];
+    for (k, (lo, hi)) in alphabet.iter().zip(cuboid) {
+        match hi {
+            None => {
+                for lin in &mut partial {
+                    lin.base.set(k.clone(), *lo);
+                    lin.periods.push(SparseVector::unit(k.clone()));
+                }
+            }
+            Some(hi) => {
+                let mut next = Vec::new();
+                for lin in &partial {
+                    for value in *lo..=*hi {
+                        let mut lin = lin.clone();
+                        lin.base.set(k.clone(), value);
+                        next.push(lin);
+                    }
+                }
+                partial = next;
+            }
+        }
+        if partial.len() > MAX_CUBOIDS {
+            break;
+        }
+    }
+    out.extend(partial);
+}
+
 /// Returns true if `target` can be expressed as a nonnegative integer combination
 /// of the vectors in `periods`.
 pub fn is_nonnegative_combination<K: Eq + Hash + Clone + Ord>(
     target: &SparseVector<K>,
     periods: &[SparseVector<K>],
 ) -> bool {
+    nonnegative_combination(target, periods).is_some()
+}
+
+/// The coefficients of `target` as a nonnegative integer combination of the vectors
+/// in `periods`, if there is one
+pub fn nonnegative_combination<K: Eq + Hash + Clone + Ord>(
+    target: &SparseVector<K>,
+    periods: &[SparseVector<K>],
+) -> Option<Vec<usize>> {
     // We'll do a DFS with memoization.  The memo stores `(current_vector, index_in_periods)`.
     let mut memo = HashSet::default();
-    dfs(target, 0, periods, &mut memo)
+    let mut coefficients = vec![0; periods.len()];
+    dfs(target, 0, periods, &mut memo, &mut coefficients).then_some(coefficients)
 }
 
 fn dfs<K: Eq + Hash + Clone + Ord>(
@@ -323,9 +974,12 @@ fn dfs<K: Eq + Hash + Clone + Ord>(
     idx: usize,
     periods: &[SparseVector<K>],
     memo: &mut HashSet<(SparseVector<K>, usize)>,
+    coefficients: &mut [usize],
 ) -> bool {
-    // If our target has become the zero vector, we are done.
+    // If our target has become the zero vector, we are done. Later periods are unused,
+    // whatever failed branches left in their coefficients.
     if target.values.is_empty() {
+        coefficients[idx..].fill(0);
         return true;
     }
     // If we've run out of period vectors, and we still haven't zeroed out `target`, fail.
@@ -354,13 +1008,18 @@ fn dfs<K: Eq + Hash + Clone + Ord>(
             }
         }
     }
+    // A zero period (e.g. the base of `a*` in an exact star) never changes the target
+    if max_coeff == usize::MAX {
+        max_coeff = 0;
+    }
 
     // We try all coefficients c = 0..=max_coeff.
     // c=0 => skip p entirely, check next.
     for c in 0..=max_coeff {
+        coefficients[idx] = c;
         if c == 0 {
             // Not using p at all
-            if dfs(target, idx + 1, periods, memo) {
+            if dfs(target, idx + 1, periods, memo, coefficients) {
                 return true;
             }
         } else {
@@ -375,7 +1034,7 @@ fn dfs<K: Eq + Hash + Clone + Ord>(
                     new_target.values.insert(k.clone(), new_val);
                 }
             }
-            if dfs(&new_target, idx + 1, periods, memo) {
+            if dfs(&new_target, idx + 1, periods, memo, coefficients) {
                 return true;
             }
         }
@@ -408,6 +1067,15 @@ fn sub_vectors<K: Eq + Hash + Clone + Ord>(
 /// Check if linear_set1 is contained in linear_set2
 /// i.e. L1 ⊆ L2
 pub fn linear_set_subset<K: Eq + Hash + Clone + Ord>(l1: &LinearSet<K>, l2: &LinearSet<K>) -> bool {
+    containment_coefficients(l1, l2).is_some()
+}
+
+/// Why L1 ⊆ L2, as coefficients over the periods of L2: those of base1 - base2, and
+/// those of every period of L1. `None` if the check of `linear_set_subset` fails.
+fn containment_coefficients<K: Eq + Hash + Clone + Ord>(
+    l1: &LinearSet<K>,
+    l2: &LinearSet<K>,
+) -> Option<(Vec<usize>, Vec<Vec<usize>>)> {
     // 1. Check if (base1 - base2) is in submonoid(periods2).
     //    We do "base1 - base2" in a nonnegative sense, so if base2 has bigger coords in some dimension,
     //    we can’t do it at all => subset is false.  But sometimes you might want to do "base2 - base1".
@@ -417,21 +1085,33 @@ pub fn linear_set_subset<K: Eq + Hash + Clone + Ord>(l1: &LinearSet<K>, l2: &Lin
     //    But if base1 < base2 in some dimension, you’d never get base1 from base2 by adding periods.
     //    So the immediate check for L1 ⊆ L2 is: base1 - base2 must be nonnegative in all coords,
     //    then check membership in submonoid generated by l2.periods.
-    let Some(diff) = sub_vectors(&l1.base, &l2.base) else {
-        return false;
-    };
-    if !is_nonnegative_combination(&diff, &l2.periods) {
-        return false;
-    }
+    let diff = sub_vectors(&l1.base, &l2.base)?;
+    let base_coefficients = nonnegative_combination(&diff, &l2.periods)?;
 
     // 2. Check that every period u_i^(1) is in the submonoid of l2.periods as well.
-    for p in &l1.periods {
-        if !is_nonnegative_combination(p, &l2.periods) {
-            return false;
+    let period_coefficients = l1
+        .periods
+        .iter()
+        .map(|p| nonnegative_combination(p, &l2.periods))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((base_coefficients, period_coefficients))
+}
+
+/// `base + Σ coefficients[i] * periods[i]`
+fn combination<K: Eq + Hash + Clone + Ord>(
+    base: &SparseVector<K>,
+    periods: &[SparseVector<K>],
+    coefficients: &[usize],
+) -> SparseVector<K> {
+    let mut result = base.clone();
+    for (period, &c) in periods.iter().zip(coefficients) {
+        for (k, &v) in &period.values {
+            let new_value = result.get(k) + c * v;
+            result.set(k.clone(), new_value);
         }
     }
-
-    true
+    result
 }
 
 /// Attempt to merge two linear sets L1 and L2 into a single linear set L
@@ -492,23 +1172,37 @@ impl<K: Eq + Hash + Clone + Ord> Kleene for SemilinearSet<K> {
         SemilinearSet::zero()
     }
 
-    // Union of two semilinear sets.
-    fn plus(mut self, mut other: Self) -> Self {
-        // Clone components of both and combine
-        if GENERATE_LESS.load(Ordering::SeqCst) {
-            self.components.append(&mut other.components);
-            SemilinearSet::new(self.components)
+    fn plus(self, other: Self) -> Self {
+        self.plus_with(other, semilinear_strategy())
+    }
+
+    fn times(self, other: Self) -> Self {
+        self.times_with(other, semilinear_strategy())
+    }
+
+    fn star(self) -> Self {
+        self.star_with(semilinear_strategy())
+    }
+}
+
+impl<K: Eq + Hash + Clone + Ord> SemilinearSet<K> {
+    /// Wrap generated components, simplifying them unless the strategy is `Exact`
+    fn from_generated(components: Vec<LinearSet<K>>, strategy: SemilinearStrategy) -> Self {
+        if strategy.simplifies() {
+            SemilinearSet::new(components)
         } else {
-            // naive: tack on without any dedupe/merge
-            self.components.extend(other.components);
-            SemilinearSet {
-                components: self.components,
-            }
+            SemilinearSet { components }
         }
     }
 
-    // Sequential composition (a.k.a. Minkowski sum) of two semilinear sets.
-    fn times(self, other: Self) -> Self {
+    /// Union of two semilinear sets.
+    pub fn plus_with(mut self, other: Self, strategy: SemilinearStrategy) -> Self {
+        self.components.extend(other.components);
+        Self::from_generated(self.components, strategy)
+    }
+
+    /// Sequential composition (a.k.a. Minkowski sum) of two semilinear sets.
+    pub fn times_with(self, other: Self, strategy: SemilinearStrategy) -> Self {
         let mut comps = Vec::new();
         for a in &self.components {
             for b in &other.components {
@@ -518,14 +1212,11 @@ impl<K: Eq + Hash + Clone + Ord> Kleene for SemilinearSet<K> {
                 comps.push(lin);
             }
         }
-        if GENERATE_LESS.load(Ordering::SeqCst) {
-            SemilinearSet::new(comps)
-        } else {
-            SemilinearSet { components: comps }
-        }
+        Self::from_generated(comps, strategy)
     }
 
-    fn star(self) -> Self {
+    /// Kleene star of a semilinear set.
+    pub fn star_with(self, strategy: SemilinearStrategy) -> Self {
         // Lots of heuristic optimizations to prevent blow up.
         // As a reminder, a linear set looks like:  b(p1+...+pN)*  and a SLS is a union of these.
         //
@@ -545,7 +1236,7 @@ impl<K: Eq + Hash + Clone + Ord> Kleene for SemilinearSet<K> {
 
         // 1. Pull out linear sets with zero base.
         let mut components = self.components;
-        if GENERATE_LESS.load(Ordering::SeqCst) {
+        if strategy.simplifies() {
             components.retain(|comp| {
                 if comp.base.is_zero() {
                     for p in &comp.periods {
@@ -560,11 +1251,17 @@ impl<K: Eq + Hash + Clone + Ord> Kleene for SemilinearSet<K> {
             // 2+3. Remove redundant periods, and pull out bases with no periods.
             loop {
                 let mut modified = false;
+                let extra: Vec<SparseVector<K>> = extra_periods.iter().cloned().collect();
                 components.retain_mut(|comp| {
-                    // Remove redundant periods.
-                    // TODO: this could, in fact, be strengthened to p \in extra_periods*
-                    if REMOVE_REDUNDANT.load(Ordering::SeqCst) {
-                        comp.periods.retain(|p| !extra_periods.contains(p));
+                    // Remove redundant periods: any period in extra_periods* is
+                    // redundant, but only Aggressive pays for the membership check.
+                    if remove_redundant_enabled() {
+                        comp.periods.retain(|p| match strategy {
+                            SemilinearStrategy::Aggressive => {
+                                !is_nonnegative_combination(p, &extra)
+                            }
+                            _ => !extra_periods.contains(p),
+                        });
                     }
                     // If the component has no periods, we add its base to extra_periods
                     if comp.periods.is_empty() {
@@ -623,16 +1320,11 @@ impl<K: Eq + Hash + Clone + Ord> Kleene for SemilinearSet<K> {
             }
         }
         // todo check this block with Jules
-        if GENERATE_LESS.load(Ordering::SeqCst) {
-            SemilinearSet::new(result_components)
-        } else {
-            SemilinearSet {
-                components: result_components,
-            }
-        }
+        Self::from_generated(result_components, strategy)
     }
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -825,6 +1517,198 @@ mod tests {
             ground_truth_a_star_times_b_plus_b_times_c
         );
     }
+
+    #[test]
+    fn test_strategies_denote_the_same_set() {
+        use SemilinearStrategy::*;
+        let atom = |k| SemilinearSet::atom(k);
+        // (a* + b + c(a+b)* + ab)*, with components of every kind star treats specially
+        let build = |strategy| {
+            let a_star = atom("a").star_with(strategy);
+            let c_ab_star = atom("c").times_with(
+                atom("a").plus_with(atom("b"), strategy).star_with(strategy),
+                strategy,
+            );
+            a_star
+                .plus_with(atom("b"), strategy)
+                .plus_with(c_ab_star, strategy)
+                .plus_with(atom("a").times_with(atom("b"), strategy), strategy)
+                .star_with(strategy)
+        };
+        let exact = build(Exact);
+        let generate_less = build(GenerateLess);
+        let aggressive = build(Aggressive);
+        assert!(generate_less.components.len() < exact.components.len());
+
+        for a in 0..4 {
+            for b in 0..4 {
+                for c in 0..4 {
+                    let mut v = SparseVector::new();
+                    v.set("a", a);
+                    v.set("b", b);
+                    v.set("c", c);
+                    assert_eq!(exact.contains(&v), generate_less.contains(&v), "{:?}", v);
+                    assert_eq!(exact.contains(&v), aggressive.contains(&v), "{:?}", v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_semilinear_contains() {
+        // a(b)* + c
+        let set = SemilinearSet::new(vec![
+            LinearSet {
+                base: SparseVector::unit("a"),
+                periods: vec![SparseVector::unit("b")],
+            },
+            LinearSet {
+                base: SparseVector::unit("c"),
+                periods: vec![],
+            },
+        ]);
+
+        let mut abbb = SparseVector::unit("a");
+        abbb.set("b", 3);
+        assert!(set.contains(&abbb));
+        assert!(set.contains(&SparseVector::unit("c")));
+        assert!(!set.contains(&SparseVector::unit("b")));
+        assert!(!set.contains(&SparseVector::unit("a").add(&SparseVector::unit("c"))));
+        assert!(!SemilinearSet::<&str>::empty().contains(&SparseVector::new()));
+    }
+
+    #[test]
+    fn test_minimize_with_witnesses() {
+        let v = |entries: &[(&str, usize)]| {
+            let mut v = SparseVector::new();
+            for (k, n) in entries {
+                v.set(k.to_string(), *n);
+            }
+            v
+        };
+        let lin = |base, periods| LinearSet { base, periods };
+        // a(a)*, a^3(a^2)*, b, a(a)* again
+        let set = SemilinearSet {
+            components: vec![
+                lin(v(&[("a", 1)]), vec![v(&[("a", 1)])]),
+                lin(v(&[("a", 3)]), vec![v(&[("a", 2)])]),
+                lin(v(&[("b", 1)]), vec![]),
+                lin(v(&[("a", 1)]), vec![v(&[("a", 1)])]),
+            ],
+        };
+        let minimized = set.minimize();
+        assert_eq!(
+            minimized.set.components,
+            vec![set.components[2].clone(), set.components[3].clone()]
+        );
+        assert_eq!(minimized.witnesses.len(), 2);
+        minimized.check().unwrap();
+
+        // a^3(a^2)* is a(a)* with base coefficient 2 and the period twice
+        let witness = &minimized.witnesses[1];
+        assert_eq!(witness.base_coefficients, vec![2]);
+        assert_eq!(witness.period_coefficients, vec![vec![2]]);
+
+        let mut forged = minimized.clone();
+        forged.witnesses[1].base_coefficients = vec![1];
+        assert!(forged.check().is_err());
+        forged.witnesses[1].covered_by = 7;
+        assert!(forged.check().is_err());
+    }
+
+    #[test]
+    fn test_semilinear_difference_and_complement() {
+        let alphabet = vec!["a", "b"];
+        // a(b)* + b^2
+        let mut bb = SparseVector::new();
+        bb.set("b", 2);
+        let set = SemilinearSet::new(vec![
+            LinearSet {
+                base: SparseVector::unit("a"),
+                periods: vec![SparseVector::unit("b")],
+            },
+            LinearSet {
+                base: bb,
+                periods: vec![],
+            },
+        ]);
+        // a^2(a + b)*
+        let mut aa = SparseVector::new();
+        aa.set("a", 2);
+        let large = SemilinearSet::new(vec![LinearSet {
+            base: aa,
+            periods: vec![SparseVector::unit("a"), SparseVector::unit("b")],
+        }]);
+
+        let complement = set.complement(&alphabet).unwrap();
+        let union = set.clone().plus(large.clone());
+        let difference = SemilinearSet::universe(alphabet.clone())
+            .difference(&union, &alphabet)
+            .unwrap();
+        for i in 0..5 {
+            for j in 0..5 {
+                let mut v = SparseVector::new();
+                v.set("a", i);
+                v.set("b", j);
+                assert_eq!(complement.contains(&v), !set.contains(&v), "{}", v);
+                assert_eq!(difference.contains(&v), !union.contains(&v), "{}", v);
+            }
+        }
+
+        // Non-unit periods are outside the supported fragment
+        let ab = SemilinearSet::atom("a").times(SemilinearSet::atom("b")).star();
+        assert!(ab.complement(&alphabet).is_none());
+        // So are keys outside the alphabet
+        assert!(SemilinearSet::atom("c").complement(&alphabet).is_none());
+    }
+
+    #[test]
+    fn test_pretty() {
+        let vector = |entries: &[(&str, usize)]| {
+            let mut v = SparseVector::new();
+            for (k, n) in entries {
+                v.set(k.to_string(), *n);
+            }
+            v
+        };
+        let set = SemilinearSet {
+            components: vec![
+                LinearSet {
+                    base: vector(&[("a/x", 1)]),
+                    periods: vec![vector(&[("b/y", 1)]), vector(&[("c/z", 2)])],
+                },
+                LinearSet {
+                    base: vector(&[("b/y", 1), ("a/x", 1)]),
+                    periods: vec![vector(&[("b/y", 1)])],
+                },
+                LinearSet {
+                    base: SparseVector::new(),
+                    periods: vec![],
+                },
+            ],
+        };
+
+        assert_eq!(
+            set.pretty(SemilinearFormat::Text).to_string(),
+            "{a/x}      + k1·{b/y} + k2·{2·c/z}\n\
+             {a/x, b/y} + k1·{b/y}\n\
+             0\n"
+        );
+        assert_eq!(
+            set.pretty(SemilinearFormat::Markdown).to_string(),
+            "| base       | k1    | k2      |\n\
+             | ---------- | ----- | ------- |\n\
+             | {a/x}      | {b/y} | {2·c/z} |\n\
+             | {a/x, b/y} | {b/y} |         |\n\
+             | 0          |       |         |\n"
+        );
+        let latex = set.pretty(SemilinearFormat::Latex).to_string();
+        assert!(latex.starts_with("\\begin{tabular}{l|ll}\nbase & $k_{1}$ & $k_{2}$ \\\\\n"));
+        assert!(latex.contains(
+            "$\\{\\text{a/x}\\}$ & $\\{\\text{b/y}\\}$ & $\\{2 \\cdot \\text{c/z}\\}$ \\\\\n"
+        ));
+        assert!(latex.contains("$0$ &  &  \\\\\n"));
+    }
 }
 
 //     #[test]
@@ -0,0 +1,307 @@
+//! Which behaviors of a non-serializable system are still serializable
+//! (`--serializable-subset`).
+//!
+//! A counterexample shows one multiset of completed requests that no serial execution
+//! produces. To show the rest of the picture, this explores the reachable markings of
+//! the Petri net with requests breadth first and splits the completed multisets of its
+//! quiescent markings into those inside the serializable set and those outside it. The
+//! analysis itself never builds the reachable set, so the exploration is bounded by
+//! `MAX_MARKINGS`; if it stops early, both lists are only the behaviors found so far.
+
+use crate::deterministic_map::{HashMap, HashSet};
+use crate::ns::NS;
+use crate::ns_decision::NSDecision;
+use crate::ns_to_petri::ReqPetriState;
+use crate::petri::Petri;
+use crate::semilinear::{SemilinearSet, SparseVector};
+use colored::*;
+use either::Either::{self, Left, Right};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Report the serializable and violating behaviors of non-serializable systems
+pub fn set_serializable_subset(enabled: bool) {
+    crate::options::update(|options| options.serializable_subset = enabled);
+}
+
+pub fn serializable_subset_enabled() -> bool {
+    crate::options::get(|options| options.serializable_subset)
+}
+
+/// Markings explored before giving up on exhausting the state space
+pub const MAX_MARKINGS: usize = 100_000;
+
+/// Behaviors shown per list in the report; the JSON file has all of them
+const MAX_PRINTED: usize = 10;
+
+/// File in the output directory that holds the report
+pub const SUBSET_FILE: &str = "serializable_subset.json";
+
+/// Completed multisets of the explored quiescent markings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedBehaviors<P: Clone + Eq + Hash + Ord> {
+    /// In the serializable set
+    pub serializable: Vec<SparseVector<P>>,
+    /// Outside the serializable set
+    pub violating: Vec<SparseVector<P>>,
+    pub explored_markings: usize,
+    /// Whether all reachable markings were explored
+    pub exhaustive: bool,
+}
+
+/// Explore the markings of `petri` breadth first, up to `max_markings` of them, and
+/// split the completed multisets (the tokens on `Right` places) of the markings where
+/// `places_that_must_be_zero` are empty by membership in `ser`
+pub fn explore_completed<P>(
+    petri: &Petri<Either<P, P>>,
+    places_that_must_be_zero: &[P],
+    ser: &SemilinearSet<P>,
+    max_markings: usize,
+) -> CompletedBehaviors<P>
+where
+    P: Clone + Ord + Hash,
+{
+    let mut places = petri.get_places();
+    places.sort();
+    let index: HashMap<Either<P, P>, usize> = places
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, p)| (p, i))
+        .collect();
+    let transitions: Vec<(Vec<usize>, Vec<usize>)> = petri
+        .get_transitions()
+        .iter()
+        .map(|(input, output)| {
+            (
+                input.iter().map(|p| index[p]).collect(),
+                output.iter().map(|p| index[p]).collect(),
+            )
+        })
+        .collect();
+    let must_be_zero: Vec<usize> = places_that_must_be_zero
+        .iter()
+        .filter_map(|p| index.get(&Left(p.clone())).copied())
+        .collect();
+
+    let mut initial = vec![0usize; places.len()];
+    for p in petri.get_initial_marking() {
+        initial[index[&p]] += 1;
+    }
+
+    let mut completed: Vec<SparseVector<P>> = Vec::new();
+    let mut seen_completed: HashSet<Vec<usize>> = HashSet::default();
+    let mut visited: HashSet<Vec<usize>> = HashSet::default();
+    let mut queue = VecDeque::new();
+    visited.insert(initial.clone());
+    queue.push_back(initial);
+    let mut exhaustive = true;
+
+    'explore: while let Some(marking) = queue.pop_front() {
+        if must_be_zero.iter().all(|&p| marking[p] == 0) {
+            // Project on the response places
+            let projection: Vec<usize> = places
+                .iter()
+                .zip(&marking)
+                .map(|(place, &count)| if place.is_right() { count } else { 0 })
+                .collect();
+            if seen_completed.insert(projection) {
+                let mut vector = SparseVector::new();
+                for (place, &count) in places.iter().zip(&marking) {
+                    if let Right(place) = place {
+                        vector.set(place.clone(), count);
+                    }
+                }
+                completed.push(vector);
+            }
+        }
+        for (input, output) in &transitions {
+            let mut next = marking.clone();
+            let mut enabled = true;
+            for &p in input {
+                if next[p] == 0 {
+                    enabled = false;
+                    break;
+                }
+                next[p] -= 1;
+            }
+            if !enabled {
+                continue;
+            }
+            for &p in output {
+                next[p] += 1;
+            }
+            if visited.contains(&next) {
+                continue;
+            }
+            if visited.len() >= max_markings {
+                exhaustive = false;
+                break 'explore;
+            }
+            visited.insert(next.clone());
+            queue.push_back(next);
+        }
+    }
+
+    let (serializable, violating) = completed.into_iter().partition(|c| ser.contains(c));
+    CompletedBehaviors {
+        serializable,
+        violating,
+        explored_markings: visited.len(),
+        exhaustive,
+    }
+}
+
+/// A completed multiset as (request, response, count) triples
+pub type Behavior<Req, Resp> = Vec<(Req, Resp, usize)>;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SubsetReport<Req, Resp> {
+    pub explored_markings: usize,
+    pub exhaustive: bool,
+    pub serializable: Vec<Behavior<Req, Resp>>,
+    pub not_serializable: Vec<Behavior<Req, Resp>>,
+    /// Documentation of the requests that have any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub request_docs: Vec<(Req, String)>,
+}
+
+fn to_behavior<L, G, Req, Resp>(
+    vector: &SparseVector<ReqPetriState<L, G, Req, Resp>>,
+) -> Behavior<Req, Resp>
+where
+    L: Clone + Ord + Hash,
+    G: Clone + Ord + Hash,
+    Req: Clone + Ord + Hash,
+    Resp: Clone + Ord + Hash,
+{
+    let mut behavior: Behavior<Req, Resp> = vector
+        .values
+        .iter()
+        .filter(|(_, count)| **count > 0)
+        .filter_map(|(place, count)| match place {
+            ReqPetriState::Response(req, resp) => Some((req.clone(), resp.clone(), *count)),
+            _ => None,
+        })
+        .collect();
+    behavior.sort();
+    behavior
+}
+
+fn format_behavior<Req: Display, Resp: Display>(behavior: &Behavior<Req, Resp>) -> String {
+    let pairs: Vec<String> = behavior
+        .iter()
+        .map(|(req, resp, count)| match count {
+            1 => format!("{}/{}", req, resp),
+            _ => format!("({}/{})^{}", req, resp, count),
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(", "))
+}
+
+/// For a non-serializable system, print which completed multisets found by the
+/// exploration are serializable and which are not, and save them to
+/// `<out_dir>/serializable_subset.json`
+pub fn report<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    decision: &NSDecision<G, L, Req, Resp>,
+    out_dir: &str,
+) -> Option<SubsetReport<Req, Resp>>
+where
+    G: Clone + Ord + Hash + Display + Debug,
+    L: Clone + Ord + Hash + Display + Debug,
+    Req: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+    Resp: Clone + Ord + Hash + Display + Debug + serde::Serialize,
+{
+    if !matches!(decision, NSDecision::NotSerializable { .. }) {
+        return None;
+    }
+    let (petri, places_that_must_be_zero, ser) = ns.serializability_problem();
+    let behaviors = explore_completed(&petri, &places_that_must_be_zero, &ser, MAX_MARKINGS);
+    let mut report = SubsetReport {
+        explored_markings: behaviors.explored_markings,
+        exhaustive: behaviors.exhaustive,
+        serializable: behaviors.serializable.iter().map(to_behavior).collect(),
+        not_serializable: behaviors.violating.iter().map(to_behavior).collect(),
+        request_docs: ns.request_docs.clone(),
+    };
+    report.serializable.sort();
+    report.not_serializable.sort();
+    print_report(&report);
+
+    let path = crate::utils::file::join(out_dir, SUBSET_FILE);
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => match crate::utils::file::safe_write_file(&path, &json) {
+            Ok(_) => println!("- {}", path.green()),
+            Err(err) => eprintln!("Failed to write {}: {}", path, err),
+        },
+        Err(err) => eprintln!("Failed to serialize the serializable subset: {}", err),
+    }
+    Some(report)
+}
+
+/// Print the report
+pub fn print_report<Req: Display, Resp: Display>(report: &SubsetReport<Req, Resp>) {
+    println!();
+    println!(
+        "Reachable completed requests ({} markings explored, {}):",
+        report.explored_markings,
+        if report.exhaustive {
+            "all of them"
+        } else {
+            "bounded, more behaviors may exist"
+        }
+    );
+    for (label, behaviors) in [
+        ("serializable".green(), &report.serializable),
+        ("not serializable".red(), &report.not_serializable),
+    ] {
+        println!("  {} ({}):", label, behaviors.len());
+        for behavior in behaviors.iter().take(MAX_PRINTED) {
+            println!("    {}", format_behavior(behavior));
+        }
+        if behaviors.len() > MAX_PRINTED {
+            println!("    ... and {} more", behaviors.len() - MAX_PRINTED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kleene::Kleene;
+
+    #[test]
+    fn test_explore_completed() {
+        // Two requests that each complete with the response "done" via a shared lock,
+        // but the second one can also complete with "late" while the first holds it
+        let mut petri: Petri<Either<&str, &str>> =
+            Petri::new(vec![Left("r1"), Left("r2"), Left("free")]);
+        petri.add_transition(vec![Left("r1"), Left("free")], vec![Left("held")]);
+        petri.add_transition(vec![Left("held")], vec![Left("free"), Right("done")]);
+        petri.add_transition(
+            vec![Left("r2"), Left("free")],
+            vec![Left("free"), Right("done")],
+        );
+        petri.add_transition(
+            vec![Left("r2"), Left("held")],
+            vec![Left("held"), Right("late")],
+        );
+
+        // Serializable: each request completes with "done"
+        let ser = SemilinearSet::singleton(SparseVector::unit("done")).star();
+        let behaviors = explore_completed(&petri, &["r1", "r2", "held"], &ser, 1000);
+
+        assert!(behaviors.exhaustive);
+        let done_twice: SparseVector<&str> =
+            SparseVector::unit("done").add(&SparseVector::unit("done"));
+        let late_and_done = SparseVector::unit("late").add(&SparseVector::unit("done"));
+        assert_eq!(behaviors.serializable, vec![done_twice]);
+        assert_eq!(behaviors.violating, vec![late_and_done]);
+
+        let bounded = explore_completed(&petri, &["r1", "r2", "held"], &ser, 2);
+        assert!(!bounded.exhaustive);
+        assert_eq!(bounded.explored_markings, 2);
+    }
+}
@@ -0,0 +1,522 @@
+//! `ser shrink <file>`: a small program that makes the analysis fail the same way.
+//!
+//! When the analysis of a program panics, fails in ISL or is killed, the program is
+//! usually much bigger than needed to show the bug. Shrinking runs the analysis in a
+//! subprocess, so that a crash does not take the shrinker down, and tries smaller
+//! programs one at a time, keeping each one that still fails the same way:
+//!
+//! - without one of its requests, sessions, bounds, globals or `idempotent` markers,
+//!   or without its doc comments
+//! - with a sequence replaced by one of its statements, an `if` by one of its branches,
+//!   a `while` by its body, or any other compound expression by `0`
+//!
+//! It stops when no single change keeps the failure, or after `--max-runs` runs. The
+//! candidates are written to `out/<name>/shrink/<name>_shrink.ser`, whose analysis goes
+//! to `out/<name>_shrink`, and the result to `out/<name>/shrink/<name>_min.ser`.
+
+use crate::error::SerError;
+use crate::parser::{Expr, ExprHc, Program};
+use colored::*;
+use hash_cons::Hc;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Default number of analyses run while shrinking
+pub const MAX_RUNS: usize = 500;
+
+/// Default time after which an analysis counts as not failing
+pub const RUN_TIMEOUT_SECONDS: u64 = 60;
+
+/// How often a running analysis is polled for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How the analysis failed. Shrinking keeps exactly the same failure, so that it does
+/// not wander off to another bug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// A panic, identified by where in the source of `ser` it happened
+    Panic { location: String },
+    /// ISL failed
+    Isl,
+    /// The process was killed by a signal, as when a library aborts
+    Killed { status: String },
+}
+
+impl Failure {
+    /// The failure of an analysis that exited with `code` (`None` if it was killed,
+    /// described by `status`) and printed `stderr`, if it crashed or failed internally
+    pub fn classify(code: Option<i32>, status: &str, stderr: &str) -> Option<Failure> {
+        match code {
+            Some(crate::error::EXIT_PANIC) => Some(Failure::Panic {
+                location: panic_location(stderr).unwrap_or_default(),
+            }),
+            Some(crate::error::EXIT_ISL) => Some(Failure::Isl),
+            Some(_) => None,
+            None => Some(Failure::Killed {
+                status: status.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Panic { location } => write!(f, "panic at {}", location),
+            Failure::Isl => write!(f, "ISL error"),
+            Failure::Killed { status } => write!(f, "killed ({})", status),
+        }
+    }
+}
+
+/// Where the first panic in `stderr` happened: `src/isl.rs:10:5` from `thread 'main'
+/// panicked at src/isl.rs:10:5:`, or from `panicked at 'message', src/isl.rs:10:5` as
+/// older Rust versions print it
+fn panic_location(stderr: &str) -> Option<String> {
+    let line = stderr.lines().find(|line| line.contains("panicked at"))?;
+    let rest = line.split_once("panicked at")?.1.trim();
+    let location = match rest.rfind("', ") {
+        Some(i) if rest.starts_with('\'') => &rest[i + 3..],
+        _ => rest.trim_end_matches(':'),
+    };
+    Some(location.to_string())
+}
+
+/// Number of nodes of `expr`, counting shared parts every time they occur
+fn expr_size(expr: &Expr) -> usize {
+    1 + parts(expr)
+        .iter()
+        .map(|part| expr_size(part))
+        .sum::<usize>()
+}
+
+/// How big `program` is; every candidate of `candidates` is smaller
+pub fn size(program: &Program) -> usize {
+    let requests: usize = program
+        .requests
+        .iter()
+        .map(|request| 1 + expr_size(&request.body) + usize::from(request.doc.is_some()))
+        .sum();
+    let sessions: usize = program
+        .sessions
+        .iter()
+        .map(|session| 1 + session.requests.len())
+        .sum();
+    requests + sessions + program.globals.len() + program.bounds.len() + program.idempotent.len()
+}
+
+/// The direct subexpressions of `expr`
+fn parts(expr: &Expr) -> Vec<Hc<Expr>> {
+    match expr {
+        Expr::Assign(_, e) | Expr::Not(e) | Expr::Return(e) => vec![e.clone()],
+        Expr::Equal(a, b)
+        | Expr::Add(a, b)
+        | Expr::Subtract(a, b)
+        | Expr::Sequence(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b)
+        | Expr::While(a, b) => vec![a.clone(), b.clone()],
+        Expr::If(c, t, e) => vec![c.clone(), t.clone(), e.clone()],
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => vec![],
+    }
+}
+
+/// `expr` with its direct subexpressions replaced by `parts`
+fn rebuild(table: &mut ExprHc, expr: &Hc<Expr>, parts: &[Hc<Expr>]) -> Hc<Expr> {
+    let part = |i: usize| parts[i].clone();
+    match expr.get() {
+        Expr::Assign(var, _) => table.assign(var.clone(), part(0)),
+        Expr::Not(_) => table.not(part(0)),
+        Expr::Return(_) => table.return_expr(part(0)),
+        Expr::Equal(_, _) => table.equal(part(0), part(1)),
+        Expr::Add(_, _) => table.add(part(0), part(1)),
+        Expr::Subtract(_, _) => table.subtract(part(0), part(1)),
+        Expr::Sequence(_, _) => table.sequence(part(0), part(1)),
+        Expr::And(_, _) => table.and(part(0), part(1)),
+        Expr::Or(_, _) => table.or(part(0), part(1)),
+        Expr::While(_, _) => table.while_expr(part(0), part(1)),
+        Expr::If(_, _, _) => table.if_expr(part(0), part(1), part(2)),
+        Expr::Yield | Expr::Exit | Expr::Unknown | Expr::Number(_) | Expr::Variable(_) => {
+            expr.clone()
+        }
+    }
+}
+
+/// `expr` with one part of it simplified, in every way, the whole expression first
+fn simplifications(table: &mut ExprHc, expr: &Hc<Expr>) -> Vec<Hc<Expr>> {
+    let mut result = match expr.get() {
+        Expr::Sequence(a, b) => vec![a.clone(), b.clone()],
+        Expr::If(_, t, e) => vec![t.clone(), e.clone()],
+        Expr::While(_, body) => vec![body.clone()],
+        _ => vec![],
+    };
+    let parts = parts(expr);
+    if !parts.is_empty() {
+        result.push(table.number(0));
+    }
+    for (i, part) in parts.iter().enumerate() {
+        for simpler in simplifications(table, part) {
+            let mut changed = parts.clone();
+            changed[i] = simpler;
+            result.push(rebuild(table, expr, &changed));
+        }
+    }
+    result
+}
+
+/// `program` without the request `name` and the declarations that mention it
+fn without_request(program: &Program, name: &str) -> Program {
+    let mut smaller = program.clone();
+    smaller.requests.retain(|request| request.name != name);
+    smaller.bounds.retain(|bound| bound.request != name);
+    smaller.idempotent.retain(|request| request != name);
+    for session in &mut smaller.sessions {
+        session.requests.retain(|request| request != name);
+    }
+    smaller
+        .sessions
+        .retain(|session| !session.requests.is_empty());
+    smaller
+}
+
+/// The programs one change smaller than `program`, biggest changes first
+pub fn candidates(table: &mut ExprHc, program: &Program) -> Vec<Program> {
+    let mut result = Vec::new();
+    if program.requests.len() > 1 {
+        for request in &program.requests {
+            result.push(without_request(program, &request.name));
+        }
+    }
+    for i in 0..program.sessions.len() {
+        let mut smaller = program.clone();
+        smaller.sessions.remove(i);
+        result.push(smaller);
+    }
+    for i in 0..program.bounds.len() {
+        let mut smaller = program.clone();
+        smaller.bounds.remove(i);
+        result.push(smaller);
+    }
+    for i in 0..program.globals.len() {
+        let mut smaller = program.clone();
+        smaller.globals.remove(i);
+        result.push(smaller);
+    }
+    for i in 0..program.idempotent.len() {
+        let mut smaller = program.clone();
+        smaller.idempotent.remove(i);
+        result.push(smaller);
+    }
+    if program.requests.iter().any(|request| request.doc.is_some()) {
+        let mut smaller = program.clone();
+        for request in &mut smaller.requests {
+            request.doc = None;
+        }
+        result.push(smaller);
+    }
+    for (i, request) in program.requests.iter().enumerate() {
+        for body in simplifications(table, &request.body) {
+            let mut smaller = program.clone();
+            smaller.requests[i].body = body;
+            result.push(smaller);
+        }
+    }
+    result
+}
+
+/// Shrink `program` while `fails` holds of it: try the candidates one at a time and
+/// continue from the first one that still fails, until none does or `max_runs`
+/// programs have been tried. Returns the smallest failing program and the number of
+/// programs tried.
+pub fn minimize(
+    table: &mut ExprHc,
+    program: Program,
+    max_runs: usize,
+    mut fails: impl FnMut(&Program) -> bool,
+) -> (Program, usize) {
+    let mut current = program;
+    let mut runs = 0;
+    // The changes before the last kept one were just tried, so the next round starts
+    // where it left off
+    let mut start = 0;
+    loop {
+        let current_size = size(&current);
+        let candidates: Vec<Program> = candidates(table, &current)
+            .into_iter()
+            .filter(|candidate| size(candidate) < current_size)
+            .collect();
+        let n = candidates.len();
+        let next = (start..n).chain(0..start.min(n)).find(|&i| {
+            if runs >= max_runs {
+                return false;
+            }
+            runs += 1;
+            fails(&candidates[i])
+        });
+        match next {
+            Some(i) => {
+                current = candidates[i].clone();
+                start = i;
+            }
+            None => return (current, runs),
+        }
+    }
+}
+
+/// Runs the analysis of a file in a subprocess
+struct Harness {
+    exe: PathBuf,
+    /// Options passed to every analysis
+    options: Vec<String>,
+    work_dir: String,
+    timeout: Duration,
+}
+
+impl Harness {
+    /// The failure of the analysis of `file`, `None` if it does not fail or times out
+    fn run(&self, file: &str) -> std::io::Result<Option<Failure>> {
+        let stderr_path = crate::utils::file::join(&self.work_dir, "stderr.txt");
+        let stderr_file = std::fs::File::create(&stderr_path)?;
+        let mut child = Command::new(&self.exe)
+            .args(&self.options)
+            .arg(file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::from(stderr_file))
+            .spawn()?;
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(None);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+        let stderr = String::from_utf8_lossy(&std::fs::read(&stderr_path)?).into_owned();
+        Ok(Failure::classify(
+            status.code(),
+            &status.to_string(),
+            &stderr,
+        ))
+    }
+
+    /// Whether the analysis of `program`, written to `file`, fails with `failure`
+    fn reproduces(
+        &self,
+        file: &str,
+        program: &Program,
+        version: Option<u32>,
+        failure: &Failure,
+    ) -> bool {
+        crate::utils::file::safe_write_file(file, &program.to_source(version)).is_ok()
+            && self.run(file).ok().flatten().as_ref() == Some(failure)
+    }
+}
+
+/// `ser shrink <file> [--max-runs <n>] [--run-timeout <seconds>] [-- <options>]`
+pub fn run(args: &[String]) -> Result<(), SerError> {
+    let usage = || {
+        SerError::Usage(
+            "Usage: ser shrink <file> [--max-runs <n>] [--run-timeout <seconds>] [-- <options of the analysis>]"
+                .to_string(),
+        )
+    };
+    let number = |flag: &str, value: Option<&String>| -> Result<u64, SerError> {
+        let value = value.ok_or_else(usage)?;
+        match value.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(SerError::Usage(format!(
+                "Invalid value '{}' for {} (expected a positive number)",
+                value, flag
+            ))),
+        }
+    };
+    let mut file = None;
+    let mut max_runs = MAX_RUNS;
+    let mut timeout = RUN_TIMEOUT_SECONDS;
+    let mut options = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--max-runs" => {
+                max_runs = number("--max-runs", args.get(i + 1))? as usize;
+                i += 2;
+            }
+            "--run-timeout" => {
+                timeout = number("--run-timeout", args.get(i + 1))?;
+                i += 2;
+            }
+            "--" => {
+                options = args[i + 1..].to_vec();
+                break;
+            }
+            name if file.is_none() && !name.starts_with('-') => {
+                file = Some(name.to_string());
+                i += 1;
+            }
+            _ => return Err(usage()),
+        }
+    }
+    let file = file.ok_or_else(usage)?;
+    let path = Path::new(&file);
+
+    let mut table = ExprHc::new();
+    let program = match crate::frontend::find(path)
+        .and_then(|front_end| front_end.load_program(path, &mut table))
+    {
+        Some(Ok(program)) => program,
+        Some(Err(err)) => return Err(SerError::ParseError(err)),
+        None => {
+            return Err(SerError::Usage(format!(
+                "ser shrink needs a program with request bodies, like a .ser file, not {}",
+                file
+            )));
+        }
+    };
+    // Candidates keep the language version of a .ser file, other front ends get the
+    // latest one
+    let version = if path.extension().is_some_and(|ext| ext == "ser") {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|source| crate::parser::parse_lang_header(&source).ok())
+            .and_then(|(version, _)| version)
+    } else {
+        Some(crate::parser::LANG_VERSION)
+    };
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("program")
+        .to_string();
+    let work_dir = crate::utils::file::join(&crate::utils::file::output_dir(path, None), "shrink");
+    crate::utils::file::ensure_dir_exists(&work_dir)?;
+    let harness = Harness {
+        exe: std::env::current_exe()?,
+        options,
+        work_dir: work_dir.clone(),
+        timeout: Duration::from_secs(timeout),
+    };
+
+    println!("{} {}", "Analyzing".blue().bold(), file.cyan());
+    let failure = harness.run(&file)?.ok_or_else(|| {
+        SerError::Usage(format!(
+            "The analysis of {} does not crash or fail internally, so there is nothing to shrink",
+            file
+        ))
+    })?;
+    println!("  {} {}", "Failure:".red().bold(), failure);
+
+    let candidate = crate::utils::file::join(&work_dir, &format!("{}_shrink.ser", stem));
+    if !harness.reproduces(&candidate, &program, version, &failure) {
+        return Err(SerError::Usage(format!(
+            "The failure does not reproduce with {} printed back as source ({})",
+            file, candidate
+        )));
+    }
+
+    println!(
+        "{} (size {}, at most {} runs)",
+        "Shrinking".blue().bold(),
+        size(&program),
+        max_runs
+    );
+    let (minimal, runs) = minimize(&mut table, program.clone(), max_runs, |smaller| {
+        let fails = harness.reproduces(&candidate, smaller, version, &failure);
+        if fails {
+            println!("  {} size {}", "→".bright_black(), size(smaller));
+        }
+        fails
+    });
+
+    let output = crate::utils::file::join(&work_dir, &format!("{}_min.ser", stem));
+    crate::utils::file::safe_write_file(&output, &minimal.to_source(version))?;
+    println!(
+        "{} from size {} to {} in {} runs ({})",
+        "Shrunk".green().bold(),
+        size(&program),
+        size(&minimal),
+        runs,
+        failure
+    );
+    println!("- {}", output.green());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program, to_source};
+
+    #[test]
+    fn test_classify() {
+        let stderr = "thread 'main' panicked at src/presburger.rs:120:9:\nassertion failed\n";
+        assert_eq!(
+            Failure::classify(Some(101), "exit status: 101", stderr),
+            Some(Failure::Panic {
+                location: "src/presburger.rs:120:9".to_string()
+            })
+        );
+        assert_eq!(
+            panic_location("thread 'main' panicked at 'boom', src/isl.rs:3:4"),
+            Some("src/isl.rs:3:4".to_string())
+        );
+        assert_eq!(
+            Failure::classify(Some(crate::error::EXIT_ISL), "", ""),
+            Some(Failure::Isl)
+        );
+        assert_eq!(
+            Failure::classify(None, "signal: 6 (SIGABRT)", ""),
+            Some(Failure::Killed {
+                status: "signal: 6 (SIGABRT)".to_string()
+            })
+        );
+        assert_eq!(Failure::classify(Some(0), "", ""), None);
+        assert_eq!(
+            Failure::classify(Some(crate::error::EXIT_PARSE), "", ""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_minimize() {
+        let mut table = ExprHc::new();
+        let program = parse_program(
+            "#lang ser/2
+global X := 0;
+global Y := 1;
+/// Writes X
+request a { x := X; yield; X := 5; y := Y }
+request b { if (X == 1) { Y := 2 } else { X := 5 } }
+bound a <= 2;
+session s { a; b }",
+            &mut table,
+        )
+        .unwrap();
+
+        // The "failure" is a request that sets X to 5
+        let (minimal, runs) = minimize(&mut table, program.clone(), MAX_RUNS, |candidate| {
+            candidate
+                .requests
+                .iter()
+                .any(|request| to_source(&request.body).contains("X := 5"))
+        });
+        assert_eq!(minimal.to_source(None), "request b {\n    X := 5\n}\n");
+        assert!(runs < MAX_RUNS);
+
+        // Every candidate is smaller, and shrinking stops after `max_runs` tries
+        assert!(
+            candidates(&mut table, &program)
+                .iter()
+                .all(|candidate| size(candidate) < size(&program))
+        );
+        let (unchanged, runs) = minimize(&mut table, program.clone(), 3, |_| false);
+        assert_eq!((unchanged, runs), (program, 3));
+    }
+}
@@ -1,9 +1,9 @@
-use crate::kleene::SMART_ORDER;
-use crate::reachability::BIDIRECTIONAL_PRUNING_ENABLED;
-use crate::semilinear::{GENERATE_LESS, REMOVE_REDUNDANT};
+use crate::kleene::{KleeneOrder, kleene_order};
+use crate::reachability::optimize_enabled;
+use crate::semilinear::{remove_redundant_enabled, semilinear_strategy};
+use colored::*;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::atomic::Ordering;
 use std::sync::Mutex;
 use std::{fs, fs::OpenOptions, io::Write, path::Path};
 
@@ -57,10 +57,10 @@ pub fn log_petri_size_csv(path: &Path, entry: &PetriNetSize) -> Result<(), std::
     let mut record = Vec::new();
 
     // read each flag and push "1"/"0"
-    let bidir_pruning = BIDIRECTIONAL_PRUNING_ENABLED.load(Ordering::Relaxed);
-    let remove_redundant = REMOVE_REDUNDANT.load(Ordering::Relaxed);
-    let generate_less = GENERATE_LESS.load(Ordering::Relaxed);
-    let smart_order = SMART_ORDER.load(Ordering::Relaxed);
+    let bidir_pruning = optimize_enabled();
+    let remove_redundant = remove_redundant_enabled();
+    let generate_less = semilinear_strategy().simplifies();
+    let smart_order = kleene_order() != KleeneOrder::Arbitrary;
     record.push(if bidir_pruning { "1" } else { "0" }.to_string());
     record.push(if remove_redundant { "1" } else { "0" }.to_string());
     record.push(if generate_less { "1" } else { "0" }.to_string());
@@ -107,6 +107,119 @@ pub fn log_petri_size_csv(path: &Path, entry: &PetriNetSize) -> Result<(), std::
     Ok(())
 }
 
+/// A size measured during the analysis that can be subject to a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeMetric {
+    PetriPlaces,
+    PetriTransitions,
+    SemilinearComponents,
+}
+
+impl SizeMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SizeMetric::PetriPlaces => "places",
+            SizeMetric::PetriTransitions => "transitions",
+            SizeMetric::SemilinearComponents => "components",
+        }
+    }
+}
+
+/// What to do once a threshold is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdAction {
+    /// Only print a warning
+    Warn,
+    /// Turn on bidirectional pruning of the Petri net for the remaining analysis
+    EnablePruning,
+}
+
+/// `metric > limit` triggers `action`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeThreshold {
+    pub metric: SizeMetric,
+    pub limit: usize,
+    pub action: ThresholdAction,
+}
+
+impl SizeThreshold {
+    /// Parse `<metric>:<limit>[:<action>]`, e.g. `places:500:prune`.
+    /// The metric is one of places, transitions, components; the action is warn (default) or prune.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(format!(
+                "Invalid size threshold '{}', expected <metric>:<limit>[:<action>]",
+                spec
+            ));
+        }
+        let metric = match parts[0] {
+            "places" => SizeMetric::PetriPlaces,
+            "transitions" => SizeMetric::PetriTransitions,
+            "components" => SizeMetric::SemilinearComponents,
+            other => return Err(format!("Unknown size metric '{}'", other)),
+        };
+        let limit = parts[1]
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid size limit '{}'", parts[1]))?;
+        let action = match parts.get(2).copied().unwrap_or("warn") {
+            "warn" => ThresholdAction::Warn,
+            "prune" => ThresholdAction::EnablePruning,
+            other => return Err(format!("Unknown threshold action '{}'", other)),
+        };
+        Ok(SizeThreshold {
+            metric,
+            limit,
+            action,
+        })
+    }
+}
+
+/// Add a size threshold (called from `main.rs`)
+pub fn add_size_threshold(threshold: SizeThreshold) {
+    crate::options::update(|options| options.size_thresholds.push(threshold));
+}
+
+/// Compare a measured size against the configured thresholds and carry out the
+/// actions of those that are exceeded. Returns a note for each triggered threshold,
+/// which is also printed and recorded in the run statistics.
+pub fn check_size_thresholds(metric: SizeMetric, value: usize) -> Vec<String> {
+    let thresholds = crate::options::get(|options| options.size_thresholds.clone());
+    let mut notes = Vec::new();
+    for threshold in thresholds.iter().filter(|t| t.metric == metric) {
+        if value <= threshold.limit {
+            continue;
+        }
+        let note = match threshold.action {
+            ThresholdAction::Warn => format!(
+                "{} {} exceeds threshold {}",
+                value,
+                metric.name(),
+                threshold.limit
+            ),
+            ThresholdAction::EnablePruning => {
+                let was_enabled = crate::reachability::optimize_enabled();
+                crate::reachability::set_optimize_flag(true);
+                format!(
+                    "{} {} exceeds threshold {}, bidirectional pruning {}",
+                    value,
+                    metric.name(),
+                    threshold.limit,
+                    if was_enabled {
+                        "already enabled"
+                    } else {
+                        "enabled automatically"
+                    }
+                )
+            }
+        };
+        eprintln!("{}: {}", "Warning".yellow().bold(), note);
+        crate::stats::record_size_threshold_event(&note);
+        notes.push(note);
+    }
+    notes
+}
+
 /// Append a record to a JSON‐lines file (one JSON object per line)
 pub fn log_size_json(path: &Path, entry: &PetriNetSize) -> Result<(), std::io::Error> {
     let mut file = OpenOptions::new().create(true).append(true).open(path)?;
@@ -159,10 +272,10 @@ pub fn log_semilinear_size_csv(path: &Path, entry: &SemilinearStats) -> Result<(
     let mut record = Vec::new();
 
     // read each flag and push "1"/"0"
-    let bidir_pruning = BIDIRECTIONAL_PRUNING_ENABLED.load(Ordering::Relaxed);
-    let remove_redundant = REMOVE_REDUNDANT.load(Ordering::Relaxed);
-    let generate_less = GENERATE_LESS.load(Ordering::Relaxed);
-    let smart_order = SMART_ORDER.load(Ordering::Relaxed);
+    let bidir_pruning = optimize_enabled();
+    let remove_redundant = remove_redundant_enabled();
+    let generate_less = semilinear_strategy().simplifies();
+    let smart_order = kleene_order() != KleeneOrder::Arbitrary;
     record.push(if bidir_pruning { "1" } else { "0" }.to_string());
     record.push(if remove_redundant { "1" } else { "0" }.to_string());
     record.push(if generate_less { "1" } else { "0" }.to_string());
@@ -438,4 +551,24 @@ mod tests {
         let _ = fs::remove_file(test_path);
         let _ = fs::remove_file(test_path2);
     }
+
+    #[test]
+    fn test_parse_size_threshold() {
+        assert_eq!(
+            SizeThreshold::parse("places:500:prune").unwrap(),
+            SizeThreshold {
+                metric: SizeMetric::PetriPlaces,
+                limit: 500,
+                action: ThresholdAction::EnablePruning,
+            }
+        );
+        let warn = SizeThreshold::parse("components:20").unwrap();
+        assert_eq!(warn.metric, SizeMetric::SemilinearComponents);
+        assert_eq!(warn.action, ThresholdAction::Warn);
+
+        assert!(SizeThreshold::parse("places").is_err());
+        assert!(SizeThreshold::parse("edges:5").is_err());
+        assert!(SizeThreshold::parse("places:many").is_err());
+        assert!(SizeThreshold::parse("places:5:explode").is_err());
+    }
 }
@@ -20,23 +20,25 @@
 //! )?;
 //! ```
 
+use crate::constraints::{Constraint, ConstraintType};
 use crate::debug_report::{SmptCall, format_constraints_description};
 use crate::deterministic_map::{HashMap, HashSet};
 use crate::petri::*;
-use crate::presburger::{Constraint, ConstraintType};
 use crate::proof_parser::{ProofInvariant, parse_proof_file};
+use crate::smpt_offline::OfflineMode;
 use colored::*;
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use std::path::Path;
-use std::process::{Command, Output};
-use std::sync::Mutex;
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 
 // === Constants ===
 const SMPT_WRAPPER_PATH: &str = "./smpt_wrapper.sh";
-const SMPT_PYTHON_MODULE: &str = "smpt";
+pub(crate) const SMPT_PYTHON_MODULE: &str = "smpt";
 // const DEFAULT_METHODS: &[&str] = &["STATE-EQUATION", "BMC", "K-INDUCTION", "SMT", "PDR-REACH"];
 const DEFAULT_METHODS: &[&str] = &["STATE-EQUATION", "BMC"];
 
@@ -89,15 +91,12 @@ static SMPT_CACHE: Mutex<Option<HashMap<u64, CacheEntry>>> = Mutex::new(None);
 /// Cache statistics for the current run
 static CACHE_STATS: Mutex<CacheStats> = Mutex::new(CacheStats { hits: 0, misses: 0 });
 
-/// Whether caching is enabled
-static USE_CACHE: Mutex<bool> = Mutex::new(false);
-
 /// Cache directory path
 const CACHE_DIR: &str = ".smpt_cache";
 
 /// Enable or disable SMPT result caching
 pub fn set_use_cache(enabled: bool) {
-    *USE_CACHE.lock().unwrap() = enabled;
+    crate::options::update(|options| options.smpt_cache = enabled);
     if enabled {
         println!("{} SMPT result caching", "Enabled".green().bold());
         // Ensure cache directory exists
@@ -109,7 +108,7 @@ pub fn set_use_cache(enabled: bool) {
 
 /// Check if caching is enabled
 pub fn is_cache_enabled() -> bool {
-    *USE_CACHE.lock().unwrap()
+    crate::options::get(|options| options.smpt_cache)
 }
 
 /// Clear the SMPT cache (both memory and filesystem)
@@ -186,7 +185,7 @@ fn load_cache_from_filesystem() {
 /// Save a cache entry to filesystem
 fn save_cache_entry(key: u64, entry: &CacheEntry) {
     if let Ok(json) = serde_json::to_string_pretty(entry) {
-        let path = format!("{}/{}.json", CACHE_DIR, key);
+        let path = crate::utils::file::join(CACHE_DIR, &format!("{}.json", key));
         std::fs::write(path, json).ok();
     }
 }
@@ -245,11 +244,22 @@ pub struct SmptVerificationResult<P> {
     pub raw_stderr: String,
 }
 
+impl<P> SmptVerificationResult<P> {
+    /// A failed query that produced no output
+    pub fn error(message: String) -> Self {
+        SmptVerificationResult {
+            outcome: SmptVerificationOutcome::Error { message },
+            raw_stdout: String::new(),
+            raw_stderr: String::new(),
+        }
+    }
+}
+
 // === Configuration ===
-/// Default SMPT timeout in seconds (can be configured at runtime)
+/// Get the current SMPT timeout in seconds (see `AnalysisOptions::smpt_timeout`)
 ///
-/// **Configuration:** To change the timeout globally, use `set_smpt_timeout()`:
-/// - `2` = 2 seconds (default, good for quick testing)
+/// **Configuration:** To change the timeout, use `set_smpt_timeout()`:
+/// - `2` = 2 seconds (good for quick testing)
 /// - `60` = 1 minute (for most examples)
 /// - `300` = 5 minutes (for complex examples)
 /// - `600` = 10 minutes (for very complex examples)
@@ -257,18 +267,108 @@ pub struct SmptVerificationResult<P> {
 ///
 /// This timeout is passed to SMPT's `--timeout` argument, which limits
 /// execution time per property verification method.
-static SMPT_TIMEOUT_SECONDS: Mutex<u64> = Mutex::new(10);
-
-/// Get the current SMPT timeout value
 pub fn get_smpt_timeout() -> u64 {
-    *SMPT_TIMEOUT_SECONDS.lock().unwrap()
+    crate::options::get(|options| options.smpt_timeout)
 }
 
-/// Set the global SMPT timeout value
+/// Set the SMPT timeout value
 pub fn set_smpt_timeout(timeout_seconds: u64) {
-    *SMPT_TIMEOUT_SECONDS.lock().unwrap() = timeout_seconds;
+    crate::options::update(|options| options.smpt_timeout = timeout_seconds);
+}
+
+/// Cooperative cancellation flag for SMPT queries.
+///
+/// Clones share the same flag, so a token handed to a running query can be
+/// cancelled from elsewhere; the query then kills its SMPT child process and
+/// returns an error outcome with `CANCELLED_MESSAGE`. Every token also counts
+/// as cancelled once Ctrl-C was pressed under an `InterruptGuard` (see `catch_interrupts`).
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of all queries holding this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst) || interrupted()
+    }
+}
+
+/// Set by the SIGINT handler while an `InterruptGuard` is alive
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Number of live `InterruptGuard`s and the SIGINT handler to restore after the last one
+#[cfg(unix)]
+static INTERRUPT_GUARDS: Mutex<(usize, libc::sighandler_t)> = Mutex::new((0, libc::SIG_DFL));
+
+/// Whether Ctrl-C was pressed since the outermost live `InterruptGuard` was created
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Routes Ctrl-C to SMPT cancellation while alive, see `catch_interrupts`
+pub struct InterruptGuard {
+    _private: (),
+}
+
+/// Route Ctrl-C to SMPT cancellation until the returned guard is dropped.
+///
+/// The first SIGINT only raises a flag, which cancels every `CancellationToken`: the
+/// running SMPT child is killed and the query loop stops before the next disjunct,
+/// keeping the journal for `--resume`. A second SIGINT exits at once. Dropping the
+/// last guard restores the previous handler, so Ctrl-C ends the other stages as usual.
+pub fn catch_interrupts() -> InterruptGuard {
+    #[cfg(unix)]
+    {
+        let mut guards = INTERRUPT_GUARDS.lock().unwrap();
+        if guards.0 == 0 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+            let handler: extern "C" fn(libc::c_int) = on_interrupt;
+            guards.1 = unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+        }
+        guards.0 += 1;
+    }
+    InterruptGuard { _private: () }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
 }
 
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let mut guards = INTERRUPT_GUARDS.lock().unwrap();
+            guards.0 -= 1;
+            if guards.0 == 0 {
+                unsafe { libc::signal(libc::SIGINT, guards.1) };
+                INTERRUPTED.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Error message of queries that were cancelled through a `CancellationToken`
+pub const CANCELLED_MESSAGE: &str = "SMPT query cancelled";
+
+/// Start of the error message of queries that SMPT gave up on because of the timeout
+pub const TIMEOUT_MESSAGE_PREFIX: &str = "SMPT timeout";
+
+/// How often a running SMPT process is polled for completion or cancellation
+const CANCELLATION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
 // === Public Types ===
 
 /// Convert a Petri net to SMPT .net format
@@ -288,11 +388,22 @@ where
 
     // 1. net {...}
     out.push_str(&format!("net {{{}}}\n", sanitize(net_name)));
+    let place_name = |place: &Place| crate::naming::place_name(&place.to_string());
+
+    debug_assert!(
+        {
+            let places = petri.get_places();
+            let names: HashSet<String> = places.iter().map(&place_name).collect();
+            names.len() == places.len()
+        },
+        "distinct places of net {} share a name",
+        net_name
+    );
 
     // 2. Count how many times each place appears in the initial marking.
     let mut marking_count: HashMap<String, usize> = HashMap::default();
     for place in petri.get_initial_marking() {
-        let place_str = sanitize(&place.to_string());
+        let place_str = place_name(&place);
         *marking_count.entry(place_str).or_insert(0) += 1;
     }
 
@@ -312,7 +423,7 @@ where
 
         // Input places
         for p in input_places {
-            out.push_str(&sanitize(&p.to_string()));
+            out.push_str(&place_name(p));
             out.push(' ');
         }
 
@@ -325,7 +436,7 @@ where
             if !first {
                 out.push(' ');
             }
-            out.push_str(&sanitize(&p.to_string()));
+            out.push_str(&place_name(p));
             first = false;
         }
         out.push('\n');
@@ -334,15 +445,145 @@ where
     out
 }
 
+/// Parse a Petri net in the `.net` format written by `petri_to_pnet`, returning the net
+/// name and the net. Place names are kept as they appear in the file; for nets written
+/// with the default naming scheme, `ident::decode` recovers the original names.
+///
+/// Besides repeated places, arc weights may be written as `place*k`. Place lines
+/// without a marking, transition labels (`tr t0 : label ...`) and comments starting
+/// with `#` are accepted, so nets written by other tools can be imported too.
+pub fn pnet_to_petri(net: &str) -> Result<(String, Petri<String>), String> {
+    // A place, repeated according to its weight
+    fn arc(token: &str, line: usize) -> Result<Vec<String>, String> {
+        let (place, weight) = match token.split_once('*') {
+            Some((place, weight)) => {
+                let weight = weight
+                    .parse::<usize>()
+                    .map_err(|_| format!("line {}: invalid arc weight in '{}'", line, token))?;
+                (place, weight)
+            }
+            None => (token, 1),
+        };
+        if place.is_empty() {
+            return Err(format!("line {}: missing place name in '{}'", line, token));
+        }
+        Ok(vec![place.to_string(); weight])
+    }
+
+    let mut name = String::new();
+    let mut initial_marking = Vec::new();
+    let mut transitions = Vec::new();
+    for (i, line) in net.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => {}
+            Some("net") => {
+                let rest = words.collect::<Vec<_>>().join(" ");
+                name = rest
+                    .trim_start_matches('{')
+                    .trim_end_matches('}')
+                    .to_string();
+            }
+            Some("pl") => {
+                let place = words
+                    .next()
+                    .ok_or_else(|| format!("line {}: missing place name", line_number))?;
+                let tokens = match words.next() {
+                    Some(marking) => marking
+                        .strip_prefix('(')
+                        .and_then(|m| m.strip_suffix(')'))
+                        .and_then(|m| m.parse::<usize>().ok())
+                        .ok_or_else(|| {
+                            format!("line {}: invalid marking '{}'", line_number, marking)
+                        })?,
+                    None => 0,
+                };
+                initial_marking.extend(vec![place.to_string(); tokens]);
+            }
+            Some("tr") => {
+                words
+                    .next()
+                    .ok_or_else(|| format!("line {}: missing transition name", line_number))?;
+                let mut words = words.peekable();
+                if words.peek() == Some(&":") {
+                    words.next();
+                    words.next();
+                }
+                let mut input = Vec::new();
+                let mut output = Vec::new();
+                let mut arrow = false;
+                for word in words {
+                    if word == "->" {
+                        arrow = true;
+                    } else if arrow {
+                        output.extend(arc(word, line_number)?);
+                    } else {
+                        input.extend(arc(word, line_number)?);
+                    }
+                }
+                if !arrow {
+                    return Err(format!("line {}: transition without '->'", line_number));
+                }
+                transitions.push((input, output));
+            }
+            Some(other) => {
+                return Err(format!(
+                    "line {}: unexpected '{}' (expected net, pl or tr)",
+                    line_number, other
+                ));
+            }
+        }
+    }
+
+    let mut petri = Petri::new(initial_marking);
+    for (input, output) in transitions {
+        petri.add_transition(input, output);
+    }
+    Ok((name, petri))
+}
+
 // === Main API Functions ===
 
+/// The input files of an SMPT query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmptQuery {
+    /// The Petri net in `.net` format
+    pub net: String,
+    /// The reachability formula in XML
+    pub xml: String,
+}
+
+/// Build the SMPT query whether `petri` can reach a marking that satisfies
+/// `constraints`. Writing the files and running SMPT is up to the caller, so the
+/// encoding can be tested without SMPT (see the golden files in `tests/golden/smpt`).
+pub fn build_query<P>(petri: &Petri<P>, constraints: &[Constraint<P>]) -> SmptQuery
+where
+    P: Clone + Hash + Ord + Display,
+{
+    // Extract places from Petri net to handle missing places in constraints
+    let petri_places: HashSet<String> = petri
+        .get_places_sorted()
+        .iter()
+        .map(|p| crate::naming::place_name(&p.to_string()))
+        .collect();
+
+    SmptQuery {
+        net: petri_to_pnet(petri, "constraint_check"),
+        xml: presburger_constraints_to_xml(constraints, "reachability-check", &petri_places),
+    }
+}
+
 /// Check if constraints are reachable in a Petri net using SMPT
 /// Returns detailed verification result with proof/counterexample
+/// The SMPT process is killed if `cancel` is cancelled while it runs.
 pub fn can_reach_constraint_set<P>(
     petri: Petri<P>,
     constraints: Vec<Constraint<P>>,
     out_dir: &str,
     disjunct_id: usize,
+    cancel: &CancellationToken,
 ) -> SmptVerificationResult<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
@@ -367,6 +608,7 @@ where
             if let Some(entry) = cache.get(&cache_key) {
             println!("{} SMPT cache hit for disjunct {}", "✓".green().bold(), disjunct_id);
             CACHE_STATS.lock().unwrap().record_hit();
+            crate::stats::record_smpt_cache_lookup(true);
             
             // Convert cached result back to the correct type
             // The cache stores results with String places, we need to convert back to P
@@ -384,7 +626,7 @@ where
                             places.iter().filter_map(|s| {
                                 // Try to convert string back to P using the petri net places
                                 petri.get_places_sorted().into_iter().find(|p| {
-                                    sanitize(&p.to_string()) == *s
+                                    crate::naming::place_name(&p.to_string()) == *s
                                 })
                             }).collect()
                         };
@@ -425,38 +667,47 @@ where
         &constraints,
     );
 
-    // Extract places from Petri net to handle missing places in constraints
-    let petri_places: HashSet<String> = petri
-        .get_places_sorted()
-        .iter()
-        .map(|p| sanitize(&p.to_string()))
-        .collect();
-
-    // Convert constraints to XML and use SMPT to check reachability
-    let xml = presburger_constraints_to_xml(&constraints, "reachability-check", &petri_places);
-
-    // Convert Petri net to SMPT format
-    let pnet_content = petri_to_pnet(&petri, "constraint_check");
+    let SmptQuery {
+        net: pnet_content,
+        xml,
+    } = build_query(&petri, &constraints);
 
     // Save files for SMPT
-    std::fs::create_dir_all(out_dir).expect("Failed to create output directory");
-    let xml_file_path = format!("{}/smpt_constraints_disjunct_{}.xml", out_dir, disjunct_id);
-    let pnet_file_path = format!("{}/smpt_petri_disjunct_{}.net", out_dir, disjunct_id);
-    let _proof_file_path = format!(
-        "{}/smpt_constraints_disjunct_{}_proof.txt",
-        out_dir, disjunct_id
-    );
+    let file = |name: String| crate::utils::file::join(out_dir, &name);
+    let xml_file_path = file(format!("smpt_constraints_disjunct_{}.xml", disjunct_id));
+    let pnet_file_path = file(format!("smpt_petri_disjunct_{}.net", disjunct_id));
+    let _proof_file_path = file(format!(
+        "smpt_constraints_disjunct_{}_proof.txt",
+        disjunct_id
+    ));
 
-    std::fs::write(&xml_file_path, &xml).expect("Failed to write SMPT XML");
-    std::fs::write(&pnet_file_path, &pnet_content).expect("Failed to write SMPT Petri net");
+    if let Err(err) = std::fs::create_dir_all(out_dir)
+        .and_then(|_| std::fs::write(&xml_file_path, &xml))
+        .and_then(|_| std::fs::write(&pnet_file_path, &pnet_content))
+    {
+        return SmptVerificationResult::error(format!(
+            "Failed to write the SMPT query to {}: {}",
+            out_dir, err
+        ));
+    }
+    if let Err(err) = crate::naming::save_name_map(out_dir) {
+        eprintln!("Warning: Failed to save place names: {}", err);
+    }
 
     // Record cache miss
     if is_cache_enabled() {
         CACHE_STATS.lock().unwrap().record_miss();
+        crate::stats::record_smpt_cache_lookup(false);
     }
     
-    // Try to run SMPT tool with the Petri net for trace mapping
-    let result = run_smpt(&pnet_file_path, &xml_file_path, &petri);
+    // Try to run SMPT tool with the Petri net for trace mapping (or export the query,
+    // or take its result from an offline run)
+    let result = match crate::smpt_offline::offline_mode() {
+        OfflineMode::Off => {
+            run_backend(&pnet_file_path, &xml_file_path, &petri, disjunct_id, cancel)
+        }
+        mode => offline_query(mode, &petri, &constraints, &pnet_content, &xml, out_dir, disjunct_id),
+    };
 
     // Log the result
     match &result.outcome {
@@ -474,6 +725,11 @@ where
                 "REACHABLE".yellow().bold()
             );
         }
+        SmptVerificationOutcome::Error { message }
+            if crate::smpt_offline::offline_mode() == OfflineMode::Export =>
+        {
+            println!("  {} {}", "→".bright_black(), message.bright_black());
+        }
         SmptVerificationOutcome::Error { message } => {
             eprintln!("ERROR: Failed to run SMPT: {}", message);
             eprintln!("Generated files for manual verification:");
@@ -508,13 +764,17 @@ where
     debug_logger.smpt_call(smpt_call);
 
     // Save raw SMPT output for debugging
-    let stdout_path = format!("{}/smpt_output_disjunct_{}.stdout", out_dir, disjunct_id);
-    let stderr_path = format!("{}/smpt_output_disjunct_{}.stderr", out_dir, disjunct_id);
+    let stdout_path = file(format!("smpt_output_disjunct_{}.stdout", disjunct_id));
+    let stderr_path = file(format!("smpt_output_disjunct_{}.stderr", disjunct_id));
     std::fs::write(&stdout_path, &result.raw_stdout).ok();
     std::fs::write(&stderr_path, &result.raw_stderr).ok();
 
-    // Cache the result if caching is enabled
-    if is_cache_enabled() {
+    // Cache the result if caching is enabled (a cancelled query has no result worth caching)
+    // Exported queries have no result yet
+    if is_cache_enabled()
+        && !cancel.is_cancelled()
+        && crate::smpt_offline::offline_mode() != OfflineMode::Export
+    {
         let cache_key = compute_cache_key(&petri, &constraints);
         
         // Convert result to String-based version for caching
@@ -529,10 +789,10 @@ where
                 // Convert trace to String for caching
                 let string_trace = trace.iter().map(|(inputs, outputs)| {
                     let string_inputs: Vec<String> = inputs.iter()
-                        .map(|p| sanitize(&p.to_string()))
+                        .map(|p| crate::naming::place_name(&p.to_string()))
                         .collect();
                     let string_outputs: Vec<String> = outputs.iter()
-                        .map(|p| sanitize(&p.to_string()))
+                        .map(|p| crate::naming::place_name(&p.to_string()))
                         .collect();
                     (string_inputs, string_outputs)
                 }).collect();
@@ -565,6 +825,10 @@ where
 
 /// Install SMPT tool - returns true if already installed or successfully installed
 pub fn install_smpt() -> Result<(), String> {
+    if !cfg!(feature = "smpt") {
+        return Err("ser was built without the smpt feature".to_string());
+    }
+
     // Check if SMPT is already available
     if is_smpt_installed() {
         return Ok(());
@@ -602,10 +866,45 @@ pub fn ensure_smpt_available() -> bool {
     }
 }
 
+/// Result of the first SMPT availability check of this run
+static SMPT_AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+/// Like `is_smpt_installed`, but only probes once per run and warns if SMPT is missing
+pub fn smpt_available() -> bool {
+    #[cfg(any(test, feature = "fake-smpt"))]
+    if crate::smpt_fake::fake_smpt().is_some() {
+        return true;
+    }
+    *SMPT_AVAILABLE.get_or_init(|| {
+        let available = is_smpt_installed();
+        if !available {
+            eprintln!(
+                "{}: SMPT is not available, falling back to structural checks and explicit search",
+                "Warning".yellow().bold()
+            );
+        }
+        available
+    })
+}
+
+/// Whether SMPT runs through `smpt_wrapper.sh`, which needs a Unix shell
+fn use_smpt_wrapper() -> bool {
+    crate::utils::process::can_run_script(std::env::consts::OS, Path::new(SMPT_WRAPPER_PATH))
+}
+
+/// The Python interpreter that runs `python -m smpt`
+fn python() -> &'static str {
+    crate::utils::process::python(std::env::consts::OS)
+}
+
 /// Check if SMPT is installed and available
 pub fn is_smpt_installed() -> bool {
+    if !cfg!(feature = "smpt") {
+        return false;
+    }
+
     // Try the wrapper script first
-    if Path::new(SMPT_WRAPPER_PATH).exists()
+    if use_smpt_wrapper()
         && Command::new(SMPT_WRAPPER_PATH)
             .args(["--help"])
             .output()
@@ -616,19 +915,43 @@ pub fn is_smpt_installed() -> bool {
     }
 
     // Fall back to global python3 -m smpt
-    Command::new("python3")
+    Command::new(python())
         .args(["-m", SMPT_PYTHON_MODULE, "--help"])
         .output()
         .map(|output| output.status.success())
         .unwrap_or(false)
 }
 
+/// Run SMPT on a query, or let the fake backend answer it if there is one
+#[cfg_attr(not(any(test, feature = "fake-smpt")), allow(unused_variables))]
+fn run_backend<P>(
+    net_file: &str,
+    xml_file: &str,
+    petri: &Petri<P>,
+    disjunct_id: usize,
+    cancel: &CancellationToken,
+) -> SmptVerificationResult<P>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
+    #[cfg(any(test, feature = "fake-smpt"))]
+    if let Some(fake) = crate::smpt_fake::fake_smpt() {
+        return fake.run(xml_file, disjunct_id, petri, cancel);
+    }
+    run_smpt(net_file, xml_file, petri, cancel)
+}
+
 /// Run SMPT on a Petri net file with constraints using the current global timeout
-fn run_smpt<P>(net_file: &str, xml_file: &str, petri: &Petri<P>) -> SmptVerificationResult<P>
+fn run_smpt<P>(
+    net_file: &str,
+    xml_file: &str,
+    petri: &Petri<P>,
+    cancel: &CancellationToken,
+) -> SmptVerificationResult<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
 {
-    run_smpt_with_timeout(net_file, xml_file, Some(get_smpt_timeout()), petri)
+    run_smpt_with_timeout(net_file, xml_file, Some(get_smpt_timeout()), petri, cancel)
 }
 
 /// Run SMPT with a specific timeout
@@ -637,11 +960,65 @@ fn run_smpt_with_timeout<P>(
     xml_file: &str,
     timeout_seconds: Option<u64>,
     petri: &Petri<P>,
+    cancel: &CancellationToken,
+) -> SmptVerificationResult<P>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
+    run_smpt_internal(net_file, xml_file, timeout_seconds, petri, cancel)
+}
+
+/// Export a query for offline solving, or answer it from an offline run's output
+fn offline_query<P>(
+    mode: OfflineMode,
+    petri: &Petri<P>,
+    constraints: &[Constraint<P>],
+    pnet_content: &str,
+    xml: &str,
+    out_dir: &str,
+    disjunct_id: usize,
 ) -> SmptVerificationResult<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
 {
-    run_smpt_internal(net_file, xml_file, timeout_seconds, petri)
+    let id = crate::smpt_offline::query_id(disjunct_id, compute_cache_key(petri, constraints));
+    let error = |message: String| SmptVerificationResult {
+        outcome: SmptVerificationOutcome::Error { message },
+        raw_stdout: String::new(),
+        raw_stderr: String::new(),
+    };
+    match mode {
+        OfflineMode::Off => unreachable!("offline_query called without an offline mode"),
+        OfflineMode::Export => {
+            let timeout = Some(get_smpt_timeout());
+            match crate::smpt_offline::export_query(
+                out_dir,
+                &id,
+                disjunct_id,
+                pnet_content,
+                xml,
+                |net, xml, proof| build_smpt_args(net, xml, proof, timeout),
+            ) {
+                Ok(_) => error(format!("SMPT query {} exported for offline solving", id)),
+                Err(err) => error(err),
+            }
+        }
+        OfflineMode::Import(dir) => match crate::smpt_offline::imported_result(&dir, &id) {
+            Ok((stdout, proof_file_path)) => {
+                let stderr_path = crate::utils::file::join(&dir, &format!("{}.stderr", id));
+                let stderr = std::fs::read_to_string(stderr_path).unwrap_or_default();
+                parse_smpt_output(
+                    stdout,
+                    stderr,
+                    None,
+                    &proof_file_path,
+                    Some(get_smpt_timeout()),
+                    petri,
+                )
+            }
+            Err(err) => error(err),
+        },
+    }
 }
 
 // === Helper Functions ===
@@ -685,16 +1062,24 @@ fn execute_smpt(
     args: &[String],
     stdout_path: &str,
     stderr_path: &str,
+    cancel: &CancellationToken,
 ) -> Result<Output, std::io::Error> {
     use std::fs::File;
     use std::process::Stdio;
 
+    if !cfg!(feature = "smpt") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "ser was built without the smpt feature",
+        ));
+    }
+
     // Create output files
     let stdout_file = File::create(stdout_path)?;
     let stderr_file = File::create(stderr_path)?;
 
     // Build the command
-    let mut cmd = if Path::new(SMPT_WRAPPER_PATH).exists() {
+    let mut cmd = if use_smpt_wrapper() {
         let mut cmd = Command::new(SMPT_WRAPPER_PATH);
         cmd.args(args);
         cmd
@@ -703,7 +1088,7 @@ fn execute_smpt(
         let mut python_args = vec!["-m".to_string(), SMPT_PYTHON_MODULE.to_string()];
         python_args.extend_from_slice(args);
 
-        let mut cmd = Command::new("python3");
+        let mut cmd = Command::new(python());
         cmd.args(&python_args);
         cmd
     };
@@ -713,8 +1098,8 @@ fn execute_smpt(
     cmd.stderr(Stdio::from(stderr_file));
     cmd.stdin(Stdio::null()); // Explicitly close stdin
 
-    // Execute and wait for completion
-    let status = cmd.status()?;
+    // Execute and wait for completion, killing the process if the query is cancelled
+    let status = wait_or_cancel(cmd.spawn()?, cancel)?;
 
     // Read the files back
     let stdout = std::fs::read(stdout_path)?;
@@ -727,6 +1112,25 @@ fn execute_smpt(
     })
 }
 
+/// Wait for a child process to exit, polling `cancel` in the meantime.
+/// If cancellation is requested the child is killed and an `Interrupted` error is returned.
+fn wait_or_cancel(mut child: Child, cancel: &CancellationToken) -> std::io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                CANCELLED_MESSAGE,
+            ));
+        }
+        std::thread::sleep(CANCELLATION_POLL_INTERVAL);
+    }
+}
+
 /// Filter out harmless Python cleanup errors from stderr
 fn filter_python_cleanup_errors(stderr: &str) -> String {
     stderr
@@ -741,6 +1145,32 @@ fn filter_python_cleanup_errors(stderr: &str) -> String {
         .join("\n")
 }
 
+/// The verdict in the `FORMULA` lines of SMPT's output: whether the property is
+/// reachable, or `None` if there is no verdict or the lines contradict each other
+fn extract_verdict(output: &str) -> Option<bool> {
+    let mut verdicts = output
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("FORMULA"))
+        .filter_map(|rest| {
+            rest.split_whitespace().find_map(|word| match word {
+                "TRUE" => Some(true),
+                "FALSE" => Some(false),
+                _ => None,
+            })
+        });
+    let verdict = verdicts.next()?;
+    verdicts.all(|other| other == verdict).then_some(verdict)
+}
+
+/// The exception that ended SMPT, if it crashed with a Python traceback
+fn extract_exception(stderr: &str) -> Option<&str> {
+    let (_, traceback) = stderr.split_once("Traceback (most recent call last):")?;
+    traceback
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+}
+
 /// Extract model from SMPT output
 fn extract_model(output: &str) -> Option<String> {
     for line in output.lines() {
@@ -783,14 +1213,25 @@ fn extract_trace_indices(output: &str) -> Vec<usize> {
 }
 
 /// Convert trace indices to actual transitions (input places, output places)
-fn indices_to_transitions<P>(indices: Vec<usize>, petri: &Petri<P>) -> Vec<(Vec<P>, Vec<P>)>
+fn indices_to_transitions<P>(
+    indices: Vec<usize>,
+    petri: &Petri<P>,
+) -> Result<Vec<(Vec<P>, Vec<P>)>, String>
 where
     P: Clone + PartialEq + Eq + Hash,
 {
     let transitions = petri.get_transitions();
     indices
         .into_iter()
-        .map(|idx| transitions[idx].clone())
+        .map(|idx| {
+            transitions.get(idx).cloned().ok_or_else(|| {
+                format!(
+                    "SMPT trace fires t{}, but the net has {} transitions",
+                    idx,
+                    transitions.len()
+                )
+            })
+        })
         .collect()
 }
 
@@ -800,10 +1241,21 @@ fn run_smpt_internal<P>(
     xml_file: &str,
     timeout_seconds: Option<u64>,
     petri: &Petri<P>,
+    cancel: &CancellationToken,
 ) -> SmptVerificationResult<P>
 where
     P: Clone + Hash + Ord + Display + Debug,
 {
+    if cancel.is_cancelled() {
+        return SmptVerificationResult {
+            outcome: SmptVerificationOutcome::Error {
+                message: CANCELLED_MESSAGE.to_string(),
+            },
+            raw_stdout: String::new(),
+            raw_stderr: String::new(),
+        };
+    }
+
     if !is_smpt_installed() {
         return SmptVerificationResult {
             outcome: SmptVerificationOutcome::Error {
@@ -841,23 +1293,32 @@ where
     };
 
     // Generate absolute proof file path based on the XML file path
-    let proof_file_path = abs_xml_file.to_str().unwrap().replace(".xml", "_proof.txt");
+    let proof_file_path = abs_xml_file.to_string_lossy().replace(".xml", "_proof.txt");
 
     // Generate stdout/stderr file paths based on the XML file path
-    let stdout_path = abs_xml_file.to_str().unwrap().replace(".xml", ".stdout");
-    let stderr_path = abs_xml_file.to_str().unwrap().replace(".xml", ".stderr");
+    let stdout_path = abs_xml_file.to_string_lossy().replace(".xml", ".stdout");
+    let stderr_path = abs_xml_file.to_string_lossy().replace(".xml", ".stderr");
 
     // Build command arguments
     let args = build_smpt_args(
-        abs_net_file.to_str().unwrap(),
-        abs_xml_file.to_str().unwrap(),
+        &abs_net_file.to_string_lossy(),
+        &abs_xml_file.to_string_lossy(),
         &proof_file_path,
         timeout_seconds,
     );
 
     // Execute SMPT
-    let output = match execute_smpt(&args, &stdout_path, &stderr_path) {
+    let output = match execute_smpt(&args, &stdout_path, &stderr_path, cancel) {
         Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+            return SmptVerificationResult {
+                outcome: SmptVerificationOutcome::Error {
+                    message: CANCELLED_MESSAGE.to_string(),
+                },
+                raw_stdout: String::new(),
+                raw_stderr: String::new(),
+            };
+        }
         Err(e) => {
             return SmptVerificationResult {
                 outcome: SmptVerificationOutcome::Error {
@@ -870,13 +1331,37 @@ where
     };
 
     let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
-    let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
 
+    parse_smpt_output(
+        stdout,
+        stderr,
+        output.status.code(),
+        &proof_file_path,
+        timeout_seconds,
+        petri,
+    )
+}
+
+/// Interpret the output of an SMPT run. `proof_file_path` is where SMPT was told to
+/// export its proof; a trace may also be in `<proof_file_path>.scn`.
+pub(crate) fn parse_smpt_output<P>(
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+    proof_file_path: &str,
+    timeout_seconds: Option<u64>,
+    petri: &Petri<P>,
+) -> SmptVerificationResult<P>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
     // Filter out harmless Python cleanup errors
-    stderr = filter_python_cleanup_errors(&stderr);
+    let stderr = filter_python_cleanup_errors(&stderr);
 
     // Parse SMPT output
-    if stdout.contains("TRUE") {
+    let verdict = extract_verdict(&stdout);
+    if verdict == Some(true) {
         // Property is reachable => NOT serializable
         let mut trace_indices = extract_trace_indices(&stdout);
 
@@ -899,30 +1384,40 @@ where
         }
 
         // Convert indices to actual transitions
-        let trace = indices_to_transitions(trace_indices, petri);
+        let outcome = match indices_to_transitions(trace_indices, petri) {
+            Ok(trace) => SmptVerificationOutcome::Reachable { trace },
+            Err(message) => SmptVerificationOutcome::Error { message },
+        };
 
         SmptVerificationResult {
-            outcome: SmptVerificationOutcome::Reachable { trace },
+            outcome,
             raw_stdout: stdout,
             raw_stderr: stderr,
         }
-    } else if stdout.contains("FALSE") {
+    } else if verdict == Some(false) {
         // Property is unreachable => IS serializable
 
-        // Try to read proof certificate if it exists
-        let proof_certificate = std::fs::read_to_string(&proof_file_path).ok();
-
-        // Try to parse the proof certificate
-        let parsed_proof =
-            proof_certificate
-                .as_ref()
-                .and_then(|cert| match parse_proof_file(cert) {
-                    Ok(proof) => Some(proof),
-                    Err(e) => {
-                        eprintln!("Warning: Failed to parse proof certificate: {:?}", e);
-                        None
-                    }
-                });
+        // Try to read proof certificate if it exists (an empty file holds no proof)
+        let proof_certificate = std::fs::read_to_string(proof_file_path)
+            .ok()
+            .filter(|cert| !cert.trim().is_empty());
+
+        // A proof that does not parse was cut off, so the run did not complete
+        let parsed_proof = match proof_certificate.as_deref().map(parse_proof_file) {
+            Some(Err(e)) => {
+                return SmptVerificationResult {
+                    outcome: SmptVerificationOutcome::Error {
+                        message: format!(
+                            "Could not parse SMPT proof certificate {}: {:?}",
+                            proof_file_path, e
+                        ),
+                    },
+                    raw_stdout: stdout,
+                    raw_stderr: stderr,
+                };
+            }
+            parsed => parsed.and_then(Result::ok),
+        };
 
         SmptVerificationResult {
             outcome: SmptVerificationOutcome::Unreachable {
@@ -934,10 +1429,13 @@ where
         }
     } else {
         // Check for timeout patterns
-        let error_msg = if output.status.code() == Some(1) && stdout.trim() == "# Hello" {
+        let error_msg = if let Some(exception) = extract_exception(&stderr) {
+            format!("SMPT crashed: {}", exception)
+        } else if exit_code == Some(1) && stdout.trim() == "# Hello" {
             crate::stats::increment_smpt_timeouts();
             format!(
-                "SMPT timeout: Analysis timed out after {}s. Try increasing timeout or enabling optimizations.",
+                "{}: Analysis timed out after {}s. Try increasing timeout or enabling optimizations.",
+                TIMEOUT_MESSAGE_PREFIX,
                 timeout_seconds.unwrap_or(get_smpt_timeout())
             )
         } else if stdout.contains("# Hello")
@@ -946,7 +1444,8 @@ where
         {
             crate::stats::increment_smpt_timeouts();
             format!(
-                "SMPT timeout: Analysis timed out after {}s (completed startup but no results). Try increasing timeout or enabling optimizations.",
+                "{}: Analysis timed out after {}s (completed startup but no results). Try increasing timeout or enabling optimizations.",
+                TIMEOUT_MESSAGE_PREFIX,
                 timeout_seconds.unwrap_or(get_smpt_timeout())
             )
         } else {
@@ -1019,16 +1518,13 @@ pub fn presburger_constraints_to_xml<P: Display>(
     xml
 }
 
-// Use the shared utility function for sanitization
-use crate::utils::string::sanitize;
-
 /// Helper function to generate place token count or constant 0 if place doesn't exist
 fn place_tokens_or_zero(place_name: &str, petri_places: &HashSet<String>) -> String {
-    let sanitized_place = sanitize(place_name);
-    if petri_places.contains(&sanitized_place) {
+    let name = crate::naming::place_name(place_name);
+    if petri_places.contains(&name) {
         format!(
             "<tokens-count><place>{}</place></tokens-count>",
-            sanitized_place
+            name
         )
     } else {
         "<integer-constant>0</integer-constant>".to_string()
@@ -1109,7 +1605,7 @@ pub fn presburger_constraint_to_xml<P: Display>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::presburger::{Constraint, ConstraintType};
+    use crate::constraints::{Constraint, ConstraintType};
 
     #[test]
     fn test_presburger_constraint_to_xml_simple() {
@@ -1196,6 +1692,120 @@ mod tests {
         assert!(pnet.contains("tr t1 P1 ->"));
     }
 
+    /// Compare the queries of the examples with the golden files in `tests/golden/smpt`.
+    /// A missing golden file fails the test; `UPDATE_GOLDEN=1 cargo test golden` writes
+    /// all of them after an intended change of the encoding.
+    #[test]
+    #[cfg(feature = "isl")]
+    fn test_golden_queries() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let golden_dir = root.join("tests/golden/smpt");
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let mut mismatches = Vec::new();
+        let mut missing = Vec::new();
+        for example in ["b1", "b2", "b3", "b4", "held_lock"] {
+            let json = std::fs::read_to_string(root.join(format!("examples/json/{}.json", example)))
+                .unwrap();
+            let ns = crate::ns::NS::<String, String, String, String>::from_json(&json).unwrap();
+            let (petri, places_that_must_be_zero, ser) = ns.serializability_problem();
+            let disjuncts = crate::reachability_with_proofs::complement_disjuncts(
+                &petri,
+                &places_that_must_be_zero,
+                ser,
+            );
+            for (i, disjunct) in disjuncts.iter().enumerate() {
+                let (_, petri, constraints) =
+                    crate::reachability_with_proofs::reify_existentials(petri.clone(), disjunct);
+                let query = build_query(&petri, &constraints);
+                for (extension, content) in [("net", &query.net), ("xml", &query.xml)] {
+                    let golden = golden_dir.join(format!("{}_{}.{}", example, i, extension));
+                    if update {
+                        std::fs::create_dir_all(&golden_dir).unwrap();
+                        std::fs::write(&golden, content).unwrap();
+                        continue;
+                    }
+                    match std::fs::read_to_string(&golden) {
+                        Ok(expected) => {
+                            if expected != *content {
+                                mismatches.push(golden.display().to_string());
+                            }
+                        }
+                        Err(_) => missing.push(golden.display().to_string()),
+                    }
+                }
+            }
+        }
+        assert!(
+            missing.is_empty(),
+            "Golden files {:?} are missing (write them with UPDATE_GOLDEN=1)",
+            missing
+        );
+        assert!(
+            mismatches.is_empty(),
+            "Generated queries differ from {:?} (rerun with UPDATE_GOLDEN=1 if the change is intended)",
+            mismatches
+        );
+    }
+
+    /// A net as multisets: the sorted initial marking and the transitions with sorted
+    /// inputs and outputs, in order
+    fn structure(petri: &Petri<String>) -> (Vec<String>, Vec<(Vec<String>, Vec<String>)>) {
+        let mut marking = petri.get_initial_marking();
+        marking.sort();
+        let transitions = petri
+            .get_transitions()
+            .into_iter()
+            .map(|(mut input, mut output)| {
+                input.sort();
+                output.sort();
+                (input, output)
+            })
+            .collect();
+        (marking, transitions)
+    }
+
+    #[test]
+    fn test_pnet_roundtrip() {
+        let s = |x: &str| x.to_string();
+        // Names that need escaping, weights, and a transition without outputs
+        let mut petri = Petri::new(vec![s("Left(a, b)"), s("Left(a, b)"), s("x_y")]);
+        petri.add_transition(vec![s("Left(a, b)"), s("Left(a, b)")], vec![s("-> out")]);
+        petri.add_transition(vec![s("x_y")], vec![s("#1"), s("#1"), s("x_y")]);
+        petri.add_transition(vec![s("-> out")], vec![]);
+
+        let pnet = petri_to_pnet(&petri, "round trip");
+        let (name, parsed) = pnet_to_petri(&pnet).unwrap();
+        assert_eq!(name, "round_trip");
+        let decoded = parsed.rename(|place| crate::ident::decode(&place).unwrap());
+        assert_eq!(structure(&decoded), structure(&petri));
+    }
+
+    #[test]
+    fn test_pnet_to_petri_external() {
+        let s = |x: &str| x.to_string();
+        let net = "# written by hand\n\
+                   net {external}\n\
+                   pl p0 (2)\n\
+                   pl p1\n\
+                   tr t0 : move p0*2 -> p1\n\
+                   tr t1 p1 -> p0 p2\n";
+        let (name, petri) = pnet_to_petri(net).unwrap();
+        assert_eq!(name, "external");
+        assert_eq!(petri.get_initial_marking(), vec!["p0", "p0"]);
+        assert_eq!(
+            petri.get_transitions(),
+            vec![
+                (vec![s("p0"), s("p0")], vec![s("p1")]),
+                (vec![s("p1")], vec![s("p0"), s("p2")]),
+            ]
+        );
+
+        assert!(pnet_to_petri("pl p0 (two)").is_err());
+        assert!(pnet_to_petri("tr t0 p0 p1").is_err());
+        assert!(pnet_to_petri("tr t0 p0*x -> p1").is_err());
+        assert!(pnet_to_petri("place p0").is_err());
+    }
+
     #[test]
     fn test_petri_to_pnet_empty() {
         let petri = Petri::new(Vec::<&str>::new());
@@ -1215,9 +1825,9 @@ mod tests {
         let pnet = petri_to_pnet(&petri, "test-net@2");
 
         assert!(pnet.contains("net {test_net_2}"));
-        assert!(pnet.contains("pl P_0 (1)"));
-        assert!(pnet.contains("pl P_1 (1)"));
-        assert!(pnet.contains("tr t0 P_0 -> P_1"));
+        assert!(pnet.contains("pl P_2d_0 (1)"));
+        assert!(pnet.contains("pl P_40_1 (1)"));
+        assert!(pnet.contains("tr t0 P_2d_0 -> P_40_1"));
     }
 
     #[test]
@@ -1249,16 +1859,75 @@ FORMULA reachability-check TRUE TIME 0.403745174407959
         assert_eq!(extract_trace_indices(no_trace), Vec::<usize>::new());
     }
 
+    #[test]
+    fn test_extract_verdict() {
+        assert_eq!(
+            extract_verdict("# Hello\nFORMULA reachability-check TRUE TIME 0.4\n# Bye bye"),
+            Some(true)
+        );
+        assert_eq!(extract_verdict("FORMULA FALSE"), Some(false));
+        // "TRUE" outside of a FORMULA line is no verdict
+        assert_eq!(
+            extract_verdict("[STATE-EQUATION] TRUE\nFORMULA reachability-check ???"),
+            None
+        );
+        assert_eq!(extract_verdict("FORMULA a TRUE\nFORMULA a FALSE"), None);
+        assert_eq!(extract_verdict("# Hello\n"), None);
+
+        let stderr =
+            "Traceback (most recent call last):\n  File \"x.py\", line 1\nKeyError: 'p0'\n";
+        assert_eq!(extract_exception(stderr), Some("KeyError: 'p0'"));
+        assert_eq!(extract_exception("warning: slow"), None);
+    }
+
     #[test]
     fn test_install_smpt_instructions() {
-        // Test that install function provides instructions when SMPT is not installed
-        if !is_smpt_installed() {
+        if !cfg!(feature = "smpt") {
+            let result = install_smpt();
+            assert!(result.unwrap_err().contains("without the smpt feature"));
+        } else if !is_smpt_installed() {
+            // Test that install function provides instructions when SMPT is not installed
             let result = install_smpt();
             assert!(result.is_err());
             assert!(result.unwrap_err().contains("not installed"));
         }
     }
 
+    #[test]
+    fn test_cancellation_token_shared_between_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+
+    #[test]
+    #[cfg(unix)] // `sleep` and `true` are Unix programs
+    fn test_wait_or_cancel_kills_child() {
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let token = CancellationToken::new();
+        let delay = std::time::Duration::from_millis(100);
+        let canceller = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                token.cancel();
+            })
+        };
+
+        // The child is already running when the token is cancelled
+        let start = std::time::Instant::now();
+        let err = wait_or_cancel(child, &token).unwrap_err();
+        canceller.join().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert!(start.elapsed() >= delay);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        let child = Command::new("true").spawn().unwrap();
+        assert!(wait_or_cancel(child, &CancellationToken::new()).unwrap().success());
+    }
+
     #[test]
     fn test_proof_parsing_integration() {
         // Test that proof certificates are parsed when available
@@ -1321,6 +1990,7 @@ FORMULA reachability-check TRUE TIME 0.403745174407959
             )],
             out_dir,
             0, // disjunct_id
+            &CancellationToken::new(),
         );
         match can_produce_result.outcome {
             SmptVerificationOutcome::Reachable { trace, .. } => {
@@ -1345,6 +2015,7 @@ FORMULA reachability-check TRUE TIME 0.403745174407959
             )],
             out_dir,
             1, // disjunct_id
+            &CancellationToken::new(),
         );
         match both_outcomes_result.outcome {
             SmptVerificationOutcome::Reachable { trace, .. } => {
@@ -0,0 +1,311 @@
+//! A fake SMPT backend for failure-injection tests (`--fake-smpt <spec>`).
+//!
+//! Instead of running SMPT, the fake answers every query with output made up to look
+//! like an SMPT run that went wrong: output without a verdict, a run stopped by its
+//! timeout, a proof that is cut off, a trace through transitions the net does not have,
+//! or a crash with a Python traceback. The output goes through the same parsing as the
+//! output of SMPT, so the tests check that each of these failures ends in a Timeout or
+//! Unknown decision, not in a panic or a wrong verdict.
+//!
+//! The fake is part of the tests of this crate, and of the binary when it is built with
+//! the `fake-smpt` feature, so that the tests of tools that drive `ser` can use it too:
+//!
+//! ```sh
+//! cargo build --features fake-smpt
+//! ser --fake-smpt timeout,2=unreachable examples/ser/bank.ser
+//! ```
+
+use crate::petri::Petri;
+use crate::smpt::{CANCELLED_MESSAGE, CancellationToken, SmptVerificationResult};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// What the fake answers to a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeOutcome {
+    /// A clean "unreachable" verdict without a proof
+    Unreachable,
+    /// Output with no verdict in its `FORMULA` line, but "TRUE" elsewhere
+    Malformed,
+    /// Only the startup banner, as when SMPT is stopped by `--timeout`
+    Timeout,
+    /// An "unreachable" verdict whose exported proof is cut off
+    PartialProof,
+    /// A "reachable" verdict with a trace through a transition the net does not have
+    BadTrace,
+    /// A Python exception halfway through the run
+    Crash,
+}
+
+impl FakeOutcome {
+    pub const ALL: [FakeOutcome; 6] = [
+        FakeOutcome::Unreachable,
+        FakeOutcome::Malformed,
+        FakeOutcome::Timeout,
+        FakeOutcome::PartialProof,
+        FakeOutcome::BadTrace,
+        FakeOutcome::Crash,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FakeOutcome::Unreachable => "unreachable",
+            FakeOutcome::Malformed => "malformed",
+            FakeOutcome::Timeout => "timeout",
+            FakeOutcome::PartialProof => "partial-proof",
+            FakeOutcome::BadTrace => "bad-trace",
+            FakeOutcome::Crash => "crash",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|outcome| outcome.name() == name)
+    }
+
+    /// The output of the fake run
+    fn output(self) -> FakeRun {
+        let run = |stdout, stderr, exit_code, proof| FakeRun {
+            stdout,
+            stderr,
+            exit_code,
+            proof,
+        };
+        match self {
+            FakeOutcome::Unreachable => run(UNREACHABLE_STDOUT, "", Some(0), None),
+            FakeOutcome::Malformed => run(
+                "# Hello\n[STATE-EQUATION] TRUE\nFORMULA reachability-check ??? TIME 0.01\n# Bye bye\n",
+                "",
+                Some(0),
+                None,
+            ),
+            FakeOutcome::Timeout => run("# Hello\n", "", Some(1), None),
+            FakeOutcome::PartialProof => run(
+                UNREACHABLE_STDOUT,
+                "",
+                Some(0),
+                Some("(define-fun cert ((p0 Int) (p1 Int)) Bool\n    (and (>= p0 0) (>= "),
+            ),
+            FakeOutcome::BadTrace => run(
+                "# Hello\n[BMC] Trace\nt0 t4294967295\nFORMULA reachability-check TRUE TIME 0.01\n# Bye bye\n",
+                "",
+                Some(0),
+                None,
+            ),
+            FakeOutcome::Crash => run(
+                "# Hello\n",
+                "Traceback (most recent call last):\n  File \"smpt/__main__.py\", line 1, in <module>\nRuntimeError: fake SMPT crash\n",
+                Some(1),
+                None,
+            ),
+        }
+    }
+}
+
+const UNREACHABLE_STDOUT: &str = "# Hello\nFORMULA reachability-check FALSE TIME 0.01\n# Bye bye\n";
+
+/// What a fake SMPT run prints, and the proof it exports
+struct FakeRun {
+    stdout: &'static str,
+    stderr: &'static str,
+    exit_code: Option<i32>,
+    proof: Option<&'static str>,
+}
+
+/// The fake backend: an outcome for every disjunct, and one for the others
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeSmpt {
+    default: FakeOutcome,
+    disjuncts: Vec<(usize, FakeOutcome)>,
+}
+
+impl FakeSmpt {
+    /// Answer every query with `outcome`
+    pub fn new(outcome: FakeOutcome) -> Self {
+        FakeSmpt {
+            default: outcome,
+            disjuncts: Vec::new(),
+        }
+    }
+
+    /// Answer the queries of `disjunct` with `outcome`
+    pub fn with_disjunct(mut self, disjunct: usize, outcome: FakeOutcome) -> Self {
+        self.disjuncts.retain(|(d, _)| *d != disjunct);
+        self.disjuncts.push((disjunct, outcome));
+        self
+    }
+
+    /// Parse a comma-separated list of `<outcome>` (the default, `unreachable` if there
+    /// is none) and `<disjunct>=<outcome>` entries
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let outcome = |name: &str| {
+            FakeOutcome::from_name(name).ok_or_else(|| {
+                let names: Vec<_> = FakeOutcome::ALL.iter().map(|o| o.name()).collect();
+                format!(
+                    "unknown fake SMPT outcome '{}' (expected {})",
+                    name,
+                    names.join(", ")
+                )
+            })
+        };
+        let mut fake = FakeSmpt::new(FakeOutcome::Unreachable);
+        for entry in spec.split(',').map(str::trim) {
+            match entry.split_once('=') {
+                Some((disjunct, name)) => {
+                    let disjunct = disjunct
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid disjunct '{}'", disjunct.trim()))?;
+                    fake = fake.with_disjunct(disjunct, outcome(name.trim())?);
+                }
+                None => fake.default = outcome(entry)?,
+            }
+        }
+        Ok(fake)
+    }
+
+    pub fn outcome(&self, disjunct: usize) -> FakeOutcome {
+        self.disjuncts
+            .iter()
+            .find(|(d, _)| *d == disjunct)
+            .map_or(self.default, |(_, outcome)| *outcome)
+    }
+
+    /// Answer the query of `disjunct` whose formula is in `xml_file`, like
+    /// `smpt::run_smpt` does by running SMPT
+    pub fn run<P>(
+        &self,
+        xml_file: &str,
+        disjunct: usize,
+        petri: &Petri<P>,
+        cancel: &CancellationToken,
+    ) -> SmptVerificationResult<P>
+    where
+        P: Clone + Hash + Ord + Display + Debug,
+    {
+        if cancel.is_cancelled() {
+            return SmptVerificationResult::error(CANCELLED_MESSAGE.to_string());
+        }
+        let output = self.outcome(disjunct).output();
+
+        // The proof goes where SMPT exports it, replacing the one of an earlier run
+        let proof_file_path = xml_file.replace(".xml", "_proof.txt");
+        let _ = std::fs::remove_file(format!("{}.scn", proof_file_path));
+        let written = match output.proof {
+            Some(proof) => std::fs::write(&proof_file_path, proof),
+            None => match std::fs::remove_file(&proof_file_path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+        };
+        if let Err(err) = written {
+            return SmptVerificationResult::error(format!(
+                "Failed to write fake proof {}: {}",
+                proof_file_path, err
+            ));
+        }
+
+        crate::smpt::parse_smpt_output(
+            output.stdout.to_string(),
+            output.stderr.to_string(),
+            output.exit_code,
+            &proof_file_path,
+            Some(crate::smpt::get_smpt_timeout()),
+            petri,
+        )
+    }
+}
+
+/// Answer SMPT queries with the fake instead of SMPT (called from `main.rs`)
+pub fn set_fake_smpt(fake: Option<FakeSmpt>) {
+    crate::options::update(|options| options.fake_smpt = fake);
+}
+
+pub fn fake_smpt() -> Option<FakeSmpt> {
+    crate::options::get(|options| options.fake_smpt.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::{Constraint, ConstraintType};
+    use crate::options::{AnalysisOptions, with_options};
+    use crate::reachability_with_proofs::{Decision, UnknownReason};
+
+    #[test]
+    fn test_fake_smpt_spec() {
+        let fake = FakeSmpt::from_spec("timeout, 2=crash,0=bad-trace").unwrap();
+        assert_eq!(fake.outcome(0), FakeOutcome::BadTrace);
+        assert_eq!(fake.outcome(1), FakeOutcome::Timeout);
+        assert_eq!(fake.outcome(2), FakeOutcome::Crash);
+        assert_eq!(
+            FakeSmpt::from_spec("1=malformed").unwrap().outcome(0),
+            FakeOutcome::Unreachable
+        );
+        assert!(
+            FakeSmpt::from_spec("flaky")
+                .unwrap_err()
+                .contains("partial-proof")
+        );
+        assert!(FakeSmpt::from_spec("x=crash").is_err());
+    }
+
+    /// Every failure of SMPT ends in a Timeout or Unknown decision
+    #[test]
+    fn test_failure_injection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let out_dir = temp_dir.path().to_str().unwrap();
+        // `A` is reachable, so neither the marking equation nor pruning decides the query
+        let mut petri = Petri::new(vec!["Start"]);
+        petri.add_transition(vec!["Start"], vec!["A"]);
+        let constraints = vec![Constraint::new(
+            vec![(1, "A")],
+            -1,
+            ConstraintType::NonNegative,
+        )];
+
+        for outcome in FakeOutcome::ALL {
+            let options = AnalysisOptions {
+                fake_smpt: Some(FakeSmpt::new(outcome)),
+                ..AnalysisOptions::default()
+            };
+            let decision = with_options(options, || {
+                crate::reachability_with_proofs::can_reach_constraint_set_with_debug(
+                    petri.clone(),
+                    constraints.clone(),
+                    out_dir,
+                    0,
+                    &CancellationToken::new(),
+                )
+            });
+            match (outcome, &decision) {
+                (FakeOutcome::Unreachable, Decision::Proof { proof: None }) => {}
+                (FakeOutcome::Timeout, Decision::Timeout { .. }) => {}
+                (
+                    FakeOutcome::Malformed
+                    | FakeOutcome::PartialProof
+                    | FakeOutcome::BadTrace
+                    | FakeOutcome::Crash,
+                    Decision::Unknown {
+                        reason: UnknownReason::SolverUnknown { .. },
+                        ..
+                    },
+                ) => {}
+                _ => panic!("{}: unexpected decision {:?}", outcome.name(), decision),
+            }
+        }
+
+        // A cancelled query is not answered
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result = FakeSmpt::new(FakeOutcome::Unreachable).run(
+            &crate::utils::file::join(out_dir, "query.xml"),
+            0,
+            &petri,
+            &cancel,
+        );
+        assert!(matches!(
+            result.outcome,
+            crate::smpt::SmptVerificationOutcome::Error { message } if message == CANCELLED_MESSAGE
+        ));
+    }
+}
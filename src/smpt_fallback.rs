@@ -0,0 +1,365 @@
+//! Reachability checks that need no external solver, used when SMPT is not installed.
+//!
+//! Two checks run in order:
+//! 1. A structural check: places that can never hold a token (every transition
+//!    producing into them needs another such place) are zero in every reachable
+//!    marking. If the target constraints are unsatisfiable with those places set to
+//!    zero, the disjunct is unreachable; "all these places are zero" is an inductive
+//!    invariant and serves as the certificate.
+//! 2. An explicit breadth-first search over markings, bounded by
+//!    `MAX_EXPLORED_MARKINGS`. Hitting the target gives a genuine counterexample; an
+//!    exhausted (finite) state space gives a certificate listing all reachable markings.
+//!
+//! If the budget runs out first, the result is `Unknown`, labeled as a best-effort
+//! verdict, since nets with request creation usually have infinite state spaces.
+
+use crate::constraints::{Constraint, ConstraintType};
+use crate::deterministic_map::{HashMap, HashSet};
+use crate::petri::Petri;
+use crate::proof_parser::{AffineExpr, CompOp, Formula, ProofInvariant};
+use crate::reachability_with_proofs::{Decision, UnknownReason};
+use crate::smpt::CancellationToken;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Maximum number of markings the explicit search visits per disjunct
+pub const MAX_EXPLORED_MARKINGS: usize = 10_000;
+
+/// Whether any disjunct of this run was decided without SMPT
+static FALLBACK_USED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the verdict of this run relies on the pure-Rust fallback
+pub fn fallback_used() -> bool {
+    FALLBACK_USED.load(Ordering::SeqCst)
+}
+
+/// Decide a disjunct without SMPT, see the module documentation
+pub fn decide<P>(
+    petri: &Petri<P>,
+    constraints: &[Constraint<P>],
+    disjunct_id: usize,
+    cancel: &CancellationToken,
+) -> Decision<P>
+where
+    P: Clone + Hash + Ord + Display + Debug,
+{
+    FALLBACK_USED.store(true, Ordering::SeqCst);
+
+    let mut places = petri.get_places();
+    places.sort();
+
+    if let Some(proof) = structural_proof(petri, &places, constraints) {
+        println!(
+            "Disjunct {} is unreachable: it needs places that can never be marked",
+            disjunct_id
+        );
+        return Decision::Proof { proof: Some(proof) };
+    }
+
+    let index: HashMap<P, usize> = places
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, p)| (p, i))
+        .collect();
+    let transitions: Vec<(Vec<usize>, Vec<usize>)> = petri
+        .get_transitions()
+        .iter()
+        .map(|(input, output)| {
+            (
+                input.iter().map(|p| index[p]).collect(),
+                output.iter().map(|p| index[p]).collect(),
+            )
+        })
+        .collect();
+
+    let mut initial = vec![0usize; places.len()];
+    for p in petri.get_initial_marking() {
+        initial[index[&p]] += 1;
+    }
+
+    // Every visited marking with the (parent, transition) that first reached it
+    let mut visited: HashMap<Vec<usize>, Option<(Vec<usize>, usize)>> = HashMap::default();
+    let mut queue = VecDeque::new();
+    visited.insert(initial.clone(), None);
+    queue.push_back(initial);
+
+    while let Some(marking) = queue.pop_front() {
+        if cancel.is_cancelled() {
+            return Decision::Unknown {
+                reason: UnknownReason::Interrupted,
+                partial_results: Vec::new(),
+            };
+        }
+        if satisfies(&marking, constraints, &index) {
+            println!(
+                "Disjunct {} is reachable (found by explicit search without SMPT)",
+                disjunct_id
+            );
+            let trace = reconstruct_trace(petri, &visited, marking);
+            return Decision::CounterExample { trace };
+        }
+        for (t, (input, output)) in transitions.iter().enumerate() {
+            let mut next = marking.clone();
+            let mut enabled = true;
+            for &p in input {
+                if next[p] == 0 {
+                    enabled = false;
+                    break;
+                }
+                next[p] -= 1;
+            }
+            if !enabled {
+                continue;
+            }
+            for &p in output {
+                next[p] += 1;
+            }
+            if visited.contains_key(&next) {
+                continue;
+            }
+            if visited.len() >= MAX_EXPLORED_MARKINGS {
+                return Decision::Unknown {
+                    reason: UnknownReason::ApproximationLoss {
+                        message: format!(
+                            "SMPT is not available; explicit search explored {} markings without \
+                             reaching the target (best-effort verdict)",
+                            MAX_EXPLORED_MARKINGS
+                        ),
+                    },
+                    partial_results: Vec::new(),
+                };
+            }
+            visited.insert(next.clone(), Some((marking.clone(), t)));
+            queue.push_back(next);
+        }
+    }
+
+    println!(
+        "Disjunct {} is unreachable: explored all {} reachable markings without SMPT",
+        disjunct_id,
+        visited.len()
+    );
+    Decision::Proof {
+        proof: Some(reachable_set_proof(&places, visited.keys())),
+    }
+}
+
+/// Places that can hold a token in some reachable marking (over-approximation)
+fn markable_places<P>(petri: &Petri<P>) -> HashSet<P>
+where
+    P: Clone + Hash + Eq,
+{
+    let mut markable: HashSet<P> = petri.get_initial_marking().into_iter().collect();
+    let transitions = petri.get_transitions();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (input, output) in &transitions {
+            if input.iter().all(|p| markable.contains(p)) {
+                for p in output {
+                    changed |= markable.insert(p.clone());
+                }
+            }
+        }
+    }
+    markable
+}
+
+/// Certificate for the structural check, if it applies
+fn structural_proof<P>(
+    petri: &Petri<P>,
+    places: &[P],
+    constraints: &[Constraint<P>],
+) -> Option<ProofInvariant<P>>
+where
+    P: Clone + Hash + Ord + Display,
+{
+    let markable = markable_places(petri);
+    let unsatisfiable = constraints.iter().any(|c| {
+        let coefficients: Vec<i32> = c
+            .linear_combination()
+            .iter()
+            .filter(|(coeff, p)| *coeff != 0 && markable.contains(p))
+            .map(|(coeff, _)| *coeff)
+            .collect();
+        let constant = c.constant_term();
+        let all_nonpositive = coefficients.iter().all(|&k| k <= 0);
+        let all_nonnegative = coefficients.iter().all(|&k| k >= 0);
+        match c.constraint_type() {
+            ConstraintType::NonNegative => all_nonpositive && constant < 0,
+            ConstraintType::EqualToZero => {
+                (all_nonpositive && constant < 0) || (all_nonnegative && constant > 0)
+            }
+        }
+    });
+    if !unsatisfiable {
+        return None;
+    }
+    let zero_constraints = places
+        .iter()
+        .filter(|p| !markable.contains(*p))
+        .map(|p| {
+            Formula::Constraint(crate::proof_parser::Constraint::new(
+                AffineExpr::from_var(p.clone()),
+                CompOp::Eq,
+            ))
+        })
+        .collect();
+    Some(ProofInvariant::new(
+        places.to_vec(),
+        Formula::And(zero_constraints),
+    ))
+}
+
+/// Certificate listing every reachable marking of a finite state space
+fn reachable_set_proof<'a, P>(
+    places: &[P],
+    markings: impl Iterator<Item = &'a Vec<usize>>,
+) -> ProofInvariant<P>
+where
+    P: Clone + Hash + Ord + Display,
+{
+    let disjuncts = markings
+        .map(|marking| {
+            Formula::And(
+                places
+                    .iter()
+                    .zip(marking)
+                    .map(|(p, &count)| {
+                        Formula::Constraint(crate::proof_parser::Constraint::new(
+                            AffineExpr::from_var(p.clone())
+                                .sub(&AffineExpr::from_const(count as i64)),
+                            CompOp::Eq,
+                        ))
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    ProofInvariant::new(places.to_vec(), Formula::Or(disjuncts))
+}
+
+fn satisfies<P>(marking: &[usize], constraints: &[Constraint<P>], index: &HashMap<P, usize>) -> bool
+where
+    P: Hash + Eq,
+{
+    constraints.iter().all(|c| {
+        let value: i64 = c
+            .linear_combination()
+            .iter()
+            .map(|(coeff, p)| *coeff as i64 * index.get(p).map_or(0, |&i| marking[i] as i64))
+            .sum::<i64>()
+            + c.constant_term() as i64;
+        match c.constraint_type() {
+            ConstraintType::NonNegative => value >= 0,
+            ConstraintType::EqualToZero => value == 0,
+        }
+    })
+}
+
+/// Follow the parent pointers back to the initial marking
+pub(crate) fn reconstruct_trace<P>(
+    petri: &Petri<P>,
+    visited: &HashMap<Vec<usize>, Option<(Vec<usize>, usize)>>,
+    mut marking: Vec<usize>,
+) -> Vec<(Vec<P>, Vec<P>)>
+where
+    P: Clone + Hash + Eq,
+{
+    let transitions = petri.get_transitions();
+    let mut trace = Vec::new();
+    while let Some(Some((parent, t))) = visited.get(&marking) {
+        trace.push(transitions[*t].clone());
+        marking = parent.clone();
+    }
+    trace.reverse();
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_least_one(place: &str) -> Constraint<String> {
+        Constraint::new(
+            vec![(1, place.to_string())],
+            -1,
+            ConstraintType::NonNegative,
+        )
+    }
+
+    #[test]
+    fn test_structural_unreachability() {
+        // c is only produced from b, and b is never produced
+        let mut petri = Petri::new(vec!["a".to_string()]);
+        petri.add_transition(vec!["b".to_string()], vec!["c".to_string()]);
+        let decision = decide(&petri, &[at_least_one("c")], 0, &CancellationToken::new());
+        assert!(matches!(decision, Decision::Proof { proof: Some(_) }));
+    }
+
+    #[test]
+    fn test_explicit_search() {
+        // a -> b -> c in a one-token net
+        let mut petri = Petri::new(vec!["a".to_string()]);
+        petri.add_transition(vec!["a".to_string()], vec!["b".to_string()]);
+        petri.add_transition(vec!["b".to_string()], vec!["c".to_string()]);
+        let cancel = CancellationToken::new();
+
+        match decide(&petri, &[at_least_one("c")], 0, &cancel) {
+            Decision::CounterExample { trace } => assert_eq!(trace.len(), 2),
+            other => panic!("Expected a counterexample, got {:?}", other),
+        }
+
+        // Two tokens in b are never reachable; the state space has three markings
+        let two_in_b = Constraint::new(vec![(1, "b".to_string())], -2, ConstraintType::NonNegative);
+        match decide(&petri, &[two_in_b], 0, &cancel) {
+            Decision::Proof { proof: Some(proof) } => match proof.formula {
+                Formula::Or(markings) => assert_eq!(markings.len(), 3),
+                other => panic!("Expected a disjunction of markings, got {:?}", other),
+            },
+            other => panic!("Expected a proof, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_infinite_state_space_is_inconclusive() {
+        // a is created freely while the token moves from x to y, so x + y = 0 is
+        // unreachable but the search never runs out of markings
+        let mut petri = Petri::new(vec!["x".to_string()]);
+        petri.add_transition(vec![], vec!["a".to_string()]);
+        petri.add_transition(vec!["x".to_string()], vec!["y".to_string()]);
+        let never = Constraint::new(
+            vec![(1, "x".to_string()), (1, "y".to_string())],
+            0,
+            ConstraintType::EqualToZero,
+        );
+        let decision = decide(&petri, &[never], 0, &CancellationToken::new());
+        assert!(matches!(
+            decision,
+            Decision::Unknown {
+                reason: UnknownReason::ApproximationLoss { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cancelled_search_is_interrupted() {
+        let mut petri = Petri::new(vec!["a".to_string()]);
+        petri.add_transition(vec!["a".to_string()], vec!["b".to_string()]);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let decision = decide(&petri, &[at_least_one("b")], 0, &cancel);
+        assert!(matches!(
+            decision,
+            Decision::Unknown {
+                reason: UnknownReason::Interrupted,
+                ..
+            }
+        ));
+    }
+}
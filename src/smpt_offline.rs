@@ -0,0 +1,214 @@
+//! Running SMPT queries elsewhere.
+//!
+//! With `--export-smpt-queries`, every SMPT query is written to
+//! `<out_dir>/smpt_queries/` instead of being run: a `.net` and an `.xml` file per
+//! query, a `smpt_manifest.json` listing them with the SMPT command line, and a
+//! `run_queries.sh` that runs them all. Each query is named after its disjunct and a
+//! hash of its Petri net and constraints, so the names are the same in every run on
+//! the same input and options.
+//!
+//! After running the queries (e.g. on a cluster), `--import-smpt-results <dir>` runs
+//! the analysis again and reads each verdict from `<dir>/<query>.stdout` (and proofs
+//! from `<dir>/<query>_proof.txt`) instead of calling SMPT.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// File name of the manifest in the query directory
+pub const MANIFEST_FILE: &str = "smpt_manifest.json";
+
+/// Whether SMPT queries are run, exported or answered from imported results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OfflineMode {
+    Off,
+    Export,
+    /// Directory with the outputs of the exported queries
+    Import(String),
+}
+
+/// Serializes updates of the manifest from parallel disjunct checks
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set the offline mode (called from `main.rs`)
+pub fn set_offline_mode(mode: OfflineMode) {
+    crate::options::update(|options| options.smpt_offline = mode);
+}
+
+pub fn offline_mode() -> OfflineMode {
+    crate::options::get(|options| options.smpt_offline.clone())
+}
+
+/// One exported query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryEntry {
+    pub id: String,
+    pub disjunct_id: usize,
+    pub net_file: String,
+    pub xml_file: String,
+    /// Where the command's standard output must be saved
+    pub stdout_file: String,
+    pub proof_file: String,
+    /// SMPT command line, relative to the query directory
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub queries: Vec<QueryEntry>,
+}
+
+/// Stable name of a query, from its disjunct and a hash of its content
+pub fn query_id(disjunct_id: usize, content_hash: u64) -> String {
+    format!("disjunct_{}_{:016x}", disjunct_id, content_hash)
+}
+
+/// Directory exported queries of an analysis go to
+pub fn query_dir(out_dir: &str) -> String {
+    crate::utils::file::join(out_dir, "smpt_queries")
+}
+
+/// Write a query and add it to the manifest (replacing an entry with the same id)
+pub fn export_query(
+    out_dir: &str,
+    id: &str,
+    disjunct_id: usize,
+    net_content: &str,
+    xml_content: &str,
+    smpt_args: impl Fn(&str, &str, &str) -> Vec<String>,
+) -> Result<QueryEntry, String> {
+    let dir = query_dir(out_dir);
+    let entry = QueryEntry {
+        id: id.to_string(),
+        disjunct_id,
+        net_file: format!("{}.net", id),
+        xml_file: format!("{}.xml", id),
+        stdout_file: format!("{}.stdout", id),
+        proof_file: format!("{}_proof.txt", id),
+        command: Vec::new(),
+    };
+    let mut command = vec![
+        "python3".to_string(),
+        "-m".to_string(),
+        crate::smpt::SMPT_PYTHON_MODULE.to_string(),
+    ];
+    command.extend(smpt_args(
+        &entry.net_file,
+        &entry.xml_file,
+        &entry.proof_file,
+    ));
+    let entry = QueryEntry { command, ..entry };
+
+    let write = |name: &str, content: &str| {
+        let path = crate::utils::file::join(&dir, name);
+        crate::utils::file::safe_write_file(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path, e))
+    };
+    write(&entry.net_file, net_content)?;
+    write(&entry.xml_file, xml_content)?;
+
+    let _guard = MANIFEST_LOCK.lock().unwrap();
+    let manifest_path = crate::utils::file::join(&dir, MANIFEST_FILE);
+    let mut manifest = match load_manifest(&manifest_path) {
+        Ok(manifest) => manifest,
+        Err(_) => Manifest {
+            version: MANIFEST_VERSION,
+            queries: Vec::new(),
+        },
+    };
+    manifest.queries.retain(|q| q.id != entry.id);
+    manifest.queries.push(entry.clone());
+    manifest.queries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    write(MANIFEST_FILE, &json)?;
+    write("run_queries.sh", &run_script(&manifest))?;
+    Ok(entry)
+}
+
+/// Shell script that runs every query of a manifest in its directory
+fn run_script(manifest: &Manifest) -> String {
+    let mut script = String::from(
+        "#!/bin/sh\n# Run from this directory; each query may run on a different machine.\n",
+    );
+    for query in &manifest.queries {
+        script.push_str(&format!(
+            "{} > {}\n",
+            query.command.join(" "),
+            query.stdout_file
+        ));
+    }
+    script
+}
+
+pub fn load_manifest(path: &str) -> Result<Manifest, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let manifest: Manifest = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(format!(
+            "unsupported manifest version {} (expected {})",
+            manifest.version, MANIFEST_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+/// Standard output and proof file path of an imported query result
+pub fn imported_result(dir: &str, id: &str) -> Result<(String, String), String> {
+    let stdout_path = crate::utils::file::join(dir, &format!("{}.stdout", id));
+    let stdout = std::fs::read_to_string(&stdout_path).map_err(|e| {
+        format!(
+            "No result for SMPT query {} ({}: {}); was it exported with the same input and options?",
+            id, stdout_path, e
+        )
+    })?;
+    let proof_path = crate::utils::file::join(dir, &format!("{}_proof.txt", id));
+    Ok((stdout, proof_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_import() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let out_dir = out_dir.path().to_str().unwrap();
+        let args = |net: &str, xml: &str, proof: &str| {
+            vec![
+                "-n".to_string(),
+                net.to_string(),
+                "--xml".to_string(),
+                xml.to_string(),
+                "--export-proof".to_string(),
+                proof.to_string(),
+            ]
+        };
+
+        let id = query_id(3, 0xabc);
+        assert_eq!(id, "disjunct_3_0000000000000abc");
+        export_query(out_dir, &id, 3, "net {n}\n", "<property-set/>", args).unwrap();
+        // Exporting the same query again does not duplicate it
+        export_query(out_dir, &id, 3, "net {n}\n", "<property-set/>", args).unwrap();
+
+        let dir = query_dir(out_dir);
+        let manifest = load_manifest(&crate::utils::file::join(&dir, MANIFEST_FILE)).unwrap();
+        assert_eq!(manifest.queries.len(), 1);
+        assert_eq!(
+            manifest.queries[0].command[3..5],
+            ["-n", "disjunct_3_0000000000000abc.net"]
+        );
+        let file = |name: &str| crate::utils::file::join(&dir, name);
+        let script = std::fs::read_to_string(file("run_queries.sh")).unwrap();
+        assert!(script.contains("> disjunct_3_0000000000000abc.stdout"));
+
+        assert!(imported_result(&dir, &id).is_err());
+        std::fs::write(file(&format!("{}.stdout", id)), "FORMULA FALSE").unwrap();
+        let (stdout, proof) = imported_result(&dir, &id).unwrap();
+        assert_eq!(stdout, "FORMULA FALSE");
+        assert!(proof.ends_with("disjunct_3_0000000000000abc_proof.txt"));
+    }
+}
@@ -8,9 +8,14 @@
 use crate::kleene::Kleene;
 use crate::presburger::PresburgerSet;
 use crate::semilinear::SemilinearSet;
+use crate::stats::{RepresentationEvent, record_representation_event};
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
+/// Operations whose estimated semilinear result exceeds this many linear-set
+/// components are done in the Presburger (ISL) representation instead.
+pub const SEMILINEAR_COMPONENT_LIMIT: usize = 4096;
+
 /// A set type that combines both SemilinearSet and PresburgerSet capabilities.
 ///
 /// Mathematically, Presburger sets and semilinear sets represent the same class of sets,
@@ -18,7 +23,11 @@ use std::hash::Hash;
 /// - SemilinearSet supports Kleene star operation
 /// - PresburgerSet supports complement operation via ISL
 ///
-/// This type automatically converts between representations as needed.
+/// This type automatically converts between representations as needed. Since there
+/// is no conversion back from Presburger to semilinear, sets stay semilinear as long
+/// as that is cheap (unions, stars, and products or differences whose estimated
+/// result size is below `SEMILINEAR_COMPONENT_LIMIT`), and switch to ISL otherwise.
+/// Every decision is recorded in the statistics (`RepresentationStats`).
 #[derive(Debug, Clone)]
 pub enum SPresburgerSet<T: Clone + Ord + Debug + ToString + Eq + Hash> {
     Semilinear(SemilinearSet<T>),
@@ -78,6 +87,7 @@ where
         match self {
             SPresburgerSet::Semilinear(sset) => {
                 // Convert to presburger
+                record_representation_event(RepresentationEvent::ConvertedToPresburger);
                 let pset = PresburgerSet::from_semilinear_set(sset);
                 *self = SPresburgerSet::Presburger(pset);
             }
@@ -146,7 +156,29 @@ where
 
     /// Difference of two sets (self - other)
     pub fn difference(mut self, mut other: Self) -> Self {
-        // Convert both to presburger for difference
+        // Stay semilinear if both sides are in the fragment that SemilinearSet can subtract
+        // directly and the result is not expected to blow up
+        if let (SPresburgerSet::Semilinear(a), SPresburgerSet::Semilinear(b)) = (&self, &other) {
+            let mut alphabet = Vec::new();
+            a.for_each_key(|k| alphabet.push(k.clone()));
+            b.for_each_key(|k| alphabet.push(k.clone()));
+            alphabet.sort();
+            alphabet.dedup();
+            let estimated = estimate_difference_components(a, b, alphabet.len());
+            if estimated > SEMILINEAR_COMPONENT_LIMIT {
+                record_representation_event(RepresentationEvent::EstimatedTooLarge);
+            } else if let Some(result) = a.difference(b, &alphabet) {
+                record_representation_event(RepresentationEvent::NativeDifference {
+                    estimated,
+                    actual: result.components.len(),
+                });
+                return SPresburgerSet::Semilinear(result);
+            } else {
+                record_representation_event(RepresentationEvent::NativeDifferenceFallback);
+            }
+        }
+
+        // Otherwise convert both to presburger for difference
         self.ensure_presburger();
         other.ensure_presburger();
         match (self, other) {
@@ -301,7 +333,15 @@ where
     }
 
     fn times(mut self, mut other: Self) -> Self {
-        // Minkowski sum - convert both to presburger
+        // Minkowski sum - stay semilinear (so that a later star still works) unless the
+        // product of the component counts gets too large
+        if let (SPresburgerSet::Semilinear(a), SPresburgerSet::Semilinear(b)) = (&self, &other) {
+            if a.components.len().saturating_mul(b.components.len()) <= SEMILINEAR_COMPONENT_LIMIT {
+                record_representation_event(RepresentationEvent::SemilinearProduct);
+                return SPresburgerSet::Semilinear(a.clone().times(b.clone()));
+            }
+        }
+        record_representation_event(RepresentationEvent::PresburgerProduct);
         self.ensure_presburger();
         other.ensure_presburger();
         match (self, other) {
@@ -322,6 +362,22 @@ where
     }
 }
 
+/// Rough estimate of the number of components `SemilinearSet::difference` produces.
+/// Subtracting one box splits each remaining box into at most 2·dims pieces, but
+/// subsumption pruning usually keeps the growth close to additive, so we estimate
+/// |a| · |b| · 2·dims rather than the exponential worst case. The estimate is
+/// recorded next to the actual size so this can be checked on real runs.
+fn estimate_difference_components<T: Clone + Ord + Hash>(
+    a: &SemilinearSet<T>,
+    b: &SemilinearSet<T>,
+    dims: usize,
+) -> usize {
+    a.components
+        .len()
+        .saturating_mul(b.components.len().max(1))
+        .saturating_mul(dims.max(1).saturating_mul(2))
+}
+
 impl<T> PartialEq for SPresburgerSet<T>
 where
     T: Clone + Ord + Debug + ToString + Eq + Hash,
@@ -1603,4 +1659,55 @@ mod tests {
 
         println!("✅ for_each_key on PresburgerSet tests passed");
     }
+
+    #[test]
+    fn test_times_stays_semilinear() {
+        // Products of small semilinear sets stay semilinear, so they can still be starred
+        let ab = SPresburgerSet::atom('a').times(SPresburgerSet::atom('b'));
+        assert!(matches!(ab, SPresburgerSet::Semilinear(_)));
+        let ab_star = ab.star();
+        let expected = SPresburgerSet::from_semilinear(
+            SemilinearSet::atom('a').times(SemilinearSet::atom('b')).star(),
+        );
+        assert_eq!(ab_star, expected);
+
+        // Products involving a Presburger set are done in ISL
+        let mixed = SPresburgerSet::atom('a')
+            .times(SPresburgerSet::from_presburger(PresburgerSet::atom('b')));
+        assert!(matches!(mixed, SPresburgerSet::Presburger(_)));
+    }
+
+    #[test]
+    fn test_difference_estimate() {
+        let universe = SemilinearSet::universe(vec!['a', 'b']);
+        let set = SemilinearSet::atom('a').plus(SemilinearSet::atom('b'));
+        assert_eq!(estimate_difference_components(&universe, &set, 2), 8);
+        assert_eq!(
+            estimate_difference_components(&universe, &set, usize::MAX),
+            usize::MAX
+        );
+    }
+
+    #[test]
+    fn test_semilinear_difference_avoids_presburger() {
+        let universe = SPresburgerSet::universe(vec!['a', 'b']);
+        let set = SPresburgerSet::atom('a').union(SPresburgerSet::atom('b').star());
+
+        // Both operands are boxes, so the difference is computed without ISL
+        let difference = universe.clone().difference(set.clone());
+        assert!(matches!(difference, SPresburgerSet::Semilinear(_)));
+
+        let mut universe_p = universe;
+        universe_p.ensure_presburger();
+        let expected = universe_p.difference(set.clone());
+        assert!(matches!(expected, SPresburgerSet::Presburger(_)));
+        assert_eq!(difference, expected);
+
+        // (a b)* is not a box, so we fall back to the Presburger representation
+        let pairs = SPresburgerSet::from_semilinear(
+            SemilinearSet::atom('a').times(SemilinearSet::atom('b')).star(),
+        );
+        let fallback = SPresburgerSet::universe(vec!['a', 'b']).difference(pairs);
+        assert!(matches!(fallback, SPresburgerSet::Presburger(_)));
+    }
 }
@@ -4,16 +4,16 @@ use std::io::Write;
 use std::sync::Mutex;
 use std::time::Instant;
 use chrono::{DateTime, Utc};
-use crate::reachability::BIDIRECTIONAL_PRUNING_ENABLED;
-use crate::semilinear::{GENERATE_LESS, REMOVE_REDUNDANT};
-use crate::kleene::SMART_ORDER;
-use std::sync::atomic::Ordering;
+use crate::reachability::optimize_enabled;
+use crate::semilinear::{remove_redundant_enabled, semilinear_strategy};
+use crate::kleene::{KleeneOrder, kleene_order};
 
 lazy_static::lazy_static! {
     pub static ref STATS_COLLECTOR: Mutex<StatsCollector> = Mutex::new(StatsCollector::new());
     pub static ref CURRENT_DISJUNCT_STATS: Mutex<DisjunctStatsCollector> = Mutex::new(DisjunctStatsCollector::new());
 }
 
+#[derive(Default)]
 pub struct DisjunctStatsCollector {
     disjunct_id: usize,
     initial_places: usize,
@@ -73,7 +73,7 @@ pub struct SerializabilityStats {
     pub timestamp: DateTime<Utc>,
     pub example: String,
     pub options: OptimizationOptions,
-    pub result: String, // "serializable", "not_serializable", "error", "timeout"
+    pub result: String, // "serializable", "not_serializable", "error", "timeout", "unknown"
     pub certificate_creation_time_ms: Option<u64>,
     pub certificate_checking_time_ms: Option<u64>,
     pub num_disjuncts: usize,
@@ -82,6 +82,94 @@ pub struct SerializabilityStats {
     pub total_time_ms: u64,
     pub smpt_calls: usize,
     pub smpt_timeouts: usize,
+    /// Disjuncts that were never queried because the outcome was already decided
+    #[serde(default)]
+    pub cancelled_disjuncts: usize,
+    /// Estimated time saved by not querying the cancelled disjuncts
+    #[serde(default)]
+    pub estimated_time_saved_ms: u64,
+    /// Representation choices made by SPresburgerSet
+    #[serde(default)]
+    pub representation: RepresentationStats,
+    /// Disjuncts proved unreachable by the marking equation prefilter
+    #[serde(default)]
+    pub lp_prefilter_proofs: usize,
+    /// Output directory of the run; `stats.json` is written there as well
+    #[serde(default)]
+    pub out_dir: Option<String>,
+    /// Wall-clock time of the individual pipeline stages, in execution order
+    #[serde(default)]
+    pub stage_timings_ms: Vec<(String, u64)>,
+    /// Total number of constraints in the invariant of a serializability proof
+    #[serde(default)]
+    pub invariant_size: Option<usize>,
+    #[serde(default)]
+    pub smpt_cache_hits: usize,
+    #[serde(default)]
+    pub smpt_cache_misses: usize,
+    /// Size thresholds that were exceeded, and what was done about it
+    #[serde(default)]
+    pub size_threshold_events: Vec<String>,
+    /// Heuristic and total label size after each eliminated state, per state elimination
+    #[serde(default)]
+    pub kleene_eliminations: Vec<KleeneElimination>,
+    /// How the `--auto-timeout` budget was spent, if there was one
+    #[serde(default)]
+    pub timeout_budget: Option<TimeoutBudgetStats>,
+}
+
+/// Read-only view of the statistics of the current run, for embedding users
+pub type Snapshot = SerializabilityStats;
+
+/// Counters for the SPresburgerSet representation switching heuristics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepresentationStats {
+    pub conversions_to_presburger: usize,
+    pub semilinear_products: usize,
+    pub presburger_products: usize,
+    pub native_differences: usize,
+    /// Native differences attempted but outside the supported fragment
+    pub native_difference_fallbacks: usize,
+    /// Differences sent straight to ISL because the size estimate was too large
+    pub estimated_too_large: usize,
+    /// (estimated, actual) component counts of native differences
+    pub difference_estimates: Vec<(usize, usize)>,
+}
+
+/// A single representation decision, see `RepresentationStats`
+pub enum RepresentationEvent {
+    ConvertedToPresburger,
+    SemilinearProduct,
+    PresburgerProduct,
+    NativeDifference { estimated: usize, actual: usize },
+    NativeDifferenceFallback,
+    EstimatedTooLarge,
+}
+
+/// The SMPT queries run within the global time budget of `--auto-timeout`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimeoutBudgetStats {
+    pub budget_seconds: u64,
+    pub spent_ms: u64,
+    /// Every query, in the order they ran
+    pub attempts: Vec<BudgetAttempt>,
+    /// Disjuncts that were still undecided when the budget ran out
+    pub undecided: Vec<usize>,
+}
+
+/// A query of one disjunct with the timeout allocated to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetAttempt {
+    pub disjunct: usize,
+    pub timeout_seconds: u64,
+    pub spent_ms: u64,
+    pub decided: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KleeneElimination {
+    pub order: String,
+    pub trajectory: Vec<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,7 +177,13 @@ pub struct OptimizationOptions {
     pub bidirectional_pruning: bool,
     pub remove_redundant: bool,
     pub generate_less: bool,
+    /// Name of the semilinear strategy (`generate_less` is false only for "exact")
+    #[serde(default)]
+    pub semilinear_strategy: String,
     pub smart_kleene_order: bool,
+    /// Name of the Kleene elimination order heuristic
+    #[serde(default)]
+    pub kleene_order: String,
     pub timeout: u64,
 }
 
@@ -121,12 +215,15 @@ pub struct DisjunctStats {
     pub removed_transitions: usize,
 }
 
+#[derive(Default)]
 pub struct StatsCollector {
     current_stats: Option<SerializabilityStats>,
     start_time: Option<Instant>,
     certificate_creation_start: Option<Instant>,
     certificate_checking_start: Option<Instant>,
     was_saved: bool,
+    /// Statistics of the last finalized analysis
+    last: Option<SerializabilityStats>,
 }
 
 impl StatsCollector {
@@ -137,6 +234,7 @@ impl StatsCollector {
             certificate_creation_start: None,
             certificate_checking_start: None,
             was_saved: false,
+            last: None,
         }
     }
 
@@ -147,10 +245,12 @@ impl StatsCollector {
             timestamp: Utc::now(),
             example,
             options: OptimizationOptions {
-                bidirectional_pruning: BIDIRECTIONAL_PRUNING_ENABLED.load(Ordering::Relaxed),
-                remove_redundant: REMOVE_REDUNDANT.load(Ordering::Relaxed),
-                generate_less: GENERATE_LESS.load(Ordering::Relaxed),
-                smart_kleene_order: SMART_ORDER.load(Ordering::Relaxed),
+                bidirectional_pruning: optimize_enabled(),
+                remove_redundant: remove_redundant_enabled(),
+                generate_less: semilinear_strategy().simplifies(),
+                semilinear_strategy: semilinear_strategy().name().to_string(),
+                smart_kleene_order: kleene_order() != KleeneOrder::Arbitrary,
+                kleene_order: kleene_order().name().to_string(),
                 timeout: crate::smpt::get_smpt_timeout(),
             },
             result: "unknown".to_string(),
@@ -169,6 +269,18 @@ impl StatsCollector {
             total_time_ms: 0,
             smpt_calls: 0,
             smpt_timeouts: 0,
+            cancelled_disjuncts: 0,
+            estimated_time_saved_ms: 0,
+            representation: RepresentationStats::default(),
+            lp_prefilter_proofs: 0,
+            out_dir: None,
+            stage_timings_ms: vec![],
+            invariant_size: None,
+            smpt_cache_hits: 0,
+            smpt_cache_misses: 0,
+            size_threshold_events: vec![],
+            kleene_eliminations: vec![],
+            timeout_budget: None,
         });
     }
 
@@ -234,6 +346,94 @@ impl StatsCollector {
         }
     }
 
+    pub fn increment_lp_prefilter_proofs(&mut self) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.lp_prefilter_proofs += 1;
+        }
+    }
+
+    pub fn set_output_dir(&mut self, out_dir: &str) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.out_dir = Some(out_dir.to_string());
+        }
+    }
+
+    pub fn record_stage_time(&mut self, stage: &str, ms: u64) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.stage_timings_ms.push((stage.to_string(), ms));
+        }
+    }
+
+    pub fn set_invariant_size(&mut self, size: usize) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.invariant_size = Some(size);
+        }
+    }
+
+    pub fn record_smpt_cache_lookup(&mut self, hit: bool) {
+        if let Some(stats) = &mut self.current_stats {
+            if hit {
+                stats.smpt_cache_hits += 1;
+            } else {
+                stats.smpt_cache_misses += 1;
+            }
+        }
+    }
+
+    pub fn record_kleene_elimination(&mut self, order: &str, trajectory: Vec<usize>) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.kleene_eliminations.push(KleeneElimination {
+                order: order.to_string(),
+                trajectory,
+            });
+        }
+    }
+
+    pub fn record_size_threshold_event(&mut self, note: &str) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.size_threshold_events.push(note.to_string());
+        }
+    }
+
+    /// Copy of the statistics collected so far, with the total time filled in
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        let mut stats = self.current_stats.clone()?;
+        if let Some(start) = self.start_time {
+            stats.total_time_ms = start.elapsed().as_millis() as u64;
+        }
+        Some(stats)
+    }
+
+    pub fn record_cancelled_disjuncts(&mut self, count: usize, saved_ms: u64) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.cancelled_disjuncts += count;
+            stats.estimated_time_saved_ms += saved_ms;
+        }
+    }
+
+    pub fn record_timeout_budget(&mut self, budget: TimeoutBudgetStats) {
+        if let Some(stats) = &mut self.current_stats {
+            stats.timeout_budget = Some(budget);
+        }
+    }
+
+    pub fn record_representation_event(&mut self, event: RepresentationEvent) {
+        if let Some(stats) = &mut self.current_stats {
+            let r = &mut stats.representation;
+            match event {
+                RepresentationEvent::ConvertedToPresburger => r.conversions_to_presburger += 1,
+                RepresentationEvent::SemilinearProduct => r.semilinear_products += 1,
+                RepresentationEvent::PresburgerProduct => r.presburger_products += 1,
+                RepresentationEvent::NativeDifference { estimated, actual } => {
+                    r.native_differences += 1;
+                    r.difference_estimates.push((estimated, actual));
+                }
+                RepresentationEvent::NativeDifferenceFallback => r.native_difference_fallbacks += 1,
+                RepresentationEvent::EstimatedTooLarge => r.estimated_too_large += 1,
+            }
+        }
+    }
+
     pub fn finalize_and_save(&mut self) {
         if self.was_saved {
             return;
@@ -247,18 +447,27 @@ impl StatsCollector {
             if let Err(e) = append_stats_to_file(&stats) {
                 eprintln!("Failed to save statistics: {}", e);
             }
+            if let Some(out_dir) = &stats.out_dir {
+                if let Err(e) = write_stats_json(&stats, out_dir) {
+                    eprintln!("Failed to write {}/stats.json: {}", out_dir, e);
+                }
+            }
+            self.last = Some(stats);
         }
     }
 }
 
 fn append_stats_to_file(stats: &SerializabilityStats) -> std::io::Result<()> {
     // Ensure out directory exists
-    std::fs::create_dir_all("out")?;
+    std::fs::create_dir_all(crate::utils::file::OUT_DIR)?;
     
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("out/serializability_stats.jsonl")?;
+        .open(crate::utils::file::join(
+            crate::utils::file::OUT_DIR,
+            "serializability_stats.jsonl",
+        ))?;
     
     let json = serde_json::to_string(stats)?;
     writeln!(file, "{}", json)?;
@@ -266,6 +475,13 @@ fn append_stats_to_file(stats: &SerializabilityStats) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Write the statistics of a single run as pretty-printed JSON into its output directory
+fn write_stats_json(stats: &SerializabilityStats, out_dir: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(out_dir)?;
+    let json = serde_json::to_string_pretty(stats)?;
+    std::fs::write(crate::utils::file::join(out_dir, "stats.json"), json)
+}
+
 // Helper functions to be called from various parts of the codebase
 pub fn start_analysis(example: String) {
     if let Ok(mut collector) = STATS_COLLECTOR.lock() {
@@ -337,6 +553,84 @@ pub fn increment_smpt_timeouts() {
     }
 }
 
+pub fn increment_lp_prefilter_proofs() {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.increment_lp_prefilter_proofs();
+    }
+}
+
+pub fn set_output_dir(out_dir: &str) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.set_output_dir(out_dir);
+    }
+}
+
+/// Run `f` and record its wall-clock time under the given stage name
+pub fn time_stage<F, R>(stage: &str, f: F) -> R
+where
+    F: FnOnce() -> R
+{
+    crate::observer::notify(|observer| observer.on_stage_start(stage));
+    let start = Instant::now();
+    let result = f();
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_stage_time(stage, start.elapsed().as_millis() as u64);
+    }
+    result
+}
+
+pub fn set_invariant_size(size: usize) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.set_invariant_size(size);
+    }
+}
+
+pub fn record_smpt_cache_lookup(hit: bool) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_smpt_cache_lookup(hit);
+    }
+}
+
+pub fn record_kleene_elimination(order: &str, trajectory: Vec<usize>) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_kleene_elimination(order, trajectory);
+    }
+}
+
+pub fn record_size_threshold_event(note: &str) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_size_threshold_event(note);
+    }
+}
+
+/// Statistics of the analysis in progress, or `None` if no analysis was started
+pub fn snapshot() -> Option<Snapshot> {
+    STATS_COLLECTOR.lock().ok()?.snapshot()
+}
+
+/// Statistics of the last finalized analysis, or `None` if none was finalized
+pub fn last_finished() -> Option<Snapshot> {
+    STATS_COLLECTOR.lock().ok()?.last.clone()
+}
+
+pub fn record_cancelled_disjuncts(count: usize, saved_ms: u64) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_cancelled_disjuncts(count, saved_ms);
+    }
+}
+
+pub fn record_timeout_budget(budget: TimeoutBudgetStats) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_timeout_budget(budget);
+    }
+}
+
+pub fn record_representation_event(event: RepresentationEvent) {
+    if let Ok(mut collector) = STATS_COLLECTOR.lock() {
+        collector.record_representation_event(event);
+    }
+}
+
 pub fn finalize_stats() {
     if let Ok(mut collector) = STATS_COLLECTOR.lock() {
         collector.finalize_and_save();
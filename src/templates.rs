@@ -0,0 +1,252 @@
+//! `ser new <template>`: `.ser` skeletons of classic protocols.
+//!
+//! Every template is parameterized by a size `n` (participants, clients, ...) and is
+//! written in the latest language version, so it can serve both as a starting point for
+//! a model and as a regression model: the tests below check that each one parses and
+//! converts to a network system.
+
+use crate::error::SerError;
+use std::fmt::Write;
+
+/// A protocol that `ser new` can generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// Two-phase commit: participants vote, a coordinator decides, participants apply
+    TwoPhaseCommit,
+    /// Clients that take a lock around a critical section and check mutual exclusion
+    LockService,
+    /// Unprotected read-modify-write increments, the classic non-serializable example
+    LostUpdate,
+}
+
+impl Template {
+    pub const ALL: [Template; 3] = [
+        Template::TwoPhaseCommit,
+        Template::LockService,
+        Template::LostUpdate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::TwoPhaseCommit => "2pc",
+            Template::LockService => "lock-service",
+            Template::LostUpdate => "lost-update",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Template::TwoPhaseCommit => "two-phase commit with n participants",
+            Template::LockService => "n clients sharing a lock around a critical section",
+            Template::LostUpdate => "n unprotected increments of a shared counter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Template> {
+        Template::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// The source of the template with size `n`
+    pub fn render(self, n: usize) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "#lang ser/{}", crate::parser::LANG_VERSION);
+        let _ = writeln!(
+            out,
+            "// {} (ser new {} --n {})",
+            self.description(),
+            self.name(),
+            n
+        );
+        match self {
+            Template::TwoPhaseCommit => two_phase_commit(&mut out, n),
+            Template::LockService => lock_service(&mut out, n),
+            Template::LostUpdate => lost_update(&mut out, n),
+        }
+        out
+    }
+}
+
+/// `VOTE_i` is 0 (not voted), 1 (yes) or 2 (no); `DECISION` is 0 (undecided),
+/// 1 (commit) or 2 (abort)
+fn two_phase_commit(out: &mut String, n: usize) {
+    let _ = writeln!(out, "global DECISION := 0;");
+    for i in 1..=n {
+        let _ = writeln!(out, "global VOTE_{} := 0;", i);
+    }
+    for i in 1..=n {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "// Participant {} votes once, before the decision", i);
+        let _ = writeln!(out, "request prepare_{} {{", i);
+        let _ = writeln!(
+            out,
+            "    if (!(DECISION == 0 && VOTE_{} == 0)) {{ return 0 }} else {{ 0 }};",
+            i
+        );
+        let _ = writeln!(out, "    VOTE_{} := 1 + ?;", i);
+        let _ = writeln!(out, "    VOTE_{}", i);
+        let _ = writeln!(out, "}}");
+    }
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "// The coordinator commits only if every participant voted yes"
+    );
+    let _ = writeln!(out, "request decide {{");
+    let _ = writeln!(
+        out,
+        "    if (!(DECISION == 0)) {{ return DECISION }} else {{ 0 }};"
+    );
+    for i in 1..=n {
+        let _ = writeln!(out, "    while (VOTE_{} == 0) {{ yield }};", i);
+    }
+    let all_yes = (1..=n)
+        .map(|i| format!("VOTE_{} == 1", i))
+        .collect::<Vec<_>>()
+        .join(" && ");
+    let _ = writeln!(
+        out,
+        "    if ({}) {{ DECISION := 1 }} else {{ DECISION := 2 }};",
+        all_yes
+    );
+    let _ = writeln!(out, "    DECISION");
+    let _ = writeln!(out, "}}");
+    for i in 1..=n {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "// Participant {} applies the decision", i);
+        let _ = writeln!(out, "request apply_{} {{", i);
+        let _ = writeln!(out, "    while (DECISION == 0) {{ yield }};");
+        let _ = writeln!(out, "    DECISION");
+        let _ = writeln!(out, "}}");
+    }
+}
+
+/// `LOCK` is 0 when free and `i` while client `i` holds it. A client responds 1 if
+/// nobody else took the lock during its critical section, and 0 otherwise.
+fn lock_service(out: &mut String, n: usize) {
+    let _ = writeln!(out, "global LOCK := 0;");
+    for i in 1..=n {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "request client_{} {{", i);
+        let _ = writeln!(
+            out,
+            "    // Testing and taking the lock is one step: no yield in between"
+        );
+        let _ = writeln!(out, "    while (!(LOCK == 0)) {{ yield }};");
+        let _ = writeln!(out, "    LOCK := {};", i);
+        let _ = writeln!(out, "    yield;");
+        let _ = writeln!(
+            out,
+            "    if (!(LOCK == {})) {{ return 0 }} else {{ 0 }};",
+            i
+        );
+        let _ = writeln!(out, "    LOCK := 0;");
+        let _ = writeln!(out, "    1");
+        let _ = writeln!(out, "}}");
+    }
+}
+
+/// Each increment reads `X`, yields and writes back; two of them can read the same value
+fn lost_update(out: &mut String, n: usize) {
+    let _ = writeln!(out, "global X := 0;");
+    for i in 1..=n {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "request increment_{} {{", i);
+        let _ = writeln!(out, "    if (X == {}) {{ return X }} else {{ 0 }};", n);
+        let _ = writeln!(out, "    y := X;");
+        let _ = writeln!(out, "    yield;");
+        let _ = writeln!(out, "    X := y + 1");
+        let _ = writeln!(out, "}}");
+    }
+}
+
+/// `ser new <template> [--n <k>] [-o <file>]`
+pub fn run(args: &[String]) -> Result<(), SerError> {
+    let usage = || {
+        let names: Vec<&str> = Template::ALL.iter().map(|t| t.name()).collect();
+        SerError::Usage(format!(
+            "Usage: ser new <template> [--n <k>] [-o <file>] (templates: {})",
+            names.join(", ")
+        ))
+    };
+    let mut template = None;
+    let mut n = 2;
+    let mut output = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--n" => {
+                let value = args.get(i + 1).ok_or_else(usage)?;
+                n = match value.parse::<usize>() {
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        return Err(SerError::Usage(format!(
+                            "Invalid value '{}' for --n (expected a positive number)",
+                            value
+                        )));
+                    }
+                };
+                i += 2;
+            }
+            "-o" => {
+                output = Some(args.get(i + 1).ok_or_else(usage)?.clone());
+                i += 2;
+            }
+            name if template.is_none() && !name.starts_with('-') => {
+                template = Some(Template::from_name(name).ok_or_else(|| {
+                    let names: Vec<&str> = Template::ALL.iter().map(|t| t.name()).collect();
+                    SerError::Usage(format!(
+                        "Unknown template '{}' (templates: {})",
+                        name,
+                        names.join(", ")
+                    ))
+                })?);
+                i += 1;
+            }
+            _ => return Err(usage()),
+        }
+    }
+    let template = template.ok_or_else(usage)?;
+    let path = output.unwrap_or_else(|| format!("{}.ser", template.name()));
+    if std::path::Path::new(&path).exists() {
+        return Err(SerError::IoError(format!("{} already exists", path)));
+    }
+    std::fs::write(&path, template.render(n))
+        .map_err(|e| SerError::IoError(format!("Cannot write {}: {}", path, e)))?;
+    println!("Wrote {} ({}, n = {})", path, template.description(), n);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr_to_ns::program_to_ns;
+    use crate::parser::{ExprHc, parse_program};
+
+    #[test]
+    fn test_templates_parse() {
+        for template in Template::ALL {
+            for n in 1..=3 {
+                let source = template.render(n);
+                let mut table = ExprHc::new();
+                let program = parse_program(&source, &mut table)
+                    .unwrap_or_else(|e| panic!("{} --n {}: {}\n{}", template.name(), n, e, source));
+                let ns = program_to_ns(&mut table, &program);
+                assert!(!ns.transitions.is_empty(), "{} --n {}", template.name(), n);
+            }
+        }
+        assert_eq!(Template::from_name("2pc"), Some(Template::TwoPhaseCommit));
+        assert_eq!(Template::from_name("paxos"), None);
+    }
+
+    #[test]
+    fn test_two_phase_commit_responses() {
+        let mut table = ExprHc::new();
+        let source = Template::TwoPhaseCommit.render(2);
+        let program = parse_program(&source, &mut table).unwrap();
+        let ns = program_to_ns(&mut table, &program);
+        // Votes are yes or no, decisions commit or abort, and 0 for a late request
+        let mut responses = ns.get_responses();
+        responses.sort();
+        assert_eq!(responses, vec![&0, &1, &2]);
+    }
+}
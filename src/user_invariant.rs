@@ -0,0 +1,243 @@
+//! `--invariant <file>`: check a hand-written invariant instead of running the analysis.
+//!
+//! Variables are named by the `Display` output of what they count (`req/ok` for
+//! completed requests, the request followed by its local state for requests in flight),
+//! or by its `ident` encoding for names that are not SMT-LIB atoms. Two formats are
+//! accepted:
+//!
+//! - SMT-LIB, with a `(define-fun cert (...) Bool ...)` like the proofs of SMPT. Global
+//!   states may be variables too; in global state `g`, the variable `g` is 1 and the
+//!   other global states are 0.
+//! - JSON (files ending in `.json`), an object from global states to the SMT-LIB body of
+//!   their invariant. Global states without an entry are claimed to be unreachable (see
+//!   `MissingInvariants`).
+
+use crate::deterministic_map::HashMap;
+use crate::ident::{self, Target};
+use crate::ns::NS;
+use crate::ns_decision::{NSInvariant, RequestState, RequestStatePair};
+use crate::proof_parser::{ProofInvariant, parse_proof_file};
+use either::Either;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::hash::Hash;
+
+type Name<G, L, Req, Resp> = Either<G, RequestStatePair<Req, L, Resp>>;
+
+/// Parse a hand-written invariant of `ns`, in JSON if `json` is set and in SMT-LIB
+/// otherwise
+pub fn parse<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    content: &str,
+    json: bool,
+) -> Result<NSInvariant<G, L, Req, Resp>, String>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    let states = request_states(ns);
+    let names = names(ns, &states);
+    if json {
+        parse_json(&states, &names, content)
+    } else {
+        parse_smtlib(ns, &states, &names, content)
+    }
+}
+
+/// The request states, which are the variables of every global state invariant
+fn request_states<G, L, Req, Resp>(ns: &NS<G, L, Req, Resp>) -> Vec<RequestStatePair<Req, L, Resp>>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    let mut states = Vec::new();
+    for req in ns.get_requests() {
+        let in_flight = ns
+            .get_local_states()
+            .into_iter()
+            .map(|local| RequestState::InFlight(local.clone()));
+        let completed = ns
+            .responses
+            .iter()
+            .map(|(_, resp)| RequestState::Completed(resp.clone()));
+        for state in in_flight.chain(completed) {
+            let pair = RequestStatePair(req.clone(), state);
+            if !states.contains(&pair) {
+                states.push(pair);
+            }
+        }
+    }
+    states
+}
+
+/// The variables an invariant may use, by name
+fn names<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    states: &[RequestStatePair<Req, L, Resp>],
+) -> HashMap<String, Name<G, L, Req, Resp>>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    let globals = ns
+        .get_global_states()
+        .into_iter()
+        .map(|g| Either::Left(g.clone()));
+    let states = states.iter().map(|pair| Either::Right(pair.clone()));
+    let mut names = HashMap::default();
+    for name in globals.chain(states) {
+        let display = match &name {
+            Either::Left(global) => global.to_string(),
+            Either::Right(pair) => pair.to_string(),
+        };
+        names
+            .entry(ident::encode(&display, Target::SmtLib))
+            .or_insert_with(|| name.clone());
+        names.entry(display).or_insert(name);
+    }
+    names
+}
+
+fn unknown_variable(name: &str) -> String {
+    format!(
+        "Unknown variable '{}': expected a global state, a request followed by a local state, or request/response",
+        name
+    )
+}
+
+fn parse_smtlib<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    states: &[RequestStatePair<Req, L, Resp>],
+    names: &HashMap<String, Name<G, L, Req, Resp>>,
+    content: &str,
+) -> Result<NSInvariant<G, L, Req, Resp>, String>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    let proof = parse_proof_file(content).map_err(|err| err.to_string())?;
+    if let Some(name) = proof.variables().iter().find(|v| !names.contains_key(*v)) {
+        return Err(unknown_variable(name));
+    }
+    let global_invariants = ns
+        .get_global_states()
+        .into_iter()
+        .map(|global| {
+            let substituted = proof.substitute(|name| match &names[name] {
+                Either::Left(other) => Either::Right((other == global) as i32),
+                Either::Right(pair) => Either::Left(pair.clone()),
+            });
+            let (_, formula) = substituted.into_parts();
+            (
+                global.clone(),
+                ProofInvariant::new(states.to_vec(), formula),
+            )
+        })
+        .collect();
+    Ok(NSInvariant { global_invariants })
+}
+
+fn parse_json<G, L, Req, Resp>(
+    states: &[RequestStatePair<Req, L, Resp>],
+    names: &HashMap<String, Name<G, L, Req, Resp>>,
+    content: &str,
+) -> Result<NSInvariant<G, L, Req, Resp>, String>
+where
+    G: Clone + Display + Eq + Hash,
+    L: Clone + Display + Eq + Hash,
+    Req: Clone + Display + Eq + Hash,
+    Resp: Clone + Display + Eq + Hash,
+{
+    let bodies: BTreeMap<String, String> =
+        serde_json::from_str(content).map_err(|err| format!("Invalid invariant file: {}", err))?;
+
+    // Declare every request state whose name is an SMT-LIB atom
+    let mut declared: Vec<&String> = names
+        .iter()
+        .filter(|(name, value)| value.is_right() && is_atom(name))
+        .map(|(name, _)| name)
+        .collect();
+    declared.sort();
+    let declarations: String = declared
+        .iter()
+        .map(|name| format!("({} Int)", name))
+        .collect();
+
+    let mut global_invariants = HashMap::default();
+    for (global_name, body) in &bodies {
+        let global = match names.get(global_name) {
+            Some(Either::Left(global)) => global,
+            _ => return Err(format!("Unknown global state '{}'", global_name)),
+        };
+        let source = format!("(define-fun cert ({}) Bool {})", declarations, body);
+        let proof = parse_proof_file(&source)
+            .map_err(|err| format!("Invariant of global state {}: {}", global_name, err))?;
+        let (_, formula) = proof.into_parts();
+        let formula = formula.map(|name| match &names[&name] {
+            Either::Right(pair) => pair.clone(),
+            Either::Left(_) => unreachable!("only request states are declared"),
+        });
+        global_invariants.insert(
+            global.clone(),
+            ProofInvariant::new(states.to_vec(), formula),
+        );
+    }
+    Ok(NSInvariant { global_invariants })
+}
+
+/// Whether `name` is read back as a single variable by the SMT-LIB parser
+fn is_atom(name: &str) -> bool {
+    !name.is_empty()
+        && name.parse::<i64>().is_err()
+        && !name.starts_with(';')
+        && !name.contains(|ch: char| ch.is_whitespace() || ch == '(' || ch == ')')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::NSFormat;
+
+    /// A lock: a request takes it in global state Free and releases it
+    const MODEL: &str = r#"{
+        "initial_global": "Free",
+        "requests": [["req", "Start"]],
+        "responses": [["Done", "ok"]],
+        "transitions": [["Start", "Free", "Held", "Locked"], ["Held", "Locked", "Done", "Free"]]
+    }"#;
+
+    #[test]
+    fn test_user_invariant() {
+        let ns = NS::<String, String, String, String>::from_str_in_format(MODEL, NSFormat::Json)
+            .unwrap();
+
+        // At most one request holds the lock, exactly when it is locked
+        let smtlib = "(define-fun cert ((reqHeld Int) (Locked Int)) Bool (= reqHeld Locked))";
+        let invariant = parse(&ns, smtlib, false).unwrap();
+        assert!(invariant.check_proof(&ns).is_ok());
+
+        let json = r#"{"Free": "(= reqHeld 0)", "Locked": "(= reqHeld 1)"}"#;
+        let invariant = parse(&ns, json, true).unwrap();
+        assert!(invariant.check_proof(&ns).is_ok());
+
+        // Not inductive: taking the lock puts a request in Held
+        let json = r#"{"Free": "(= reqHeld 0)", "Locked": "(= reqHeld 0)"}"#;
+        let invariant = parse(&ns, json, true).unwrap();
+        assert!(invariant.check_proof(&ns).is_err());
+
+        // Claims Locked is unreachable
+        let invariant = parse(&ns, r#"{"Free": "(= reqHeld 0)"}"#, true).unwrap();
+        assert!(invariant.check_proof(&ns).is_err());
+
+        assert!(parse(&ns, r#"{"Busy": "true"}"#, true).is_err());
+        assert!(parse(&ns, "(define-fun cert ((x Int)) Bool (>= x 0))", false).is_err());
+    }
+}
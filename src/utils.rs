@@ -31,6 +31,28 @@ pub mod string {
             .collect()
     }
 
+    /// Index distinct items by their `Display` output
+    ///
+    /// Code that matches values by their string form silently confuses two
+    /// distinct values that print the same, so this panics if two items collide.
+    /// `items` must not contain duplicates.
+    pub fn display_index<T: std::fmt::Display>(
+        items: &[T],
+        context: &str,
+    ) -> crate::deterministic_map::HashMap<String, usize> {
+        let mut index = crate::deterministic_map::HashMap::default();
+        for (i, item) in items.iter().enumerate() {
+            let key = item.to_string();
+            if let Some(previous) = index.insert(key.clone(), i) {
+                panic!(
+                    "{}: items {} and {} are distinct but both display as `{}`",
+                    context, previous, i, key
+                );
+            }
+        }
+        index
+    }
+
     /// Escape HTML special characters
     ///
     /// Converts HTML special characters to their entity equivalents
@@ -47,7 +69,18 @@ pub mod string {
 /// File and directory utilities
 pub mod file {
     use std::fs;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
+
+    /// Directory all analysis outputs go to
+    pub const OUT_DIR: &str = "out";
+
+    /// `name` inside `dir`, with the path separator of the platform. `name` may have
+    /// several components separated by `/` (`repair/check_1`).
+    pub fn join(dir: &str, name: &str) -> String {
+        let mut path = PathBuf::from(dir);
+        path.extend(name.split('/'));
+        path.to_string_lossy().into_owned()
+    }
 
     /// Ensure a directory exists, creating it if necessary
     ///
@@ -67,6 +100,93 @@ pub mod file {
         }
         fs::write(file_path, content)
     }
+
+    /// Output directory of an input file: `out/<stem>` for a single file, and
+    /// `out/<path relative to root, without extension>` for a file found in the
+    /// directory `root`, so that `a/model.ser` and `b/model.ser` do not share one
+    pub fn output_dir(file: &Path, root: Option<&Path>) -> String {
+        let relative = root
+            .and_then(|root| file.strip_prefix(root).ok())
+            .filter(|relative| relative.parent().is_some_and(|p| !p.as_os_str().is_empty()));
+        match relative {
+            Some(relative) => Path::new(OUT_DIR)
+                .join(relative.with_extension(""))
+                .to_string_lossy()
+                .into_owned(),
+            None => join(OUT_DIR, &stem(file)),
+        }
+    }
+
+    /// The output directories an analysis of `file` may have written to, most likely
+    /// first: `out/<stem>`, then `out/<parent>/<stem>`, `out/<grandparent>/<parent>/<stem>`,
+    /// ... for directory analyses rooted at each ancestor
+    pub fn output_dir_candidates(file: &Path) -> Vec<String> {
+        let mut candidates = vec![join(OUT_DIR, &stem(file))];
+        let mut relative = PathBuf::from(stem(file));
+        let parents = file
+            .parent()
+            .into_iter()
+            .flat_map(|p| p.components().rev())
+            .take_while(|c| matches!(c, std::path::Component::Normal(_)));
+        for parent in parents {
+            relative = Path::new(parent.as_os_str()).join(relative);
+            let candidate = Path::new(OUT_DIR).join(&relative);
+            candidates.push(candidate.to_string_lossy().into_owned());
+        }
+        candidates
+    }
+
+    fn stem(file: &Path) -> String {
+        file.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("network")
+            .to_string()
+    }
+}
+
+/// External programs, which are started differently on each platform
+pub mod process {
+    use std::ffi::OsString;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Program and arguments that open `path` in the default viewer of the operating
+    /// system `os` (as in `std::env::consts::OS`), or `None` if we do not know how
+    pub fn opener(os: &str, path: &Path) -> Option<(&'static str, Vec<OsString>)> {
+        let path = path.as_os_str().to_owned();
+        match os {
+            "macos" => Some(("open", vec![path])),
+            // `start` takes its first quoted argument as a window title, hence the ""
+            "windows" => Some(("cmd", vec!["/C".into(), "start".into(), "".into(), path])),
+            "linux" | "freebsd" | "netbsd" | "openbsd" | "dragonfly" => {
+                Some(("xdg-open", vec![path]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Open `path` in the default viewer, without waiting for it
+    pub fn open_in_viewer(path: &Path) -> std::io::Result<()> {
+        let (program, args) = opener(std::env::consts::OS, path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("no viewer known on {}", std::env::consts::OS),
+            )
+        })?;
+        Command::new(program).args(args).spawn().map(|_| ())
+    }
+
+    /// Name of the Python 3 interpreter on the operating system `os`: Windows
+    /// installs it as `python`, and only Unix-like systems can run shell scripts
+    pub fn python(os: &str) -> &'static str {
+        if os == "windows" { "python" } else { "python3" }
+    }
+
+    /// Whether `script` can be run directly: it exists and, being a shell script, the
+    /// operating system `os` is not Windows
+    pub fn can_run_script(os: &str, script: &Path) -> bool {
+        os != "windows" && script.exists()
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +216,25 @@ mod tests {
         assert_eq!(string::sanitize("123"), "123");
     }
 
+    #[test]
+    fn test_display_index() {
+        let index = string::display_index(&["a", "b"], "test");
+        assert_eq!(index["b"], 1);
+
+        // Distinct values with the same Display output
+        #[derive(Debug)]
+        struct Opaque(u32);
+        impl std::fmt::Display for Opaque {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "opaque")
+            }
+        }
+        let result = std::panic::catch_unwind(|| {
+            string::display_index(&[Opaque(1), Opaque(2)], "test");
+        });
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_html_escape() {
         assert_eq!(string::html_escape("<div>"), "&lt;div&gt;");
@@ -125,4 +264,78 @@ mod tests {
         // Clean up
         let _ = std::fs::remove_dir_all(&*temp_path);
     }
+
+    #[test]
+    fn test_output_dir() {
+        use std::path::{Path, PathBuf};
+        // Compare as paths, which do not depend on the separator of the platform
+        let paths = |dirs: &[&str]| -> Vec<PathBuf> { dirs.iter().map(PathBuf::from).collect() };
+        let root = Path::new("models");
+        let output_dir = |file: &str, root: Option<&Path>| {
+            PathBuf::from(file::output_dir(Path::new(file), root))
+        };
+        assert_eq!(output_dir("models/a.ser", None), Path::new("out/a"));
+        assert_eq!(output_dir("models/a.ser", Some(root)), Path::new("out/a"));
+        assert_eq!(
+            output_dir("models/x/model.ser", Some(root)),
+            Path::new("out/x/model")
+        );
+        assert_eq!(
+            output_dir("models/y/model.ser", Some(root)),
+            Path::new("out/y/model")
+        );
+        let candidates = |file: &str| -> Vec<PathBuf> {
+            file::output_dir_candidates(Path::new(file))
+                .into_iter()
+                .map(PathBuf::from)
+                .collect()
+        };
+        assert_eq!(
+            candidates("models/x/model.ser"),
+            paths(&["out/model", "out/x/model", "out/models/x/model"])
+        );
+        assert_eq!(
+            candidates("../x/model.ser"),
+            paths(&["out/model", "out/x/model"])
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        use std::path::{MAIN_SEPARATOR, Path};
+        let sep = MAIN_SEPARATOR;
+        assert_eq!(
+            file::join("out/a", "trace.mmd"),
+            format!("out/a{}trace.mmd", sep)
+        );
+        assert_eq!(
+            file::join("out", "repair/check_1"),
+            format!("out{}repair{}check_1", sep, sep)
+        );
+        assert_eq!(
+            Path::new(&file::join("out/a", "repair/check_1")),
+            Path::new("out/a/repair/check_1")
+        );
+    }
+
+    #[test]
+    fn test_opener() {
+        use std::path::Path;
+        let png = Path::new("out/a/network.png");
+        let (program, args) = process::opener("windows", png).unwrap();
+        assert_eq!(program, "cmd");
+        assert_eq!(args, ["/C", "start", "", "out/a/network.png"]);
+        assert_eq!(process::opener("macos", png).unwrap().0, "open");
+        assert_eq!(process::opener("linux", png).unwrap().0, "xdg-open");
+        assert!(process::opener("solaris", png).is_none());
+
+        assert_eq!(process::python("windows"), "python");
+        assert_eq!(process::python("linux"), "python3");
+        // Shell scripts are not run on Windows, even if they exist
+        let script = Path::new("Cargo.toml");
+        assert!(!process::can_run_script("windows", script));
+        assert!(process::can_run_script("macos", script));
+        let missing = Path::new("no_such_script.sh");
+        assert!(!process::can_run_script("linux", missing));
+    }
 }
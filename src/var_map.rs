@@ -0,0 +1,188 @@
+//! The atoms of the dimensions of a Presburger set.
+//!
+//! ISL only knows the dimensions of a set by their position, so a `PresburgerSet` keeps
+//! the atom of every dimension in a `VarMap`. Two sets can only be combined once their
+//! maps are equal, and a set is brought into a bigger map by inserting dimensions, which
+//! requires the atoms they share to be in the same order. `VarMap::sorted` and
+//! `VarMap::union` build maps in the order of `dim_order`, and `VarMap::embedding`
+//! checks that one map fits into another.
+
+use std::fmt::{self, Debug};
+use std::ops::{Deref, Range};
+
+/// The atom of every dimension, in dimension order. The atoms are distinct.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct VarMap<T> {
+    atoms: Vec<T>,
+}
+
+impl<T> VarMap<T> {
+    /// The map with the dimensions in the given order
+    pub fn new(atoms: Vec<T>) -> Self {
+        VarMap { atoms }
+    }
+
+    /// The map of a set without dimensions
+    pub fn empty() -> Self {
+        VarMap { atoms: Vec::new() }
+    }
+
+    pub fn atoms(&self) -> &[T] {
+        &self.atoms
+    }
+
+    pub fn into_atoms(self) -> Vec<T> {
+        self.atoms
+    }
+
+    /// Rename the atoms, keeping the dimensions
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> VarMap<U> {
+        VarMap {
+            atoms: self.atoms.into_iter().map(f).collect(),
+        }
+    }
+
+    /// Add a dimension at the end
+    pub fn push(&mut self, atom: T) {
+        self.atoms.push(atom);
+    }
+
+    /// Drop the dimensions in `dims`, once they are projected out of the set
+    pub fn remove_dims(&mut self, dims: Range<usize>) {
+        self.atoms.drain(dims);
+    }
+}
+
+impl<T: PartialEq> VarMap<T> {
+    /// The dimension of `atom`
+    pub fn index_of(&self, atom: &T) -> Option<usize> {
+        self.atoms.iter().position(|a| a == atom)
+    }
+
+    /// The atoms of `self` that are also in `other`, in the order of `self`
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        VarMap {
+            atoms: self
+                .atoms
+                .iter()
+                .filter(|atom| other.contains(atom))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The dimension in `target` of every dimension of `self`, if `target` has all atoms
+    /// of `self` in the same order, so that a set is embedded into `target` by inserting
+    /// the missing dimensions
+    pub fn embedding(&self, target: &Self) -> Option<Vec<usize>> {
+        let mut next = 0;
+        self.atoms
+            .iter()
+            .map(|atom| {
+                let dim = next + target.atoms[next..].iter().position(|a| a == atom)?;
+                next = dim + 1;
+                Some(dim)
+            })
+            .collect()
+    }
+}
+
+impl<T: Ord + ToString> VarMap<T> {
+    /// The map of the given atoms, in the order of `dim_order` and without duplicates
+    pub fn sorted(atoms: impl IntoIterator<Item = T>) -> Self {
+        let mut atoms: Vec<T> = atoms.into_iter().collect();
+        crate::dim_order::sort_atoms(&mut atoms);
+        atoms.dedup();
+        VarMap { atoms }
+    }
+
+    /// The map of the atoms of both maps, in the order of `dim_order`
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Self::union_all([self, other])
+    }
+
+    /// The map of the atoms of all maps, in the order of `dim_order`
+    pub fn union_all<'a>(maps: impl IntoIterator<Item = &'a Self>) -> Self
+    where
+        T: Clone + 'a,
+    {
+        Self::sorted(maps.into_iter().flat_map(|map| map.atoms.iter().cloned()))
+    }
+}
+
+impl<T> Default for VarMap<T> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> Deref for VarMap<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.atoms
+    }
+}
+
+impl<T> From<Vec<T>> for VarMap<T> {
+    fn from(atoms: Vec<T>) -> Self {
+        VarMap { atoms }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a VarMap<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.atoms.iter()
+    }
+}
+
+impl<T: PartialEq> PartialEq<Vec<T>> for VarMap<T> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        &self.atoms == other
+    }
+}
+
+impl<T: Debug> Debug for VarMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.atoms).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_map() {
+        let ab = VarMap::sorted(vec!["b", "a", "b"]);
+        assert_eq!(ab, vec!["a", "b"]);
+        assert_eq!(ab.index_of(&"b"), Some(1));
+        assert_eq!(ab.index_of(&"c"), None);
+
+        let bcd = VarMap::new(vec!["b", "c", "d"]);
+        let union = ab.union(&bcd);
+        assert_eq!(union, vec!["a", "b", "c", "d"]);
+        assert_eq!(ab.intersection(&bcd), vec!["b"]);
+        assert_eq!(VarMap::union_all([&ab, &bcd, &VarMap::empty()]), union);
+
+        // Embedding only inserts dimensions
+        assert_eq!(ab.embedding(&union), Some(vec![0, 1]));
+        assert_eq!(bcd.embedding(&union), Some(vec![1, 2, 3]));
+        assert_eq!(union.embedding(&bcd), None);
+        assert_eq!(VarMap::new(vec!["b", "a"]).embedding(&union), None);
+
+        let mut map = union.map(|atom| atom.to_uppercase());
+        map.remove_dims(1..3);
+        assert_eq!(map, vec!["A".to_string(), "D".to_string()]);
+        assert_eq!(format!("{:?}", map), r#"["A", "D"]"#);
+    }
+}
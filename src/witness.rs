@@ -0,0 +1,212 @@
+//! Decomposition of the serializable language, for the report of a serializability
+//! proof (`--witness`).
+//!
+//! The completed multisets of serial executions form a semilinear set: a union of
+//! linear sets, each a base multiset of `request/response` pairs plus any number of
+//! copies of its periods. When the system is serializable, these are exactly the
+//! multisets that can complete, so the linear sets describe all allowed behaviors.
+//! For each of them, the report lists the global states in which its multisets can
+//! complete according to the invariant of the proof, and whether all of its multisets
+//! or only some do.
+
+use crate::ns::NS;
+use crate::ns_decision::NSInvariant;
+use crate::presburger::PresburgerSet;
+use crate::proofinvariant_to_presburger::formula_to_presburger;
+use crate::semilinear::{LinearSetTerms, SemilinearSet};
+use colored::*;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Enable the witness decomposition in the report of serializability proofs
+pub fn set_witness(enabled: bool) {
+    crate::options::update(|options| options.witness = enabled);
+}
+
+pub fn witness_enabled() -> bool {
+    crate::options::get(|options| options.witness)
+}
+
+/// File in the output directory that holds the decomposition
+pub const WITNESS_FILE: &str = "witness.json";
+
+/// How much of a linear set completes in a global state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Coverage {
+    All,
+    Some,
+}
+
+/// A linear set of the serializable language and where its multisets complete
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WitnessComponent {
+    pub linear_set: LinearSetTerms<String>,
+    /// Global states whose invariant admits multisets of the linear set
+    pub global_states: Vec<(String, Coverage)>,
+}
+
+/// Decompose the serializable language of `ns` and place its linear sets in the
+/// global states of `invariant`
+pub fn decompose<G, L, Req, Resp>(
+    ns: &NS<G, L, Req, Resp>,
+    invariant: &NSInvariant<G, L, Req, Resp>,
+) -> Vec<WitnessComponent>
+where
+    G: Clone + Ord + Hash + Display + Debug,
+    L: Clone + Ord + Hash + Display + Debug,
+    Req: Clone + Ord + Hash + Display + Debug,
+    Resp: Clone + Ord + Hash + Display + Debug,
+{
+    // The invariant of each global state, restricted to states without requests in
+    // flight, over the same `request/response` names as the semilinear set
+    let mut globals: Vec<&G> = invariant.global_invariants.keys().collect();
+    globals.sort();
+    let completed: Vec<(String, PresburgerSet<String>)> = globals
+        .into_iter()
+        .filter_map(|global| {
+            let projected = invariant
+                .project_to_completed(global)?
+                .map(|pair| pair.to_string());
+            let mut variables = projected.variables.clone();
+            variables.sort();
+            Some((
+                global.to_string(),
+                formula_to_presburger(&projected.formula, &variables),
+            ))
+        })
+        .collect();
+
+    ns.serialized_automaton_semilinear()
+        .components
+        .into_iter()
+        .map(|linear_set| {
+            let terms = LinearSetTerms::from(&linear_set);
+            let set = PresburgerSet::from_semilinear_set(&SemilinearSet {
+                components: vec![linear_set],
+            });
+            let global_states = completed
+                .iter()
+                .filter_map(|(global, holds)| {
+                    if set.difference(holds).is_empty() {
+                        Some((global.clone(), Coverage::All))
+                    } else if !set.intersection(holds).is_empty() {
+                        Some((global.clone(), Coverage::Some))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            WitnessComponent {
+                linear_set: terms,
+                global_states,
+            }
+        })
+        .collect()
+}
+
+/// A multiset of `request/response` pairs, as `a/x + 2 b/y`
+fn format_multiset(entries: &[(String, usize)]) -> String {
+    if entries.is_empty() {
+        return "∅".to_string();
+    }
+    entries
+        .iter()
+        .map(|(pair, count)| match count {
+            1 => pair.clone(),
+            _ => format!("{} {}", count, pair),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Print the decomposition as part of the report
+pub fn print_decomposition(components: &[WitnessComponent]) {
+    println!();
+    println!(
+        "Serializable language: {} linear set{}",
+        components.len(),
+        if components.len() == 1 { "" } else { "s" }
+    );
+    for (i, component) in components.iter().enumerate() {
+        println!();
+        println!(
+            "  {} {}",
+            format!("Linear set {}:", i + 1).bold(),
+            format_multiset(&component.linear_set.base)
+        );
+        for period in &component.linear_set.periods {
+            println!("    + any number of ({})", format_multiset(period));
+        }
+        if component.global_states.is_empty() {
+            println!(
+                "    {} completes in no global state of the invariant",
+                "⚠".yellow()
+            );
+        }
+        for (global, coverage) in &component.global_states {
+            let coverage = match coverage {
+                Coverage::All => "all multisets",
+                Coverage::Some => "some multisets",
+            };
+            println!("    completes in {} ({})", global.cyan(), coverage);
+        }
+    }
+}
+
+/// Save the decomposition to `<out_dir>/witness.json`
+pub fn save_decomposition(
+    out_dir: &str,
+    components: &[WitnessComponent],
+) -> Result<String, String> {
+    let path = crate::utils::file::join(out_dir, WITNESS_FILE);
+    let json = serde_json::to_string_pretty(components).map_err(|e| e.to_string())?;
+    crate::utils::file::safe_write_file(&path, &json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompose() {
+        use crate::ns_decision::{RequestState, RequestStatePair};
+        use crate::proof_parser::{Formula, ProofInvariant};
+
+        // Requests complete immediately, so any number of r/ok completes in G
+        let mut ns = NS::<String, String, String, String>::new("G".to_string());
+        ns.add_request("r".to_string(), "l".to_string());
+        ns.add_response("l".to_string(), "ok".to_string());
+
+        let mut global_invariants = crate::deterministic_map::HashMap::default();
+        global_invariants.insert(
+            "G".to_string(),
+            ProofInvariant {
+                variables: vec![
+                    RequestStatePair("r".to_string(), RequestState::InFlight("l".to_string())),
+                    RequestStatePair("r".to_string(), RequestState::Completed("ok".to_string())),
+                ],
+                formula: Formula::And(vec![]),
+            },
+        );
+        let invariant = NSInvariant { global_invariants };
+
+        let components = decompose(&ns, &invariant);
+        assert!(!components.is_empty());
+        for component in &components {
+            assert_eq!(
+                component.global_states,
+                vec![("G".to_string(), Coverage::All)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_multiset() {
+        assert_eq!(format_multiset(&[]), "∅");
+        assert_eq!(
+            format_multiset(&[("inc/0".to_string(), 1), ("get/1".to_string(), 2)]),
+            "inc/0 + 2 get/1"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! End-to-end snapshots of the analysis of the bundled examples.
+//!
+//! Every example is analyzed by the `ser` binary with SMPT replaced by outputs recorded
+//! earlier (`--import-smpt-results`), and the verdict, the certificate check and the
+//! completed multisets are compared with `tests/golden/e2e`. See the README there for
+//! recording SMPT outputs and updating the snapshots.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Directories of `examples/` with one model per file
+const EXAMPLE_DIRS: &[&str] = &["ser", "json", "pluscal", "proto"];
+
+/// Marks a directory of recorded SMPT outputs, and lists the recorded queries
+const RECORDED_FILE: &str = "recorded.txt";
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn golden_dir() -> PathBuf {
+    root().join("tests/golden/e2e")
+}
+
+/// The examples as (snapshot name, file), in order
+fn examples() -> Vec<(String, PathBuf)> {
+    let mut examples = Vec::new();
+    for dir in EXAMPLE_DIRS {
+        let mut files: Vec<PathBuf> = fs::read_dir(root().join("examples").join(dir))
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext != "md"))
+            .collect();
+        files.sort();
+        for file in files {
+            examples.push((format!("{}_{}", dir, stem(&file)), file));
+        }
+    }
+    examples
+}
+
+fn stem(file: &Path) -> String {
+    file.file_stem().unwrap().to_string_lossy().into_owned()
+}
+
+/// Run `ser` in `dir`, where it writes its output directory `out/`
+fn ser(dir: &Path, args: &[&str], file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ser"))
+        .current_dir(dir)
+        .args(args)
+        .arg(file)
+        .output()
+        .expect("failed to run ser")
+}
+
+/// Export the SMPT queries of `file`, run them with SMPT and keep their outputs
+fn record_smpt(file: &Path, replay_dir: &Path) {
+    let work = tempfile::tempdir().unwrap();
+    ser(work.path(), &["--no-viz", "--export-smpt-queries"], file);
+    let query_dir = work
+        .path()
+        .join("out")
+        .join(stem(file))
+        .join("smpt_queries");
+
+    let _ = fs::remove_dir_all(replay_dir);
+    fs::create_dir_all(replay_dir).unwrap();
+    let mut recorded = Vec::new();
+    if query_dir.exists() {
+        let status = Command::new("sh")
+            .arg("run_queries.sh")
+            .current_dir(&query_dir)
+            .status()
+            .expect("failed to run the exported SMPT queries");
+        assert!(
+            status.success(),
+            "SMPT queries of {} failed",
+            file.display()
+        );
+        for entry in fs::read_dir(&query_dir).unwrap().flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.ends_with(".stdout")
+                || name.ends_with(".stderr")
+                || name.ends_with("_proof.txt")
+            {
+                fs::copy(entry.path(), replay_dir.join(&name)).unwrap();
+            }
+            if let Some(id) = name.strip_suffix(".stdout") {
+                recorded.push(id.to_string());
+            }
+        }
+    }
+    recorded.sort();
+    let listing: String = recorded.iter().map(|id| format!("{}\n", id)).collect();
+    fs::write(replay_dir.join(RECORDED_FILE), listing).unwrap();
+}
+
+/// Analyze `file` with the recorded SMPT outputs and describe the outcome
+fn snapshot(file: &Path, replay_dir: &Path) -> String {
+    let work = tempfile::tempdir().unwrap();
+    let replay_dir = replay_dir.to_string_lossy();
+    let analysis = ser(
+        work.path(),
+        &["--no-viz", "--import-smpt-results", &replay_dir],
+        file,
+    );
+    let out_dir = work.path().join("out").join(stem(file));
+
+    let verdict = fs::read_to_string(out_dir.join("certificate.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|certificate| {
+            let decision = certificate["decision"].as_object()?;
+            decision.keys().next().cloned()
+        })
+        .unwrap_or_else(|| "none".to_string());
+    let check = if verdict == "none" {
+        "not run"
+    } else if ser(work.path(), &["--check-certificate"], file)
+        .status
+        .success()
+    {
+        "passed"
+    } else {
+        "failed"
+    };
+    let multisets = fs::read_to_string(out_dir.join("completed_multisets.json"))
+        .unwrap_or_else(|_| "none".to_string());
+
+    format!(
+        "exit code: {}\nverdict: {}\ncertificate check: {}\ncompleted multisets:\n{}\n",
+        analysis.status.code().unwrap_or(-1),
+        verdict,
+        check,
+        multisets.trim_end()
+    )
+}
+
+#[test]
+fn test_example_snapshots() {
+    let record = std::env::var_os("RECORD_SMPT").is_some();
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+    let mut missing = Vec::new();
+    for (name, file) in examples() {
+        let replay_dir = golden_dir().join("smpt").join(&name);
+        if record {
+            record_smpt(&file, &replay_dir);
+        }
+        if !replay_dir.join(RECORDED_FILE).exists() {
+            missing.push(replay_dir.display().to_string());
+            continue;
+        }
+        let actual = snapshot(&file, &replay_dir);
+        let golden = golden_dir().join(format!("{}.txt", name));
+        if update {
+            fs::write(&golden, actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) => {
+                if expected != actual {
+                    mismatches.push(golden.display().to_string());
+                }
+            }
+            Err(_) => missing.push(golden.display().to_string()),
+        }
+    }
+    assert!(
+        missing.is_empty(),
+        "Recorded SMPT outputs or snapshots {:?} are missing (record them with RECORD_SMPT=1 UPDATE_GOLDEN=1)",
+        missing
+    );
+    assert!(
+        mismatches.is_empty(),
+        "Analysis outcomes differ from {:?} (rerun with UPDATE_GOLDEN=1 if the change is intended)",
+        mismatches
+    );
+}